@@ -0,0 +1,270 @@
+//! AAPCS64 parameter passing
+//!
+//! Integers and pointers are passed in x0-x7 and floats in d0-d7 (s0-s7 at
+//! single precision). A homogeneous floating-point aggregate (HFA), made of
+//! at most four floats of one precision, takes one float register per
+//! member. Any other aggregate of at most 16 bytes takes one general
+//! register per word, and larger ones are copied by the caller and passed
+//! by reference. An argument that does not fit in the remaining registers
+//! goes on the stack whole, and so do all later arguments of its class.
+//!
+//! Values are returned in the registers of their first argument; larger
+//! aggregates are written through the address passed in x8.
+//!
+//! As on x86-64, aggregates keep Aurora's layout of one 8-byte slot per
+//! scalar, so a struct with narrower fields does not match C's.
+
+use crate::abi::ArgLocation;
+use crate::air::Register;
+use crate::emit::Precision;
+use crate::target::CallingConvention;
+use aurora_mir::layout::WORD_SIZE;
+use aurora_mir::LayoutTable;
+use aurora_types::Type;
+
+/// Integer and pointer argument registers
+const ARG_REGISTERS: [Register; 8] = [
+    Register::X(0),
+    Register::X(1),
+    Register::X(2),
+    Register::X(3),
+    Register::X(4),
+    Register::X(5),
+    Register::X(6),
+    Register::X(7),
+];
+
+/// Float argument registers
+const FLOAT_ARG_REGISTERS: [Register; 8] = [
+    Register::D(0),
+    Register::D(1),
+    Register::D(2),
+    Register::D(3),
+    Register::D(4),
+    Register::D(5),
+    Register::D(6),
+    Register::D(7),
+];
+
+/// Integer return registers, in word order
+const RETURN_REGISTERS: [Register; 2] = [Register::X(0), Register::X(1)];
+
+/// Float return registers, in member order
+const FLOAT_RETURN_REGISTERS: [Register; 4] = [Register::D(0), Register::D(1), Register::D(2), Register::D(3)];
+
+/// Register passing the address a result in memory is written to
+pub const INDIRECT_RESULT: Register = Register::X(8);
+
+/// Registers of the AAPCS64 calling convention
+pub const AAPCS64: CallingConvention = CallingConvention {
+    int_args: &ARG_REGISTERS,
+    float_args: &FLOAT_ARG_REGISTERS,
+    int_returns: &RETURN_REGISTERS,
+    float_returns: &FLOAT_RETURN_REGISTERS,
+    indirect_result: Some(INDIRECT_RESULT),
+};
+
+/// Largest aggregate other than an HFA passed in registers, in bytes
+const MAX_REGISTER_AGGREGATE: u64 = 16;
+
+/// Most members of a homogeneous floating-point aggregate
+const MAX_HFA_MEMBERS: usize = 4;
+
+/// How a value is passed or returned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Passing {
+    /// In general registers, one per word
+    Integer(usize),
+    /// In float registers, one per member, with its byte offset and precision
+    Float(Vec<(u64, Precision)>),
+    /// As the address of a copy, or in memory when returned
+    Reference,
+}
+
+/// Classify a value of type `ty`
+pub fn classify(ty: &Type, layouts: &LayoutTable) -> Passing {
+    if !layouts.is_aggregate(ty) {
+        return match Precision::of(ty) {
+            Some(precision) => Passing::Float(vec![(0, precision)]),
+            None => Passing::Integer(1),
+        };
+    }
+
+    let mut scalars = Vec::new();
+    flatten(ty, 0, layouts, &mut scalars);
+    if let Some(&(_, Some(first))) = scalars.first() {
+        if scalars.len() <= MAX_HFA_MEMBERS && scalars.iter().all(|&(_, precision)| precision == Some(first)) {
+            return Passing::Float(scalars.iter().map(|&(offset, _)| (offset, first)).collect());
+        }
+    }
+    let size = layouts.size_of(ty);
+    if size > MAX_REGISTER_AGGREGATE {
+        return Passing::Reference;
+    }
+    Passing::Integer(size.div_ceil(WORD_SIZE) as usize)
+}
+
+/// Assign registers and stack words to arguments of the given types
+///
+/// Returns the location of each argument and the number of stack words.
+/// An argument passed by reference takes one register or word.
+pub fn assign(types: &[Type], layouts: &LayoutTable) -> (Vec<ArgLocation>, usize) {
+    let mut ints = ARG_REGISTERS.iter().copied();
+    let mut floats = FLOAT_ARG_REGISTERS.iter().copied();
+    let mut stack_words = 0;
+
+    let locations = types
+        .iter()
+        .map(|ty| {
+            let words = match classify(ty, layouts) {
+                Passing::Integer(words) if words <= ints.len() => {
+                    return ArgLocation::Registers(ints.by_ref().take(words).collect());
+                }
+                Passing::Float(members) if members.len() <= floats.len() => {
+                    return ArgLocation::Registers(floats.by_ref().take(members.len()).collect());
+                }
+                Passing::Reference if ints.len() > 0 => return ArgLocation::Registers(ints.next().into_iter().collect()),
+                Passing::Float(_) => {
+                    floats.by_ref().for_each(drop);
+                    layouts.size_of(ty).div_ceil(WORD_SIZE) as usize
+                }
+                Passing::Integer(_) => {
+                    ints.by_ref().for_each(drop);
+                    layouts.size_of(ty).div_ceil(WORD_SIZE) as usize
+                }
+                Passing::Reference => 1,
+            };
+            let words = words.max(1);
+            let offset = stack_words;
+            stack_words += words;
+            ArgLocation::Stack { offset, words }
+        })
+        .collect();
+    (locations, stack_words)
+}
+
+/// Registers a value of type `ty` is returned in, one per word or HFA
+/// member, or `None` if it is returned in memory
+pub fn return_registers(ty: &Type, layouts: &LayoutTable) -> Option<Vec<Register>> {
+    match classify(ty, layouts) {
+        Passing::Integer(words) => Some(RETURN_REGISTERS[..words].to_vec()),
+        Passing::Float(members) => Some(FLOAT_RETURN_REGISTERS[..members.len()].to_vec()),
+        Passing::Reference => None,
+    }
+}
+
+/// Offsets of the scalars making up a value of type `ty`, with the
+/// precision of those that are floats
+///
+/// The variants of an enum overlap, each following the tag word.
+fn flatten(ty: &Type, base: u64, layouts: &LayoutTable, scalars: &mut Vec<(u64, Option<Precision>)>) {
+    if !layouts.is_aggregate(ty) {
+        scalars.push((base, Precision::of(ty)));
+    } else if let Some(variants) = layouts.variants(ty) {
+        scalars.push((base, None));
+        for field in variants.iter().flat_map(|variant| &variant.fields) {
+            flatten(&field.ty, base + field.offset, layouts, scalars);
+        }
+    } else if let Some(fields) = layouts.fields(ty) {
+        for field in &fields {
+            flatten(&field.ty, base + field.offset, layouts, scalars);
+        }
+    } else if let Type::Array { elem, size: Some(len) } = ty {
+        let stride = layouts.size_of(elem);
+        for i in 0..*len as u64 {
+            flatten(elem, base + i * stride, layouts, scalars);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aurora_types::{PrimitiveType, StructDef};
+
+    fn f32_ty() -> Type {
+        Type::Primitive(PrimitiveType::F32)
+    }
+
+    fn f64_ty() -> Type {
+        Type::Primitive(PrimitiveType::F64)
+    }
+
+    fn i64_ty() -> Type {
+        Type::Primitive(PrimitiveType::I64)
+    }
+
+    fn named(name: &str) -> Type {
+        Type::Named {
+            name: name.to_string(),
+            args: vec![],
+        }
+    }
+
+    fn layouts() -> LayoutTable {
+        let mut layouts = LayoutTable::new();
+        let mut add = |name: &str, fields: Vec<Type>| {
+            layouts.add_struct(
+                name.to_string(),
+                StructDef {
+                    params: vec![],
+                    fields: fields.into_iter().enumerate().map(|(i, ty)| (format!("f{}", i), ty)).collect(),
+                },
+            );
+        };
+        add("Quad", vec![f64_ty(); 4]);
+        add("Mixed", vec![f32_ty(), f64_ty()]);
+        add("Pair", vec![i64_ty(), f64_ty()]);
+        add("Big", vec![i64_ty(), i64_ty(), i64_ty()]);
+        layouts
+    }
+
+    #[test]
+    fn test_classify_hfas_and_composites() {
+        let layouts = layouts();
+        assert_eq!(
+            classify(&named("Quad"), &layouts),
+            Passing::Float((0..4).map(|i| (i * 8, Precision::Double)).collect())
+        );
+        // Members of different precisions do not make an HFA
+        assert_eq!(classify(&named("Mixed"), &layouts), Passing::Integer(2));
+        assert_eq!(classify(&named("Pair"), &layouts), Passing::Integer(2));
+        assert_eq!(classify(&named("Big"), &layouts), Passing::Reference);
+        assert_eq!(
+            classify(&Type::Option(Box::new(f64_ty())), &layouts),
+            Passing::Integer(2)
+        );
+        assert_eq!(return_registers(&named("Big"), &layouts), None);
+        assert_eq!(
+            return_registers(&named("Pair"), &layouts),
+            Some(vec![Register::X(0), Register::X(1)])
+        );
+    }
+
+    #[test]
+    fn test_assign_stops_register_use_after_spilling_a_class() {
+        let layouts = layouts();
+        let mut types = vec![i64_ty(); 7];
+        types.extend([named("Pair"), i64_ty(), named("Big"), named("Quad"), named("Quad"), named("Quad"), f64_ty()]);
+        let (locations, stack_words) = assign(&types, &layouts);
+
+        // One integer register is left, so the pair goes on the stack and
+        // later integers follow it there
+        assert_eq!(locations[6], ArgLocation::Registers(vec![Register::X(6)]));
+        assert_eq!(locations[7], ArgLocation::Stack { offset: 0, words: 2 });
+        assert_eq!(locations[8], ArgLocation::Stack { offset: 2, words: 1 });
+        assert_eq!(locations[9], ArgLocation::Stack { offset: 3, words: 1 });
+        assert_eq!(
+            locations[10],
+            ArgLocation::Registers((0..4).map(Register::D).collect())
+        );
+        assert_eq!(
+            locations[11],
+            ArgLocation::Registers((4..8).map(Register::D).collect())
+        );
+        // An HFA that does not fit goes on the stack, and later floats follow it
+        assert_eq!(locations[12], ArgLocation::Stack { offset: 4, words: 4 });
+        assert_eq!(locations[13], ArgLocation::Stack { offset: 8, words: 1 });
+        assert_eq!(stack_words, 9);
+    }
+}
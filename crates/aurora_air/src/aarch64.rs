@@ -0,0 +1,1914 @@
+//! AArch64 code generation
+//!
+//! MIR is lowered to GNU assembler source for AArch64 Linux. Values get
+//! registers from [`AARCH64_REGISTERS`] through the same allocators as on
+//! x86_64. x16 and x17, the intra-procedure-call scratch registers, and
+//! d30/d31 are kept for spilled values, constants and addresses, and x18 is
+//! left to the platform. No argument register is allocated, so arguments
+//! and parameters are moved one at a time without overwriting each other.
+//!
+//! Every function sets up a frame record (x29, x30) and addresses its spill
+//! slots and stack slots from x29, with the callee-saved registers it uses
+//! stored at the bottom of the frame. Calls follow AAPCS64 as classified in
+//! [`crate::aapcs64`]. Frames are described by `.cfi` directives; there are
+//! no line tables for this target yet.
+
+use crate::aapcs64::{self, Passing, INDIRECT_RESULT};
+use crate::abi::ArgLocation;
+use crate::air::{DataDirective, DataKind, Register};
+use crate::emit::{Precision, Signature};
+use crate::regalloc::{Allocator, AllocatorKind};
+use crate::target::RegisterFile;
+use aurora_mir::layout::WORD_SIZE;
+use aurora_mir::{
+    BinOp, BlockId, Constant, Function as MirFunction, Instruction as MirInst, LayoutTable, MirModule,
+    Operand as MirOp, UnaryOp, ValueId,
+};
+use aurora_types::{PrimitiveType, Type};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Allocatable general registers: the caller-saved temporaries, then the
+/// callee-saved x19-x28
+const ALLOCATABLE: [Register; 17] = [
+    Register::X(9),
+    Register::X(10),
+    Register::X(11),
+    Register::X(12),
+    Register::X(13),
+    Register::X(14),
+    Register::X(15),
+    Register::X(19),
+    Register::X(20),
+    Register::X(21),
+    Register::X(22),
+    Register::X(23),
+    Register::X(24),
+    Register::X(25),
+    Register::X(26),
+    Register::X(27),
+    Register::X(28),
+];
+
+/// Allocatable float registers, all caller-saved
+const ALLOCATABLE_FP: [Register; 14] = [
+    Register::D(16),
+    Register::D(17),
+    Register::D(18),
+    Register::D(19),
+    Register::D(20),
+    Register::D(21),
+    Register::D(22),
+    Register::D(23),
+    Register::D(24),
+    Register::D(25),
+    Register::D(26),
+    Register::D(27),
+    Register::D(28),
+    Register::D(29),
+];
+
+/// Registers a function preserves for its caller
+const CALLEE_SAVED: [Register; 10] = [
+    Register::X(19),
+    Register::X(20),
+    Register::X(21),
+    Register::X(22),
+    Register::X(23),
+    Register::X(24),
+    Register::X(25),
+    Register::X(26),
+    Register::X(27),
+    Register::X(28),
+];
+
+/// Registers handed out on AArch64
+pub const AARCH64_REGISTERS: RegisterFile = RegisterFile {
+    general: &ALLOCATABLE,
+    float: &ALLOCATABLE_FP,
+    callee_saved: &CALLEE_SAVED,
+};
+
+/// Scratch register for values of spill slots, constants and results; never allocated
+const SCRATCH: Register = Register::X(16);
+
+/// Scratch register for right operands and addresses; never allocated
+const SCRATCH2: Register = Register::X(17);
+
+/// Scratch register for remainders, large immediates and copied
+/// aggregates; x8 only carries an address during a call sequence
+const SCRATCH3: Register = Register::X(8);
+
+/// Scratch register for float values of spill slots, constants and results
+const SCRATCH_FP: Register = Register::D(31);
+
+/// Scratch register for the right operand of float instructions
+const SCRATCH_FP2: Register = Register::D(30);
+
+/// Frame pointer
+const FP: Register = Register::X(29);
+
+/// Link register
+const LR: Register = Register::X(30);
+
+/// Condition of a `cset`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cond {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// Less than, after a float compare (false if unordered)
+    Mi,
+    /// Less than or equal, after a float compare (false if unordered)
+    Ls,
+}
+
+impl fmt::Display for Cond {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Cond::Eq => "eq",
+            Cond::Ne => "ne",
+            Cond::Lt => "lt",
+            Cond::Le => "le",
+            Cond::Gt => "gt",
+            Cond::Ge => "ge",
+            Cond::Mi => "mi",
+            Cond::Ls => "ls",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Integer operation on two registers, or a register and an immediate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntOp {
+    Add,
+    Sub,
+    Mul,
+    Sdiv,
+    And,
+    Orr,
+    Eor,
+    Lsl,
+    Lsr,
+}
+
+impl fmt::Display for IntOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            IntOp::Add => "add",
+            IntOp::Sub => "sub",
+            IntOp::Mul => "mul",
+            IntOp::Sdiv => "sdiv",
+            IntOp::And => "and",
+            IntOp::Orr => "orr",
+            IntOp::Eor => "eor",
+            IntOp::Lsl => "lsl",
+            IntOp::Lsr => "lsr",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Float arithmetic operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatOp {
+    Fadd,
+    Fsub,
+    Fmul,
+    Fdiv,
+}
+
+impl fmt::Display for FloatOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FloatOp::Fadd => "fadd",
+            FloatOp::Fsub => "fsub",
+            FloatOp::Fmul => "fmul",
+            FloatOp::Fdiv => "fdiv",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Second operand of an integer operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand2 {
+    Reg(Register),
+    /// An immediate the instruction can encode
+    Imm(u32),
+}
+
+impl fmt::Display for Operand2 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand2::Reg(reg) => write!(f, "{}", reg),
+            Operand2::Imm(imm) => write!(f, "#{}", imm),
+        }
+    }
+}
+
+/// Memory operand `[base, #offset]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Address {
+    pub base: Register,
+    pub offset: i32,
+}
+
+impl Address {
+    /// The address held in `base`
+    pub fn at(base: Register) -> Self {
+        Self { base, offset: 0 }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.offset == 0 {
+            write!(f, "[{}]", self.base)
+        } else {
+            write!(f, "[{}, #{}]", self.base, self.offset)
+        }
+    }
+}
+
+/// AArch64 instruction
+///
+/// A register paired with a precision is named for that width: `s` or `w`
+/// for single precision, `d` or `x` for double. Loads and stores without a
+/// precision move a 64-bit general register.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum A64Inst {
+    /// Copy a general register
+    Mov { dest: Register, src: Register },
+    /// Copy a float register, or move bits between register classes
+    Fmov {
+        dest: Register,
+        src: Register,
+        precision: Precision,
+    },
+    /// Set a register to a 16-bit immediate shifted left
+    Movz { dest: Register, imm: u16, shift: u8 },
+    /// Replace 16 bits of a register, keeping the rest
+    Movk { dest: Register, imm: u16, shift: u8 },
+    /// Set a register to the complement of a 16-bit immediate
+    Movn { dest: Register, imm: u16 },
+    /// Address of a symbol, through its page
+    Adr { dest: Register, symbol: String },
+    Ldr {
+        dest: Register,
+        addr: Address,
+        precision: Option<Precision>,
+    },
+    Str {
+        src: Register,
+        addr: Address,
+        precision: Option<Precision>,
+    },
+    /// Store a pair, lowering the base first (`stp a, b, [base, #offset]!`)
+    StpPre {
+        first: Register,
+        second: Register,
+        base: Register,
+        offset: i32,
+    },
+    /// Load a pair, raising the base after (`ldp a, b, [base], #offset`)
+    LdpPost {
+        first: Register,
+        second: Register,
+        base: Register,
+        offset: i32,
+    },
+    Op {
+        op: IntOp,
+        dest: Register,
+        lhs: Register,
+        rhs: Operand2,
+    },
+    /// `dest = minuend - lhs * rhs`
+    Msub {
+        dest: Register,
+        lhs: Register,
+        rhs: Register,
+        minuend: Register,
+    },
+    Neg { dest: Register, src: Register },
+    Mvn { dest: Register, src: Register },
+    Cmp { lhs: Register, rhs: Operand2 },
+    /// Set a register to 1 if the condition holds, else 0
+    Cset { dest: Register, cond: Cond },
+    FOp {
+        op: FloatOp,
+        precision: Precision,
+        dest: Register,
+        lhs: Register,
+        rhs: Register,
+    },
+    Fneg {
+        precision: Precision,
+        dest: Register,
+        src: Register,
+    },
+    Fsqrt {
+        precision: Precision,
+        dest: Register,
+        src: Register,
+    },
+    Fcmp {
+        precision: Precision,
+        lhs: Register,
+        rhs: Register,
+    },
+    /// Signed integer to float
+    Scvtf {
+        precision: Precision,
+        dest: Register,
+        src: Register,
+    },
+    /// Float to signed integer, rounding toward zero
+    Fcvtzs {
+        precision: Precision,
+        dest: Register,
+        src: Register,
+    },
+    /// Float to float of the other precision
+    Fcvt {
+        to: Precision,
+        dest: Register,
+        src: Register,
+    },
+    B { target: String },
+    Cbz { reg: Register, target: String },
+    Cbnz { reg: Register, target: String },
+    /// Call a symbol
+    Bl { target: String },
+    /// Call the address in a register
+    Blr { target: Register },
+    Ret,
+    Label { name: String },
+    Comment { text: String },
+    /// Call frame information directive, without its `.cfi_` prefix
+    Cfi { directive: String },
+}
+
+impl fmt::Display for A64Inst {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fp = |reg: &Register, precision: &Precision| name(*reg, Some(*precision));
+        match self {
+            A64Inst::Mov { dest, src } => write!(f, "mov {}, {}", dest, src),
+            A64Inst::Fmov { dest, src, precision } => {
+                write!(f, "fmov {}, {}", fp(dest, precision), fp(src, precision))
+            }
+            A64Inst::Movz { dest, imm, shift: 0 } => write!(f, "movz {}, #{}", dest, imm),
+            A64Inst::Movz { dest, imm, shift } => write!(f, "movz {}, #{}, lsl #{}", dest, imm, shift),
+            A64Inst::Movk { dest, imm, shift } => write!(f, "movk {}, #{}, lsl #{}", dest, imm, shift),
+            A64Inst::Movn { dest, imm } => write!(f, "movn {}, #{}", dest, imm),
+            A64Inst::Adr { dest, symbol } => {
+                write!(f, "adrp {}, {}\n    add {}, {}, :lo12:{}", dest, symbol, dest, dest, symbol)
+            }
+            A64Inst::Ldr { dest, addr, precision } => write!(f, "ldr {}, {}", name(*dest, *precision), addr),
+            A64Inst::Str { src, addr, precision } => write!(f, "str {}, {}", name(*src, *precision), addr),
+            A64Inst::StpPre { first, second, base, offset } => {
+                write!(f, "stp {}, {}, [{}, #{}]!", first, second, base, offset)
+            }
+            A64Inst::LdpPost { first, second, base, offset } => {
+                write!(f, "ldp {}, {}, [{}], #{}", first, second, base, offset)
+            }
+            A64Inst::Op { op, dest, lhs, rhs } => write!(f, "{} {}, {}, {}", op, dest, lhs, rhs),
+            A64Inst::Msub { dest, lhs, rhs, minuend } => {
+                write!(f, "msub {}, {}, {}, {}", dest, lhs, rhs, minuend)
+            }
+            A64Inst::Neg { dest, src } => write!(f, "neg {}, {}", dest, src),
+            A64Inst::Mvn { dest, src } => write!(f, "mvn {}, {}", dest, src),
+            A64Inst::Cmp { lhs, rhs } => write!(f, "cmp {}, {}", lhs, rhs),
+            A64Inst::Cset { dest, cond } => write!(f, "cset {}, {}", dest, cond),
+            A64Inst::FOp { op, precision, dest, lhs, rhs } => write!(
+                f,
+                "{} {}, {}, {}",
+                op,
+                fp(dest, precision),
+                fp(lhs, precision),
+                fp(rhs, precision)
+            ),
+            A64Inst::Fneg { precision, dest, src } => {
+                write!(f, "fneg {}, {}", fp(dest, precision), fp(src, precision))
+            }
+            A64Inst::Fsqrt { precision, dest, src } => {
+                write!(f, "fsqrt {}, {}", fp(dest, precision), fp(src, precision))
+            }
+            A64Inst::Fcmp { precision, lhs, rhs } => {
+                write!(f, "fcmp {}, {}", fp(lhs, precision), fp(rhs, precision))
+            }
+            A64Inst::Scvtf { precision, dest, src } => write!(f, "scvtf {}, {}", fp(dest, precision), src),
+            A64Inst::Fcvtzs { precision, dest, src } => write!(f, "fcvtzs {}, {}", dest, fp(src, precision)),
+            A64Inst::Fcvt { to, dest, src } => {
+                let from = match to {
+                    Precision::Single => Precision::Double,
+                    Precision::Double => Precision::Single,
+                };
+                write!(f, "fcvt {}, {}", fp(dest, to), fp(src, &from))
+            }
+            A64Inst::B { target } => write!(f, "b {}", target),
+            A64Inst::Cbz { reg, target } => write!(f, "cbz {}, {}", reg, target),
+            A64Inst::Cbnz { reg, target } => write!(f, "cbnz {}, {}", reg, target),
+            A64Inst::Bl { target } => write!(f, "bl {}", target),
+            A64Inst::Blr { target } => write!(f, "blr {}", target),
+            A64Inst::Ret => write!(f, "ret"),
+            A64Inst::Label { name } => write!(f, "{}:", name),
+            A64Inst::Comment { text } => write!(f, "// {}", text),
+            A64Inst::Cfi { directive } => write!(f, ".cfi_{}", directive),
+        }
+    }
+}
+
+/// Name of a register at a precision: a single-precision float register
+/// is `s`, and a general register holding single-precision bits is `w`
+fn name(reg: Register, precision: Option<Precision>) -> String {
+    match (reg, precision) {
+        (Register::D(n), Some(Precision::Single)) => format!("s{}", n),
+        (Register::X(n), Some(Precision::Single)) => format!("w{}", n),
+        (reg, _) => reg.to_string(),
+    }
+}
+
+/// Whether a register is a float register
+fn is_fp(reg: Register) -> bool {
+    matches!(reg, Register::D(_))
+}
+
+/// Load a 64-bit immediate into a general register
+fn mov_immediate(dest: Register, imm: i64) -> Vec<A64Inst> {
+    if (0..=0xFFFF).contains(&imm) {
+        return vec![A64Inst::Movz { dest, imm: imm as u16, shift: 0 }];
+    }
+    if (-0x10000..0).contains(&imm) {
+        return vec![A64Inst::Movn { dest, imm: !imm as u16 }];
+    }
+    let mut insts = Vec::new();
+    for shift in (0..64).step_by(16) {
+        let imm = (imm as u64 >> shift) as u16;
+        if imm != 0 {
+            insts.push(match insts.is_empty() {
+                true => A64Inst::Movz { dest, imm, shift },
+                false => A64Inst::Movk { dest, imm, shift },
+            });
+        }
+    }
+    insts
+}
+
+/// Move the stack pointer by `bytes`, with `op` being add or sub
+fn adjust_sp(op: IntOp, bytes: u32) -> Vec<A64Inst> {
+    let sp = Register::SP;
+    if bytes <= 0xFFF {
+        return vec![A64Inst::Op { op, dest: sp, lhs: sp, rhs: Operand2::Imm(bytes) }];
+    }
+    let mut insts = mov_immediate(SCRATCH, i64::from(bytes));
+    insts.push(A64Inst::Op { op, dest: sp, lhs: sp, rhs: Operand2::Reg(SCRATCH) });
+    insts
+}
+
+/// Set `dest` to the address at `offset` from the frame pointer
+fn frame_address(dest: Register, offset: i32) -> Vec<A64Inst> {
+    match offset {
+        -0xFFF..=-1 => vec![A64Inst::Op {
+            op: IntOp::Sub,
+            dest,
+            lhs: FP,
+            rhs: Operand2::Imm(offset.unsigned_abs()),
+        }],
+        0..=0xFFF => vec![A64Inst::Op {
+            op: IntOp::Add,
+            dest,
+            lhs: FP,
+            rhs: Operand2::Imm(offset as u32),
+        }],
+        _ => {
+            let mut insts = mov_immediate(dest, i64::from(offset));
+            insts.push(A64Inst::Op {
+                op: IntOp::Add,
+                dest,
+                lhs: FP,
+                rhs: Operand2::Reg(dest),
+            });
+            insts
+        }
+    }
+}
+
+/// Whether a load or store of any width can encode `offset` directly
+fn fits_offset(offset: i32) -> bool {
+    (-256..=255).contains(&offset) || ((0..=16376).contains(&offset) && offset % 8 == 0)
+}
+
+/// Byte offset and precision of each register's part of a value passed
+/// in registers
+fn register_parts(passing: &Passing) -> Vec<(i32, Option<Precision>)> {
+    match passing {
+        Passing::Integer(words) => (0..*words).map(|i| ((i * 8) as i32, None)).collect(),
+        Passing::Float(members) => members
+            .iter()
+            .map(|&(offset, precision)| (offset as i32, Some(precision)))
+            .collect(),
+        Passing::Reference => Vec::new(),
+    }
+}
+
+/// Where a value is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    Reg(Register),
+    /// A slot at an offset from the frame pointer
+    Frame(i32),
+}
+
+/// Function lowered to AArch64
+#[derive(Debug, Clone)]
+pub struct A64Function {
+    pub name: String,
+    /// Body, without the prologue and epilogue
+    pub instructions: Vec<A64Inst>,
+    /// Bytes reserved below the frame record for spill slots, stack slots
+    /// and saved registers
+    pub frame_size: u32,
+    /// Callee-saved registers stored at the bottom of the frame
+    pub saved_regs: Vec<Register>,
+}
+
+impl A64Function {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            instructions: Vec::new(),
+            frame_size: 0,
+            saved_regs: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, inst: A64Inst) {
+        self.instructions.push(inst);
+    }
+
+    /// The body with the prologue and epilogue, and call frame information
+    pub fn framed_instructions(&self) -> Vec<A64Inst> {
+        let cfi = |directive: String| A64Inst::Cfi { directive };
+        let sp = Register::SP;
+        let mut insts = vec![
+            cfi("startproc".to_string()),
+            A64Inst::StpPre { first: FP, second: LR, base: sp, offset: -16 },
+            cfi("def_cfa_offset 16".to_string()),
+            cfi(format!("offset {}, -16", FP)),
+            cfi(format!("offset {}, -8", LR)),
+            A64Inst::Mov { dest: FP, src: sp },
+            cfi(format!("def_cfa_register {}", FP)),
+        ];
+        if self.frame_size > 0 {
+            insts.extend(adjust_sp(IntOp::Sub, self.frame_size));
+        }
+        for (i, &reg) in self.saved_regs.iter().enumerate() {
+            let offset = (i * 8) as i32;
+            insts.push(A64Inst::Str { src: reg, addr: Address { base: sp, offset }, precision: None });
+            insts.push(cfi(format!("offset {}, {}", reg, offset - 16 - self.frame_size as i32)));
+        }
+
+        insts.extend(self.instructions.iter().cloned());
+
+        for (i, &reg) in self.saved_regs.iter().enumerate() {
+            let offset = (i * 8) as i32;
+            insts.push(A64Inst::Ldr { dest: reg, addr: Address { base: sp, offset }, precision: None });
+            insts.push(cfi(format!("restore {}", reg)));
+        }
+        insts.push(A64Inst::Mov { dest: sp, src: FP });
+        insts.push(cfi(format!("def_cfa {}, 16", sp)));
+        insts.push(A64Inst::LdpPost { first: FP, second: LR, base: sp, offset: 16 });
+        insts.push(cfi("def_cfa_offset 0".to_string()));
+        insts.push(cfi(format!("restore {}", FP)));
+        insts.push(cfi(format!("restore {}", LR)));
+        insts.push(A64Inst::Ret);
+        insts.push(cfi("endproc".to_string()));
+        insts
+    }
+
+    /// Assembly text of the function
+    pub fn to_text(&self) -> String {
+        let mut output = format!("    .p2align 2\n    .type {}, %function\n{}:\n", self.name, self.name);
+        for inst in self.framed_instructions() {
+            match inst {
+                A64Inst::Label { .. } => output.push_str(&format!("{}\n", inst)),
+                inst => output.push_str(&format!("    {}\n", inst)),
+            }
+        }
+        output.push_str(&format!("    .size {}, .-{}\n", self.name, self.name));
+        output
+    }
+}
+
+/// Module lowered to AArch64
+#[derive(Debug, Clone)]
+pub struct A64Module {
+    pub name: String,
+    pub functions: Vec<A64Function>,
+    pub data: Vec<DataDirective>,
+}
+
+impl A64Module {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            functions: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    /// GNU assembler source for the module
+    pub fn to_text(&self) -> String {
+        let mut output = format!("// AArch64 assembly for module: {}\n", self.name);
+        output.push_str("    .text\n    .globl main\n\n");
+        for func in &self.functions {
+            output.push_str(&func.to_text());
+            output.push('\n');
+        }
+        if !self.data.is_empty() {
+            output.push_str("    .data\n");
+            for data in &self.data {
+                output.push_str(&data_text(data));
+            }
+        }
+        output
+    }
+}
+
+/// Assembler directives defining a data item
+fn data_text(data: &DataDirective) -> String {
+    let (align, directive, value) = match data.kind {
+        DataKind::String | DataKind::Byte => {
+            let bytes: Vec<String> = data.value.iter().map(u8::to_string).collect();
+            return format!("{}:\n    .byte {}\n", data.label, bytes.join(", "));
+        }
+        DataKind::Word => (1, ".hword", data.le_value(2)),
+        DataKind::Dword => (2, ".word", data.le_value(4)),
+        DataKind::Qword => (3, ".quad", data.le_value(8)),
+    };
+    format!("    .p2align {}\n{}:\n    {} {}\n", align, data.label, directive, value)
+}
+
+/// Lowers MIR to AArch64 following AAPCS64
+pub struct A64Emitter {
+    regalloc: Box<dyn Allocator>,
+    /// Register allocation algorithm used for each function
+    allocator: AllocatorKind,
+    string_constants: HashMap<String, String>,
+    next_string_id: usize,
+    /// Precision of the current function's float values
+    floats: HashMap<ValueId, Precision>,
+    /// Layouts used to size stack slots and classify arguments
+    layouts: LayoutTable,
+    /// Signatures of the module's functions, keyed by symbol
+    signatures: HashMap<String, Signature>,
+    /// Bytes of stack slots allocated in the current frame
+    slot_bytes: u32,
+    /// Prefix making the current function's block labels unique
+    label_prefix: String,
+    /// MIR block whose instructions are being emitted
+    current_block: BlockId,
+    /// Label of the current function's epilogue
+    return_label: String,
+}
+
+impl A64Emitter {
+    pub fn new() -> Self {
+        Self::with_allocator(AllocatorKind::default())
+    }
+
+    /// Create an emitter using the given register allocator
+    pub fn with_allocator(allocator: AllocatorKind) -> Self {
+        Self {
+            regalloc: allocator.create_for(&AARCH64_REGISTERS),
+            allocator,
+            string_constants: HashMap::new(),
+            next_string_id: 0,
+            floats: HashMap::new(),
+            layouts: LayoutTable::new(),
+            signatures: HashMap::new(),
+            slot_bytes: 0,
+            label_prefix: String::new(),
+            current_block: 0,
+            return_label: String::new(),
+        }
+    }
+
+    /// Lower a MIR module
+    pub fn emit_module(&mut self, mir_module: &MirModule) -> A64Module {
+        let mut module = A64Module::new("main".to_string());
+        self.layouts = mir_module.layouts.clone();
+        self.signatures = mir_module
+            .functions
+            .values()
+            .map(|func| (func.name.clone(), Signature::of(func)))
+            .collect();
+
+        // Emit in a fixed order so the output is deterministic
+        let mut functions: Vec<_> = mir_module.functions.values().collect();
+        functions.sort_by_key(|func| func.id);
+        for func in functions {
+            let a64_func = self.emit_function(func);
+            module.functions.push(a64_func);
+        }
+
+        // Strings are null-terminated for C
+        let mut strings: Vec<_> = self.string_constants.iter().collect();
+        strings.sort();
+        for (label, content) in strings {
+            let mut bytes = content.as_bytes().to_vec();
+            bytes.push(0);
+            module.data.push(DataDirective {
+                label: label.clone(),
+                kind: DataKind::String,
+                value: bytes,
+            });
+        }
+        module
+    }
+
+    /// Lower a MIR function
+    pub fn emit_function(&mut self, mir_func: &MirFunction) -> A64Function {
+        let mut func = A64Function::new(mir_func.name.clone());
+
+        self.regalloc = self.allocator.create_for(&AARCH64_REGISTERS);
+        self.slot_bytes = 0;
+        self.label_prefix = format!(".L{}_", mir_func.id);
+        self.return_label = format!(".Lret{}", mir_func.id);
+
+        let mut mir_func = mir_func.clone();
+        self.regalloc.allocate(&mut mir_func);
+        let mir_func = &mir_func;
+        self.floats = mir_func
+            .values
+            .iter()
+            .filter_map(|(&id, value)| Precision::of(&value.ty).map(|precision| (id, precision)))
+            .collect();
+
+        self.emit_parameters(&mut func, mir_func);
+
+        let mut block_ids: Vec<_> = mir_func.blocks.keys().copied().collect();
+        block_ids.sort();
+        for block_id in block_ids {
+            if let Some(block) = mir_func.block(block_id) {
+                self.current_block = block_id;
+                func.push(A64Inst::Label {
+                    name: self.block_label(block_id),
+                });
+                for inst in &block.instructions {
+                    self.emit_instruction(inst, &mut func, mir_func);
+                }
+            }
+        }
+
+        // Returns branch to the epilogue, which follows the last block
+        let returns_last = matches!(
+            func.instructions.last(),
+            Some(A64Inst::B { target }) if *target == self.return_label
+        );
+        if returns_last {
+            func.instructions.pop();
+        }
+        let returns_early = func
+            .instructions
+            .iter()
+            .any(|inst| matches!(inst, A64Inst::B { target } if *target == self.return_label));
+        if returns_early {
+            func.push(A64Inst::Label {
+                name: self.return_label.clone(),
+            });
+        }
+
+        // Saved registers go below the spill area and stack slots, and the
+        // frame keeps sp 16-byte aligned
+        func.saved_regs = self.regalloc.callee_saved_registers();
+        let saved_bytes = 8 * func.saved_regs.len() as u32;
+        func.frame_size = (self.regalloc.stack_size() + self.slot_bytes + saved_bytes).next_multiple_of(16);
+        func
+    }
+
+    /// Move parameters from where AAPCS64 passes them to their locations
+    fn emit_parameters(&mut self, func: &mut A64Function, mir_func: &MirFunction) {
+        let mut signature = Signature::of(mir_func);
+        let mut params = mir_func.params.clone();
+
+        // An aggregate result is written to memory at the address passed in
+        // x8, or built in a local slot when returned in registers
+        let mut return_slot = None;
+        if self.layouts.is_aggregate(&signature.ret) && !params.is_empty() {
+            let slot = params.remove(0);
+            signature.params.remove(0);
+            match aapcs64::classify(&signature.ret, &self.layouts) {
+                Passing::Reference => self.copy(self.location(slot), Location::Reg(INDIRECT_RESULT), func),
+                _ => return_slot = Some(slot),
+            }
+        }
+
+        let (locations, _) = aapcs64::assign(&signature.params, &self.layouts);
+        for ((&param, ty), location) in params.iter().zip(&signature.params).zip(locations) {
+            let passing = aapcs64::classify(ty, &self.layouts);
+            let by_value = self.layouts.is_aggregate(ty) && passing != Passing::Reference;
+            match location {
+                // Aggregates passed in registers are stored to a frame slot
+                ArgLocation::Registers(registers) if by_value => {
+                    let offset = self.frame_slot(ty);
+                    for (&reg, (part, precision)) in registers.iter().zip(register_parts(&passing)) {
+                        let addr = self.frame_slot_address(offset + part, func);
+                        func.push(A64Inst::Str { src: reg, addr, precision });
+                    }
+                    self.emit_frame_address(param, offset, func);
+                }
+                ArgLocation::Registers(registers) => {
+                    if let [reg] = registers[..] {
+                        self.copy(self.location(param), Location::Reg(reg), func);
+                    }
+                }
+                // Stack arguments sit above the frame record
+                ArgLocation::Stack { offset, .. } => {
+                    let offset = 16 + (offset * 8) as i32;
+                    if by_value {
+                        self.emit_frame_address(param, offset, func);
+                    } else {
+                        self.copy(self.location(param), Location::Frame(offset), func);
+                    }
+                }
+            }
+        }
+
+        if let Some(slot) = return_slot {
+            let offset = self.frame_slot(&signature.ret);
+            self.emit_frame_address(slot, offset, func);
+        }
+    }
+
+    /// Reserve a slot for a value of type `ty` in the frame, below the spill
+    /// area, returning its offset from x29
+    fn frame_slot(&mut self, ty: &Type) -> i32 {
+        let size = self.layouts.size_of(ty).max(1).next_multiple_of(WORD_SIZE) as u32;
+        self.slot_bytes += size;
+        -((self.regalloc.stack_size() + self.slot_bytes) as i32)
+    }
+
+    /// Set a value to the address at `offset` from x29
+    fn emit_frame_address(&self, value: ValueId, offset: i32, func: &mut A64Function) {
+        let dest_reg = self.dest_register(value);
+        func.instructions.extend(frame_address(dest_reg, offset));
+        self.store_dest(value, func);
+    }
+
+    /// Label of a block of the current function
+    fn block_label(&self, block: BlockId) -> String {
+        format!("{}{}", self.label_prefix, block)
+    }
+
+    fn emit_instruction(&mut self, inst: &MirInst, func: &mut A64Function, mir_func: &MirFunction) {
+        func.push(A64Inst::Comment {
+            text: format!("MIR: {:?}", inst).chars().take(60).collect(),
+        });
+
+        match inst {
+            MirInst::Assign { dest, value, .. } => self.emit_assign(*dest, value, func),
+
+            MirInst::BinOp {
+                dest, op, lhs, rhs, ..
+            } => match self.floats.get(dest).copied().or_else(|| self.float_precision(&[lhs, rhs])) {
+                Some(precision) => self.emit_float_binop(*dest, op, lhs, rhs, precision, func),
+                None => self.emit_binop(*dest, op, lhs, rhs, func),
+            },
+
+            MirInst::UnaryOp {
+                dest,
+                op: UnaryOp::Neg,
+                value,
+                ..
+            } if self.floats.contains_key(dest) => {
+                let precision = self.floats[dest];
+                let src = self.read_float(value, precision, SCRATCH_FP, func);
+                let dest_reg = self.dest_register(*dest);
+                func.push(A64Inst::Fneg { precision, dest: dest_reg, src });
+                self.store_dest(*dest, func);
+            }
+
+            MirInst::UnaryOp { dest, op, value, .. } => {
+                let src = self.read(value, SCRATCH, func);
+                let dest_reg = self.dest_register(*dest);
+                func.push(match op {
+                    UnaryOp::Neg => A64Inst::Neg { dest: dest_reg, src },
+                    UnaryOp::Not => A64Inst::Op {
+                        op: IntOp::Eor,
+                        dest: dest_reg,
+                        lhs: src,
+                        rhs: Operand2::Imm(1),
+                    },
+                    UnaryOp::BitNot => A64Inst::Mvn { dest: dest_reg, src },
+                });
+                self.store_dest(*dest, func);
+            }
+
+            MirInst::Call { dest, func: callee, args, .. } => self.emit_call(dest, callee, args, func),
+
+            MirInst::Return { value, .. } => {
+                match value {
+                    // An aggregate returned in registers is loaded from its
+                    // slot; one returned in memory is already written
+                    Some(val) if self.layouts.is_aggregate(&mir_func.ret_ty) => {
+                        let passing = aapcs64::classify(&mir_func.ret_ty, &self.layouts);
+                        let registers = aapcs64::return_registers(&mir_func.ret_ty, &self.layouts).unwrap_or_default();
+                        let ptr = self.read(val, SCRATCH2, func);
+                        for (reg, (part, precision)) in registers.into_iter().zip(register_parts(&passing)) {
+                            func.push(A64Inst::Ldr {
+                                dest: reg,
+                                addr: Address { base: ptr, offset: part },
+                                precision,
+                            });
+                        }
+                    }
+                    Some(val) => match Precision::of(&mir_func.ret_ty) {
+                        Some(precision) => {
+                            let result = Register::D(0);
+                            let src = self.read_float(val, precision, result, func);
+                            self.copy(Location::Reg(result), Location::Reg(src), func);
+                        }
+                        None => self.move_into(Register::X(0), val, func),
+                    },
+                    // Return unit (0)
+                    None => func.push(A64Inst::Mov {
+                        dest: Register::X(0),
+                        src: Register::XZR,
+                    }),
+                }
+                func.push(A64Inst::B {
+                    target: self.return_label.clone(),
+                });
+            }
+
+            MirInst::Branch {
+                cond,
+                then_block,
+                else_block,
+                ..
+            } => match cond {
+                MirOp::Const(Constant::Bool(taken)) => {
+                    let target = if *taken { *then_block } else { *else_block };
+                    self.emit_phi_moves(target, func, mir_func);
+                    func.push(A64Inst::B {
+                        target: self.block_label(target),
+                    });
+                }
+                _ => {
+                    let cond_reg = self.read(cond, SCRATCH, func);
+                    // An edge into phis gets its own moves, so the taken
+                    // branch skips over the moves of the other edge
+                    if self.has_phi_inputs(*then_block, mir_func) {
+                        let else_edge = format!("{}_{}", self.block_label(self.current_block), else_block);
+                        func.push(A64Inst::Cbz {
+                            reg: cond_reg,
+                            target: else_edge.clone(),
+                        });
+                        self.emit_phi_moves(*then_block, func, mir_func);
+                        func.push(A64Inst::B {
+                            target: self.block_label(*then_block),
+                        });
+                        func.push(A64Inst::Label { name: else_edge });
+                    } else {
+                        func.push(A64Inst::Cbnz {
+                            reg: cond_reg,
+                            target: self.block_label(*then_block),
+                        });
+                    }
+                    self.emit_phi_moves(*else_block, func, mir_func);
+                    func.push(A64Inst::B {
+                        target: self.block_label(*else_block),
+                    });
+                }
+            },
+
+            MirInst::Jump { target, .. } => {
+                self.emit_phi_moves(*target, func, mir_func);
+                func.push(A64Inst::B {
+                    target: self.block_label(*target),
+                });
+            }
+
+            MirInst::Load { dest, ptr, .. } => {
+                let ptr_reg = self.read(ptr, SCRATCH2, func);
+                let dest_reg = self.dest_register(*dest);
+                func.push(A64Inst::Ldr {
+                    dest: dest_reg,
+                    addr: Address::at(ptr_reg),
+                    precision: self.floats.get(dest).copied(),
+                });
+                self.store_dest(*dest, func);
+            }
+
+            MirInst::Store { ptr, value, .. } => {
+                // A float is stored at the width of the pointed-to type
+                let stored = match ptr {
+                    MirOp::Value(ptr) => match mir_func.value(*ptr).map(|value| &value.ty) {
+                        Some(Type::Ptr { inner, .. } | Type::Ref { inner, .. }) => Precision::of(inner),
+                        _ => None,
+                    },
+                    MirOp::Const(_) => None,
+                };
+                let precision = stored.or_else(|| self.float_precision(&[value]));
+                let src = match precision {
+                    Some(precision) => self.read_float(value, precision, SCRATCH_FP, func),
+                    None => self.read(value, SCRATCH, func),
+                };
+                let ptr_reg = self.read(ptr, SCRATCH2, func);
+                func.push(A64Inst::Str {
+                    src,
+                    addr: Address::at(ptr_reg),
+                    precision,
+                });
+            }
+
+            MirInst::Alloca { dest, ty, .. } => {
+                let offset = self.frame_slot(ty);
+                self.emit_frame_address(*dest, offset, func);
+            }
+
+            MirInst::Cast { dest, value, .. } => self.emit_cast(*dest, value, func),
+
+            MirInst::GetElement { dest, base, index, .. } => {
+                let base_reg = self.read(base, SCRATCH2, func);
+                let dest_reg = self.dest_register(*dest);
+                match index {
+                    MirOp::Const(Constant::Int(offset)) if offset.unsigned_abs() <= 0xFFF => {
+                        let op = if *offset < 0 { IntOp::Sub } else { IntOp::Add };
+                        func.push(A64Inst::Op {
+                            op,
+                            dest: dest_reg,
+                            lhs: base_reg,
+                            rhs: Operand2::Imm(offset.unsigned_abs() as u32),
+                        });
+                    }
+                    index => {
+                        let index_reg = self.read(index, SCRATCH3, func);
+                        func.push(A64Inst::Op {
+                            op: IntOp::Add,
+                            dest: dest_reg,
+                            lhs: base_reg,
+                            rhs: Operand2::Reg(index_reg),
+                        });
+                    }
+                }
+                self.store_dest(*dest, func);
+            }
+
+            MirInst::Phi { dest, inputs, .. } => {
+                // Values are moved into place by the branches to this block
+                func.push(A64Inst::Comment {
+                    text: format!("Phi node: {:?} = {:?}", self.location(*dest), inputs),
+                });
+            }
+        }
+    }
+
+    /// Whether `target` has phis taking an input from the current block
+    fn has_phi_inputs(&self, target: BlockId, mir_func: &MirFunction) -> bool {
+        mir_func.block(target).is_some_and(|block| {
+            block.instructions.iter().any(|inst| {
+                matches!(inst, MirInst::Phi { inputs, .. }
+                    if inputs.iter().any(|(pred, _)| *pred == self.current_block))
+            })
+        })
+    }
+
+    /// Move the inputs of `target`'s phis for an edge from the current block
+    ///
+    /// The moves happen in parallel: register and spilled inputs are all
+    /// stored below sp before any is loaded into place, as a phi may be
+    /// allocated to another phi's input location, and constants are moved
+    /// last.
+    fn emit_phi_moves(&mut self, target: BlockId, func: &mut A64Function, mir_func: &MirFunction) {
+        let Some(block) = mir_func.block(target) else {
+            return;
+        };
+
+        let mut moves = Vec::new();
+        let mut constants = Vec::new();
+        for inst in &block.instructions {
+            let MirInst::Phi { dest, inputs, .. } = inst else {
+                continue;
+            };
+            if let Some((_, input)) = inputs.iter().find(|(pred, _)| *pred == self.current_block) {
+                match input {
+                    MirOp::Value(value) => {
+                        let dest = self.location(*dest);
+                        let src = self.location(*value);
+                        if dest != src {
+                            moves.push((dest, src));
+                        }
+                    }
+                    MirOp::Const(_) => constants.push((*dest, input.clone())),
+                }
+            }
+        }
+
+        if let [(dest, src)] = moves[..] {
+            self.copy(dest, src, func);
+        } else if !moves.is_empty() {
+            let sp = Register::SP;
+            let bytes = (8 * moves.len() as u32).next_multiple_of(16);
+            func.instructions.extend(adjust_sp(IntOp::Sub, bytes));
+            for (i, &(_, src)) in moves.iter().enumerate() {
+                let reg = match src {
+                    Location::Reg(reg) => reg,
+                    Location::Frame(offset) => {
+                        self.load_frame(SCRATCH, offset, func);
+                        SCRATCH
+                    }
+                };
+                func.push(A64Inst::Str {
+                    src: reg,
+                    addr: Address { base: sp, offset: (i * 8) as i32 },
+                    precision: is_fp(reg).then_some(Precision::Double),
+                });
+            }
+            for (i, &(dest, _)) in moves.iter().enumerate() {
+                let addr = Address { base: sp, offset: (i * 8) as i32 };
+                match dest {
+                    Location::Reg(reg) => func.push(A64Inst::Ldr {
+                        dest: reg,
+                        addr,
+                        precision: is_fp(reg).then_some(Precision::Double),
+                    }),
+                    Location::Frame(offset) => {
+                        func.push(A64Inst::Ldr { dest: SCRATCH, addr, precision: None });
+                        self.store_frame(SCRATCH, offset, func);
+                    }
+                }
+            }
+            func.instructions.extend(adjust_sp(IntOp::Add, bytes));
+        }
+        for (dest, src) in constants {
+            self.emit_assign(dest, &src, func);
+        }
+    }
+
+    fn emit_binop(&mut self, dest: ValueId, op: &BinOp, lhs: &MirOp, rhs: &MirOp, func: &mut A64Function) {
+        let lhs_reg = self.read(lhs, SCRATCH, func);
+
+        // Add, subtract and compare take a 12-bit immediate, and shifts a
+        // 6-bit one
+        let imm = match rhs {
+            MirOp::Const(Constant::Int(imm)) => Some(*imm),
+            MirOp::Const(Constant::Bool(imm)) => Some(i64::from(*imm)),
+            _ => None,
+        };
+        let rhs_op = match (op, imm) {
+            (
+                BinOp::Add | BinOp::Sub | BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge,
+                Some(imm @ 0..=0xFFF),
+            )
+            | (BinOp::Shl | BinOp::Shr, Some(imm @ 0..=63)) => Operand2::Imm(imm as u32),
+            _ => Operand2::Reg(self.read(rhs, SCRATCH2, func)),
+        };
+
+        let dest_reg = self.dest_register(dest);
+        let int_op = |op| A64Inst::Op {
+            op,
+            dest: dest_reg,
+            lhs: lhs_reg,
+            rhs: rhs_op,
+        };
+        match op {
+            BinOp::Add => func.push(int_op(IntOp::Add)),
+            BinOp::Sub => func.push(int_op(IntOp::Sub)),
+            BinOp::Mul => func.push(int_op(IntOp::Mul)),
+            BinOp::Div => func.push(int_op(IntOp::Sdiv)),
+            BinOp::Mod => {
+                // The remainder is the dividend less the truncated quotient times the divisor
+                if let Operand2::Reg(rhs_reg) = rhs_op {
+                    func.push(A64Inst::Op {
+                        op: IntOp::Sdiv,
+                        dest: SCRATCH3,
+                        lhs: lhs_reg,
+                        rhs: rhs_op,
+                    });
+                    func.push(A64Inst::Msub {
+                        dest: dest_reg,
+                        lhs: SCRATCH3,
+                        rhs: rhs_reg,
+                        minuend: lhs_reg,
+                    });
+                }
+            }
+            BinOp::BitAnd | BinOp::And => func.push(int_op(IntOp::And)),
+            BinOp::BitOr | BinOp::Or => func.push(int_op(IntOp::Orr)),
+            BinOp::BitXor => func.push(int_op(IntOp::Eor)),
+            BinOp::Shl => func.push(int_op(IntOp::Lsl)),
+            BinOp::Shr => func.push(int_op(IntOp::Lsr)),
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+                let cond = match op {
+                    BinOp::Eq => Cond::Eq,
+                    BinOp::Ne => Cond::Ne,
+                    BinOp::Lt => Cond::Lt,
+                    BinOp::Le => Cond::Le,
+                    BinOp::Gt => Cond::Gt,
+                    _ => Cond::Ge,
+                };
+                func.push(A64Inst::Cmp { lhs: lhs_reg, rhs: rhs_op });
+                func.push(A64Inst::Cset { dest: dest_reg, cond });
+            }
+        }
+        self.store_dest(dest, func);
+    }
+
+    fn emit_float_binop(
+        &mut self,
+        dest: ValueId,
+        op: &BinOp,
+        lhs: &MirOp,
+        rhs: &MirOp,
+        precision: Precision,
+        func: &mut A64Function,
+    ) {
+        let rhs_reg = self.read_float(rhs, precision, SCRATCH_FP2, func);
+        let lhs_reg = self.read_float(lhs, precision, SCRATCH_FP, func);
+        let dest_reg = self.dest_register(dest);
+        let float_op = match op {
+            BinOp::Add => Some(FloatOp::Fadd),
+            BinOp::Sub => Some(FloatOp::Fsub),
+            BinOp::Mul => Some(FloatOp::Fmul),
+            BinOp::Div => Some(FloatOp::Fdiv),
+            _ => None,
+        };
+        // An unordered compare (a NaN operand) fails every condition but `ne`
+        let cond = match op {
+            BinOp::Eq => Some(Cond::Eq),
+            BinOp::Ne => Some(Cond::Ne),
+            BinOp::Lt => Some(Cond::Mi),
+            BinOp::Le => Some(Cond::Ls),
+            BinOp::Gt => Some(Cond::Gt),
+            BinOp::Ge => Some(Cond::Ge),
+            _ => None,
+        };
+        match (float_op, cond) {
+            (Some(op), _) => func.push(A64Inst::FOp {
+                op,
+                precision,
+                dest: dest_reg,
+                lhs: lhs_reg,
+                rhs: rhs_reg,
+            }),
+            (_, Some(cond)) => {
+                func.push(A64Inst::Fcmp {
+                    precision,
+                    lhs: lhs_reg,
+                    rhs: rhs_reg,
+                });
+                func.push(A64Inst::Cset { dest: dest_reg, cond });
+            }
+            _ => {
+                // There is no float remainder or bitwise arithmetic
+                func.push(A64Inst::Comment {
+                    text: format!("Float op: {:?}", op),
+                });
+            }
+        }
+        self.store_dest(dest, func);
+    }
+
+    /// Convert between integers and floats of either precision
+    fn emit_cast(&mut self, dest: ValueId, value: &MirOp, func: &mut A64Function) {
+        let from = self.float_precision(&[value]);
+        let to = self.floats.get(&dest).copied();
+        let inst = match (from, to) {
+            (None, None) => return self.emit_assign(dest, value, func),
+            (Some(from), Some(to)) if from == to => return self.emit_assign(dest, value, func),
+            (Some(from), to) => {
+                let src = self.read_float(value, from, SCRATCH_FP2, func);
+                let dest = self.dest_register(dest);
+                match to {
+                    Some(to) => A64Inst::Fcvt { to, dest, src },
+                    None => A64Inst::Fcvtzs { precision: from, dest, src },
+                }
+            }
+            (None, Some(precision)) => {
+                let src = self.read(value, SCRATCH, func);
+                let dest = self.dest_register(dest);
+                A64Inst::Scvtf { precision, dest, src }
+            }
+        };
+        func.push(inst);
+        self.store_dest(dest, func);
+    }
+
+    /// Copy an operand into a value
+    fn emit_assign(&mut self, dest: ValueId, value: &MirOp, func: &mut A64Function) {
+        let dest_reg = self.dest_register(dest);
+        let src = match self.floats.get(&dest) {
+            Some(&precision) => self.read_float(value, precision, dest_reg, func),
+            None => self.read(value, dest_reg, func),
+        };
+        self.copy(self.location(dest), Location::Reg(src), func);
+    }
+
+    /// Square root, for calls to the `f32.sqrt` and `f64.sqrt` intrinsics
+    fn emit_sqrt(&mut self, dest: ValueId, value: &MirOp, precision: Precision, func: &mut A64Function) {
+        let src = self.read_float(value, precision, SCRATCH_FP2, func);
+        let dest_reg = self.dest_register(dest);
+        func.push(A64Inst::Fsqrt {
+            precision,
+            dest: dest_reg,
+            src,
+        });
+        self.store_dest(dest, func);
+    }
+
+    fn emit_call(&mut self, dest: &Option<ValueId>, callee: &MirOp, args: &[MirOp], func: &mut A64Function) {
+        if let (MirOp::Const(Constant::String(name)), Some(dest), [arg]) = (callee, dest, args) {
+            let precision = match name.as_str() {
+                "f32.sqrt" => Some(Precision::Single),
+                "f64.sqrt" => Some(Precision::Double),
+                _ => None,
+            };
+            if let Some(precision) = precision {
+                return self.emit_sqrt(*dest, arg, precision, func);
+            }
+        }
+
+        // Arguments are classified by the callee's parameter types when it
+        // is a function of this module, and by their own types otherwise
+        let signature = match callee {
+            MirOp::Const(Constant::String(name)) => self.signatures.get(name).cloned(),
+            _ => None,
+        };
+        let returned = signature
+            .as_ref()
+            .filter(|signature| self.layouts.is_aggregate(&signature.ret))
+            .map(|signature| signature.ret.clone());
+        let (return_slot, args) = match (&returned, args) {
+            (Some(_), [slot, args @ ..]) => (Some(slot), args),
+            _ => (None, args),
+        };
+        let skipped = usize::from(return_slot.is_some());
+        let types: Vec<Type> = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let param = signature.as_ref().and_then(|signature| signature.params.get(i + skipped));
+                match (param, self.float_precision(&[arg])) {
+                    (Some(ty), _) => ty.clone(),
+                    (None, Some(Precision::Single)) => Type::Primitive(PrimitiveType::F32),
+                    (None, Some(Precision::Double)) => Type::Primitive(PrimitiveType::F64),
+                    (None, None) => Type::Primitive(PrimitiveType::I64),
+                }
+            })
+            .collect();
+        let (locations, stack_words) = aapcs64::assign(&types, &self.layouts);
+
+        // Aggregates passed by reference are copied to the caller's frame,
+        // so the callee may modify its argument
+        let mut copies = HashMap::new();
+        for (i, (arg, ty)) in args.iter().zip(&types).enumerate() {
+            if self.layouts.is_aggregate(ty) && aapcs64::classify(ty, &self.layouts) == Passing::Reference {
+                let offset = self.frame_slot(ty);
+                let src = self.read(arg, SCRATCH2, func);
+                func.instructions.extend(frame_address(SCRATCH3, offset));
+                for word in 0..self.layouts.size_of(ty).div_ceil(WORD_SIZE) {
+                    let offset = (word * WORD_SIZE) as i32;
+                    func.push(A64Inst::Ldr {
+                        dest: SCRATCH,
+                        addr: Address { base: src, offset },
+                        precision: None,
+                    });
+                    func.push(A64Inst::Str {
+                        src: SCRATCH,
+                        addr: Address { base: SCRATCH3, offset },
+                        precision: None,
+                    });
+                }
+                copies.insert(i, offset);
+            }
+        }
+
+        // Preserve caller-saved registers holding values below sp, keeping
+        // it 16-byte aligned
+        let sp = Register::SP;
+        let dest_loc = dest.map(|dest| self.location(dest));
+        let allocated = self.regalloc.allocated_registers();
+        let saved: Vec<Register> = AARCH64_REGISTERS
+            .general
+            .iter()
+            .chain(AARCH64_REGISTERS.float)
+            .copied()
+            .filter(|&reg| {
+                !AARCH64_REGISTERS.is_callee_saved(reg)
+                    && dest_loc != Some(Location::Reg(reg))
+                    && allocated.contains(&reg)
+            })
+            .collect();
+        let saved_bytes = (8 * saved.len() as u32).next_multiple_of(16);
+        if saved_bytes > 0 {
+            func.instructions.extend(adjust_sp(IntOp::Sub, saved_bytes));
+        }
+        for (i, &reg) in saved.iter().enumerate() {
+            func.push(A64Inst::Str {
+                src: reg,
+                addr: Address { base: sp, offset: (i * 8) as i32 },
+                precision: is_fp(reg).then_some(Precision::Double),
+            });
+        }
+
+        // Stack arguments are stored first. No argument register is
+        // allocated, so filling one never overwrites another argument.
+        let stack_bytes = (8 * stack_words as u32).next_multiple_of(16);
+        if stack_bytes > 0 {
+            func.instructions.extend(adjust_sp(IntOp::Sub, stack_bytes));
+        }
+        let mut register_args = Vec::new();
+        for (i, ((arg, ty), location)) in args.iter().zip(&types).zip(locations).enumerate() {
+            let ArgLocation::Stack { offset, words } = location else {
+                register_args.push((i, arg, ty, location));
+                continue;
+            };
+            let offset = (offset * 8) as i32;
+            if let Some(&copy) = copies.get(&i) {
+                func.instructions.extend(frame_address(SCRATCH, copy));
+                func.push(A64Inst::Str {
+                    src: SCRATCH,
+                    addr: Address { base: sp, offset },
+                    precision: None,
+                });
+            } else if self.layouts.is_aggregate(ty) {
+                let src = self.read(arg, SCRATCH2, func);
+                for word in 0..words as i32 {
+                    func.push(A64Inst::Ldr {
+                        dest: SCRATCH,
+                        addr: Address { base: src, offset: word * 8 },
+                        precision: None,
+                    });
+                    func.push(A64Inst::Str {
+                        src: SCRATCH,
+                        addr: Address { base: sp, offset: offset + word * 8 },
+                        precision: None,
+                    });
+                }
+            } else {
+                let precision = Precision::of(ty);
+                let src = match precision {
+                    Some(precision) => self.read_float(arg, precision, SCRATCH_FP, func),
+                    None => self.read(arg, SCRATCH, func),
+                };
+                func.push(A64Inst::Str {
+                    src,
+                    addr: Address { base: sp, offset },
+                    precision,
+                });
+            }
+        }
+
+        for (i, arg, ty, location) in register_args {
+            let ArgLocation::Registers(registers) = location else {
+                continue;
+            };
+            if let Some(&copy) = copies.get(&i) {
+                func.instructions.extend(frame_address(registers[0], copy));
+            } else if self.layouts.is_aggregate(ty) {
+                let passing = aapcs64::classify(ty, &self.layouts);
+                let src = self.read(arg, SCRATCH2, func);
+                for (reg, (part, precision)) in registers.into_iter().zip(register_parts(&passing)) {
+                    func.push(A64Inst::Ldr {
+                        dest: reg,
+                        addr: Address { base: src, offset: part },
+                        precision,
+                    });
+                }
+            } else if let [reg] = registers[..] {
+                match Precision::of(ty) {
+                    Some(precision) => {
+                        let src = self.read_float(arg, precision, reg, func);
+                        self.copy(Location::Reg(reg), Location::Reg(src), func);
+                    }
+                    None => self.move_into(reg, arg, func),
+                }
+            }
+        }
+
+        let memory_result = returned
+            .as_ref()
+            .is_some_and(|ret| aapcs64::classify(ret, &self.layouts) == Passing::Reference);
+        if let (Some(slot), true) = (return_slot, memory_result) {
+            self.move_into(INDIRECT_RESULT, slot, func);
+        }
+
+        match callee {
+            MirOp::Const(Constant::String(name)) => func.push(A64Inst::Bl { target: name.clone() }),
+            callee => {
+                let target = self.read(callee, SCRATCH, func);
+                func.push(A64Inst::Blr { target });
+            }
+        }
+        if stack_bytes > 0 {
+            func.instructions.extend(adjust_sp(IntOp::Add, stack_bytes));
+        }
+
+        // Store an aggregate returned in registers to its slot. The callee may
+        // have clobbered a caller-saved register holding the slot's address,
+        // so that is read back from where it was saved.
+        if let (Some(slot), Some(ret), false) = (return_slot, &returned, memory_result) {
+            let saved_at = match slot {
+                MirOp::Value(value) => match self.location(*value) {
+                    Location::Reg(reg) => saved.iter().position(|&other| other == reg),
+                    Location::Frame(_) => None,
+                },
+                MirOp::Const(_) => None,
+            };
+            let ptr = match saved_at {
+                Some(i) => {
+                    func.push(A64Inst::Ldr {
+                        dest: SCRATCH2,
+                        addr: Address { base: sp, offset: (i * 8) as i32 },
+                        precision: None,
+                    });
+                    SCRATCH2
+                }
+                None => self.read(slot, SCRATCH2, func),
+            };
+            let passing = aapcs64::classify(ret, &self.layouts);
+            let registers = aapcs64::return_registers(ret, &self.layouts).unwrap_or_default();
+            for (reg, (part, precision)) in registers.into_iter().zip(register_parts(&passing)) {
+                func.push(A64Inst::Str {
+                    src: reg,
+                    addr: Address { base: ptr, offset: part },
+                    precision,
+                });
+            }
+        }
+
+        if let (Some(dest), Some(dest_loc)) = (dest, dest_loc) {
+            let result = if self.floats.contains_key(dest) {
+                Register::D(0)
+            } else {
+                Register::X(0)
+            };
+            self.copy(dest_loc, Location::Reg(result), func);
+        }
+
+        for (i, &reg) in saved.iter().enumerate() {
+            func.push(A64Inst::Ldr {
+                dest: reg,
+                addr: Address { base: sp, offset: (i * 8) as i32 },
+                precision: is_fp(reg).then_some(Precision::Double),
+            });
+        }
+        if saved_bytes > 0 {
+            func.instructions.extend(adjust_sp(IntOp::Add, saved_bytes));
+        }
+    }
+
+    /// Register or frame slot holding a value
+    fn location(&self, value: ValueId) -> Location {
+        match self.regalloc.spill_offset(value) {
+            Some(offset) => Location::Frame(-(offset + 8)),
+            None => Location::Reg(self.regalloc.get_register(value)),
+        }
+    }
+
+    /// Register to compute a value in; spilled values are computed in a
+    /// scratch register and written back by [`Self::store_dest`]
+    fn dest_register(&self, value: ValueId) -> Register {
+        match self.location(value) {
+            Location::Reg(reg) => reg,
+            _ if self.floats.contains_key(&value) => SCRATCH_FP,
+            _ => SCRATCH,
+        }
+    }
+
+    /// Write a value computed by [`Self::dest_register`] to its spill slot
+    fn store_dest(&self, value: ValueId, func: &mut A64Function) {
+        if let Location::Frame(offset) = self.location(value) {
+            self.store_frame(self.dest_register(value), offset, func);
+        }
+    }
+
+    /// Copy all 64 bits of a value between locations
+    fn copy(&self, dest: Location, src: Location, func: &mut A64Function) {
+        match (dest, src) {
+            _ if dest == src => {}
+            (Location::Reg(dest), Location::Reg(src)) if is_fp(dest) || is_fp(src) => func.push(A64Inst::Fmov {
+                dest,
+                src,
+                precision: Precision::Double,
+            }),
+            (Location::Reg(dest), Location::Reg(src)) => func.push(A64Inst::Mov { dest, src }),
+            (Location::Reg(dest), Location::Frame(offset)) => self.load_frame(dest, offset, func),
+            (Location::Frame(offset), Location::Reg(src)) => self.store_frame(src, offset, func),
+            (Location::Frame(dest), Location::Frame(src)) => {
+                self.load_frame(SCRATCH, src, func);
+                self.store_frame(SCRATCH, dest, func);
+            }
+        }
+    }
+
+    /// Address of the frame slot at `offset` from x29, computed into `tmp`
+    /// if no load or store can encode the offset
+    fn frame_slot_address_in(&self, offset: i32, tmp: Register, func: &mut A64Function) -> Address {
+        if fits_offset(offset) {
+            return Address { base: FP, offset };
+        }
+        func.instructions.extend(frame_address(tmp, offset));
+        Address::at(tmp)
+    }
+
+    /// Address of the frame slot at `offset` from x29 for a store
+    fn frame_slot_address(&self, offset: i32, func: &mut A64Function) -> Address {
+        self.frame_slot_address_in(offset, SCRATCH2, func)
+    }
+
+    /// Load the frame slot at `offset` into `dest`
+    fn load_frame(&self, dest: Register, offset: i32, func: &mut A64Function) {
+        let tmp = if is_fp(dest) { SCRATCH } else { dest };
+        let addr = self.frame_slot_address_in(offset, tmp, func);
+        func.push(A64Inst::Ldr {
+            dest,
+            addr,
+            precision: is_fp(dest).then_some(Precision::Double),
+        });
+    }
+
+    /// Store `src` to the frame slot at `offset`; `src` is never x17, which
+    /// may hold the address
+    fn store_frame(&self, src: Register, offset: i32, func: &mut A64Function) {
+        let addr = self.frame_slot_address(offset, func);
+        func.push(A64Inst::Str {
+            src,
+            addr,
+            precision: is_fp(src).then_some(Precision::Double),
+        });
+    }
+
+    /// Register holding an integer operand, with spilled values and
+    /// constants loaded into `scratch`
+    fn read(&mut self, op: &MirOp, scratch: Register, func: &mut A64Function) -> Register {
+        match op {
+            MirOp::Value(value) => match self.location(*value) {
+                Location::Reg(reg) if is_fp(reg) => {
+                    self.copy(Location::Reg(scratch), Location::Reg(reg), func);
+                    scratch
+                }
+                Location::Reg(reg) => reg,
+                Location::Frame(offset) => {
+                    self.load_frame(scratch, offset, func);
+                    scratch
+                }
+            },
+            MirOp::Const(constant) => {
+                let inst = match constant {
+                    Constant::Int(int) => mov_immediate(scratch, *int),
+                    Constant::Bool(b) => mov_immediate(scratch, i64::from(*b)),
+                    Constant::Unit => mov_immediate(scratch, 0),
+                    Constant::Float(bits) => mov_immediate(scratch, *bits as i64),
+                    Constant::String(s) => {
+                        let symbol = self.string_constant(s);
+                        vec![A64Inst::Adr { dest: scratch, symbol }]
+                    }
+                };
+                func.instructions.extend(inst);
+                scratch
+            }
+        }
+    }
+
+    /// Move an integer operand into `dest`
+    fn move_into(&mut self, dest: Register, op: &MirOp, func: &mut A64Function) {
+        let src = self.read(op, dest, func);
+        self.copy(Location::Reg(dest), Location::Reg(src), func);
+    }
+
+    /// Register holding a float operand, with spilled values and constants
+    /// loaded into `scratch`; constants are built in x16 and moved across
+    fn read_float(&mut self, op: &MirOp, precision: Precision, scratch: Register, func: &mut A64Function) -> Register {
+        let value = match op {
+            MirOp::Const(Constant::Float(bits)) => f64::from_bits(*bits),
+            MirOp::Const(Constant::Int(int)) => *int as f64,
+            MirOp::Const(_) => 0.0,
+            MirOp::Value(value) => {
+                return match self.location(*value) {
+                    Location::Reg(reg) if is_fp(reg) => reg,
+                    Location::Reg(reg) => {
+                        func.push(A64Inst::Fmov {
+                            dest: scratch,
+                            src: reg,
+                            precision,
+                        });
+                        scratch
+                    }
+                    Location::Frame(offset) => {
+                        self.load_frame(scratch, offset, func);
+                        scratch
+                    }
+                };
+            }
+        };
+        func.instructions.extend(mov_immediate(SCRATCH, precision.bits(value) as i64));
+        func.push(A64Inst::Fmov {
+            dest: scratch,
+            src: SCRATCH,
+            precision,
+        });
+        scratch
+    }
+
+    /// Precision of the first float among `ops`, if any is a float
+    fn float_precision(&self, ops: &[&MirOp]) -> Option<Precision> {
+        let values = ops.iter().find_map(|op| match op {
+            MirOp::Value(value) => self.floats.get(value).copied(),
+            MirOp::Const(_) => None,
+        });
+        values.or_else(|| {
+            ops.iter()
+                .any(|op| matches!(op, MirOp::Const(Constant::Float(_))))
+                .then_some(Precision::Double)
+        })
+    }
+
+    /// Label of a string constant in the data section
+    fn string_constant(&mut self, content: &str) -> String {
+        let label = format!("str_{}", self.next_string_id);
+        self.next_string_id += 1;
+        self.string_constants.insert(label.clone(), content.to_string());
+        label
+    }
+}
+
+impl Default for A64Emitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aurora_mir::{MirBuilder, Span};
+    use aurora_types::{EffectSet, StructDef};
+    use std::process::Command;
+
+    fn i64_ty() -> Type {
+        Type::Primitive(PrimitiveType::I64)
+    }
+
+    fn f64_ty() -> Type {
+        Type::Primitive(PrimitiveType::F64)
+    }
+
+    /// `sum(n)` looping over a phi, and `main` calling it and `scale(x, n)`
+    fn sample_module() -> MirModule {
+        let mut module = MirModule::new();
+        let mut builder = MirBuilder::new();
+
+        builder.start_function(0, "sum".to_string(), i64_ty(), EffectSet::PURE);
+        let n = builder.add_param(i64_ty(), Span::dummy());
+        let entry = builder.current_block().unwrap();
+        let head = builder.new_block();
+        let body = builder.new_block();
+        let exit = builder.new_block();
+        builder.build_jump(head, Span::dummy());
+        builder.set_block(head);
+        let next = builder.new_value(i64_ty(), Span::dummy());
+        let i = builder.build_phi(vec![(entry, MirOp::Const(Constant::Int(0))), (body, MirOp::Value(next))], i64_ty(), Span::dummy());
+        let more = builder.build_binop(BinOp::Lt, MirOp::Value(i), MirOp::Value(n), Type::Primitive(PrimitiveType::Bool), Span::dummy());
+        builder.build_branch(MirOp::Value(more), body, exit, Span::dummy());
+        builder.set_block(body);
+        builder.emit(MirInst::BinOp {
+            dest: next,
+            op: BinOp::Add,
+            lhs: MirOp::Value(i),
+            rhs: MirOp::Const(Constant::Int(1)),
+            span: Span::dummy(),
+        });
+        builder.build_jump(head, Span::dummy());
+        builder.set_block(exit);
+        let rem = builder.build_binop(BinOp::Mod, MirOp::Value(i), MirOp::Const(Constant::Int(7)), i64_ty(), Span::dummy());
+        builder.build_return(Some(MirOp::Value(rem)), Span::dummy());
+        module.add_function(builder.finish_function().unwrap());
+
+        builder.start_function(1, "main".to_string(), i64_ty(), EffectSet::PURE);
+        let sum = MirOp::Const(Constant::String("sum".to_string()));
+        let total = builder.build_call(sum, vec![MirOp::Const(Constant::Int(10))], Some(i64_ty()), EffectSet::PURE, Span::dummy()).unwrap();
+        let x = MirOp::Const(Constant::Float(1.5f64.to_bits()));
+        let scale = MirOp::Const(Constant::String("scale".to_string()));
+        let scaled = builder.build_call(scale, vec![x, MirOp::Value(total)], Some(f64_ty()), EffectSet::PURE, Span::dummy()).unwrap();
+        let truncated = builder.new_value(i64_ty(), Span::dummy());
+        builder.emit(MirInst::Cast {
+            dest: truncated,
+            value: MirOp::Value(scaled),
+            target_ty: i64_ty(),
+            span: Span::dummy(),
+        });
+        builder.build_return(Some(MirOp::Value(truncated)), Span::dummy());
+        module.add_function(builder.finish_function().unwrap());
+        module
+    }
+
+    #[test]
+    fn test_frame_record_and_epilogue() {
+        let module = A64Emitter::new().emit_module(&sample_module());
+        let text = module.to_text();
+        assert!(text.contains(".globl main"));
+        assert!(text.contains("stp x29, x30, [sp, #-16]!"));
+        assert!(text.contains("ldp x29, x30, [sp], #16"));
+        assert!(text.contains("msub"));
+        // Block labels carry the function's id, so they are unique in the module
+        assert!(text.contains(".L0_0:") && text.contains(".L1_4:"));
+    }
+
+    #[test]
+    fn test_call_arguments_follow_aapcs64() {
+        let module = A64Emitter::new().emit_module(&sample_module());
+        let main = module.functions.iter().find(|func| func.name == "main").unwrap();
+        let insts = &main.instructions;
+        let call = insts
+            .iter()
+            .position(|inst| *inst == A64Inst::Bl { target: "scale".to_string() })
+            .unwrap();
+
+        // The float goes in d0, built from its bits in x16, and the integer in x0
+        let setup = &insts[..call];
+        assert!(setup.contains(&A64Inst::Fmov {
+            dest: Register::D(0),
+            src: SCRATCH,
+            precision: Precision::Double,
+        }));
+        assert!(setup.iter().rev().take(6).any(|inst| matches!(inst,
+            A64Inst::Mov { dest: Register::X(0), .. })));
+        // The result comes back in d0 and is truncated toward zero
+        assert!(insts[call..].iter().any(|inst| matches!(inst, A64Inst::Fcvtzs { .. })));
+    }
+
+    #[test]
+    fn test_large_aggregates_passed_by_reference_and_returned_through_x8() {
+        let big = Type::Named {
+            name: "Big".to_string(),
+            args: vec![],
+        };
+        let mut module = MirModule::new();
+        module.layouts.add_struct(
+            "Big".to_string(),
+            StructDef {
+                params: vec![],
+                fields: (0..3).map(|i| (format!("f{}", i), i64_ty())).collect(),
+            },
+        );
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "id".to_string(), big.clone(), EffectSet::PURE);
+        let slot = builder.add_param(Type::Ptr { inner: Box::new(big.clone()), mutable: true }, Span::dummy());
+        builder.add_param(big.clone(), Span::dummy());
+        builder.build_return(Some(MirOp::Value(slot)), Span::dummy());
+        module.add_function(builder.finish_function().unwrap());
+
+        builder.start_function(1, "main".to_string(), Type::Unit, EffectSet::PURE);
+        let result = builder.build_alloca(big.clone(), Span::dummy());
+        let arg = builder.build_alloca(big, Span::dummy());
+        let id = MirOp::Const(Constant::String("id".to_string()));
+        builder.build_call(id, vec![MirOp::Value(result), MirOp::Value(arg)], None, EffectSet::PURE, Span::dummy());
+        builder.build_return(None, Span::dummy());
+        module.add_function(builder.finish_function().unwrap());
+
+        let module = A64Emitter::new().emit_module(&module);
+        let function = |name: &str| module.functions.iter().find(|func| func.name == name).unwrap();
+        let reads = |func: &A64Function, reg: Register| {
+            func.instructions.iter().any(|inst| matches!(inst,
+                A64Inst::Mov { src, .. } | A64Inst::Str { src, .. } if *src == reg))
+        };
+        // The callee keeps the result address from x8 and its argument's address from x0
+        assert!(reads(function("id"), INDIRECT_RESULT));
+        // The caller copies the argument and passes the copy's address in x0
+        let caller = function("main");
+        let call = caller.instructions.iter().position(|inst| matches!(inst, A64Inst::Bl { .. })).unwrap();
+        let setup = &caller.instructions[..call];
+        assert_eq!(setup.iter().filter(|inst| matches!(inst, A64Inst::Ldr { dest: SCRATCH, .. })).count(), 3);
+        assert!(setup.iter().any(|inst| matches!(inst,
+            A64Inst::Op { dest: Register::X(0), lhs: FP, .. })));
+        assert!(setup.iter().any(|inst| matches!(inst,
+            A64Inst::Mov { dest: INDIRECT_RESULT, .. } | A64Inst::Op { dest: INDIRECT_RESULT, .. })));
+    }
+
+    #[test]
+    fn test_output_assembles() {
+        // Assembling needs an AArch64 assembler; skip without one
+        let path = std::env::temp_dir().join(format!("aurora_a64_{}.s", std::process::id()));
+        std::fs::write(&path, A64Emitter::new().emit_module(&sample_module()).to_text()).unwrap();
+        let object = path.with_extension("o");
+        let status = Command::new("llvm-mc")
+            .args(["-triple=aarch64-linux-gnu", "-filetype=obj", "-o"])
+            .arg(&object)
+            .arg(&path)
+            .status();
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&object);
+        if let Ok(status) = status {
+            assert!(status.success());
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! System V AMD64 parameter passing
+//!
+//! Each argument is split into eightbytes and classified: floats are SSE
+//! and everything else is INTEGER. An aggregate of at most 16 bytes is
+//! passed in one register per eightbyte, an eightbyte being SSE only when
+//! every scalar in it is; larger aggregates are MEMORY and are copied onto
+//! the stack. An argument that does not fit in the remaining registers goes
+//! on the stack whole.
+//!
+//! Aggregates returned in registers come back in RAX/RDX and XMM0/XMM1;
+//! MEMORY aggregates are written through a hidden pointer passed in RDI.
+//!
+//! Aggregates are classified over Aurora's layout from
+//! [`aurora_mir::layout`], where every scalar takes an 8-byte slot. Scalars
+//! are passed as C passes them, but an aggregate only matches the C struct
+//! when all of its scalars are 8 bytes wide. A struct holding an `i32`,
+//! `f32` or `bool` has another size and other eightbytes than in C, so it
+//! cannot cross a call to or from C by value.
+
+use crate::air::Register;
+use crate::target::CallingConvention;
+use aurora_mir::layout::WORD_SIZE;
+use aurora_mir::LayoutTable;
+use aurora_types::{PrimitiveType, Type};
+
+/// System V ABI calling convention (x86_64)
+const ARG_REGISTERS: [Register; 6] = [
+    Register::RDI,
+    Register::RSI,
+    Register::RDX,
+    Register::RCX,
+    Register::R8,
+    Register::R9,
+];
+
+/// System V ABI float argument registers
+const FLOAT_ARG_REGISTERS: [Register; 8] = [
+    Register::XMM0,
+    Register::XMM1,
+    Register::XMM2,
+    Register::XMM3,
+    Register::XMM4,
+    Register::XMM5,
+    Register::XMM6,
+    Register::XMM7,
+];
+
+/// Integer return registers, in eightbyte order
+const RETURN_REGISTERS: [Register; 2] = [Register::RAX, Register::RDX];
+
+/// Float return registers, in eightbyte order
+const FLOAT_RETURN_REGISTERS: [Register; 2] = [Register::XMM0, Register::XMM1];
+
+/// Registers of the System V AMD64 calling convention
+pub const SYSV_AMD64: CallingConvention = CallingConvention {
+    int_args: &ARG_REGISTERS,
+    float_args: &FLOAT_ARG_REGISTERS,
+    int_returns: &RETURN_REGISTERS,
+    float_returns: &FLOAT_RETURN_REGISTERS,
+    indirect_result: None,
+};
+
+/// Largest aggregate passed in registers, in bytes
+const MAX_REGISTER_AGGREGATE: u64 = 16;
+
+/// Register class of an eightbyte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    /// General-purpose register
+    Integer,
+    /// XMM register
+    Sse,
+}
+
+/// How a value is passed or returned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Passing {
+    /// One register of the given class per eightbyte
+    Registers(Vec<Class>),
+    /// In memory
+    Memory,
+}
+
+/// Where an argument is passed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgLocation {
+    /// One register per eightbyte
+    Registers(Vec<Register>),
+    /// Words starting at a word offset into the stack argument area
+    Stack { offset: usize, words: usize },
+}
+
+/// Classify a value of type `ty`
+pub fn classify(ty: &Type, layouts: &LayoutTable) -> Passing {
+    if !layouts.is_aggregate(ty) {
+        return Passing::Registers(vec![scalar_class(ty)]);
+    }
+    let size = layouts.size_of(ty);
+    if size > MAX_REGISTER_AGGREGATE {
+        return Passing::Memory;
+    }
+
+    let mut scalars = Vec::new();
+    flatten(ty, 0, layouts, &mut scalars);
+    let classes = (0..size.div_ceil(WORD_SIZE))
+        .map(|eightbyte| {
+            let range = eightbyte * WORD_SIZE..(eightbyte + 1) * WORD_SIZE;
+            let mut classes = scalars
+                .iter()
+                .filter(|(offset, _)| range.contains(offset))
+                .map(|&(_, class)| class)
+                .peekable();
+            // An eightbyte is SSE only if all of it is
+            if classes.peek().is_some() && classes.all(|class| class == Class::Sse) {
+                Class::Sse
+            } else {
+                Class::Integer
+            }
+        })
+        .collect();
+    Passing::Registers(classes)
+}
+
+/// Assign registers and stack words to arguments of the given types
+///
+/// Returns the location of each argument and the number of stack words.
+pub fn assign(types: &[Type], layouts: &LayoutTable) -> (Vec<ArgLocation>, usize) {
+    let mut ints = ARG_REGISTERS.iter().copied();
+    let mut floats = FLOAT_ARG_REGISTERS.iter().copied();
+    let mut stack_words = 0;
+
+    let locations = types
+        .iter()
+        .map(|ty| {
+            if let Passing::Registers(classes) = classify(ty, layouts) {
+                let needed = |class| classes.iter().filter(|&&other| other == class).count();
+                if needed(Class::Integer) <= ints.len() && needed(Class::Sse) <= floats.len() {
+                    let registers = classes
+                        .iter()
+                        .map(|class| match class {
+                            Class::Integer => ints.next(),
+                            Class::Sse => floats.next(),
+                        })
+                        .collect::<Option<Vec<_>>>()
+                        .unwrap_or_default();
+                    return ArgLocation::Registers(registers);
+                }
+            }
+            let words = (layouts.size_of(ty).div_ceil(WORD_SIZE) as usize).max(1);
+            let offset = stack_words;
+            stack_words += words;
+            ArgLocation::Stack { offset, words }
+        })
+        .collect();
+    (locations, stack_words)
+}
+
+/// Registers an aggregate of type `ty` is returned in, one per eightbyte,
+/// or `None` if it is returned through a hidden pointer
+pub fn return_registers(ty: &Type, layouts: &LayoutTable) -> Option<Vec<Register>> {
+    match classify(ty, layouts) {
+        Passing::Registers(classes) => {
+            let mut ints = RETURN_REGISTERS.iter().copied();
+            let mut floats = FLOAT_RETURN_REGISTERS.iter().copied();
+            classes
+                .iter()
+                .map(|class| match class {
+                    Class::Integer => ints.next(),
+                    Class::Sse => floats.next(),
+                })
+                .collect()
+        }
+        Passing::Memory => None,
+    }
+}
+
+/// Class of a scalar
+fn scalar_class(ty: &Type) -> Class {
+    match ty {
+        Type::Primitive(PrimitiveType::F32 | PrimitiveType::F64) => Class::Sse,
+        _ => Class::Integer,
+    }
+}
+
+/// Offsets and classes of the scalars making up a value of type `ty`
+///
+/// The variants of an enum overlap, each following the tag word.
+fn flatten(ty: &Type, base: u64, layouts: &LayoutTable, scalars: &mut Vec<(u64, Class)>) {
+    if !layouts.is_aggregate(ty) {
+        scalars.push((base, scalar_class(ty)));
+    } else if let Some(variants) = layouts.variants(ty) {
+        scalars.push((base, Class::Integer));
+        for field in variants.iter().flat_map(|variant| &variant.fields) {
+            flatten(&field.ty, base + field.offset, layouts, scalars);
+        }
+    } else if let Some(fields) = layouts.fields(ty) {
+        for field in &fields {
+            flatten(&field.ty, base + field.offset, layouts, scalars);
+        }
+    } else if let Type::Array { elem, size: Some(len) } = ty {
+        let stride = layouts.size_of(elem);
+        for i in 0..*len as u64 {
+            flatten(elem, base + i * stride, layouts, scalars);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aurora_types::StructDef;
+
+    fn f64_ty() -> Type {
+        Type::Primitive(PrimitiveType::F64)
+    }
+
+    fn i64_ty() -> Type {
+        Type::Primitive(PrimitiveType::I64)
+    }
+
+    fn named(name: &str) -> Type {
+        Type::Named {
+            name: name.to_string(),
+            args: vec![],
+        }
+    }
+
+    fn layouts() -> LayoutTable {
+        let mut layouts = LayoutTable::new();
+        let mut add = |name: &str, fields: Vec<Type>| {
+            layouts.add_struct(
+                name.to_string(),
+                StructDef {
+                    params: vec![],
+                    fields: fields.into_iter().enumerate().map(|(i, ty)| (format!("f{}", i), ty)).collect(),
+                },
+            );
+        };
+        add("Point", vec![f64_ty(), f64_ty()]);
+        add("Pair", vec![i64_ty(), f64_ty()]);
+        add("Big", vec![i64_ty(), i64_ty(), i64_ty()]);
+        layouts
+    }
+
+    #[test]
+    fn test_classify_small_aggregates_by_eightbyte() {
+        let layouts = layouts();
+        assert_eq!(
+            classify(&named("Point"), &layouts),
+            Passing::Registers(vec![Class::Sse, Class::Sse])
+        );
+        assert_eq!(
+            classify(&named("Pair"), &layouts),
+            Passing::Registers(vec![Class::Integer, Class::Sse])
+        );
+        assert_eq!(classify(&named("Big"), &layouts), Passing::Memory);
+        assert_eq!(
+            classify(&Type::Option(Box::new(f64_ty())), &layouts),
+            Passing::Registers(vec![Class::Integer, Class::Sse])
+        );
+        assert_eq!(
+            return_registers(&named("Pair"), &layouts),
+            Some(vec![Register::RAX, Register::XMM0])
+        );
+    }
+
+    #[test]
+    fn test_assign_spills_whole_aggregates_to_stack() {
+        let layouts = layouts();
+        let mut types = vec![i64_ty(); 5];
+        types.extend([named("Pair"), i64_ty(), named("Pair"), named("Big"), f64_ty()]);
+        let (locations, stack_words) = assign(&types, &layouts);
+
+        assert_eq!(locations[5], ArgLocation::Registers(vec![Register::R9, Register::XMM0]));
+        assert_eq!(locations[6], ArgLocation::Stack { offset: 0, words: 1 });
+        // No integer register is left, so the pair goes on the stack with its float
+        assert_eq!(locations[7], ArgLocation::Stack { offset: 1, words: 2 });
+        assert_eq!(locations[8], ArgLocation::Stack { offset: 3, words: 3 });
+        assert_eq!(locations[9], ArgLocation::Registers(vec![Register::XMM1]));
+        assert_eq!(stack_words, 6);
+    }
+}
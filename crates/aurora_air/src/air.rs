@@ -3,6 +3,7 @@
 //! AIR is a NASM-like textual assembly format that sits between MIR and machine code.
 //! It supports x86_64 instruction set and includes debug info and unwind directives.
 
+use crate::debug::DebugVariable;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
@@ -21,6 +22,15 @@ pub enum Register {
     // XMM registers (SIMD)
     XMM0, XMM1, XMM2, XMM3, XMM4, XMM5, XMM6, XMM7,
     XMM8, XMM9, XMM10, XMM11, XMM12, XMM13, XMM14, XMM15,
+
+    // AArch64 general purpose registers x0-x30, the stack pointer and the
+    // zero register
+    X(u8),
+    SP,
+    XZR,
+
+    // AArch64 floating-point registers d0-d31, named s0-s31 at single precision
+    D(u8),
 }
 
 impl fmt::Display for Register {
@@ -66,11 +76,71 @@ impl fmt::Display for Register {
             Register::XMM13 => "xmm13",
             Register::XMM14 => "xmm14",
             Register::XMM15 => "xmm15",
+            Register::X(n) => return write!(f, "x{}", n),
+            Register::SP => "sp",
+            Register::XZR => "xzr",
+            Register::D(n) => return write!(f, "d{}", n),
         };
         write!(f, "{}", name)
     }
 }
 
+/// The SSE registers, in encoding order
+pub const XMM_REGISTERS: [Register; 16] = [
+    Register::XMM0,
+    Register::XMM1,
+    Register::XMM2,
+    Register::XMM3,
+    Register::XMM4,
+    Register::XMM5,
+    Register::XMM6,
+    Register::XMM7,
+    Register::XMM8,
+    Register::XMM9,
+    Register::XMM10,
+    Register::XMM11,
+    Register::XMM12,
+    Register::XMM13,
+    Register::XMM14,
+    Register::XMM15,
+];
+
+impl Register {
+    /// Whether this is an SSE register, which holds floating-point values
+    pub fn is_xmm(&self) -> bool {
+        XMM_REGISTERS.contains(self)
+    }
+
+    /// Name of the register's low byte
+    pub fn low_byte(&self) -> &'static str {
+        match self {
+            Register::RAX | Register::EAX => "al",
+            Register::RBX | Register::EBX => "bl",
+            Register::RCX | Register::ECX => "cl",
+            Register::RDX | Register::EDX => "dl",
+            Register::RSI | Register::ESI => "sil",
+            Register::RDI | Register::EDI => "dil",
+            Register::RBP | Register::EBP => "bpl",
+            Register::RSP | Register::ESP => "spl",
+            Register::R8 => "r8b",
+            Register::R9 => "r9b",
+            Register::R10 => "r10b",
+            Register::R11 => "r11b",
+            Register::R12 => "r12b",
+            Register::R13 => "r13b",
+            Register::R14 => "r14b",
+            Register::R15 => "r15b",
+            _ => "al",
+        }
+    }
+}
+
+/// Write a `setcc` into the low byte of `dest`, zero-extended to the full register
+fn write_setcc(f: &mut fmt::Formatter, op: &str, dest: Register) -> fmt::Result {
+    let byte = dest.low_byte();
+    write!(f, "    {} {}\n    movzx {}, {}", op, byte, dest, byte)
+}
+
 /// AIR operand
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Operand {
@@ -121,6 +191,19 @@ pub enum Instruction {
     // Comparison
     Cmp { left: Operand, right: Operand },
     Test { left: Operand, right: Operand },
+
+    // Set a register to 1 if the flags satisfy the condition, 0 otherwise
+    Sete { dest: Register },
+    Setne { dest: Register },
+    Setl { dest: Register },
+    Setle { dest: Register },
+    Setg { dest: Register },
+    Setge { dest: Register },
+    // Unsigned and parity conditions, as set by `ucomisd`/`ucomiss`
+    Seta { dest: Register },
+    Setae { dest: Register },
+    Setp { dest: Register },
+    Setnp { dest: Register },
     
     // Control flow
     Jmp { target: String },
@@ -141,17 +224,54 @@ pub enum Instruction {
     Movaps { dest: Operand, src: Operand },
     Addps { dest: Operand, src: Operand },
     Mulps { dest: Operand, src: Operand },
+
+    // Scalar floating point (SSE2); `sd` is f64 and `ss` is f32
+    Movsd { dest: Operand, src: Operand },
+    Movss { dest: Operand, src: Operand },
+    /// Move 64 bits between a general purpose and an XMM register
+    Movq { dest: Operand, src: Operand },
+    Addsd { dest: Operand, src: Operand },
+    Subsd { dest: Operand, src: Operand },
+    Mulsd { dest: Operand, src: Operand },
+    Divsd { dest: Operand, src: Operand },
+    Sqrtsd { dest: Operand, src: Operand },
+    Addss { dest: Operand, src: Operand },
+    Subss { dest: Operand, src: Operand },
+    Mulss { dest: Operand, src: Operand },
+    Divss { dest: Operand, src: Operand },
+    Sqrtss { dest: Operand, src: Operand },
+    Xorpd { dest: Operand, src: Operand },
+    /// Unordered compare, setting ZF, PF and CF
+    Ucomisd { left: Operand, right: Operand },
+    Ucomiss { left: Operand, right: Operand },
+    /// Convert a 64-bit integer to f64
+    Cvtsi2sd { dest: Operand, src: Operand },
+    /// Convert a 64-bit integer to f32
+    Cvtsi2ss { dest: Operand, src: Operand },
+    /// Convert f64 to a 64-bit integer, truncating
+    Cvttsd2si { dest: Operand, src: Operand },
+    /// Convert f32 to a 64-bit integer, truncating
+    Cvttss2si { dest: Operand, src: Operand },
+    Cvtss2sd { dest: Operand, src: Operand },
+    Cvtsd2ss { dest: Operand, src: Operand },
     
     // Special
     Nop,
     Label { name: String },
     Comment { text: String },
+    /// Source position of the instructions that follow, for debug info
+    Loc { line: u32, column: u32 },
 }
 
 impl fmt::Display for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             // Data movement
+            // Storing an immediate needs an explicit operand size
+            Instruction::Mov {
+                dest: dest @ (Operand::Mem { .. } | Operand::MemComplex { .. }),
+                src: src @ Operand::Imm(_),
+            } => write!(f, "    mov qword {}, {}", dest, src),
             Instruction::Mov { dest, src } => write!(f, "    mov {}, {}", dest, src),
             Instruction::Movzx { dest, src } => write!(f, "    movzx {}, {}", dest, src),
             Instruction::Movsx { dest, src } => write!(f, "    movsx {}, {}", dest, src),
@@ -161,6 +281,9 @@ impl fmt::Display for Instruction {
             Instruction::Add { dest, src } => write!(f, "    add {}, {}", dest, src),
             Instruction::Sub { dest, src } => write!(f, "    sub {}, {}", dest, src),
             Instruction::Imul { dest, src } => write!(f, "    imul {}, {}", dest, src),
+            Instruction::Idiv {
+                operand: operand @ (Operand::Mem { .. } | Operand::MemComplex { .. }),
+            } => write!(f, "    idiv qword {}", operand),
             Instruction::Idiv { operand } => write!(f, "    idiv {}", operand),
             Instruction::Inc { operand } => write!(f, "    inc {}", operand),
             Instruction::Dec { operand } => write!(f, "    dec {}", operand),
@@ -176,8 +299,22 @@ impl fmt::Display for Instruction {
             Instruction::Sar { dest, count } => write!(f, "    sar {}, {}", dest, count),
 
             // Comparison
+            Instruction::Cmp {
+                left: left @ (Operand::Mem { .. } | Operand::MemComplex { .. }),
+                right,
+            } => write!(f, "    cmp qword {}, {}", left, right),
             Instruction::Cmp { left, right } => write!(f, "    cmp {}, {}", left, right),
             Instruction::Test { left, right } => write!(f, "    test {}, {}", left, right),
+            Instruction::Sete { dest } => write_setcc(f, "sete", *dest),
+            Instruction::Setne { dest } => write_setcc(f, "setne", *dest),
+            Instruction::Setl { dest } => write_setcc(f, "setl", *dest),
+            Instruction::Setle { dest } => write_setcc(f, "setle", *dest),
+            Instruction::Setg { dest } => write_setcc(f, "setg", *dest),
+            Instruction::Setge { dest } => write_setcc(f, "setge", *dest),
+            Instruction::Seta { dest } => write_setcc(f, "seta", *dest),
+            Instruction::Setae { dest } => write_setcc(f, "setae", *dest),
+            Instruction::Setp { dest } => write_setcc(f, "setp", *dest),
+            Instruction::Setnp { dest } => write_setcc(f, "setnp", *dest),
 
             // Control flow
             Instruction::Jmp { target } => write!(f, "    jmp {}", target),
@@ -191,7 +328,13 @@ impl fmt::Display for Instruction {
             Instruction::Ret => write!(f, "    ret"),
 
             // Stack
+            Instruction::Push {
+                operand: operand @ (Operand::Mem { .. } | Operand::MemComplex { .. }),
+            } => write!(f, "    push qword {}", operand),
             Instruction::Push { operand } => write!(f, "    push {}", operand),
+            Instruction::Pop {
+                operand: operand @ (Operand::Mem { .. } | Operand::MemComplex { .. }),
+            } => write!(f, "    pop qword {}", operand),
             Instruction::Pop { operand } => write!(f, "    pop {}", operand),
 
             // SIMD
@@ -199,10 +342,44 @@ impl fmt::Display for Instruction {
             Instruction::Addps { dest, src } => write!(f, "    addps {}, {}", dest, src),
             Instruction::Mulps { dest, src } => write!(f, "    mulps {}, {}", dest, src),
 
+            // Scalar floating point
+            Instruction::Movsd { dest, src } => write!(f, "    movsd {}, {}", dest, src),
+            Instruction::Movss { dest, src } => write!(f, "    movss {}, {}", dest, src),
+            Instruction::Movq { dest, src } => write!(f, "    movq {}, {}", dest, src),
+            Instruction::Addsd { dest, src } => write!(f, "    addsd {}, {}", dest, src),
+            Instruction::Subsd { dest, src } => write!(f, "    subsd {}, {}", dest, src),
+            Instruction::Mulsd { dest, src } => write!(f, "    mulsd {}, {}", dest, src),
+            Instruction::Divsd { dest, src } => write!(f, "    divsd {}, {}", dest, src),
+            Instruction::Sqrtsd { dest, src } => write!(f, "    sqrtsd {}, {}", dest, src),
+            Instruction::Addss { dest, src } => write!(f, "    addss {}, {}", dest, src),
+            Instruction::Subss { dest, src } => write!(f, "    subss {}, {}", dest, src),
+            Instruction::Mulss { dest, src } => write!(f, "    mulss {}, {}", dest, src),
+            Instruction::Divss { dest, src } => write!(f, "    divss {}, {}", dest, src),
+            Instruction::Sqrtss { dest, src } => write!(f, "    sqrtss {}, {}", dest, src),
+            Instruction::Xorpd { dest, src } => write!(f, "    xorpd {}, {}", dest, src),
+            Instruction::Ucomisd { left, right } => write!(f, "    ucomisd {}, {}", left, right),
+            Instruction::Ucomiss { left, right } => write!(f, "    ucomiss {}, {}", left, right),
+            // The integer source's size is ambiguous for memory operands
+            Instruction::Cvtsi2sd {
+                dest,
+                src: src @ (Operand::Mem { .. } | Operand::MemComplex { .. }),
+            } => write!(f, "    cvtsi2sd {}, qword {}", dest, src),
+            Instruction::Cvtsi2sd { dest, src } => write!(f, "    cvtsi2sd {}, {}", dest, src),
+            Instruction::Cvtsi2ss {
+                dest,
+                src: src @ (Operand::Mem { .. } | Operand::MemComplex { .. }),
+            } => write!(f, "    cvtsi2ss {}, qword {}", dest, src),
+            Instruction::Cvtsi2ss { dest, src } => write!(f, "    cvtsi2ss {}, {}", dest, src),
+            Instruction::Cvttsd2si { dest, src } => write!(f, "    cvttsd2si {}, {}", dest, src),
+            Instruction::Cvttss2si { dest, src } => write!(f, "    cvttss2si {}, {}", dest, src),
+            Instruction::Cvtss2sd { dest, src } => write!(f, "    cvtss2sd {}, {}", dest, src),
+            Instruction::Cvtsd2ss { dest, src } => write!(f, "    cvtsd2ss {}, {}", dest, src),
+
             // Special
             Instruction::Nop => write!(f, "    nop"),
             Instruction::Label { name } => write!(f, "{}:", name),
             Instruction::Comment { text } => write!(f, "    ; {}", text),
+            Instruction::Loc { line, column } => write!(f, "    ; line {}:{}", line, column),
         }
     }
 }
@@ -215,6 +392,8 @@ impl fmt::Display for Operand {
             Operand::Mem { base, offset } => {
                 if *offset == 0 {
                     write!(f, "[{}]", base)
+                } else if *offset < 0 {
+                    write!(f, "[{} - {}]", base, -offset)
                 } else {
                     write!(f, "[{} + {}]", base, offset)
                 }
@@ -254,6 +433,10 @@ pub struct AirFunction {
     pub frame_size: u32,
     /// Used registers (for saving/restoring)
     pub used_regs: Vec<Register>,
+    /// Declaration line, 0 without debug info
+    pub line: u32,
+    /// Named source variables, empty without debug info
+    pub debug_vars: Vec<DebugVariable>,
 }
 
 impl AirFunction {
@@ -263,6 +446,8 @@ impl AirFunction {
             instructions: Vec::new(),
             frame_size: 0,
             used_regs: Vec::new(),
+            line: 0,
+            debug_vars: Vec::new(),
         }
     }
 
@@ -270,39 +455,54 @@ impl AirFunction {
         self.instructions.push(inst);
     }
 
-    /// Generate AIR text
-    pub fn to_text(&self) -> String {
-        let mut output = String::new();
-        output.push_str(&format!("{}:\n", self.name));
-        
+    /// Instructions with the prologue and epilogue setting up the frame and
+    /// saving the used callee-saved registers
+    pub fn framed_instructions(&self) -> Vec<Instruction> {
+        let mut instructions = Vec::new();
+        let reg = Operand::Reg;
+
         // Prologue
         if self.frame_size > 0 {
-            output.push_str("    push rbp\n");
-            output.push_str("    mov rbp, rsp\n");
-            output.push_str(&format!("    sub rsp, {}\n", self.frame_size));
+            instructions.push(Instruction::Push { operand: reg(Register::RBP) });
+            instructions.push(Instruction::Mov {
+                dest: reg(Register::RBP),
+                src: reg(Register::RSP),
+            });
+            instructions.push(Instruction::Sub {
+                dest: reg(Register::RSP),
+                src: Operand::Imm(i64::from(self.frame_size)),
+            });
         }
-        
+
         // Save used registers
-        for reg in &self.used_regs {
-            output.push_str(&format!("    push {}\n", reg));
-        }
-        
-        // Body
-        for inst in &self.instructions {
-            output.push_str(&format!("{}\n", inst));
+        for &used in &self.used_regs {
+            instructions.push(Instruction::Push { operand: reg(used) });
         }
-        
+
+        instructions.extend(self.instructions.iter().cloned());
+
         // Epilogue
-        for reg in self.used_regs.iter().rev() {
-            output.push_str(&format!("    pop {}\n", reg));
+        for &used in self.used_regs.iter().rev() {
+            instructions.push(Instruction::Pop { operand: reg(used) });
         }
-        
         if self.frame_size > 0 {
-            output.push_str("    mov rsp, rbp\n");
-            output.push_str("    pop rbp\n");
+            instructions.push(Instruction::Mov {
+                dest: reg(Register::RSP),
+                src: reg(Register::RBP),
+            });
+            instructions.push(Instruction::Pop { operand: reg(Register::RBP) });
+        }
+        instructions.push(Instruction::Ret);
+        instructions
+    }
+
+    /// Generate AIR text
+    pub fn to_text(&self) -> String {
+        let mut output = String::new();
+        output.push_str(&format!("{}:\n", self.name));
+        for inst in self.framed_instructions() {
+            output.push_str(&format!("{}\n", inst));
         }
-        
-        output.push_str("    ret\n");
         output
     }
 }
@@ -372,6 +572,17 @@ pub enum DataKind {
     String,
 }
 
+impl DataDirective {
+    /// The first `width` bytes of the value as a little-endian integer
+    pub(crate) fn le_value(&self, width: usize) -> u64 {
+        self.value
+            .iter()
+            .take(width)
+            .enumerate()
+            .fold(0, |acc, (i, &byte)| acc | (u64::from(byte) << (8 * i)))
+    }
+}
+
 impl fmt::Display for DataDirective {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self.kind {
@@ -396,9 +607,10 @@ impl fmt::Display for DataDirective {
                 }
                 Ok(())
             }
-            DataKind::Word => write!(f, "{}:  dw {}", self.label, self.value[0]),
-            DataKind::Dword => write!(f, "{}:  dd {}", self.label, self.value[0]),
-            DataKind::Qword => write!(f, "{}:  dq {}", self.label, self.value[0]),
+            // Wider values are stored little-endian
+            DataKind::Word => write!(f, "{}:  dw {}", self.label, self.le_value(2)),
+            DataKind::Dword => write!(f, "{}:  dd {}", self.label, self.le_value(4)),
+            DataKind::Qword => write!(f, "{}:  dq {}", self.label, self.le_value(8)),
         }
     }
 }
@@ -434,6 +646,24 @@ mod tests {
         assert!(format!("{}", mov).contains("mov"));
     }
 
+    #[test]
+    fn test_float_instruction_display() {
+        let convert = Instruction::Cvtsi2sd {
+            dest: Operand::Reg(Register::XMM1),
+            src: Operand::Mem { base: Register::RBP, offset: -8 },
+        };
+        assert_eq!(format!("{}", convert), "    cvtsi2sd xmm1, qword [rbp - 8]");
+        assert!(Register::XMM15.is_xmm());
+        assert!(!Register::RAX.is_xmm());
+
+        let one = DataDirective {
+            label: "flt_0".to_string(),
+            kind: DataKind::Qword,
+            value: 1.0f64.to_bits().to_le_bytes().to_vec(),
+        };
+        assert_eq!(format!("{}", one), format!("flt_0:  dq {}", 1.0f64.to_bits()));
+    }
+
     #[test]
     fn test_air_function() {
         let mut func = AirFunction::new("test".to_string());
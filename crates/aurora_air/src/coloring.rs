@@ -0,0 +1,1056 @@
+//! Register Allocation - Graph Coloring with Iterated Register Coalescing
+//!
+//! An alternative to the linear scan allocator for code where register
+//! pressure matters, such as hot loops. It follows George and Appel's
+//! iterated register coalescing:
+//! - Interference graph built from SSA liveness, with phis defined at the
+//!   start of their block and their inputs used at the end of the predecessor
+//! - Conservative (Briggs) coalescing of copies, casts and phi inputs, so the
+//!   moves between them disappear
+//! - Spill candidates chosen by use count weighted by loop depth over degree
+//!
+//! Values that spill are not simply left in memory. A spilled constant is
+//! rematerialized at its uses instead, and a spilled value used several times
+//! in a block is reloaded into a fresh value once for that block, splitting
+//! its live range. The graph is then built and colored again.
+//!
+//! Integer and floating-point values never share a register, so each class
+//! is colored separately, with the general or float registers of the
+//! [`RegisterFile`].
+
+use crate::air::Register;
+use crate::regalloc::{float_values, Allocator, X86_64_REGISTERS};
+use crate::target::RegisterFile;
+use aurora_mir::{BlockId, Constant, Function, Instruction, Operand, ValueId, CFG};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// Colorings tried before the remaining spills stay in memory
+const MAX_ROUNDS: usize = 4;
+
+/// Register allocator using iterated register coalescing
+pub struct GraphColoringAllocator {
+    /// Value to register mapping
+    colors: HashMap<ValueId, Register>,
+    /// Stack slots for spilled values
+    spills: HashMap<ValueId, i32>,
+    /// Next stack offset
+    next_stack_offset: i32,
+    /// Callee-saved registers that we're using
+    callee_saved: Vec<Register>,
+    /// Registers handed out
+    registers: &'static RegisterFile,
+    /// Moves whose source and destination share a location
+    coalesced_moves: usize,
+    /// Colorings done by the last allocation
+    rounds: usize,
+}
+
+impl GraphColoringAllocator {
+    pub fn new() -> Self {
+        Self::with_registers(&X86_64_REGISTERS)
+    }
+
+    /// Create an allocator handing out `registers`
+    pub fn with_registers(registers: &'static RegisterFile) -> Self {
+        Self {
+            colors: HashMap::new(),
+            spills: HashMap::new(),
+            next_stack_offset: 0,
+            callee_saved: Vec::new(),
+            registers,
+            coalesced_moves: 0,
+            rounds: 0,
+        }
+    }
+
+    /// Run register allocation on function, splitting and rematerializing spilled values
+    pub fn allocate(&mut self, func: &mut Function) {
+        // Values created by splitting and the values they were split from
+        let mut split = HashSet::new();
+        let mut temps = HashSet::new();
+
+        for round in 1..=MAX_ROUNDS {
+            self.rounds = round;
+            let graph = Interference::build(func, &temps);
+            let floats = float_values(func);
+            let mut colorings = [
+                Coloring::new(graph.class(|value| !floats.contains(&value)), self.registers.general),
+                Coloring::new(graph.class(|value| floats.contains(&value)), self.registers.float),
+            ];
+
+            let mut spilled = Vec::new();
+            for coloring in &mut colorings {
+                coloring.run();
+                spilled.extend(
+                    coloring
+                        .graph
+                        .nodes
+                        .iter()
+                        .copied()
+                        .filter(|&node| coloring.spilled.contains(&coloring.alias(node))),
+                );
+            }
+            if round == MAX_ROUNDS || spilled.is_empty() || !rewrite(func, &spilled, &mut split, &mut temps) {
+                self.assign(&colorings);
+                return;
+            }
+        }
+    }
+
+    /// Record the locations of finished colorings, one per register class
+    fn assign(&mut self, colorings: &[Coloring]) {
+        self.colors.clear();
+        self.spills.clear();
+        self.next_stack_offset = 0;
+        self.callee_saved.clear();
+        self.coalesced_moves = colorings.iter().map(|coloring| coloring.coalesced_moves.len()).sum();
+
+        for coloring in colorings {
+            // Values coalesced together share a register or a stack slot
+            let mut slots = BTreeMap::new();
+            for &node in &coloring.graph.nodes {
+                let root = coloring.alias(node);
+                if let Some(&color) = coloring.color.get(&root) {
+                    let reg = coloring.palette[color];
+                    self.colors.insert(node, reg);
+                    if self.registers.is_callee_saved(reg) && !self.callee_saved.contains(&reg) {
+                        self.callee_saved.push(reg);
+                    }
+                } else {
+                    slots.entry(root).or_insert_with(Vec::new).push(node);
+                }
+            }
+            for values in slots.into_values() {
+                let offset = self.next_stack_offset;
+                self.next_stack_offset += 8; // 8 bytes per value
+                for value in values {
+                    self.spills.insert(value, offset);
+                }
+            }
+        }
+    }
+
+    /// Get register for value
+    pub fn get_register(&self, value: ValueId) -> Register {
+        self.colors.get(&value).copied().unwrap_or(self.registers.general[0])
+    }
+
+    /// Get stack size needed
+    pub fn stack_size(&self) -> u32 {
+        self.next_stack_offset as u32
+    }
+
+    /// Get callee-saved registers that need saving/restoring
+    pub fn callee_saved_registers(&self) -> Vec<Register> {
+        self.callee_saved.clone()
+    }
+
+    /// Check if value is spilled
+    pub fn is_spilled(&self, value: ValueId) -> bool {
+        self.spills.contains_key(&value)
+    }
+
+    /// Get spill offset
+    pub fn spill_offset(&self, value: ValueId) -> Option<i32> {
+        self.spills.get(&value).copied()
+    }
+
+    /// Check if value was given a register or spill slot
+    pub fn has_location(&self, value: ValueId) -> bool {
+        self.colors.contains_key(&value) || self.spills.contains_key(&value)
+    }
+
+    /// Get all allocated registers
+    pub fn allocated_registers(&self) -> Vec<Register> {
+        self.colors.values().copied().collect()
+    }
+
+    /// Number of moves removed by coalescing in the last allocation
+    pub fn coalesced_moves(&self) -> usize {
+        self.coalesced_moves
+    }
+
+    /// Number of colorings the last allocation needed
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+}
+
+impl Allocator for GraphColoringAllocator {
+    fn allocate(&mut self, func: &mut Function) {
+        GraphColoringAllocator::allocate(self, func);
+    }
+
+    fn get_register(&self, value: ValueId) -> Register {
+        GraphColoringAllocator::get_register(self, value)
+    }
+
+    fn spill_offset(&self, value: ValueId) -> Option<i32> {
+        GraphColoringAllocator::spill_offset(self, value)
+    }
+
+    fn has_location(&self, value: ValueId) -> bool {
+        GraphColoringAllocator::has_location(self, value)
+    }
+
+    fn stack_size(&self) -> u32 {
+        GraphColoringAllocator::stack_size(self)
+    }
+
+    fn callee_saved_registers(&self) -> Vec<Register> {
+        GraphColoringAllocator::callee_saved_registers(self)
+    }
+
+    fn allocated_registers(&self) -> Vec<Register> {
+        GraphColoringAllocator::allocated_registers(self)
+    }
+}
+
+impl Default for GraphColoringAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Interference graph of a function's values
+struct Interference {
+    nodes: BTreeSet<ValueId>,
+    edges: HashSet<(ValueId, ValueId)>,
+    adjacent: HashMap<ValueId, BTreeSet<ValueId>>,
+    /// Copies between values, as (dest, src)
+    moves: Vec<(ValueId, ValueId)>,
+    /// Cost of keeping each value in memory
+    spill_cost: HashMap<ValueId, f64>,
+}
+
+impl Interference {
+    /// Build the graph of `func`; `temps` are split values that must not spill
+    fn build(func: &Function, temps: &HashSet<ValueId>) -> Self {
+        let mut graph = Self {
+            nodes: func.params.iter().copied().collect(),
+            edges: HashSet::new(),
+            adjacent: HashMap::new(),
+            moves: Vec::new(),
+            spill_cost: HashMap::new(),
+        };
+
+        let cfg = CFG::build(func);
+        let live_out = live_out(func, &cfg);
+        let depth = loop_depth(&cfg);
+
+        let mut block_ids: Vec<BlockId> = func.blocks.keys().copied().collect();
+        block_ids.sort_unstable();
+        for block_id in block_ids {
+            let block = &func.blocks[&block_id];
+            let weight = 10f64.powi(depth.get(&block_id).copied().unwrap_or(0).min(4) as i32);
+            let mut live = live_out.get(&block_id).cloned().unwrap_or_default();
+
+            let mut phis = Vec::new();
+            for inst in block.instructions.iter().rev() {
+                for value in values(inst) {
+                    *graph.spill_cost.entry(value).or_default() += weight;
+                    graph.nodes.insert(value);
+                }
+
+                if let Instruction::Phi { dest, inputs, .. } = inst {
+                    phis.push(*dest);
+                    for (_, input) in inputs {
+                        if let Operand::Value(src) = input {
+                            graph.moves.push((*dest, *src));
+                        }
+                    }
+                    continue;
+                }
+
+                let uses: Vec<ValueId> = operand_values(inst).collect();
+                let Some(dest) = inst.dest() else {
+                    live.extend(uses);
+                    continue;
+                };
+                match copy_source(inst) {
+                    // A copy's source may share its destination's register
+                    Some(src) if !temps.contains(&dest) => {
+                        live.remove(&src);
+                        graph.moves.push((dest, src));
+                    }
+                    // A split copy stays apart from the value it reloads
+                    Some(src) => graph.add_edge(dest, src),
+                    None => {}
+                }
+                // `mov dest, lhs` is emitted before the right operand is read
+                if let Instruction::BinOp { lhs, rhs: Operand::Value(rhs), .. } = inst {
+                    if *lhs != Operand::Value(*rhs) {
+                        graph.add_edge(dest, *rhs);
+                    }
+                }
+                for &value in &live {
+                    graph.add_edge(dest, value);
+                }
+                live.remove(&dest);
+                live.extend(uses);
+            }
+
+            // Phis are all written at the start of the block, and parameters
+            // at the start of the function
+            let mut defined = phis;
+            if block_id == func.entry {
+                defined.extend(func.params.iter().copied());
+            }
+            live.extend(defined.iter().copied());
+            for &dest in &defined {
+                for &value in &live {
+                    graph.add_edge(dest, value);
+                }
+            }
+        }
+
+        for value in temps {
+            graph.spill_cost.insert(*value, f64::INFINITY);
+        }
+        graph
+    }
+
+    /// The subgraph of the values `keep` accepts
+    fn class(&self, keep: impl Fn(ValueId) -> bool) -> Self {
+        let mut graph = Self {
+            nodes: self.nodes.iter().copied().filter(|&node| keep(node)).collect(),
+            edges: HashSet::new(),
+            adjacent: HashMap::new(),
+            moves: self
+                .moves
+                .iter()
+                .copied()
+                .filter(|&(dest, src)| keep(dest) && keep(src))
+                .collect(),
+            spill_cost: self
+                .spill_cost
+                .iter()
+                .filter(|(&value, _)| keep(value))
+                .map(|(&value, &cost)| (value, cost))
+                .collect(),
+        };
+        for &(a, b) in &self.edges {
+            if keep(a) && keep(b) {
+                graph.add_edge(a, b);
+            }
+        }
+        graph
+    }
+
+    fn add_edge(&mut self, a: ValueId, b: ValueId) {
+        if a != b && self.edges.insert((a, b)) {
+            self.edges.insert((b, a));
+            self.adjacent.entry(a).or_default().insert(b);
+            self.adjacent.entry(b).or_default().insert(a);
+        }
+    }
+
+    fn interferes(&self, a: ValueId, b: ValueId) -> bool {
+        self.edges.contains(&(a, b))
+    }
+}
+
+/// Values live at the end of each block
+///
+/// A phi's inputs are live at the end of the predecessor they come from,
+/// not at the start of the phi's block.
+fn live_out(func: &Function, cfg: &CFG) -> HashMap<BlockId, HashSet<ValueId>> {
+    let mut live_in: HashMap<BlockId, HashSet<ValueId>> = HashMap::new();
+    let mut live_out: HashMap<BlockId, HashSet<ValueId>> = HashMap::new();
+
+    let mut order = cfg.post_order.clone();
+    order.extend(func.blocks.keys().filter(|id| !cfg.post_order.contains(id)));
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &block_id in &order {
+            let Some(block) = func.block(block_id) else {
+                continue;
+            };
+            let mut live: HashSet<ValueId> = HashSet::new();
+            for &succ in cfg.succs(block_id) {
+                live.extend(live_in.get(&succ).into_iter().flatten().copied());
+                live.extend(phi_inputs(func, succ, block_id));
+            }
+            if live_out.get(&block_id) != Some(&live) {
+                live_out.insert(block_id, live.clone());
+            }
+
+            for inst in block.instructions.iter().rev() {
+                if let Some(dest) = inst.dest() {
+                    live.remove(&dest);
+                }
+                if !matches!(inst, Instruction::Phi { .. }) {
+                    live.extend(operand_values(inst));
+                }
+            }
+            if live_in.get(&block_id) != Some(&live) {
+                live_in.insert(block_id, live);
+                changed = true;
+            }
+        }
+    }
+    live_out
+}
+
+/// Values `block`'s phis take from `pred`
+fn phi_inputs(func: &Function, block: BlockId, pred: BlockId) -> Vec<ValueId> {
+    let Some(block) = func.block(block) else {
+        return Vec::new();
+    };
+    block
+        .instructions
+        .iter()
+        .filter_map(|inst| match inst {
+            Instruction::Phi { inputs, .. } => inputs.iter().find(|(from, _)| *from == pred),
+            _ => None,
+        })
+        .filter_map(|(_, input)| match input {
+            Operand::Value(value) => Some(*value),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Number of loops each block is in
+fn loop_depth(cfg: &CFG) -> HashMap<BlockId, usize> {
+    // Loops with several back edges are found once per edge
+    let mut loops: BTreeMap<BlockId, HashSet<BlockId>> = BTreeMap::new();
+    for natural in cfg.find_loops() {
+        loops.entry(natural.header).or_default().extend(natural.blocks);
+    }
+    let mut depth = HashMap::new();
+    for block in loops.into_values().flatten() {
+        *depth.entry(block).or_insert(0) += 1;
+    }
+    depth
+}
+
+/// Values an instruction reads
+fn operand_values(inst: &Instruction) -> impl Iterator<Item = ValueId> + '_ {
+    inst.operands().into_iter().filter_map(|operand| match operand {
+        Operand::Value(value) => Some(*value),
+        _ => None,
+    })
+}
+
+/// Values an instruction reads or writes
+fn values(inst: &Instruction) -> impl Iterator<Item = ValueId> + '_ {
+    inst.dest().into_iter().chain(operand_values(inst))
+}
+
+/// Value an instruction copies into its destination
+fn copy_source(inst: &Instruction) -> Option<ValueId> {
+    match inst {
+        Instruction::Assign { value: Operand::Value(src), .. } | Instruction::Cast { value: Operand::Value(src), .. } => {
+            Some(*src)
+        }
+        _ => None,
+    }
+}
+
+/// Constant a value can be recomputed from instead of reloaded
+fn rematerializable(func: &Function, value: ValueId) -> Option<Constant> {
+    func.blocks.values().flat_map(|block| &block.instructions).find_map(|inst| match inst {
+        Instruction::Assign { dest, value: Operand::Const(constant), .. } if *dest == value => match constant {
+            // Other instructions only take 32-bit immediates
+            Constant::Int(int) if i32::try_from(*int).is_ok() => Some(constant.clone()),
+            Constant::Bool(_) | Constant::Unit => Some(constant.clone()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Rewrite spilled values so the next coloring can keep more in registers
+///
+/// Constants are rematerialized at each use, and values used several times
+/// in a block get a copy there that the uses read instead. Returns whether
+/// anything changed.
+fn rewrite(
+    func: &mut Function,
+    spilled: &[ValueId],
+    split: &mut HashSet<ValueId>,
+    temps: &mut HashSet<ValueId>,
+) -> bool {
+    let mut changed = false;
+
+    let mut constants = HashMap::new();
+    for &value in spilled {
+        if let Some(constant) = rematerializable(func, value) {
+            constants.insert(value, Operand::Const(constant));
+        }
+    }
+    if !constants.is_empty() {
+        for block in func.blocks.values_mut() {
+            block
+                .instructions
+                .retain(|inst| !inst.dest().is_some_and(|dest| constants.contains_key(&dest)));
+        }
+        func.replace_uses(&constants);
+        changed = true;
+    }
+
+    let mut block_ids: Vec<BlockId> = func.blocks.keys().copied().collect();
+    block_ids.sort_unstable();
+    for &value in spilled {
+        if constants.contains_key(&value) || !split.insert(value) {
+            continue;
+        }
+        let Some(info) = func.value(value).cloned() else {
+            continue;
+        };
+        for &block_id in &block_ids {
+            let Some(block) = func.block(block_id) else {
+                continue;
+            };
+            let uses: Vec<usize> = block
+                .instructions
+                .iter()
+                .enumerate()
+                .filter(|(_, inst)| !matches!(inst, Instruction::Phi { .. }))
+                .flat_map(|(index, inst)| operand_values(inst).filter(move |&used| used == value).map(move |_| index))
+                .collect();
+            // A single use reads the stack slot directly
+            let &[first, _, ..] = uses.as_slice() else {
+                continue;
+            };
+
+            let temp = func.new_value(info.ty.clone(), info.span);
+            split.insert(temp);
+            temps.insert(temp);
+            let block = func.block_mut(block_id).expect("block exists");
+            for inst in &mut block.instructions[first..] {
+                for operand in inst.operands_mut() {
+                    if *operand == Operand::Value(value) {
+                        *operand = Operand::Value(temp);
+                    }
+                }
+            }
+            block.instructions.insert(
+                first,
+                Instruction::Assign {
+                    dest: temp,
+                    value: Operand::Value(value),
+                    span: info.span,
+                },
+            );
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// State of one run of iterated register coalescing
+struct Coloring {
+    graph: Interference,
+    /// Registers of the class being colored
+    palette: &'static [Register],
+    /// Number of registers values are colored with
+    k: usize,
+    degree: HashMap<ValueId, usize>,
+    /// Moves each value takes part in
+    move_list: HashMap<ValueId, BTreeSet<usize>>,
+    /// Moves that may be coalesced
+    worklist_moves: BTreeSet<usize>,
+    /// Moves not yet ready for coalescing
+    active_moves: BTreeSet<usize>,
+    coalesced_moves: BTreeSet<usize>,
+    /// Low-degree values not related to moves
+    simplify_worklist: BTreeSet<ValueId>,
+    /// Low-degree values related to moves
+    freeze_worklist: BTreeSet<ValueId>,
+    /// High-degree values
+    spill_worklist: BTreeSet<ValueId>,
+    spilled: BTreeSet<ValueId>,
+    coalesced_nodes: HashSet<ValueId>,
+    /// Value each coalesced value was merged into
+    alias: HashMap<ValueId, ValueId>,
+    select_stack: Vec<ValueId>,
+    on_stack: HashSet<ValueId>,
+    /// Index in `palette` of each colored value
+    color: HashMap<ValueId, usize>,
+}
+
+impl Coloring {
+    fn new(graph: Interference, palette: &'static [Register]) -> Self {
+        let degree = graph
+            .nodes
+            .iter()
+            .map(|node| (*node, graph.adjacent.get(node).map_or(0, BTreeSet::len)))
+            .collect();
+        let mut move_list: HashMap<ValueId, BTreeSet<usize>> = HashMap::new();
+        for (index, &(dest, src)) in graph.moves.iter().enumerate() {
+            move_list.entry(dest).or_default().insert(index);
+            move_list.entry(src).or_default().insert(index);
+        }
+
+        Self {
+            worklist_moves: (0..graph.moves.len()).collect(),
+            graph,
+            palette,
+            k: palette.len(),
+            degree,
+            move_list,
+            active_moves: BTreeSet::new(),
+            coalesced_moves: BTreeSet::new(),
+            simplify_worklist: BTreeSet::new(),
+            freeze_worklist: BTreeSet::new(),
+            spill_worklist: BTreeSet::new(),
+            spilled: BTreeSet::new(),
+            coalesced_nodes: HashSet::new(),
+            alias: HashMap::new(),
+            select_stack: Vec::new(),
+            on_stack: HashSet::new(),
+            color: HashMap::new(),
+        }
+    }
+
+    fn run(&mut self) {
+        for node in self.graph.nodes.clone() {
+            if self.degree[&node] >= self.k {
+                self.spill_worklist.insert(node);
+            } else if self.move_related(node) {
+                self.freeze_worklist.insert(node);
+            } else {
+                self.simplify_worklist.insert(node);
+            }
+        }
+
+        loop {
+            if let Some(node) = self.simplify_worklist.pop_first() {
+                self.simplify(node);
+            } else if let Some(index) = self.worklist_moves.pop_first() {
+                self.coalesce(index);
+            } else if let Some(node) = self.freeze_worklist.pop_first() {
+                self.simplify_worklist.insert(node);
+                self.freeze_moves(node);
+            } else if !self.spill_worklist.is_empty() {
+                self.select_spill();
+            } else {
+                break;
+            }
+        }
+
+        self.assign_colors();
+    }
+
+    /// Neighbours still in the graph
+    fn adjacent(&self, node: ValueId) -> Vec<ValueId> {
+        self.graph
+            .adjacent
+            .get(&node)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|n| !self.on_stack.contains(n) && !self.coalesced_nodes.contains(n))
+            .collect()
+    }
+
+    /// Moves of `node` that may still be coalesced
+    fn node_moves(&self, node: ValueId) -> Vec<usize> {
+        self.move_list
+            .get(&node)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|index| self.active_moves.contains(index) || self.worklist_moves.contains(index))
+            .collect()
+    }
+
+    fn move_related(&self, node: ValueId) -> bool {
+        !self.node_moves(node).is_empty()
+    }
+
+    fn alias(&self, mut node: ValueId) -> ValueId {
+        while self.coalesced_nodes.contains(&node) {
+            node = self.alias[&node];
+        }
+        node
+    }
+
+    fn simplify(&mut self, node: ValueId) {
+        self.select_stack.push(node);
+        self.on_stack.insert(node);
+        for neighbour in self.adjacent(node) {
+            self.decrement_degree(neighbour);
+        }
+    }
+
+    fn decrement_degree(&mut self, node: ValueId) {
+        let degree = self.degree[&node];
+        self.degree.insert(node, degree.saturating_sub(1));
+        if degree == self.k {
+            let mut nodes = self.adjacent(node);
+            nodes.push(node);
+            self.enable_moves(&nodes);
+            self.spill_worklist.remove(&node);
+            if self.move_related(node) {
+                self.freeze_worklist.insert(node);
+            } else {
+                self.simplify_worklist.insert(node);
+            }
+        }
+    }
+
+    fn enable_moves(&mut self, nodes: &[ValueId]) {
+        for &node in nodes {
+            for index in self.node_moves(node) {
+                if self.active_moves.remove(&index) {
+                    self.worklist_moves.insert(index);
+                }
+            }
+        }
+    }
+
+    fn coalesce(&mut self, index: usize) {
+        let (dest, src) = self.graph.moves[index];
+        let (u, v) = (self.alias(dest), self.alias(src));
+
+        if u == v {
+            self.coalesced_moves.insert(index);
+            self.add_worklist(u);
+        } else if self.graph.interferes(u, v) {
+            self.add_worklist(u);
+            self.add_worklist(v);
+        } else if self.conservative(u, v) {
+            self.coalesced_moves.insert(index);
+            self.combine(u, v);
+            self.add_worklist(u);
+        } else {
+            self.active_moves.insert(index);
+        }
+    }
+
+    /// Move `node` to the simplify worklist once it can no longer be coalesced
+    fn add_worklist(&mut self, node: ValueId) {
+        if !self.move_related(node) && self.degree[&node] < self.k {
+            self.freeze_worklist.remove(&node);
+            self.simplify_worklist.insert(node);
+        }
+    }
+
+    /// Briggs' test: the merged value has fewer than k significant neighbours
+    fn conservative(&self, u: ValueId, v: ValueId) -> bool {
+        let neighbours: BTreeSet<ValueId> = self.adjacent(u).into_iter().chain(self.adjacent(v)).collect();
+        neighbours.iter().filter(|n| self.degree[n] >= self.k).count() < self.k
+    }
+
+    /// Merge `v` into `u`
+    fn combine(&mut self, u: ValueId, v: ValueId) {
+        if !self.freeze_worklist.remove(&v) {
+            self.spill_worklist.remove(&v);
+        }
+        self.coalesced_nodes.insert(v);
+        self.alias.insert(v, u);
+        let moves = self.move_list.get(&v).cloned().unwrap_or_default();
+        self.move_list.entry(u).or_default().extend(moves);
+        let cost = self.graph.spill_cost.get(&v).copied().unwrap_or_default();
+        *self.graph.spill_cost.entry(u).or_default() += cost;
+        self.enable_moves(&[v]);
+
+        for neighbour in self.adjacent(v) {
+            if !self.graph.interferes(neighbour, u) {
+                self.graph.add_edge(neighbour, u);
+                *self.degree.get_mut(&u).expect("node has a degree") += 1;
+                *self.degree.get_mut(&neighbour).expect("node has a degree") += 1;
+            }
+            self.decrement_degree(neighbour);
+        }
+        if self.degree[&u] >= self.k && self.freeze_worklist.remove(&u) {
+            self.spill_worklist.insert(u);
+        }
+    }
+
+    /// Give up coalescing the moves of `node`
+    fn freeze_moves(&mut self, node: ValueId) {
+        for index in self.node_moves(node) {
+            let (dest, src) = self.graph.moves[index];
+            let other = if self.alias(src) == self.alias(node) {
+                self.alias(dest)
+            } else {
+                self.alias(src)
+            };
+            self.active_moves.remove(&index);
+            self.worklist_moves.remove(&index);
+            if !self.move_related(other) && self.degree[&other] < self.k && self.freeze_worklist.remove(&other) {
+                self.simplify_worklist.insert(other);
+            }
+        }
+    }
+
+    /// Push the cheapest high-degree value, which may not get a register
+    fn select_spill(&mut self) {
+        let priority = |node: &ValueId| {
+            let cost = self.graph.spill_cost.get(node).copied().unwrap_or_default();
+            cost / self.degree[node].max(1) as f64
+        };
+        let node = self
+            .spill_worklist
+            .iter()
+            .copied()
+            .min_by(|a, b| priority(a).total_cmp(&priority(b)))
+            .expect("spill worklist is not empty");
+        self.spill_worklist.remove(&node);
+        self.simplify_worklist.insert(node);
+        self.freeze_moves(node);
+    }
+
+    fn assign_colors(&mut self) {
+        while let Some(node) = self.select_stack.pop() {
+            self.on_stack.remove(&node);
+            let mut free = vec![true; self.k];
+            for neighbour in self.graph.adjacent.get(&node).into_iter().flatten() {
+                if let Some(&color) = self.color.get(&self.alias(*neighbour)) {
+                    free[color] = false;
+                }
+            }
+            match free.iter().position(|&free| free) {
+                Some(color) => {
+                    self.color.insert(node, color);
+                }
+                None => {
+                    self.spilled.insert(node);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regalloc::{ALLOCATABLE, ALLOCATABLE_XMM};
+    use aurora_mir::{BasicBlock, BinOp, MirBuilder, Span, Value};
+    use aurora_types::{EffectSet, PrimitiveType, Type};
+
+    fn i64_ty() -> Type {
+        Type::Primitive(PrimitiveType::I64)
+    }
+
+    /// Defines `count` constants, then adds them all up
+    fn pressure(count: i64) -> Function {
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "pressure".to_string(), i64_ty(), EffectSet::PURE);
+        let param = builder.add_param(i64_ty(), Span::dummy());
+        let values: Vec<ValueId> = (0..count)
+            .map(|i| {
+                let value = Operand::Const(Constant::Int(i));
+                builder.build_binop(BinOp::Add, Operand::Value(param), value, i64_ty(), Span::dummy())
+            })
+            .collect();
+        let mut sum = Operand::Value(param);
+        for value in values {
+            let add = builder.build_binop(BinOp::Add, sum, Operand::Value(value), i64_ty(), Span::dummy());
+            sum = Operand::Value(add);
+        }
+        builder.build_return(Some(sum), Span::dummy());
+        builder.finish_function().unwrap()
+    }
+
+    /// Values live at the same time never share a location
+    fn assert_valid(alloc: &GraphColoringAllocator, func: &Function) {
+        let graph = Interference::build(func, &HashSet::new());
+        for &(a, b) in &graph.edges {
+            match (alloc.spill_offset(a), alloc.spill_offset(b)) {
+                (None, None) => assert_ne!(alloc.get_register(a), alloc.get_register(b), "v{} and v{}", a, b),
+                (Some(x), Some(y)) => assert_ne!(x, y, "v{} and v{}", a, b),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_coalesces_copies() {
+        // v1 = v0; v2 = v1; return v2
+        let mut func = Function::new(0, "copies".to_string(), i64_ty(), EffectSet::PURE);
+        for id in 0..3 {
+            func.add_value(Value { id, ty: i64_ty(), span: Span::dummy() });
+        }
+        func.params.push(0);
+        let mut block = BasicBlock::new(0);
+        for dest in 1..3 {
+            block.push(Instruction::Assign {
+                dest,
+                value: Operand::Value(dest - 1),
+                span: Span::dummy(),
+            });
+        }
+        block.push(Instruction::Return { value: Some(Operand::Value(2)), span: Span::dummy() });
+        func.add_block(block);
+
+        let mut alloc = GraphColoringAllocator::new();
+        alloc.allocate(&mut func);
+        assert_eq!(alloc.coalesced_moves(), 2);
+        assert_eq!(alloc.get_register(0), alloc.get_register(2));
+        assert_eq!(alloc.stack_size(), 0);
+    }
+
+    #[test]
+    fn test_phi_inputs_coalesced_across_loop() {
+        // i = 0; while i < n { i = i + 1 }; return i
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "count".to_string(), i64_ty(), EffectSet::PURE);
+        let n = builder.add_param(i64_ty(), Span::dummy());
+        let entry = builder.current_block().unwrap();
+        let header = builder.new_block();
+        let body = builder.new_block();
+        let exit = builder.new_block();
+        builder.build_jump(header, Span::dummy());
+        builder.set_block(body);
+        let placeholder = builder.new_value(i64_ty(), Span::dummy());
+        let next = builder.build_binop(BinOp::Add, Operand::Value(placeholder), Operand::Const(Constant::Int(1)), i64_ty(), Span::dummy());
+        builder.build_jump(header, Span::dummy());
+        builder.set_block(header);
+        let inputs = vec![(entry, Operand::Const(Constant::Int(0))), (body, Operand::Value(next))];
+        let i = builder.build_phi(inputs, i64_ty(), Span::dummy());
+        let cond = builder.build_binop(BinOp::Lt, Operand::Value(i), Operand::Value(n), Type::Primitive(PrimitiveType::Bool), Span::dummy());
+        builder.build_branch(Operand::Value(cond), body, exit, Span::dummy());
+        builder.set_block(exit);
+        builder.build_return(Some(Operand::Value(i)), Span::dummy());
+        let mut func = builder.finish_function().unwrap();
+        func.replace_uses(&HashMap::from([(placeholder, Operand::Value(i))]));
+
+        let mut alloc = GraphColoringAllocator::new();
+        alloc.allocate(&mut func);
+        assert_eq!(alloc.get_register(i), alloc.get_register(next));
+        assert_ne!(alloc.get_register(i), alloc.get_register(n));
+        assert_valid(&alloc, &func);
+    }
+
+    #[test]
+    fn test_high_pressure_spills_validly() {
+        let mut func = pressure(20);
+        let mut alloc = GraphColoringAllocator::new();
+        alloc.allocate(&mut func);
+
+        assert!(alloc.stack_size() > 0);
+        assert_valid(&alloc, &func);
+        assert!(!alloc.allocated_registers().contains(&Register::RSI));
+        assert!(!alloc.allocated_registers().contains(&Register::RDI));
+    }
+
+    #[test]
+    fn test_spilled_constants_are_rematerialized() {
+        // Twenty constants all live at once, summed at the end
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "constants".to_string(), i64_ty(), EffectSet::PURE);
+        let values: Vec<ValueId> = (0..20)
+            .map(|i| {
+                let value = builder.new_value(i64_ty(), Span::dummy());
+                builder.build_assign(value, Operand::Const(Constant::Int(i)), Span::dummy());
+                value
+            })
+            .collect();
+        let mut sum = Operand::Const(Constant::Int(0));
+        for value in values {
+            let add = builder.build_binop(BinOp::Add, sum, Operand::Value(value), i64_ty(), Span::dummy());
+            sum = Operand::Value(add);
+        }
+        builder.build_return(Some(sum), Span::dummy());
+        let mut func = builder.finish_function().unwrap();
+
+        let mut alloc = GraphColoringAllocator::new();
+        alloc.allocate(&mut func);
+        assert_eq!(alloc.stack_size(), 0);
+        assert!(alloc.rounds() > 1);
+        assert_valid(&alloc, &func);
+    }
+
+    #[test]
+    fn test_spilled_value_split_per_block() {
+        // v1 = v0 * v0; v2 = v1 + v0; return v2
+        let mut func = Function::new(0, "square".to_string(), i64_ty(), EffectSet::PURE);
+        for id in 0..3 {
+            func.add_value(Value { id, ty: i64_ty(), span: Span::dummy() });
+        }
+        func.params.push(0);
+        let mut block = BasicBlock::new(0);
+        block.push(Instruction::BinOp {
+            dest: 1,
+            op: BinOp::Mul,
+            lhs: Operand::Value(0),
+            rhs: Operand::Value(0),
+            span: Span::dummy(),
+        });
+        block.push(Instruction::BinOp {
+            dest: 2,
+            op: BinOp::Add,
+            lhs: Operand::Value(1),
+            rhs: Operand::Value(0),
+            span: Span::dummy(),
+        });
+        block.push(Instruction::Return { value: Some(Operand::Value(2)), span: Span::dummy() });
+        func.add_block(block);
+
+        let (mut split, mut temps) = (HashSet::new(), HashSet::new());
+        assert!(rewrite(&mut func, &[0], &mut split, &mut temps));
+        let insts = &func.blocks[&0].instructions;
+        assert_eq!(insts[0], Instruction::Assign { dest: 3, value: Operand::Value(0), span: Span::dummy() });
+        assert!(temps.contains(&3));
+        assert!(insts[1..].iter().all(|inst| !operand_values(inst).any(|value| value == 0)));
+
+        // Neither the value nor its reload is split again
+        assert!(!rewrite(&mut func, &[0, 3], &mut split, &mut temps));
+    }
+
+    #[test]
+    fn test_float_values_colored_with_xmm_registers() {
+        // More live floats than XMM registers, alongside integers
+        let f64_ty = Type::Primitive(PrimitiveType::F64);
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "mixed".to_string(), f64_ty.clone(), EffectSet::PURE);
+        let x = builder.add_param(f64_ty.clone(), Span::dummy());
+        let n = builder.add_param(i64_ty(), Span::dummy());
+        let floats: Vec<ValueId> = (0..20)
+            .map(|i| {
+                let value = Operand::Const(Constant::Float((i as f64).to_bits()));
+                builder.build_binop(BinOp::Add, Operand::Value(x), value, f64_ty.clone(), Span::dummy())
+            })
+            .collect();
+        let m = builder.build_binop(BinOp::Add, Operand::Value(n), Operand::Const(Constant::Int(1)), i64_ty(), Span::dummy());
+        let mut sum = Operand::Value(x);
+        for value in floats {
+            let add = builder.build_binop(BinOp::Add, sum, Operand::Value(value), f64_ty.clone(), Span::dummy());
+            sum = Operand::Value(add);
+        }
+        builder.build_return(Some(sum), Span::dummy());
+        let mut func = builder.finish_function().unwrap();
+
+        let mut alloc = GraphColoringAllocator::new();
+        alloc.allocate(&mut func);
+        assert_valid(&alloc, &func);
+        assert!(alloc.stack_size() > 0);
+        for (&id, value) in &func.values {
+            if alloc.is_spilled(id) {
+                continue;
+            }
+            let palette: &[Register] = match value.ty {
+                Type::Primitive(PrimitiveType::F64) => &ALLOCATABLE_XMM,
+                _ => &ALLOCATABLE,
+            };
+            assert!(palette.contains(&alloc.get_register(id)), "v{}", id);
+        }
+        assert!(ALLOCATABLE.contains(&alloc.get_register(m)));
+    }
+
+    #[test]
+    fn test_emitter_uses_spill_slots() {
+        use crate::air::{Instruction as AirInst, Operand as AirOp};
+        use crate::emit::AirEmitter;
+        use crate::regalloc::AllocatorKind;
+
+        let func = pressure(20);
+        let mut emitter = AirEmitter::with_allocator(AllocatorKind::GraphColoring);
+        let air_func = emitter.emit_function(&func);
+
+        assert!(air_func.frame_size > 0);
+        let stores = air_func
+            .instructions
+            .iter()
+            .filter(|inst| matches!(inst, AirInst::Mov { dest: AirOp::Mem { base: Register::RBP, .. }, .. }))
+            .count();
+        assert!(stores > 0);
+    }
+}
@@ -0,0 +1,278 @@
+//! Debug information attached to AIR functions
+//!
+//! The emitter records where each named source variable lives once
+//! registers are allocated, and the type it has. Line positions are carried
+//! in the instruction stream as [`Instruction::Loc`] markers.
+//!
+//! Call frame information is derived from the framed instructions: the
+//! prologue saves registers and moves the canonical frame address (CFA)
+//! from RSP to RBP, and the epilogue undoes it. Pushes and pops in the body
+//! only move the CFA while it is still computed from RSP.
+
+use crate::air::{AirFunction, Instruction, Operand, Register};
+use aurora_mir::LayoutTable;
+use aurora_types::{PrimitiveType, Type};
+use serde::{Deserialize, Serialize};
+
+/// Whether a variable is a parameter or a local
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VariableKind {
+    Parameter,
+    Local,
+}
+
+/// How a base type's bits are interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Encoding {
+    Signed,
+    Unsigned,
+    Float,
+    Boolean,
+    /// A Unicode scalar value
+    Utf,
+}
+
+/// Type of a variable, as described to the debugger
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DebugType {
+    Base { name: String, encoding: Encoding, size: u64 },
+    /// Pointer to a value of the given type, or to nothing known
+    Pointer(Option<Box<DebugType>>),
+    Struct { name: String, size: u64, fields: Vec<(String, u64, DebugType)> },
+    Array { elem: Box<DebugType>, len: u64 },
+}
+
+impl DebugType {
+    /// Describe `ty`, expanding aggregates from their layouts
+    pub fn of(ty: &Type, layouts: &LayoutTable) -> Self {
+        Self::describe(ty, layouts, true)
+    }
+
+    /// Describe `ty`; pointees are only expanded at the outermost pointer,
+    /// so that recursive types stay finite
+    fn describe(ty: &Type, layouts: &LayoutTable, expand: bool) -> Self {
+        let base = |name: &str, encoding, size| DebugType::Base {
+            name: name.to_string(),
+            encoding,
+            size,
+        };
+        match ty {
+            Type::Primitive(primitive) => match primitive {
+                PrimitiveType::I8 => base("i8", Encoding::Signed, 1),
+                PrimitiveType::I16 => base("i16", Encoding::Signed, 2),
+                PrimitiveType::I32 => base("i32", Encoding::Signed, 4),
+                PrimitiveType::I64 | PrimitiveType::I128 => base("i64", Encoding::Signed, 8),
+                PrimitiveType::ISize => base("isize", Encoding::Signed, 8),
+                PrimitiveType::U8 => base("u8", Encoding::Unsigned, 1),
+                PrimitiveType::U16 => base("u16", Encoding::Unsigned, 2),
+                PrimitiveType::U32 => base("u32", Encoding::Unsigned, 4),
+                PrimitiveType::U64 | PrimitiveType::U128 => base("u64", Encoding::Unsigned, 8),
+                PrimitiveType::USize => base("usize", Encoding::Unsigned, 8),
+                PrimitiveType::F32 => base("f32", Encoding::Float, 4),
+                PrimitiveType::F64 => base("f64", Encoding::Float, 8),
+                PrimitiveType::Bool => base("bool", Encoding::Boolean, 1),
+                PrimitiveType::Char => base("char", Encoding::Utf, 4),
+                // Strings are NUL-terminated bytes
+                PrimitiveType::Str => DebugType::Pointer(Some(Box::new(base("u8", Encoding::Unsigned, 1)))),
+            },
+            Type::Ref { inner, .. } | Type::Ptr { inner, .. } => DebugType::Pointer(
+                expand.then(|| Box::new(Self::describe(inner, layouts, false))),
+            ),
+            Type::Array { elem, size: Some(len) } => DebugType::Array {
+                elem: Box::new(Self::describe(elem, layouts, expand)),
+                len: *len as u64,
+            },
+            _ if layouts.is_aggregate(ty) => {
+                let fields = layouts
+                    .fields(ty)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|field| (field.name, field.offset, Self::describe(&field.ty, layouts, expand)))
+                    .collect();
+                DebugType::Struct {
+                    name: ty.to_string(),
+                    size: layouts.size_of(ty),
+                    fields,
+                }
+            }
+            _ => base(&ty.to_string(), Encoding::Signed, 8),
+        }
+    }
+}
+
+/// Where a variable's value is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VariableLocation {
+    Register(Register),
+    /// A stack slot at an offset from RBP
+    Frame(i32),
+}
+
+/// A named source variable of a function
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DebugVariable {
+    pub name: String,
+    pub kind: VariableKind,
+    pub ty: DebugType,
+    /// Where the value is kept throughout the function, if in one place
+    pub location: Option<VariableLocation>,
+    /// The location holds the variable's address rather than its value,
+    /// as for aggregates
+    pub by_address: bool,
+    /// Declaration line, 0 if unknown
+    pub line: u32,
+}
+
+/// A change to the rules for unwinding a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CfiOp {
+    /// The CFA is now this many bytes above the same register
+    DefCfaOffset(i32),
+    /// The CFA is now computed from this register, at the same offset
+    DefCfaRegister(Register),
+    /// The register's caller value is saved at this offset from the CFA
+    Offset(Register, i32),
+    /// The register holds its caller value again
+    Restore(Register),
+}
+
+/// Registers the System V ABI requires a function to preserve
+const CALLEE_SAVED: [Register; 6] = [
+    Register::RBX,
+    Register::RBP,
+    Register::R12,
+    Register::R13,
+    Register::R14,
+    Register::R15,
+];
+
+/// Call frame changes of a function, each paired with the index into
+/// [`AirFunction::framed_instructions`] of the instruction it follows
+pub fn call_frame_ops(func: &AirFunction) -> Vec<(usize, CfiOp)> {
+    let instructions = func.framed_instructions();
+    // `push rbp; mov rbp, rsp; sub rsp, N` and `mov rsp, rbp; pop rbp`
+    let (frame_setup, frame_teardown) = if func.frame_size > 0 { (3, 2) } else { (0, 0) };
+    let prologue_len = frame_setup + func.used_regs.len();
+    let epilogue_start = instructions.len() - 1 - func.used_regs.len() - frame_teardown;
+
+    let mut ops = Vec::new();
+    // Bytes from RSP up to the CFA; the call pushed the return address
+    let mut sp = 8;
+    let mut cfa_register = Register::RSP;
+    let mut cfa_offset = 8;
+    for (index, inst) in instructions.iter().enumerate() {
+        let in_prologue = index < prologue_len;
+        let in_epilogue = index >= epilogue_start;
+        match inst {
+            Instruction::Push { operand } => {
+                sp += 8;
+                if let (true, Operand::Reg(reg)) = (in_prologue, operand) {
+                    if CALLEE_SAVED.contains(reg) {
+                        ops.push((index, CfiOp::Offset(*reg, -sp)));
+                    }
+                }
+            }
+            Instruction::Pop { operand } => {
+                sp -= 8;
+                if let (true, Operand::Reg(reg)) = (in_epilogue, operand) {
+                    if CALLEE_SAVED.contains(reg) {
+                        ops.push((index, CfiOp::Restore(*reg)));
+                    }
+                }
+            }
+            Instruction::Sub { dest: Operand::Reg(Register::RSP), src: Operand::Imm(bytes) } => sp += *bytes as i32,
+            Instruction::Add { dest: Operand::Reg(Register::RSP), src: Operand::Imm(bytes) } => sp -= *bytes as i32,
+            Instruction::Mov { dest: Operand::Reg(Register::RBP), src: Operand::Reg(Register::RSP) } if in_prologue => {
+                cfa_register = Register::RBP;
+                ops.push((index, CfiOp::DefCfaRegister(Register::RBP)));
+            }
+            Instruction::Mov { dest: Operand::Reg(Register::RSP), src: Operand::Reg(Register::RBP) } if in_epilogue => {
+                cfa_register = Register::RSP;
+                sp = cfa_offset;
+                ops.push((index, CfiOp::DefCfaRegister(Register::RSP)));
+            }
+            _ => {}
+        }
+        if cfa_register == Register::RSP && sp != cfa_offset {
+            cfa_offset = sp;
+            ops.push((index, CfiOp::DefCfaOffset(sp)));
+        }
+    }
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aurora_types::StructDef;
+
+    #[test]
+    fn test_call_frame_ops_follow_prologue_and_epilogue() {
+        let mut func = AirFunction::new("f".to_string());
+        func.frame_size = 16;
+        func.used_regs = vec![Register::RBX];
+        func.push(Instruction::Push { operand: Operand::Reg(Register::RDI) });
+        func.push(Instruction::Pop { operand: Operand::Reg(Register::RBX) });
+
+        // push rbp; mov rbp, rsp; sub rsp, 16; push rbx; <body>; pop rbx;
+        // mov rsp, rbp; pop rbp; ret
+        assert_eq!(
+            call_frame_ops(&func),
+            vec![
+                (0, CfiOp::Offset(Register::RBP, -16)),
+                (0, CfiOp::DefCfaOffset(16)),
+                (1, CfiOp::DefCfaRegister(Register::RBP)),
+                (3, CfiOp::Offset(Register::RBX, -40)),
+                (6, CfiOp::Restore(Register::RBX)),
+                (7, CfiOp::DefCfaRegister(Register::RSP)),
+                (8, CfiOp::Restore(Register::RBP)),
+                (8, CfiOp::DefCfaOffset(8)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_frameless_pushes_move_the_cfa() {
+        let mut func = AirFunction::new("f".to_string());
+        func.push(Instruction::Push { operand: Operand::Reg(Register::RDI) });
+        func.push(Instruction::Pop { operand: Operand::Reg(Register::RAX) });
+        assert_eq!(
+            call_frame_ops(&func),
+            vec![(0, CfiOp::DefCfaOffset(16)), (1, CfiOp::DefCfaOffset(8))]
+        );
+    }
+
+    #[test]
+    fn test_debug_type_of_struct() {
+        let mut layouts = LayoutTable::new();
+        let i64_ty = Type::Primitive(PrimitiveType::I64);
+        layouts.add_struct(
+            "Node".to_string(),
+            StructDef {
+                params: vec![],
+                fields: vec![
+                    ("value".to_string(), i64_ty.clone()),
+                    (
+                        "next".to_string(),
+                        Type::Ptr {
+                            inner: Box::new(Type::Named { name: "Node".to_string(), args: vec![] }),
+                            mutable: false,
+                        },
+                    ),
+                ],
+            },
+        );
+
+        let node = Type::Named { name: "Node".to_string(), args: vec![] };
+        let DebugType::Struct { name, size, fields } = DebugType::of(&node, &layouts) else {
+            panic!("expected a struct");
+        };
+        assert_eq!((name.as_str(), size), ("Node", 16));
+        assert_eq!(fields[0], ("value".to_string(), 0, DebugType::of(&i64_ty, &layouts)));
+        // The pointee of the recursive field is expanded once
+        let DebugType::Pointer(Some(pointee)) = &fields[1].2 else {
+            panic!("expected a pointer");
+        };
+        assert!(matches!(&**pointee, DebugType::Struct { fields, .. } if fields[1].2 == DebugType::Pointer(None)));
+    }
+}
@@ -1,49 +1,202 @@
 //! AIR Emission - Lower MIR to AIR with proper calling conventions
-
+//!
+//! `f32` and `f64` values are computed with scalar SSE2 instructions in XMM
+//! registers. Float constants are placed in the data section and loaded
+//! from there.
+//!
+//! Calls follow the System V AMD64 ABI as classified in [`crate::abi`],
+//! which describes where Aurora's aggregate layout departs from C's.
+//! Aggregates are held by address in MIR: one passed in registers is stored
+//! to a slot in the callee's frame, one passed in memory is used in place in
+//! the caller's argument area, and one returned in registers is built in a
+//! local slot and loaded into the return registers.
+
+use crate::abi::{self, ArgLocation};
 use crate::air::*;
-use crate::regalloc::RegisterAllocator;
+use crate::debug::{DebugType, DebugVariable, VariableKind, VariableLocation};
+use crate::regalloc::{Allocator, AllocatorKind};
 use aurora_mir::{
-    BinOp, Constant, Function as MirFunction, Instruction as MirInst, Operand as MirOp, UnaryOp,
+    BinOp, BlockId, Constant, Function as MirFunction, Instruction as MirInst, LayoutTable,
+    Operand as MirOp, Span, UnaryOp, ValueId,
 };
-use std::collections::HashMap;
+use aurora_types::{PrimitiveType, Type};
+use std::collections::{HashMap, HashSet};
 
-/// System V ABI calling convention (x86_64)
-const ARG_REGISTERS: [Register; 6] = [
-    Register::RDI,
-    Register::RSI,
-    Register::RDX,
+/// Registers not preserved across calls (System V ABI)
+const CALLER_SAVED: [Register; 9] = [
+    Register::RAX,
     Register::RCX,
+    Register::RDX,
+    Register::RSI,
+    Register::RDI,
     Register::R8,
     Register::R9,
+    Register::R10,
+    Register::R11,
 ];
 
+/// Scratch register for addresses read from spill slots; never allocated
+const SCRATCH_PTR: Register = Register::RSI;
+
+/// Scratch register for values of spill slots; never allocated
+const SCRATCH: Register = Register::RDI;
+
+/// Register holding the address of a call's return slot while the
+/// aggregate returned in registers is stored to it
+const RETURN_SLOT_PTR: Register = Register::R11;
+
+/// Scratch register for float values of spill slots and constants; never allocated
+const SCRATCH_XMM: Register = Register::XMM15;
+
+/// Scratch register for the right operand of float instructions; never allocated
+const SCRATCH_XMM_RHS: Register = Register::XMM14;
+
+/// Width of a floating-point value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    Single,
+    Double,
+}
+
+impl Precision {
+    pub(crate) fn of(ty: &Type) -> Option<Self> {
+        match ty {
+            Type::Primitive(PrimitiveType::F32) => Some(Precision::Single),
+            Type::Primitive(PrimitiveType::F64) => Some(Precision::Double),
+            _ => None,
+        }
+    }
+
+    /// Bit pattern of `value` at this precision
+    pub(crate) fn bits(self, value: f64) -> u64 {
+        match self {
+            Precision::Single => u64::from((value as f32).to_bits()),
+            Precision::Double => value.to_bits(),
+        }
+    }
+
+    /// Move of a scalar of this precision, to or from memory
+    fn mov(self, dest: Operand, src: Operand) -> Instruction {
+        match self {
+            Precision::Single => Instruction::Movss { dest, src },
+            Precision::Double => Instruction::Movsd { dest, src },
+        }
+    }
+}
+
+/// Source of an argument register or stack word
+#[derive(Debug, Clone, Copy)]
+enum Piece<'a> {
+    /// A scalar argument, with its precision if it is a float
+    Scalar(&'a MirOp, Option<Precision>),
+    /// The eightbyte at a byte offset into an aggregate argument
+    Word(&'a MirOp, i32),
+}
+
+/// Parameter and return types of a function
+#[derive(Debug, Clone)]
+pub(crate) struct Signature {
+    /// Types of the MIR parameters, including a hidden return slot
+    pub(crate) params: Vec<Type>,
+    /// Return type
+    pub(crate) ret: Type,
+}
+
+impl Signature {
+    pub(crate) fn of(func: &MirFunction) -> Self {
+        let params = func
+            .params
+            .iter()
+            .map(|&param| match func.value(param) {
+                Some(value) => value.ty.clone(),
+                None => Type::Primitive(PrimitiveType::I64),
+            })
+            .collect();
+        Self {
+            params,
+            ret: func.ret_ty.clone(),
+        }
+    }
+}
+
 /// AIR emitter with full calling convention support
 pub struct AirEmitter {
-    regalloc: RegisterAllocator,
+    regalloc: Box<dyn Allocator>,
+    /// Register allocation algorithm used for each function
+    allocator: AllocatorKind,
     string_constants: HashMap<String, String>,
     next_string_id: usize,
+    /// Float constants with their labels, as bits at their precision
+    float_constants: Vec<(String, Precision, u64)>,
+    /// Precision of the current function's float values
+    floats: HashMap<ValueId, Precision>,
+    /// Layouts used to size stack slots and classify arguments
+    layouts: LayoutTable,
+    /// Signatures of the module's functions, keyed by symbol
+    signatures: HashMap<String, Signature>,
+    /// Bytes of stack slots allocated in the current frame
+    slot_bytes: u32,
+    /// MIR block whose instructions are being emitted
+    current_block: BlockId,
+    /// Label of the current function's epilogue
+    return_label: String,
+    /// Record source positions and variable locations
+    debug_info: bool,
+    /// Source line and column of the last position emitted
+    position: (u32, u32),
 }
 
 impl AirEmitter {
     pub fn new() -> Self {
+        Self::with_allocator(AllocatorKind::default())
+    }
+
+    /// Create an emitter using the given register allocator
+    pub fn with_allocator(allocator: AllocatorKind) -> Self {
         Self {
-            regalloc: RegisterAllocator::new(),
+            regalloc: allocator.create(),
+            allocator,
             string_constants: HashMap::new(),
             next_string_id: 0,
+            float_constants: Vec::new(),
+            floats: HashMap::new(),
+            layouts: LayoutTable::new(),
+            signatures: HashMap::new(),
+            slot_bytes: 0,
+            current_block: 0,
+            return_label: String::new(),
+            debug_info: false,
+            position: (0, 0),
         }
     }
 
+    /// Record source positions and variable locations in the emitted functions
+    pub fn set_debug_info(&mut self, enabled: bool) {
+        self.debug_info = enabled;
+    }
+
     /// Emit AIR module from MIR module
     pub fn emit_module(&mut self, mir_module: &aurora_mir::MirModule) -> AirModule {
         let mut air_module = AirModule::new("main".to_string());
-
-        for func in mir_module.functions.values() {
+        self.layouts = mir_module.layouts.clone();
+        self.signatures = mir_module
+            .functions
+            .values()
+            .map(|func| (func.name.clone(), Signature::of(func)))
+            .collect();
+
+        // Emit in a fixed order so the output is deterministic
+        let mut functions: Vec<_> = mir_module.functions.values().collect();
+        functions.sort_by_key(|func| func.id);
+        for func in functions {
             let air_func = self.emit_function(func);
             air_module.add_function(air_func);
         }
 
         // Add string constants to data section with null terminators
-        for (label, content) in &self.string_constants {
+        let mut strings: Vec<_> = self.string_constants.iter().collect();
+        strings.sort();
+        for (label, content) in strings {
             let mut bytes = content.as_bytes().to_vec();
             bytes.push(0); // Add null terminator for C strings
             air_module.data.push(DataDirective {
@@ -52,6 +205,17 @@ impl AirEmitter {
                 value: bytes,
             });
         }
+        for (label, precision, bits) in &self.float_constants {
+            let (kind, width) = match precision {
+                Precision::Single => (DataKind::Dword, 4),
+                Precision::Double => (DataKind::Qword, 8),
+            };
+            air_module.data.push(DataDirective {
+                label: label.clone(),
+                kind,
+                value: bits.to_le_bytes()[..width].to_vec(),
+            });
+        }
 
         air_module
     }
@@ -60,14 +224,30 @@ impl AirEmitter {
     pub fn emit_function(&mut self, mir_func: &MirFunction) -> AirFunction {
         let mut air_func = AirFunction::new(mir_func.name.clone());
 
-        // Reset register allocator for each function
-        self.regalloc = RegisterAllocator::new();
-
-        // Allocate registers
-        self.regalloc.allocate(mir_func);
+        // Reset register allocator and stack slots for each function
+        self.regalloc = self.allocator.create();
+        self.slot_bytes = 0;
+        self.return_label = format!(".Lret{}", mir_func.id);
+        self.position = (0, 0);
+
+        // Allocate registers, emitting the function as the allocator rewrote it
+        let mut mir_func = mir_func.clone();
+        self.regalloc.allocate(&mut mir_func);
+        let mir_func = &mir_func;
+        self.floats = mir_func
+            .values
+            .iter()
+            .filter_map(|(&id, value)| Precision::of(&value.ty).map(|precision| (id, precision)))
+            .collect();
+
+        // Parameter moves belong to the declaration
+        if self.debug_info {
+            air_func.line = mir_func.span.line;
+            self.emit_position(mir_func.span, &mut air_func);
+        }
 
         // Emit function parameters (following System V ABI)
-        self.emit_function_prologue(&mut air_func, mir_func);
+        let reads_stack_args = self.emit_function_prologue(&mut air_func, mir_func);
 
         // Emit instructions for each block (in order)
         let mut block_ids: Vec<_> = mir_func.blocks.keys().copied().collect();
@@ -75,6 +255,8 @@ impl AirEmitter {
 
         for block_id in block_ids {
             if let Some(block) = mir_func.block(block_id) {
+                self.current_block = block_id;
+
                 // Emit block label
                 air_func.push(Instruction::Label {
                     name: format!(".L{}", block_id),
@@ -87,74 +269,268 @@ impl AirEmitter {
             }
         }
 
-        air_func.frame_size = self.regalloc.stack_size();
+        // Returns jump to the epilogue, which follows the last block
+        let returns_last = matches!(
+            air_func.instructions.last(),
+            Some(Instruction::Jmp { target }) if *target == self.return_label
+        );
+        if returns_last {
+            air_func.instructions.pop();
+        }
+        let returns_early = air_func
+            .instructions
+            .iter()
+            .any(|inst| matches!(inst, Instruction::Jmp { target } if *target == self.return_label));
+        if returns_early {
+            air_func.push(Instruction::Label {
+                name: self.return_label.clone(),
+            });
+        }
+
+        // Keep the stack 16-byte aligned for calls: the frame's `push rbp`
+        // realigns it, and padding covers an odd number of callee-saved
+        // registers pushed below the frame. Stack arguments are addressed
+        // from RBP, so reading them also needs a frame.
         air_func.used_regs = self.regalloc.callee_saved_registers();
+        let mut frame_size = (self.regalloc.stack_size() + self.slot_bytes).next_multiple_of(16);
+        let has_calls = air_func.instructions.iter().any(|inst| matches!(inst, Instruction::Call { .. }));
+        if (has_calls || reads_stack_args) && frame_size == 0 {
+            frame_size = 16;
+        }
+        if air_func.used_regs.len() % 2 == 1 {
+            frame_size += 8;
+        }
+        air_func.frame_size = frame_size;
+        if self.debug_info {
+            air_func.debug_vars = self.debug_variables(mir_func);
+        }
         air_func
     }
 
+    /// Mark the instructions that follow as coming from `span`
+    fn emit_position(&mut self, span: Span, air_func: &mut AirFunction) {
+        let position = (span.line, span.column);
+        if span.line > 0 && position != self.position {
+            self.position = position;
+            air_func.push(Instruction::Loc {
+                line: span.line,
+                column: span.column,
+            });
+        }
+    }
+
+    /// The function's named variables and where they are kept
+    ///
+    /// A variable has a location only if every value it holds was given the
+    /// same one. Values removed by optimization are skipped.
+    fn debug_variables(&self, mir_func: &MirFunction) -> Vec<DebugVariable> {
+        let defined: HashSet<ValueId> = mir_func
+            .blocks
+            .values()
+            .flat_map(|block| block.instructions.iter().filter_map(|inst| inst.dest()))
+            .chain(mir_func.params.iter().copied())
+            .filter(|&value| self.regalloc.has_location(value))
+            .collect();
+
+        mir_func
+            .debug_vars
+            .iter()
+            .filter_map(|var| {
+                let first = *var.values.first()?;
+                let ty = &var.ty;
+                // Values no longer of the declared type, as when an aggregate
+                // was split into scalars, do not describe the variable
+                let mut locations = var
+                    .values
+                    .iter()
+                    .filter(|value| defined.contains(value))
+                    .map(|&value| match self.location(value) {
+                        _ if mir_func.value(value).is_none_or(|value| value.ty != *ty) => None,
+                        Operand::Reg(reg) => Some(VariableLocation::Register(reg)),
+                        Operand::Mem { offset, .. } => Some(VariableLocation::Frame(offset)),
+                        _ => None,
+                    });
+                let location = locations.next().flatten().filter(|&location| locations.all(|other| other == Some(location)));
+                let kind = if mir_func.params.contains(&first) {
+                    VariableKind::Parameter
+                } else {
+                    VariableKind::Local
+                };
+                Some(DebugVariable {
+                    name: var.name.clone(),
+                    kind,
+                    ty: DebugType::of(ty, &self.layouts),
+                    location,
+                    by_address: self.layouts.is_aggregate(ty),
+                    line: var.span.line,
+                })
+            })
+            .collect()
+    }
+
     /// Emit function prologue with parameter handling
-    fn emit_function_prologue(&self, air_func: &mut AirFunction, mir_func: &MirFunction) {
-        // Move parameters from argument registers to allocated locations
-        for (i, &param_id) in mir_func.params.iter().enumerate() {
-            if i < ARG_REGISTERS.len() {
-                let dest_reg = self.regalloc.get_register(param_id);
-                air_func.push(Instruction::Mov {
-                    dest: Operand::Reg(dest_reg),
-                    src: Operand::Reg(ARG_REGISTERS[i]),
-                });
+    ///
+    /// Returns whether parameters are read from the caller's stack.
+    fn emit_function_prologue(&mut self, air_func: &mut AirFunction, mir_func: &MirFunction) -> bool {
+        let mut signature = Signature::of(mir_func);
+        let mut params = mir_func.params.clone();
+
+        // An aggregate returned in registers is built in a local slot rather
+        // than one passed by the caller
+        let mut return_slot = None;
+        if self.return_registers(&signature.ret).is_some() && !params.is_empty() {
+            return_slot = Some(params.remove(0));
+            signature.params.remove(0);
+        }
+
+        // Aggregates passed in registers are stored to frame slots before any
+        // argument register is overwritten
+        let (locations, stack_words) = abi::assign(&signature.params, &self.layouts);
+        let mut int_params = Vec::new();
+        let mut float_params = Vec::new();
+        let mut stack_params = Vec::new();
+        let mut addresses = Vec::new();
+        for ((&param_id, ty), location) in params.iter().zip(&signature.params).zip(locations) {
+            let aggregate = self.layouts.is_aggregate(ty);
+            match location {
+                ArgLocation::Registers(registers) if aggregate => {
+                    let offset = self.frame_slot(ty);
+                    for (i, &reg) in registers.iter().enumerate() {
+                        let dest = Operand::Mem {
+                            base: Register::RBP,
+                            offset: offset + (i * 8) as i32,
+                        };
+                        self.emit_move(dest, Operand::Reg(reg), air_func);
+                    }
+                    addresses.push((param_id, offset));
+                }
+                ArgLocation::Registers(registers) => match registers[..] {
+                    [reg] if reg.is_xmm() => float_params.push((param_id, reg)),
+                    [reg] => int_params.push((param_id, reg)),
+                    _ => {}
+                },
+                ArgLocation::Stack { offset, .. } => {
+                    let offset = 16 + (offset * 8) as i32;
+                    if aggregate {
+                        addresses.push((param_id, offset));
+                    } else {
+                        stack_params.push((param_id, offset));
+                    }
+                }
+            }
+        }
+
+        // Move parameters from argument registers to allocated locations. With
+        // several register parameters the moves go through the stack, since a
+        // parameter may be allocated to another parameter's argument register.
+        for params in [int_params, float_params] {
+            if let [(param_id, reg)] = params[..] {
+                self.emit_move(self.location(param_id), Operand::Reg(reg), air_func);
             } else {
-                // Parameters beyond 6 are passed on stack
-                let stack_offset = 16 + ((i - 6) * 8) as i32;
-                let dest_reg = self.regalloc.get_register(param_id);
-                air_func.push(Instruction::Mov {
-                    dest: Operand::Reg(dest_reg),
-                    src: Operand::Mem {
-                        base: Register::RBP,
-                        offset: stack_offset,
-                    },
-                });
+                for &(_, reg) in &params {
+                    self.emit_push(Operand::Reg(reg), air_func);
+                }
+                for &(param_id, _) in params.iter().rev() {
+                    self.emit_pop(self.location(param_id), air_func);
+                }
             }
         }
+
+        for (param_id, offset) in stack_params {
+            let src = Operand::Mem {
+                base: Register::RBP,
+                offset,
+            };
+            self.emit_move(self.location(param_id), src, air_func);
+        }
+
+        if let Some(slot) = return_slot {
+            addresses.push((slot, self.frame_slot(&signature.ret)));
+        }
+        for (param_id, offset) in addresses {
+            let dest_reg = self.dest_register(param_id);
+            air_func.push(Instruction::Lea {
+                dest: Operand::Reg(dest_reg),
+                src: Operand::Mem {
+                    base: Register::RBP,
+                    offset,
+                },
+            });
+            self.store_dest(param_id, air_func);
+        }
+
+        stack_words > 0
+    }
+
+    /// Reserve a slot for a value of type `ty` in the frame, below the spill
+    /// area, returning its offset from RBP
+    fn frame_slot(&mut self, ty: &Type) -> i32 {
+        let size = self.layouts.size_of(ty).max(1).next_multiple_of(8) as u32;
+        self.slot_bytes += size;
+        -((self.regalloc.stack_size() + self.slot_bytes) as i32)
+    }
+
+    /// Registers an aggregate of type `ty` is returned in, or `None` for
+    /// scalars and aggregates returned through a hidden pointer
+    fn return_registers(&self, ty: &Type) -> Option<Vec<Register>> {
+        if self.layouts.is_aggregate(ty) {
+            abi::return_registers(ty, &self.layouts)
+        } else {
+            None
+        }
     }
 
     fn emit_instruction(
         &mut self,
         inst: &MirInst,
         air_func: &mut AirFunction,
-        _mir_func: &MirFunction,
+        mir_func: &MirFunction,
     ) {
         air_func.push(Instruction::Comment {
             text: format!("MIR: {:?}", inst).chars().take(60).collect(),
         });
+        if self.debug_info {
+            self.emit_position(inst.span(), air_func);
+        }
 
         match inst {
-            MirInst::Assign { dest, value, .. } => {
-                let dest_reg = self.regalloc.get_register(*dest);
-                let src_op = self.operand_to_air(value);
-
-                if let Operand::Reg(src_reg) = src_op {
-                    if src_reg != dest_reg {
-                        air_func.push(Instruction::Mov {
-                            dest: Operand::Reg(dest_reg),
-                            src: src_op,
-                        });
-                    }
-                } else {
-                    air_func.push(Instruction::Mov {
-                        dest: Operand::Reg(dest_reg),
-                        src: src_op,
-                    });
-                }
-            }
+            MirInst::Assign { dest, value, .. } => self.emit_assign(*dest, value, air_func),
 
             MirInst::BinOp {
                 dest, op, lhs, rhs, ..
-            } => {
-                self.emit_binop(dest, op, lhs, rhs, air_func);
+            } => match self.floats.get(dest).copied().or_else(|| self.float_precision(&[lhs, rhs])) {
+                Some(precision) => self.emit_float_binop(*dest, op, lhs, rhs, precision, air_func),
+                None => self.emit_binop(dest, op, lhs, rhs, air_func),
+            },
+
+            MirInst::UnaryOp {
+                dest,
+                op: UnaryOp::Neg,
+                value,
+                ..
+            } if self.floats.contains_key(dest) => {
+                // Flip the sign bit
+                let precision = self.floats[dest];
+                let dest_reg = self.dest_register(*dest);
+                let val_op = self.float_operand(value, precision, dest_reg, air_func);
+                self.emit_move(Operand::Reg(dest_reg), val_op, air_func);
+                air_func.push(Instruction::Mov {
+                    dest: Operand::Reg(SCRATCH_PTR),
+                    src: Operand::Imm(precision.bits(-0.0) as i64),
+                });
+                air_func.push(Instruction::Movq {
+                    dest: Operand::Reg(SCRATCH_XMM_RHS),
+                    src: Operand::Reg(SCRATCH_PTR),
+                });
+                air_func.push(Instruction::Xorpd {
+                    dest: Operand::Reg(dest_reg),
+                    src: Operand::Reg(SCRATCH_XMM_RHS),
+                });
+                self.store_dest(*dest, air_func);
             }
 
             MirInst::UnaryOp { dest, op, value, .. } => {
-                let dest_reg = self.regalloc.get_register(*dest);
+                let dest_reg = self.dest_register(*dest);
                 let val_op = self.operand_to_air(value);
 
                 // Load value into dest
@@ -182,6 +558,7 @@ impl AirEmitter {
                         });
                     }
                 }
+                self.store_dest(*dest, air_func);
             }
 
             MirInst::Call { dest, func, args, .. } => {
@@ -189,12 +566,30 @@ impl AirEmitter {
             }
 
             MirInst::Return { value, .. } => {
-                if let Some(val) = value {
-                    let val_op = self.operand_to_air(val);
-                    air_func.push(Instruction::Mov {
-                        dest: Operand::Reg(Register::RAX),
-                        src: val_op,
-                    });
+                if let (Some(val), Some(registers)) = (value, self.return_registers(&mir_func.ret_ty)) {
+                    // Load a small aggregate's eightbytes from its slot
+                    let slot = self.operand_to_air(val);
+                    self.emit_move(Operand::Reg(SCRATCH_PTR), slot, air_func);
+                    for (i, reg) in registers.into_iter().enumerate() {
+                        let src = Operand::Mem {
+                            base: SCRATCH_PTR,
+                            offset: (i * 8) as i32,
+                        };
+                        self.emit_move(Operand::Reg(reg), src, air_func);
+                    }
+                } else if let Some(val) = value {
+                    // Floats are returned in XMM0
+                    let val_op = match Precision::of(&mir_func.ret_ty) {
+                        Some(precision) => {
+                            let val_op = self.float_operand(val, precision, Register::XMM0, air_func);
+                            self.emit_move(Operand::Reg(Register::XMM0), val_op, air_func);
+                            None
+                        }
+                        None => Some(self.operand_to_air(val)),
+                    };
+                    if let Some(val_op) = val_op {
+                        self.emit_move(Operand::Reg(Register::RAX), val_op, air_func);
+                    }
                 } else {
                     // Return unit (0)
                     air_func.push(Instruction::Xor {
@@ -202,7 +597,9 @@ impl AirEmitter {
                         src: Operand::Reg(Register::RAX),
                     });
                 }
-                // Note: actual ret is added by function epilogue
+                air_func.push(Instruction::Jmp {
+                    target: self.return_label.clone(),
+                });
             }
 
             MirInst::Branch {
@@ -215,12 +612,14 @@ impl AirEmitter {
                 match cond {
                     MirOp::Const(Constant::Bool(true)) => {
                         // Always branch to then
+                        self.emit_phi_moves(*then_block, air_func, mir_func);
                         air_func.push(Instruction::Jmp {
                             target: format!(".L{}", then_block),
                         });
                     }
                     MirOp::Const(Constant::Bool(false)) => {
                         // Always branch to else
+                        self.emit_phi_moves(*else_block, air_func, mir_func);
                         air_func.push(Instruction::Jmp {
                             target: format!(".L{}", else_block),
                         });
@@ -228,25 +627,45 @@ impl AirEmitter {
                     _ => {
                         // Runtime condition - need to test it
                         let cond_op = self.operand_to_air(cond);
-                        // Move to a register if it's an immediate
-                        let cond_reg = match &cond_op {
+                        match cond_op {
+                            // Move to a register if it's an immediate
                             Operand::Imm(_) => {
-                                let reg = Register::RAX;
                                 air_func.push(Instruction::Mov {
-                                    dest: Operand::Reg(reg),
+                                    dest: Operand::Reg(SCRATCH),
                                     src: cond_op,
                                 });
-                                Operand::Reg(reg)
+                                air_func.push(Instruction::Test {
+                                    left: Operand::Reg(SCRATCH),
+                                    right: Operand::Reg(SCRATCH),
+                                });
                             }
-                            _ => cond_op,
-                        };
-                        air_func.push(Instruction::Test {
-                            left: cond_reg.clone(),
-                            right: cond_reg,
-                        });
-                        air_func.push(Instruction::Jne {
-                            target: format!(".L{}", then_block),
-                        });
+                            Operand::Mem { .. } => air_func.push(Instruction::Cmp {
+                                left: cond_op,
+                                right: Operand::Imm(0),
+                            }),
+                            cond_reg => air_func.push(Instruction::Test {
+                                left: cond_reg.clone(),
+                                right: cond_reg,
+                            }),
+                        }
+                        // An edge into phis gets its own moves, so the taken
+                        // branch skips over the moves of the other edge
+                        if self.has_phi_inputs(*then_block, mir_func) {
+                            let else_edge = format!(".L{}_{}", self.current_block, else_block);
+                            air_func.push(Instruction::Je {
+                                target: else_edge.clone(),
+                            });
+                            self.emit_phi_moves(*then_block, air_func, mir_func);
+                            air_func.push(Instruction::Jmp {
+                                target: format!(".L{}", then_block),
+                            });
+                            air_func.push(Instruction::Label { name: else_edge });
+                        } else {
+                            air_func.push(Instruction::Jne {
+                                target: format!(".L{}", then_block),
+                            });
+                        }
+                        self.emit_phi_moves(*else_block, air_func, mir_func);
                         air_func.push(Instruction::Jmp {
                             target: format!(".L{}", else_block),
                         });
@@ -255,90 +674,236 @@ impl AirEmitter {
             }
 
             MirInst::Jump { target, .. } => {
+                self.emit_phi_moves(*target, air_func, mir_func);
                 air_func.push(Instruction::Jmp {
                     target: format!(".L{}", target),
                 });
             }
 
             MirInst::Load { dest, ptr, .. } => {
-                let dest_reg = self.regalloc.get_register(*dest);
+                let dest_reg = self.dest_register(*dest);
                 let ptr_op = self.operand_to_air(ptr);
 
-                if let Operand::Reg(ptr_reg) = ptr_op {
-                    air_func.push(Instruction::Mov {
-                        dest: Operand::Reg(dest_reg),
-                        src: Operand::Mem {
-                            base: ptr_reg,
-                            offset: 0,
+                if let Some(ptr_reg) = self.address_register(ptr_op, SCRATCH_PTR, air_func) {
+                    let src = Operand::Mem {
+                        base: ptr_reg,
+                        offset: 0,
+                    };
+                    air_func.push(match self.floats.get(dest) {
+                        Some(precision) => precision.mov(Operand::Reg(dest_reg), src),
+                        None => Instruction::Mov {
+                            dest: Operand::Reg(dest_reg),
+                            src,
                         },
                     });
+                    self.store_dest(*dest, air_func);
                 }
             }
 
             MirInst::Store { ptr, value, .. } => {
+                // A float is stored at the width of the pointed-to type
+                let stored = match ptr {
+                    MirOp::Value(ptr) => match mir_func.value(*ptr).map(|value| &value.ty) {
+                        Some(Type::Ptr { inner, .. } | Type::Ref { inner, .. }) => Precision::of(inner),
+                        _ => None,
+                    },
+                    MirOp::Const(_) => None,
+                };
+                if let Some(precision) = stored.or_else(|| self.float_precision(&[value])) {
+                    let val_op = self.float_operand(value, precision, SCRATCH_XMM, air_func);
+                    let val_reg = match val_op {
+                        Operand::Reg(reg) if reg.is_xmm() => reg,
+                        val_op => {
+                            air_func.push(precision.mov(Operand::Reg(SCRATCH_XMM), val_op));
+                            SCRATCH_XMM
+                        }
+                    };
+                    let ptr_op = self.operand_to_air(ptr);
+                    if let Some(ptr_reg) = self.address_register(ptr_op, SCRATCH_PTR, air_func) {
+                        let dest = Operand::Mem {
+                            base: ptr_reg,
+                            offset: 0,
+                        };
+                        air_func.push(precision.mov(dest, Operand::Reg(val_reg)));
+                    }
+                    return;
+                }
+
                 let val_op = self.operand_to_air(value);
                 let ptr_op = self.operand_to_air(ptr);
 
-                if let Operand::Reg(ptr_reg) = ptr_op {
-                    air_func.push(Instruction::Mov {
-                        dest: Operand::Mem {
-                            base: ptr_reg,
-                            offset: 0,
-                        },
-                        src: val_op,
-                    });
+                if let Some(ptr_reg) = self.address_register(ptr_op, SCRATCH_PTR, air_func) {
+                    let dest = Operand::Mem {
+                        base: ptr_reg,
+                        offset: 0,
+                    };
+                    self.emit_move(dest, val_op, air_func);
                 }
             }
 
-            MirInst::Alloca { dest, .. } => {
-                // Allocate on stack
-                let dest_reg = self.regalloc.get_register(*dest);
-                air_func.push(Instruction::Sub {
-                    dest: Operand::Reg(Register::RSP),
-                    src: Operand::Imm(8),
-                });
-                air_func.push(Instruction::Mov {
+            MirInst::Alloca { dest, ty, .. } => {
+                let offset = self.frame_slot(ty);
+                let dest_reg = self.dest_register(*dest);
+                air_func.push(Instruction::Lea {
                     dest: Operand::Reg(dest_reg),
-                    src: Operand::Reg(Register::RSP),
+                    src: Operand::Mem {
+                        base: Register::RBP,
+                        offset,
+                    },
                 });
+                self.store_dest(*dest, air_func);
             }
 
-            MirInst::Cast { dest, value, .. } => {
-                let dest_reg = self.regalloc.get_register(*dest);
-                let val_op = self.operand_to_air(value);
-                air_func.push(Instruction::Mov {
-                    dest: Operand::Reg(dest_reg),
-                    src: val_op,
-                });
-            }
+            MirInst::Cast { dest, value, .. } => self.emit_cast(*dest, value, air_func),
 
             MirInst::GetElement { dest, base, index, .. } => {
-                let dest_reg = self.regalloc.get_register(*dest);
+                let dest_reg = self.dest_register(*dest);
                 let base_op = self.operand_to_air(base);
                 let index_op = self.operand_to_air(index);
 
-                // Calculate address: base + index * 8
-                if let (Operand::Reg(base_reg), Operand::Imm(idx)) = (base_op, index_op) {
-                    air_func.push(Instruction::Lea {
-                        dest: Operand::Reg(dest_reg),
-                        src: Operand::Mem {
-                            base: base_reg,
-                            offset: (idx * 8) as i32,
-                        },
-                    });
+                // Spilled operands are loaded into the scratch registers
+                let base_op = self.address_register(base_op, SCRATCH_PTR, air_func).map(Operand::Reg);
+                let index_op = match index_op {
+                    index @ Operand::Mem { .. } => self.address_register(index, SCRATCH, air_func).map(Operand::Reg),
+                    index => Some(index),
+                };
+
+                // Calculate address: base + byte offset
+                match (base_op, index_op) {
+                    (Some(Operand::Reg(base_reg)), Some(Operand::Imm(offset))) => {
+                        air_func.push(Instruction::Lea {
+                            dest: Operand::Reg(dest_reg),
+                            src: Operand::Mem {
+                                base: base_reg,
+                                offset: offset as i32,
+                            },
+                        });
+                    }
+                    (Some(Operand::Reg(base_reg)), Some(Operand::Reg(index_reg))) => {
+                        air_func.push(Instruction::Lea {
+                            dest: Operand::Reg(dest_reg),
+                            src: Operand::MemComplex {
+                                base: Some(base_reg),
+                                index: Some(index_reg),
+                                scale: 1,
+                                offset: 0,
+                            },
+                        });
+                    }
+                    _ => {}
                 }
+                self.store_dest(*dest, air_func);
             }
 
             MirInst::Phi { dest, inputs, .. } => {
-                // Phi nodes are handled during SSA elimination
-                let dest_reg = self.regalloc.get_register(*dest);
+                // Values are moved into place by the jumps to this block
                 air_func.push(Instruction::Comment {
-                    text: format!("Phi node: {} = {:?}", dest_reg, inputs),
+                    text: format!("Phi node: {} = {:?}", self.location(*dest), inputs),
                 });
             }
         }
     }
 
+    /// Whether `target` has phis taking an input from the current block
+    fn has_phi_inputs(&self, target: BlockId, mir_func: &MirFunction) -> bool {
+        mir_func.block(target).is_some_and(|block| {
+            block.instructions.iter().any(|inst| {
+                matches!(inst, MirInst::Phi { inputs, .. }
+                    if inputs.iter().any(|(pred, _)| *pred == self.current_block))
+            })
+        })
+    }
+
+    /// Move the inputs of `target`'s phis for an edge from the current block
+    ///
+    /// The moves happen in parallel: register and spilled inputs go through
+    /// the stack, as a phi may be allocated to another phi's input location,
+    /// and constants are moved last.
+    fn emit_phi_moves(&mut self, target: BlockId, air_func: &mut AirFunction, mir_func: &MirFunction) {
+        let Some(block) = mir_func.block(target) else {
+            return;
+        };
+
+        let mut registers = Vec::new();
+        let mut constants = Vec::new();
+        for inst in &block.instructions {
+            let MirInst::Phi { dest, inputs, .. } = inst else {
+                continue;
+            };
+            if let Some((_, input)) = inputs.iter().find(|(pred, _)| *pred == self.current_block) {
+                match input {
+                    MirOp::Value(value) => {
+                        let dest = self.location(*dest);
+                        let src = self.location(*value);
+                        if dest != src {
+                            registers.push((dest, src));
+                        }
+                    }
+                    MirOp::Const(_) => constants.push((*dest, input.clone())),
+                }
+            }
+        }
+
+        if let [(dest, src)] = registers.as_slice() {
+            self.emit_move(dest.clone(), src.clone(), air_func);
+        } else {
+            for (_, src) in &registers {
+                self.emit_push(src.clone(), air_func);
+            }
+            for (dest, _) in registers.iter().rev() {
+                self.emit_pop(dest.clone(), air_func);
+            }
+        }
+        for (dest, src) in constants {
+            self.emit_assign(dest, &src, air_func);
+        }
+    }
+
+    /// Divide `dest` by `divisor`, leaving the quotient (RAX) or remainder (RDX) in `dest`
+    ///
+    /// `idiv` divides RDX:RAX and overwrites both, so values allocated to them
+    /// are saved around it. The divisor is read from the stack, which also
+    /// covers immediates and divisors living in RAX or RDX.
+    fn emit_division(&mut self, dest: Register, divisor: Operand, result: Register, air_func: &mut AirFunction) {
+        let saved: Vec<Register> = [Register::RAX, Register::RDX]
+            .into_iter()
+            .filter(|reg| *reg != dest)
+            .collect();
+        for reg in &saved {
+            air_func.push(Instruction::Push { operand: Operand::Reg(*reg) });
+        }
+        air_func.push(Instruction::Push { operand: divisor });
+
+        // Sign-extend the dividend into RDX:RAX
+        air_func.push(Instruction::Mov {
+            dest: Operand::Reg(Register::RAX),
+            src: Operand::Reg(dest),
+        });
+        air_func.push(Instruction::Mov {
+            dest: Operand::Reg(Register::RDX),
+            src: Operand::Reg(Register::RAX),
+        });
+        air_func.push(Instruction::Sar {
+            dest: Operand::Reg(Register::RDX),
+            count: Operand::Imm(63),
+        });
+        air_func.push(Instruction::Idiv {
+            operand: Operand::Mem { base: Register::RSP, offset: 0 },
+        });
+        air_func.push(Instruction::Mov {
+            dest: Operand::Reg(dest),
+            src: Operand::Reg(result),
+        });
+
+        air_func.push(Instruction::Add {
+            dest: Operand::Reg(Register::RSP),
+            src: Operand::Imm(8),
+        });
+        for reg in saved.iter().rev() {
+            air_func.push(Instruction::Pop { operand: Operand::Reg(*reg) });
+        }
+    }
+
     fn emit_binop(
         &mut self,
         dest: &u32,
@@ -347,7 +912,7 @@ impl AirEmitter {
         rhs: &MirOp,
         air_func: &mut AirFunction,
     ) {
-        let dest_reg = self.regalloc.get_register(*dest);
+        let dest_reg = self.dest_register(*dest);
         let lhs_op = self.operand_to_air(lhs);
         let rhs_op = self.operand_to_air(rhs);
 
@@ -377,38 +942,8 @@ impl AirEmitter {
                     src: rhs_op,
                 });
             }
-            BinOp::Div => {
-                // Division requires special handling (rdx:rax / operand)
-                air_func.push(Instruction::Mov {
-                    dest: Operand::Reg(Register::RAX),
-                    src: Operand::Reg(dest_reg),
-                });
-                air_func.push(Instruction::Xor {
-                    dest: Operand::Reg(Register::RDX),
-                    src: Operand::Reg(Register::RDX),
-                });
-                air_func.push(Instruction::Idiv { operand: rhs_op });
-                air_func.push(Instruction::Mov {
-                    dest: Operand::Reg(dest_reg),
-                    src: Operand::Reg(Register::RAX),
-                });
-            }
-            BinOp::Mod => {
-                // Modulo uses remainder in rdx
-                air_func.push(Instruction::Mov {
-                    dest: Operand::Reg(Register::RAX),
-                    src: Operand::Reg(dest_reg),
-                });
-                air_func.push(Instruction::Xor {
-                    dest: Operand::Reg(Register::RDX),
-                    src: Operand::Reg(Register::RDX),
-                });
-                air_func.push(Instruction::Idiv { operand: rhs_op });
-                air_func.push(Instruction::Mov {
-                    dest: Operand::Reg(dest_reg),
-                    src: Operand::Reg(Register::RDX),
-                });
-            }
+            BinOp::Div => self.emit_division(dest_reg, rhs_op, Register::RAX, air_func),
+            BinOp::Mod => self.emit_division(dest_reg, rhs_op, Register::RDX, air_func),
             BinOp::BitAnd => {
                 air_func.push(Instruction::And {
                     dest: Operand::Reg(dest_reg),
@@ -446,34 +981,172 @@ impl AirEmitter {
                     right: rhs_op,
                 });
 
-                // Set destination based on comparison
-                let cond_jump = match op {
-                    BinOp::Eq => "je",
-                    BinOp::Ne => "jne",
-                    BinOp::Lt => "jl",
-                    BinOp::Le => "jle",
-                    BinOp::Gt => "jg",
-                    BinOp::Ge => "jge",
+                // Set destination to 0 or 1 based on comparison
+                let setcc = match op {
+                    BinOp::Eq => Instruction::Sete { dest: dest_reg },
+                    BinOp::Ne => Instruction::Setne { dest: dest_reg },
+                    BinOp::Lt => Instruction::Setl { dest: dest_reg },
+                    BinOp::Le => Instruction::Setle { dest: dest_reg },
+                    BinOp::Gt => Instruction::Setg { dest: dest_reg },
+                    BinOp::Ge => Instruction::Setge { dest: dest_reg },
                     _ => unreachable!(),
                 };
-
+                air_func.push(setcc);
+            }
+            BinOp::And | BinOp::Or => {
+                // Logical AND/OR are short-circuiting, need special handling
                 air_func.push(Instruction::Comment {
-                    text: format!("Comparison: {}", cond_jump),
+                    text: format!("Logical op: {:?}", op),
                 });
+            }
+        }
+        self.store_dest(*dest, air_func);
+    }
 
-                // Simplified: set to 0 or 1
-                air_func.push(Instruction::Mov {
-                    dest: Operand::Reg(dest_reg),
-                    src: Operand::Imm(0),
+    fn emit_float_binop(
+        &mut self,
+        dest: ValueId,
+        op: &BinOp,
+        lhs: &MirOp,
+        rhs: &MirOp,
+        precision: Precision,
+        air_func: &mut AirFunction,
+    ) {
+        let dest_reg = self.dest_register(dest);
+        match op {
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div => {
+                let rhs_op = self.float_operand(rhs, precision, SCRATCH_XMM_RHS, air_func);
+                let lhs_op = self.float_operand(lhs, precision, dest_reg, air_func);
+                self.emit_move(Operand::Reg(dest_reg), lhs_op, air_func);
+
+                let dest = Operand::Reg(dest_reg);
+                let src = rhs_op;
+                air_func.push(match (op, precision) {
+                    (BinOp::Add, Precision::Single) => Instruction::Addss { dest, src },
+                    (BinOp::Add, Precision::Double) => Instruction::Addsd { dest, src },
+                    (BinOp::Sub, Precision::Single) => Instruction::Subss { dest, src },
+                    (BinOp::Sub, Precision::Double) => Instruction::Subsd { dest, src },
+                    (BinOp::Mul, Precision::Single) => Instruction::Mulss { dest, src },
+                    (BinOp::Mul, Precision::Double) => Instruction::Mulsd { dest, src },
+                    (_, Precision::Single) => Instruction::Divss { dest, src },
+                    (_, Precision::Double) => Instruction::Divsd { dest, src },
                 });
             }
-            BinOp::And | BinOp::Or => {
-                // Logical AND/OR are short-circuiting, need special handling
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+                // `ucomis` sets the flags like an unsigned compare, so `<` and
+                // `<=` are `>` and `>=` with the operands swapped
+                let (left, right) = match op {
+                    BinOp::Lt | BinOp::Le => (rhs, lhs),
+                    _ => (lhs, rhs),
+                };
+                let right = self.float_operand(right, precision, SCRATCH_XMM_RHS, air_func);
+                let left = match self.float_operand(left, precision, SCRATCH_XMM, air_func) {
+                    Operand::Reg(reg) if reg.is_xmm() => reg,
+                    left => {
+                        air_func.push(precision.mov(Operand::Reg(SCRATCH_XMM), left));
+                        SCRATCH_XMM
+                    }
+                };
+                let left = Operand::Reg(left);
+                air_func.push(match precision {
+                    Precision::Single => Instruction::Ucomiss { left, right },
+                    Precision::Double => Instruction::Ucomisd { left, right },
+                });
+
+                // An unordered compare (a NaN operand) sets the parity flag
+                match op {
+                    BinOp::Eq => {
+                        air_func.push(Instruction::Sete { dest: dest_reg });
+                        air_func.push(Instruction::Setnp { dest: SCRATCH_PTR });
+                        air_func.push(Instruction::And {
+                            dest: Operand::Reg(dest_reg),
+                            src: Operand::Reg(SCRATCH_PTR),
+                        });
+                    }
+                    BinOp::Ne => {
+                        air_func.push(Instruction::Setne { dest: dest_reg });
+                        air_func.push(Instruction::Setp { dest: SCRATCH_PTR });
+                        air_func.push(Instruction::Or {
+                            dest: Operand::Reg(dest_reg),
+                            src: Operand::Reg(SCRATCH_PTR),
+                        });
+                    }
+                    BinOp::Lt | BinOp::Gt => air_func.push(Instruction::Seta { dest: dest_reg }),
+                    _ => air_func.push(Instruction::Setae { dest: dest_reg }),
+                }
+            }
+            _ => {
+                // SSE2 has no remainder or bitwise scalar arithmetic
                 air_func.push(Instruction::Comment {
-                    text: format!("Logical op: {:?}", op),
+                    text: format!("Float op: {:?}", op),
+                });
+            }
+        }
+        self.store_dest(dest, air_func);
+    }
+
+    /// Convert between integers and floats of either precision
+    fn emit_cast(&mut self, dest: ValueId, value: &MirOp, air_func: &mut AirFunction) {
+        let from = self.float_precision(&[value]);
+        let to = self.floats.get(&dest).copied();
+        let dest_reg = self.dest_register(dest);
+        match (from, to) {
+            (None, None) => {
+                let val_op = self.operand_to_air(value);
+                self.emit_move(self.location(dest), val_op, air_func);
+                return;
+            }
+            (Some(from), Some(to)) if from == to => return self.emit_assign(dest, value, air_func),
+            (Some(from), to) => {
+                let src = self.float_operand(value, from, SCRATCH_XMM_RHS, air_func);
+                let dest = Operand::Reg(dest_reg);
+                air_func.push(match (from, to) {
+                    (Precision::Single, None) => Instruction::Cvttss2si { dest, src },
+                    (Precision::Double, None) => Instruction::Cvttsd2si { dest, src },
+                    (Precision::Single, Some(_)) => Instruction::Cvtss2sd { dest, src },
+                    (Precision::Double, Some(_)) => Instruction::Cvtsd2ss { dest, src },
+                });
+            }
+            (None, Some(to)) => {
+                let src = match self.operand_to_air(value) {
+                    src @ (Operand::Reg(_) | Operand::Mem { .. }) => src,
+                    src => {
+                        self.emit_move(Operand::Reg(SCRATCH), src, air_func);
+                        Operand::Reg(SCRATCH)
+                    }
+                };
+                let dest = Operand::Reg(dest_reg);
+                air_func.push(match to {
+                    Precision::Single => Instruction::Cvtsi2ss { dest, src },
+                    Precision::Double => Instruction::Cvtsi2sd { dest, src },
                 });
             }
         }
+        self.store_dest(dest, air_func);
+    }
+
+    /// Copy an operand into a value
+    fn emit_assign(&mut self, dest: ValueId, value: &MirOp, air_func: &mut AirFunction) {
+        let src = match self.floats.get(&dest) {
+            Some(&precision) => {
+                let scratch = self.dest_register(dest);
+                self.float_operand(value, precision, scratch, air_func)
+            }
+            None => self.operand_to_air(value),
+        };
+        self.emit_move(self.location(dest), src, air_func);
+    }
+
+    /// Square root, for calls to the `f32.sqrt` and `f64.sqrt` intrinsics
+    fn emit_sqrt(&mut self, dest: ValueId, value: &MirOp, precision: Precision, air_func: &mut AirFunction) {
+        let dest_reg = self.dest_register(dest);
+        let src = self.float_operand(value, precision, SCRATCH_XMM_RHS, air_func);
+        let dest_op = Operand::Reg(dest_reg);
+        air_func.push(match precision {
+            Precision::Single => Instruction::Sqrtss { dest: dest_op, src },
+            Precision::Double => Instruction::Sqrtsd { dest: dest_op, src },
+        });
+        self.store_dest(dest, air_func);
     }
 
     fn emit_call(
@@ -483,35 +1156,137 @@ impl AirEmitter {
         args: &[MirOp],
         air_func: &mut AirFunction,
     ) {
-        // System V ABI: first 6 args in registers, rest on stack
+        if let (MirOp::Const(Constant::String(name)), Some(dest), [arg]) = (func, dest, args) {
+            let precision = match name.as_str() {
+                "f32.sqrt" => Some(Precision::Single),
+                "f64.sqrt" => Some(Precision::Double),
+                _ => None,
+            };
+            if let Some(precision) = precision {
+                return self.emit_sqrt(*dest, arg, precision, air_func);
+            }
+        }
 
-        // Place arguments in reverse order on stack if needed
-        if args.len() > 6 {
-            for arg in args.iter().skip(6).rev() {
-                let arg_op = self.operand_to_air(arg);
-                air_func.push(Instruction::Push { operand: arg_op });
+        // Arguments are classified by the callee's parameter types when it
+        // is a function of this module, and by their own types otherwise
+        let signature = match func {
+            MirOp::Const(Constant::String(name)) => self.signatures.get(name).cloned(),
+            _ => None,
+        };
+        let returned = signature.as_ref().and_then(|signature| self.return_registers(&signature.ret));
+        let (return_slot, args) = match (&returned, args) {
+            (Some(_), [slot, args @ ..]) => (Some(slot), args),
+            _ => (None, args),
+        };
+        let skipped = usize::from(return_slot.is_some());
+        let types: Vec<Type> = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let param = signature.as_ref().and_then(|signature| signature.params.get(i + skipped));
+                match (param, self.float_precision(&[arg])) {
+                    (Some(ty), _) => ty.clone(),
+                    (None, Some(Precision::Single)) => Type::Primitive(PrimitiveType::F32),
+                    (None, Some(Precision::Double)) => Type::Primitive(PrimitiveType::F64),
+                    (None, None) => Type::Primitive(PrimitiveType::I64),
+                }
+            })
+            .collect();
+
+        // Each argument register and stack word is filled from a scalar
+        // argument or from an eightbyte of an aggregate argument
+        let (locations, stack_words) = abi::assign(&types, &self.layouts);
+        let mut pieces = Vec::new();
+        let mut stack_pieces = Vec::new();
+        for ((arg, ty), location) in args.iter().zip(&types).zip(locations) {
+            let piece = |i: usize| {
+                if self.layouts.is_aggregate(ty) {
+                    Piece::Word(arg, (i * 8) as i32)
+                } else {
+                    Piece::Scalar(arg, Precision::of(ty))
+                }
+            };
+            match location {
+                ArgLocation::Registers(registers) => {
+                    pieces.extend(registers.into_iter().enumerate().map(|(i, reg)| (reg, piece(i))));
+                }
+                ArgLocation::Stack { words, .. } => stack_pieces.extend((0..words).map(piece)),
             }
         }
 
-        // Place first 6 arguments in registers
-        for (i, arg) in args.iter().take(6).enumerate() {
-            let arg_op = self.operand_to_air(arg);
+        // Preserve caller-saved registers holding values, keeping the stack
+        // pointer 16-byte aligned at the call
+        let dest_loc = dest.map(|dest| self.location(dest));
+        let allocated = self.regalloc.allocated_registers();
+        let saved: Vec<Register> = CALLER_SAVED
+            .into_iter()
+            .chain(XMM_REGISTERS)
+            .filter(|reg| dest_loc != Some(Operand::Reg(*reg)) && allocated.contains(reg))
+            .collect();
+        let padded = (saved.len() + stack_words) % 2 == 1;
+        if padded {
+            air_func.push(Instruction::Sub {
+                dest: Operand::Reg(Register::RSP),
+                src: Operand::Imm(8),
+            });
+        }
+        for reg in &saved {
+            self.emit_push(Operand::Reg(*reg), air_func);
+        }
 
-            // Use LEA for label addresses (Position Independent Code)
-            match &arg_op {
-                Operand::Label(label) => {
-                    air_func.push(Instruction::Lea {
-                        dest: Operand::Reg(ARG_REGISTERS[i]),
-                        src: Operand::Label(label.clone()),
-                    });
-                }
-                _ => {
-                    air_func.push(Instruction::Mov {
-                        dest: Operand::Reg(ARG_REGISTERS[i]),
-                        src: arg_op,
-                    });
+        // Place stack arguments in reverse order
+        for piece in stack_pieces.into_iter().rev() {
+            let arg_op = match piece {
+                Piece::Scalar(MirOp::Const(Constant::Float(bits)), Some(precision)) => {
+                    Operand::Imm(precision.bits(f64::from_bits(*bits)) as i64)
                 }
+                Piece::Scalar(arg, _) => self.operand_to_air(arg),
+                Piece::Word(arg, offset) => self.word_operand(arg, offset, air_func),
+            };
+            self.emit_push(arg_op, air_func);
+        }
+
+        // Register arguments go through the stack so that no argument
+        // register is overwritten before it is read, and constants are moved
+        // last. Float arguments come first, as loading float constants uses
+        // a scratch argument register.
+        let float_registers = pieces.iter().filter(|(reg, _)| reg.is_xmm()).count();
+        let (float_pieces, int_pieces): (Vec<_>, Vec<_>) = pieces.into_iter().partition(|(reg, _)| reg.is_xmm());
+        for pieces in [float_pieces, int_pieces] {
+            let mut pushed = Vec::new();
+            let mut constants = Vec::new();
+            for (reg, piece) in pieces {
+                let arg_op = match piece {
+                    Piece::Scalar(arg, precision) => match self.operand_to_air(arg) {
+                        arg_op @ (Operand::Reg(_) | Operand::Mem { .. }) => arg_op,
+                        arg_op => {
+                            constants.push((reg, arg, precision, arg_op));
+                            continue;
+                        }
+                    },
+                    Piece::Word(arg, offset) => self.word_operand(arg, offset, air_func),
+                };
+                self.emit_push(arg_op, air_func);
+                pushed.push(reg);
+            }
+            for reg in pushed.into_iter().rev() {
+                self.emit_pop(Operand::Reg(reg), air_func);
             }
+            for (reg, arg, precision, arg_op) in constants {
+                let arg_op = match precision {
+                    Some(precision) => self.float_operand(arg, precision, reg, air_func),
+                    None => arg_op,
+                };
+                self.emit_move(Operand::Reg(reg), arg_op, air_func);
+            }
+        }
+
+        // Variadic callees read the number of vector registers used from AL
+        if float_registers > 0 {
+            air_func.push(Instruction::Mov {
+                dest: Operand::Reg(Register::RAX),
+                src: Operand::Imm(float_registers as i64),
+            });
         }
 
         // Call function - handle function name as label/symbol
@@ -526,32 +1301,267 @@ impl AirEmitter {
         air_func.push(Instruction::Call { target: func_target });
 
         // Clean up stack if we pushed arguments
-        if args.len() > 6 {
-            let stack_cleanup = ((args.len() - 6) * 8) as i64;
+        if stack_words > 0 {
             air_func.push(Instruction::Add {
                 dest: Operand::Reg(Register::RSP),
-                src: Operand::Imm(stack_cleanup),
+                src: Operand::Imm((stack_words * 8) as i64),
             });
         }
 
+        // Store an aggregate returned in registers to its slot. The callee may
+        // have clobbered a caller-saved register holding the slot's address,
+        // so that is read back from where it was saved.
+        if let (Some(slot), Some(registers)) = (return_slot, returned) {
+            let slot_op = match self.operand_to_air(slot) {
+                Operand::Reg(reg) => match saved.iter().position(|&other| other == reg) {
+                    Some(i) => Operand::Mem {
+                        base: Register::RSP,
+                        offset: ((saved.len() - 1 - i) * 8) as i32,
+                    },
+                    None => Operand::Reg(reg),
+                },
+                slot_op => slot_op,
+            };
+            self.emit_move(Operand::Reg(RETURN_SLOT_PTR), slot_op, air_func);
+            for (i, reg) in registers.into_iter().enumerate() {
+                let dest = Operand::Mem {
+                    base: RETURN_SLOT_PTR,
+                    offset: (i * 8) as i32,
+                };
+                self.emit_move(dest, Operand::Reg(reg), air_func);
+            }
+        }
+
         // Move result if needed
-        if let Some(dest_val) = dest {
-            let dest_reg = self.regalloc.get_register(*dest_val);
-            if dest_reg != Register::RAX {
+        if let (Some(dest), Some(dest_loc)) = (dest, dest_loc) {
+            let result = if self.floats.contains_key(dest) {
+                Register::XMM0
+            } else {
+                Register::RAX
+            };
+            self.emit_move(dest_loc, Operand::Reg(result), air_func);
+        }
+
+        for reg in saved.iter().rev() {
+            self.emit_pop(Operand::Reg(*reg), air_func);
+        }
+        if padded {
+            air_func.push(Instruction::Add {
+                dest: Operand::Reg(Register::RSP),
+                src: Operand::Imm(8),
+            });
+        }
+    }
+
+    /// The eightbyte at `offset` into the aggregate whose address is `arg`
+    fn word_operand(&mut self, arg: &MirOp, offset: i32, air_func: &mut AirFunction) -> Operand {
+        let address = self.operand_to_air(arg);
+        match self.address_register(address, SCRATCH_PTR, air_func) {
+            Some(base) => Operand::Mem { base, offset },
+            None => Operand::Imm(0),
+        }
+    }
+
+    /// Register or spill slot holding a value
+    fn location(&self, value: ValueId) -> Operand {
+        match self.regalloc.spill_offset(value) {
+            Some(offset) => Operand::Mem {
+                base: Register::RBP,
+                offset: -(offset + 8),
+            },
+            None => Operand::Reg(self.regalloc.get_register(value)),
+        }
+    }
+
+    /// Register to compute a value in; spilled values are computed in a
+    /// scratch register and written back by [`Self::store_dest`]
+    fn dest_register(&self, value: ValueId) -> Register {
+        match self.location(value) {
+            Operand::Reg(reg) => reg,
+            _ if self.floats.contains_key(&value) => SCRATCH_XMM,
+            _ => SCRATCH,
+        }
+    }
+
+    /// Write a value computed by [`Self::dest_register`] to its spill slot
+    fn store_dest(&self, value: ValueId, air_func: &mut AirFunction) {
+        let dest = self.location(value);
+        if !matches!(dest, Operand::Reg(_)) {
+            self.emit_move(dest, Operand::Reg(self.dest_register(value)), air_func);
+        }
+    }
+
+    /// Precision of the first float among `ops`, if any is a float
+    fn float_precision(&self, ops: &[&MirOp]) -> Option<Precision> {
+        let values = ops.iter().find_map(|op| match op {
+            MirOp::Value(value) => self.floats.get(value).copied(),
+            MirOp::Const(_) => None,
+        });
+        values.or_else(|| {
+            ops.iter()
+                .any(|op| matches!(op, MirOp::Const(Constant::Float(_))))
+                .then_some(Precision::Double)
+        })
+    }
+
+    /// A float operand, with constants loaded from the data section into `scratch`
+    fn float_operand(
+        &mut self,
+        op: &MirOp,
+        precision: Precision,
+        scratch: Register,
+        air_func: &mut AirFunction,
+    ) -> Operand {
+        let value = match op {
+            MirOp::Const(Constant::Float(bits)) => f64::from_bits(*bits),
+            MirOp::Const(Constant::Int(int)) => *int as f64,
+            op => return self.operand_to_air(op),
+        };
+        let label = self.float_constant(value, precision);
+        air_func.push(Instruction::Lea {
+            dest: Operand::Reg(SCRATCH_PTR),
+            src: Operand::Label(label),
+        });
+        air_func.push(precision.mov(
+            Operand::Reg(scratch),
+            Operand::Mem {
+                base: SCRATCH_PTR,
+                offset: 0,
+            },
+        ));
+        Operand::Reg(scratch)
+    }
+
+    /// Label of a float constant in the data section
+    fn float_constant(&mut self, value: f64, precision: Precision) -> String {
+        let bits = precision.bits(value);
+        let existing = self
+            .float_constants
+            .iter()
+            .find(|(_, other, other_bits)| *other == precision && *other_bits == bits);
+        if let Some((label, _, _)) = existing {
+            return label.clone();
+        }
+        let label = format!("flt_{}", self.float_constants.len());
+        self.float_constants.push((label.clone(), precision, bits));
+        label
+    }
+
+    /// Push an operand, including XMM registers and 64-bit immediates
+    fn emit_push(&self, operand: Operand, air_func: &mut AirFunction) {
+        match operand {
+            Operand::Reg(reg) if reg.is_xmm() => {
+                air_func.push(Instruction::Sub {
+                    dest: Operand::Reg(Register::RSP),
+                    src: Operand::Imm(8),
+                });
+                air_func.push(Instruction::Movsd {
+                    dest: Operand::Mem {
+                        base: Register::RSP,
+                        offset: 0,
+                    },
+                    src: Operand::Reg(reg),
+                });
+            }
+            operand @ (Operand::Label(_) | Operand::Imm(_)) if !fits_imm32(&operand) => {
+                self.emit_move(Operand::Reg(SCRATCH), operand, air_func);
+                air_func.push(Instruction::Push {
+                    operand: Operand::Reg(SCRATCH),
+                });
+            }
+            operand => air_func.push(Instruction::Push { operand }),
+        }
+    }
+
+    /// Pop into an operand, including XMM registers
+    fn emit_pop(&self, operand: Operand, air_func: &mut AirFunction) {
+        match operand {
+            Operand::Reg(reg) if reg.is_xmm() => {
+                air_func.push(Instruction::Movsd {
+                    dest: Operand::Reg(reg),
+                    src: Operand::Mem {
+                        base: Register::RSP,
+                        offset: 0,
+                    },
+                });
+                air_func.push(Instruction::Add {
+                    dest: Operand::Reg(Register::RSP),
+                    src: Operand::Imm(8),
+                });
+            }
+            operand => air_func.push(Instruction::Pop { operand }),
+        }
+    }
+
+    /// Register holding an address operand, loading a spilled one into `scratch`
+    fn address_register(&self, op: Operand, scratch: Register, air_func: &mut AirFunction) -> Option<Register> {
+        match op {
+            Operand::Reg(reg) => Some(reg),
+            op @ Operand::Mem { .. } => {
                 air_func.push(Instruction::Mov {
-                    dest: Operand::Reg(dest_reg),
-                    src: Operand::Reg(Register::RAX),
+                    dest: Operand::Reg(scratch),
+                    src: op,
                 });
+                Some(scratch)
             }
+            _ => None,
+        }
+    }
+
+    /// Move `src` into `dest`, going through a scratch register where x86
+    /// has no direct move
+    fn emit_move(&self, dest: Operand, src: Operand, air_func: &mut AirFunction) {
+        if dest == src {
+            return;
+        }
+        let is_xmm = |op: &Operand| matches!(op, Operand::Reg(reg) if reg.is_xmm());
+        if is_xmm(&dest) || is_xmm(&src) {
+            match (&dest, &src) {
+                (Operand::Reg(_), Operand::Reg(_)) if is_xmm(&dest) != is_xmm(&src) => {
+                    air_func.push(Instruction::Movq { dest, src });
+                }
+                (_, Operand::Imm(_) | Operand::Label(_)) => {
+                    self.emit_move(Operand::Reg(SCRATCH), src, air_func);
+                    air_func.push(Instruction::Movq {
+                        dest,
+                        src: Operand::Reg(SCRATCH),
+                    });
+                }
+                _ => air_func.push(Instruction::Movsd { dest, src }),
+            }
+            return;
+        }
+        let in_memory = matches!(dest, Operand::Mem { .. });
+        match src {
+            // Use LEA for label addresses (Position Independent Code)
+            Operand::Label(label) => {
+                let reg = if in_memory { Operand::Reg(SCRATCH) } else { dest.clone() };
+                air_func.push(Instruction::Lea {
+                    dest: reg.clone(),
+                    src: Operand::Label(label),
+                });
+                if in_memory {
+                    air_func.push(Instruction::Mov { dest, src: reg });
+                }
+            }
+            // Memory to memory, or a 64-bit immediate to memory
+            Operand::Mem { .. } | Operand::Imm(_) if in_memory && !fits_imm32(&src) => {
+                air_func.push(Instruction::Mov {
+                    dest: Operand::Reg(SCRATCH),
+                    src,
+                });
+                air_func.push(Instruction::Mov {
+                    dest,
+                    src: Operand::Reg(SCRATCH),
+                });
+            }
+            src => air_func.push(Instruction::Mov { dest, src }),
         }
     }
 
     fn operand_to_air(&mut self, op: &MirOp) -> Operand {
         match op {
-            MirOp::Value(v) => {
-                let reg = self.regalloc.get_register(*v);
-                Operand::Reg(reg)
-            }
+            MirOp::Value(v) => self.location(*v),
             MirOp::Const(c) => match c {
                 Constant::Int(i) => Operand::Imm(*i),
                 Constant::Bool(b) => Operand::Imm(if *b { 1 } else { 0 }),
@@ -563,7 +1573,7 @@ impl AirEmitter {
                     Operand::Label(label)
                 }
                 Constant::Unit => Operand::Imm(0),
-                Constant::Float(_) => Operand::Imm(0), // TODO: proper float handling
+                Constant::Float(bits) => Operand::Imm(*bits as i64),
             },
         }
     }
@@ -574,6 +1584,11 @@ impl AirEmitter {
     }
 }
 
+/// Whether an operand is an immediate x86 can sign-extend from 32 bits
+fn fits_imm32(op: &Operand) -> bool {
+    matches!(op, Operand::Imm(imm) if i32::try_from(*imm).is_ok())
+}
+
 impl Default for AirEmitter {
     fn default() -> Self {
         Self::new()
@@ -668,6 +1683,247 @@ mod tests {
         assert!(air_func.instructions.len() > 0);
     }
 
+    #[test]
+    fn test_phi_inputs_moved_before_jump() {
+        use crate::air::Instruction as AirInst;
+        use aurora_mir::MirBuilder;
+        use aurora_types::PrimitiveType;
+
+        let i64_ty = Type::Primitive(PrimitiveType::I64);
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "choose".to_string(), i64_ty.clone(), EffectSet::PURE);
+        let flag = builder.add_param(Type::Primitive(PrimitiveType::Bool), Span::dummy());
+        let then_bb = builder.new_block();
+        let else_bb = builder.new_block();
+        let join_bb = builder.new_block();
+        builder.build_branch(MirOp::Value(flag), then_bb, else_bb, Span::dummy());
+        builder.set_block(then_bb);
+        builder.build_jump(join_bb, Span::dummy());
+        builder.set_block(else_bb);
+        builder.build_jump(join_bb, Span::dummy());
+        builder.set_block(join_bb);
+        let inputs = vec![
+            (then_bb, MirOp::Const(Constant::Int(1))),
+            (else_bb, MirOp::Value(flag)),
+        ];
+        let phi = builder.build_phi(inputs, i64_ty, Span::dummy());
+        builder.build_return(Some(MirOp::Value(phi)), Span::dummy());
+        let func = builder.finish_function().unwrap();
+
+        let mut emitter = AirEmitter::new();
+        let air_func = emitter.emit_function(&func);
+        let phi_reg = Operand::Reg(emitter.regalloc.get_register(phi));
+        let flag_reg = Operand::Reg(emitter.regalloc.get_register(flag));
+        assert_ne!(phi_reg, flag_reg);
+
+        // The move for each edge comes right before the jump to the join block
+        let join_label = format!(".L{}", join_bb);
+        let moves: Vec<&AirInst> = air_func
+            .instructions
+            .windows(2)
+            .filter(|pair| matches!(&pair[1], AirInst::Jmp { target } if *target == join_label))
+            .map(|pair| &pair[0])
+            .collect();
+        assert_eq!(
+            moves,
+            vec![
+                &AirInst::Mov { dest: phi_reg.clone(), src: Operand::Imm(1) },
+                &AirInst::Mov { dest: phi_reg, src: flag_reg },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_early_return_jumps_to_epilogue() {
+        use crate::air::Instruction as AirInst;
+
+        let mut emitter = AirEmitter::new();
+        let mut func = Function::new(3, "early".to_string(), Type::Unit, EffectSet::PURE);
+        func.add_value(Value { id: 0, ty: Type::Unit, span: Span::dummy() });
+        func.params.push(0);
+        let mut entry = BasicBlock::new(0);
+        entry.push(Instruction::Branch {
+            cond: MirOp::Value(0),
+            then_block: 1,
+            else_block: 2,
+            span: Span::dummy(),
+        });
+        func.add_block(entry);
+        for (id, value) in [(1, 10), (2, 20)] {
+            let mut block = BasicBlock::new(id);
+            block.push(Instruction::Return {
+                value: Some(MirOp::Const(Constant::Int(value))),
+                span: Span::dummy(),
+            });
+            func.add_block(block);
+        }
+
+        let air_func = emitter.emit_function(&func);
+        let jumps = air_func
+            .instructions
+            .iter()
+            .filter(|inst| matches!(inst, AirInst::Jmp { target } if target == ".Lret3"))
+            .count();
+        assert_eq!(jumps, 1);
+        assert_eq!(
+            air_func.instructions.last(),
+            Some(&AirInst::Label { name: ".Lret3".to_string() })
+        );
+    }
+
+    #[test]
+    fn test_float_arithmetic_uses_sse() {
+        use crate::air::Instruction as AirInst;
+        use aurora_mir::MirBuilder;
+        use aurora_types::PrimitiveType;
+
+        // `a * b + 2.5`
+        let f64_ty = Type::Primitive(PrimitiveType::F64);
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "fma".to_string(), f64_ty.clone(), EffectSet::PURE);
+        let a = builder.add_param(f64_ty.clone(), Span::dummy());
+        let b = builder.add_param(f64_ty.clone(), Span::dummy());
+        let product = builder.build_binop(BinOp::Mul, MirOp::Value(a), MirOp::Value(b), f64_ty.clone(), Span::dummy());
+        let constant = MirOp::Const(Constant::Float(2.5f64.to_bits()));
+        let sum = builder.build_binop(BinOp::Add, MirOp::Value(product), constant, f64_ty, Span::dummy());
+        builder.build_return(Some(MirOp::Value(sum)), Span::dummy());
+        let mut module = aurora_mir::MirModule::new();
+        module.add_function(builder.finish_function().unwrap());
+
+        let mut emitter = AirEmitter::new();
+        let air_module = emitter.emit_module(&module);
+        let insts = &air_module.functions[0].instructions;
+        assert!(insts.iter().any(|inst| matches!(inst, AirInst::Mulsd { .. })));
+        assert!(insts.iter().any(|inst| matches!(inst, AirInst::Addsd { .. })));
+        assert!(!insts.iter().any(|inst| matches!(inst, AirInst::Imul { .. })));
+
+        // The constant is loaded from the data section, and the sum returned in XMM0
+        let data = &air_module.data[0];
+        assert!(matches!(data.kind, DataKind::Qword));
+        assert_eq!(data.value, 2.5f64.to_bits().to_le_bytes());
+        assert!(insts.contains(&AirInst::Lea {
+            dest: Operand::Reg(SCRATCH_PTR),
+            src: Operand::Label(data.label.clone()),
+        }));
+        let xmm0 = Operand::Reg(Register::XMM0);
+        let sum_reg = Operand::Reg(emitter.regalloc.get_register(sum));
+        assert!(sum_reg == xmm0 || insts.contains(&AirInst::Movsd { dest: xmm0, src: sum_reg }));
+    }
+
+    #[test]
+    fn test_float_call_arguments() {
+        use crate::air::Instruction as AirInst;
+        use aurora_mir::MirBuilder;
+        use aurora_types::PrimitiveType;
+
+        // `scale(n, x)` and `f64.sqrt(x)`
+        let f64_ty = Type::Primitive(PrimitiveType::F64);
+        let i64_ty = Type::Primitive(PrimitiveType::I64);
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "caller".to_string(), f64_ty.clone(), EffectSet::PURE);
+        let x = builder.add_param(f64_ty.clone(), Span::dummy());
+        let n = builder.add_param(i64_ty, Span::dummy());
+        let scale = MirOp::Const(Constant::String("scale".to_string()));
+        let args = vec![MirOp::Value(n), MirOp::Value(x)];
+        let scaled = builder.build_call(scale, args, Some(f64_ty.clone()), EffectSet::PURE, Span::dummy()).unwrap();
+        let sqrt = MirOp::Const(Constant::String("f64.sqrt".to_string()));
+        let root = builder.build_call(sqrt, vec![MirOp::Value(scaled)], Some(f64_ty), EffectSet::PURE, Span::dummy()).unwrap();
+        builder.build_return(Some(MirOp::Value(root)), Span::dummy());
+        let func = builder.finish_function().unwrap();
+
+        let mut emitter = AirEmitter::new();
+        let air_func = emitter.emit_function(&func);
+        let insts = &air_func.instructions;
+        let calls: Vec<&AirInst> = insts.iter().filter(|inst| matches!(inst, AirInst::Call { .. })).collect();
+        assert_eq!(calls, vec![&AirInst::Call { target: Operand::Label("scale".to_string()) }]);
+        assert!(insts.iter().any(|inst| matches!(inst, AirInst::Sqrtsd { .. })));
+
+        // The float argument goes in XMM0, the integer in RDI, and AL counts vector registers
+        let call = insts.iter().position(|inst| matches!(inst, AirInst::Call { .. })).unwrap();
+        let setup = &insts[..call];
+        let x_reg = Operand::Reg(emitter.regalloc.get_register(x));
+        let n_reg = Operand::Reg(emitter.regalloc.get_register(n));
+        assert!(x_reg == Operand::Reg(Register::XMM0) || setup.iter().any(|inst| matches!(inst,
+            AirInst::Movsd { dest: Operand::Reg(Register::XMM0), .. })));
+        assert!(n_reg == Operand::Reg(Register::RDI) || setup.contains(&AirInst::Push { operand: n_reg }));
+        assert!(setup.contains(&AirInst::Mov { dest: Operand::Reg(Register::RAX), src: Operand::Imm(1) }));
+
+        // The result comes back in XMM0
+        let scaled_reg = Operand::Reg(emitter.regalloc.get_register(scaled));
+        assert!(scaled_reg == Operand::Reg(Register::XMM0)
+            || insts[call..].contains(&AirInst::Movsd { dest: scaled_reg, src: Operand::Reg(Register::XMM0) }));
+    }
+
+    #[test]
+    fn test_small_struct_passed_and_returned_in_registers() {
+        use crate::air::Instruction as AirInst;
+        use aurora_mir::MirBuilder;
+        use aurora_types::{PrimitiveType, StructDef};
+
+        // `id(p: Point) -> Point` with `Point { x: f64, y: f64 }`, and a caller
+        let f64_ty = Type::Primitive(PrimitiveType::F64);
+        let point = Type::Named {
+            name: "Point".to_string(),
+            args: vec![],
+        };
+        let mut mir_module = aurora_mir::MirModule::new();
+        mir_module.layouts.add_struct(
+            "Point".to_string(),
+            StructDef {
+                params: vec![],
+                fields: vec![("x".to_string(), f64_ty.clone()), ("y".to_string(), f64_ty)],
+            },
+        );
+
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "id".to_string(), point.clone(), EffectSet::PURE);
+        let slot_ty = Type::Ptr {
+            inner: Box::new(point.clone()),
+            mutable: true,
+        };
+        let slot = builder.add_param(slot_ty, Span::dummy());
+        builder.add_param(point.clone(), Span::dummy());
+        builder.build_return(Some(MirOp::Value(slot)), Span::dummy());
+        mir_module.add_function(builder.finish_function().unwrap());
+
+        builder.start_function(1, "main".to_string(), Type::Unit, EffectSet::PURE);
+        let result = builder.build_alloca(point.clone(), Span::dummy());
+        let arg = builder.build_alloca(point, Span::dummy());
+        let id = MirOp::Const(Constant::String("id".to_string()));
+        builder.build_call(id, vec![MirOp::Value(result), MirOp::Value(arg)], None, EffectSet::PURE, Span::dummy());
+        builder.build_return(None, Span::dummy());
+        mir_module.add_function(builder.finish_function().unwrap());
+
+        let air_module = AirEmitter::new().emit_module(&mir_module);
+        let function = |name: &str| air_module.functions.iter().find(|func| func.name == name).unwrap();
+        let stores_from = |func: &AirFunction, reg: Register| {
+            func.instructions.iter().any(|inst| matches!(inst,
+                AirInst::Movsd { dest: Operand::Mem { .. }, src: Operand::Reg(src) } if *src == reg))
+        };
+        let loads_into = |func: &AirFunction, reg: Register| {
+            func.instructions.iter().any(|inst| matches!(inst,
+                AirInst::Movsd { dest: Operand::Reg(dest), src: Operand::Mem { .. } } if *dest == reg))
+        };
+
+        // The callee stores the point's eightbytes from XMM0 and XMM1 and
+        // loads the result back into them; no slot pointer comes in RDI
+        let callee = function("id");
+        assert!(stores_from(callee, Register::XMM0) && stores_from(callee, Register::XMM1));
+        assert!(loads_into(callee, Register::XMM0) && loads_into(callee, Register::XMM1));
+        assert!(!callee.instructions.iter().any(|inst| matches!(inst,
+            AirInst::Mov { src: Operand::Reg(Register::RDI), .. } | AirInst::Push { operand: Operand::Reg(Register::RDI) })));
+
+        // The caller loads the argument into XMM0 and XMM1 and stores the
+        // returned eightbytes to its slot
+        let caller = function("main");
+        let call = caller.instructions.iter().position(|inst| matches!(inst, AirInst::Call { .. })).unwrap();
+        let (setup, after) = caller.instructions.split_at(call);
+        let setup = AirFunction { instructions: setup.to_vec(), ..AirFunction::new("setup".to_string()) };
+        let after = AirFunction { instructions: after.to_vec(), ..AirFunction::new("after".to_string()) };
+        assert!(loads_into(&setup, Register::XMM0) && loads_into(&setup, Register::XMM1));
+        assert!(stores_from(&after, Register::XMM0) && stores_from(&after, Register::XMM1));
+    }
+
     #[test]
     fn test_emit_module() {
         let mut emitter = AirEmitter::new();
@@ -4,11 +4,19 @@
 //!
 //! # Pipeline
 //!
-//! 1. **Emission**: Lower MIR to AIR with calling conventions
-//! 2. **Register Allocation**: Linear scan with liveness analysis
+//! 1. **Emission**: Lower MIR to AIR with calling conventions, optionally
+//!    recording source positions and variable locations for debug info
+//! 2. **Register Allocation**: Linear scan with liveness analysis, or graph
+//!    coloring with iterated register coalescing
 //! 3. **Peephole Optimization**: Pattern-based local optimizations
 //! 4. **Instruction Scheduling**: CPU-aware instruction reordering
 //!
+//! AIR targets x86_64. Other architectures implement [`Target`] and lower
+//! MIR to their own instructions with the same register allocators; AArch64
+//! is emitted as GNU assembler source following AAPCS64, and WebAssembly
+//! as a module for WASI with its control flow made structured. Use
+//! [`lower_mir_for_target`] to lower for the architecture in [`AirOptions`].
+//!
 //! # Example
 //!
 //! ```ignore
@@ -18,17 +26,29 @@
 //! println!("{}", air_module.to_string());
 //! ```
 
+pub mod aapcs64;
+pub mod aarch64;
+pub mod abi;
 pub mod air;
+pub mod coloring;
+pub mod debug;
 pub mod emit;
 pub mod peephole;
 pub mod regalloc;
 pub mod schedule;
+pub mod target;
+pub mod wasm;
 
+pub use aarch64::{A64Emitter, A64Function, A64Inst, A64Module};
 pub use air::{AirFunction, AirModule, Instruction, Operand, Register, DataDirective, DataKind};
+pub use coloring::GraphColoringAllocator;
+pub use debug::{CfiOp, DebugType, DebugVariable, VariableKind, VariableLocation};
 pub use emit::AirEmitter;
 pub use peephole::PeepholeOptimizer;
-pub use regalloc::RegisterAllocator;
+pub use regalloc::{Allocator, AllocatorKind, RegisterAllocator};
 pub use schedule::{CpuProfile, InstructionScheduler};
+pub use target::{Arch, RegisterFile, Target, TargetModule};
+pub use wasm::{WasmEmitter, WasmFunction, WasmInst, WasmModule};
 
 // Pipeline integration
 use aurora_mir::MirModule;
@@ -52,6 +72,12 @@ pub struct AirOptions {
     pub enable_scheduling: bool,
     /// Optimization level (0-3)
     pub opt_level: u8,
+    /// Register allocation algorithm
+    pub allocator: AllocatorKind,
+    /// Record source positions and variable locations for debug info
+    pub debug_info: bool,
+    /// Architecture to generate code for
+    pub arch: Arch,
 }
 
 impl Default for AirOptions {
@@ -61,6 +87,9 @@ impl Default for AirOptions {
             enable_peephole: true,
             enable_scheduling: true,
             opt_level: 2,
+            allocator: AllocatorKind::default(),
+            debug_info: false,
+            arch: Arch::default(),
         }
     }
 }
@@ -79,6 +108,9 @@ impl AirOptions {
             enable_peephole: true,
             enable_scheduling: true,
             opt_level: 2,
+            allocator: AllocatorKind::default(),
+            debug_info: false,
+            arch: Arch::default(),
         }
     }
 
@@ -89,6 +121,9 @@ impl AirOptions {
             enable_peephole: false,
             enable_scheduling: false,
             opt_level: 0,
+            allocator: AllocatorKind::default(),
+            debug_info: false,
+            arch: Arch::default(),
         }
     }
 }
@@ -107,9 +142,24 @@ pub fn lower_mir_to_air_with_options<D: Send + Sync + 'static>(
     _diagnostics: Arc<D>,
     options: AirOptions,
 ) -> AirModule {
+    lower_x86_64(&mir, &options)
+}
+
+/// Lower MIR for the architecture selected in `options`
+pub fn lower_mir_for_target<D: Send + Sync + 'static>(
+    mir: MirModule,
+    _diagnostics: Arc<D>,
+    options: AirOptions,
+) -> TargetModule {
+    options.arch.target().lower(&mir, &options)
+}
+
+/// Lower MIR to x86_64 AIR and optimize it
+pub(crate) fn lower_x86_64(mir: &MirModule, options: &AirOptions) -> AirModule {
     // Step 1: Emit AIR from MIR
-    let mut emitter = AirEmitter::new();
-    let mut air_module = emitter.emit_module(&mir);
+    let mut emitter = AirEmitter::with_allocator(options.allocator);
+    emitter.set_debug_info(options.debug_info);
+    let mut air_module = emitter.emit_module(mir);
 
     // Step 2: Apply optimizations per function
     for func in &mut air_module.functions {
@@ -141,6 +191,7 @@ mod tests {
         assert_eq!(opts.opt_level, 2);
         assert!(opts.enable_peephole);
         assert!(opts.enable_scheduling);
+        assert_eq!(opts.allocator, AllocatorKind::LinearScan);
     }
 
     #[test]
@@ -285,6 +336,29 @@ mod tests {
         assert!(air.data.len() > 0);
     }
 
+    #[test]
+    fn test_lower_for_aarch64() {
+        let mut mir = MirModule::new();
+        let mut func = Function::new(0, "main".to_string(), Type::Unit, EffectSet::PURE);
+        let mut block = BasicBlock::new(0);
+        block.push(Instruction::Return {
+            value: Some(Operand::Const(Constant::Int(7))),
+            span: Span::dummy(),
+        });
+        func.add_block(block);
+        mir.add_function(func);
+
+        let options = AirOptions {
+            arch: Arch::AArch64,
+            ..AirOptions::default()
+        };
+        let module = lower_mir_for_target(mir, Arc::new(()), options);
+        assert_eq!(module.arch(), Arch::AArch64);
+        let text = module.to_text();
+        assert!(text.contains("movz x0, #7"));
+        assert!(text.contains("ret"));
+    }
+
     #[test]
     fn test_multiple_functions() {
         let mut mir = MirModule::new();
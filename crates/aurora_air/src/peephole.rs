@@ -240,10 +240,10 @@ impl PeepholeOptimizer {
             if let [inst1, inst2] = &func.instructions[i..=i + 1] {
                 // Pattern: mov [mem], rax; mov rax, [mem] → mov [mem], rax
                 if let (
-                    Instruction::Mov { dest: Operand::Mem { .. }, src: Operand::Reg(r1) },
-                    Instruction::Mov { dest: Operand::Reg(r2), src: Operand::Mem { .. } }
+                    Instruction::Mov { dest: m1 @ Operand::Mem { .. }, src: Operand::Reg(r1) },
+                    Instruction::Mov { dest: Operand::Reg(r2), src: m2 @ Operand::Mem { .. } }
                 ) = (inst1, inst2) {
-                    if r1 == r2 {
+                    if r1 == r2 && m1 == m2 {
                         to_remove.push(i + 1);
                         self.optimizations_applied += 1;
                     }
@@ -334,6 +334,24 @@ mod tests {
         assert!(opt.optimizations_count() > 0);
     }
 
+    #[test]
+    fn test_store_then_load_of_other_slot_kept() {
+        let mut opt = PeepholeOptimizer::new();
+        let mut func = AirFunction::new("test".to_string());
+
+        func.push(Instruction::Mov {
+            dest: Operand::Mem { base: Register::RBP, offset: -8 },
+            src: Operand::Reg(Register::RDI),
+        });
+        func.push(Instruction::Mov {
+            dest: Operand::Reg(Register::RDI),
+            src: Operand::Mem { base: Register::RBP, offset: -16 },
+        });
+
+        opt.optimize(&mut func);
+        assert_eq!(func.instructions.len(), 2);
+    }
+
     #[test]
     fn test_nop_removal() {
         let mut opt = PeepholeOptimizer::new();
@@ -5,11 +5,139 @@
 //! - Interference graph
 //! - Spill code generation
 //! - Register coalescing hints
+//!
+//! [`Allocator`] is the interface the emitter uses, so it can work with
+//! either this allocator or the
+//! [`GraphColoringAllocator`](crate::coloring::GraphColoringAllocator).
+//! Both hand out the registers of a [`RegisterFile`], by default
+//! [`X86_64_REGISTERS`]. Neither hands out RSI and RDI, which the emitter
+//! keeps as scratch registers for spilled values.
+//!
+//! Floating-point values form a second register class, allocated from
+//! [`ALLOCATABLE_XMM`]. XMM14 and XMM15 are the emitter's float scratch
+//! registers.
 
 use crate::air::Register;
+use crate::coloring::GraphColoringAllocator;
+use crate::target::RegisterFile;
 use aurora_mir::{BlockId, Function, Instruction, Operand, ValueId};
+use aurora_types::{PrimitiveType, Type};
 use std::collections::{HashMap, HashSet};
 
+/// Registers handed out to values, caller-saved (volatile) first
+pub(crate) const ALLOCATABLE: [Register; 12] = [
+    Register::RAX,
+    Register::RCX,
+    Register::RDX,
+    Register::R8,
+    Register::R9,
+    Register::R10,
+    Register::R11,
+    // Callee-saved (non-volatile) - need to save/restore
+    Register::RBX,
+    Register::R12,
+    Register::R13,
+    Register::R14,
+    Register::R15,
+];
+
+/// Registers handed out to floating-point values, all caller-saved
+pub(crate) const ALLOCATABLE_XMM: [Register; 14] = [
+    Register::XMM0,
+    Register::XMM1,
+    Register::XMM2,
+    Register::XMM3,
+    Register::XMM4,
+    Register::XMM5,
+    Register::XMM6,
+    Register::XMM7,
+    Register::XMM8,
+    Register::XMM9,
+    Register::XMM10,
+    Register::XMM11,
+    Register::XMM12,
+    Register::XMM13,
+];
+
+/// Whether values of `ty` live in XMM registers
+pub(crate) fn is_float(ty: &Type) -> bool {
+    matches!(ty, Type::Primitive(PrimitiveType::F32 | PrimitiveType::F64))
+}
+
+/// Values of `func` in the floating-point register class
+pub(crate) fn float_values(func: &Function) -> HashSet<ValueId> {
+    func.values
+        .iter()
+        .filter(|(_, value)| is_float(&value.ty))
+        .map(|(&id, _)| id)
+        .collect()
+}
+
+/// Registers handed out on x86_64
+pub const X86_64_REGISTERS: RegisterFile = RegisterFile {
+    general: &ALLOCATABLE,
+    float: &ALLOCATABLE_XMM,
+    callee_saved: &[
+        Register::RBX,
+        Register::R12,
+        Register::R13,
+        Register::R14,
+        Register::R15,
+    ],
+};
+
+/// Assignment of registers and stack slots to the values of a function
+pub trait Allocator {
+    /// Allocate locations for the values of `func`
+    ///
+    /// An allocator may rewrite `func`, for example to split live ranges;
+    /// the rewritten function is the one to emit.
+    fn allocate(&mut self, func: &mut Function);
+
+    /// Register holding a value that is not spilled
+    fn get_register(&self, value: ValueId) -> Register;
+
+    /// Offset of a spilled value's slot in the spill area
+    fn spill_offset(&self, value: ValueId) -> Option<i32>;
+
+    /// Whether a value was given a register or spill slot
+    fn has_location(&self, value: ValueId) -> bool;
+
+    /// Bytes of stack needed for spill slots
+    fn stack_size(&self) -> u32;
+
+    /// Callee-saved registers that need saving/restoring
+    fn callee_saved_registers(&self) -> Vec<Register>;
+
+    /// All registers holding values
+    fn allocated_registers(&self) -> Vec<Register>;
+}
+
+/// Register allocation algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AllocatorKind {
+    /// Linear scan over live intervals
+    #[default]
+    LinearScan,
+    /// Iterated register coalescing (graph coloring)
+    GraphColoring,
+}
+
+impl AllocatorKind {
+    /// Create a fresh allocator of this kind
+    pub fn create(self) -> Box<dyn Allocator> {
+        self.create_for(&X86_64_REGISTERS)
+    }
+
+    /// Create a fresh allocator of this kind handing out `registers`
+    pub fn create_for(self, registers: &'static RegisterFile) -> Box<dyn Allocator> {
+        match self {
+            AllocatorKind::LinearScan => Box::new(RegisterAllocator::with_registers(registers)),
+            AllocatorKind::GraphColoring => Box::new(GraphColoringAllocator::with_registers(registers)),
+        }
+    }
+}
+
 /// Live interval for a value
 #[derive(Debug, Clone)]
 struct LiveInterval {
@@ -26,8 +154,14 @@ pub struct RegisterAllocator {
     spills: HashMap<ValueId, i32>,
     /// Next stack offset
     next_stack_offset: i32,
+    /// Registers handed out
+    registers: &'static RegisterFile,
     /// Available general purpose registers (caller-saved first for efficiency)
     available_regs: Vec<Register>,
+    /// Available floating-point registers
+    float_regs: Vec<Register>,
+    /// Values allocated from `float_regs`
+    floats: HashSet<ValueId>,
     /// Callee-saved registers that we're using
     callee_saved: Vec<Register>,
     /// Live intervals
@@ -36,26 +170,19 @@ pub struct RegisterAllocator {
 
 impl RegisterAllocator {
     pub fn new() -> Self {
+        Self::with_registers(&X86_64_REGISTERS)
+    }
+
+    /// Create an allocator handing out `registers`
+    pub fn with_registers(registers: &'static RegisterFile) -> Self {
         Self {
             allocation: HashMap::new(),
             spills: HashMap::new(),
             next_stack_offset: 0,
-            // Caller-saved (volatile) registers first - don't need saving
-            available_regs: vec![
-                Register::RAX,
-                Register::RCX,
-                Register::RDX,
-                Register::R8,
-                Register::R9,
-                Register::R10,
-                Register::R11,
-                // Callee-saved (non-volatile) - need to save/restore
-                Register::RBX,
-                Register::R12,
-                Register::R13,
-                Register::R14,
-                Register::R15,
-            ],
+            registers,
+            available_regs: registers.general.to_vec(),
+            float_regs: registers.float.to_vec(),
+            floats: HashSet::new(),
             callee_saved: Vec::new(),
             intervals: Vec::new(),
         }
@@ -63,11 +190,14 @@ impl RegisterAllocator {
 
     /// Run register allocation on function
     pub fn allocate(&mut self, func: &Function) {
+        self.floats = float_values(func);
+
         // Step 1: Compute live intervals
         self.compute_live_intervals(func);
 
-        // Step 2: Sort intervals by start point
-        self.intervals.sort_by_key(|i| i.start);
+        // Step 2: Sort intervals by start point, breaking ties by value
+        // so the allocation does not depend on hash order
+        self.intervals.sort_by_key(|i| (i.start, i.value));
 
         // Step 3: Linear scan allocation
         self.linear_scan();
@@ -125,8 +255,19 @@ impl RegisterAllocator {
         // Build intervals from liveness information
         let mut value_ranges: HashMap<ValueId, (usize, usize)> = HashMap::new();
 
-        for (block_id, block) in &func.blocks {
-            let block_start = *block_id as usize * 100; // Approximate position
+        // Number instructions in block order, leaving a gap at each block boundary
+        let mut block_ids: Vec<BlockId> = func.blocks.keys().copied().collect();
+        block_ids.sort_unstable();
+        let mut block_starts: HashMap<BlockId, usize> = HashMap::new();
+        let mut next_start = 0;
+        for block_id in &block_ids {
+            block_starts.insert(*block_id, next_start);
+            next_start += func.blocks[block_id].instructions.len() + 1;
+        }
+
+        for block_id in &block_ids {
+            let block = &func.blocks[block_id];
+            let block_start = block_starts[block_id];
 
             // Values live at block entry
             for &val in live_in.get(block_id).unwrap() {
@@ -161,6 +302,31 @@ impl RegisterAllocator {
             }
         }
 
+        // Phi moves happen at the end of each predecessor, so the phi and its
+        // input are both live there
+        for block in func.blocks.values() {
+            for inst in &block.instructions {
+                let Instruction::Phi { dest, inputs, .. } = inst else {
+                    continue;
+                };
+                for (pred, input) in inputs {
+                    let Some(&pred_start) = block_starts.get(pred) else {
+                        continue;
+                    };
+                    let pred_end = pred_start + func.blocks[pred].instructions.len().saturating_sub(1);
+                    let values = std::iter::once(*dest).chain(match input {
+                        Operand::Value(value) => Some(*value),
+                        _ => None,
+                    });
+                    for value in values {
+                        let entry = value_ranges.entry(value).or_insert((pred_end, pred_end));
+                        entry.0 = entry.0.min(pred_end);
+                        entry.1 = entry.1.max(pred_end);
+                    }
+                }
+            }
+        }
+
         // Convert to intervals
         self.intervals = value_ranges
             .into_iter()
@@ -222,23 +388,23 @@ impl RegisterAllocator {
             // Remove expired intervals
             active.retain(|a| a.end >= interval.start);
 
-            // Try to allocate register
-            if active.len() < self.available_regs.len() {
-                let reg = self.available_regs[active.len()];
+            // Try to allocate a register of the value's class not held by a live interval
+            let regs = if self.floats.contains(&interval.value) {
+                &self.float_regs
+            } else {
+                &self.available_regs
+            };
+            let free = regs.iter().copied().find(|reg| {
+                !active
+                    .iter()
+                    .any(|a| self.allocation.get(&a.value) == Some(reg))
+            });
+            if let Some(reg) = free {
                 self.allocation.insert(interval.value, reg);
 
                 // Track callee-saved registers
-                if matches!(
-                    reg,
-                    Register::RBX
-                        | Register::R12
-                        | Register::R13
-                        | Register::R14
-                        | Register::R15
-                ) {
-                    if !self.callee_saved.contains(&reg) {
-                        self.callee_saved.push(reg);
-                    }
+                if self.registers.is_callee_saved(reg) && !self.callee_saved.contains(&reg) {
+                    self.callee_saved.push(reg);
                 }
 
                 active.push(interval.clone());
@@ -258,7 +424,12 @@ impl RegisterAllocator {
 
     /// Get register for value
     pub fn get_register(&self, value: ValueId) -> Register {
-        self.allocation.get(&value).copied().unwrap_or(Register::RAX)
+        let fallback = if self.floats.contains(&value) {
+            self.registers.float[0]
+        } else {
+            self.registers.general[0]
+        };
+        self.allocation.get(&value).copied().unwrap_or(fallback)
     }
 
     /// Get stack size needed
@@ -281,12 +452,47 @@ impl RegisterAllocator {
         self.spills.get(&value).copied()
     }
 
+    /// Check if value was given a register or spill slot
+    pub fn has_location(&self, value: ValueId) -> bool {
+        self.allocation.contains_key(&value) || self.spills.contains_key(&value)
+    }
+
     /// Get all allocated registers
     pub fn allocated_registers(&self) -> Vec<Register> {
         self.allocation.values().copied().collect()
     }
 }
 
+impl Allocator for RegisterAllocator {
+    fn allocate(&mut self, func: &mut Function) {
+        RegisterAllocator::allocate(self, func);
+    }
+
+    fn get_register(&self, value: ValueId) -> Register {
+        RegisterAllocator::get_register(self, value)
+    }
+
+    fn spill_offset(&self, value: ValueId) -> Option<i32> {
+        RegisterAllocator::spill_offset(self, value)
+    }
+
+    fn has_location(&self, value: ValueId) -> bool {
+        RegisterAllocator::has_location(self, value)
+    }
+
+    fn stack_size(&self) -> u32 {
+        RegisterAllocator::stack_size(self)
+    }
+
+    fn callee_saved_registers(&self) -> Vec<Register> {
+        RegisterAllocator::callee_saved_registers(self)
+    }
+
+    fn allocated_registers(&self) -> Vec<Register> {
+        RegisterAllocator::allocated_registers(self)
+    }
+}
+
 impl Default for RegisterAllocator {
     fn default() -> Self {
         Self::new()
@@ -425,6 +631,83 @@ mod tests {
         assert!(spilled_count > 0, "Expected some values to be spilled with {} values and {} registers", num_values, alloc.available_regs.len());
     }
 
+    #[test]
+    fn test_expired_register_not_shared_with_live_value() {
+        let mut alloc = RegisterAllocator::new();
+        let mut func = Function::new(0, "test".to_string(), Type::Unit, EffectSet::PURE);
+        for id in 0..4 {
+            func.add_value(Value {
+                id,
+                ty: Type::Unit,
+                span: Span::dummy(),
+            });
+        }
+
+        // v0 dies when v1 is defined, and v1 is still live when v2 is defined
+        let mut block = BasicBlock::new(0);
+        block.push(MirInst::Assign {
+            dest: 0,
+            value: MirOp::Const(Constant::Int(1)),
+            span: Span::dummy(),
+        });
+        block.push(MirInst::Assign {
+            dest: 1,
+            value: MirOp::Value(0),
+            span: Span::dummy(),
+        });
+        block.push(MirInst::Assign {
+            dest: 2,
+            value: MirOp::Const(Constant::Int(2)),
+            span: Span::dummy(),
+        });
+        block.push(MirInst::BinOp {
+            dest: 3,
+            op: aurora_mir::BinOp::Add,
+            lhs: MirOp::Value(1),
+            rhs: MirOp::Value(2),
+            span: Span::dummy(),
+        });
+        func.add_block(block);
+
+        alloc.allocate(&func);
+        assert_ne!(alloc.get_register(1), alloc.get_register(2));
+    }
+
+    #[test]
+    fn test_float_values_get_xmm_registers() {
+        let mut alloc = RegisterAllocator::new();
+        let mut func = Function::new(0, "test".to_string(), Type::Unit, EffectSet::PURE);
+        let f64_ty = Type::Primitive(PrimitiveType::F64);
+        for (id, ty) in [(0, f64_ty.clone()), (1, Type::Primitive(PrimitiveType::I64)), (2, f64_ty)] {
+            func.add_value(Value { id, ty, span: Span::dummy() });
+        }
+
+        let mut block = BasicBlock::new(0);
+        block.push(MirInst::Assign {
+            dest: 0,
+            value: MirOp::Const(Constant::Float(1.5f64.to_bits())),
+            span: Span::dummy(),
+        });
+        block.push(MirInst::Assign {
+            dest: 1,
+            value: MirOp::Const(Constant::Int(2)),
+            span: Span::dummy(),
+        });
+        block.push(MirInst::BinOp {
+            dest: 2,
+            op: aurora_mir::BinOp::Mul,
+            lhs: MirOp::Value(0),
+            rhs: MirOp::Value(0),
+            span: Span::dummy(),
+        });
+        func.add_block(block);
+
+        alloc.allocate(&func);
+        assert!(ALLOCATABLE_XMM.contains(&alloc.get_register(0)));
+        assert!(ALLOCATABLE.contains(&alloc.get_register(1)));
+        assert!(ALLOCATABLE_XMM.contains(&alloc.get_register(2)));
+    }
+
     #[test]
     fn test_callee_saved_tracking() {
         let mut alloc = RegisterAllocator::new();
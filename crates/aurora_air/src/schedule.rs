@@ -6,7 +6,7 @@
 //! - List scheduling algorithm
 //! - Critical path analysis
 
-use crate::air::{AirFunction, Instruction, Operand, Register};
+use crate::air::{AirFunction, Instruction, Operand, Register, XMM_REGISTERS};
 use std::collections::{HashMap, HashSet};
 
 /// CPU Profile for instruction costs
@@ -40,6 +40,9 @@ impl CpuProfile {
         latencies.insert("test".to_string(), 1);
         latencies.insert("jmp".to_string(), 1);
         latencies.insert("call".to_string(), 2);
+        latencies.insert("fadd".to_string(), 4);
+        latencies.insert("fmul".to_string(), 4);
+        latencies.insert("fdiv".to_string(), 14);
 
         let mut throughput = HashMap::new();
         throughput.insert("mov".to_string(), 0.5);  // 2 per cycle
@@ -74,6 +77,9 @@ impl CpuProfile {
         latencies.insert("test".to_string(), 1);
         latencies.insert("jmp".to_string(), 1);
         latencies.insert("call".to_string(), 2);
+        latencies.insert("fadd".to_string(), 3);
+        latencies.insert("fmul".to_string(), 3);
+        latencies.insert("fdiv".to_string(), 13);
 
         let mut throughput = HashMap::new();
         throughput.insert("mov".to_string(), 0.5);
@@ -122,6 +128,14 @@ impl CpuProfile {
             Instruction::Test { .. } => "test",
             Instruction::Jmp { .. } | Instruction::Je { .. } | Instruction::Jne { .. } => "jmp",
             Instruction::Call { .. } => "call",
+            Instruction::Addsd { .. } | Instruction::Subsd { .. } | Instruction::Addss { .. } | Instruction::Subss { .. } => {
+                "fadd"
+            }
+            Instruction::Mulsd { .. } | Instruction::Mulss { .. } => "fmul",
+            Instruction::Divsd { .. }
+            | Instruction::Divss { .. }
+            | Instruction::Sqrtsd { .. }
+            | Instruction::Sqrtss { .. } => "fdiv",
             _ => "mov", // Default
         };
 
@@ -135,6 +149,10 @@ struct InstructionDeps {
     index: usize,
     reads: HashSet<Register>,
     writes: HashSet<Register>,
+    reads_memory: bool,
+    writes_memory: bool,
+    reads_flags: bool,
+    writes_flags: bool,
     latency: u32,
 }
 
@@ -199,15 +217,34 @@ impl InstructionScheduler {
     }
 
     /// Schedule a basic block using list scheduling
+    ///
+    /// Source positions stay with the instructions that follow them, so
+    /// debug info never changes the schedule.
     fn schedule_block(&mut self, block: Vec<Instruction>) -> Vec<Instruction> {
-        if block.is_empty() {
+        let is_loc = |inst: &Instruction| matches!(inst, Instruction::Loc { .. });
+        let len = block.iter().filter(|inst| !is_loc(inst)).count();
+        if len <= 1 {
+            self.scheduled_count += len;
             return block;
         }
 
-        if block.len() == 1 {
-            self.scheduled_count += 1;
-            return block;
+        // Position each instruction was emitted at; positions after the last
+        // instruction are kept at the end
+        let mut position = None;
+        let mut positions = Vec::new();
+        let mut trailing = Vec::new();
+        let mut instructions = Vec::new();
+        for inst in block {
+            if is_loc(&inst) {
+                position = Some(inst.clone());
+                trailing.push(inst);
+            } else {
+                positions.push(position.clone());
+                trailing.clear();
+                instructions.push(inst);
+            }
         }
+        let block = instructions;
 
         // Build dependency information
         let deps: Vec<InstructionDeps> = block
@@ -217,6 +254,10 @@ impl InstructionScheduler {
                 index: i,
                 reads: self.get_reads(inst),
                 writes: self.get_writes(inst),
+                reads_memory: self.reads_memory(inst),
+                writes_memory: self.writes_memory(inst),
+                reads_flags: self.reads_flags(inst),
+                writes_flags: self.writes_flags(inst),
                 latency: self.profile.latency(inst),
             })
             .collect();
@@ -248,7 +289,7 @@ impl InstructionScheduler {
             let best_idx = self.pick_best(&ready, &deps);
             let inst_idx = ready.remove(best_idx);
 
-            scheduled.push(block[inst_idx].clone());
+            scheduled.push(inst_idx);
             completed.insert(inst_idx);
             self.scheduled_count += 1;
 
@@ -269,7 +310,17 @@ impl InstructionScheduler {
             }
         }
 
-        scheduled
+        let mut current = None;
+        let mut instructions = Vec::new();
+        for index in scheduled {
+            if positions[index].is_some() && positions[index] != current {
+                current = positions[index].clone();
+                instructions.extend(current.clone());
+            }
+            instructions.push(block[index].clone());
+        }
+        instructions.extend(trailing);
+        instructions
     }
 
     /// Check if there's a dependency between instructions
@@ -289,6 +340,22 @@ impl InstructionScheduler {
             return true;
         }
 
+        // Memory accesses may alias, so stores stay ordered with other accesses
+        if earlier.writes_memory && (later.reads_memory || later.writes_memory) {
+            return true;
+        }
+        if earlier.reads_memory && later.writes_memory {
+            return true;
+        }
+
+        // A flags consumer must see the flags of the instruction before it
+        if earlier.writes_flags && (later.reads_flags || later.writes_flags) {
+            return true;
+        }
+        if earlier.reads_flags && later.writes_flags {
+            return true;
+        }
+
         false
     }
 
@@ -315,25 +382,75 @@ impl InstructionScheduler {
         let mut reads = HashSet::new();
 
         match inst {
-            Instruction::Mov { src, .. } => self.add_operand_reads(src, &mut reads),
-            Instruction::Add { dest, src } | Instruction::Sub { dest, src } => {
-                self.add_operand_reads(dest, &mut reads);
+            Instruction::Mov { dest, src }
+            | Instruction::Movzx { dest, src }
+            | Instruction::Movsx { dest, src } => {
+                self.add_address_reads(dest, &mut reads);
                 self.add_operand_reads(src, &mut reads);
             }
-            Instruction::Imul { dest, src } => {
+            Instruction::Movsd { dest, src }
+            | Instruction::Movss { dest, src }
+            | Instruction::Movq { dest, src }
+            | Instruction::Sqrtsd { dest, src }
+            | Instruction::Sqrtss { dest, src }
+            | Instruction::Cvtsi2sd { dest, src }
+            | Instruction::Cvtsi2ss { dest, src }
+            | Instruction::Cvttsd2si { dest, src }
+            | Instruction::Cvttss2si { dest, src }
+            | Instruction::Cvtss2sd { dest, src }
+            | Instruction::Cvtsd2ss { dest, src } => {
+                self.add_address_reads(dest, &mut reads);
+                self.add_operand_reads(src, &mut reads);
+            }
+            Instruction::Lea { src, .. } => self.add_operand_reads(src, &mut reads),
+            Instruction::Addsd { dest, src }
+            | Instruction::Subsd { dest, src }
+            | Instruction::Mulsd { dest, src }
+            | Instruction::Divsd { dest, src }
+            | Instruction::Addss { dest, src }
+            | Instruction::Subss { dest, src }
+            | Instruction::Mulss { dest, src }
+            | Instruction::Divss { dest, src }
+            | Instruction::Xorpd { dest, src }
+            | Instruction::Add { dest, src }
+            | Instruction::Sub { dest, src }
+            | Instruction::Imul { dest, src }
+            | Instruction::And { dest, src }
+            | Instruction::Or { dest, src }
+            | Instruction::Xor { dest, src } => {
                 self.add_operand_reads(dest, &mut reads);
                 self.add_operand_reads(src, &mut reads);
             }
+            Instruction::Shl { dest, count }
+            | Instruction::Shr { dest, count }
+            | Instruction::Sar { dest, count } => {
+                self.add_operand_reads(dest, &mut reads);
+                self.add_operand_reads(count, &mut reads);
+            }
+            Instruction::Inc { operand }
+            | Instruction::Dec { operand }
+            | Instruction::Neg { operand }
+            | Instruction::Not { operand } => self.add_operand_reads(operand, &mut reads),
             Instruction::Idiv { operand } => {
                 self.add_operand_reads(operand, &mut reads);
                 reads.insert(Register::RAX);
                 reads.insert(Register::RDX);
             }
-            Instruction::Cmp { left, right } | Instruction::Test { left, right } => {
+            Instruction::Cmp { left, right }
+            | Instruction::Test { left, right }
+            | Instruction::Ucomisd { left, right }
+            | Instruction::Ucomiss { left, right } => {
                 self.add_operand_reads(left, &mut reads);
                 self.add_operand_reads(right, &mut reads);
             }
-            Instruction::Push { operand } => self.add_operand_reads(operand, &mut reads),
+            Instruction::Push { operand } => {
+                self.add_operand_reads(operand, &mut reads);
+                reads.insert(Register::RSP);
+            }
+            Instruction::Pop { operand } => {
+                self.add_address_reads(operand, &mut reads);
+                reads.insert(Register::RSP);
+            }
             _ => {}
         }
 
@@ -348,7 +465,27 @@ impl InstructionScheduler {
             Instruction::Mov { dest, .. }
             | Instruction::Lea { dest, .. }
             | Instruction::Movzx { dest, .. }
-            | Instruction::Movsx { dest, .. } => {
+            | Instruction::Movsx { dest, .. }
+            | Instruction::Movsd { dest, .. }
+            | Instruction::Movss { dest, .. }
+            | Instruction::Movq { dest, .. }
+            | Instruction::Addsd { dest, .. }
+            | Instruction::Subsd { dest, .. }
+            | Instruction::Mulsd { dest, .. }
+            | Instruction::Divsd { dest, .. }
+            | Instruction::Sqrtsd { dest, .. }
+            | Instruction::Addss { dest, .. }
+            | Instruction::Subss { dest, .. }
+            | Instruction::Mulss { dest, .. }
+            | Instruction::Divss { dest, .. }
+            | Instruction::Sqrtss { dest, .. }
+            | Instruction::Xorpd { dest, .. }
+            | Instruction::Cvtsi2sd { dest, .. }
+            | Instruction::Cvtsi2ss { dest, .. }
+            | Instruction::Cvttsd2si { dest, .. }
+            | Instruction::Cvttss2si { dest, .. }
+            | Instruction::Cvtss2sd { dest, .. }
+            | Instruction::Cvtsd2ss { dest, .. } => {
                 self.add_operand_writes(dest, &mut writes);
             }
             Instruction::Add { dest, .. }
@@ -362,13 +499,35 @@ impl InstructionScheduler {
             | Instruction::Sar { dest, .. } => {
                 self.add_operand_writes(dest, &mut writes);
             }
+            Instruction::Inc { operand }
+            | Instruction::Dec { operand }
+            | Instruction::Neg { operand }
+            | Instruction::Not { operand } => self.add_operand_writes(operand, &mut writes),
+            Instruction::Sete { dest }
+            | Instruction::Setne { dest }
+            | Instruction::Setl { dest }
+            | Instruction::Setle { dest }
+            | Instruction::Setg { dest }
+            | Instruction::Setge { dest }
+            | Instruction::Seta { dest }
+            | Instruction::Setae { dest }
+            | Instruction::Setp { dest }
+            | Instruction::Setnp { dest } => {
+                writes.insert(*dest);
+            }
             Instruction::Idiv { .. } => {
                 writes.insert(Register::RAX);
                 writes.insert(Register::RDX);
             }
-            Instruction::Pop { operand } => self.add_operand_writes(operand, &mut writes),
+            Instruction::Push { .. } => {
+                writes.insert(Register::RSP);
+            }
+            Instruction::Pop { operand } => {
+                self.add_operand_writes(operand, &mut writes);
+                writes.insert(Register::RSP);
+            }
             Instruction::Call { .. } => {
-                // Calls clobber caller-saved registers
+                // Calls clobber caller-saved registers, which include every XMM register
                 writes.insert(Register::RAX);
                 writes.insert(Register::RCX);
                 writes.insert(Register::RDX);
@@ -376,6 +535,7 @@ impl InstructionScheduler {
                 writes.insert(Register::R9);
                 writes.insert(Register::R10);
                 writes.insert(Register::R11);
+                writes.extend(XMM_REGISTERS);
             }
             _ => {}
         }
@@ -403,6 +563,117 @@ impl InstructionScheduler {
         }
     }
 
+    /// Registers used to address a memory operand
+    fn add_address_reads(&self, op: &Operand, reads: &mut HashSet<Register>) {
+        if matches!(op, Operand::Mem { .. } | Operand::MemComplex { .. }) {
+            self.add_operand_reads(op, reads);
+        }
+    }
+
+    /// Check if instruction loads from memory (including the stack)
+    fn reads_memory(&self, inst: &Instruction) -> bool {
+        match inst {
+            Instruction::Mov { src, .. }
+            | Instruction::Movzx { src, .. }
+            | Instruction::Movsx { src, .. }
+            | Instruction::Movsd { src, .. }
+            | Instruction::Movss { src, .. }
+            | Instruction::Sqrtsd { src, .. }
+            | Instruction::Sqrtss { src, .. }
+            | Instruction::Cvtsi2sd { src, .. }
+            | Instruction::Cvtsi2ss { src, .. }
+            | Instruction::Cvttsd2si { src, .. }
+            | Instruction::Cvttss2si { src, .. }
+            | Instruction::Cvtss2sd { src, .. }
+            | Instruction::Cvtsd2ss { src, .. }
+            | Instruction::Addsd { src, .. }
+            | Instruction::Subsd { src, .. }
+            | Instruction::Mulsd { src, .. }
+            | Instruction::Divsd { src, .. }
+            | Instruction::Addss { src, .. }
+            | Instruction::Subss { src, .. }
+            | Instruction::Mulss { src, .. }
+            | Instruction::Divss { src, .. }
+            | Instruction::Xorpd { src, .. } => is_memory(src),
+            Instruction::Ucomisd { right, .. } | Instruction::Ucomiss { right, .. } => is_memory(right),
+            Instruction::Add { dest, src }
+            | Instruction::Sub { dest, src }
+            | Instruction::Imul { dest, src }
+            | Instruction::And { dest, src }
+            | Instruction::Or { dest, src }
+            | Instruction::Xor { dest, src } => is_memory(dest) || is_memory(src),
+            Instruction::Cmp { left, right } | Instruction::Test { left, right } => {
+                is_memory(left) || is_memory(right)
+            }
+            Instruction::Idiv { operand } | Instruction::Push { operand } => is_memory(operand),
+            Instruction::Pop { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Check if instruction reads the condition flags
+    fn reads_flags(&self, inst: &Instruction) -> bool {
+        matches!(
+            inst,
+            Instruction::Sete { .. }
+                | Instruction::Setne { .. }
+                | Instruction::Setl { .. }
+                | Instruction::Setle { .. }
+                | Instruction::Setg { .. }
+                | Instruction::Setge { .. }
+                | Instruction::Seta { .. }
+                | Instruction::Setae { .. }
+                | Instruction::Setp { .. }
+                | Instruction::Setnp { .. }
+        )
+    }
+
+    /// Check if instruction sets the condition flags
+    fn writes_flags(&self, inst: &Instruction) -> bool {
+        matches!(
+            inst,
+            Instruction::Add { .. }
+                | Instruction::Sub { .. }
+                | Instruction::Imul { .. }
+                | Instruction::Idiv { .. }
+                | Instruction::Inc { .. }
+                | Instruction::Dec { .. }
+                | Instruction::Neg { .. }
+                | Instruction::And { .. }
+                | Instruction::Or { .. }
+                | Instruction::Xor { .. }
+                | Instruction::Shl { .. }
+                | Instruction::Shr { .. }
+                | Instruction::Sar { .. }
+                | Instruction::Cmp { .. }
+                | Instruction::Test { .. }
+                | Instruction::Ucomisd { .. }
+                | Instruction::Ucomiss { .. }
+        )
+    }
+
+    /// Check if instruction stores to memory (including the stack)
+    fn writes_memory(&self, inst: &Instruction) -> bool {
+        match inst {
+            Instruction::Mov { dest, .. }
+            | Instruction::Add { dest, .. }
+            | Instruction::Sub { dest, .. }
+            | Instruction::Imul { dest, .. }
+            | Instruction::And { dest, .. }
+            | Instruction::Or { dest, .. }
+            | Instruction::Xor { dest, .. }
+            | Instruction::Movsd { dest, .. }
+            | Instruction::Movss { dest, .. } => is_memory(dest),
+            Instruction::Inc { operand }
+            | Instruction::Dec { operand }
+            | Instruction::Neg { operand }
+            | Instruction::Not { operand }
+            | Instruction::Pop { operand } => is_memory(operand),
+            Instruction::Push { .. } => true,
+            _ => false,
+        }
+    }
+
     fn add_operand_writes(&self, op: &Operand, writes: &mut HashSet<Register>) {
         if let Operand::Reg(r) = op {
             writes.insert(*r);
@@ -414,6 +685,11 @@ impl InstructionScheduler {
     }
 }
 
+/// Check if an operand refers to memory
+fn is_memory(op: &Operand) -> bool {
+    matches!(op, Operand::Mem { .. } | Operand::MemComplex { .. })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -501,6 +777,65 @@ mod tests {
         assert_eq!(func.instructions.len(), before);
     }
 
+    #[test]
+    fn test_memory_order_preserved() {
+        let mut scheduler = InstructionScheduler::new(CpuProfile::skylake());
+        let mut func = AirFunction::new("test".to_string());
+
+        // store [rbx], 1; imul rcx, 3; load rax, [rdx] — the load may alias the store
+        func.push(Instruction::Mov {
+            dest: Operand::Mem { base: Register::RBX, offset: 0 },
+            src: Operand::Imm(1),
+        });
+        func.push(Instruction::Imul {
+            dest: Operand::Reg(Register::RCX),
+            src: Operand::Imm(3),
+        });
+        func.push(Instruction::Mov {
+            dest: Operand::Reg(Register::RAX),
+            src: Operand::Mem { base: Register::RDX, offset: 0 },
+        });
+        func.push(Instruction::Push {
+            operand: Operand::Reg(Register::RAX),
+        });
+        func.push(Instruction::Pop {
+            operand: Operand::Reg(Register::RDI),
+        });
+
+        let store = func.instructions[0].clone();
+        let load = func.instructions[2].clone();
+        let push = func.instructions[3].clone();
+        scheduler.schedule(&mut func);
+
+        let position = |inst: &Instruction| func.instructions.iter().position(|i| i == inst);
+        assert!(position(&store) < position(&load));
+        assert!(position(&load) < position(&push));
+        assert_eq!(func.instructions.last(), Some(&Instruction::Pop {
+            operand: Operand::Reg(Register::RDI),
+        }));
+    }
+
+    #[test]
+    fn test_flags_order_preserved() {
+        let mut scheduler = InstructionScheduler::new(CpuProfile::skylake());
+        let mut func = AirFunction::new("test".to_string());
+
+        // cmp rcx, 0; sete rcx; imul rdx, 3 — the multiply must not land between them
+        func.push(Instruction::Cmp {
+            left: Operand::Reg(Register::RCX),
+            right: Operand::Imm(0),
+        });
+        func.push(Instruction::Sete { dest: Register::RCX });
+        func.push(Instruction::Imul {
+            dest: Operand::Reg(Register::RDX),
+            src: Operand::Imm(3),
+        });
+        let expected = func.instructions.clone();
+        scheduler.schedule(&mut func);
+
+        assert_eq!(func.instructions, expected);
+    }
+
     #[test]
     fn test_block_boundary_detection() {
         let scheduler = InstructionScheduler::new(CpuProfile::skylake());
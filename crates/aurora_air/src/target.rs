@@ -0,0 +1,251 @@
+//! Code generation targets
+//!
+//! A [`Target`] describes an architecture to the code generator: the
+//! registers the allocators hand out, the registers of its calling
+//! convention, and the instruction selection lowering MIR to it.
+//!
+//! x86_64 is lowered to AIR (see [`crate::emit`]), which the backend can
+//! encode itself. AArch64 is lowered to GNU assembler syntax following
+//! AAPCS64 (see [`crate::aarch64`]). WebAssembly has no registers: values
+//! live in wasm locals and the module is written for WASI (see
+//! [`crate::wasm`]).
+
+use crate::aapcs64::AAPCS64;
+use crate::aarch64::{A64Emitter, A64Module, AARCH64_REGISTERS};
+use crate::abi::SYSV_AMD64;
+use crate::air::{AirModule, Register};
+use crate::regalloc::X86_64_REGISTERS;
+use crate::wasm::{WasmEmitter, WasmModule};
+use crate::AirOptions;
+use aurora_mir::MirModule;
+use std::fmt;
+
+/// Instruction set architecture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arch {
+    #[default]
+    X86_64,
+    AArch64,
+    Wasm32,
+}
+
+impl Arch {
+    /// Architecture of a target triple such as `aarch64-unknown-linux-gnu`
+    pub fn from_triple(triple: &str) -> Option<Self> {
+        match triple.split('-').next()? {
+            "x86_64" | "amd64" => Some(Arch::X86_64),
+            "aarch64" | "arm64" => Some(Arch::AArch64),
+            "wasm32" => Some(Arch::Wasm32),
+            _ => None,
+        }
+    }
+
+    /// Code generation for this architecture
+    pub fn target(self) -> &'static dyn Target {
+        match self {
+            Arch::X86_64 => &X86_64Target,
+            Arch::AArch64 => &AArch64Target,
+            Arch::Wasm32 => &Wasm32Target,
+        }
+    }
+}
+
+impl fmt::Display for Arch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arch::X86_64 => write!(f, "x86_64"),
+            Arch::AArch64 => write!(f, "aarch64"),
+            Arch::Wasm32 => write!(f, "wasm32"),
+        }
+    }
+}
+
+/// Registers handed out to values by the register allocators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterFile {
+    /// Registers for integer and pointer values, caller-saved first
+    pub general: &'static [Register],
+    /// Registers for floating-point values
+    pub float: &'static [Register],
+    /// The registers among these that a function preserves for its caller
+    pub callee_saved: &'static [Register],
+}
+
+impl RegisterFile {
+    /// Whether a function must save `reg` before using it
+    pub fn is_callee_saved(&self, reg: Register) -> bool {
+        self.callee_saved.contains(&reg)
+    }
+}
+
+/// Registers arguments are passed and results returned in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallingConvention {
+    /// Integer and pointer argument registers, in order
+    pub int_args: &'static [Register],
+    /// Floating-point argument registers, in order
+    pub float_args: &'static [Register],
+    /// Integer return registers
+    pub int_returns: &'static [Register],
+    /// Floating-point return registers
+    pub float_returns: &'static [Register],
+    /// Register passing the address a result is returned through, or
+    /// `None` if it is passed as the first argument
+    pub indirect_result: Option<Register>,
+}
+
+/// Code lowered for a target
+#[derive(Debug, Clone)]
+pub enum TargetModule {
+    /// AIR, encoded by the backend or printed as NASM-like text
+    X86_64(AirModule),
+    /// GNU assembler source for AArch64
+    AArch64(A64Module),
+    /// WebAssembly for WASI
+    Wasm32(WasmModule),
+}
+
+impl TargetModule {
+    /// Architecture the code is for
+    pub fn arch(&self) -> Arch {
+        match self {
+            TargetModule::X86_64(_) => Arch::X86_64,
+            TargetModule::AArch64(_) => Arch::AArch64,
+            TargetModule::Wasm32(_) => Arch::Wasm32,
+        }
+    }
+
+    /// Assembly text of the module, or the text format for WebAssembly
+    pub fn to_text(&self) -> String {
+        match self {
+            TargetModule::X86_64(module) => module.to_text(),
+            TargetModule::AArch64(module) => module.to_text(),
+            TargetModule::Wasm32(module) => module.to_text(),
+        }
+    }
+}
+
+/// Code generation for one architecture
+pub trait Target: Sync {
+    /// Architecture generated for
+    fn arch(&self) -> Arch;
+
+    /// Registers the register allocators hand out
+    fn register_file(&self) -> &'static RegisterFile;
+
+    /// Registers of the calling convention
+    fn calling_convention(&self) -> &'static CallingConvention;
+
+    /// Select instructions for a MIR module
+    fn lower(&self, mir: &MirModule, options: &AirOptions) -> TargetModule;
+}
+
+/// x86_64 with the System V AMD64 ABI
+#[derive(Debug, Clone, Copy)]
+pub struct X86_64Target;
+
+impl Target for X86_64Target {
+    fn arch(&self) -> Arch {
+        Arch::X86_64
+    }
+
+    fn register_file(&self) -> &'static RegisterFile {
+        &X86_64_REGISTERS
+    }
+
+    fn calling_convention(&self) -> &'static CallingConvention {
+        &SYSV_AMD64
+    }
+
+    fn lower(&self, mir: &MirModule, options: &AirOptions) -> TargetModule {
+        TargetModule::X86_64(crate::lower_x86_64(mir, options))
+    }
+}
+
+/// AArch64 with the AAPCS64 procedure call standard
+#[derive(Debug, Clone, Copy)]
+pub struct AArch64Target;
+
+impl Target for AArch64Target {
+    fn arch(&self) -> Arch {
+        Arch::AArch64
+    }
+
+    fn register_file(&self) -> &'static RegisterFile {
+        &AARCH64_REGISTERS
+    }
+
+    fn calling_convention(&self) -> &'static CallingConvention {
+        &AAPCS64
+    }
+
+    fn lower(&self, mir: &MirModule, options: &AirOptions) -> TargetModule {
+        let mut emitter = A64Emitter::with_allocator(options.allocator);
+        TargetModule::AArch64(emitter.emit_module(mir))
+    }
+}
+
+/// No registers are allocated for WebAssembly
+const NO_REGISTERS: RegisterFile = RegisterFile {
+    general: &[],
+    float: &[],
+    callee_saved: &[],
+};
+
+/// Arguments and results are wasm function parameters and results
+const WASM_CALLS: CallingConvention = CallingConvention {
+    int_args: &[],
+    float_args: &[],
+    int_returns: &[],
+    float_returns: &[],
+    indirect_result: None,
+};
+
+/// 32-bit WebAssembly for WASI
+#[derive(Debug, Clone, Copy)]
+pub struct Wasm32Target;
+
+impl Target for Wasm32Target {
+    fn arch(&self) -> Arch {
+        Arch::Wasm32
+    }
+
+    fn register_file(&self) -> &'static RegisterFile {
+        &NO_REGISTERS
+    }
+
+    fn calling_convention(&self) -> &'static CallingConvention {
+        &WASM_CALLS
+    }
+
+    fn lower(&self, mir: &MirModule, _options: &AirOptions) -> TargetModule {
+        TargetModule::Wasm32(WasmEmitter::new().emit_module(mir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arch_from_triple() {
+        assert_eq!(Arch::from_triple("x86_64-unknown-linux-gnu"), Some(Arch::X86_64));
+        assert_eq!(Arch::from_triple("aarch64-unknown-linux-gnu"), Some(Arch::AArch64));
+        assert_eq!(Arch::from_triple("arm64-apple-darwin"), Some(Arch::AArch64));
+        assert_eq!(Arch::from_triple("wasm32-wasi"), Some(Arch::Wasm32));
+        assert_eq!(Arch::from_triple("riscv64gc-unknown-linux-gnu"), None);
+        assert_eq!(Arch::from_triple("aarch64-unknown-linux-gnu").unwrap().target().arch(), Arch::AArch64);
+    }
+
+    #[test]
+    fn test_register_files_keep_scratch_registers_out() {
+        let aarch64 = Arch::AArch64.target().register_file();
+        // x16/x17 are the emitter's scratch registers, x18 is reserved by
+        // the platform, and the argument registers are never allocated
+        for reg in [Register::X(0), Register::X(8), Register::X(16), Register::X(17), Register::X(18)] {
+            assert!(!aarch64.general.contains(&reg));
+        }
+        assert!(aarch64.callee_saved.iter().all(|reg| aarch64.general.contains(reg)));
+        assert!(Arch::X86_64.target().register_file().is_callee_saved(Register::RBX));
+    }
+}
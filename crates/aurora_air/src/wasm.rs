@@ -0,0 +1,2375 @@
+//! WebAssembly code generation
+//!
+//! MIR is lowered to a WebAssembly module for WASI (`wasm32-wasi`). Every
+//! MIR value gets a local: floats are `f32` or `f64` and everything else,
+//! pointers included, an `i64`, so values keep the 64-bit width they have
+//! on the other targets. Stack slots live in a shadow stack at the top of
+//! linear memory, below `__stack_pointer`, and string constants in data
+//! segments near the bottom.
+//!
+//! Wasm only branches out of enclosing blocks, so each function's control
+//! flow is rebuilt from its dominator tree: a loop header found by
+//! [`CFG::find_loops`] starts a `loop`, a block reached by more than one
+//! forward edge follows a `block` its predecessors branch out of, and any
+//! other block is placed inside the one branching to it. This handles the
+//! reducible control flow graphs MIR lowering produces.
+//!
+//! `print` and `println` are implemented in the module over WASI's
+//! `fd_write`, printing a string, or a number in decimal, and so are the C
+//! runtime's `aurora_str_eq` and `aurora_panic`, which match lowering
+//! calls. `_start` calls `main` and exits with its result.
+//!
+//! Modules formatting interpolated strings also get the C runtime's
+//! `aurora_fmt_*` routines. Strings are built in a heap above the shadow
+//! stack, growing memory as needed, and never freed.
+
+use crate::emit::{Precision, Signature};
+use aurora_mir::layout::WORD_SIZE;
+use aurora_mir::{
+    BinOp, BlockId, Constant, DominatorTree, Function as MirFunction, Instruction as MirInst, LayoutTable,
+    MirModule, Operand as MirOp, UnaryOp, ValueId, CFG,
+};
+use aurora_types::Type;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Module the WASI imports come from
+const WASI: &str = "wasi_snapshot_preview1";
+
+/// Index of the `__stack_pointer` global
+const STACK_POINTER: u32 = 0;
+
+/// File descriptor of standard output
+const STDOUT: i32 = 1;
+
+/// File descriptor of standard error
+const STDERR: i32 = 2;
+
+/// Address of the iovec passed to `fd_write`
+const IOVEC: i32 = 16;
+
+/// Address `fd_write` stores the number of bytes written at
+const NWRITTEN: i32 = 24;
+
+/// Address of the heap pointer, an i32: the end of the string being
+/// formatted
+const HEAP_POINTER: i32 = 32;
+
+/// Address characters are encoded at before being formatted
+const CHAR_BYTES: i32 = 36;
+
+/// End of the buffer numbers are formatted into, backwards
+const DIGITS_END: i32 = 64;
+
+/// Address of the first string constant; the bytes below are scratch
+/// space for the runtime
+const DATA_START: u32 = 64;
+
+/// Bytes of memory reserved for the shadow stack
+const STACK_SIZE: u32 = 1 << 20;
+
+/// Size of a wasm memory page
+const PAGE_SIZE: u32 = 1 << 16;
+
+/// Routines formatting interpolated strings, with their parameters and
+/// results, as in the C runtime
+const FORMAT_ROUTINES: [(&str, &[ValType], &[ValType]); 10] = [
+    ("aurora_fmt_new", &[], &[ValType::I64]),
+    ("aurora_fmt_str", &[ValType::I64; 2], &[]),
+    ("aurora_fmt_i64", &[ValType::I64; 2], &[]),
+    ("aurora_fmt_u64", &[ValType::I64; 2], &[]),
+    ("aurora_fmt_f32", &[ValType::I64, ValType::F32], &[]),
+    ("aurora_fmt_f64", &[ValType::I64, ValType::F64], &[]),
+    ("aurora_fmt_bool", &[ValType::I64; 2], &[]),
+    ("aurora_fmt_char", &[ValType::I64; 2], &[]),
+    ("aurora_fmt_finish", &[ValType::I64], &[ValType::I64]),
+    ("aurora_fmt_free", &[ValType::I64], &[]),
+];
+
+/// Routines of the C runtime other than the formatting ones, with their
+/// parameters and results
+const RUNTIME_ROUTINES: [(&str, &[ValType], &[ValType]); 2] = [
+    ("aurora_str_eq", &[ValType::I64; 2], &[ValType::I64]),
+    ("aurora_panic", &[ValType::I64; 3], &[]),
+];
+
+/// Functions of the formatting runtime: the routines, then `append`,
+/// `append_byte` and `digits`
+const FORMAT_FUNCTIONS: u32 = FORMAT_ROUTINES.len() as u32 + 3;
+
+/// Type of a wasm value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl ValType {
+    /// Type of the local holding a MIR value of type `ty`
+    pub fn of(ty: &Type) -> Self {
+        match Precision::of(ty) {
+            Some(Precision::Single) => ValType::F32,
+            Some(Precision::Double) => ValType::F64,
+            None => ValType::I64,
+        }
+    }
+
+    fn precision(self) -> Option<Precision> {
+        match self {
+            ValType::F32 => Some(Precision::Single),
+            ValType::F64 => Some(Precision::Double),
+            ValType::I32 | ValType::I64 => None,
+        }
+    }
+}
+
+impl From<Precision> for ValType {
+    fn from(precision: Precision) -> Self {
+        match precision {
+            Precision::Single => ValType::F32,
+            Precision::Double => ValType::F64,
+        }
+    }
+}
+
+impl fmt::Display for ValType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValType::I32 => write!(f, "i32"),
+            ValType::I64 => write!(f, "i64"),
+            ValType::F32 => write!(f, "f32"),
+            ValType::F64 => write!(f, "f64"),
+        }
+    }
+}
+
+/// Width of a load or store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    I32,
+    I64,
+    F32,
+    F64,
+    /// One byte, zero-extended to an i32 when loaded
+    Byte32,
+    /// One byte, zero-extended to an i64 when loaded
+    Byte64,
+}
+
+impl Access {
+    fn of(ty: ValType) -> Self {
+        match ty {
+            ValType::I32 => Access::I32,
+            ValType::I64 => Access::I64,
+            ValType::F32 => Access::F32,
+            ValType::F64 => Access::F64,
+        }
+    }
+
+    /// Bytes accessed
+    pub fn size(self) -> u32 {
+        match self {
+            Access::Byte32 | Access::Byte64 => 1,
+            Access::I32 | Access::F32 => 4,
+            Access::I64 | Access::F64 => 8,
+        }
+    }
+
+    /// Type of the value loaded or stored
+    fn ty(self) -> ValType {
+        match self {
+            Access::I32 | Access::Byte32 => ValType::I32,
+            Access::I64 | Access::Byte64 => ValType::I64,
+            Access::F32 => ValType::F32,
+            Access::F64 => ValType::F64,
+        }
+    }
+}
+
+/// Numeric instruction, taking its operands from the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumOp {
+    I32Eqz,
+    I32GtU,
+    I32Add,
+    I32Sub,
+    I32Shl,
+    I32ShrU,
+    I64Eqz,
+    I64Eq,
+    I64Ne,
+    I64LtS,
+    I64LeS,
+    I64GtS,
+    I64GeS,
+    I64Add,
+    I64Sub,
+    I64Mul,
+    I64DivS,
+    I64DivU,
+    I64RemS,
+    I64RemU,
+    I64And,
+    I64Or,
+    I64Xor,
+    I64Shl,
+    I64ShrU,
+    F32Eq,
+    F32Ne,
+    F32Lt,
+    F32Le,
+    F32Gt,
+    F32Ge,
+    F32Neg,
+    F32Sqrt,
+    F32Add,
+    F32Sub,
+    F32Mul,
+    F32Div,
+    F64Eq,
+    F64Ne,
+    F64Lt,
+    F64Le,
+    F64Gt,
+    F64Ge,
+    F64Neg,
+    F64Sqrt,
+    F64Add,
+    F64Sub,
+    F64Mul,
+    F64Div,
+    I32WrapI64,
+    I64ExtendI32U,
+    /// Float to integer, rounding toward zero and saturating instead of trapping
+    I64TruncSatF32S,
+    I64TruncSatF64S,
+    I64TruncSatF64U,
+    F32ConvertI64S,
+    F64ConvertI64S,
+    F32DemoteF64,
+    F64PromoteF32,
+    I32ReinterpretF32,
+    I64ReinterpretF64,
+    F32ReinterpretI32,
+    F64ReinterpretI64,
+}
+
+impl NumOp {
+    /// Text format name
+    pub fn name(self) -> &'static str {
+        match self {
+            NumOp::I32Eqz => "i32.eqz",
+            NumOp::I32GtU => "i32.gt_u",
+            NumOp::I32Add => "i32.add",
+            NumOp::I32Sub => "i32.sub",
+            NumOp::I32Shl => "i32.shl",
+            NumOp::I32ShrU => "i32.shr_u",
+            NumOp::I64Eqz => "i64.eqz",
+            NumOp::I64Eq => "i64.eq",
+            NumOp::I64Ne => "i64.ne",
+            NumOp::I64LtS => "i64.lt_s",
+            NumOp::I64LeS => "i64.le_s",
+            NumOp::I64GtS => "i64.gt_s",
+            NumOp::I64GeS => "i64.ge_s",
+            NumOp::I64Add => "i64.add",
+            NumOp::I64Sub => "i64.sub",
+            NumOp::I64Mul => "i64.mul",
+            NumOp::I64DivS => "i64.div_s",
+            NumOp::I64DivU => "i64.div_u",
+            NumOp::I64RemS => "i64.rem_s",
+            NumOp::I64RemU => "i64.rem_u",
+            NumOp::I64And => "i64.and",
+            NumOp::I64Or => "i64.or",
+            NumOp::I64Xor => "i64.xor",
+            NumOp::I64Shl => "i64.shl",
+            NumOp::I64ShrU => "i64.shr_u",
+            NumOp::F32Eq => "f32.eq",
+            NumOp::F32Ne => "f32.ne",
+            NumOp::F32Lt => "f32.lt",
+            NumOp::F32Le => "f32.le",
+            NumOp::F32Gt => "f32.gt",
+            NumOp::F32Ge => "f32.ge",
+            NumOp::F32Neg => "f32.neg",
+            NumOp::F32Sqrt => "f32.sqrt",
+            NumOp::F32Add => "f32.add",
+            NumOp::F32Sub => "f32.sub",
+            NumOp::F32Mul => "f32.mul",
+            NumOp::F32Div => "f32.div",
+            NumOp::F64Eq => "f64.eq",
+            NumOp::F64Ne => "f64.ne",
+            NumOp::F64Lt => "f64.lt",
+            NumOp::F64Le => "f64.le",
+            NumOp::F64Gt => "f64.gt",
+            NumOp::F64Ge => "f64.ge",
+            NumOp::F64Neg => "f64.neg",
+            NumOp::F64Sqrt => "f64.sqrt",
+            NumOp::F64Add => "f64.add",
+            NumOp::F64Sub => "f64.sub",
+            NumOp::F64Mul => "f64.mul",
+            NumOp::F64Div => "f64.div",
+            NumOp::I32WrapI64 => "i32.wrap_i64",
+            NumOp::I64ExtendI32U => "i64.extend_i32_u",
+            NumOp::I64TruncSatF32S => "i64.trunc_sat_f32_s",
+            NumOp::I64TruncSatF64S => "i64.trunc_sat_f64_s",
+            NumOp::I64TruncSatF64U => "i64.trunc_sat_f64_u",
+            NumOp::F32ConvertI64S => "f32.convert_i64_s",
+            NumOp::F64ConvertI64S => "f64.convert_i64_s",
+            NumOp::F32DemoteF64 => "f32.demote_f64",
+            NumOp::F64PromoteF32 => "f64.promote_f32",
+            NumOp::I32ReinterpretF32 => "i32.reinterpret_f32",
+            NumOp::I64ReinterpretF64 => "i64.reinterpret_f64",
+            NumOp::F32ReinterpretI32 => "f32.reinterpret_i32",
+            NumOp::F64ReinterpretI64 => "f64.reinterpret_i64",
+        }
+    }
+}
+
+/// WebAssembly instruction
+///
+/// Blocks, loops and ifs produce no values; the block a branch targets is
+/// counted outward from the innermost enclosing one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WasmInst {
+    /// Start a block; branching to it exits it
+    Block,
+    /// Start a loop; branching to it repeats it
+    Loop,
+    /// Start a block run if the i32 popped is nonzero
+    If,
+    Else,
+    End,
+    Br(u32),
+    /// Branch if the i32 popped is nonzero
+    BrIf(u32),
+    Return,
+    Unreachable,
+    Drop,
+    /// Call a function by index, imports first
+    Call(u32),
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    I32Const(i32),
+    I64Const(i64),
+    /// An f32, by its bits
+    F32Const(u32),
+    /// An f64, by its bits
+    F64Const(u64),
+    /// Load from the i32 address popped plus `offset`
+    Load { access: Access, offset: u32 },
+    /// Store the value popped at the i32 address below it plus `offset`
+    Store { access: Access, offset: u32 },
+    /// Push the size of memory in pages, as an i32
+    MemorySize,
+    /// Grow memory by the i32 popped in pages, pushing the old size or -1
+    MemoryGrow,
+    /// Copy bytes: pops the length, the source and the destination address
+    MemoryCopy,
+    Num(NumOp),
+}
+
+/// Text format of a float constant
+fn float_text(value: f64) -> String {
+    if value.is_nan() {
+        "nan".to_string()
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+impl fmt::Display for WasmInst {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let memarg = |f: &mut fmt::Formatter, offset: u32| match offset {
+            0 => Ok(()),
+            offset => write!(f, " offset={}", offset),
+        };
+        match self {
+            WasmInst::Block => write!(f, "block"),
+            WasmInst::Loop => write!(f, "loop"),
+            WasmInst::If => write!(f, "if"),
+            WasmInst::Else => write!(f, "else"),
+            WasmInst::End => write!(f, "end"),
+            WasmInst::Br(depth) => write!(f, "br {}", depth),
+            WasmInst::BrIf(depth) => write!(f, "br_if {}", depth),
+            WasmInst::Return => write!(f, "return"),
+            WasmInst::Unreachable => write!(f, "unreachable"),
+            WasmInst::Drop => write!(f, "drop"),
+            WasmInst::Call(index) => write!(f, "call {}", index),
+            WasmInst::LocalGet(index) => write!(f, "local.get {}", index),
+            WasmInst::LocalSet(index) => write!(f, "local.set {}", index),
+            WasmInst::LocalTee(index) => write!(f, "local.tee {}", index),
+            WasmInst::GlobalGet(index) => write!(f, "global.get {}", index),
+            WasmInst::GlobalSet(index) => write!(f, "global.set {}", index),
+            WasmInst::I32Const(value) => write!(f, "i32.const {}", value),
+            WasmInst::I64Const(value) => write!(f, "i64.const {}", value),
+            WasmInst::F32Const(bits) => write!(f, "f32.const {}", float_text(f32::from_bits(*bits).into())),
+            WasmInst::F64Const(bits) => write!(f, "f64.const {}", float_text(f64::from_bits(*bits))),
+            WasmInst::Load { access, offset } => {
+                match access {
+                    Access::Byte32 | Access::Byte64 => write!(f, "{}.load8_u", access.ty())?,
+                    access => write!(f, "{}.load", access.ty())?,
+                }
+                memarg(f, *offset)
+            }
+            WasmInst::Store { access, offset } => {
+                match access {
+                    Access::Byte32 | Access::Byte64 => write!(f, "{}.store8", access.ty())?,
+                    access => write!(f, "{}.store", access.ty())?,
+                }
+                memarg(f, *offset)
+            }
+            WasmInst::MemorySize => write!(f, "memory.size"),
+            WasmInst::MemoryGrow => write!(f, "memory.grow"),
+            WasmInst::MemoryCopy => write!(f, "memory.copy"),
+            WasmInst::Num(op) => write!(f, "{}", op.name()),
+        }
+    }
+}
+
+/// Function imported from the host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasmImport {
+    pub module: String,
+    pub name: String,
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>,
+}
+
+/// Function defined in the module
+#[derive(Debug, Clone)]
+pub struct WasmFunction {
+    pub name: String,
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>,
+    /// Locals after the parameters
+    pub locals: Vec<ValType>,
+    pub body: Vec<WasmInst>,
+    /// Name the function is exported under
+    pub export: Option<String>,
+}
+
+impl WasmFunction {
+    pub fn new(name: String, params: Vec<ValType>, results: Vec<ValType>) -> Self {
+        Self {
+            name,
+            params,
+            results,
+            locals: Vec::new(),
+            body: Vec::new(),
+            export: None,
+        }
+    }
+}
+
+/// Bytes placed in memory when the module is instantiated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataSegment {
+    pub offset: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// Module lowered to WebAssembly
+///
+/// Functions are indexed imports first. The module defines one memory,
+/// exported as `memory`, and one global, the shadow stack pointer.
+#[derive(Debug, Clone)]
+pub struct WasmModule {
+    pub name: String,
+    pub imports: Vec<WasmImport>,
+    pub functions: Vec<WasmFunction>,
+    pub data: Vec<DataSegment>,
+    /// Size of memory in 64 KiB pages
+    pub memory_pages: u32,
+    /// Initial value of `__stack_pointer`, the top of the shadow stack
+    pub stack_pointer: u32,
+}
+
+impl WasmModule {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            imports: Vec::new(),
+            functions: Vec::new(),
+            data: Vec::new(),
+            memory_pages: 1,
+            stack_pointer: PAGE_SIZE,
+        }
+    }
+
+    /// Name of the function with the given index
+    pub fn function_name(&self, index: u32) -> Option<&str> {
+        let index = index as usize;
+        match index.checked_sub(self.imports.len()) {
+            None => Some(&self.imports[index].name),
+            Some(index) => self.functions.get(index).map(|func| func.name.as_str()),
+        }
+    }
+
+    /// WebAssembly text format of the module
+    pub fn to_text(&self) -> String {
+        let signature = |params: &[ValType], results: &[ValType]| {
+            let mut text = String::new();
+            if !params.is_empty() {
+                let params: Vec<String> = params.iter().map(ValType::to_string).collect();
+                text.push_str(&format!(" (param {})", params.join(" ")));
+            }
+            if !results.is_empty() {
+                let results: Vec<String> = results.iter().map(ValType::to_string).collect();
+                text.push_str(&format!(" (result {})", results.join(" ")));
+            }
+            text
+        };
+
+        let mut output = format!(";; WebAssembly for module: {}\n(module\n", self.name);
+        for import in &self.imports {
+            output.push_str(&format!(
+                "  (import \"{}\" \"{}\" (func ${}{}))\n",
+                import.module,
+                import.name,
+                import.name,
+                signature(&import.params, &import.results)
+            ));
+        }
+        output.push_str(&format!("  (memory (export \"memory\") {})\n", self.memory_pages));
+        output.push_str(&format!(
+            "  (global $__stack_pointer (mut i32) (i32.const {}))\n",
+            self.stack_pointer
+        ));
+
+        for func in &self.functions {
+            let export = match &func.export {
+                Some(name) => format!(" (export \"{}\")", name),
+                None => String::new(),
+            };
+            output.push_str(&format!(
+                "  (func ${}{}{}\n",
+                func.name,
+                export,
+                signature(&func.params, &func.results)
+            ));
+            if !func.locals.is_empty() {
+                let locals: Vec<String> = func.locals.iter().map(ValType::to_string).collect();
+                output.push_str(&format!("    (local {})\n", locals.join(" ")));
+            }
+            let mut depth = 2;
+            for inst in &func.body {
+                if matches!(inst, WasmInst::Else | WasmInst::End) {
+                    depth -= 1;
+                }
+                let text = match inst {
+                    WasmInst::Call(index) => match self.function_name(*index) {
+                        Some(name) => format!("call ${}", name),
+                        None => inst.to_string(),
+                    },
+                    inst => inst.to_string(),
+                };
+                output.push_str(&format!("{}{}\n", "  ".repeat(depth), text));
+                if matches!(inst, WasmInst::Block | WasmInst::Loop | WasmInst::If | WasmInst::Else) {
+                    depth += 1;
+                }
+            }
+            output.push_str("  )\n");
+        }
+
+        for segment in &self.data {
+            let bytes: String = segment.bytes.iter().map(|byte| format!("\\{:02x}", byte)).collect();
+            output.push_str(&format!("  (data (i32.const {}) \"{}\")\n", segment.offset, bytes));
+        }
+        output.push_str(")\n");
+        output
+    }
+}
+
+/// Structure enclosing the code being emitted, innermost last
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Enclosing {
+    If,
+    /// A loop whose start is the block's
+    Loop(BlockId),
+    /// A block whose end is followed by the block's code
+    Block(BlockId),
+}
+
+/// Shape of a function's control flow graph
+#[derive(Debug, Default)]
+struct Structure {
+    /// Position of each reachable block in reverse postorder
+    order: HashMap<BlockId, usize>,
+    /// Children of each block in the dominator tree
+    children: HashMap<BlockId, Vec<BlockId>>,
+    loop_headers: HashSet<BlockId>,
+    /// Blocks with more than one forward edge into them
+    merges: HashSet<BlockId>,
+}
+
+impl Structure {
+    fn of(func: &MirFunction) -> Self {
+        let cfg = CFG::build(func);
+        let dominators = DominatorTree::compute(&cfg);
+        let order: HashMap<_, _> = cfg
+            .reverse_post_order
+            .iter()
+            .enumerate()
+            .map(|(i, &block)| (block, i))
+            .collect();
+
+        let mut children: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+        let mut forward_edges: HashMap<BlockId, usize> = HashMap::new();
+        for &block in &cfg.reverse_post_order {
+            if let Some(idom) = dominators.idom(block).filter(|&idom| idom != block) {
+                children.entry(idom).or_default().push(block);
+            }
+            for succ in cfg.succs(block) {
+                if order[succ] > order[&block] {
+                    *forward_edges.entry(*succ).or_default() += 1;
+                }
+            }
+        }
+
+        Self {
+            order,
+            children,
+            loop_headers: cfg.find_loops().iter().map(|l| l.header).collect(),
+            merges: forward_edges
+                .into_iter()
+                .filter(|&(_, edges)| edges > 1)
+                .map(|(block, _)| block)
+                .collect(),
+        }
+    }
+
+    /// Children of `block` in the dominator tree that are merge points,
+    /// latest in reverse postorder first
+    fn merge_children(&self, block: BlockId) -> Vec<BlockId> {
+        let mut merges: Vec<_> = self
+            .children
+            .get(&block)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|child| self.merges.contains(child))
+            .collect();
+        merges.sort_by_key(|child| std::cmp::Reverse(self.order[child]));
+        merges
+    }
+
+    /// Whether an edge goes back to a loop header
+    fn is_backward(&self, from: BlockId, to: BlockId) -> bool {
+        self.order.get(&to) <= self.order.get(&from)
+    }
+}
+
+/// Indices of the functions implementing printing and startup
+#[derive(Debug, Clone, Copy, Default)]
+struct Runtime {
+    fd_write: u32,
+    proc_exit: u32,
+    /// `write(fd: i32, ptr: i32, len: i32)`
+    write: u32,
+    /// `print(fd: i32, str: i64)` of a null-terminated string
+    print: u32,
+    newline: u32,
+    /// Index of the first of [`RUNTIME_ROUTINES`]
+    routines: u32,
+    /// Index of the first of [`FORMAT_ROUTINES`], which are followed by
+    /// their helpers, if the module formats strings
+    format: Option<u32>,
+}
+
+/// Lowers MIR to a WebAssembly module for WASI
+pub struct WasmEmitter {
+    /// Layouts used to size stack slots and copy aggregate arguments
+    layouts: LayoutTable,
+    /// Index and signature of each function of the module, keyed by name
+    functions: HashMap<String, (u32, Signature)>,
+    /// Indices of functions imported for callees outside the module
+    imports: HashMap<String, u32>,
+    runtime: Runtime,
+    /// Address of each string constant
+    strings: HashMap<String, u32>,
+    data: Vec<DataSegment>,
+    /// Address the next string constant goes at
+    data_end: u32,
+    /// Local index and type of each value of the current function
+    locals: HashMap<ValueId, (u32, ValType)>,
+    /// Types of the current function's locals after its parameters
+    local_types: Vec<ValType>,
+    param_count: u32,
+    results: Vec<ValType>,
+    body: Vec<WasmInst>,
+    /// Locals holding the frame's base address and the stack pointer on
+    /// entry, once the function needs a frame
+    frame: Option<(u32, u32)>,
+    /// Bytes of stack slots allocated in the current frame
+    slot_bytes: u32,
+    /// Positions in the body of `return` instructions
+    returns: Vec<usize>,
+    structure: Structure,
+}
+
+impl WasmEmitter {
+    pub fn new() -> Self {
+        Self {
+            layouts: LayoutTable::new(),
+            functions: HashMap::new(),
+            imports: HashMap::new(),
+            runtime: Runtime::default(),
+            strings: HashMap::new(),
+            data: Vec::new(),
+            data_end: DATA_START,
+            locals: HashMap::new(),
+            local_types: Vec::new(),
+            param_count: 0,
+            results: Vec::new(),
+            body: Vec::new(),
+            frame: None,
+            slot_bytes: 0,
+            returns: Vec::new(),
+            structure: Structure::default(),
+        }
+    }
+
+    /// Lower a MIR module
+    pub fn emit_module(&mut self, mir_module: &MirModule) -> WasmModule {
+        let mut module = WasmModule::new("main".to_string());
+        self.layouts = mir_module.layouts.clone();
+
+        // Emit in a fixed order so the output is deterministic
+        let mut mir_functions: Vec<_> = mir_module.functions.values().collect();
+        mir_functions.sort_by_key(|func| func.id);
+
+        module.imports = external_imports(&mir_functions);
+        self.imports = module
+            .imports
+            .iter()
+            .enumerate()
+            .skip(2)
+            .map(|(i, import)| (import.name.clone(), i as u32))
+            .collect();
+        let first_function = module.imports.len() as u32;
+        self.runtime = Runtime {
+            fd_write: 0,
+            proc_exit: 1,
+            write: first_function,
+            print: first_function + 1,
+            newline: first_function + 2,
+            routines: first_function + 3,
+            format: formats_strings(&mir_functions).then_some(first_function + 5),
+        };
+        let first_mir_function = match self.runtime.format {
+            Some(format) => format + FORMAT_FUNCTIONS,
+            None => first_function + 5,
+        };
+        self.functions = mir_functions
+            .iter()
+            .enumerate()
+            .map(|(i, func)| (func.name.clone(), (first_mir_function + i as u32, Signature::of(func))))
+            .collect();
+
+        module.functions.extend(self.runtime_functions());
+        if let Some(format) = self.runtime.format {
+            module.functions.extend(self.format_functions(format));
+        }
+        for func in &mir_functions {
+            let wasm_func = self.emit_function(func);
+            module.functions.push(wasm_func);
+        }
+
+        module.data = std::mem::take(&mut self.data);
+        let stack_top = (self.data_end + STACK_SIZE).next_multiple_of(PAGE_SIZE);
+        let start = self.start_function(&module, stack_top);
+        module.functions.push(start);
+        module.memory_pages = stack_top / PAGE_SIZE;
+        module.stack_pointer = stack_top;
+        module
+    }
+
+    /// Lower a MIR function
+    pub fn emit_function(&mut self, mir_func: &MirFunction) -> WasmFunction {
+        let signature = Signature::of(mir_func);
+        let params: Vec<ValType> = signature.params.iter().map(ValType::of).collect();
+        self.results = results_of(&signature.ret);
+        self.locals.clear();
+        self.local_types.clear();
+        self.body.clear();
+        self.returns.clear();
+        self.frame = None;
+        self.slot_bytes = 0;
+        self.param_count = params.len() as u32;
+
+        for (i, (&param, &ty)) in mir_func.params.iter().zip(&params).enumerate() {
+            self.locals.insert(param, (i as u32, ty));
+        }
+        let mut values: Vec<ValueId> = mir_func.values.keys().copied().collect();
+        for block in mir_func.blocks.values() {
+            for inst in &block.instructions {
+                values.extend(inst.dest());
+                values.extend(inst.operands().into_iter().filter_map(|op| match op {
+                    MirOp::Value(value) => Some(*value),
+                    MirOp::Const(_) => None,
+                }));
+            }
+        }
+        values.sort_unstable();
+        values.dedup();
+        for value in values {
+            if !self.locals.contains_key(&value) {
+                let ty = mir_func.value(value).map_or(ValType::I64, |value| ValType::of(&value.ty));
+                let index = self.new_local(ty);
+                self.locals.insert(value, (index, ty));
+            }
+        }
+
+        self.structure = Structure::of(mir_func);
+        self.emit_tree(mir_func.entry, &mut Vec::new(), mir_func);
+        if !self.results.is_empty() {
+            self.push(WasmInst::Unreachable);
+        }
+
+        let mut body = Vec::new();
+        if let Some((fp, base)) = self.frame {
+            // Stack slots are addressed upward from the frame's base, and
+            // every return puts the stack pointer back
+            body.extend([
+                WasmInst::GlobalGet(STACK_POINTER),
+                WasmInst::LocalTee(base),
+                WasmInst::I32Const(self.slot_bytes.next_multiple_of(16) as i32),
+                WasmInst::Num(NumOp::I32Sub),
+                WasmInst::LocalTee(fp),
+                WasmInst::GlobalSet(STACK_POINTER),
+            ]);
+            for &position in self.returns.iter().rev() {
+                self.body.splice(
+                    position..position,
+                    [WasmInst::LocalGet(base), WasmInst::GlobalSet(STACK_POINTER)],
+                );
+            }
+        }
+        body.append(&mut self.body);
+
+        let mut func = WasmFunction::new(mir_func.name.clone(), params, self.results.clone());
+        func.locals = std::mem::take(&mut self.local_types);
+        func.body = body;
+        func
+    }
+
+    /// Emit a block and the blocks it dominates
+    fn emit_tree(&mut self, block: BlockId, enclosing: &mut Vec<Enclosing>, mir_func: &MirFunction) {
+        let merges = self.structure.merge_children(block);
+        if self.structure.loop_headers.contains(&block) {
+            self.push(WasmInst::Loop);
+            enclosing.push(Enclosing::Loop(block));
+            self.emit_within(block, &merges, enclosing, mir_func);
+            enclosing.pop();
+            self.push(WasmInst::End);
+        } else {
+            self.emit_within(block, &merges, enclosing, mir_func);
+        }
+    }
+
+    /// Emit a block inside one wasm block per merge point it dominates,
+    /// each merge point following the end of its wasm block
+    fn emit_within(
+        &mut self,
+        block: BlockId,
+        merges: &[BlockId],
+        enclosing: &mut Vec<Enclosing>,
+        mir_func: &MirFunction,
+    ) {
+        match merges.split_first() {
+            Some((&merge, inner)) => {
+                self.push(WasmInst::Block);
+                enclosing.push(Enclosing::Block(merge));
+                self.emit_within(block, inner, enclosing, mir_func);
+                enclosing.pop();
+                self.push(WasmInst::End);
+                self.emit_tree(merge, enclosing, mir_func);
+            }
+            None => self.emit_block(block, enclosing, mir_func),
+        }
+    }
+
+    /// Emit the instructions of a block; one without a terminator returns
+    fn emit_block(&mut self, block_id: BlockId, enclosing: &mut Vec<Enclosing>, mir_func: &MirFunction) {
+        let Some(block) = mir_func.block(block_id) else {
+            return self.emit_return(None);
+        };
+        let last = block.instructions.len().saturating_sub(1);
+        for (i, inst) in block.instructions.iter().enumerate() {
+            match inst {
+                MirInst::Return { value, .. } => return self.emit_return(value.as_ref()),
+                // The control flow graph only follows the last instruction
+                MirInst::Jump { .. } | MirInst::Branch { .. } if i != last => {}
+                MirInst::Jump { target, .. } => return self.emit_branch(block_id, *target, enclosing, mir_func),
+                MirInst::Branch {
+                    cond,
+                    then_block,
+                    else_block,
+                    ..
+                } => {
+                    if let MirOp::Const(Constant::Bool(taken)) = cond {
+                        let target = if *taken { *then_block } else { *else_block };
+                        return self.emit_branch(block_id, target, enclosing, mir_func);
+                    }
+                    self.push_operand(cond, ValType::I64);
+                    self.push(WasmInst::I64Const(0));
+                    self.push(WasmInst::Num(NumOp::I64Ne));
+                    self.push(WasmInst::If);
+                    enclosing.push(Enclosing::If);
+                    self.emit_branch(block_id, *then_block, enclosing, mir_func);
+                    self.push(WasmInst::Else);
+                    self.emit_branch(block_id, *else_block, enclosing, mir_func);
+                    enclosing.pop();
+                    return self.push(WasmInst::End);
+                }
+                inst => self.emit_instruction(inst, mir_func),
+            }
+        }
+        self.emit_return(None);
+    }
+
+    /// Set the phis of `to` and continue there: back to its loop, out of
+    /// the wasm block it follows, or with its code inline
+    fn emit_branch(&mut self, from: BlockId, to: BlockId, enclosing: &mut Vec<Enclosing>, mir_func: &MirFunction) {
+        self.emit_phi_moves(from, to, mir_func);
+        let target = if self.structure.is_backward(from, to) {
+            Enclosing::Loop(to)
+        } else if self.structure.merges.contains(&to) {
+            Enclosing::Block(to)
+        } else {
+            return self.emit_tree(to, enclosing, mir_func);
+        };
+        match enclosing.iter().rev().position(|&outer| outer == target) {
+            Some(depth) => self.push(WasmInst::Br(depth as u32)),
+            // Only an irreducible control flow graph branches elsewhere
+            None => self.push(WasmInst::Unreachable),
+        }
+    }
+
+    /// Set the phis of `to` from their inputs on the edge from `from`
+    ///
+    /// Every input is read before any phi is set, since a phi can be the
+    /// input of another.
+    fn emit_phi_moves(&mut self, from: BlockId, to: BlockId, mir_func: &MirFunction) {
+        let Some(block) = mir_func.block(to) else {
+            return;
+        };
+        let moves: Vec<(ValueId, &MirOp)> = block
+            .instructions
+            .iter()
+            .filter_map(|inst| match inst {
+                MirInst::Phi { dest, inputs, .. } => inputs
+                    .iter()
+                    .find(|(pred, _)| *pred == from)
+                    .map(|(_, input)| (*dest, input)),
+                _ => None,
+            })
+            .collect();
+        for &(dest, input) in &moves {
+            let ty = self.local(dest).1;
+            self.push_operand(input, ty);
+        }
+        for &(dest, _) in moves.iter().rev() {
+            let index = self.local(dest).0;
+            self.push(WasmInst::LocalSet(index));
+        }
+    }
+
+    fn emit_return(&mut self, value: Option<&MirOp>) {
+        if let Some(&result) = self.results.first() {
+            self.push_operand(value.unwrap_or(&MirOp::Const(Constant::Int(0))), result);
+        }
+        self.returns.push(self.body.len());
+        self.push(WasmInst::Return);
+    }
+
+    fn emit_instruction(&mut self, inst: &MirInst, mir_func: &MirFunction) {
+        match inst {
+            MirInst::Assign { dest, value, .. } => {
+                let ty = self.local(*dest).1;
+                self.push_operand(value, ty);
+                self.set(*dest, ty);
+            }
+
+            MirInst::BinOp {
+                dest, op, lhs, rhs, ..
+            } => match self.local(*dest).1.precision().or_else(|| self.float_precision(&[lhs, rhs])) {
+                Some(precision) => self.emit_float_binop(*dest, op, lhs, rhs, precision),
+                None => self.emit_binop(*dest, op, lhs, rhs),
+            },
+
+            MirInst::UnaryOp { dest, op, value, .. } => {
+                let ty = match self.float_precision(&[value]) {
+                    Some(precision) if *op == UnaryOp::Neg => ValType::from(precision),
+                    _ => ValType::I64,
+                };
+                self.push_operand(value, ty);
+                match (op, ty) {
+                    (UnaryOp::Neg, ValType::F32) => self.push(WasmInst::Num(NumOp::F32Neg)),
+                    (UnaryOp::Neg, ValType::F64) => self.push(WasmInst::Num(NumOp::F64Neg)),
+                    (UnaryOp::Neg, _) => {
+                        self.push(WasmInst::I64Const(-1));
+                        self.push(WasmInst::Num(NumOp::I64Mul));
+                    }
+                    (UnaryOp::Not, _) => {
+                        self.push(WasmInst::I64Const(1));
+                        self.push(WasmInst::Num(NumOp::I64Xor));
+                    }
+                    (UnaryOp::BitNot, _) => {
+                        self.push(WasmInst::I64Const(-1));
+                        self.push(WasmInst::Num(NumOp::I64Xor));
+                    }
+                }
+                self.set(*dest, ty);
+            }
+
+            MirInst::Call { dest, func: callee, args, .. } => self.emit_call(dest, callee, args, mir_func),
+
+            MirInst::Load { dest, ptr, .. } => {
+                let ty = self.local(*dest).1;
+                self.push_address(ptr);
+                self.push(WasmInst::Load {
+                    access: Access::of(ty),
+                    offset: 0,
+                });
+                self.set(*dest, ty);
+            }
+
+            MirInst::Store { ptr, value, .. } => {
+                // A float is stored at the width of the pointed-to type
+                let stored = match ptr {
+                    MirOp::Value(ptr) => match mir_func.value(*ptr).map(|value| &value.ty) {
+                        Some(Type::Ptr { inner, .. } | Type::Ref { inner, .. }) => Precision::of(inner),
+                        _ => None,
+                    },
+                    MirOp::Const(_) => None,
+                };
+                let ty = stored
+                    .or_else(|| self.float_precision(&[value]))
+                    .map_or(ValType::I64, ValType::from);
+                self.push_address(ptr);
+                self.push_operand(value, ty);
+                self.push(WasmInst::Store {
+                    access: Access::of(ty),
+                    offset: 0,
+                });
+            }
+
+            MirInst::Alloca { dest, ty, .. } => {
+                let offset = self.frame_slot(ty);
+                self.push_slot_address(offset);
+                self.set(*dest, ValType::I64);
+            }
+
+            MirInst::Cast { dest, value, .. } => self.emit_cast(*dest, value),
+
+            MirInst::GetElement { dest, base, index, .. } => {
+                self.push_operand(base, ValType::I64);
+                self.push_operand(index, ValType::I64);
+                self.push(WasmInst::Num(NumOp::I64Add));
+                self.set(*dest, ValType::I64);
+            }
+
+            // Phis are set by the branches to their block, and the other
+            // terminators are emitted with the control flow
+            MirInst::Phi { .. } | MirInst::Return { .. } | MirInst::Jump { .. } | MirInst::Branch { .. } => {}
+        }
+    }
+
+    fn emit_binop(&mut self, dest: ValueId, op: &BinOp, lhs: &MirOp, rhs: &MirOp) {
+        let (op, compare) = match op {
+            BinOp::Add => (NumOp::I64Add, false),
+            BinOp::Sub => (NumOp::I64Sub, false),
+            BinOp::Mul => (NumOp::I64Mul, false),
+            BinOp::Div => (NumOp::I64DivS, false),
+            BinOp::Mod => (NumOp::I64RemS, false),
+            BinOp::BitAnd | BinOp::And => (NumOp::I64And, false),
+            BinOp::BitOr | BinOp::Or => (NumOp::I64Or, false),
+            BinOp::BitXor => (NumOp::I64Xor, false),
+            BinOp::Shl => (NumOp::I64Shl, false),
+            BinOp::Shr => (NumOp::I64ShrU, false),
+            BinOp::Eq => (NumOp::I64Eq, true),
+            BinOp::Ne => (NumOp::I64Ne, true),
+            BinOp::Lt => (NumOp::I64LtS, true),
+            BinOp::Le => (NumOp::I64LeS, true),
+            BinOp::Gt => (NumOp::I64GtS, true),
+            BinOp::Ge => (NumOp::I64GeS, true),
+        };
+        self.push_operand(lhs, ValType::I64);
+        self.push_operand(rhs, ValType::I64);
+        self.push(WasmInst::Num(op));
+        if compare {
+            self.push(WasmInst::Num(NumOp::I64ExtendI32U));
+        }
+        self.set(dest, ValType::I64);
+    }
+
+    fn emit_float_binop(&mut self, dest: ValueId, op: &BinOp, lhs: &MirOp, rhs: &MirOp, precision: Precision) {
+        let single = precision == Precision::Single;
+        let pick = |f32_op, f64_op| if single { f32_op } else { f64_op };
+        let (op, compare) = match op {
+            BinOp::Add => (pick(NumOp::F32Add, NumOp::F64Add), false),
+            BinOp::Sub => (pick(NumOp::F32Sub, NumOp::F64Sub), false),
+            BinOp::Mul => (pick(NumOp::F32Mul, NumOp::F64Mul), false),
+            BinOp::Div => (pick(NumOp::F32Div, NumOp::F64Div), false),
+            BinOp::Eq => (pick(NumOp::F32Eq, NumOp::F64Eq), true),
+            BinOp::Ne => (pick(NumOp::F32Ne, NumOp::F64Ne), true),
+            BinOp::Lt => (pick(NumOp::F32Lt, NumOp::F64Lt), true),
+            BinOp::Le => (pick(NumOp::F32Le, NumOp::F64Le), true),
+            BinOp::Gt => (pick(NumOp::F32Gt, NumOp::F64Gt), true),
+            BinOp::Ge => (pick(NumOp::F32Ge, NumOp::F64Ge), true),
+            // There is no float remainder or bitwise arithmetic
+            _ => return,
+        };
+        let ty = ValType::from(precision);
+        self.push_operand(lhs, ty);
+        self.push_operand(rhs, ty);
+        self.push(WasmInst::Num(op));
+        if compare {
+            self.push(WasmInst::Num(NumOp::I64ExtendI32U));
+            self.set(dest, ValType::I64);
+        } else {
+            self.set(dest, ty);
+        }
+    }
+
+    /// Convert between integers and floats of either precision
+    fn emit_cast(&mut self, dest: ValueId, value: &MirOp) {
+        let from = self.float_precision(&[value]);
+        let to = self.local(dest).1.precision();
+        let (ty, op) = match (from, to) {
+            (None, None) => (ValType::I64, None),
+            (Some(from), Some(to)) if from == to => (ValType::from(from), None),
+            (Some(from), Some(_)) => (
+                ValType::from(from),
+                Some(match from {
+                    Precision::Single => NumOp::F64PromoteF32,
+                    Precision::Double => NumOp::F32DemoteF64,
+                }),
+            ),
+            (Some(from), None) => (
+                ValType::from(from),
+                Some(match from {
+                    Precision::Single => NumOp::I64TruncSatF32S,
+                    Precision::Double => NumOp::I64TruncSatF64S,
+                }),
+            ),
+            (None, Some(to)) => (
+                ValType::I64,
+                Some(match to {
+                    Precision::Single => NumOp::F32ConvertI64S,
+                    Precision::Double => NumOp::F64ConvertI64S,
+                }),
+            ),
+        };
+        self.push_operand(value, ty);
+        let converted = match op {
+            Some(op) => {
+                self.push(WasmInst::Num(op));
+                to.map_or(ValType::I64, ValType::from)
+            }
+            None => ty,
+        };
+        self.set(dest, converted);
+    }
+
+    fn emit_call(&mut self, dest: &Option<ValueId>, callee: &MirOp, args: &[MirOp], mir_func: &MirFunction) {
+        // There is no function table, so only direct calls are supported
+        let MirOp::Const(Constant::String(name)) = callee else {
+            return self.push(WasmInst::Unreachable);
+        };
+
+        if let (Some(dest), [arg]) = (dest, args) {
+            let op = match name.as_str() {
+                "f32.sqrt" => Some((ValType::F32, NumOp::F32Sqrt)),
+                "f64.sqrt" => Some((ValType::F64, NumOp::F64Sqrt)),
+                _ => None,
+            };
+            if let Some((ty, op)) = op {
+                self.push_operand(arg, ty);
+                self.push(WasmInst::Num(op));
+                return self.set(*dest, ty);
+            }
+        }
+
+        if let Some((index, signature)) = self.functions.get(name).cloned() {
+            // Aggregates are passed as the address of a copy, except for
+            // the slot an aggregate result is written to
+            let returns_aggregate = self.layouts.is_aggregate(&signature.ret);
+            for (i, ty) in signature.params.iter().enumerate() {
+                let arg = args.get(i).unwrap_or(&MirOp::Const(Constant::Int(0)));
+                if self.layouts.is_aggregate(ty) && !(i == 0 && returns_aggregate) {
+                    self.push_copy(arg, ty);
+                } else {
+                    self.push_operand(arg, ValType::of(ty));
+                }
+            }
+            self.push(WasmInst::Call(index));
+            return self.take_result(&results_of(&signature.ret), dest);
+        }
+
+        let routine = FORMAT_ROUTINES.iter().position(|(routine, ..)| routine == name);
+        if let (Some(format), Some(position)) = (self.runtime.format, routine) {
+            let (_, params, results) = FORMAT_ROUTINES[position];
+            for (arg, &ty) in args.iter().zip(params) {
+                self.push_operand(arg, ty);
+            }
+            self.push(WasmInst::Call(format + position as u32));
+            return self.take_result(results, dest);
+        }
+
+        let routine = RUNTIME_ROUTINES.iter().position(|(routine, ..)| routine == name);
+        if let Some(position) = routine {
+            let (_, params, results) = RUNTIME_ROUTINES[position];
+            for (i, &ty) in params.iter().enumerate() {
+                self.push_operand(args.get(i).unwrap_or(&MirOp::Const(Constant::Int(0))), ty);
+            }
+            self.push(WasmInst::Call(self.runtime.routines + position as u32));
+            return self.take_result(results, dest);
+        }
+
+        // Like the C runtime's, these print their first argument only
+        if name == "print" || name == "println" {
+            if let Some(arg) = args.first() {
+                self.push(WasmInst::I32Const(STDOUT));
+                self.push_operand(arg, ValType::I64);
+                self.push(WasmInst::Call(self.runtime.print));
+            }
+            if name == "println" {
+                self.push(WasmInst::Call(self.runtime.newline));
+            }
+            return self.take_result(&[], dest);
+        }
+
+        let index = self.imports[name];
+        for arg in args {
+            self.push_operand(arg, operand_type(arg, mir_func));
+        }
+        self.push(WasmInst::Call(index));
+        let results: Vec<_> = dest.iter().map(|&dest| self.local(dest).1).collect();
+        self.take_result(&results, dest);
+    }
+
+    /// Set a call's destination from its result, dropping an unused result
+    /// and setting a destination without one to zero
+    fn take_result(&mut self, results: &[ValType], dest: &Option<ValueId>) {
+        match (results.first(), dest) {
+            (Some(&result), Some(dest)) => self.set(*dest, result),
+            (Some(_), None) => self.push(WasmInst::Drop),
+            (None, Some(dest)) => {
+                let ty = self.local(*dest).1;
+                self.push_operand(&MirOp::Const(Constant::Int(0)), ty);
+                self.set(*dest, ty);
+            }
+            (None, None) => {}
+        }
+    }
+
+    /// Copy an aggregate to a new stack slot and push the slot's address
+    fn push_copy(&mut self, arg: &MirOp, ty: &Type) {
+        let offset = self.frame_slot(ty);
+        let fp = self.frame_pointer();
+        let size = self.layouts.size_of(ty).next_multiple_of(WORD_SIZE) as u32;
+        for word in (0..size).step_by(WORD_SIZE as usize) {
+            self.push(WasmInst::LocalGet(fp));
+            self.push_address(arg);
+            self.push(WasmInst::Load {
+                access: Access::I64,
+                offset: word,
+            });
+            self.push(WasmInst::Store {
+                access: Access::I64,
+                offset: offset + word,
+            });
+        }
+        self.push_slot_address(offset);
+    }
+
+    /// Reserve a slot for a value of type `ty` in the frame, returning its
+    /// offset from the frame's base
+    fn frame_slot(&mut self, ty: &Type) -> u32 {
+        let size = self.layouts.size_of(ty).max(1).next_multiple_of(WORD_SIZE) as u32;
+        let offset = self.slot_bytes;
+        self.slot_bytes += size;
+        offset
+    }
+
+    /// Local holding the frame's base address, added on first use
+    fn frame_pointer(&mut self) -> u32 {
+        match self.frame {
+            Some((fp, _)) => fp,
+            None => {
+                let fp = self.new_local(ValType::I32);
+                let base = self.new_local(ValType::I32);
+                self.frame = Some((fp, base));
+                fp
+            }
+        }
+    }
+
+    /// Push the address of a stack slot as an i64
+    fn push_slot_address(&mut self, offset: u32) {
+        let fp = self.frame_pointer();
+        self.push(WasmInst::LocalGet(fp));
+        if offset > 0 {
+            self.push(WasmInst::I32Const(offset as i32));
+            self.push(WasmInst::Num(NumOp::I32Add));
+        }
+        self.push(WasmInst::Num(NumOp::I64ExtendI32U));
+    }
+
+    /// Push a pointer operand as an i32 address
+    fn push_address(&mut self, op: &MirOp) {
+        self.push_operand(op, ValType::I64);
+        self.push(WasmInst::Num(NumOp::I32WrapI64));
+    }
+
+    /// Push an operand as a value of type `ty`
+    ///
+    /// Integer constants are converted to floats by value, and other
+    /// operands of the wrong type keep their bits.
+    fn push_operand(&mut self, op: &MirOp, ty: ValType) {
+        let int = match op {
+            MirOp::Value(value) => {
+                let (index, from) = self.local(*value);
+                self.push(WasmInst::LocalGet(index));
+                return self.convert(from, ty);
+            }
+            MirOp::Const(Constant::Float(bits)) => {
+                return self.push(match ty {
+                    ValType::I32 => WasmInst::I32Const(*bits as i32),
+                    ValType::I64 => WasmInst::I64Const(*bits as i64),
+                    ValType::F32 => WasmInst::F32Const((f64::from_bits(*bits) as f32).to_bits()),
+                    ValType::F64 => WasmInst::F64Const(*bits),
+                });
+            }
+            MirOp::Const(Constant::Int(int)) => *int,
+            MirOp::Const(Constant::Bool(b)) => i64::from(*b),
+            MirOp::Const(Constant::Unit) => 0,
+            MirOp::Const(Constant::String(s)) => i64::from(self.string_constant(s)),
+        };
+        self.push(match ty {
+            ValType::I32 => WasmInst::I32Const(int as i32),
+            ValType::I64 => WasmInst::I64Const(int),
+            ValType::F32 => WasmInst::F32Const((int as f32).to_bits()),
+            ValType::F64 => WasmInst::F64Const((int as f64).to_bits()),
+        });
+    }
+
+    /// Convert the value on top of the stack between types, keeping its
+    /// bits between integers and floats
+    fn convert(&mut self, from: ValType, to: ValType) {
+        let ops: &[NumOp] = match (from, to) {
+            (ValType::I32, ValType::I64) => &[NumOp::I64ExtendI32U],
+            (ValType::I32, ValType::F32) => &[NumOp::F32ReinterpretI32],
+            (ValType::I32, ValType::F64) => &[NumOp::I64ExtendI32U, NumOp::F64ReinterpretI64],
+            (ValType::I64, ValType::I32) => &[NumOp::I32WrapI64],
+            (ValType::I64, ValType::F32) => &[NumOp::I32WrapI64, NumOp::F32ReinterpretI32],
+            (ValType::I64, ValType::F64) => &[NumOp::F64ReinterpretI64],
+            (ValType::F32, ValType::I32) => &[NumOp::I32ReinterpretF32],
+            (ValType::F32, ValType::I64) => &[NumOp::I32ReinterpretF32, NumOp::I64ExtendI32U],
+            (ValType::F32, ValType::F64) => &[NumOp::F64PromoteF32],
+            (ValType::F64, ValType::I32) => &[NumOp::I64ReinterpretF64, NumOp::I32WrapI64],
+            (ValType::F64, ValType::I64) => &[NumOp::I64ReinterpretF64],
+            (ValType::F64, ValType::F32) => &[NumOp::F32DemoteF64],
+            _ => &[],
+        };
+        for &op in ops {
+            self.push(WasmInst::Num(op));
+        }
+    }
+
+    /// Set a value from the top of the stack, of type `ty`
+    fn set(&mut self, dest: ValueId, ty: ValType) {
+        let (index, local_ty) = self.local(dest);
+        self.convert(ty, local_ty);
+        self.push(WasmInst::LocalSet(index));
+    }
+
+    /// Local index and type of a value
+    fn local(&self, value: ValueId) -> (u32, ValType) {
+        self.locals.get(&value).copied().unwrap_or((0, ValType::I64))
+    }
+
+    fn new_local(&mut self, ty: ValType) -> u32 {
+        self.local_types.push(ty);
+        self.param_count + self.local_types.len() as u32 - 1
+    }
+
+    /// Precision of the first float among `ops`, if any is a float
+    fn float_precision(&self, ops: &[&MirOp]) -> Option<Precision> {
+        let values = ops.iter().find_map(|op| match op {
+            MirOp::Value(value) => self.locals.get(value).and_then(|(_, ty)| ty.precision()),
+            MirOp::Const(_) => None,
+        });
+        values.or_else(|| {
+            ops.iter()
+                .any(|op| matches!(op, MirOp::Const(Constant::Float(_))))
+                .then_some(Precision::Double)
+        })
+    }
+
+    /// Address of a null-terminated string constant
+    fn string_constant(&mut self, content: &str) -> u32 {
+        if let Some(&address) = self.strings.get(content) {
+            return address;
+        }
+        let address = self.data_end;
+        let mut bytes = content.as_bytes().to_vec();
+        bytes.push(0);
+        self.data_end += bytes.len() as u32;
+        self.data.push(DataSegment { offset: address, bytes });
+        self.strings.insert(content.to_string(), address);
+        address
+    }
+
+    fn push(&mut self, inst: WasmInst) {
+        self.body.push(inst);
+    }
+
+    /// `write`, `print`, `newline` and [`RUNTIME_ROUTINES`], at the
+    /// indices in [`Runtime`]
+    fn runtime_functions(&mut self) -> Vec<WasmFunction> {
+        use WasmInst::*;
+        let runtime = self.runtime;
+        let newline = self.string_constant("\n");
+        let (panic_text, colon, separator) = (
+            self.string_constant("Aurora panic at "),
+            self.string_constant(":"),
+            self.string_constant(": "),
+        );
+
+        let mut write = WasmFunction::new("write".to_string(), vec![ValType::I32; 3], Vec::new());
+        write.body = vec![
+            I32Const(IOVEC),
+            LocalGet(1),
+            Store { access: Access::I32, offset: 0 },
+            I32Const(IOVEC),
+            LocalGet(2),
+            Store { access: Access::I32, offset: 4 },
+            LocalGet(0),
+            I32Const(IOVEC),
+            I32Const(1),
+            I32Const(NWRITTEN),
+            Call(runtime.fd_write),
+            Drop,
+        ];
+
+        // Scan for the terminating null, then write what precedes it
+        let mut print = WasmFunction::new("print".to_string(), vec![ValType::I32, ValType::I64], Vec::new());
+        print.locals = vec![ValType::I32; 2];
+        print.body = vec![
+            LocalGet(1),
+            Num(NumOp::I32WrapI64),
+            LocalTee(2),
+            LocalSet(3),
+            Block,
+            Loop,
+            LocalGet(3),
+            Load { access: Access::Byte32, offset: 0 },
+            Num(NumOp::I32Eqz),
+            BrIf(1),
+            LocalGet(3),
+            I32Const(1),
+            Num(NumOp::I32Add),
+            LocalSet(3),
+            Br(0),
+            End,
+            End,
+            LocalGet(0),
+            LocalGet(2),
+            LocalGet(3),
+            LocalGet(2),
+            Num(NumOp::I32Sub),
+            Call(runtime.write),
+        ];
+
+        let mut newline_func = WasmFunction::new("newline".to_string(), Vec::new(), Vec::new());
+        newline_func.body = vec![I32Const(STDOUT), I32Const(newline as i32), I32Const(1), Call(runtime.write)];
+
+        let mut routines: Vec<WasmFunction> = RUNTIME_ROUTINES
+            .iter()
+            .map(|(name, params, results)| WasmFunction::new(name.to_string(), params.to_vec(), results.to_vec()))
+            .collect();
+
+        // aurora_str_eq(a: i64, b: i64) -> i64, comparing bytes up to the
+        // first difference or the null ending both
+        let str_eq = &mut routines[0];
+        str_eq.locals = vec![ValType::I32; 3];
+        str_eq.body = vec![
+            LocalGet(0),
+            Num(NumOp::I32WrapI64),
+            LocalSet(2),
+            LocalGet(1),
+            Num(NumOp::I32WrapI64),
+            LocalSet(3),
+            Block,
+            Loop,
+            LocalGet(2),
+            Load { access: Access::Byte32, offset: 0 },
+            LocalTee(4),
+            LocalGet(3),
+            Load { access: Access::Byte32, offset: 0 },
+            Num(NumOp::I32Sub),
+            BrIf(1),
+            LocalGet(4),
+            Num(NumOp::I32Eqz),
+            If,
+            I64Const(1),
+            Return,
+            End,
+            LocalGet(2),
+            I32Const(1),
+            Num(NumOp::I32Add),
+            LocalSet(2),
+            LocalGet(3),
+            I32Const(1),
+            Num(NumOp::I32Add),
+            LocalSet(3),
+            Br(0),
+            End,
+            End,
+            I64Const(0),
+        ];
+
+        // aurora_panic(msg: i64, file: i64, line: i64), printing to
+        // standard error like the C runtime's, the line's digits written
+        // backwards from DIGITS_END, then trapping
+        let panic = &mut routines[1];
+        panic.locals = vec![ValType::I32];
+        let print_err = |text: WasmInst| [I32Const(STDERR), text, Call(runtime.print)];
+        panic.body = [
+            &print_err(I64Const(i64::from(panic_text)))[..],
+            &print_err(LocalGet(1)),
+            &print_err(I64Const(i64::from(colon))),
+            &[
+                I32Const(DIGITS_END),
+                LocalSet(3),
+                Loop,
+                LocalGet(3),
+                I32Const(1),
+                Num(NumOp::I32Sub),
+                LocalTee(3),
+                LocalGet(2),
+                I64Const(10),
+                Num(NumOp::I64RemU),
+                I64Const(i64::from(b'0')),
+                Num(NumOp::I64Add),
+                Store { access: Access::Byte64, offset: 0 },
+                LocalGet(2),
+                I64Const(10),
+                Num(NumOp::I64DivU),
+                LocalTee(2),
+                I64Const(0),
+                Num(NumOp::I64Ne),
+                BrIf(0),
+                End,
+                I32Const(STDERR),
+                LocalGet(3),
+                I32Const(DIGITS_END),
+                LocalGet(3),
+                Num(NumOp::I32Sub),
+                Call(runtime.write),
+            ],
+            &print_err(I64Const(i64::from(separator))),
+            &print_err(LocalGet(0)),
+            &print_err(I64Const(i64::from(newline))),
+            &[Unreachable],
+        ]
+        .concat();
+
+        let mut functions = vec![write, print, newline_func];
+        functions.extend(routines);
+        functions
+    }
+
+    /// [`FORMAT_ROUTINES`] and their helpers, from index `format`
+    ///
+    /// `append(ptr: i32, len: i32)` copies bytes to the end of the heap,
+    /// leaving room for a null, and `digits(value: i64) -> i32` writes an
+    /// unsigned number backwards from [`DIGITS_END`], returning its start.
+    /// Floats are formatted like the C runtime's, with at most six decimals
+    /// and an exponent from 1e13.
+    fn format_functions(&mut self, format: u32) -> Vec<WasmFunction> {
+        use WasmInst::*;
+        let routine = |name: &str| {
+            let position = FORMAT_ROUTINES.iter().position(|(routine, ..)| *routine == name);
+            format + position.unwrap() as u32
+        };
+        let (append, append_byte, digits) = (
+            format + FORMAT_FUNCTIONS - 3,
+            format + FORMAT_FUNCTIONS - 2,
+            format + FORMAT_FUNCTIONS - 1,
+        );
+        let fmt_str = routine("aurora_fmt_str");
+        let fmt_u64 = routine("aurora_fmt_u64");
+        let fmt_f64 = routine("aurora_fmt_f64");
+        let (true_text, false_text) = (self.string_constant("true"), self.string_constant("false"));
+        let (nan_text, inf_text) = (self.string_constant("NaN"), self.string_constant("inf"));
+        let heap_end = [I32Const(HEAP_POINTER), Load { access: Access::I32, offset: 0 }];
+
+        let mut functions: Vec<WasmFunction> = FORMAT_ROUTINES
+            .iter()
+            .map(|(name, params, results)| WasmFunction::new(name.to_string(), params.to_vec(), results.to_vec()))
+            .collect();
+        let mut routines = functions.iter_mut();
+        let mut next = || routines.next().unwrap();
+
+        // aurora_fmt_new() -> i64
+        next().body = [&heap_end[..], &[Num(NumOp::I64ExtendI32U)]].concat();
+
+        // aurora_fmt_str(buffer: i64, str: i64), scanning for the null
+        // like `print`
+        let func = next();
+        func.locals = vec![ValType::I32; 2];
+        func.body = vec![
+            LocalGet(1),
+            Num(NumOp::I32WrapI64),
+            LocalTee(2),
+            LocalSet(3),
+            Block,
+            Loop,
+            LocalGet(3),
+            Load { access: Access::Byte32, offset: 0 },
+            Num(NumOp::I32Eqz),
+            BrIf(1),
+            LocalGet(3),
+            I32Const(1),
+            Num(NumOp::I32Add),
+            LocalSet(3),
+            Br(0),
+            End,
+            End,
+            LocalGet(2),
+            LocalGet(3),
+            LocalGet(2),
+            Num(NumOp::I32Sub),
+            Call(append),
+        ];
+
+        // aurora_fmt_i64(buffer: i64, value: i64), as the sign and the
+        // magnitude, so the minimum is exact
+        next().body = vec![
+            LocalGet(1),
+            I64Const(0),
+            Num(NumOp::I64LtS),
+            If,
+            I32Const(i32::from(b'-')),
+            Call(append_byte),
+            I64Const(0),
+            LocalGet(1),
+            Num(NumOp::I64Sub),
+            LocalSet(1),
+            End,
+            LocalGet(0),
+            LocalGet(1),
+            Call(fmt_u64),
+        ];
+
+        // aurora_fmt_u64(buffer: i64, value: i64)
+        let func = next();
+        func.locals = vec![ValType::I32];
+        func.body = vec![
+            LocalGet(1),
+            Call(digits),
+            LocalTee(2),
+            I32Const(DIGITS_END),
+            LocalGet(2),
+            Num(NumOp::I32Sub),
+            Call(append),
+        ];
+
+        // aurora_fmt_f32(buffer: i64, value: f32)
+        next().body = vec![LocalGet(0), LocalGet(1), Num(NumOp::F64PromoteF32), Call(fmt_f64)];
+
+        // aurora_fmt_f64(buffer: i64, value: f64), with locals for the
+        // exponent and the value scaled to six decimals
+        let func = next();
+        func.locals = vec![ValType::I64, ValType::I64, ValType::I32];
+        func.body = vec![
+            LocalGet(1),
+            LocalGet(1),
+            Num(NumOp::F64Ne),
+            If,
+            LocalGet(0),
+            I64Const(i64::from(nan_text)),
+            Call(fmt_str),
+            Return,
+            End,
+            // Test the sign bit, so that -0 keeps its sign
+            LocalGet(1),
+            Num(NumOp::I64ReinterpretF64),
+            I64Const(0),
+            Num(NumOp::I64LtS),
+            If,
+            I32Const(i32::from(b'-')),
+            Call(append_byte),
+            LocalGet(1),
+            Num(NumOp::F64Neg),
+            LocalSet(1),
+            End,
+            LocalGet(1),
+            F64Const(f64::MAX.to_bits()),
+            Num(NumOp::F64Gt),
+            If,
+            LocalGet(0),
+            I64Const(i64::from(inf_text)),
+            Call(fmt_str),
+            Return,
+            End,
+            LocalGet(1),
+            F64Const(1e13f64.to_bits()),
+            Num(NumOp::F64Ge),
+            If,
+            Block,
+            Loop,
+            LocalGet(1),
+            F64Const(10.0f64.to_bits()),
+            Num(NumOp::F64Lt),
+            BrIf(1),
+            LocalGet(1),
+            F64Const(10.0f64.to_bits()),
+            Num(NumOp::F64Div),
+            LocalSet(1),
+            LocalGet(2),
+            I64Const(1),
+            Num(NumOp::I64Add),
+            LocalSet(2),
+            Br(0),
+            End,
+            End,
+            End,
+            LocalGet(1),
+            F64Const(1e6f64.to_bits()),
+            Num(NumOp::F64Mul),
+            F64Const(0.5f64.to_bits()),
+            Num(NumOp::F64Add),
+            Num(NumOp::I64TruncSatF64U),
+            LocalSet(3),
+            // A mantissa rounded up to 10
+            LocalGet(2),
+            Num(NumOp::I64Eqz),
+            Num(NumOp::I32Eqz),
+            If,
+            LocalGet(3),
+            I64Const(10_000_000),
+            Num(NumOp::I64GeS),
+            If,
+            LocalGet(3),
+            I64Const(10),
+            Num(NumOp::I64DivU),
+            LocalSet(3),
+            LocalGet(2),
+            I64Const(1),
+            Num(NumOp::I64Add),
+            LocalSet(2),
+            End,
+            End,
+            LocalGet(0),
+            LocalGet(3),
+            I64Const(1_000_000),
+            Num(NumOp::I64DivU),
+            Call(fmt_u64),
+            // The fraction's digits, without trailing zeros, follow a 1
+            // that is overwritten with the point
+            LocalGet(3),
+            I64Const(1_000_000),
+            Num(NumOp::I64RemU),
+            LocalTee(3),
+            Num(NumOp::I64Eqz),
+            Num(NumOp::I32Eqz),
+            If,
+            LocalGet(3),
+            I64Const(1_000_000),
+            Num(NumOp::I64Add),
+            LocalSet(3),
+            Block,
+            Loop,
+            LocalGet(3),
+            I64Const(10),
+            Num(NumOp::I64RemU),
+            Num(NumOp::I64Eqz),
+            Num(NumOp::I32Eqz),
+            BrIf(1),
+            LocalGet(3),
+            I64Const(10),
+            Num(NumOp::I64DivU),
+            LocalSet(3),
+            Br(0),
+            End,
+            End,
+            LocalGet(3),
+            Call(digits),
+            LocalTee(4),
+            I32Const(i32::from(b'.')),
+            Store { access: Access::Byte32, offset: 0 },
+            LocalGet(4),
+            I32Const(DIGITS_END),
+            LocalGet(4),
+            Num(NumOp::I32Sub),
+            Call(append),
+            End,
+            LocalGet(2),
+            Num(NumOp::I64Eqz),
+            Num(NumOp::I32Eqz),
+            If,
+            I32Const(i32::from(b'e')),
+            Call(append_byte),
+            LocalGet(0),
+            LocalGet(2),
+            Call(fmt_u64),
+            End,
+        ];
+
+        // aurora_fmt_bool(buffer: i64, value: i64)
+        next().body = vec![
+            LocalGet(1),
+            Num(NumOp::I64Eqz),
+            If,
+            LocalGet(0),
+            I64Const(i64::from(false_text)),
+            Call(fmt_str),
+            Else,
+            LocalGet(0),
+            I64Const(i64::from(true_text)),
+            Call(fmt_str),
+            End,
+        ];
+
+        // aurora_fmt_char(buffer: i64, c: i64) in UTF-8: continuation
+        // bytes are written last first, then the lead byte, marked with
+        // the length unless it is a single byte
+        let func = next();
+        func.locals = vec![ValType::I32; 2];
+        func.body = vec![
+            I32Const(1),
+            LocalGet(1),
+            I64Const(0x80),
+            Num(NumOp::I64GeS),
+            Num(NumOp::I32Add),
+            LocalGet(1),
+            I64Const(0x800),
+            Num(NumOp::I64GeS),
+            Num(NumOp::I32Add),
+            LocalGet(1),
+            I64Const(0x10000),
+            Num(NumOp::I64GeS),
+            Num(NumOp::I32Add),
+            LocalTee(2),
+            LocalSet(3),
+            Block,
+            Loop,
+            LocalGet(3),
+            I32Const(1),
+            Num(NumOp::I32Sub),
+            LocalTee(3),
+            Num(NumOp::I32Eqz),
+            BrIf(1),
+            LocalGet(3),
+            LocalGet(1),
+            I64Const(0x3F),
+            Num(NumOp::I64And),
+            I64Const(0x80),
+            Num(NumOp::I64Or),
+            Store { access: Access::Byte64, offset: CHAR_BYTES as u32 },
+            LocalGet(1),
+            I64Const(6),
+            Num(NumOp::I64ShrU),
+            LocalSet(1),
+            Br(0),
+            End,
+            End,
+            LocalGet(2),
+            I32Const(1),
+            Num(NumOp::I32Sub),
+            If,
+            LocalGet(1),
+            I64Const(0xFF00),
+            LocalGet(2),
+            Num(NumOp::I64ExtendI32U),
+            Num(NumOp::I64ShrU),
+            Num(NumOp::I64Or),
+            LocalSet(1),
+            End,
+            I32Const(CHAR_BYTES),
+            LocalGet(1),
+            Store { access: Access::Byte64, offset: 0 },
+            I32Const(CHAR_BYTES),
+            LocalGet(2),
+            Call(append),
+        ];
+
+        // aurora_fmt_finish(buffer: i64) -> i64, null-terminating the string
+        next().body = vec![I32Const(0), Call(append_byte), LocalGet(0)];
+
+        // aurora_fmt_free(str: i64), giving the heap back when the string
+        // is the last one on it
+        let func = next();
+        func.locals = vec![ValType::I32];
+        func.body = [
+            &[
+                LocalGet(0),
+                Num(NumOp::I32WrapI64),
+                LocalSet(1),
+                Block,
+                Loop,
+                LocalGet(1),
+                Load { access: Access::Byte32, offset: 0 },
+                Num(NumOp::I32Eqz),
+                BrIf(1),
+                LocalGet(1),
+                I32Const(1),
+                Num(NumOp::I32Add),
+                LocalSet(1),
+                Br(0),
+                End,
+                End,
+                LocalGet(1),
+                I32Const(1),
+                Num(NumOp::I32Add),
+            ][..],
+            &heap_end[..],
+            &[
+                Num(NumOp::I32Sub),
+                Num(NumOp::I32Eqz),
+                If,
+                I32Const(HEAP_POINTER),
+                LocalGet(0),
+                Num(NumOp::I32WrapI64),
+                Store { access: Access::I32, offset: 0 },
+                End,
+            ],
+        ]
+        .concat();
+
+        // append(ptr: i32, len: i32), growing memory when the bytes and a
+        // null do not fit
+        let mut append_func = WasmFunction::new("append".to_string(), vec![ValType::I32; 2], Vec::new());
+        append_func.locals = vec![ValType::I32];
+        append_func.body = [
+            &heap_end[..],
+            &[
+                LocalTee(2),
+                LocalGet(1),
+                Num(NumOp::I32Add),
+                I32Const(1),
+                Num(NumOp::I32Add),
+                MemorySize,
+                I32Const(PAGE_SIZE.trailing_zeros() as i32),
+                Num(NumOp::I32Shl),
+                Num(NumOp::I32GtU),
+                If,
+                LocalGet(1),
+                I32Const(PAGE_SIZE.trailing_zeros() as i32),
+                Num(NumOp::I32ShrU),
+                I32Const(1),
+                Num(NumOp::I32Add),
+                MemoryGrow,
+                Drop,
+                End,
+                LocalGet(2),
+                LocalGet(0),
+                LocalGet(1),
+                MemoryCopy,
+                I32Const(HEAP_POINTER),
+                LocalGet(2),
+                LocalGet(1),
+                Num(NumOp::I32Add),
+                Store { access: Access::I32, offset: 0 },
+            ],
+        ]
+        .concat();
+
+        let mut append_byte_func = WasmFunction::new("append_byte".to_string(), vec![ValType::I32], Vec::new());
+        append_byte_func.body = vec![
+            I32Const(CHAR_BYTES),
+            LocalGet(0),
+            Store { access: Access::Byte32, offset: 0 },
+            I32Const(CHAR_BYTES),
+            I32Const(1),
+            Call(append),
+        ];
+
+        let mut digits_func = WasmFunction::new("digits".to_string(), vec![ValType::I64], vec![ValType::I32]);
+        digits_func.locals = vec![ValType::I32];
+        digits_func.body = vec![
+            I32Const(DIGITS_END),
+            LocalSet(1),
+            Loop,
+            LocalGet(1),
+            I32Const(1),
+            Num(NumOp::I32Sub),
+            LocalTee(1),
+            LocalGet(0),
+            I64Const(10),
+            Num(NumOp::I64RemU),
+            I64Const(i64::from(b'0')),
+            Num(NumOp::I64Add),
+            Store { access: Access::Byte64, offset: 0 },
+            LocalGet(0),
+            I64Const(10),
+            Num(NumOp::I64DivU),
+            LocalTee(0),
+            Num(NumOp::I64Eqz),
+            Num(NumOp::I32Eqz),
+            BrIf(0),
+            End,
+            LocalGet(1),
+        ];
+
+        functions.extend([append_func, append_byte_func, digits_func]);
+        functions
+    }
+
+    /// `_start`, starting the heap at `heap`, then calling `main` and
+    /// exiting with its result
+    fn start_function(&self, module: &WasmModule, heap: u32) -> WasmFunction {
+        let mut start = WasmFunction::new("_start".to_string(), Vec::new(), Vec::new());
+        start.export = Some("_start".to_string());
+        if self.runtime.format.is_some() {
+            start.body.extend([
+                WasmInst::I32Const(HEAP_POINTER),
+                WasmInst::I32Const(heap as i32),
+                WasmInst::Store {
+                    access: Access::I32,
+                    offset: 0,
+                },
+            ]);
+        }
+        match self.functions.get("main") {
+            Some(&(index, ref signature)) => {
+                start.body.push(WasmInst::Call(index));
+                match results_of(&signature.ret).first() {
+                    Some(ValType::I64) => start.body.push(WasmInst::Num(NumOp::I32WrapI64)),
+                    Some(_) => start.body.extend([WasmInst::Drop, WasmInst::I32Const(0)]),
+                    None => start.body.push(WasmInst::I32Const(0)),
+                }
+            }
+            None => start.body.push(WasmInst::I32Const(0)),
+        }
+        start.body.push(WasmInst::Call(self.runtime.proc_exit));
+        debug_assert_eq!(module.function_name(self.runtime.proc_exit), Some("proc_exit"));
+        start
+    }
+}
+
+impl Default for WasmEmitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Result types of a function returning `ret`
+fn results_of(ret: &Type) -> Vec<ValType> {
+    match ret {
+        Type::Unit | Type::Never => Vec::new(),
+        ty => vec![ValType::of(ty)],
+    }
+}
+
+/// Type an operand is passed as to a function outside the module
+fn operand_type(op: &MirOp, mir_func: &MirFunction) -> ValType {
+    match op {
+        MirOp::Value(value) => mir_func.value(*value).map_or(ValType::I64, |value| ValType::of(&value.ty)),
+        MirOp::Const(Constant::Float(_)) => ValType::F64,
+        MirOp::Const(_) => ValType::I64,
+    }
+}
+
+/// Whether any function calls one of [`FORMAT_ROUTINES`]
+fn formats_strings(mir_functions: &[&MirFunction]) -> bool {
+    mir_functions
+        .iter()
+        .flat_map(|func| func.blocks.values())
+        .flat_map(|block| &block.instructions)
+        .any(|inst| {
+            matches!(inst, MirInst::Call { func: MirOp::Const(Constant::String(name)), .. }
+                if FORMAT_ROUTINES.iter().any(|(routine, ..)| routine == name))
+        })
+}
+
+/// The WASI imports, then one import per function called but not defined
+/// in the module, typed by its first call
+fn external_imports(mir_functions: &[&MirFunction]) -> Vec<WasmImport> {
+    let wasi = |name: &str, params: Vec<ValType>, results: Vec<ValType>| WasmImport {
+        module: WASI.to_string(),
+        name: name.to_string(),
+        params,
+        results,
+    };
+    let mut imports = vec![
+        wasi("fd_write", vec![ValType::I32; 4], vec![ValType::I32]),
+        wasi("proc_exit", vec![ValType::I32], Vec::new()),
+    ];
+
+    let defined: HashSet<&str> = mir_functions.iter().map(|func| func.name.as_str()).collect();
+    let builtin = ["print", "println", "f32.sqrt", "f64.sqrt"];
+    let is_runtime_routine = |name: &str| {
+        FORMAT_ROUTINES
+            .iter()
+            .chain(&RUNTIME_ROUTINES)
+            .any(|(routine, ..)| *routine == name)
+    };
+    let mut external = Vec::new();
+    for func in mir_functions {
+        let mut block_ids: Vec<_> = func.blocks.keys().copied().collect();
+        block_ids.sort();
+        for inst in block_ids.iter().flat_map(|id| &func.blocks[id].instructions) {
+            if let MirInst::Call {
+                dest,
+                func: MirOp::Const(Constant::String(name)),
+                args,
+                ..
+            } = inst
+            {
+                if defined.contains(name.as_str())
+                    || builtin.contains(&name.as_str())
+                    || is_runtime_routine(name)
+                    || external.iter().any(|import: &WasmImport| import.name == *name)
+                {
+                    continue;
+                }
+                external.push(WasmImport {
+                    module: "env".to_string(),
+                    name: name.clone(),
+                    params: args.iter().map(|arg| operand_type(arg, func)).collect(),
+                    results: dest
+                        .iter()
+                        .map(|&dest| operand_type(&MirOp::Value(dest), func))
+                        .collect(),
+                });
+            }
+        }
+    }
+    external.sort_by(|a, b| a.name.cmp(&b.name));
+    imports.extend(external);
+    imports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aurora_mir::{MirBuilder, Span};
+    use aurora_types::{EffectSet, PrimitiveType, StructDef};
+
+    fn i64_ty() -> Type {
+        Type::Primitive(PrimitiveType::I64)
+    }
+
+    /// `sum(n)` looping over phis, and `main` printing it
+    fn sample_module() -> MirModule {
+        let mut module = MirModule::new();
+        let mut builder = MirBuilder::new();
+
+        builder.start_function(0, "sum".to_string(), i64_ty(), EffectSet::PURE);
+        let n = builder.add_param(i64_ty(), Span::dummy());
+        let entry = builder.current_block().unwrap();
+        let head = builder.new_block();
+        let body = builder.new_block();
+        let exit = builder.new_block();
+        builder.build_jump(head, Span::dummy());
+        builder.set_block(head);
+        let next_i = builder.new_value(i64_ty(), Span::dummy());
+        let next_total = builder.new_value(i64_ty(), Span::dummy());
+        let i = builder.build_phi(vec![(entry, MirOp::Const(Constant::Int(0))), (body, MirOp::Value(next_i))], i64_ty(), Span::dummy());
+        let total = builder.build_phi(
+            vec![(entry, MirOp::Const(Constant::Int(0))), (body, MirOp::Value(next_total))],
+            i64_ty(),
+            Span::dummy(),
+        );
+        let more = builder.build_binop(BinOp::Lt, MirOp::Value(i), MirOp::Value(n), Type::Primitive(PrimitiveType::Bool), Span::dummy());
+        builder.build_branch(MirOp::Value(more), body, exit, Span::dummy());
+        builder.set_block(body);
+        for (dest, lhs, rhs) in [(next_total, total, MirOp::Value(i)), (next_i, i, MirOp::Const(Constant::Int(1)))] {
+            builder.emit(MirInst::BinOp {
+                dest,
+                op: BinOp::Add,
+                lhs: MirOp::Value(lhs),
+                rhs,
+                span: Span::dummy(),
+            });
+        }
+        builder.build_jump(head, Span::dummy());
+        builder.set_block(exit);
+        builder.build_return(Some(MirOp::Value(total)), Span::dummy());
+        module.add_function(builder.finish_function().unwrap());
+
+        builder.start_function(1, "main".to_string(), i64_ty(), EffectSet::PURE);
+        let sum = MirOp::Const(Constant::String("sum".to_string()));
+        let total = builder.build_call(sum, vec![MirOp::Const(Constant::Int(10))], Some(i64_ty()), EffectSet::PURE, Span::dummy()).unwrap();
+        let println = MirOp::Const(Constant::String("println".to_string()));
+        builder.build_call(println, vec![MirOp::Const(Constant::String("sum".to_string()))], None, EffectSet::IO, Span::dummy());
+        builder.build_return(Some(MirOp::Value(total)), Span::dummy());
+        module.add_function(builder.finish_function().unwrap());
+        module
+    }
+
+    fn function<'a>(module: &'a WasmModule, name: &str) -> &'a WasmFunction {
+        module.functions.iter().find(|func| func.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_loops_become_structured_control_flow() {
+        let module = WasmEmitter::new().emit_module(&sample_module());
+        let sum = function(&module, "sum");
+
+        // The loop header tests the condition and either runs the body,
+        // which sets both phis and repeats the loop, or returns
+        let loop_start = sum.body.iter().position(|inst| *inst == WasmInst::Loop).unwrap();
+        let body = &sum.body[loop_start..];
+        assert_eq!(body.iter().filter(|inst| **inst == WasmInst::If).count(), 1);
+        let repeat = body.iter().position(|inst| *inst == WasmInst::Br(1)).unwrap();
+        let sets = body[..repeat]
+            .iter()
+            .rev()
+            .take_while(|inst| matches!(inst, WasmInst::LocalSet(_)))
+            .count();
+        assert_eq!(sets, 2);
+        assert!(body.contains(&WasmInst::Return));
+        assert_eq!(sum.params, vec![ValType::I64]);
+        assert_eq!(sum.results, vec![ValType::I64]);
+    }
+
+    #[test]
+    fn test_merge_points_follow_blocks() {
+        // if (c) { a } else { b }; join
+        let mut module = MirModule::new();
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "main".to_string(), i64_ty(), EffectSet::PURE);
+        let c = builder.add_param(i64_ty(), Span::dummy());
+        let entry = builder.current_block().unwrap();
+        let then_block = builder.new_block();
+        let else_block = builder.new_block();
+        let join = builder.new_block();
+        builder.build_branch(MirOp::Value(c), then_block, else_block, Span::dummy());
+        for block in [then_block, else_block] {
+            builder.set_block(block);
+            builder.build_jump(join, Span::dummy());
+        }
+        builder.set_block(join);
+        let x = builder.build_phi(
+            vec![(then_block, MirOp::Const(Constant::Int(1))), (else_block, MirOp::Const(Constant::Int(2)))],
+            i64_ty(),
+            Span::dummy(),
+        );
+        builder.build_return(Some(MirOp::Value(x)), Span::dummy());
+        module.add_function(builder.finish_function().unwrap());
+        let _ = entry;
+
+        let module = WasmEmitter::new().emit_module(&module);
+        let main = function(&module, "main");
+        assert_eq!(main.body[0], WasmInst::Block);
+        // Both arms set the phi and branch out past the if to the join
+        assert_eq!(main.body.iter().filter(|inst| **inst == WasmInst::Br(1)).count(), 2);
+        let end = main.body.iter().rposition(|inst| *inst == WasmInst::End).unwrap();
+        assert_eq!(main.body[end + 1..end + 3], [WasmInst::LocalGet(1), WasmInst::Return]);
+    }
+
+    #[test]
+    fn test_aggregates_are_copied_to_the_shadow_stack() {
+        let pair = Type::Named {
+            name: "Pair".to_string(),
+            args: vec![],
+        };
+        let mut module = MirModule::new();
+        module.layouts.add_struct(
+            "Pair".to_string(),
+            StructDef {
+                params: vec![],
+                fields: vec![("a".to_string(), i64_ty()), ("b".to_string(), i64_ty())],
+            },
+        );
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "first".to_string(), i64_ty(), EffectSet::PURE);
+        let p = builder.add_param(pair.clone(), Span::dummy());
+        let a = builder.build_load(MirOp::Value(p), i64_ty(), Span::dummy());
+        builder.build_return(Some(MirOp::Value(a)), Span::dummy());
+        module.add_function(builder.finish_function().unwrap());
+
+        builder.start_function(1, "main".to_string(), i64_ty(), EffectSet::PURE);
+        let slot = builder.build_alloca(pair, Span::dummy());
+        let first = MirOp::Const(Constant::String("first".to_string()));
+        let a = builder.build_call(first, vec![MirOp::Value(slot)], Some(i64_ty()), EffectSet::PURE, Span::dummy()).unwrap();
+        builder.build_return(Some(MirOp::Value(a)), Span::dummy());
+        module.add_function(builder.finish_function().unwrap());
+
+        let module = WasmEmitter::new().emit_module(&module);
+        let main = function(&module, "main");
+        // The frame holds the pair and its copy, and is popped on return
+        assert_eq!(main.body[2], WasmInst::I32Const(32));
+        let stores: Vec<_> = main
+            .body
+            .iter()
+            .filter_map(|inst| match inst {
+                WasmInst::Store { offset, .. } => Some(*offset),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(stores, vec![16, 24]);
+        let ret = main.body.iter().position(|inst| *inst == WasmInst::Return).unwrap();
+        assert_eq!(main.body[ret - 1], WasmInst::GlobalSet(STACK_POINTER));
+    }
+
+    #[test]
+    fn test_module_layout_and_text() {
+        let module = WasmEmitter::new().emit_module(&sample_module());
+        assert_eq!(module.imports[0].name, "fd_write");
+        assert_eq!(module.imports[1].name, "proc_exit");
+        assert_eq!(module.function_name(2), Some("write"));
+        // Strings are null-terminated, after the newline and the panic
+        // message's pieces the runtime prints
+        assert_eq!(module.data[0], DataSegment { offset: DATA_START, bytes: b"\n\0".to_vec() });
+        assert_eq!(module.data[1].bytes, b"Aurora panic at \0");
+        assert_eq!(module.data[4].bytes, b"sum\0");
+        assert_eq!(module.stack_pointer, module.memory_pages * PAGE_SIZE);
+
+        let start = function(&module, "_start");
+        assert_eq!(start.export.as_deref(), Some("_start"));
+        let text = module.to_text();
+        assert!(text.contains("(import \"wasi_snapshot_preview1\" \"fd_write\""));
+        assert!(text.contains("call $print"));
+        assert!(text.contains("call $sum"));
+        assert!(text.contains("(func $_start (export \"_start\")"));
+    }
+
+    #[test]
+    fn test_formatting_runtime_is_included_when_used() {
+        let ptr = Type::Ptr {
+            inner: Box::new(Type::Primitive(PrimitiveType::U8)),
+            mutable: true,
+        };
+        let str_ty = Type::Primitive(PrimitiveType::Str);
+        let call = |builder: &mut MirBuilder, name: &str, args: Vec<MirOp>, ret: Option<Type>| {
+            let func = MirOp::Const(Constant::String(name.to_string()));
+            builder.build_call(func, args, ret, EffectSet::ALLOC, Span::dummy())
+        };
+        let mut module = MirModule::new();
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "main".to_string(), i64_ty(), EffectSet::PURE);
+        let buffer = MirOp::Value(call(&mut builder, "aurora_fmt_new", vec![], Some(ptr)).unwrap());
+        let value = MirOp::Const(Constant::Float(2.5f64.to_bits()));
+        call(&mut builder, "aurora_fmt_f64", vec![buffer.clone(), value], None);
+        let text = call(&mut builder, "aurora_fmt_finish", vec![buffer], Some(str_ty)).unwrap();
+        call(&mut builder, "println", vec![MirOp::Value(text)], None);
+        builder.build_return(Some(MirOp::Const(Constant::Int(0))), Span::dummy());
+        module.add_function(builder.finish_function().unwrap());
+
+        let module = WasmEmitter::new().emit_module(&module);
+        assert_eq!(module.imports.len(), 2);
+        assert_eq!(module.function_name(7), Some("aurora_fmt_new"));
+        let main = function(&module, "main");
+        assert!(main.body.contains(&WasmInst::F64Const(2.5f64.to_bits())));
+        // The heap starts above the shadow stack
+        let start = function(&module, "_start");
+        assert_eq!(start.body[1], WasmInst::I32Const(module.stack_pointer as i32));
+        assert!(module.to_text().contains("call $aurora_fmt_finish"));
+
+        // Modules that do not format strings are left as they were
+        let plain = WasmEmitter::new().emit_module(&sample_module());
+        assert!(plain.functions.iter().all(|func| !func.name.starts_with("aurora_fmt")));
+    }
+}
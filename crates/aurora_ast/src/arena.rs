@@ -90,6 +90,14 @@ impl Arena {
         self.alloc(AstNode::Pattern(pattern))
     }
 
+    /// Remove the nodes with IDs from `at` on, returning them in order
+    pub fn split_off(&mut self, at: usize) -> Vec<AstNode> {
+        self.parents.truncate(at);
+        self.preorder.truncate(at);
+        self.postorder.truncate(at);
+        self.nodes.split_off(at)
+    }
+
     /// Get a node by ID
     pub fn get(&self, id: u32) -> Option<&AstNode> {
         self.nodes.get(id as usize)
@@ -282,6 +290,13 @@ impl Arena {
             Yield { value } => vec![*value],
             Continue => vec![],
             Block(_) => vec![],
+            Interpolated(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    crate::expr::InterpolationPart::Expr(expr_id) => Some(*expr_id),
+                    crate::expr::InterpolationPart::Text(_) => None,
+                })
+                .collect(),
             Tuple(exprs) | Array(exprs) => exprs.clone(),
             Struct { .. } => vec![],
             Range { start, end, .. } => {
@@ -383,7 +398,7 @@ mod tests {
         let mut arena = Arena::new();
 
         let expr = Expr {
-            kind: ExprKind::Literal(Literal::Int(42)),
+            kind: ExprKind::Literal(Literal::Int(42, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         };
@@ -396,19 +411,36 @@ mod tests {
         assert!(node.is_some());
     }
 
+    #[test]
+    fn test_split_off() {
+        let mut arena = Arena::new();
+        for value in 0..3 {
+            arena.alloc_expr(Expr {
+                kind: ExprKind::Literal(Literal::Int(value, None)),
+                span: Span::dummy(),
+                hygiene: Default::default(),
+            });
+        }
+
+        let tail = arena.split_off(1);
+        assert_eq!(tail.len(), 2);
+        assert_eq!(arena.len(), 1);
+        assert_eq!(arena.alloc(tail[1].clone()), 1);
+    }
+
     #[test]
     fn test_parent_links() {
         let mut arena = Arena::new();
 
         // Create a simple expression tree: (1 + 2)
         let left = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(1)),
+            kind: ExprKind::Literal(Literal::Int(1, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
 
         let right = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(2)),
+            kind: ExprKind::Literal(Literal::Int(2, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
@@ -438,13 +470,13 @@ mod tests {
 
         // Create: (1 + 2)
         let left = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(1)),
+            kind: ExprKind::Literal(Literal::Int(1, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
 
         let right = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(2)),
+            kind: ExprKind::Literal(Literal::Int(2, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
@@ -478,13 +510,13 @@ mod tests {
         let mut arena = Arena::new();
 
         let left = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(1)),
+            kind: ExprKind::Literal(Literal::Int(1, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
 
         let right = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(2)),
+            kind: ExprKind::Literal(Literal::Int(2, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
@@ -513,13 +545,13 @@ mod tests {
         let mut arena = Arena::new();
 
         let left = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(1)),
+            kind: ExprKind::Literal(Literal::Int(1, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
 
         let right = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(2)),
+            kind: ExprKind::Literal(Literal::Int(2, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
@@ -1,7 +1,7 @@
 //! Declaration AST nodes
 //!
-//! This module defines top-level item declarations: functions, types,
-//! traits, impls, constants, modules, and use statements.
+//! This module defines top-level item declarations: functions, structs,
+//! enums, types, traits, impls, constants, modules, and use statements.
 
 use crate::expr::{ExprId, Path, TypeId};
 use crate::span::Span;
@@ -25,6 +25,10 @@ pub struct Item {
 pub enum ItemKind {
     /// Function declaration
     Function(FunctionDecl),
+    /// Struct declaration
+    Struct(StructDecl),
+    /// Enum declaration
+    Enum(EnumDecl),
     /// Type declaration (type alias)
     Type(TypeDecl),
     /// Trait declaration
@@ -120,6 +124,85 @@ pub struct WherePredicate {
     pub span: Span,
 }
 
+/// Struct declaration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StructDecl {
+    /// Struct name
+    pub name: String,
+    /// Generic parameters
+    pub generics: Vec<GenericParam>,
+    /// Where clause constraints
+    pub where_clause: Option<WhereClause>,
+    /// Struct fields
+    pub fields: VariantFields,
+    /// Whether struct is public
+    pub is_pub: bool,
+    /// Source span
+    pub span: Span,
+}
+
+/// Enum declaration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnumDecl {
+    /// Enum name
+    pub name: String,
+    /// Generic parameters
+    pub generics: Vec<GenericParam>,
+    /// Where clause constraints
+    pub where_clause: Option<WhereClause>,
+    /// Enum variants
+    pub variants: Vec<EnumVariant>,
+    /// Whether enum is public
+    pub is_pub: bool,
+    /// Source span
+    pub span: Span,
+}
+
+/// Enum variant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnumVariant {
+    /// Variant name
+    pub name: String,
+    /// Variant fields
+    pub fields: VariantFields,
+    /// Source span
+    pub span: Span,
+}
+
+/// Fields of a struct or enum variant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VariantFields {
+    /// Named fields (e.g., `struct Point { x: f64, y: f64 }`)
+    Named(Vec<FieldDecl>),
+    /// Positional fields (e.g., `struct Meters(f64);`)
+    Tuple(Vec<FieldDecl>),
+    /// No fields (e.g., `struct Marker;`)
+    Unit,
+}
+
+impl VariantFields {
+    /// Get the declared fields (empty for unit variants)
+    pub fn fields(&self) -> &[FieldDecl] {
+        match self {
+            VariantFields::Named(fields) | VariantFields::Tuple(fields) => fields,
+            VariantFields::Unit => &[],
+        }
+    }
+}
+
+/// Field declaration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldDecl {
+    /// Field name (the index for positional fields)
+    pub name: String,
+    /// Field type
+    pub ty: TypeId,
+    /// Whether field is public
+    pub is_pub: bool,
+    /// Source span
+    pub span: Span,
+}
+
 /// Type declaration (type alias)
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TypeDecl {
@@ -314,6 +397,18 @@ mod tests {
         assert_eq!(param.name, "T");
     }
 
+    #[test]
+    fn test_variant_fields() {
+        let field = FieldDecl {
+            name: "0".to_string(),
+            ty: 0,
+            is_pub: false,
+            span: Span::dummy(),
+        };
+        assert_eq!(VariantFields::Tuple(vec![field]).fields().len(), 1);
+        assert!(VariantFields::Unit.fields().is_empty());
+    }
+
     #[test]
     fn test_const_decl() {
         let const_decl = ConstDecl {
@@ -4,6 +4,7 @@
 //! to complex control flow expressions.
 
 use crate::span::{HygieneId, Span};
+use crate::ty::{FloatType, IntType, UintType};
 use serde::{Deserialize, Serialize};
 
 /// Expression node ID (index into arena)
@@ -27,6 +28,9 @@ pub enum ExprKind {
     /// Integer literal (e.g., `42`, `0x2A`)
     Literal(Literal),
 
+    /// Interpolated string literal (e.g., `"x = {x + 1}"`)
+    Interpolated(Vec<InterpolationPart>),
+
     /// Identifier reference (e.g., `x`, `foo`)
     Ident(String),
 
@@ -222,10 +226,13 @@ pub type PatternId = u32;
 /// Literal value
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Literal {
-    /// Integer literal
-    Int(i64),
-    /// Float literal
-    Float(f64),
+    /// Integer literal, with its type suffix if it has one
+    ///
+    /// The value is wide enough for every integer type; whether it fits
+    /// the literal's type is checked during type checking.
+    Int(i128, Option<IntSuffix>),
+    /// Float literal, with its type suffix if it has one
+    Float(f64, Option<FloatType>),
     /// String literal
     String(String),
     /// Character literal
@@ -234,6 +241,24 @@ pub enum Literal {
     Bool(bool),
 }
 
+/// Type named by the suffix of an integer literal, as in `255u8`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IntSuffix {
+    /// Signed integer suffix
+    Int(IntType),
+    /// Unsigned integer suffix
+    Uint(UintType),
+}
+
+/// Piece of an interpolated string literal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InterpolationPart {
+    /// Literal text, with its escapes decoded
+    Text(String),
+    /// Expression whose value is formatted into the string
+    Expr(ExprId),
+}
+
 /// Unary operator
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum UnaryOp {
@@ -376,8 +401,8 @@ mod tests {
 
     #[test]
     fn test_literal_creation() {
-        let lit = Literal::Int(42);
-        assert_eq!(lit, Literal::Int(42));
+        let lit = Literal::Int(42, None);
+        assert_eq!(lit, Literal::Int(42, None));
 
         let lit = Literal::String("hello".to_string());
         assert_eq!(lit, Literal::String("hello".to_string()));
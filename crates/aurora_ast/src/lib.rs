@@ -16,7 +16,8 @@
 //! - `decl`: Declaration nodes (functions, types, traits, impls)
 //! - `ty`: Type nodes (primitives, compounds, generics)
 //! - `pattern`: Pattern nodes (destructuring, matching)
-//! - `span`: Source location and hygiene tracking
+//! - `span`: Source locations, the source map and hygiene tracking
+//! - `remap`: Rewriting the node IDs and spans held in nodes
 //!
 //! # Node IDs
 //!
@@ -36,6 +37,7 @@ pub mod expr;
 pub mod nodes;
 pub mod pattern;
 pub mod pretty;
+pub mod remap;
 pub mod span;
 pub mod stmt;
 pub mod ty;
@@ -49,7 +51,7 @@ pub use decl::{Item, ItemId, ItemKind};
 pub use expr::{Expr, ExprId, ExprKind};
 pub use nodes::{AstNode, Program};
 pub use pattern::{Pattern, PatternId, PatternKind};
-pub use span::{HygieneId, Span};
+pub use span::{HygieneId, SourceFile, SourceMap, Span};
 pub use stmt::{Block, Stmt, StmtId, StmtKind};
 pub use ty::{Type, TypeId, TypeKind};
 
@@ -89,7 +89,7 @@ mod tests {
 
     #[test]
     fn test_program_with_items() {
-        let program = Program::new(vec![0, 1, 2], Span::dummy());
+        let program = Program::new(vec![0, 1, 2], Span::dummy(), Arena::new());
         assert_eq!(program.items.len(), 3);
     }
 }
@@ -259,6 +259,14 @@ impl PrettyPrinter {
                     pub_str, async_str, func.name, id_str
                 ));
             }
+            Struct(struct_decl) => {
+                let pub_str = if struct_decl.is_pub { "pub " } else { "" };
+                self.write_line(&format!("{}struct {}{}", pub_str, struct_decl.name, id_str));
+            }
+            Enum(enum_decl) => {
+                let pub_str = if enum_decl.is_pub { "pub " } else { "" };
+                self.write_line(&format!("{}enum {}{}", pub_str, enum_decl.name, id_str));
+            }
             Type(ty_decl) => {
                 let pub_str = if ty_decl.is_pub { "pub " } else { "" };
                 self.write_line(&format!("{}type {}{}",pub_str, ty_decl.name, id_str));
@@ -359,7 +367,7 @@ mod tests {
         let mut arena = Arena::new();
 
         let expr = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(42)),
+            kind: ExprKind::Literal(Literal::Int(42, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
@@ -367,7 +375,7 @@ mod tests {
         let mut printer = PrettyPrinter::new();
         let output = printer.print_node(&arena, expr);
 
-        assert!(output.contains("Literal(Int(42))"));
+        assert!(output.contains("Literal(Int(42, None))"));
         assert!(output.contains("[id=0]"));
     }
 
@@ -376,13 +384,13 @@ mod tests {
         let mut arena = Arena::new();
 
         let left = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(1)),
+            kind: ExprKind::Literal(Literal::Int(1, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
 
         let right = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(2)),
+            kind: ExprKind::Literal(Literal::Int(2, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
@@ -403,8 +411,8 @@ mod tests {
         assert!(output.contains("Binary(Add)"));
         assert!(output.contains("left:"));
         assert!(output.contains("right:"));
-        assert!(output.contains("Literal(Int(1))"));
-        assert!(output.contains("Literal(Int(2))"));
+        assert!(output.contains("Literal(Int(1, None))"));
+        assert!(output.contains("Literal(Int(2, None))"));
     }
 
     #[test]
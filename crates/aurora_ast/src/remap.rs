@@ -0,0 +1,443 @@
+//! Rewriting the node IDs and spans held in AST nodes
+//!
+//! Nodes reused from an earlier parse of an edited file keep their shape
+//! but move: to other slots of the arena and other places in the source.
+//! `AstNode::remap_ids` and `AstNode::remap_spans` rewrite every ID and
+//! span a node holds, including those inside its declarations, paths,
+//! match arms and the like.
+
+use crate::decl::{
+    ConstDecl, FunctionDecl, FunctionSignature, GenericParam, ImplItem, ItemKind, Param, TraitItem,
+    TypeDecl, UseTree, VariantFields, WhereClause,
+};
+use crate::expr::{ExprKind, GenericArg, InterpolationPart, Path};
+use crate::nodes::AstNode;
+use crate::pattern::PatternKind;
+use crate::span::Span;
+use crate::stmt::{Block, StmtKind};
+use crate::ty::TypeKind;
+use crate::{decl, ty};
+
+/// Rewrites the IDs and spans found in a node
+trait Remap {
+    /// Rewrite a node ID
+    fn id(&mut self, id: &mut u32);
+
+    /// Rewrite a span
+    fn span(&mut self, span: &mut Span);
+
+    fn ids(&mut self, ids: &mut [u32]) {
+        for id in ids {
+            self.id(id);
+        }
+    }
+
+    fn opt_id(&mut self, id: &mut Option<u32>) {
+        if let Some(id) = id {
+            self.id(id);
+        }
+    }
+}
+
+/// Remaps IDs only
+struct Ids<F>(F);
+
+impl<F: FnMut(u32) -> u32> Remap for Ids<F> {
+    fn id(&mut self, id: &mut u32) {
+        *id = (self.0)(*id);
+    }
+
+    fn span(&mut self, _span: &mut Span) {}
+}
+
+/// Remaps spans only
+struct Spans<F>(F);
+
+impl<F: FnMut(Span) -> Span> Remap for Spans<F> {
+    fn id(&mut self, _id: &mut u32) {}
+
+    fn span(&mut self, span: &mut Span) {
+        *span = (self.0)(*span);
+    }
+}
+
+impl AstNode {
+    /// Replace every node ID this node holds by `f` of it
+    pub fn remap_ids(&mut self, f: impl FnMut(u32) -> u32) {
+        node(self, &mut Ids(f));
+    }
+
+    /// Replace every span this node holds by `f` of it
+    pub fn remap_spans(&mut self, f: impl FnMut(Span) -> Span) {
+        node(self, &mut Spans(f));
+    }
+}
+
+fn node(node: &mut AstNode, r: &mut impl Remap) {
+    match node {
+        AstNode::Expr(e) => {
+            r.span(&mut e.span);
+            expr(&mut e.kind, r);
+        }
+        AstNode::Stmt(s) => {
+            r.span(&mut s.span);
+            match &mut s.kind {
+                StmtKind::Let { pattern, ty, init, .. } => {
+                    r.id(pattern);
+                    r.opt_id(ty);
+                    r.opt_id(init);
+                }
+                StmtKind::Expr { expr, .. } => r.id(expr),
+                StmtKind::Item(item) => r.id(item),
+            }
+        }
+        AstNode::Block(b) => block(b, r),
+        AstNode::Item(i) => {
+            r.span(&mut i.span);
+            item(&mut i.kind, r);
+        }
+        AstNode::Type(t) => {
+            r.span(&mut t.span);
+            type_kind(&mut t.kind, r);
+        }
+        AstNode::Pattern(p) => {
+            r.span(&mut p.span);
+            pattern(&mut p.kind, r);
+        }
+    }
+}
+
+fn expr(kind: &mut ExprKind, r: &mut impl Remap) {
+    match kind {
+        ExprKind::Literal(_) | ExprKind::Ident(_) | ExprKind::Continue => {}
+        ExprKind::Interpolated(parts) => {
+            for part in parts {
+                if let InterpolationPart::Expr(id) = part {
+                    r.id(id);
+                }
+            }
+        }
+        ExprKind::Path(p) => path(p, r),
+        ExprKind::Unary { operand, .. } => r.id(operand),
+        ExprKind::Binary { left, right, .. } | ExprKind::Pipeline { left, right } => {
+            r.id(left);
+            r.id(right);
+        }
+        ExprKind::Call { func, args } => {
+            r.id(func);
+            r.ids(args);
+        }
+        ExprKind::MethodCall { receiver, args, .. } => {
+            r.id(receiver);
+            r.ids(args);
+        }
+        ExprKind::Field { object, .. } => r.id(object),
+        ExprKind::Index { collection, index } => {
+            r.id(collection);
+            r.id(index);
+        }
+        ExprKind::If { condition, then_block, else_block } => {
+            r.id(condition);
+            r.id(then_block);
+            r.opt_id(else_block);
+        }
+        ExprKind::Match { scrutinee, arms } => {
+            r.id(scrutinee);
+            for arm in arms {
+                r.id(&mut arm.pattern);
+                r.opt_id(&mut arm.guard);
+                r.id(&mut arm.body);
+                r.span(&mut arm.span);
+            }
+        }
+        ExprKind::Loop { body } => r.id(body),
+        ExprKind::While { condition, body } => {
+            r.id(condition);
+            r.id(body);
+        }
+        ExprKind::For { pattern, iterator, body } => {
+            r.id(pattern);
+            r.id(iterator);
+            r.id(body);
+        }
+        ExprKind::Return { value } | ExprKind::Break { value } => r.opt_id(value),
+        ExprKind::Yield { value } => r.id(value),
+        ExprKind::Block(block) | ExprKind::Unsafe { block } => r.id(block),
+        ExprKind::Tuple(ids) | ExprKind::Array(ids) => r.ids(ids),
+        ExprKind::Struct { path: p, fields } => {
+            path(p, r);
+            for field in fields {
+                r.id(&mut field.value);
+                r.span(&mut field.span);
+            }
+        }
+        ExprKind::Range { start, end, .. } => {
+            r.opt_id(start);
+            r.opt_id(end);
+        }
+        ExprKind::Try { expr } | ExprKind::Await { expr } | ExprKind::Comptime { expr } => r.id(expr),
+    }
+}
+
+fn path(path: &mut Path, r: &mut impl Remap) {
+    for arg in &mut path.generics {
+        match arg {
+            GenericArg::Type(id) | GenericArg::Const(id) => r.id(id),
+        }
+    }
+}
+
+fn block(block: &mut Block, r: &mut impl Remap) {
+    r.ids(&mut block.stmts);
+    r.opt_id(&mut block.expr);
+    r.span(&mut block.span);
+}
+
+fn item(kind: &mut ItemKind, r: &mut impl Remap) {
+    match kind {
+        ItemKind::Function(f) => function(f, r),
+        ItemKind::Struct(s) => {
+            generics(&mut s.generics, r);
+            where_clause(&mut s.where_clause, r);
+            variant_fields(&mut s.fields, r);
+            r.span(&mut s.span);
+        }
+        ItemKind::Enum(e) => {
+            generics(&mut e.generics, r);
+            where_clause(&mut e.where_clause, r);
+            for variant in &mut e.variants {
+                variant_fields(&mut variant.fields, r);
+                r.span(&mut variant.span);
+            }
+            r.span(&mut e.span);
+        }
+        ItemKind::Type(t) => type_decl(t, r),
+        ItemKind::Trait(t) => {
+            generics(&mut t.generics, r);
+            where_clause(&mut t.where_clause, r);
+            for trait_item in &mut t.items {
+                match trait_item {
+                    TraitItem::Function(sig) => signature(sig, r),
+                    TraitItem::Type(assoc) => {
+                        decl_bounds(&mut assoc.bounds, r);
+                        r.opt_id(&mut assoc.default);
+                        r.span(&mut assoc.span);
+                    }
+                    TraitItem::Const(c) => const_decl(c, r),
+                }
+            }
+            r.span(&mut t.span);
+        }
+        ItemKind::Impl(i) => {
+            generics(&mut i.generics, r);
+            r.id(&mut i.self_ty);
+            if let Some(trait_ref) = &mut i.trait_ref {
+                path(&mut trait_ref.path, r);
+                r.span(&mut trait_ref.span);
+            }
+            where_clause(&mut i.where_clause, r);
+            for impl_item in &mut i.items {
+                match impl_item {
+                    ImplItem::Function(f) => function(f, r),
+                    ImplItem::Const(c) => const_decl(c, r),
+                    ImplItem::Type(t) => type_decl(t, r),
+                }
+            }
+            r.span(&mut i.span);
+        }
+        ItemKind::Const(c) => const_decl(c, r),
+        ItemKind::Module(m) => {
+            if let Some(items) = &mut m.items {
+                r.ids(items);
+            }
+            r.span(&mut m.span);
+        }
+        ItemKind::Use(u) => {
+            use_tree(&mut u.tree, r);
+            r.span(&mut u.span);
+        }
+    }
+}
+
+fn function(f: &mut FunctionDecl, r: &mut impl Remap) {
+    generics(&mut f.generics, r);
+    params(&mut f.params, r);
+    r.opt_id(&mut f.return_type);
+    where_clause(&mut f.where_clause, r);
+    block(&mut f.body, r);
+    r.span(&mut f.span);
+}
+
+fn signature(sig: &mut FunctionSignature, r: &mut impl Remap) {
+    generics(&mut sig.generics, r);
+    params(&mut sig.params, r);
+    r.opt_id(&mut sig.return_type);
+    where_clause(&mut sig.where_clause, r);
+    if let Some(body) = &mut sig.body {
+        block(body, r);
+    }
+    r.span(&mut sig.span);
+}
+
+fn params(params: &mut [Param], r: &mut impl Remap) {
+    for param in params {
+        r.id(&mut param.pattern);
+        r.id(&mut param.ty);
+        r.span(&mut param.span);
+    }
+}
+
+fn generics(generics: &mut [GenericParam], r: &mut impl Remap) {
+    for param in generics {
+        decl_bounds(&mut param.bounds, r);
+        r.span(&mut param.span);
+    }
+}
+
+fn where_clause(clause: &mut Option<WhereClause>, r: &mut impl Remap) {
+    if let Some(clause) = clause {
+        for predicate in &mut clause.predicates {
+            r.id(&mut predicate.ty);
+            decl_bounds(&mut predicate.bounds, r);
+            r.span(&mut predicate.span);
+        }
+        r.span(&mut clause.span);
+    }
+}
+
+fn decl_bounds(bounds: &mut [decl::TypeBound], r: &mut impl Remap) {
+    for bound in bounds {
+        path(&mut bound.trait_path, r);
+        r.span(&mut bound.span);
+    }
+}
+
+fn variant_fields(fields: &mut VariantFields, r: &mut impl Remap) {
+    if let VariantFields::Named(fields) | VariantFields::Tuple(fields) = fields {
+        for field in fields {
+            r.id(&mut field.ty);
+            r.span(&mut field.span);
+        }
+    }
+}
+
+fn type_decl(t: &mut TypeDecl, r: &mut impl Remap) {
+    generics(&mut t.generics, r);
+    r.id(&mut t.ty);
+    r.span(&mut t.span);
+}
+
+fn const_decl(c: &mut ConstDecl, r: &mut impl Remap) {
+    r.id(&mut c.ty);
+    r.id(&mut c.value);
+    r.span(&mut c.span);
+}
+
+fn use_tree(tree: &mut UseTree, r: &mut impl Remap) {
+    match tree {
+        UseTree::Path { path: p, .. } | UseTree::Glob { path: p } => path(p, r),
+        UseTree::Nested { path: p, trees } => {
+            path(p, r);
+            for tree in trees {
+                use_tree(tree, r);
+            }
+        }
+    }
+}
+
+fn type_kind(kind: &mut TypeKind, r: &mut impl Remap) {
+    match kind {
+        TypeKind::Int(_)
+        | TypeKind::Uint(_)
+        | TypeKind::Float(_)
+        | TypeKind::Bool
+        | TypeKind::Char
+        | TypeKind::Str
+        | TypeKind::Infer
+        | TypeKind::Never => {}
+        TypeKind::Path { path: p } => path(p, r),
+        TypeKind::Tuple(ids) => r.ids(ids),
+        TypeKind::Array { element, length } => {
+            r.id(element);
+            r.id(length);
+        }
+        TypeKind::Slice { element } => r.id(element),
+        TypeKind::Reference { inner, .. } | TypeKind::Pointer { inner, .. } => r.id(inner),
+        TypeKind::Function { params, return_type } => {
+            r.ids(params);
+            if let Some(return_type) = return_type {
+                r.id(return_type);
+            }
+        }
+        TypeKind::TraitObject { bounds } | TypeKind::ImplTrait { bounds } => type_bounds(bounds, r),
+    }
+}
+
+fn type_bounds(bounds: &mut [ty::TypeBound], r: &mut impl Remap) {
+    for bound in bounds {
+        path(&mut bound.trait_path, r);
+        r.span(&mut bound.span);
+    }
+}
+
+fn pattern(kind: &mut PatternKind, r: &mut impl Remap) {
+    match kind {
+        PatternKind::Wildcard | PatternKind::Ident { .. } | PatternKind::Literal(_) | PatternKind::Rest => {}
+        PatternKind::Path(p) => path(p, r),
+        PatternKind::Tuple(ids) | PatternKind::Or(ids) => r.ids(ids),
+        PatternKind::Struct { path: p, fields, .. } => {
+            path(p, r);
+            for field in fields {
+                r.opt_id(&mut field.pattern);
+                r.span(&mut field.span);
+            }
+        }
+        PatternKind::TupleStruct { path: p, fields } => {
+            path(p, r);
+            r.ids(fields);
+        }
+        PatternKind::Range { start, end, .. } => {
+            r.id(start);
+            r.id(end);
+        }
+        PatternKind::Ref { inner, .. } => r.id(inner),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::{Expr, MatchArm};
+    use crate::pattern::Pattern;
+
+    #[test]
+    fn test_remap_reaches_nested_ids_and_spans() {
+        let span = |start| Span::new(0, start, start + 1, 1, start + 1);
+        let mut node = AstNode::Expr(Expr {
+            kind: ExprKind::Match {
+                scrutinee: 1,
+                arms: vec![MatchArm { pattern: 2, guard: Some(3), body: 4, span: span(5) }],
+            },
+            span: span(0),
+            hygiene: Default::default(),
+        });
+
+        node.remap_ids(|id| id + 10);
+        node.remap_spans(|s| Span { start: s.start + 100, ..s });
+        let AstNode::Expr(Expr { kind: ExprKind::Match { scrutinee, arms }, span: outer, .. }) = &node else {
+            unreachable!()
+        };
+        assert_eq!(*scrutinee, 11);
+        assert_eq!((arms[0].pattern, arms[0].guard, arms[0].body), (12, Some(13), 14));
+        assert_eq!((outer.start, arms[0].span.start), (100, 105));
+
+        let mut node = AstNode::Pattern(Pattern {
+            kind: PatternKind::Range { start: Box::new(1), end: Box::new(2), inclusive: true },
+            span: span(0),
+            hygiene: Default::default(),
+        });
+        node.remap_ids(|id| id * 2);
+        assert!(matches!(&node, AstNode::Pattern(Pattern { kind: PatternKind::Range { start, end, .. }, .. })
+            if **start == 2 && **end == 4));
+    }
+}
@@ -54,14 +54,17 @@ impl Span {
     }
 
     /// Merge two spans into a span covering both
+    ///
+    /// The line and column are those of whichever span starts first.
     pub fn merge(self, other: Span) -> Span {
         debug_assert_eq!(self.file_id, other.file_id);
+        let first = if other.start < self.start { other } else { self };
         Span {
             file_id: self.file_id,
             start: self.start.min(other.start),
             end: self.end.max(other.end),
-            line: self.line.min(other.line),
-            column: self.column.min(other.column),
+            line: first.line,
+            column: first.column,
         }
     }
 }
@@ -72,6 +75,103 @@ impl Default for Span {
     }
 }
 
+/// A source file registered in a [`SourceMap`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    /// File name
+    pub name: String,
+    /// Full source text
+    pub source: String,
+    /// Byte offset at which each line starts
+    line_starts: Vec<u32>,
+}
+
+impl SourceFile {
+    /// Create a source file, indexing its line starts
+    pub fn new(name: String, source: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i as u32 + 1))
+            .collect();
+        Self {
+            name,
+            source,
+            line_starts,
+        }
+    }
+
+    /// Line and column (both 1-indexed, column in characters) of a byte offset
+    pub fn line_col(&self, offset: u32) -> (u32, u32) {
+        let offset = offset.min(self.source.len() as u32);
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = self.line_starts[line] as usize;
+        let column = self.source[line_start..offset as usize].chars().count();
+        (line as u32 + 1, column as u32 + 1)
+    }
+
+    /// Text of a 1-indexed line, without its line terminator
+    pub fn line_text(&self, line: u32) -> &str {
+        let Some(&start) = self.line_starts.get((line as usize).wrapping_sub(1)) else {
+            return "";
+        };
+        let end = self
+            .line_starts
+            .get(line as usize)
+            .map_or(self.source.len(), |&next| next as usize);
+        self.source[start as usize..end].trim_end_matches(['\n', '\r'])
+    }
+
+    /// Number of lines in the file
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+}
+
+/// Table of all source files in a compilation, indexed by `Span::file_id`
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    /// Create an empty source map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a file and return its file ID
+    pub fn add_file(&mut self, name: impl Into<String>, source: impl Into<String>) -> u32 {
+        self.files.push(SourceFile::new(name.into(), source.into()));
+        self.files.len() as u32 - 1
+    }
+
+    /// Get a file by ID
+    pub fn get(&self, file_id: u32) -> Option<&SourceFile> {
+        self.files.get(file_id as usize)
+    }
+
+    /// Iterate over all files with their IDs
+    pub fn files(&self) -> impl Iterator<Item = (u32, &SourceFile)> {
+        self.files.iter().enumerate().map(|(id, file)| (id as u32, file))
+    }
+
+    /// Source text covered by a span
+    pub fn span_text(&self, span: Span) -> Option<&str> {
+        self.get(span.file_id)?
+            .source
+            .get(span.start as usize..span.end as usize)
+    }
+
+    /// Number of registered files
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Check if no files are registered
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
 /// Hygiene identifier for macro expansion
 ///
 /// Each identifier gets a unique hygiene ID that tracks its lexical context.
@@ -120,6 +220,35 @@ mod tests {
         assert_eq!(merged.end, 30);
     }
 
+    #[test]
+    fn test_span_merge_keeps_first_location() {
+        let later = Span::new(0, 30, 34, 3, 1);
+        let earlier = Span::new(0, 12, 15, 2, 9);
+        let merged = later.merge(earlier);
+        assert_eq!((merged.start, merged.end), (12, 34));
+        assert_eq!((merged.line, merged.column), (2, 9));
+    }
+
+    #[test]
+    fn test_source_map() {
+        let mut map = SourceMap::new();
+        let main = map.add_file("main.ax", "fn main() {\n    héllo();\n}\n");
+        let other = map.add_file("other.ax", "");
+        assert_eq!((main, other), (0, 1));
+
+        let file = map.get(main).unwrap();
+        assert_eq!(file.line_col(0), (1, 1));
+        assert_eq!(file.line_col(16), (2, 5));
+        // Columns count characters, not bytes
+        assert_eq!(file.line_col(23), (2, 11));
+        assert_eq!(file.line_text(2), "    héllo();");
+        assert_eq!(file.line_count(), 4);
+
+        let span = Span::new(main, 16, 22, 2, 5);
+        assert_eq!(map.span_text(span), Some("héllo"));
+        assert_eq!(map.get(other).unwrap().line_col(0), (1, 1));
+    }
+
     #[test]
     fn test_hygiene_id() {
         let id = HygieneId::new(42);
@@ -5,6 +5,7 @@
 
 use crate::arena::Arena;
 use crate::nodes::AstNode;
+use crate::expr::InterpolationPart;
 use crate::{Expr, Item, Pattern, Stmt, Type};
 
 /// Visitor trait for traversing AST nodes
@@ -103,6 +104,13 @@ pub fn walk_expr<V: Visitor>(visitor: &mut V, arena: &Arena, expr: &Expr) {
             }
         }
         Yield { value } => visitor.visit_node(arena, *value),
+        Interpolated(parts) => {
+            for part in parts {
+                if let InterpolationPart::Expr(expr_id) = part {
+                    visitor.visit_node(arena, *expr_id);
+                }
+            }
+        }
         Tuple(exprs) | Array(exprs) => {
             for &expr_id in exprs {
                 visitor.visit_node(arena, expr_id);
@@ -258,13 +266,13 @@ mod tests {
         let mut arena = Arena::new();
 
         let left = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(1)),
+            kind: ExprKind::Literal(Literal::Int(1, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
 
         let right = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(2)),
+            kind: ExprKind::Literal(Literal::Int(2, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
@@ -293,13 +301,13 @@ mod tests {
         let mut arena = Arena::new();
 
         let left = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(1)),
+            kind: ExprKind::Literal(Literal::Int(1, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
 
         let right = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(2)),
+            kind: ExprKind::Literal(Literal::Int(2, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
@@ -330,13 +338,13 @@ mod tests {
         let mut arena = Arena::new();
 
         let left = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(1)),
+            kind: ExprKind::Literal(Literal::Int(1, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
 
         let right = arena.alloc_expr(Expr {
-            kind: ExprKind::Literal(Literal::Int(2)),
+            kind: ExprKind::Literal(Literal::Int(2, None)),
             span: Span::dummy(),
             hygiene: Default::default(),
         });
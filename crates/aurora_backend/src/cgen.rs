@@ -0,0 +1,952 @@
+//! C Source Backend - Portable C99 from MIR
+//!
+//! An alternative to the AIR pipeline, for platforms it does not support
+//! yet and as a second implementation to test it against. Each MIR
+//! function becomes one C function whose basic blocks are labels: jumps and
+//! branches are `goto`s, and a phi is an assignment from a temporary that
+//! every edge into its block sets first, so phis exchanging values read the
+//! old ones.
+//!
+//! Values keep the representation they have on the native targets: floats
+//! are `float` or `double`, and everything else, pointers and aggregates
+//! included, an `int64_t`. Memory is accessed through `memcpy`, so the code
+//! makes no alignment or aliasing assumptions, and integer arithmetic that
+//! may overflow is done unsigned so it wraps. The result is compiled with
+//! the C runtime by any C99 compiler (see [`compile_c`]).
+
+use crate::llvm::{BackendError, OptLevel};
+use aurora_mir::layout::WORD_SIZE;
+use aurora_mir::{
+    BasicBlock, BinOp, BlockId, Constant, Function as MirFunction, Instruction as MirInst, LayoutTable, MirModule,
+    Operand as MirOp, UnaryOp, ValueId,
+};
+use aurora_types::{PrimitiveType, Type};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Start of every translation unit: the runtime's functions, and helpers
+/// for memory access, moving bits between integers and floats, and
+/// printing numbers
+const PRELUDE: &str = r#"#include <inttypes.h>
+#include <math.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+void print(const char *str);
+void println(const char *str);
+void aurora_panic(const char *msg, const char *file, int line);
+int64_t aurora_str_eq(const char *a, const char *b);
+
+static inline int64_t aurora_load_i64(int64_t ptr) { int64_t v; memcpy(&v, (void *)(intptr_t)ptr, sizeof v); return v; }
+static inline float aurora_load_f32(int64_t ptr) { float v; memcpy(&v, (void *)(intptr_t)ptr, sizeof v); return v; }
+static inline double aurora_load_f64(int64_t ptr) { double v; memcpy(&v, (void *)(intptr_t)ptr, sizeof v); return v; }
+static inline void aurora_store_i64(int64_t ptr, int64_t v) { memcpy((void *)(intptr_t)ptr, &v, sizeof v); }
+static inline void aurora_store_f32(int64_t ptr, float v) { memcpy((void *)(intptr_t)ptr, &v, sizeof v); }
+static inline void aurora_store_f64(int64_t ptr, double v) { memcpy((void *)(intptr_t)ptr, &v, sizeof v); }
+static inline float aurora_bits_f32(int64_t bits) { uint32_t b = (uint32_t)bits; float v; memcpy(&v, &b, sizeof v); return v; }
+static inline double aurora_bits_f64(int64_t bits) { double v; memcpy(&v, &bits, sizeof v); return v; }
+static inline int64_t aurora_f32_bits(float v) { uint32_t b; memcpy(&b, &v, sizeof b); return (int64_t)b; }
+static inline int64_t aurora_f64_bits(double v) { int64_t b; memcpy(&b, &v, sizeof b); return b; }
+"#;
+
+/// Functions of the runtime the prelude declares, with the C types of
+/// their parameters and whether they return a value
+const RUNTIME_FUNCTIONS: [(&str, &[&str], bool); 4] = [
+    ("print", &["const char *"], false),
+    ("println", &["const char *"], false),
+    ("aurora_panic", &["const char *", "const char *", "int"], false),
+    ("aurora_str_eq", &["const char *", "const char *"], true),
+];
+
+/// Prefix of the C names of the module's functions, keeping them apart
+/// from C keywords and library functions
+const FUNCTION_PREFIX: &str = "aurora_fn_";
+
+/// C name of a function of the module
+///
+/// Characters other than ASCII letters and digits, such as the `.` of a
+/// method name, are replaced by their code in hex between underscores, so
+/// distinct names stay distinct.
+fn function_name(name: &str) -> String {
+    let mut c_name = FUNCTION_PREFIX.to_string();
+    for c in name.chars() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => c_name.push(c),
+            c => c_name.push_str(&format!("_{:x}_", u32::from(c))),
+        }
+    }
+    c_name
+}
+
+/// Representation of a value in C
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Int,
+    F32,
+    F64,
+}
+
+impl Kind {
+    fn of(ty: &Type) -> Self {
+        match ty {
+            Type::Primitive(PrimitiveType::F32) => Kind::F32,
+            Type::Primitive(PrimitiveType::F64) => Kind::F64,
+            _ => Kind::Int,
+        }
+    }
+
+    fn c_type(self) -> &'static str {
+        match self {
+            Kind::Int => "int64_t",
+            Kind::F32 => "float",
+            Kind::F64 => "double",
+        }
+    }
+
+    /// Suffix of the prelude's load and store helpers
+    fn suffix(self) -> &'static str {
+        match self {
+            Kind::Int => "i64",
+            Kind::F32 => "f32",
+            Kind::F64 => "f64",
+        }
+    }
+
+    fn is_float(self) -> bool {
+        self != Kind::Int
+    }
+}
+
+/// C return type of a function returning `ty`
+fn return_type(ty: &Type) -> &'static str {
+    match ty {
+        Type::Unit | Type::Never => "void",
+        ty => Kind::of(ty).c_type(),
+    }
+}
+
+/// Lowers MIR to C99 source
+pub struct CEmitter {
+    /// Layouts used to size stack slots and copy aggregate arguments
+    layouts: LayoutTable,
+    /// Parameter types and return type of each function of the module
+    functions: HashMap<String, (Vec<Type>, Type)>,
+    /// Representation of each value of the current function
+    kinds: HashMap<ValueId, Kind>,
+    /// Sizes in words of the current function's stack slots and argument
+    /// copies
+    buffers: Vec<u64>,
+    /// Return type of the current function
+    ret: Type,
+}
+
+impl CEmitter {
+    pub fn new() -> Self {
+        Self {
+            layouts: LayoutTable::new(),
+            functions: HashMap::new(),
+            kinds: HashMap::new(),
+            buffers: Vec::new(),
+            ret: Type::Unit,
+        }
+    }
+
+    /// Lower a MIR module to a C translation unit
+    ///
+    /// A `main` function in the module is called by the C `main`, which
+    /// exits with its result.
+    pub fn emit_module(&mut self, mir_module: &MirModule) -> String {
+        self.layouts = mir_module.layouts.clone();
+
+        // Emit in a fixed order so the output is deterministic
+        let mut mir_functions: Vec<_> = mir_module.functions.values().collect();
+        mir_functions.sort_by_key(|func| func.id);
+        self.functions = mir_functions
+            .iter()
+            .map(|func| (func.name.clone(), (param_types(func), func.ret_ty.clone())))
+            .collect();
+
+        let mut output = format!("/* C99 for module: main */\n{}", PRELUDE);
+
+        let externs = self.external_prototypes(&mir_functions);
+        if !externs.is_empty() {
+            output.push('\n');
+            for prototype in externs {
+                output.push_str(&prototype);
+            }
+        }
+
+        output.push('\n');
+        for func in &mir_functions {
+            output.push_str(&format!("{};\n", self.function_header(func)));
+        }
+
+        for func in &mir_functions {
+            output.push('\n');
+            output.push_str(&self.emit_function(func));
+        }
+
+        if let Some((params, ret)) = self.functions.get("main") {
+            let args = vec!["0"; params.len()].join(", ");
+            let call = format!("{}({})", function_name("main"), args);
+            output.push_str("\nint main(void) {\n");
+            match return_type(ret) {
+                "void" => output.push_str(&format!("    {};\n    return 0;\n", call)),
+                "int64_t" => output.push_str(&format!("    return (int){};\n", call)),
+                _ => output.push_str(&format!("    return (int)(int64_t){};\n", call)),
+            }
+            output.push_str("}\n");
+        }
+        output
+    }
+
+    /// Lower a MIR function to a C function definition
+    pub fn emit_function(&mut self, mir_func: &MirFunction) -> String {
+        self.kinds.clear();
+        self.buffers.clear();
+        self.ret = mir_func.ret_ty.clone();
+
+        for (&param, ty) in mir_func.params.iter().zip(param_types(mir_func)) {
+            self.kinds.insert(param, Kind::of(&ty));
+        }
+        let mut values: Vec<ValueId> = mir_func.values.keys().copied().collect();
+        let mut phis = Vec::new();
+        for block in mir_func.blocks.values() {
+            for inst in &block.instructions {
+                values.extend(inst.dest());
+                values.extend(inst.operands().into_iter().filter_map(|op| match op {
+                    MirOp::Value(value) => Some(*value),
+                    MirOp::Const(_) => None,
+                }));
+                if let MirInst::Phi { dest, .. } = inst {
+                    phis.push(*dest);
+                }
+            }
+        }
+        values.sort_unstable();
+        values.dedup();
+        phis.sort_unstable();
+        for &value in &values {
+            let kind = mir_func.value(value).map_or(Kind::Int, |value| Kind::of(&value.ty));
+            self.kinds.entry(value).or_insert(kind);
+        }
+
+        // The entry block comes first, falling into no other
+        let mut block_ids: Vec<BlockId> = mir_func.blocks.keys().copied().collect();
+        block_ids.sort_by_key(|&id| (id != mir_func.entry, id));
+        let targets: Vec<BlockId> = mir_func
+            .blocks
+            .values()
+            .flat_map(|block| &block.instructions)
+            .flat_map(|inst| match inst {
+                MirInst::Jump { target, .. } => vec![*target],
+                MirInst::Branch {
+                    then_block, else_block, ..
+                } => vec![*then_block, *else_block],
+                _ => vec![],
+            })
+            .collect();
+        let mut body = String::new();
+        for block_id in block_ids {
+            if targets.contains(&block_id) {
+                body.push_str(&format!("bb{}:\n", block_id));
+            }
+            let instructions = &mir_func.blocks[&block_id].instructions;
+            for inst in instructions {
+                self.emit_instruction(&mut body, block_id, inst, mir_func);
+            }
+            // A block without a terminator returns
+            if !instructions.last().is_some_and(BasicBlock::is_terminator) {
+                self.emit_return(&mut body, None);
+            }
+        }
+
+        let mut output = format!("{} {{\n", self.function_header(mir_func));
+        for value in values.iter().filter(|value| !mir_func.params.contains(value)) {
+            output.push_str(&format!("    {} v{} = 0;\n", self.kind(*value).c_type(), value));
+        }
+        for phi in &phis {
+            output.push_str(&format!("    {} p{} = 0;\n", self.kind(*phi).c_type(), phi));
+        }
+        for (i, words) in self.buffers.iter().enumerate() {
+            output.push_str(&format!("    int64_t s{}[{}];\n", i, words));
+        }
+        output.push_str(&body);
+        output.push_str("}\n");
+        output
+    }
+
+    /// `static RET aurora_fn_NAME(PARAMS)`
+    fn function_header(&self, mir_func: &MirFunction) -> String {
+        let params: Vec<String> = mir_func
+            .params
+            .iter()
+            .zip(param_types(mir_func))
+            .map(|(param, ty)| format!("{} v{}", Kind::of(&ty).c_type(), param))
+            .collect();
+        let params = if params.is_empty() {
+            "void".to_string()
+        } else {
+            params.join(", ")
+        };
+        format!(
+            "static {} {}({})",
+            return_type(&mir_func.ret_ty),
+            function_name(&mir_func.name),
+            params
+        )
+    }
+
+    fn emit_instruction(&mut self, out: &mut String, block: BlockId, inst: &MirInst, mir_func: &MirFunction) {
+        match inst {
+            MirInst::Assign { dest, value, .. } => {
+                let value = self.operand(value, self.kind(*dest));
+                self.assign(out, *dest, value, self.kind(*dest));
+            }
+
+            MirInst::BinOp {
+                dest, op, lhs, rhs, ..
+            } => {
+                let dest_kind = self.kind(*dest);
+                let float = Some(dest_kind)
+                    .filter(|kind| kind.is_float())
+                    .or_else(|| self.float_kind(&[lhs, rhs]));
+                match float {
+                    Some(kind) => self.emit_float_binop(out, *dest, op, lhs, rhs, kind),
+                    None => {
+                        let (a, b) = (self.operand(lhs, Kind::Int), self.operand(rhs, Kind::Int));
+                        let value = match op {
+                            BinOp::Add => format!("(int64_t)((uint64_t){} + (uint64_t){})", a, b),
+                            BinOp::Sub => format!("(int64_t)((uint64_t){} - (uint64_t){})", a, b),
+                            BinOp::Mul => format!("(int64_t)((uint64_t){} * (uint64_t){})", a, b),
+                            BinOp::Div => format!("{} / {}", a, b),
+                            BinOp::Mod => format!("{} % {}", a, b),
+                            BinOp::BitAnd | BinOp::And => format!("{} & {}", a, b),
+                            BinOp::BitOr | BinOp::Or => format!("{} | {}", a, b),
+                            BinOp::BitXor => format!("{} ^ {}", a, b),
+                            // Shift counts are taken modulo 64, as on x86_64
+                            BinOp::Shl => format!("(int64_t)((uint64_t){} << ({} & 63))", a, b),
+                            BinOp::Shr => format!("(int64_t)((uint64_t){} >> ({} & 63))", a, b),
+                            op => format!("(int64_t)({} {} {})", a, comparison(op), b),
+                        };
+                        self.assign(out, *dest, value, Kind::Int);
+                    }
+                }
+            }
+
+            MirInst::UnaryOp { dest, op, value, .. } => {
+                let float = self.float_kind(&[value]).filter(|_| *op == UnaryOp::Neg);
+                match float {
+                    Some(kind) => {
+                        let value = format!("-{}", self.operand(value, kind));
+                        self.assign(out, *dest, value, kind);
+                    }
+                    None => {
+                        let a = self.operand(value, Kind::Int);
+                        let value = match op {
+                            UnaryOp::Neg => format!("(int64_t)(0 - (uint64_t){})", a),
+                            UnaryOp::Not => format!("{} ^ 1", a),
+                            UnaryOp::BitNot => format!("~{}", a),
+                        };
+                        self.assign(out, *dest, value, Kind::Int);
+                    }
+                }
+            }
+
+            MirInst::Call { dest, func: callee, args, .. } => self.emit_call(out, dest, callee, args, mir_func),
+
+            MirInst::Load { dest, ptr, .. } => {
+                let kind = self.kind(*dest);
+                let value = format!("aurora_load_{}({})", kind.suffix(), self.operand(ptr, Kind::Int));
+                self.assign(out, *dest, value, kind);
+            }
+
+            MirInst::Store { ptr, value, .. } => {
+                // A float is stored at the width of the pointed-to type
+                let stored = match ptr {
+                    MirOp::Value(ptr) => match mir_func.value(*ptr).map(|value| &value.ty) {
+                        Some(Type::Ptr { inner, .. } | Type::Ref { inner, .. }) => {
+                            Some(Kind::of(inner)).filter(|kind| kind.is_float())
+                        }
+                        _ => None,
+                    },
+                    MirOp::Const(_) => None,
+                };
+                let kind = stored.or_else(|| self.float_kind(&[value])).unwrap_or(Kind::Int);
+                out.push_str(&format!(
+                    "    aurora_store_{}({}, {});\n",
+                    kind.suffix(),
+                    self.operand(ptr, Kind::Int),
+                    self.operand(value, kind)
+                ));
+            }
+
+            MirInst::Alloca { dest, ty, .. } => {
+                let buffer = self.buffer(ty);
+                self.assign(out, *dest, format!("(int64_t)(intptr_t)s{}", buffer), Kind::Int);
+            }
+
+            MirInst::Cast { dest, value, .. } => {
+                let from = self.float_kind(&[value]).unwrap_or(Kind::Int);
+                let to = self.kind(*dest);
+                let value = self.operand(value, from);
+                let value = match from == to {
+                    true => value,
+                    false => format!("({}){}", to.c_type(), value),
+                };
+                self.assign(out, *dest, value, to);
+            }
+
+            MirInst::GetElement { dest, base, index, .. } => {
+                let value = format!(
+                    "(int64_t)((uint64_t){} + (uint64_t){})",
+                    self.operand(base, Kind::Int),
+                    self.operand(index, Kind::Int)
+                );
+                self.assign(out, *dest, value, Kind::Int);
+            }
+
+            MirInst::Phi { dest, .. } => {
+                out.push_str(&format!("    v{} = p{};\n", dest, dest));
+            }
+
+            MirInst::Return { value, .. } => self.emit_return(out, value.as_ref()),
+
+            MirInst::Jump { target, .. } => {
+                self.emit_phi_copies(out, block, *target, mir_func, "    ");
+                out.push_str(&format!("    goto bb{};\n", target));
+            }
+
+            MirInst::Branch {
+                cond,
+                then_block,
+                else_block,
+                ..
+            } => {
+                out.push_str(&format!("    if ({} != 0) {{\n", self.operand(cond, Kind::Int)));
+                self.emit_phi_copies(out, block, *then_block, mir_func, "        ");
+                out.push_str(&format!("        goto bb{};\n    }} else {{\n", then_block));
+                self.emit_phi_copies(out, block, *else_block, mir_func, "        ");
+                out.push_str(&format!("        goto bb{};\n    }}\n", else_block));
+            }
+        }
+    }
+
+    fn emit_float_binop(&self, out: &mut String, dest: ValueId, op: &BinOp, lhs: &MirOp, rhs: &MirOp, kind: Kind) {
+        let (a, b) = (self.operand(lhs, kind), self.operand(rhs, kind));
+        match op {
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div => {
+                let symbol = match op {
+                    BinOp::Add => "+",
+                    BinOp::Sub => "-",
+                    BinOp::Mul => "*",
+                    _ => "/",
+                };
+                self.assign(out, dest, format!("{} {} {}", a, symbol, b), kind);
+            }
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+                let value = format!("(int64_t)({} {} {})", a, comparison(op), b);
+                self.assign(out, dest, value, Kind::Int);
+            }
+            // There is no float remainder or bitwise arithmetic
+            _ => {}
+        }
+    }
+
+    fn emit_call(
+        &mut self,
+        out: &mut String,
+        dest: &Option<ValueId>,
+        callee: &MirOp,
+        args: &[MirOp],
+        mir_func: &MirFunction,
+    ) {
+        // Calls through function pointers are not supported
+        let MirOp::Const(Constant::String(name)) = callee else {
+            return out.push_str("    abort();\n");
+        };
+
+        if let (Some(dest), [arg]) = (dest, args) {
+            let intrinsic = match name.as_str() {
+                "f32.sqrt" => Some((Kind::F32, "sqrtf")),
+                "f64.sqrt" => Some((Kind::F64, "sqrt")),
+                _ => None,
+            };
+            if let Some((kind, function)) = intrinsic {
+                let value = format!("{}({})", function, self.operand(arg, kind));
+                return self.assign(out, *dest, value, kind);
+            }
+        }
+
+        let zero = MirOp::Const(Constant::Int(0));
+        let (call, ret) = if let Some((params, ret)) = self.functions.get(name).cloned() {
+            // Aggregates are passed as the address of a copy, except for
+            // the slot an aggregate result is written to
+            let returns_aggregate = self.layouts.is_aggregate(&ret);
+            let mut call_args = Vec::new();
+            for (i, ty) in params.iter().enumerate() {
+                let arg = self.operand(args.get(i).unwrap_or(&zero), Kind::of(ty));
+                if self.layouts.is_aggregate(ty) && !(i == 0 && returns_aggregate) {
+                    let buffer = self.buffer(ty);
+                    out.push_str(&format!(
+                        "    memcpy(s{}, (void *)(intptr_t){}, sizeof s{});\n",
+                        buffer, arg, buffer
+                    ));
+                    call_args.push(format!("(int64_t)(intptr_t)s{}", buffer));
+                } else {
+                    call_args.push(arg);
+                }
+            }
+            let call = format!("{}({})", function_name(name), call_args.join(", "));
+            (call, Some(ret).filter(|ret| !matches!(ret, Type::Unit | Type::Never)).map(|ret| Kind::of(&ret)))
+        } else if let Some((_, params, returns)) = RUNTIME_FUNCTIONS.iter().find(|(function, ..)| function == name) {
+            // Like the C runtime's, print and println take their first
+            // argument only
+            let call_args: Vec<String> = params
+                .iter()
+                .enumerate()
+                .map(|(i, &c_type)| match (args.get(i), c_type) {
+                    (Some(arg), "int") => format!("(int){}", self.operand(arg, Kind::Int)),
+                    (Some(arg), c_type) => format!("({})(intptr_t){}", c_type, self.operand(arg, Kind::Int)),
+                    (None, "int") => "0".to_string(),
+                    (None, _) => "\"\"".to_string(),
+                })
+                .collect();
+            (format!("{}({})", name, call_args.join(", ")), returns.then_some(Kind::Int))
+        } else {
+            let call_args: Vec<String> = args
+                .iter()
+                .map(|arg| self.operand(arg, operand_kind(arg, mir_func)))
+                .collect();
+            let ret = dest.map(|dest| self.kind(dest));
+            (format!("{}({})", name, call_args.join(", ")), ret)
+        };
+
+        match (dest, ret) {
+            (Some(dest), Some(kind)) => self.assign(out, *dest, call, kind),
+            (Some(dest), None) => {
+                out.push_str(&format!("    {};\n", call));
+                let zero = self.operand(&zero, self.kind(*dest));
+                self.assign(out, *dest, zero, self.kind(*dest));
+            }
+            (None, _) => out.push_str(&format!("    {};\n", call)),
+        }
+    }
+
+    fn emit_return(&self, out: &mut String, value: Option<&MirOp>) {
+        match return_type(&self.ret) {
+            "void" => out.push_str("    return;\n"),
+            _ => {
+                let kind = Kind::of(&self.ret);
+                let value = self.operand(value.unwrap_or(&MirOp::Const(Constant::Int(0))), kind);
+                out.push_str(&format!("    return {};\n", value));
+            }
+        }
+    }
+
+    /// Set the phi temporaries of `to` from their inputs on the edge from
+    /// `from`
+    fn emit_phi_copies(&self, out: &mut String, from: BlockId, to: BlockId, mir_func: &MirFunction, indent: &str) {
+        let Some(block) = mir_func.block(to) else {
+            return;
+        };
+        for inst in &block.instructions {
+            if let MirInst::Phi { dest, inputs, .. } = inst {
+                if let Some((_, input)) = inputs.iter().find(|(pred, _)| *pred == from) {
+                    out.push_str(&format!("{}p{} = {};\n", indent, dest, self.operand(input, self.kind(*dest))));
+                }
+            }
+        }
+    }
+
+    /// Set a value from an expression of representation `kind`
+    fn assign(&self, out: &mut String, dest: ValueId, value: String, kind: Kind) {
+        let value = convert(value, kind, self.kind(dest));
+        out.push_str(&format!("    v{} = {};\n", dest, value));
+    }
+
+    /// An operand as an expression of representation `kind`
+    ///
+    /// Integer constants are converted to floats by value, and other
+    /// operands of the wrong representation keep their bits.
+    fn operand(&self, op: &MirOp, kind: Kind) -> String {
+        let int = match op {
+            MirOp::Value(value) => return convert(format!("v{}", value), self.kind(*value), kind),
+            MirOp::Const(Constant::Float(bits)) => {
+                let value = f64::from_bits(*bits);
+                return match kind {
+                    Kind::Int => int_literal(*bits as i64),
+                    Kind::F32 => float_literal(f64::from(value as f32), "f"),
+                    Kind::F64 => float_literal(value, ""),
+                };
+            }
+            MirOp::Const(Constant::String(s)) => {
+                return convert(format!("(int64_t)(intptr_t){}", string_literal(s)), Kind::Int, kind);
+            }
+            MirOp::Const(Constant::Int(int)) => *int,
+            MirOp::Const(Constant::Bool(b)) => i64::from(*b),
+            MirOp::Const(Constant::Unit) => 0,
+        };
+        match kind {
+            Kind::Int => int_literal(int),
+            Kind::F32 => float_literal(f64::from(int as f32), "f"),
+            Kind::F64 => float_literal(int as f64, ""),
+        }
+    }
+
+    fn kind(&self, value: ValueId) -> Kind {
+        self.kinds.get(&value).copied().unwrap_or(Kind::Int)
+    }
+
+    /// Representation of the first float among `ops`, if any is a float
+    fn float_kind(&self, ops: &[&MirOp]) -> Option<Kind> {
+        let values = ops.iter().find_map(|op| match op {
+            MirOp::Value(value) => Some(self.kind(*value)).filter(|kind| kind.is_float()),
+            MirOp::Const(_) => None,
+        });
+        values.or_else(|| {
+            ops.iter()
+                .any(|op| matches!(op, MirOp::Const(Constant::Float(_))))
+                .then_some(Kind::F64)
+        })
+    }
+
+    /// Add a local buffer holding a value of type `ty`, returning its number
+    fn buffer(&mut self, ty: &Type) -> usize {
+        let words = self.layouts.size_of(ty).div_ceil(WORD_SIZE).max(1);
+        self.buffers.push(words);
+        self.buffers.len() - 1
+    }
+
+    /// Prototypes of the functions called but not defined in the module,
+    /// typed by their first call
+    fn external_prototypes(&self, mir_functions: &[&MirFunction]) -> Vec<String> {
+        let builtin = ["f32.sqrt", "f64.sqrt"];
+        let mut names = Vec::new();
+        let mut prototypes = Vec::new();
+        for func in mir_functions {
+            let mut block_ids: Vec<_> = func.blocks.keys().copied().collect();
+            block_ids.sort();
+            for inst in block_ids.iter().flat_map(|id| &func.blocks[id].instructions) {
+                if let MirInst::Call {
+                    dest,
+                    func: MirOp::Const(Constant::String(name)),
+                    args,
+                    ..
+                } = inst
+                {
+                    if self.functions.contains_key(name)
+                        || builtin.contains(&name.as_str())
+                        || RUNTIME_FUNCTIONS.iter().any(|(function, ..)| function == name)
+                        || names.contains(name)
+                    {
+                        continue;
+                    }
+                    let params: Vec<&str> = args.iter().map(|arg| operand_kind(arg, func).c_type()).collect();
+                    let params = if params.is_empty() {
+                        "void".to_string()
+                    } else {
+                        params.join(", ")
+                    };
+                    let ret = dest.map_or("void", |dest| operand_kind(&MirOp::Value(dest), func).c_type());
+                    names.push(name.clone());
+                    prototypes.push((name.clone(), format!("{} {}({});\n", ret, name, params)));
+                }
+            }
+        }
+        prototypes.sort();
+        prototypes.into_iter().map(|(_, prototype)| prototype).collect()
+    }
+}
+
+impl Default for CEmitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Types of a function's parameters
+fn param_types(mir_func: &MirFunction) -> Vec<Type> {
+    mir_func
+        .params
+        .iter()
+        .map(|&param| match mir_func.value(param) {
+            Some(value) => value.ty.clone(),
+            None => Type::Primitive(PrimitiveType::I64),
+        })
+        .collect()
+}
+
+/// Representation an operand is passed in to a function outside the module
+fn operand_kind(op: &MirOp, mir_func: &MirFunction) -> Kind {
+    match op {
+        MirOp::Value(value) => mir_func.value(*value).map_or(Kind::Int, |value| Kind::of(&value.ty)),
+        MirOp::Const(Constant::Float(_)) => Kind::F64,
+        MirOp::Const(_) => Kind::Int,
+    }
+}
+
+/// C operator of a comparison
+fn comparison(op: &BinOp) -> &'static str {
+    match op {
+        BinOp::Eq => "==",
+        BinOp::Ne => "!=",
+        BinOp::Lt => "<",
+        BinOp::Le => "<=",
+        BinOp::Gt => ">",
+        _ => ">=",
+    }
+}
+
+/// Convert an expression between representations, keeping its bits
+/// between integers and floats
+fn convert(value: String, from: Kind, to: Kind) -> String {
+    match (from, to) {
+        (from, to) if from == to => value,
+        (Kind::Int, float) => format!("aurora_bits_{}({})", float.suffix(), value),
+        (float, Kind::Int) => format!("aurora_{}_bits({})", float.suffix(), value),
+        (_, float) => format!("({}){}", float.c_type(), value),
+    }
+}
+
+fn int_literal(value: i64) -> String {
+    match value {
+        // The minimum cannot be written as a negated literal
+        i64::MIN => "INT64_MIN".to_string(),
+        value if value < 0 => format!("(-INT64_C({}))", value.unsigned_abs()),
+        value => format!("INT64_C({})", value),
+    }
+}
+
+/// A float literal, with `suffix` `f` at single precision
+fn float_literal(value: f64, suffix: &str) -> String {
+    let literal = if value.is_nan() {
+        "NAN".to_string()
+    } else if value.is_infinite() {
+        "INFINITY".to_string()
+    } else {
+        // Debug formatting round-trips and always has a `.` or exponent
+        let text = if suffix.is_empty() {
+            format!("{:?}", value.abs())
+        } else {
+            format!("{:?}", (value as f32).abs())
+        };
+        format!("{}{}", text, suffix)
+    };
+    match value.is_sign_negative() && !value.is_nan() {
+        true => format!("(-{})", literal),
+        false => literal,
+    }
+}
+
+/// A string constant as a C string literal
+fn string_literal(s: &str) -> String {
+    let mut literal = String::from("\"");
+    for byte in s.bytes() {
+        match byte {
+            b'"' => literal.push_str("\\\""),
+            b'\\' => literal.push_str("\\\\"),
+            b'\n' => literal.push_str("\\n"),
+            b'\t' => literal.push_str("\\t"),
+            // Octal escapes take at most three digits, so they cannot run
+            // into the next character the way hex escapes can
+            0x20..=0x7E => literal.push(byte as char),
+            byte => literal.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Compile C sources to an executable, linking the math library
+pub fn compile_c(
+    compiler: &str,
+    sources: &[PathBuf],
+    output_path: &Path,
+    opt_level: OptLevel,
+    debug_info: bool,
+) -> Result<(), BackendError> {
+    let mut cmd = Command::new(compiler);
+    cmd.arg("-std=c99").arg(opt_level.to_gcc_flag());
+    if debug_info {
+        cmd.arg("-g");
+    }
+    cmd.arg("-o").arg(output_path).args(sources).arg("-lm");
+
+    let status = cmd
+        .status()
+        .map_err(|e| BackendError::CompilationFailed(format!("Failed to run {}: {}", compiler, e)))?;
+    if !status.success() {
+        return Err(BackendError::CompilationFailed(format!(
+            "{} returned non-zero exit code: {}",
+            compiler,
+            status.code().unwrap_or(-1)
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aurora_mir::{MirBuilder, Span};
+    use aurora_types::{EffectSet, StructDef};
+    use std::fs;
+
+    fn i64_ty() -> Type {
+        Type::Primitive(PrimitiveType::I64)
+    }
+
+    /// `fib(n)` keeping two phis that exchange values, `first` reading a
+    /// struct passed by value, and `main` printing them and exiting with
+    /// `fib(10) + 1`
+    fn sample_module() -> MirModule {
+        let pair = Type::Named {
+            name: "Pair".to_string(),
+            args: vec![],
+        };
+        let mut module = MirModule::new();
+        module.layouts.add_struct(
+            "Pair".to_string(),
+            StructDef {
+                params: vec![],
+                fields: vec![("a".to_string(), i64_ty()), ("b".to_string(), i64_ty())],
+            },
+        );
+        let mut builder = MirBuilder::new();
+
+        builder.start_function(0, "fib".to_string(), i64_ty(), EffectSet::PURE);
+        let n = builder.add_param(i64_ty(), Span::dummy());
+        let entry = builder.current_block().unwrap();
+        let head = builder.new_block();
+        let body = builder.new_block();
+        let exit = builder.new_block();
+        builder.build_jump(head, Span::dummy());
+        builder.set_block(head);
+        let next_i = builder.new_value(i64_ty(), Span::dummy());
+        let sum = builder.new_value(i64_ty(), Span::dummy());
+        let i = builder.build_phi(vec![(entry, MirOp::Const(Constant::Int(0))), (body, MirOp::Value(next_i))], i64_ty(), Span::dummy());
+        // a, b = b, a + b
+        let a = builder.new_value(i64_ty(), Span::dummy());
+        let b = builder.new_value(i64_ty(), Span::dummy());
+        for (dest, first, next) in [(a, 0, MirOp::Value(b)), (b, 1, MirOp::Value(sum))] {
+            builder.emit(MirInst::Phi {
+                dest,
+                inputs: vec![(entry, MirOp::Const(Constant::Int(first))), (body, next)],
+                span: Span::dummy(),
+            });
+        }
+        let more = builder.build_binop(BinOp::Lt, MirOp::Value(i), MirOp::Value(n), Type::Primitive(PrimitiveType::Bool), Span::dummy());
+        builder.build_branch(MirOp::Value(more), body, exit, Span::dummy());
+        builder.set_block(body);
+        for (dest, lhs, rhs) in [(sum, a, MirOp::Value(b)), (next_i, i, MirOp::Const(Constant::Int(1)))] {
+            builder.emit(MirInst::BinOp {
+                dest,
+                op: BinOp::Add,
+                lhs: MirOp::Value(lhs),
+                rhs,
+                span: Span::dummy(),
+            });
+        }
+        builder.build_jump(head, Span::dummy());
+        builder.set_block(exit);
+        builder.build_return(Some(MirOp::Value(a)), Span::dummy());
+        module.add_function(builder.finish_function().unwrap());
+
+        builder.start_function(1, "first".to_string(), i64_ty(), EffectSet::PURE);
+        let p = builder.add_param(pair.clone(), Span::dummy());
+        let a = builder.build_load(MirOp::Value(p), i64_ty(), Span::dummy());
+        builder.build_store(MirOp::Value(p), MirOp::Const(Constant::Int(0)), Span::dummy());
+        builder.build_return(Some(MirOp::Value(a)), Span::dummy());
+        module.add_function(builder.finish_function().unwrap());
+
+        builder.start_function(2, "main".to_string(), i64_ty(), EffectSet::IO);
+        let slot = builder.build_alloca(pair, Span::dummy());
+        builder.build_store(MirOp::Value(slot), MirOp::Const(Constant::Int(1)), Span::dummy());
+        let fib = MirOp::Const(Constant::String("fib".to_string()));
+        let f = builder.build_call(fib, vec![MirOp::Const(Constant::Int(10))], Some(i64_ty()), EffectSet::PURE, Span::dummy()).unwrap();
+        let println = MirOp::Const(Constant::String("println".to_string()));
+        builder.build_call(println.clone(), vec![MirOp::Const(Constant::String("fib \"10\"".to_string()))], None, EffectSet::IO, Span::dummy());
+        // Numbers are formatted into a string before they are printed, and
+        // the string freed after
+        let routine = |name: &str| MirOp::Const(Constant::String(name.to_string()));
+        let buffer_ty = Type::Ptr { inner: Box::new(Type::Primitive(PrimitiveType::U8)), mutable: true };
+        let buffer = builder.build_call(routine("aurora_fmt_new"), Vec::new(), Some(buffer_ty), EffectSet::ALLOC, Span::dummy()).unwrap();
+        builder.build_call(routine("aurora_fmt_i64"), vec![MirOp::Value(buffer), MirOp::Value(f)], None, EffectSet::ALLOC, Span::dummy());
+        let text = builder.build_call(routine("aurora_fmt_finish"), vec![MirOp::Value(buffer)], Some(Type::Primitive(PrimitiveType::Str)), EffectSet::ALLOC, Span::dummy()).unwrap();
+        builder.build_call(println, vec![MirOp::Value(text)], None, EffectSet::IO, Span::dummy());
+        builder.build_call(routine("aurora_fmt_free"), vec![MirOp::Value(text)], None, EffectSet::ALLOC, Span::dummy());
+        // `first` clears its copy, so the second call sees 1 again
+        let first = MirOp::Const(Constant::String("first".to_string()));
+        builder.build_call(first.clone(), vec![MirOp::Value(slot)], Some(i64_ty()), EffectSet::PURE, Span::dummy());
+        let one = builder.build_call(first, vec![MirOp::Value(slot)], Some(i64_ty()), EffectSet::PURE, Span::dummy()).unwrap();
+        let result = builder.build_binop(BinOp::Add, MirOp::Value(f), MirOp::Value(one), i64_ty(), Span::dummy());
+        builder.build_return(Some(MirOp::Value(result)), Span::dummy());
+        module.add_function(builder.finish_function().unwrap());
+        module
+    }
+
+    #[test]
+    fn test_phis_are_assigned_through_temporaries() {
+        let mut module = MirModule::new();
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "main".to_string(), i64_ty(), EffectSet::PURE);
+        let entry = builder.current_block().unwrap();
+        let head = builder.new_block();
+        builder.build_jump(head, Span::dummy());
+        builder.set_block(head);
+        let (x, y) = (builder.new_value(i64_ty(), Span::dummy()), builder.new_value(i64_ty(), Span::dummy()));
+        for (dest, first, next) in [(x, 1, y), (y, 2, x)] {
+            builder.emit(MirInst::Phi {
+                dest,
+                inputs: vec![(entry, MirOp::Const(Constant::Int(first))), (head, MirOp::Value(next))],
+                span: Span::dummy(),
+            });
+        }
+        builder.build_jump(head, Span::dummy());
+        module.add_function(builder.finish_function().unwrap());
+
+        let c = CEmitter::new().emit_module(&module);
+        let swap = format!(
+            "bb{head}:\n    v{x} = p{x};\n    v{y} = p{y};\n    p{x} = v{y};\n    p{y} = v{x};\n    goto bb{head};\n"
+        );
+        assert!(c.contains(&swap), "{}", c);
+        assert!(c.contains(&format!("    p{x} = INT64_C(1);\n    p{y} = INT64_C(2);\n    goto bb{head};\n")));
+        assert!(c.contains("static int64_t aurora_fn_main(void);"));
+        assert!(c.contains("    return (int)aurora_fn_main();"));
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(int_literal(-5), "(-INT64_C(5))");
+        assert_eq!(int_literal(i64::MIN), "INT64_MIN");
+        assert_eq!(float_literal(1.0, ""), "1.0");
+        assert_eq!(float_literal(-0.1, "f"), "(-0.1f)");
+        assert_eq!(float_literal(f64::NEG_INFINITY, ""), "(-INFINITY)");
+        assert_eq!(function_name("Point.new_x"), "aurora_fn_Point_2e_new_5f_x");
+        assert_eq!(string_literal("a\"b\\\n\u{7f}1"), "\"a\\\"b\\\\\\n\\1771\"");
+    }
+
+    /// Compile and run with the system C compiler, where there is one
+    #[test]
+    fn test_compile_and_run() {
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        let runtime = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../runtime/c_runtime.c");
+        let dir = std::env::temp_dir().join(format!("aurora_cgen_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("main.c");
+        let output = dir.join("main");
+        fs::write(&source, CEmitter::new().emit_module(&sample_module())).unwrap();
+
+        compile_c("cc", &[source, runtime], &output, OptLevel::Default, false).unwrap();
+        let run = Command::new(&output).output().unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "fib \"10\"\n55\n");
+        assert_eq!(run.status.code(), Some(56));
+    }
+}
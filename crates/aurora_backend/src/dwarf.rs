@@ -0,0 +1,759 @@
+//! DWARF 4 debug info for objects written by the native encoder
+//!
+//! Produces `.debug_abbrev`, `.debug_info` with a compile unit holding a
+//! subprogram per function and its parameters and locals, `.debug_line`
+//! from the `Loc` markers of the AIR, and `.debug_frame` describing the
+//! prologues and epilogues. Addresses and references between sections are
+//! left to relocations so the linker can combine them with other objects'
+//! debug info.
+
+use crate::elf::{DebugRelocation, DebugRelocationKind, DebugSection, SectionRef};
+use aurora_air::air::XMM_REGISTERS;
+use aurora_air::debug::{CfiOp, DebugType, DebugVariable, Encoding, VariableKind, VariableLocation};
+use aurora_air::Register;
+use std::collections::HashMap;
+
+/// Index of each section in the list returned by [`debug_sections`]
+const ABBREV: usize = 0;
+const INFO: usize = 1;
+const LINE: usize = 2;
+const FRAME: usize = 3;
+
+const DW_TAG_ARRAY_TYPE: u16 = 0x01;
+const DW_TAG_FORMAL_PARAMETER: u16 = 0x05;
+const DW_TAG_MEMBER: u16 = 0x0d;
+const DW_TAG_POINTER_TYPE: u16 = 0x0f;
+const DW_TAG_COMPILE_UNIT: u16 = 0x11;
+const DW_TAG_STRUCTURE_TYPE: u16 = 0x13;
+const DW_TAG_SUBRANGE_TYPE: u16 = 0x21;
+const DW_TAG_BASE_TYPE: u16 = 0x24;
+const DW_TAG_SUBPROGRAM: u16 = 0x2e;
+const DW_TAG_VARIABLE: u16 = 0x34;
+
+const DW_AT_LOCATION: u16 = 0x02;
+const DW_AT_NAME: u16 = 0x03;
+const DW_AT_BYTE_SIZE: u16 = 0x0b;
+const DW_AT_STMT_LIST: u16 = 0x10;
+const DW_AT_LOW_PC: u16 = 0x11;
+const DW_AT_HIGH_PC: u16 = 0x12;
+const DW_AT_LANGUAGE: u16 = 0x13;
+const DW_AT_COMP_DIR: u16 = 0x1b;
+const DW_AT_PRODUCER: u16 = 0x25;
+const DW_AT_COUNT: u16 = 0x37;
+const DW_AT_DATA_MEMBER_LOCATION: u16 = 0x38;
+const DW_AT_DECL_FILE: u16 = 0x3a;
+const DW_AT_DECL_LINE: u16 = 0x3b;
+const DW_AT_ENCODING: u16 = 0x3e;
+const DW_AT_EXTERNAL: u16 = 0x3f;
+const DW_AT_FRAME_BASE: u16 = 0x40;
+const DW_AT_TYPE: u16 = 0x49;
+
+const DW_FORM_ADDR: u16 = 0x01;
+const DW_FORM_DATA2: u16 = 0x05;
+const DW_FORM_DATA4: u16 = 0x06;
+const DW_FORM_DATA8: u16 = 0x07;
+const DW_FORM_STRING: u16 = 0x08;
+const DW_FORM_DATA1: u16 = 0x0b;
+const DW_FORM_REF4: u16 = 0x13;
+const DW_FORM_SEC_OFFSET: u16 = 0x17;
+const DW_FORM_EXPRLOC: u16 = 0x18;
+const DW_FORM_FLAG_PRESENT: u16 = 0x19;
+
+/// The closest language debuggers know
+const DW_LANG_C99: u16 = 0x0c;
+
+const DW_OP_DEREF: u8 = 0x06;
+const DW_OP_REG0: u8 = 0x50;
+const DW_OP_BREG0: u8 = 0x70;
+const DW_OP_REGX: u8 = 0x90;
+const DW_OP_FBREG: u8 = 0x91;
+const DW_OP_BREGX: u8 = 0x92;
+const DW_OP_CALL_FRAME_CFA: u8 = 0x9c;
+
+const DW_LNS_COPY: u8 = 0x01;
+const DW_LNS_ADVANCE_PC: u8 = 0x02;
+const DW_LNS_ADVANCE_LINE: u8 = 0x03;
+const DW_LNS_SET_COLUMN: u8 = 0x05;
+const DW_LNE_END_SEQUENCE: u8 = 0x01;
+const DW_LNE_SET_ADDRESS: u8 = 0x02;
+
+const DW_CFA_ADVANCE_LOC: u8 = 0x40;
+const DW_CFA_OFFSET: u8 = 0x80;
+const DW_CFA_RESTORE: u8 = 0xc0;
+const DW_CFA_ADVANCE_LOC1: u8 = 0x02;
+const DW_CFA_ADVANCE_LOC2: u8 = 0x03;
+const DW_CFA_ADVANCE_LOC4: u8 = 0x04;
+const DW_CFA_DEF_CFA: u8 = 0x0c;
+const DW_CFA_DEF_CFA_REGISTER: u8 = 0x0d;
+const DW_CFA_DEF_CFA_OFFSET: u8 = 0x0e;
+
+/// DWARF number of the return address column on x86_64
+const RETURN_ADDRESS: u8 = 16;
+
+/// Factor frame offsets are divided by in `.debug_frame`
+const DATA_ALIGNMENT: i32 = -8;
+
+/// Source file an object is compiled from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileUnit {
+    /// Source file path
+    pub name: String,
+    /// Directory the compiler ran in
+    pub comp_dir: String,
+    /// Name and version of the compiler
+    pub producer: String,
+}
+
+/// A source position at an offset into `.text`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LineRow {
+    pub offset: u64,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// What the debug info says about one function
+#[derive(Debug, Clone)]
+pub(crate) struct FunctionDebug {
+    pub name: String,
+    /// Offset and size in `.text`
+    pub start: u64,
+    pub size: u64,
+    pub global: bool,
+    pub line: u32,
+    pub rows: Vec<LineRow>,
+    /// Call frame changes and the `.text` offset each takes effect at
+    pub frame: Vec<(u64, CfiOp)>,
+    pub variables: Vec<DebugVariable>,
+}
+
+/// DWARF number of a register
+fn dwarf_register(reg: Register) -> Option<u16> {
+    let number = match reg {
+        Register::RAX => 0,
+        Register::RDX => 1,
+        Register::RCX => 2,
+        Register::RBX => 3,
+        Register::RSI => 4,
+        Register::RDI => 5,
+        Register::RBP => 6,
+        Register::RSP => 7,
+        Register::R8 => 8,
+        Register::R9 => 9,
+        Register::R10 => 10,
+        Register::R11 => 11,
+        Register::R12 => 12,
+        Register::R13 => 13,
+        Register::R14 => 14,
+        Register::R15 => 15,
+        _ => 17 + XMM_REGISTERS.iter().position(|&xmm| xmm == reg)? as u16,
+    };
+    Some(number)
+}
+
+/// Debug sections for the functions of an object whose `.text` is
+/// `text_size` bytes
+pub(crate) fn debug_sections(unit: &CompileUnit, text_size: u64, functions: &[FunctionDebug]) -> Vec<DebugSection> {
+    let mut sections: Vec<DebugSection> = [".debug_abbrev", ".debug_info", ".debug_line", ".debug_frame"]
+        .iter()
+        .map(|name| DebugSection {
+            name: name.to_string(),
+            data: Vec::new(),
+            relocations: Vec::new(),
+        })
+        .collect();
+
+    let mut info = InfoWriter::default();
+    info.write(unit, text_size, functions);
+    sections[ABBREV].data = info.abbreviations.finish();
+    sections[INFO].data = info.out.data;
+    sections[INFO].relocations = info.out.relocations;
+
+    let line = line_program(unit, functions);
+    sections[LINE].data = line.data;
+    sections[LINE].relocations = line.relocations;
+
+    let frame = frame_table(functions);
+    sections[FRAME].data = frame.data;
+    sections[FRAME].relocations = frame.relocations;
+    sections
+}
+
+/// Contents of a section being written, with its relocations
+#[derive(Debug, Default)]
+struct SectionWriter {
+    data: Vec<u8>,
+    relocations: Vec<DebugRelocation>,
+}
+
+impl SectionWriter {
+    fn offset(&self) -> usize {
+        self.data.len()
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    fn uleb(&mut self, value: u64) {
+        uleb(&mut self.data, value);
+    }
+
+    fn sleb(&mut self, value: i64) {
+        sleb(&mut self.data, value);
+    }
+
+    fn string(&mut self, string: &str) {
+        self.data.extend(string.as_bytes());
+        self.data.push(0);
+    }
+
+    /// An address or section offset filled in by the linker
+    fn reference(&mut self, kind: DebugRelocationKind, target: SectionRef, addend: u64) {
+        self.relocations.push(DebugRelocation {
+            offset: self.offset() as u64,
+            target,
+            kind,
+            addend: addend as i64,
+        });
+        match kind {
+            DebugRelocationKind::Abs32 => self.u32(0),
+            DebugRelocationKind::Abs64 => self.u64(0),
+        }
+    }
+
+    /// A 32-bit length field, returning its offset for [`Self::finish_length`]
+    fn start_length(&mut self) -> usize {
+        self.u32(0);
+        self.offset()
+    }
+
+    /// Fill in the length of everything written since `start`
+    fn finish_length(&mut self, start: usize) {
+        let length = (self.offset() - start) as u32;
+        self.data[start - 4..start].copy_from_slice(&length.to_le_bytes());
+    }
+}
+
+/// Append an unsigned LEB128 number
+pub(crate) fn uleb(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Append a signed LEB128 number
+pub(crate) fn sleb(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Tag, whether it has children, and attribute forms of an entry
+type EntryShape = (u16, bool, Vec<(u16, u16)>);
+
+/// Abbreviation declarations, shared by the entries of the same shape
+#[derive(Debug, Default)]
+struct Abbreviations {
+    table: Vec<u8>,
+    codes: HashMap<EntryShape, u64>,
+}
+
+impl Abbreviations {
+    /// Code of the abbreviation for an entry with these attributes and forms
+    fn code(&mut self, tag: u16, children: bool, attributes: &[(u16, u16)]) -> u64 {
+        let next = self.codes.len() as u64 + 1;
+        let table = &mut self.table;
+        *self
+            .codes
+            .entry((tag, children, attributes.to_vec()))
+            .or_insert_with(|| {
+                uleb(table, next);
+                uleb(table, u64::from(tag));
+                table.push(u8::from(children));
+                for &(attribute, form) in attributes {
+                    uleb(table, u64::from(attribute));
+                    uleb(table, u64::from(form));
+                }
+                table.extend([0, 0]);
+                next
+            })
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.table.push(0);
+        self.table
+    }
+}
+
+/// Writer of the `.debug_info` entries
+#[derive(Debug, Default)]
+struct InfoWriter {
+    out: SectionWriter,
+    abbreviations: Abbreviations,
+    /// Offset of the entry written for each type
+    types: HashMap<DebugType, u32>,
+}
+
+impl InfoWriter {
+    fn write(&mut self, unit: &CompileUnit, text_size: u64, functions: &[FunctionDebug]) {
+        let start = self.out.start_length();
+        self.out.u16(4);
+        self.out.reference(DebugRelocationKind::Abs32, SectionRef::Debug(ABBREV), 0);
+        self.out.u8(8);
+
+        let code = self.abbreviations.code(
+            DW_TAG_COMPILE_UNIT,
+            true,
+            &[
+                (DW_AT_PRODUCER, DW_FORM_STRING),
+                (DW_AT_LANGUAGE, DW_FORM_DATA2),
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_COMP_DIR, DW_FORM_STRING),
+                (DW_AT_LOW_PC, DW_FORM_ADDR),
+                (DW_AT_HIGH_PC, DW_FORM_DATA8),
+                (DW_AT_STMT_LIST, DW_FORM_SEC_OFFSET),
+            ],
+        );
+        self.out.uleb(code);
+        self.out.string(&unit.producer);
+        self.out.u16(DW_LANG_C99);
+        self.out.string(&unit.name);
+        self.out.string(&unit.comp_dir);
+        self.out.reference(DebugRelocationKind::Abs64, SectionRef::Text, 0);
+        self.out.u64(text_size);
+        self.out.reference(DebugRelocationKind::Abs32, SectionRef::Debug(LINE), 0);
+
+        // Types come first so variables can refer to them
+        for variable in functions.iter().flat_map(|func| &func.variables) {
+            self.type_entry(&variable.ty);
+        }
+        for func in functions {
+            self.subprogram(func);
+        }
+
+        self.out.u8(0);
+        self.out.finish_length(start);
+    }
+
+    /// Offset of the entry describing `ty`, writing it and the types it
+    /// refers to if needed
+    fn type_entry(&mut self, ty: &DebugType) -> u32 {
+        if let Some(&offset) = self.types.get(ty) {
+            return offset;
+        }
+        let offset = match ty {
+            DebugType::Base { name, encoding, size } => {
+                let code = self.abbreviations.code(
+                    DW_TAG_BASE_TYPE,
+                    false,
+                    &[
+                        (DW_AT_NAME, DW_FORM_STRING),
+                        (DW_AT_ENCODING, DW_FORM_DATA1),
+                        (DW_AT_BYTE_SIZE, DW_FORM_DATA1),
+                    ],
+                );
+                let offset = self.out.offset() as u32;
+                self.out.uleb(code);
+                self.out.string(name);
+                self.out.u8(match encoding {
+                    Encoding::Boolean => 0x02,
+                    Encoding::Float => 0x04,
+                    Encoding::Signed => 0x05,
+                    Encoding::Unsigned => 0x08,
+                    Encoding::Utf => 0x10,
+                });
+                self.out.u8(*size as u8);
+                offset
+            }
+            DebugType::Pointer(pointee) => {
+                let pointee = pointee.as_ref().map(|pointee| self.type_entry(pointee));
+                let mut attributes = vec![(DW_AT_BYTE_SIZE, DW_FORM_DATA1)];
+                if pointee.is_some() {
+                    attributes.push((DW_AT_TYPE, DW_FORM_REF4));
+                }
+                let code = self.abbreviations.code(DW_TAG_POINTER_TYPE, false, &attributes);
+                let offset = self.out.offset() as u32;
+                self.out.uleb(code);
+                self.out.u8(8);
+                if let Some(pointee) = pointee {
+                    self.out.u32(pointee);
+                }
+                offset
+            }
+            DebugType::Struct { name, size, fields } => {
+                let field_types: Vec<u32> = fields.iter().map(|(_, _, ty)| self.type_entry(ty)).collect();
+                let code = self.abbreviations.code(
+                    DW_TAG_STRUCTURE_TYPE,
+                    !fields.is_empty(),
+                    &[(DW_AT_NAME, DW_FORM_STRING), (DW_AT_BYTE_SIZE, DW_FORM_DATA4)],
+                );
+                let member = self.abbreviations.code(
+                    DW_TAG_MEMBER,
+                    false,
+                    &[
+                        (DW_AT_NAME, DW_FORM_STRING),
+                        (DW_AT_TYPE, DW_FORM_REF4),
+                        (DW_AT_DATA_MEMBER_LOCATION, DW_FORM_DATA4),
+                    ],
+                );
+                let offset = self.out.offset() as u32;
+                self.out.uleb(code);
+                self.out.string(name);
+                self.out.u32(*size as u32);
+                for ((name, field_offset, _), ty) in fields.iter().zip(field_types) {
+                    self.out.uleb(member);
+                    self.out.string(name);
+                    self.out.u32(ty);
+                    self.out.u32(*field_offset as u32);
+                }
+                if !fields.is_empty() {
+                    self.out.u8(0);
+                }
+                offset
+            }
+            DebugType::Array { elem, len } => {
+                let elem = self.type_entry(elem);
+                let code = self.abbreviations.code(DW_TAG_ARRAY_TYPE, true, &[(DW_AT_TYPE, DW_FORM_REF4)]);
+                let subrange = self.abbreviations.code(DW_TAG_SUBRANGE_TYPE, false, &[(DW_AT_COUNT, DW_FORM_DATA4)]);
+                let offset = self.out.offset() as u32;
+                self.out.uleb(code);
+                self.out.u32(elem);
+                self.out.uleb(subrange);
+                self.out.u32(*len as u32);
+                self.out.u8(0);
+                offset
+            }
+        };
+        self.types.insert(ty.clone(), offset);
+        offset
+    }
+
+    fn subprogram(&mut self, func: &FunctionDebug) {
+        let mut attributes = vec![(DW_AT_NAME, DW_FORM_STRING)];
+        if func.global {
+            attributes.push((DW_AT_EXTERNAL, DW_FORM_FLAG_PRESENT));
+        }
+        attributes.extend([
+            (DW_AT_LOW_PC, DW_FORM_ADDR),
+            (DW_AT_HIGH_PC, DW_FORM_DATA8),
+            (DW_AT_FRAME_BASE, DW_FORM_EXPRLOC),
+            (DW_AT_DECL_FILE, DW_FORM_DATA1),
+            (DW_AT_DECL_LINE, DW_FORM_DATA4),
+        ]);
+        let code = self.abbreviations.code(DW_TAG_SUBPROGRAM, !func.variables.is_empty(), &attributes);
+        self.out.uleb(code);
+        self.out.string(&func.name);
+        self.out.reference(DebugRelocationKind::Abs64, SectionRef::Text, func.start);
+        self.out.u64(func.size);
+        // Frame offsets are relative to the CFA
+        self.out.uleb(1);
+        self.out.u8(DW_OP_CALL_FRAME_CFA);
+        self.out.u8(1);
+        self.out.u32(func.line);
+
+        for variable in &func.variables {
+            let ty = self.types[&variable.ty];
+            let location = variable.location.and_then(|location| location_expression(location, variable.by_address));
+            let tag = match variable.kind {
+                VariableKind::Parameter => DW_TAG_FORMAL_PARAMETER,
+                VariableKind::Local => DW_TAG_VARIABLE,
+            };
+            let mut attributes = vec![
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_DECL_FILE, DW_FORM_DATA1),
+                (DW_AT_DECL_LINE, DW_FORM_DATA4),
+                (DW_AT_TYPE, DW_FORM_REF4),
+            ];
+            if location.is_some() {
+                attributes.push((DW_AT_LOCATION, DW_FORM_EXPRLOC));
+            }
+            let code = self.abbreviations.code(tag, false, &attributes);
+            self.out.uleb(code);
+            self.out.string(&variable.name);
+            self.out.u8(1);
+            self.out.u32(variable.line);
+            self.out.u32(ty);
+            if let Some(location) = location {
+                self.out.uleb(location.len() as u64);
+                self.out.data.extend(location);
+            }
+        }
+        if !func.variables.is_empty() {
+            self.out.u8(0);
+        }
+    }
+}
+
+/// DWARF expression for a variable's location
+///
+/// Frame slots are addressed from RBP, which the prologue leaves 16 bytes
+/// below the CFA.
+fn location_expression(location: VariableLocation, by_address: bool) -> Option<Vec<u8>> {
+    let mut expr = Vec::new();
+    match location {
+        VariableLocation::Register(reg) => {
+            let number = dwarf_register(reg)?;
+            match (by_address, number) {
+                (false, 0..=31) => expr.push(DW_OP_REG0 + number as u8),
+                (false, _) => {
+                    expr.push(DW_OP_REGX);
+                    uleb(&mut expr, u64::from(number));
+                }
+                (true, 0..=31) => {
+                    expr.push(DW_OP_BREG0 + number as u8);
+                    sleb(&mut expr, 0);
+                }
+                (true, _) => {
+                    expr.push(DW_OP_BREGX);
+                    uleb(&mut expr, u64::from(number));
+                    sleb(&mut expr, 0);
+                }
+            }
+        }
+        VariableLocation::Frame(offset) => {
+            expr.push(DW_OP_FBREG);
+            sleb(&mut expr, i64::from(offset) - 16);
+            if by_address {
+                expr.push(DW_OP_DEREF);
+            }
+        }
+    }
+    Some(expr)
+}
+
+/// `.debug_line`, with one sequence per function
+fn line_program(unit: &CompileUnit, functions: &[FunctionDebug]) -> SectionWriter {
+    let mut out = SectionWriter::default();
+    let start = out.start_length();
+    out.u16(4);
+    let header = out.start_length();
+    // Minimum instruction length, maximum operations per instruction,
+    // default is_stmt, line base, line range and opcode base
+    out.data.extend([1, 1, 1, -5i8 as u8, 14, 13]);
+    // Operand counts of the standard opcodes
+    out.data.extend([0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1]);
+    // No include directories; the one file is relative to the compile
+    // directory and has no known time or size
+    out.u8(0);
+    out.string(&unit.name);
+    out.data.extend([0, 0, 0]);
+    out.u8(0);
+    out.finish_length(header);
+
+    for func in functions.iter().filter(|func| !func.rows.is_empty()) {
+        out.data.extend([0, 9, DW_LNE_SET_ADDRESS]);
+        out.reference(DebugRelocationKind::Abs64, SectionRef::Text, func.start);
+        let (mut address, mut line, mut column) = (func.start, 1, 0);
+        for row in &func.rows {
+            if row.line != line {
+                out.u8(DW_LNS_ADVANCE_LINE);
+                out.sleb(i64::from(row.line) - i64::from(line));
+                line = row.line;
+            }
+            if row.column != column {
+                out.u8(DW_LNS_SET_COLUMN);
+                out.uleb(u64::from(row.column));
+                column = row.column;
+            }
+            if row.offset != address {
+                out.u8(DW_LNS_ADVANCE_PC);
+                out.uleb(row.offset - address);
+                address = row.offset;
+            }
+            out.u8(DW_LNS_COPY);
+        }
+        out.u8(DW_LNS_ADVANCE_PC);
+        out.uleb(func.start + func.size - address);
+        out.data.extend([0, 1, DW_LNE_END_SEQUENCE]);
+    }
+
+    out.finish_length(start);
+    out
+}
+
+/// `.debug_frame`, with a common entry for the state at a call and an
+/// entry per function
+fn frame_table(functions: &[FunctionDebug]) -> SectionWriter {
+    let mut out = SectionWriter::default();
+
+    let cie = out.start_length();
+    out.u32(u32::MAX);
+    out.u8(1);
+    // No augmentation; code alignment 1
+    out.data.extend([0, 1]);
+    out.sleb(i64::from(DATA_ALIGNMENT));
+    out.u8(RETURN_ADDRESS);
+    // On entry the CFA is RSP + 8, with the return address just below it
+    out.data.extend([DW_CFA_DEF_CFA, 7, 8, DW_CFA_OFFSET | RETURN_ADDRESS, 1]);
+    pad_entry(&mut out);
+    out.finish_length(cie);
+
+    for func in functions {
+        let fde = out.start_length();
+        out.reference(DebugRelocationKind::Abs32, SectionRef::Debug(FRAME), 0);
+        out.reference(DebugRelocationKind::Abs64, SectionRef::Text, func.start);
+        out.u64(func.size);
+
+        let mut location = func.start;
+        for &(offset, op) in &func.frame {
+            let Some(op) = frame_op(op) else {
+                continue;
+            };
+            let delta = offset - location;
+            location = offset;
+            match delta {
+                0 => {}
+                1..=0x3f => out.u8(DW_CFA_ADVANCE_LOC | delta as u8),
+                0x40..=0xff => {
+                    out.u8(DW_CFA_ADVANCE_LOC1);
+                    out.u8(delta as u8);
+                }
+                0x100..=0xffff => {
+                    out.u8(DW_CFA_ADVANCE_LOC2);
+                    out.u16(delta as u16);
+                }
+                _ => {
+                    out.u8(DW_CFA_ADVANCE_LOC4);
+                    out.u32(delta as u32);
+                }
+            }
+            out.data.extend(op);
+        }
+        pad_entry(&mut out);
+        out.finish_length(fde);
+    }
+    out
+}
+
+/// Instructions for a call frame change
+fn frame_op(op: CfiOp) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match op {
+        CfiOp::DefCfaOffset(offset) => {
+            bytes.push(DW_CFA_DEF_CFA_OFFSET);
+            uleb(&mut bytes, offset as u64);
+        }
+        CfiOp::DefCfaRegister(reg) => {
+            bytes.push(DW_CFA_DEF_CFA_REGISTER);
+            uleb(&mut bytes, u64::from(dwarf_register(reg)?));
+        }
+        CfiOp::Offset(reg, offset) => {
+            bytes.push(DW_CFA_OFFSET | dwarf_register(reg)? as u8);
+            uleb(&mut bytes, (offset / DATA_ALIGNMENT) as u64);
+        }
+        CfiOp::Restore(reg) => bytes.push(DW_CFA_RESTORE | dwarf_register(reg)? as u8),
+    }
+    Some(bytes)
+}
+
+/// Pad a frame entry to a multiple of the address size with `DW_CFA_nop`
+fn pad_entry(out: &mut SectionWriter) {
+    out.data.resize(out.offset().next_multiple_of(8), 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function() -> FunctionDebug {
+        FunctionDebug {
+            name: "main".to_string(),
+            start: 0,
+            size: 32,
+            global: true,
+            line: 1,
+            rows: vec![
+                LineRow { offset: 0, line: 1, column: 1 },
+                LineRow { offset: 8, line: 2, column: 5 },
+            ],
+            frame: vec![(1, CfiOp::Offset(Register::RBP, -16)), (1, CfiOp::DefCfaOffset(16))],
+            variables: vec![DebugVariable {
+                name: "x".to_string(),
+                kind: VariableKind::Local,
+                ty: DebugType::Base {
+                    name: "i64".to_string(),
+                    encoding: Encoding::Signed,
+                    size: 8,
+                },
+                location: Some(VariableLocation::Register(Register::RBX)),
+                by_address: false,
+                line: 2,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_leb128() {
+        let mut out = Vec::new();
+        uleb(&mut out, 624485);
+        sleb(&mut out, -123456);
+        sleb(&mut out, 63);
+        sleb(&mut out, 64);
+        assert_eq!(out, [0xe5, 0x8e, 0x26, 0xc0, 0xbb, 0x78, 0x3f, 0xc0, 0x00]);
+    }
+
+    #[test]
+    fn test_location_expressions() {
+        assert_eq!(location_expression(VariableLocation::Register(Register::RBX), false), Some(vec![0x53]));
+        assert_eq!(location_expression(VariableLocation::Register(Register::XMM15), false), Some(vec![0x90, 32]));
+        assert_eq!(location_expression(VariableLocation::Register(Register::RDI), true), Some(vec![0x75, 0]));
+        // [rbp - 8] is 24 bytes below the CFA
+        assert_eq!(location_expression(VariableLocation::Frame(-8), true), Some(vec![0x91, 0x68, 0x06]));
+    }
+
+    #[test]
+    fn test_debug_sections() {
+        let unit = CompileUnit {
+            name: "main.ax".to_string(),
+            comp_dir: "/src".to_string(),
+            producer: "aurorac".to_string(),
+        };
+        let sections = debug_sections(&unit, 32, &[function()]);
+        let names: Vec<_> = sections.iter().map(|section| section.name.as_str()).collect();
+        assert_eq!(names, [".debug_abbrev", ".debug_info", ".debug_line", ".debug_frame"]);
+
+        // The unit length excludes its own field
+        let info = &sections[INFO];
+        assert_eq!(u32::from_le_bytes(info.data[..4].try_into().unwrap()) as usize, info.data.len() - 4);
+        // Abbreviations, `.text` for the unit and the function, and the line table
+        let targets: Vec<_> = info.relocations.iter().map(|relocation| relocation.target).collect();
+        assert_eq!(
+            targets,
+            [SectionRef::Debug(ABBREV), SectionRef::Text, SectionRef::Debug(LINE), SectionRef::Text]
+        );
+
+        // The CIE and the function's entry, each padded to 8 bytes
+        let frame = &sections[FRAME];
+        assert_eq!(frame.data.len() % 8, 0);
+        assert_eq!(&frame.data[4..8], &u32::MAX.to_le_bytes());
+        let fde = &frame.data[24..];
+        // advance_loc 1, offset rbp at cfa-16, def_cfa_offset 16
+        assert_eq!(&fde[24..30], &[0x41, 0x86, 0x02, 0x0e, 0x10, 0x00]);
+    }
+}
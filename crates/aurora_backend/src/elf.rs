@@ -0,0 +1,629 @@
+//! ELF64 Object Writer - Relocatable x86_64 object files
+//!
+//! Writes an AIR module as an ELF64 relocatable object with `.text`,
+//! `.data`, `.rela.text`, `.symtab` and `.strtab` sections, ready for the
+//! system linker. Functions and data are local symbols except for `main`;
+//! every other symbol the code refers to is left undefined and global, to
+//! be resolved at link time through R_X86_64_PC32 and R_X86_64_PLT32
+//! relocations.
+//!
+//! With debug info, the DWARF sections follow `.rela.text`, each with its
+//! relocations against section symbols.
+
+use crate::dwarf::{self, CompileUnit, FunctionDebug, LineRow};
+use crate::encode::{EncodeError, Encoder, FixupKind};
+use aurora_air::{AirModule, DataKind, Instruction};
+
+/// ELF file type of a relocatable object
+pub(crate) const ET_REL: u16 = 1;
+
+/// ELF machine number of x86_64
+pub(crate) const EM_X86_64: u16 = 62;
+
+/// Size of the ELF header
+pub(crate) const EHDR_SIZE: usize = 64;
+
+/// Size of a section header
+pub(crate) const SHDR_SIZE: usize = 64;
+
+/// Size of a symbol table entry
+pub(crate) const SYM_SIZE: usize = 24;
+
+/// Size of a relocation entry with an addend
+pub(crate) const RELA_SIZE: usize = 24;
+
+pub(crate) const SHT_PROGBITS: u32 = 1;
+pub(crate) const SHT_SYMTAB: u32 = 2;
+pub(crate) const SHT_STRTAB: u32 = 3;
+pub(crate) const SHT_RELA: u32 = 4;
+
+pub(crate) const SHF_WRITE: u64 = 0x1;
+pub(crate) const SHF_ALLOC: u64 = 0x2;
+pub(crate) const SHF_EXECINSTR: u64 = 0x4;
+const SHF_INFO_LINK: u64 = 0x40;
+
+pub(crate) const STB_LOCAL: u8 = 0;
+pub(crate) const STB_GLOBAL: u8 = 1;
+
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const STT_SECTION: u8 = 3;
+
+pub(crate) const R_X86_64_64: u32 = 1;
+pub(crate) const R_X86_64_PC32: u32 = 2;
+pub(crate) const R_X86_64_PLT32: u32 = 4;
+pub(crate) const R_X86_64_32: u32 = 10;
+
+/// Section header index of `.text`
+const TEXT_INDEX: u16 = 1;
+
+/// Section header index of `.data`
+const DATA_INDEX: u16 = 2;
+
+/// Section header index of the first debug section
+const FIRST_DEBUG_INDEX: u32 = 4;
+
+/// Section a symbol is defined in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolSection {
+    Text,
+    Data,
+    /// Defined in another object
+    Undefined,
+}
+
+/// What a symbol names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Object,
+    NoType,
+}
+
+/// Symbol table entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub section: SymbolSection,
+    /// Offset in its section
+    pub value: u64,
+    pub size: u64,
+    pub kind: SymbolKind,
+    pub global: bool,
+}
+
+/// Relocation in `.text`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    /// Offset of the field in `.text`
+    pub offset: u64,
+    /// Index into the object's symbols
+    pub symbol: usize,
+    pub kind: FixupKind,
+    pub addend: i64,
+}
+
+/// Section a debug relocation refers to the start of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionRef {
+    Text,
+    /// Index into the object's debug sections
+    Debug(usize),
+}
+
+/// Width of an absolute relocation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugRelocationKind {
+    /// R_X86_64_32, for offsets into other sections
+    Abs32,
+    /// R_X86_64_64, for addresses
+    Abs64,
+}
+
+/// Relocation in a debug section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugRelocation {
+    /// Offset of the field in its section
+    pub offset: u64,
+    pub target: SectionRef,
+    pub kind: DebugRelocationKind,
+    pub addend: i64,
+}
+
+/// Non-allocated section of debug info
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugSection {
+    pub name: String,
+    pub data: Vec<u8>,
+    pub relocations: Vec<DebugRelocation>,
+}
+
+/// Relocatable object file
+#[derive(Debug, Clone, Default)]
+pub struct ObjectFile {
+    pub text: Vec<u8>,
+    pub data: Vec<u8>,
+    pub symbols: Vec<Symbol>,
+    pub relocations: Vec<Relocation>,
+    pub debug_sections: Vec<DebugSection>,
+}
+
+impl ObjectFile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index of the symbol named `name`, adding it as undefined if missing
+    pub fn symbol(&mut self, name: &str) -> usize {
+        if let Some(index) = self.symbols.iter().position(|symbol| symbol.name == name) {
+            return index;
+        }
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            section: SymbolSection::Undefined,
+            value: 0,
+            size: 0,
+            kind: SymbolKind::NoType,
+            global: true,
+        });
+        self.symbols.len() - 1
+    }
+
+    /// Serialize as an ELF64 relocatable object
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // Section headers: null, .text, .data and .rela.text, then each debug
+        // section followed by its relocations, then the symbol and string tables
+        let mut debug_indices = Vec::new();
+        let mut next_index = FIRST_DEBUG_INDEX;
+        for section in &self.debug_sections {
+            debug_indices.push(next_index);
+            next_index += if section.relocations.is_empty() { 1 } else { 2 };
+        }
+        let symtab_index = next_index;
+
+        // Debug relocations refer to section symbols, which come first
+        let section_symbols: Vec<u32> = if self.debug_sections.is_empty() {
+            Vec::new()
+        } else {
+            std::iter::once(u32::from(TEXT_INDEX)).chain(debug_indices.iter().copied()).collect()
+        };
+
+        // Local symbols must come before global ones
+        let mut order: Vec<usize> = (0..self.symbols.len()).collect();
+        order.sort_by_key(|&index| self.symbols[index].global);
+        let mut symbol_index = vec![0; self.symbols.len()];
+        for (position, &index) in order.iter().enumerate() {
+            // Entry 0 is the null symbol
+            symbol_index[index] = position + 1 + section_symbols.len();
+        }
+        let first_global =
+            1 + section_symbols.len() + self.symbols.iter().filter(|symbol| !symbol.global).count();
+
+        let mut strtab = vec![0];
+        let mut symtab = vec![0; SYM_SIZE];
+        for &section in &section_symbols {
+            symtab.extend(0u32.to_le_bytes());
+            symtab.push(STB_LOCAL << 4 | STT_SECTION);
+            symtab.push(0);
+            symtab.extend((section as u16).to_le_bytes());
+            symtab.extend([0; 16]);
+        }
+        for &index in &order {
+            let symbol = &self.symbols[index];
+            let name = add_string(&mut strtab, &symbol.name);
+            let binding = if symbol.global { STB_GLOBAL } else { STB_LOCAL };
+            let kind = match symbol.kind {
+                SymbolKind::Function => STT_FUNC,
+                SymbolKind::Object => STT_OBJECT,
+                SymbolKind::NoType => STT_NOTYPE,
+            };
+            let section = match symbol.section {
+                SymbolSection::Text => TEXT_INDEX,
+                SymbolSection::Data => DATA_INDEX,
+                SymbolSection::Undefined => 0,
+            };
+            symtab.extend(name.to_le_bytes());
+            symtab.push(binding << 4 | kind);
+            symtab.push(0);
+            symtab.extend(section.to_le_bytes());
+            symtab.extend(symbol.value.to_le_bytes());
+            symtab.extend(symbol.size.to_le_bytes());
+        }
+
+        let mut rela = Vec::new();
+        for relocation in &self.relocations {
+            let kind = match relocation.kind {
+                FixupKind::Pc32 => R_X86_64_PC32,
+                FixupKind::Plt32 => R_X86_64_PLT32,
+            };
+            let symbol = symbol_index[relocation.symbol] as u64;
+            add_relocation(&mut rela, relocation.offset, symbol, kind, relocation.addend);
+        }
+
+        let mut shstrtab = vec![0];
+        let mut sections = vec![(SectionHeader::default(), Vec::new())];
+        let mut add = |header: SectionHeader, name: &str, content: Vec<u8>| {
+            let name = add_string(&mut shstrtab, name);
+            sections.push((SectionHeader { name, ..header }, content));
+        };
+        add(
+            SectionHeader {
+                kind: SHT_PROGBITS,
+                flags: SHF_ALLOC | SHF_EXECINSTR,
+                align: 16,
+                ..Default::default()
+            },
+            ".text",
+            self.text.clone(),
+        );
+        add(
+            SectionHeader {
+                kind: SHT_PROGBITS,
+                flags: SHF_WRITE | SHF_ALLOC,
+                align: 8,
+                ..Default::default()
+            },
+            ".data",
+            self.data.clone(),
+        );
+        add(
+            SectionHeader {
+                kind: SHT_RELA,
+                flags: SHF_INFO_LINK,
+                link: symtab_index,
+                info: u32::from(TEXT_INDEX),
+                align: 8,
+                entsize: RELA_SIZE as u64,
+                ..Default::default()
+            },
+            ".rela.text",
+            rela,
+        );
+        for (section, &index) in self.debug_sections.iter().zip(&debug_indices) {
+            add(
+                SectionHeader {
+                    kind: SHT_PROGBITS,
+                    align: 1,
+                    ..Default::default()
+                },
+                &section.name,
+                section.data.clone(),
+            );
+            if section.relocations.is_empty() {
+                continue;
+            }
+            let mut rela = Vec::new();
+            for relocation in &section.relocations {
+                // Section symbols follow the null symbol, `.text` first
+                let symbol = match relocation.target {
+                    SectionRef::Text => 1,
+                    SectionRef::Debug(target) => 2 + target as u64,
+                };
+                let kind = match relocation.kind {
+                    DebugRelocationKind::Abs32 => R_X86_64_32,
+                    DebugRelocationKind::Abs64 => R_X86_64_64,
+                };
+                add_relocation(&mut rela, relocation.offset, symbol, kind, relocation.addend);
+            }
+            add(
+                SectionHeader {
+                    kind: SHT_RELA,
+                    flags: SHF_INFO_LINK,
+                    link: symtab_index,
+                    info: index,
+                    align: 8,
+                    entsize: RELA_SIZE as u64,
+                    ..Default::default()
+                },
+                &format!(".rela{}", section.name),
+                rela,
+            );
+        }
+        add(
+            SectionHeader {
+                kind: SHT_SYMTAB,
+                link: symtab_index + 1,
+                info: first_global as u32,
+                align: 8,
+                entsize: SYM_SIZE as u64,
+                ..Default::default()
+            },
+            ".symtab",
+            symtab,
+        );
+        add(
+            SectionHeader {
+                kind: SHT_STRTAB,
+                align: 1,
+                ..Default::default()
+            },
+            ".strtab",
+            strtab,
+        );
+        // Named before its own contents are taken
+        let shstrtab_name = add_string(&mut shstrtab, ".shstrtab");
+        let note_name = add_string(&mut shstrtab, ".note.GNU-stack");
+        sections.push((
+            SectionHeader {
+                name: shstrtab_name,
+                kind: SHT_STRTAB,
+                align: 1,
+                ..Default::default()
+            },
+            shstrtab,
+        ));
+        // Marks the stack as non-executable
+        sections.push((
+            SectionHeader {
+                name: note_name,
+                kind: SHT_PROGBITS,
+                align: 1,
+                ..Default::default()
+            },
+            Vec::new(),
+        ));
+
+        // Section contents follow the ELF header, then the section headers
+        let mut out = vec![0; EHDR_SIZE];
+        for (header, content) in sections.iter_mut().skip(1) {
+            let align = header.align.max(1) as usize;
+            out.resize(out.len().next_multiple_of(align), 0);
+            header.offset = out.len() as u64;
+            header.size = content.len() as u64;
+            out.extend_from_slice(content);
+        }
+        out.resize(out.len().next_multiple_of(8), 0);
+        let section_headers = out.len() as u64;
+        for (header, _) in &sections {
+            header.write(&mut out);
+        }
+
+        let shstrndx = (sections.len() - 2) as u16;
+        let mut header = Vec::with_capacity(EHDR_SIZE);
+        header.extend(b"\x7fELF");
+        // 64-bit, little-endian, version 1, System V ABI
+        header.extend([2, 1, 1, 0]);
+        header.extend([0; 8]);
+        header.extend(ET_REL.to_le_bytes());
+        header.extend(EM_X86_64.to_le_bytes());
+        header.extend(1u32.to_le_bytes());
+        // No entry point or program headers
+        header.extend(0u64.to_le_bytes());
+        header.extend(0u64.to_le_bytes());
+        header.extend(section_headers.to_le_bytes());
+        header.extend(0u32.to_le_bytes());
+        header.extend((EHDR_SIZE as u16).to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+        header.extend((SHDR_SIZE as u16).to_le_bytes());
+        header.extend((sections.len() as u16).to_le_bytes());
+        header.extend(shstrndx.to_le_bytes());
+        out[..EHDR_SIZE].copy_from_slice(&header);
+        out
+    }
+}
+
+/// Section header table entry
+#[derive(Debug, Clone, Copy, Default)]
+struct SectionHeader {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+}
+
+impl SectionHeader {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.name.to_le_bytes());
+        out.extend(self.kind.to_le_bytes());
+        out.extend(self.flags.to_le_bytes());
+        // Not loaded at an address
+        out.extend(0u64.to_le_bytes());
+        out.extend(self.offset.to_le_bytes());
+        out.extend(self.size.to_le_bytes());
+        out.extend(self.link.to_le_bytes());
+        out.extend(self.info.to_le_bytes());
+        out.extend(self.align.to_le_bytes());
+        out.extend(self.entsize.to_le_bytes());
+    }
+}
+
+/// Append a relocation entry with an addend
+fn add_relocation(rela: &mut Vec<u8>, offset: u64, symbol: u64, kind: u32, addend: i64) {
+    let info = symbol << 32 | u64::from(kind);
+    rela.extend(offset.to_le_bytes());
+    rela.extend(info.to_le_bytes());
+    rela.extend(addend.to_le_bytes());
+}
+
+/// Append a NUL-terminated string to a string table, returning its offset
+fn add_string(table: &mut Vec<u8>, string: &str) -> u32 {
+    let offset = table.len() as u32;
+    table.extend(string.as_bytes());
+    table.push(0);
+    offset
+}
+
+/// Encode an AIR module as an x86_64 object file
+pub fn write_object(module: &AirModule) -> Result<ObjectFile, EncodeError> {
+    encode_module(module, None)
+}
+
+/// Encode an AIR module as an x86_64 object file with DWARF debug info
+///
+/// Line numbers come from the `Loc` markers and variables from the debug
+/// variables of each function, as recorded by an emitter with debug info
+/// enabled.
+pub fn write_object_with_debug_info(module: &AirModule, unit: &CompileUnit) -> Result<ObjectFile, EncodeError> {
+    encode_module(module, Some(unit))
+}
+
+fn encode_module(module: &AirModule, unit: Option<&CompileUnit>) -> Result<ObjectFile, EncodeError> {
+    let mut object = ObjectFile::new();
+
+    let mut encoder = Encoder::new();
+    let mut functions = Vec::new();
+    for func in &module.functions {
+        let start = encoder.offset() as u64;
+        encoder.encode(&Instruction::Label { name: func.name.clone() })?;
+
+        // Positions are recorded at the instruction that follows them, and
+        // the prologue belongs to the declaration
+        let mut rows = Vec::new();
+        let mut ends = Vec::new();
+        let mut position = (func.line, 0);
+        for inst in func.framed_instructions() {
+            if let Instruction::Loc { line, column } = inst {
+                position = (line, column);
+            } else if position.0 > 0 {
+                add_row(&mut rows, encoder.offset() as u64, position);
+            }
+            encoder.encode(&inst)?;
+            ends.push(encoder.offset() as u64);
+        }
+
+        let size = encoder.offset() as u64 - start;
+        let global = func.name == "main";
+        object.symbols.push(Symbol {
+            name: func.name.clone(),
+            section: SymbolSection::Text,
+            value: start,
+            size,
+            kind: SymbolKind::Function,
+            global,
+        });
+        if unit.is_some() {
+            functions.push(FunctionDebug {
+                name: func.name.clone(),
+                start,
+                size,
+                global,
+                line: func.line,
+                rows,
+                frame: aurora_air::debug::call_frame_ops(func)
+                    .into_iter()
+                    .map(|(index, op)| (ends[index], op))
+                    .collect(),
+                variables: func.debug_vars.clone(),
+            });
+        }
+    }
+    let (text, fixups) = encoder.finish();
+    object.text = text;
+    if let Some(unit) = unit {
+        object.debug_sections = dwarf::debug_sections(unit, object.text.len() as u64, &functions);
+    }
+
+    for data in &module.data {
+        let (align, width) = match data.kind {
+            DataKind::Byte | DataKind::String => (1, data.value.len()),
+            DataKind::Word => (2, 2),
+            DataKind::Dword => (4, 4),
+            DataKind::Qword => (8, 8),
+        };
+        object.data.resize(object.data.len().next_multiple_of(align), 0);
+        let start = object.data.len();
+        object.data.extend(data.value.iter().take(width));
+        object.data.resize(start + width, 0);
+        object.symbols.push(Symbol {
+            name: data.label.clone(),
+            section: SymbolSection::Data,
+            value: start as u64,
+            size: width as u64,
+            kind: SymbolKind::Object,
+            global: false,
+        });
+    }
+
+    for fixup in fixups {
+        // Local labels are never defined in another object
+        if fixup.symbol.starts_with(".L") {
+            return Err(EncodeError::UndefinedLabel(fixup.symbol));
+        }
+        let symbol = object.symbol(&fixup.symbol);
+        object.relocations.push(Relocation {
+            offset: fixup.offset as u64,
+            symbol,
+            kind: fixup.kind,
+            addend: fixup.addend,
+        });
+    }
+
+    Ok(object)
+}
+
+/// Add a line table row, unless the position is unchanged
+fn add_row(rows: &mut Vec<LineRow>, offset: u64, (line, column): (u32, u32)) {
+    match rows.last_mut() {
+        Some(last) if (last.line, last.column) == (line, column) => {}
+        // Nothing was emitted since the last position
+        Some(last) if last.offset == offset => {
+            last.line = line;
+            last.column = column;
+        }
+        _ => rows.push(LineRow { offset, line, column }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aurora_air::{AirFunction, DataDirective, Instruction, Operand, Register};
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_write_object_symbols_and_relocations() {
+        let mut module = AirModule::new("test".to_string());
+        let mut main = AirFunction::new("main".to_string());
+        main.push(Instruction::Lea {
+            dest: Operand::Reg(Register::RDI),
+            src: Operand::Label("str_0".to_string()),
+        });
+        main.push(Instruction::Call { target: Operand::Label("puts".to_string()) });
+        main.push(Instruction::Call { target: Operand::Label("helper".to_string()) });
+        module.add_function(main);
+        module.add_function(AirFunction::new("helper".to_string()));
+        module.data.push(DataDirective {
+            label: "str_0".to_string(),
+            kind: DataKind::String,
+            value: b"hi\0".to_vec(),
+        });
+
+        let object = write_object(&module).unwrap();
+        let names: Vec<_> = object.symbols.iter().map(|symbol| symbol.name.as_str()).collect();
+        assert_eq!(names, ["main", "helper", "str_0", "puts"]);
+        assert!(object.symbols[0].global && !object.symbols[1].global);
+        assert_eq!(object.symbols[3].section, SymbolSection::Undefined);
+        assert_eq!(object.data, b"hi\0");
+
+        // The call to `helper` is resolved in place
+        assert_eq!(object.relocations.len(), 2);
+        assert_eq!(object.relocations[0].kind, FixupKind::Pc32);
+        assert_eq!(object.relocations[1].kind, FixupKind::Plt32);
+        assert_eq!(object.relocations[1].symbol, 3);
+
+        let bytes = object.to_bytes();
+        assert_eq!(&bytes[..4], b"\x7fELF");
+        assert_eq!(u16_at(&bytes, 16), ET_REL);
+        assert_eq!(u16_at(&bytes, 18), EM_X86_64);
+        assert_eq!(u16_at(&bytes, 60), 8);
+    }
+
+    #[test]
+    fn test_undefined_local_label_is_an_error() {
+        let mut module = AirModule::new("test".to_string());
+        let mut main = AirFunction::new("main".to_string());
+        main.push(Instruction::Jmp { target: ".L7".to_string() });
+        module.add_function(main);
+        assert!(matches!(write_object(&module), Err(EncodeError::UndefinedLabel(_))));
+    }
+}
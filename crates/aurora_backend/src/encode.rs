@@ -0,0 +1,690 @@
+//! x86_64 Encoder - Encode AIR instructions as machine code
+//!
+//! Instructions are encoded into a single code buffer. References to labels
+//! defined in the buffer are resolved once it is complete; references to
+//! other symbols (data, external functions) are left as fixups for the
+//! object writer to turn into relocations. Jumps and calls always use
+//! 32-bit displacements.
+
+use aurora_air::air::XMM_REGISTERS;
+use aurora_air::{Instruction, Operand, Register};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// REX prefix with no bits set, needed to address SPL, BPL, SIL and DIL
+const REX: u8 = 0x40;
+
+/// REX.W: 64-bit operand size
+const REX_W: u8 = 0x08;
+
+/// REX.R: extension of the ModRM reg field
+const REX_R: u8 = 0x04;
+
+/// REX.X: extension of the SIB index field
+const REX_X: u8 = 0x02;
+
+/// REX.B: extension of the ModRM r/m, SIB base or opcode register field
+const REX_B: u8 = 0x01;
+
+#[derive(Debug, Error)]
+pub enum EncodeError {
+    #[error("Cannot encode instruction: {0}")]
+    Unsupported(String),
+
+    #[error("Label defined more than once: {0}")]
+    DuplicateLabel(String),
+
+    #[error("Undefined label: {0}")]
+    UndefinedLabel(String),
+}
+
+/// How a fixup's field refers to its symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixupKind {
+    /// PC-relative address
+    Pc32,
+    /// PC-relative call target, through the PLT for external functions
+    Plt32,
+}
+
+/// A 32-bit PC-relative field referring to a symbol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixup {
+    /// Offset of the field in the code
+    pub offset: usize,
+    /// Symbol referred to
+    pub symbol: String,
+    /// Added to the symbol's address, accounting for the bytes of the
+    /// instruction after the field
+    pub addend: i64,
+    /// Kind of reference
+    pub kind: FixupKind,
+}
+
+/// x86_64 machine code encoder
+pub struct Encoder {
+    code: Vec<u8>,
+    labels: HashMap<String, usize>,
+    fixups: Vec<Fixup>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self {
+            code: Vec::new(),
+            labels: HashMap::new(),
+            fixups: Vec::new(),
+        }
+    }
+
+    /// Offset of the next instruction
+    pub fn offset(&self) -> usize {
+        self.code.len()
+    }
+
+    /// Define a label at the current offset
+    pub fn define_label(&mut self, name: &str) -> Result<(), EncodeError> {
+        if self.labels.insert(name.to_string(), self.code.len()).is_some() {
+            return Err(EncodeError::DuplicateLabel(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Encode an instruction
+    pub fn encode(&mut self, inst: &Instruction) -> Result<(), EncodeError> {
+        match inst {
+            Instruction::Label { name } => self.define_label(name),
+            Instruction::Comment { .. } | Instruction::Loc { .. } => Ok(()),
+            inst => self
+                .encode_instruction(inst)
+                .ok_or_else(|| EncodeError::Unsupported(inst.to_string().trim().to_string())),
+        }
+    }
+
+    /// Resolve references to labels defined in the code
+    ///
+    /// Returns the code and the fixups referring to other symbols.
+    pub fn finish(mut self) -> (Vec<u8>, Vec<Fixup>) {
+        let mut unresolved = Vec::new();
+        for fixup in std::mem::take(&mut self.fixups) {
+            match self.labels.get(&fixup.symbol) {
+                Some(&target) => {
+                    let value = target as i64 + fixup.addend - fixup.offset as i64;
+                    self.code[fixup.offset..fixup.offset + 4].copy_from_slice(&(value as i32).to_le_bytes());
+                }
+                None => unresolved.push(fixup),
+            }
+        }
+        (self.code, unresolved)
+    }
+
+    /// Encode an instruction, or `None` if it has no encoding
+    fn encode_instruction(&mut self, inst: &Instruction) -> Option<()> {
+        match inst {
+            // Data movement
+            Instruction::Mov { dest, src } => self.mov(dest, src),
+            Instruction::Lea {
+                dest: Operand::Reg(dest),
+                src: src @ (Operand::Mem { .. } | Operand::MemComplex { .. } | Operand::Label(_)),
+            } => self.rm(None, width(&[]), &[0x8D], gp(*dest)?, src, &[]),
+
+            // Arithmetic
+            Instruction::Add { dest, src } => self.alu(0, dest, src),
+            Instruction::Sub { dest, src } => self.alu(5, dest, src),
+            Instruction::Imul {
+                dest: dest @ Operand::Reg(reg),
+                src,
+            } => {
+                let w = width(&[dest, src]);
+                match src {
+                    Operand::Imm(imm) => match i8::try_from(*imm) {
+                        Ok(imm) => self.rm(None, w, &[0x6B], gp(*reg)?, dest, &[imm as u8]),
+                        Err(_) => self.rm(None, w, &[0x69], gp(*reg)?, dest, &imm32(*imm)?),
+                    },
+                    src => self.rm(None, w, &[0x0F, 0xAF], gp(*reg)?, gp_rm(src)?, &[]),
+                }
+            }
+            Instruction::Idiv { operand } => self.unary(0xF7, 7, operand),
+            Instruction::Inc { operand } => self.unary(0xFF, 0, operand),
+            Instruction::Dec { operand } => self.unary(0xFF, 1, operand),
+            Instruction::Neg { operand } => self.unary(0xF7, 3, operand),
+
+            // Logical
+            Instruction::And { dest, src } => self.alu(4, dest, src),
+            Instruction::Or { dest, src } => self.alu(1, dest, src),
+            Instruction::Xor { dest, src } => self.alu(6, dest, src),
+            Instruction::Not { operand } => self.unary(0xF7, 2, operand),
+            Instruction::Shl { dest, count } => self.shift(4, dest, count),
+            Instruction::Shr { dest, count } => self.shift(5, dest, count),
+            Instruction::Sar { dest, count } => self.shift(7, dest, count),
+
+            // Comparison
+            Instruction::Cmp { left, right } => self.alu(7, left, right),
+            Instruction::Test { left, right } => {
+                let w = width(&[left, right]);
+                match (left, right) {
+                    (left, Operand::Imm(imm)) => self.rm(None, w, &[0xF7], 0, gp_rm(left)?, &imm32(*imm)?),
+                    (left, Operand::Reg(right)) | (Operand::Reg(right), left) => {
+                        self.rm(None, w, &[0x85], gp(*right)?, gp_rm(left)?, &[])
+                    }
+                    _ => None,
+                }
+            }
+            Instruction::Sete { dest } => self.setcc(0x94, *dest),
+            Instruction::Setne { dest } => self.setcc(0x95, *dest),
+            Instruction::Setl { dest } => self.setcc(0x9C, *dest),
+            Instruction::Setle { dest } => self.setcc(0x9E, *dest),
+            Instruction::Setg { dest } => self.setcc(0x9F, *dest),
+            Instruction::Setge { dest } => self.setcc(0x9D, *dest),
+            Instruction::Seta { dest } => self.setcc(0x97, *dest),
+            Instruction::Setae { dest } => self.setcc(0x93, *dest),
+            Instruction::Setp { dest } => self.setcc(0x9A, *dest),
+            Instruction::Setnp { dest } => self.setcc(0x9B, *dest),
+
+            // Control flow
+            Instruction::Jmp { target } => self.branch(&[0xE9], target, FixupKind::Pc32),
+            Instruction::Je { target } => self.branch(&[0x0F, 0x84], target, FixupKind::Pc32),
+            Instruction::Jne { target } => self.branch(&[0x0F, 0x85], target, FixupKind::Pc32),
+            Instruction::Jl { target } => self.branch(&[0x0F, 0x8C], target, FixupKind::Pc32),
+            Instruction::Jle { target } => self.branch(&[0x0F, 0x8E], target, FixupKind::Pc32),
+            Instruction::Jg { target } => self.branch(&[0x0F, 0x8F], target, FixupKind::Pc32),
+            Instruction::Jge { target } => self.branch(&[0x0F, 0x8D], target, FixupKind::Pc32),
+            Instruction::Call {
+                target: Operand::Label(target),
+            } => self.branch(&[0xE8], target, FixupKind::Plt32),
+            Instruction::Call { target } => self.rm(None, 0, &[0xFF], 2, gp_rm(target)?, &[]),
+            Instruction::Ret => self.bytes(vec![0xC3]),
+
+            // Stack
+            Instruction::Push { operand } => match operand {
+                Operand::Reg(reg) => self.opcode_register(0x50, *reg),
+                Operand::Imm(imm) => match i8::try_from(*imm) {
+                    Ok(imm) => self.bytes(vec![0x6A, imm as u8]),
+                    Err(_) => self.bytes([&[0x68][..], &imm32(*imm)?].concat()),
+                },
+                operand => self.rm(None, 0, &[0xFF], 6, memory(operand)?, &[]),
+            },
+            Instruction::Pop { operand } => match operand {
+                Operand::Reg(reg) => self.opcode_register(0x58, *reg),
+                operand => self.rm(None, 0, &[0x8F], 0, memory(operand)?, &[]),
+            },
+
+            // SIMD
+            Instruction::Movaps { dest, src } => self.sse_move(None, 0x28, 0x29, dest, src),
+            Instruction::Addps { dest, src } => self.sse(None, 0x58, dest, src),
+            Instruction::Mulps { dest, src } => self.sse(None, 0x59, dest, src),
+
+            // Scalar floating point
+            Instruction::Movsd { dest, src } => self.sse_move(Some(0xF2), 0x10, 0x11, dest, src),
+            Instruction::Movss { dest, src } => self.sse_move(Some(0xF3), 0x10, 0x11, dest, src),
+            Instruction::Movq { dest, src } => self.movq(dest, src),
+            Instruction::Addsd { dest, src } => self.sse(Some(0xF2), 0x58, dest, src),
+            Instruction::Subsd { dest, src } => self.sse(Some(0xF2), 0x5C, dest, src),
+            Instruction::Mulsd { dest, src } => self.sse(Some(0xF2), 0x59, dest, src),
+            Instruction::Divsd { dest, src } => self.sse(Some(0xF2), 0x5E, dest, src),
+            Instruction::Sqrtsd { dest, src } => self.sse(Some(0xF2), 0x51, dest, src),
+            Instruction::Addss { dest, src } => self.sse(Some(0xF3), 0x58, dest, src),
+            Instruction::Subss { dest, src } => self.sse(Some(0xF3), 0x5C, dest, src),
+            Instruction::Mulss { dest, src } => self.sse(Some(0xF3), 0x59, dest, src),
+            Instruction::Divss { dest, src } => self.sse(Some(0xF3), 0x5E, dest, src),
+            Instruction::Sqrtss { dest, src } => self.sse(Some(0xF3), 0x51, dest, src),
+            Instruction::Xorpd { dest, src } => self.sse(Some(0x66), 0x57, dest, src),
+            Instruction::Ucomisd { left, right } => self.sse(Some(0x66), 0x2E, left, right),
+            Instruction::Ucomiss { left, right } => self.sse(None, 0x2E, left, right),
+            Instruction::Cvtss2sd { dest, src } => self.sse(Some(0xF3), 0x5A, dest, src),
+            Instruction::Cvtsd2ss { dest, src } => self.sse(Some(0xF2), 0x5A, dest, src),
+            Instruction::Cvtsi2sd {
+                dest: Operand::Reg(dest),
+                src,
+            } => self.rm(Some(0xF2), REX_W, &[0x0F, 0x2A], xmm(*dest)?, gp_rm(src)?, &[]),
+            Instruction::Cvtsi2ss {
+                dest: Operand::Reg(dest),
+                src,
+            } => self.rm(Some(0xF3), REX_W, &[0x0F, 0x2A], xmm(*dest)?, gp_rm(src)?, &[]),
+            Instruction::Cvttsd2si {
+                dest: Operand::Reg(dest),
+                src,
+            } => self.rm(Some(0xF2), REX_W, &[0x0F, 0x2C], gp(*dest)?, xmm_rm(src)?, &[]),
+            Instruction::Cvttss2si {
+                dest: Operand::Reg(dest),
+                src,
+            } => self.rm(Some(0xF3), REX_W, &[0x0F, 0x2C], gp(*dest)?, xmm_rm(src)?, &[]),
+
+            // Special
+            Instruction::Nop => self.bytes(vec![0x90]),
+            _ => None,
+        }
+    }
+
+    fn mov(&mut self, dest: &Operand, src: &Operand) -> Option<()> {
+        let w = width(&[dest, src]);
+        match (dest, src) {
+            // A 64-bit immediate only has the `movabs` form
+            (Operand::Reg(dest), Operand::Imm(imm)) if i32::try_from(*imm).is_err() => {
+                let reg = gp(*dest)?;
+                let rex = REX | w | if reg >= 8 { REX_B } else { 0 };
+                self.bytes([&[rex, 0xB8 + (reg & 7)][..], &imm.to_le_bytes()].concat())
+            }
+            (dest, Operand::Imm(imm)) => self.rm(None, w, &[0xC7], 0, gp_rm(dest)?, &imm32(*imm)?),
+            (dest, Operand::Reg(src)) => self.rm(None, w, &[0x89], gp(*src)?, gp_rm(dest)?, &[]),
+            (Operand::Reg(dest), src) => self.rm(None, w, &[0x8B], gp(*dest)?, memory(src)?, &[]),
+            _ => None,
+        }
+    }
+
+    /// `add`, `or`, `and`, `sub`, `xor` or `cmp`, selected by the opcode
+    /// extension used with an immediate
+    fn alu(&mut self, ext: u8, dest: &Operand, src: &Operand) -> Option<()> {
+        let w = width(&[dest, src]);
+        let dest = gp_rm(dest)?;
+        match (dest, src) {
+            (dest, Operand::Imm(imm)) => match i8::try_from(*imm) {
+                Ok(imm) => self.rm(None, w, &[0x83], ext, dest, &[imm as u8]),
+                Err(_) => self.rm(None, w, &[0x81], ext, dest, &imm32(*imm)?),
+            },
+            (dest, Operand::Reg(src)) => self.rm(None, w, &[ext << 3 | 0x01], gp(*src)?, dest, &[]),
+            (Operand::Reg(dest), src) => self.rm(None, w, &[ext << 3 | 0x03], gp(*dest)?, memory(src)?, &[]),
+            _ => None,
+        }
+    }
+
+    fn unary(&mut self, opcode: u8, ext: u8, operand: &Operand) -> Option<()> {
+        self.rm(None, width(&[operand]), &[opcode], ext, gp_rm(operand)?, &[])
+    }
+
+    fn shift(&mut self, ext: u8, dest: &Operand, count: &Operand) -> Option<()> {
+        let w = width(&[dest]);
+        let dest = gp_rm(dest)?;
+        match count {
+            Operand::Imm(1) => self.rm(None, w, &[0xD1], ext, dest, &[]),
+            Operand::Imm(count) => self.rm(None, w, &[0xC1], ext, dest, &[u8::try_from(*count).ok()?]),
+            // The count register is CL
+            Operand::Reg(Register::RCX) => self.rm(None, w, &[0xD3], ext, dest, &[]),
+            _ => None,
+        }
+    }
+
+    /// `setcc` into the low byte of `dest`, zero-extended with `movzx`
+    fn setcc(&mut self, opcode: u8, dest: Register) -> Option<()> {
+        let reg = gp(dest)?;
+        let rex = if (4..8).contains(&reg) { REX } else { 0 };
+        self.rm(None, rex, &[0x0F, opcode], 0, &Operand::Reg(dest), &[])?;
+        self.rm(None, REX_W, &[0x0F, 0xB6], reg, &Operand::Reg(dest), &[])
+    }
+
+    /// Jump or call with a 32-bit displacement to `target`
+    fn branch(&mut self, opcode: &[u8], target: &str, kind: FixupKind) -> Option<()> {
+        let field = opcode.len();
+        let bytes = [opcode, &[0; 4]].concat();
+        self.commit(bytes, Some((field, target.to_string(), kind)));
+        Some(())
+    }
+
+    /// Instruction with the register in the low bits of the opcode
+    fn opcode_register(&mut self, opcode: u8, reg: Register) -> Option<()> {
+        let reg = gp(reg)?;
+        let mut bytes = Vec::new();
+        if reg >= 8 {
+            bytes.push(REX | REX_B);
+        }
+        bytes.push(opcode + (reg & 7));
+        self.bytes(bytes)
+    }
+
+    /// SSE instruction on an XMM register and an XMM register or memory
+    fn sse(&mut self, prefix: Option<u8>, opcode: u8, dest: &Operand, src: &Operand) -> Option<()> {
+        let Operand::Reg(dest) = dest else { return None };
+        self.rm(prefix, 0, &[0x0F, opcode], xmm(*dest)?, xmm_rm(src)?, &[])
+    }
+
+    /// SSE move with separate load and store opcodes
+    fn sse_move(&mut self, prefix: Option<u8>, load: u8, store: u8, dest: &Operand, src: &Operand) -> Option<()> {
+        match (dest, src) {
+            (Operand::Reg(_), src) => self.sse(prefix, load, dest, src),
+            (dest, Operand::Reg(src)) => self.rm(prefix, 0, &[0x0F, store], xmm(*src)?, memory(dest)?, &[]),
+            _ => None,
+        }
+    }
+
+    fn movq(&mut self, dest: &Operand, src: &Operand) -> Option<()> {
+        match (dest, src) {
+            (Operand::Reg(dest), Operand::Reg(src)) if dest.is_xmm() && !src.is_xmm() => {
+                self.rm(Some(0x66), REX_W, &[0x0F, 0x6E], xmm(*dest)?, &Operand::Reg(*src), &[])
+            }
+            (Operand::Reg(dest), Operand::Reg(src)) if !dest.is_xmm() && src.is_xmm() => {
+                self.rm(Some(0x66), REX_W, &[0x0F, 0x7E], xmm(*src)?, &Operand::Reg(*dest), &[])
+            }
+            (Operand::Reg(dest), src) => self.rm(Some(0xF3), 0, &[0x0F, 0x7E], xmm(*dest)?, xmm_rm(src)?, &[]),
+            (dest, Operand::Reg(src)) => self.rm(Some(0x66), 0, &[0x0F, 0xD6], xmm(*src)?, memory(dest)?, &[]),
+            _ => None,
+        }
+    }
+
+    /// Instruction with a ModRM byte: a mandatory prefix, REX bits, the
+    /// opcode, the ModRM reg field and r/m operand, and an immediate
+    fn rm(&mut self, prefix: Option<u8>, rex: u8, opcode: &[u8], reg: u8, rm: &Operand, imm: &[u8]) -> Option<()> {
+        let modrm = modrm(reg, rm)?;
+        let rex = rex | modrm.rex | if reg >= 8 { REX_R } else { 0 };
+
+        let mut bytes = Vec::new();
+        bytes.extend(prefix);
+        if rex != 0 {
+            bytes.push(REX | rex);
+        }
+        bytes.extend_from_slice(opcode);
+        let reference = modrm.label.map(|(field, name)| (bytes.len() + field, name, FixupKind::Pc32));
+        bytes.extend(modrm.bytes);
+        bytes.extend_from_slice(imm);
+        self.commit(bytes, reference);
+        Some(())
+    }
+
+    fn bytes(&mut self, bytes: Vec<u8>) -> Option<()> {
+        self.commit(bytes, None);
+        Some(())
+    }
+
+    /// Append an instruction, with the offset in it of a field referring to a symbol
+    fn commit(&mut self, bytes: Vec<u8>, reference: Option<(usize, String, FixupKind)>) {
+        let start = self.code.len();
+        if let Some((field, symbol, kind)) = reference {
+            self.fixups.push(Fixup {
+                offset: start + field,
+                symbol,
+                addend: field as i64 - bytes.len() as i64,
+                kind,
+            });
+        }
+        self.code.extend(bytes);
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Number of a general-purpose register
+fn gp(reg: Register) -> Option<u8> {
+    let number = match reg {
+        Register::RAX | Register::EAX => 0,
+        Register::RCX | Register::ECX => 1,
+        Register::RDX | Register::EDX => 2,
+        Register::RBX | Register::EBX => 3,
+        Register::RSP | Register::ESP => 4,
+        Register::RBP | Register::EBP => 5,
+        Register::RSI | Register::ESI => 6,
+        Register::RDI | Register::EDI => 7,
+        Register::R8 => 8,
+        Register::R9 => 9,
+        Register::R10 => 10,
+        Register::R11 => 11,
+        Register::R12 => 12,
+        Register::R13 => 13,
+        Register::R14 => 14,
+        Register::R15 => 15,
+        _ => return None,
+    };
+    Some(number)
+}
+
+/// Number of an XMM register
+fn xmm(reg: Register) -> Option<u8> {
+    XMM_REGISTERS.iter().position(|&other| other == reg).map(|number| number as u8)
+}
+
+/// An r/m operand of an integer instruction
+fn gp_rm(op: &Operand) -> Option<&Operand> {
+    match op {
+        Operand::Reg(reg) if reg.is_xmm() => None,
+        Operand::Imm(_) => None,
+        op => Some(op),
+    }
+}
+
+/// An r/m operand of an SSE instruction
+fn xmm_rm(op: &Operand) -> Option<&Operand> {
+    match op {
+        Operand::Reg(reg) if !reg.is_xmm() => None,
+        Operand::Imm(_) => None,
+        op => Some(op),
+    }
+}
+
+/// A memory r/m operand
+fn memory(op: &Operand) -> Option<&Operand> {
+    matches!(op, Operand::Mem { .. } | Operand::MemComplex { .. } | Operand::Label(_)).then_some(op)
+}
+
+/// REX.W unless an operand is a 32-bit register
+fn width(ops: &[&Operand]) -> u8 {
+    let is_32 = |op: &&Operand| {
+        matches!(op, Operand::Reg(Register::EAX | Register::EBX | Register::ECX | Register::EDX
+            | Register::ESI | Register::EDI | Register::EBP | Register::ESP))
+    };
+    if ops.iter().any(is_32) {
+        0
+    } else {
+        REX_W
+    }
+}
+
+/// A 32-bit immediate, sign-extended by the instruction
+fn imm32(imm: i64) -> Option<[u8; 4]> {
+    i32::try_from(imm).ok().map(i32::to_le_bytes)
+}
+
+/// Encoded r/m operand
+struct ModRm {
+    /// REX.X and REX.B bits
+    rex: u8,
+    /// ModRM byte, then any SIB byte and displacement
+    bytes: Vec<u8>,
+    /// For a label, the offset in the bytes of its RIP-relative displacement
+    label: Option<(usize, String)>,
+}
+
+/// Encode the ModRM byte for a reg field and an r/m operand
+fn modrm(reg: u8, rm: &Operand) -> Option<ModRm> {
+    let reg = (reg & 7) << 3;
+    match rm {
+        Operand::Reg(rm) => {
+            let number = gp(*rm).or_else(|| xmm(*rm))?;
+            let rex = if number >= 8 { REX_B } else { 0 };
+            Some(ModRm {
+                rex,
+                bytes: vec![0xC0 | reg | (number & 7)],
+                label: None,
+            })
+        }
+        Operand::Mem { base, offset } => address(reg, Some(*base), None, 1, *offset),
+        Operand::MemComplex {
+            base,
+            index,
+            scale,
+            offset,
+        } => address(reg, *base, *index, *scale, *offset),
+        Operand::Label(name) => Some(ModRm {
+            rex: 0,
+            bytes: vec![0x05 | reg, 0, 0, 0, 0],
+            label: Some((1, name.clone())),
+        }),
+        Operand::Imm(_) => None,
+    }
+}
+
+/// ModRM, SIB and displacement bytes for `[base + index * scale + offset]`
+fn address(
+    reg: u8,
+    base: Option<Register>,
+    index: Option<Register>,
+    scale: u8,
+    offset: i32,
+) -> Option<ModRm> {
+    let scale = match scale {
+        1 => 0,
+        2 => 1,
+        4 => 2,
+        8 => 3,
+        _ => return None,
+    };
+    let base = match base {
+        Some(base) => Some(gp(base)?),
+        None => None,
+    };
+    // RSP cannot be an index
+    let index = match index {
+        Some(Register::RSP) => return None,
+        Some(index) => Some(gp(index)?),
+        None => None,
+    };
+
+    // Without a base there is always a 32-bit displacement, and a base
+    // encoded like RBP has no form without one
+    let (mode, displacement) = match base {
+        None => (0b00, offset.to_le_bytes().to_vec()),
+        Some(base) if offset == 0 && base & 7 != 5 => (0b00, vec![]),
+        Some(_) => match i8::try_from(offset) {
+            Ok(offset) => (0b01, vec![offset as u8]),
+            Err(_) => (0b10, offset.to_le_bytes().to_vec()),
+        },
+    };
+
+    let mut rex = 0;
+    let mut bytes = Vec::new();
+    match (base, index) {
+        // A base encoded like RSP needs a SIB byte
+        (Some(base), None) if base & 7 != 4 => bytes.push(mode << 6 | reg | (base & 7)),
+        _ => {
+            let index = index.unwrap_or(0b100);
+            let sib_base = base.unwrap_or(0b101);
+            bytes.push(mode << 6 | reg | 0b100);
+            bytes.push(scale << 6 | (index & 7) << 3 | (sib_base & 7));
+            if index >= 8 {
+                rex |= REX_X;
+            }
+        }
+    }
+    if base.is_some_and(|base| base >= 8) {
+        rex |= REX_B;
+    }
+    bytes.extend(displacement);
+    Some(ModRm { rex, bytes, label: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(inst: Instruction) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.encode(&inst).unwrap();
+        encoder.finish().0
+    }
+
+    fn reg(reg: Register) -> Operand {
+        Operand::Reg(reg)
+    }
+
+    fn mem(base: Register, offset: i32) -> Operand {
+        Operand::Mem { base, offset }
+    }
+
+    #[test]
+    fn test_encode_integer_instructions() {
+        use Register::*;
+
+        assert_eq!(encode(Instruction::Mov { dest: reg(RAX), src: reg(RBX) }), [0x48, 0x89, 0xD8]);
+        assert_eq!(encode(Instruction::Mov { dest: reg(RAX), src: mem(RBP, -8) }), [0x48, 0x8B, 0x45, 0xF8]);
+        assert_eq!(encode(Instruction::Mov { dest: mem(RSP, 0), src: reg(R12) }), [0x4C, 0x89, 0x24, 0x24]);
+        assert_eq!(encode(Instruction::Mov { dest: mem(R13, 0), src: reg(RAX) }), [0x49, 0x89, 0x45, 0x00]);
+        assert_eq!(
+            encode(Instruction::Mov { dest: reg(R9), src: Operand::Imm(1 << 40) }),
+            [0x49, 0xB9, 0, 0, 0, 0, 0, 1, 0, 0]
+        );
+        assert_eq!(encode(Instruction::Add { dest: reg(R11), src: Operand::Imm(8) }), [0x49, 0x83, 0xC3, 0x08]);
+        assert_eq!(
+            encode(Instruction::Sub { dest: reg(RSP), src: Operand::Imm(256) }),
+            [0x48, 0x81, 0xEC, 0x00, 0x01, 0x00, 0x00]
+        );
+        assert_eq!(encode(Instruction::Cmp { left: reg(RCX), right: mem(RBP, -16) }), [0x48, 0x3B, 0x4D, 0xF0]);
+        assert_eq!(encode(Instruction::Imul { dest: reg(RDX), src: Operand::Imm(10) }), [0x48, 0x6B, 0xD2, 0x0A]);
+        assert_eq!(encode(Instruction::Idiv { operand: mem(RSP, 0) }), [0x48, 0xF7, 0x3C, 0x24]);
+        assert_eq!(encode(Instruction::Sar { dest: reg(RDX), count: Operand::Imm(63) }), [0x48, 0xC1, 0xFA, 0x3F]);
+        assert_eq!(
+            encode(Instruction::Lea {
+                dest: reg(RAX),
+                src: Operand::MemComplex { base: Some(R13), index: Some(RCX), scale: 1, offset: 0 },
+            }),
+            [0x49, 0x8D, 0x44, 0x0D, 0x00]
+        );
+        assert_eq!(encode(Instruction::Push { operand: reg(R12) }), [0x41, 0x54]);
+        assert_eq!(encode(Instruction::Pop { operand: reg(RBX) }), [0x5B]);
+        assert_eq!(encode(Instruction::Push { operand: mem(RBP, 16) }), [0xFF, 0x75, 0x10]);
+
+        // SIL needs a REX prefix to not mean DH
+        assert_eq!(
+            encode(Instruction::Sete { dest: RSI }),
+            [0x40, 0x0F, 0x94, 0xC6, 0x48, 0x0F, 0xB6, 0xF6]
+        );
+    }
+
+    #[test]
+    fn test_encode_sse_instructions() {
+        use Register::*;
+
+        assert_eq!(
+            encode(Instruction::Movsd { dest: reg(XMM8), src: mem(RSI, 8) }),
+            [0xF2, 0x44, 0x0F, 0x10, 0x46, 0x08]
+        );
+        assert_eq!(
+            encode(Instruction::Movsd { dest: mem(RSP, 0), src: reg(XMM1) }),
+            [0xF2, 0x0F, 0x11, 0x0C, 0x24]
+        );
+        assert_eq!(encode(Instruction::Addsd { dest: reg(XMM0), src: reg(XMM15) }), [0xF2, 0x41, 0x0F, 0x58, 0xC7]);
+        assert_eq!(encode(Instruction::Movq { dest: reg(XMM14), src: reg(RSI) }), [0x66, 0x4C, 0x0F, 0x6E, 0xF6]);
+        assert_eq!(encode(Instruction::Movq { dest: reg(RAX), src: reg(XMM0) }), [0x66, 0x48, 0x0F, 0x7E, 0xC0]);
+        assert_eq!(encode(Instruction::Ucomisd { left: reg(XMM1), right: reg(XMM2) }), [0x66, 0x0F, 0x2E, 0xCA]);
+        assert_eq!(
+            encode(Instruction::Cvtsi2sd { dest: reg(XMM0), src: reg(R10) }),
+            [0xF2, 0x49, 0x0F, 0x2A, 0xC2]
+        );
+        assert_eq!(
+            encode(Instruction::Cvttsd2si { dest: reg(RAX), src: reg(XMM3) }),
+            [0xF2, 0x48, 0x0F, 0x2C, 0xC3]
+        );
+    }
+
+    #[test]
+    fn test_labels_resolved_and_symbols_left_as_fixups() {
+        let mut encoder = Encoder::new();
+        encoder.encode(&Instruction::Label { name: ".L0".to_string() }).unwrap();
+        encoder.encode(&Instruction::Lea { dest: reg(Register::RDI), src: Operand::Label("str_0".to_string()) }).unwrap();
+        encoder.encode(&Instruction::Call { target: Operand::Label("printf".to_string()) }).unwrap();
+        encoder.encode(&Instruction::Jmp { target: ".L0".to_string() }).unwrap();
+        let (code, fixups) = encoder.finish();
+
+        // lea rdi, [rip + str_0]; call printf; jmp .L0
+        assert_eq!(&code[..3], [0x48, 0x8D, 0x3D]);
+        assert_eq!(code[7], 0xE8);
+        assert_eq!(&code[12..], [0xE9, 0xEF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(
+            fixups,
+            vec![
+                Fixup { offset: 3, symbol: "str_0".to_string(), addend: -4, kind: FixupKind::Pc32 },
+                Fixup { offset: 8, symbol: "printf".to_string(), addend: -4, kind: FixupKind::Plt32 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unencodable_instruction_is_an_error() {
+        let mut encoder = Encoder::new();
+        let inst = Instruction::Add { dest: reg(Register::RAX), src: Operand::Imm(1 << 40) };
+        assert!(matches!(encoder.encode(&inst), Err(EncodeError::Unsupported(_))));
+        let label = Instruction::Label { name: "f".to_string() };
+        encoder.encode(&label).unwrap();
+        assert!(matches!(encoder.encode(&label), Err(EncodeError::DuplicateLabel(_))));
+    }
+}
@@ -1,31 +1,76 @@
 //! Aurora Backend - Code generation and linking
 //!
 //! This module handles the final stages of compilation:
-//! 1. Converting AIR (Assembly IR) to machine code via GCC
+//! 1. Encoding AIR (Assembly IR) as machine code
 //! 2. Linking object files with runtime and system libraries
 //! 3. Producing executable binaries
 //!
 //! # Pipeline
 //!
 //! ```text
-//! AIR Module → Assembly (.s) → Object File (.o) → Executable
-//!                  ↓              ↓                   ↓
-//!              GAS syntax    GCC assemble      GCC link + runtime
+//! AIR Module → Object File (.o) → Executable
+//!                   ↓                 ↓
+//!            x86_64 encoder    GCC link + runtime
+//!            + ELF writer
 //! ```
-
+//!
+//! Targets other than x86_64 ELF go through GAS syntax assembled by GCC.
+//! AArch64 modules are GAS source already and are assembled and linked with
+//! the GNU cross toolchain for the target (see [`generate_code_for_target`]).
+//! WebAssembly modules are written as `.wasm` binaries for WASI, needing
+//! neither an assembler nor a linker (see [`wasm`]).
+//!
+//! Instead of AIR, MIR can also go through portable C99 built with any C
+//! compiler (see [`cgen`] and [`generate_c_code`]).
+//! With `debug_info`, the native encoder also writes DWARF line tables,
+//! variable locations and call frame information (see [`dwarf`]).
+
+pub mod cgen;
+pub mod dwarf;
+pub mod elf;
+pub mod encode;
 pub mod link;
 pub mod llvm;
-
-pub use link::{LinkError, Linker, LinkerConfig};
-pub use llvm::{BackendError, LlvmBackend, OptLevel};
-
-use aurora_air::AirModule;
+pub mod static_link;
+pub mod wasm;
+
+pub use cgen::{compile_c, CEmitter};
+pub use dwarf::CompileUnit;
+pub use elf::{write_object, write_object_with_debug_info, ObjectFile};
+pub use encode::{EncodeError, Encoder};
+pub use link::{LinkError, Linker, LinkerConfig, LinkerKind};
+pub use llvm::{c_compiler, BackendError, LlvmBackend, OptLevel};
+pub use wasm::write_wasm;
+
+use aurora_air::{AirModule, TargetModule};
+use aurora_mir::MirModule;
 use anyhow::{Context, Result};
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+/// Code generator a module goes through after MIR
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// AIR, lowered for the target architecture
+    #[default]
+    Air,
+    /// Portable C99, compiled by a C compiler
+    C,
+}
+
+impl Backend {
+    /// Backend of a name given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "air" => Some(Backend::Air),
+            "c" => Some(Backend::C),
+            _ => None,
+        }
+    }
+}
+
 /// Code generation options
 #[derive(Debug, Clone)]
 pub struct CodegenOptions {
@@ -43,6 +88,10 @@ pub struct CodegenOptions {
     pub keep_intermediates: bool,
     /// Target triple (e.g., "x86_64-unknown-linux-gnu")
     pub target_triple: Option<String>,
+    /// Source file the module was compiled from, named in debug info
+    pub source_path: Option<PathBuf>,
+    /// Linker producing the executable
+    pub linker: LinkerKind,
 }
 
 impl Default for CodegenOptions {
@@ -55,6 +104,8 @@ impl Default for CodegenOptions {
             output_path: PathBuf::from("a.out"),
             keep_intermediates: false,
             target_triple: None,
+            source_path: None,
+            linker: LinkerKind::default(),
         }
     }
 }
@@ -92,15 +143,16 @@ impl CodegenOptions {
 /// Generate machine code from AIR and link to executable
 ///
 /// This is the main entry point for the backend. It:
-/// 1. Converts AIR to assembly
-/// 2. Compiles assembly to object file
+/// 1. Encodes AIR as an object file, or on targets without a native
+///    encoder converts it to assembly
+/// 2. Assembles the assembly to an object file
 /// 3. Compiles C runtime
 /// 4. Links everything into an executable
 ///
 /// # Errors
 ///
 /// Returns an error if:
-/// - Assembly generation fails
+/// - Encoding or assembly generation fails
 /// - Compilation fails
 /// - Linking fails
 /// - File I/O fails
@@ -132,30 +184,47 @@ pub fn generate_code<D: Send + Sync + 'static>(
 
     // Step 4: Compile AIR to object file
     let obj_path = get_temp_path("aurora_main.o");
-    backend
-        .compile_to_object(&air_text, &obj_path)
-        .context("Failed to compile AIR to object file")?;
+    if has_native_encoder(backend.target_triple()) {
+        let object = if options.debug_info {
+            write_object_with_debug_info(&air, &compile_unit(&options))
+        } else {
+            write_object(&air)
+        }
+        .context("Failed to encode AIR")?;
+        fs::write(&obj_path, object.to_bytes())
+            .with_context(|| format!("Failed to write object file to {:?}", obj_path))?;
+    } else {
+        backend
+            .compile_to_object(&air_text, &obj_path)
+            .context("Failed to compile AIR to object file")?;
+    }
 
     if options.keep_intermediates {
         println!("Generated object file: {}", obj_path.display());
     }
 
-    // Step 5: Compile C runtime
+    // Step 5: Compile C runtime, without the C library for the built-in
+    // linker, which does not search for libraries
     let runtime_c_path = find_runtime_c()?;
-    let linker = Linker::new();
-    let runtime_obj = linker
-        .compile_c_runtime(&runtime_c_path)
-        .context("Failed to compile C runtime")?;
+    let mut linker = Linker::new();
+    linker.set_kind(options.linker);
+    let runtime_obj = match options.linker {
+        LinkerKind::Internal => {
+            linker.compile_freestanding_runtime(&runtime_c_path, &get_temp_path("aurora_runtime_static.o"))
+        }
+        LinkerKind::External => linker.compile_c_runtime(&runtime_c_path),
+    }
+    .context("Failed to compile C runtime")?;
 
     if options.keep_intermediates {
         println!("Generated runtime object: {}", runtime_obj.display());
     }
 
     // Step 6: Link to executable
-    let mut linker = Linker::new();
-
-    // Add standard C library
-    linker.add_library("c".to_string());
+    if options.linker == LinkerKind::External {
+        // Add standard C library
+        linker.add_library("c".to_string());
+    }
 
     // Link object files
     let object_files = vec![obj_path.clone(), runtime_obj.clone()];
@@ -174,6 +243,145 @@ pub fn generate_code<D: Send + Sync + 'static>(
     Ok(())
 }
 
+/// Generate machine code for a module lowered for any target and link it
+///
+/// x86_64 modules go through [`generate_code`], and WebAssembly modules
+/// are written to the output path as they are. Other targets are
+/// assembled, and the C runtime compiled and linked, with the target's C
+/// compiler (see [`c_compiler`]).
+pub fn generate_code_for_target<D: Send + Sync + 'static>(
+    module: TargetModule,
+    options: CodegenOptions,
+    diagnostics: Arc<D>,
+) -> Result<()> {
+    let module = match module {
+        TargetModule::X86_64(air) => return generate_code(air, options, diagnostics),
+        TargetModule::AArch64(_) if options.linker == LinkerKind::Internal => {
+            anyhow::bail!("The built-in linker only links x86_64 executables")
+        }
+        TargetModule::AArch64(module) => module,
+        TargetModule::Wasm32(module) => {
+            if options.emit_llvm {
+                let wat_path = options.output_path.with_extension("wat");
+                fs::write(&wat_path, module.to_text())
+                    .with_context(|| format!("Failed to write WebAssembly text to {:?}", wat_path))?;
+                println!("Generated WebAssembly text: {}", wat_path.display());
+            }
+            return fs::write(&options.output_path, write_wasm(&module))
+                .with_context(|| format!("Failed to write {:?}", options.output_path));
+        }
+    };
+
+    let target_triple = options
+        .target_triple
+        .clone()
+        .unwrap_or_else(|| "aarch64-unknown-linux-gnu".to_string());
+    let compiler = c_compiler(&target_triple);
+    let mut backend = LlvmBackend::new(target_triple);
+    backend.set_opt_level(options.opt_level_enum());
+
+    let asm = module.to_text();
+    if options.emit_llvm {
+        let asm_path = options.output_path.with_extension("s");
+        fs::write(&asm_path, &asm)
+            .with_context(|| format!("Failed to write assembly to {:?}", asm_path))?;
+        println!("Generated assembly: {}", asm_path.display());
+    }
+
+    // Assemble the module
+    let obj_path = get_temp_path("aurora_main.o");
+    backend
+        .assemble(&asm, &obj_path)
+        .with_context(|| format!("Failed to assemble with {}", compiler))?;
+
+    // Compile the C runtime for the target
+    let runtime_c_path = find_runtime_c()?;
+    let runtime_obj = get_temp_path("aurora_runtime.o");
+    Linker::new()
+        .compile_c_runtime_with(&compiler, &runtime_c_path, &runtime_obj)
+        .context("Failed to compile C runtime")?;
+
+    // Link with the target's C compiler driver
+    let mut linker = Linker::new();
+    linker.set_linker(compiler);
+    linker.add_library("c".to_string());
+    linker
+        .link(&[obj_path.clone(), runtime_obj.clone()], &options.output_path)
+        .context("Failed to link executable")?;
+
+    if !options.keep_intermediates {
+        let _ = fs::remove_file(&obj_path);
+        let _ = fs::remove_file(&runtime_obj);
+    }
+
+    Ok(())
+}
+
+/// Generate C from MIR and compile it with the C runtime
+///
+/// The compiler is `$CC` if set, and otherwise the target's C compiler (see
+/// [`c_compiler`]). With `emit_llvm` the C source is kept next to the
+/// output.
+pub fn generate_c_code<D: Send + Sync + 'static>(
+    mir: &MirModule,
+    options: CodegenOptions,
+    _diagnostics: Arc<D>,
+) -> Result<()> {
+    let target_triple = options
+        .target_triple
+        .clone()
+        .unwrap_or_else(|| "x86_64-unknown-linux-gnu".to_string());
+    let compiler = env::var("CC").unwrap_or_else(|_| c_compiler(&target_triple));
+
+    let source = CEmitter::new().emit_module(mir);
+    let c_path = if options.emit_llvm {
+        options.output_path.with_extension("c")
+    } else {
+        get_temp_path("aurora_main.c")
+    };
+    fs::write(&c_path, source).with_context(|| format!("Failed to write C source to {:?}", c_path))?;
+    if options.emit_llvm {
+        println!("Generated C source: {}", c_path.display());
+    }
+
+    let runtime_c_path = find_runtime_c()?;
+    compile_c(
+        &compiler,
+        &[c_path.clone(), runtime_c_path],
+        &options.output_path,
+        options.opt_level_enum(),
+        options.debug_info,
+    )
+    .with_context(|| format!("Failed to compile C with {}", compiler))?;
+
+    if !options.emit_llvm && !options.keep_intermediates {
+        let _ = fs::remove_file(&c_path);
+    }
+
+    Ok(())
+}
+
+/// Debug info description of the source being compiled
+fn compile_unit(options: &CodegenOptions) -> CompileUnit {
+    let name = options
+        .source_path
+        .as_ref()
+        .map_or_else(|| "<unknown>".to_string(), |path| path.display().to_string());
+    let comp_dir = env::current_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_default();
+    CompileUnit {
+        name,
+        comp_dir,
+        producer: format!("aurorac {}", env!("CARGO_PKG_VERSION")),
+    }
+}
+
+/// Whether objects for a target are written without an assembler
+fn has_native_encoder(target_triple: &str) -> bool {
+    target_triple.starts_with("x86_64") && target_triple.contains("linux")
+}
+
 /// Get host target triple
 fn get_host_triple() -> String {
     // Simple detection based on current platform
@@ -304,6 +512,13 @@ mod tests {
         assert!(triple.contains("-"));
     }
 
+    #[test]
+    fn test_has_native_encoder() {
+        assert!(has_native_encoder("x86_64-unknown-linux-gnu"));
+        assert!(!has_native_encoder("x86_64-apple-darwin"));
+        assert!(!has_native_encoder("aarch64-unknown-linux-gnu"));
+    }
+
     #[test]
     fn test_get_temp_path() {
         let path = get_temp_path("test.o");
@@ -392,6 +607,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_emit_aarch64_assembly() {
+        use aurora_air::{A64Function, A64Module};
+
+        let mut module = A64Module::new("test".to_string());
+        module.functions.push(A64Function::new("test_func".to_string()));
+
+        let options = CodegenOptions {
+            output_path: PathBuf::from("/tmp/test_a64_asm"),
+            emit_llvm: true,
+            target_triple: Some("aarch64-unknown-linux-gnu".to_string()),
+            ..Default::default()
+        };
+
+        // Assembling fails without an AArch64 cross toolchain, after the
+        // assembly has been written
+        let _ = generate_code_for_target(TargetModule::AArch64(module), options, Arc::new(()));
+
+        let asm_path = PathBuf::from("/tmp/test_a64_asm.s");
+        let content = fs::read_to_string(&asm_path).unwrap();
+        assert!(content.contains("test_func:"));
+        let _ = fs::remove_file(&asm_path);
+    }
+
     #[test]
     fn test_codegen_with_data_section() {
         use aurora_air::{DataDirective, DataKind};
@@ -3,6 +3,7 @@
 //! This module handles linking object files with runtime libraries and system libraries
 //! to produce final executables.
 
+use crate::static_link::link_static;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use thiserror::Error;
@@ -17,11 +18,57 @@ pub enum LinkError {
 
     #[error("IO error: {0}")]
     IoError(String),
+
+    #[error("Invalid object file: {0}")]
+    InvalidObject(String),
+
+    #[error("Undefined symbols: {0}")]
+    UndefinedSymbol(String),
+
+    #[error("Duplicate symbol: {0}")]
+    DuplicateSymbol(String),
+
+    #[error("Unsupported relocation: {0}")]
+    UnsupportedRelocation(String),
+
+    #[error("Relocation out of range in {0}")]
+    RelocationOverflow(String),
+
+    #[error("The built-in linker does not search for libraries ({0}); pass their static archives instead")]
+    LibrarySearch(String),
+}
+
+/// Which linker produces the executable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LinkerKind {
+    /// Built-in static ELF linker
+    ///
+    /// Links the object files with the static libraries; libraries to be
+    /// found by name are not searched, so programs are linked with the
+    /// runtime built without the C library (see
+    /// [`Linker::compile_freestanding_runtime`]).
+    Internal,
+    /// External linker driver
+    #[default]
+    External,
+}
+
+impl LinkerKind {
+    /// Linker of a name given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "internal" => Some(LinkerKind::Internal),
+            "external" => Some(LinkerKind::External),
+            _ => None,
+        }
+    }
 }
 
 /// Platform-specific linker configuration
 #[derive(Debug, Clone)]
 pub struct LinkerConfig {
+    /// Linker to use
+    pub kind: LinkerKind,
     /// Linker executable (gcc, ld, lld, etc.)
     pub linker: String,
     /// Additional linker arguments
@@ -37,6 +84,7 @@ pub struct LinkerConfig {
 impl Default for LinkerConfig {
     fn default() -> Self {
         Self {
+            kind: LinkerKind::default(),
             linker: "gcc".to_string(),
             args: Vec::new(),
             library_paths: Vec::new(),
@@ -63,6 +111,11 @@ impl Linker {
         Self { config }
     }
 
+    /// Set which linker to use
+    pub fn set_kind(&mut self, kind: LinkerKind) {
+        self.config.kind = kind;
+    }
+
     /// Set linker executable
     pub fn set_linker(&mut self, linker: String) {
         self.config.linker = linker;
@@ -90,7 +143,8 @@ impl Linker {
 
     /// Link object files to executable
     ///
-    /// This uses GCC as the linker driver, which handles:
+    /// The built-in linker produces a static executable entering through
+    /// `aurora_start`. Otherwise GCC is used as the linker driver, which handles:
     /// - Invoking the system linker (ld)
     /// - Linking with C runtime (crt0.o, etc.)
     /// - Linking with system libraries (libc, etc.)
@@ -99,6 +153,14 @@ impl Linker {
         object_files: &[PathBuf],
         output: &Path,
     ) -> Result<(), LinkError> {
+        if self.config.kind == LinkerKind::Internal {
+            if !self.config.libraries.is_empty() {
+                let names: Vec<String> = self.config.libraries.iter().map(|lib| format!("-l{}", lib)).collect();
+                return Err(LinkError::LibrarySearch(names.join(" ")));
+            }
+            return link_static(object_files, &self.config.static_libs, output);
+        }
+
         // Use GCC as linker driver for simplicity
         let mut cmd = Command::new(&self.config.linker);
 
@@ -217,13 +279,21 @@ impl Linker {
 
     /// Compile C runtime and return object file path
     pub fn compile_c_runtime(&self, runtime_c: &Path) -> Result<PathBuf, LinkError> {
-        let obj_path = runtime_c.with_extension("o");
+        self.compile_c_runtime_with("gcc", runtime_c, &runtime_c.with_extension("o"))
+    }
 
-        let status = Command::new("gcc")
+    /// Compile C runtime with the given C compiler, e.g. a cross compiler
+    pub fn compile_c_runtime_with(
+        &self,
+        compiler: &str,
+        runtime_c: &Path,
+        obj_path: &Path,
+    ) -> Result<PathBuf, LinkError> {
+        let status = Command::new(compiler)
             .arg("-c")
             .arg("-O2")
             .arg("-o")
-            .arg(&obj_path)
+            .arg(obj_path)
             .arg(runtime_c)
             .status()
             .map_err(|e| LinkError::IoError(format!("Failed to compile C runtime: {}", e)))?;
@@ -234,7 +304,30 @@ impl Linker {
             ));
         }
 
-        Ok(obj_path)
+        Ok(obj_path.to_path_buf())
+    }
+
+    /// Compile the C runtime without the C library, for the built-in linker
+    ///
+    /// The runtime then makes its own system calls and provides the
+    /// `aurora_start` entry point.
+    pub fn compile_freestanding_runtime(&self, runtime_c: &Path, obj_path: &Path) -> Result<PathBuf, LinkError> {
+        let status = Command::new("gcc")
+            .args(["-c", "-O2", "-DAURORA_FREESTANDING", "-ffreestanding", "-fno-builtin"])
+            .args(["-fno-stack-protector", "-fno-pie", "-fno-asynchronous-unwind-tables"])
+            .arg("-o")
+            .arg(obj_path)
+            .arg(runtime_c)
+            .status()
+            .map_err(|e| LinkError::IoError(format!("Failed to compile C runtime: {}", e)))?;
+
+        if !status.success() {
+            return Err(LinkError::LinkFailed(
+                "Failed to compile C runtime".to_string(),
+            ));
+        }
+
+        Ok(obj_path.to_path_buf())
     }
 }
 
@@ -278,6 +371,7 @@ mod tests {
     #[test]
     fn test_linker_config_default() {
         let config = LinkerConfig::default();
+        assert_eq!(config.kind, LinkerKind::External);
         assert_eq!(config.linker, "gcc");
         assert!(config.args.is_empty());
         assert!(config.libraries.is_empty());
@@ -294,6 +388,15 @@ mod tests {
         assert_eq!(linker.config.args.len(), 1);
     }
 
+    #[test]
+    fn test_internal_linker_does_not_search_libraries() {
+        let mut linker = Linker::new();
+        linker.set_kind(LinkerKind::Internal);
+        linker.add_library("c".to_string());
+        let result = linker.link(&[], Path::new("/nonexistent/program"));
+        assert!(matches!(result, Err(LinkError::LibrarySearch(libs)) if libs == "-lc"));
+    }
+
     #[test]
     fn test_set_linker() {
         let mut linker = Linker::new();
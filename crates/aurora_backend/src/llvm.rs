@@ -24,7 +24,7 @@ pub enum OptLevel {
 
 impl OptLevel {
     /// Convert to GCC optimization flag
-    fn to_gcc_flag(&self) -> &str {
+    pub(crate) fn to_gcc_flag(&self) -> &str {
         match self {
             OptLevel::None => "-O0",
             OptLevel::Less => "-O1",
@@ -44,6 +44,9 @@ pub enum BackendError {
 
     #[error("Assembly failed: {0}")]
     AssemblyFailed(String),
+
+    #[error("C compilation failed: {0}")]
+    CompilationFailed(String),
 }
 
 impl LlvmBackend {
@@ -69,24 +72,30 @@ impl LlvmBackend {
         // Step 1: Convert AIR (NASM syntax) to GAS (AT&T syntax with Intel mode)
         let gas_asm = self.air_to_gas(air_text)?;
 
-        // Step 2: Write to temporary assembly file
+        // Step 2: Assemble it
+        self.assemble(&gas_asm, output_path)
+    }
+
+    /// Assemble GAS source to an object file with the target's C compiler
+    pub fn assemble(&self, gas_asm: &str, output_path: &Path) -> Result<(), BackendError> {
         let asm_path = output_path.with_extension("s");
         fs::write(&asm_path, gas_asm)
             .map_err(|e| BackendError::IoError(format!("Failed to write assembly: {}", e)))?;
 
-        // Step 3: Assemble with GCC
-        let status = Command::new("gcc")
+        let compiler = c_compiler(&self.target_triple);
+        let status = Command::new(&compiler)
             .arg(self.opt_level.to_gcc_flag())
             .arg("-c") // Compile only, don't link
             .arg("-o")
             .arg(output_path)
             .arg(&asm_path)
             .status()
-            .map_err(|e| BackendError::AssemblyFailed(format!("Failed to run gcc: {}", e)))?;
+            .map_err(|e| BackendError::AssemblyFailed(format!("Failed to run {}: {}", compiler, e)))?;
 
         if !status.success() {
             return Err(BackendError::AssemblyFailed(format!(
-                "GCC returned non-zero exit code: {}",
+                "{} returned non-zero exit code: {}",
+                compiler,
                 status.code().unwrap_or(-1)
             )));
         }
@@ -162,8 +171,9 @@ impl LlvmBackend {
                 continue;
             }
 
-            // Regular instruction - keep as-is for Intel syntax
-            output.push_str(line);
+            // Regular instruction - keep as-is for Intel syntax, apart from
+            // NASM operand sizes (`qword [rax]` is `qword ptr [rax]` in GAS)
+            output.push_str(&line.replace("qword [", "qword ptr ["));
             output.push('\n');
         }
 
@@ -221,6 +231,20 @@ impl LlvmBackend {
     }
 }
 
+/// C compiler driver assembling and linking for a target triple
+///
+/// The host's `gcc` builds for its own architecture; other architectures
+/// use the GNU cross toolchain prefix, e.g. `aarch64-linux-gnu-gcc`.
+pub fn c_compiler(target_triple: &str) -> String {
+    let arch = target_triple.split('-').next().unwrap_or_default();
+    if arch == std::env::consts::ARCH {
+        return "gcc".to_string();
+    }
+    let os = if target_triple.contains("linux") { "linux" } else { "none" };
+    let env = target_triple.rsplit('-').next().filter(|env| env.starts_with("gnu")).unwrap_or("gnu");
+    format!("{}-{}-{}-gcc", arch, os, env)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -307,6 +331,18 @@ test_func:
         let _ = result;
     }
 
+    #[test]
+    fn test_c_compiler_for_target() {
+        let host = format!("{}-unknown-linux-gnu", std::env::consts::ARCH);
+        assert_eq!(c_compiler(&host), "gcc");
+        if std::env::consts::ARCH != "aarch64" {
+            assert_eq!(c_compiler("aarch64-unknown-linux-gnu"), "aarch64-linux-gnu-gcc");
+        }
+        if std::env::consts::ARCH != "x86_64" {
+            assert_eq!(c_compiler("x86_64-unknown-linux-gnu"), "x86_64-linux-gnu-gcc");
+        }
+    }
+
     #[test]
     fn test_comments_conversion() {
         let backend = LlvmBackend::new("x86_64-unknown-linux-gnu".to_string());
@@ -0,0 +1,963 @@
+//! Static Linker - Link x86_64 ELF objects into a static executable
+//!
+//! Objects are linked together with the archive members that define
+//! symbols they refer to, the way `ld` searches archives. Allocated
+//! sections are merged into a read-only executable segment (code, then
+//! read-only data) and a writable one (data, the GOT, then zero-initialized
+//! data); symbols are resolved, relocations applied, and the result written
+//! as an ELF executable with no dynamic dependencies.
+//!
+//! The entry point `_start` passes `argc`, `argv`, `envp` and `main` to
+//! `aurora_start` and exits with its result. Thread-local storage and
+//! dynamic relocations are not supported.
+
+use crate::elf::{
+    EHDR_SIZE, EM_X86_64, ET_REL, RELA_SIZE, R_X86_64_PC32, R_X86_64_PLT32, SHF_ALLOC, SHF_EXECINSTR,
+    SHF_WRITE, SHT_RELA, SHT_SYMTAB, STB_GLOBAL, STB_LOCAL, SYM_SIZE,
+};
+use crate::link::LinkError;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// ELF file type of an executable
+const ET_EXEC: u16 = 2;
+
+/// Size of a program header
+const PHDR_SIZE: usize = 56;
+
+/// Number of program headers written
+const PHDR_COUNT: usize = 3;
+
+const PT_LOAD: u32 = 1;
+const PT_GNU_STACK: u32 = 0x6474_E551;
+
+const PF_X: u32 = 0x1;
+const PF_W: u32 = 0x2;
+const PF_R: u32 = 0x4;
+
+const SHT_NOBITS: u32 = 8;
+const SHT_REL: u32 = 9;
+
+const SHF_TLS: u64 = 0x400;
+
+const SHN_UNDEF: u16 = 0;
+const SHN_ABS: u16 = 0xFFF1;
+const SHN_COMMON: u16 = 0xFFF2;
+
+const STB_WEAK: u8 = 2;
+
+const R_X86_64_NONE: u32 = 0;
+const R_X86_64_64: u32 = 1;
+const R_X86_64_GOTPCREL: u32 = 9;
+const R_X86_64_32: u32 = 10;
+const R_X86_64_32S: u32 = 11;
+const R_X86_64_PC64: u32 = 24;
+const R_X86_64_GOTPCRELX: u32 = 41;
+const R_X86_64_REX_GOTPCRELX: u32 = 42;
+
+/// Address the executable is loaded at
+const BASE_ADDRESS: u64 = 0x40_0000;
+
+/// Segment alignment
+const PAGE_SIZE: u64 = 0x1000;
+
+/// Size of a GOT entry
+const GOT_ENTRY_SIZE: u64 = 8;
+
+/// Entry point calling `aurora_start(argc, argv, envp, main)`
+const START_CODE: [u8; 39] = [
+    0x31, 0xED, // xor ebp, ebp
+    0x48, 0x8B, 0x3C, 0x24, // mov rdi, [rsp]
+    0x48, 0x8D, 0x74, 0x24, 0x08, // lea rsi, [rsp+8]
+    0x48, 0x8D, 0x54, 0xFE, 0x08, // lea rdx, [rsi+rdi*8+8]
+    0x48, 0x83, 0xE4, 0xF0, // and rsp, -16
+    0x48, 0x8D, 0x0D, 0x00, 0x00, 0x00, 0x00, // lea rcx, [rip+main]
+    0xE8, 0x00, 0x00, 0x00, 0x00, // call aurora_start
+    0x89, 0xC7, // mov edi, eax
+    0xB8, 0xE7, 0x00, 0x00, 0x00, // mov eax, 231 (exit_group)
+];
+
+/// `syscall`, ending the entry point
+const SYSCALL: [u8; 2] = [0x0F, 0x05];
+
+/// Where a merged section goes in the executable, in layout order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum OutputKind {
+    Text,
+    ReadOnly,
+    Data,
+    Bss,
+    /// Thread-local data, which is not supported
+    ThreadLocal,
+}
+
+/// Allocated section of an input object
+#[derive(Debug)]
+struct InputSection {
+    kind: OutputKind,
+    /// Contents, empty for zero-initialized sections
+    data: Vec<u8>,
+    size: u64,
+    align: u64,
+    relocations: Vec<InputRelocation>,
+    /// Address in the executable, once laid out
+    address: u64,
+}
+
+#[derive(Debug)]
+struct InputRelocation {
+    offset: u64,
+    kind: u32,
+    symbol: usize,
+    addend: i64,
+}
+
+/// Where a symbol is defined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Definition {
+    Undefined,
+    Absolute,
+    /// Zero-initialized space allocated by the linker
+    Common { size: u64, align: u64 },
+    Section(usize),
+}
+
+#[derive(Debug)]
+struct InputSymbol {
+    name: String,
+    binding: u8,
+    definition: Definition,
+    value: u64,
+}
+
+impl InputSymbol {
+    fn is_local(&self) -> bool {
+        self.binding == STB_LOCAL
+    }
+
+    fn is_defined(&self) -> bool {
+        self.definition != Definition::Undefined
+    }
+}
+
+/// Relocatable object being linked
+#[derive(Debug)]
+struct InputObject {
+    name: String,
+    /// Allocated sections by section header index
+    sections: Vec<Option<InputSection>>,
+    symbols: Vec<InputSymbol>,
+}
+
+impl InputObject {
+    /// Parse an x86_64 ELF relocatable object
+    fn parse(name: String, bytes: &[u8]) -> Result<Self, LinkError> {
+        let invalid = |reason: &str| LinkError::InvalidObject(format!("{}: {}", name, reason));
+        let reader = Reader(bytes);
+        if bytes.get(..4) != Some(b"\x7fELF") || bytes.get(4..6) != Some(&[2, 1]) {
+            return Err(invalid("not a 64-bit little-endian ELF file"));
+        }
+        if reader.u16(16)? != ET_REL || reader.u16(18)? != EM_X86_64 {
+            return Err(invalid("not an x86_64 relocatable object"));
+        }
+
+        let section_headers = reader.u64(40)? as usize;
+        let headers = (0..reader.u16(60)? as usize)
+            .map(|index| SectionHeader::parse(&reader, section_headers + index * 64))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut sections: Vec<Option<InputSection>> = Vec::with_capacity(headers.len());
+        for header in &headers {
+            if header.flags & SHF_ALLOC == 0 {
+                sections.push(None);
+                continue;
+            }
+            let kind = if header.flags & SHF_TLS != 0 {
+                OutputKind::ThreadLocal
+            } else if header.kind == SHT_NOBITS {
+                OutputKind::Bss
+            } else if header.flags & SHF_EXECINSTR != 0 {
+                OutputKind::Text
+            } else if header.flags & SHF_WRITE != 0 {
+                OutputKind::Data
+            } else {
+                OutputKind::ReadOnly
+            };
+            let data = match kind {
+                OutputKind::Bss | OutputKind::ThreadLocal => Vec::new(),
+                _ => reader.slice(header.offset as usize, header.size as usize)?.to_vec(),
+            };
+            sections.push(Some(InputSection {
+                kind,
+                data,
+                size: header.size,
+                align: header.align.max(1),
+                relocations: Vec::new(),
+                address: 0,
+            }));
+        }
+
+        let mut symbols = Vec::new();
+        if let Some(symtab) = headers.iter().find(|header| header.kind == SHT_SYMTAB) {
+            let strtab = headers.get(symtab.link as usize).ok_or_else(|| invalid("missing string table"))?;
+            // Entry 0 is the null symbol
+            for index in 1..symtab.size as usize / SYM_SIZE {
+                let entry = symtab.offset as usize + index * SYM_SIZE;
+                let name = reader.string(strtab.offset as usize + reader.u32(entry)? as usize)?;
+                let info = reader.u8(entry + 4)?;
+                let definition = match reader.u16(entry + 6)? {
+                    SHN_UNDEF => Definition::Undefined,
+                    SHN_ABS => Definition::Absolute,
+                    SHN_COMMON => Definition::Common {
+                        size: reader.u64(entry + 16)?,
+                        align: reader.u64(entry + 8)?.max(1),
+                    },
+                    section => Definition::Section(section as usize),
+                };
+                symbols.push(InputSymbol {
+                    name,
+                    binding: info >> 4,
+                    definition,
+                    value: reader.u64(entry + 8)?,
+                });
+            }
+        }
+
+        for header in &headers {
+            if header.kind == SHT_REL && sections.get(header.info as usize).is_some_and(Option::is_some) {
+                return Err(invalid("REL relocations are not supported"));
+            }
+            if header.kind != SHT_RELA {
+                continue;
+            }
+            // Relocations of sections not in the executable are dropped
+            let Some(Some(section)) = sections.get_mut(header.info as usize) else {
+                continue;
+            };
+            for index in 0..header.size as usize / RELA_SIZE {
+                let entry = header.offset as usize + index * RELA_SIZE;
+                let info = reader.u64(entry + 8)?;
+                let symbol = (info >> 32) as usize;
+                if symbol == 0 || symbol > symbols.len() {
+                    return Err(invalid("relocation against an invalid symbol"));
+                }
+                section.relocations.push(InputRelocation {
+                    offset: reader.u64(entry)?,
+                    kind: info as u32,
+                    symbol: symbol - 1,
+                    addend: reader.u64(entry + 16)? as i64,
+                });
+            }
+        }
+
+        Ok(Self { name, sections, symbols })
+    }
+
+    /// The entry point, referring to `main` and `aurora_start`
+    fn start() -> Self {
+        let mut data = START_CODE.to_vec();
+        data.extend(SYSCALL);
+        let symbol = |name: &str, definition| InputSymbol {
+            name: name.to_string(),
+            binding: STB_GLOBAL,
+            definition,
+            value: 0,
+        };
+        let relocation = |offset, kind, symbol| InputRelocation {
+            offset,
+            kind,
+            symbol,
+            addend: -4,
+        };
+        Self {
+            name: "<entry point>".to_string(),
+            sections: vec![Some(InputSection {
+                kind: OutputKind::Text,
+                size: data.len() as u64,
+                data,
+                align: 16,
+                relocations: vec![relocation(23, R_X86_64_PC32, 1), relocation(28, R_X86_64_PLT32, 2)],
+                address: 0,
+            })],
+            symbols: vec![
+                symbol("_start", Definition::Section(0)),
+                symbol("main", Definition::Undefined),
+                symbol("aurora_start", Definition::Undefined),
+            ],
+        }
+    }
+
+    /// Global symbols defined in the object
+    fn definitions(&self) -> impl Iterator<Item = &InputSymbol> {
+        self.symbols.iter().filter(|symbol| !symbol.is_local() && symbol.is_defined())
+    }
+}
+
+/// Section header fields used by the linker
+struct SectionHeader {
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+}
+
+impl SectionHeader {
+    fn parse(reader: &Reader, at: usize) -> Result<Self, LinkError> {
+        Ok(Self {
+            kind: reader.u32(at + 4)?,
+            flags: reader.u64(at + 8)?,
+            offset: reader.u64(at + 24)?,
+            size: reader.u64(at + 32)?,
+            link: reader.u32(at + 40)?,
+            info: reader.u32(at + 44)?,
+            align: reader.u64(at + 48)?,
+        })
+    }
+}
+
+/// Bounds-checked little-endian reads
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn slice(&self, at: usize, len: usize) -> Result<&[u8], LinkError> {
+        at.checked_add(len)
+            .and_then(|end| self.0.get(at..end))
+            .ok_or_else(|| LinkError::InvalidObject("truncated file".to_string()))
+    }
+
+    fn u8(&self, at: usize) -> Result<u8, LinkError> {
+        Ok(self.slice(at, 1)?[0])
+    }
+
+    fn u16(&self, at: usize) -> Result<u16, LinkError> {
+        Ok(u16::from_le_bytes(self.slice(at, 2)?.try_into().unwrap_or_default()))
+    }
+
+    fn u32(&self, at: usize) -> Result<u32, LinkError> {
+        Ok(u32::from_le_bytes(self.slice(at, 4)?.try_into().unwrap_or_default()))
+    }
+
+    fn u64(&self, at: usize) -> Result<u64, LinkError> {
+        Ok(u64::from_le_bytes(self.slice(at, 8)?.try_into().unwrap_or_default()))
+    }
+
+    fn string(&self, at: usize) -> Result<String, LinkError> {
+        let rest = self.0.get(at..).unwrap_or_default();
+        let len = rest
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| LinkError::InvalidObject("unterminated string".to_string()))?;
+        Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+    }
+}
+
+/// Members of an `ar` archive, by name
+///
+/// The symbol index is skipped; members are searched by their own symbol
+/// tables instead.
+pub fn archive_members(bytes: &[u8]) -> Result<Vec<(String, Vec<u8>)>, LinkError> {
+    let invalid = |reason: &str| LinkError::InvalidObject(format!("archive: {}", reason));
+    let rest = bytes.strip_prefix(b"!<arch>\n").ok_or_else(|| invalid("bad magic"))?;
+    let reader = Reader(rest);
+
+    let mut members = Vec::new();
+    let mut long_names: &[u8] = &[];
+    let mut at = 0;
+    while at < rest.len() {
+        let header = reader.slice(at, 60)?;
+        let field = |range: std::ops::Range<usize>| String::from_utf8_lossy(&header[range]).trim_end().to_string();
+        let size: usize = field(48..58).parse().map_err(|_| invalid("bad member size"))?;
+        let data = reader.slice(at + 60, size)?;
+        let name = field(0..16);
+
+        match name.as_str() {
+            // Symbol index
+            "/" | "/SYM64/" => {}
+            // GNU long name table
+            "//" => long_names = data,
+            _ => {
+                let name = match name.strip_prefix('/') {
+                    Some(offset) => {
+                        let offset: usize = offset.parse().map_err(|_| invalid("bad long name"))?;
+                        let names = long_names.get(offset..).unwrap_or_default();
+                        let end = names.iter().position(|&byte| byte == b'\n').unwrap_or(names.len());
+                        String::from_utf8_lossy(&names[..end]).trim_end_matches('/').to_string()
+                    }
+                    None => name.trim_end_matches('/').to_string(),
+                };
+                members.push((name, data.to_vec()));
+            }
+        }
+
+        // Members are 2-byte aligned
+        at += 60 + size + size % 2;
+    }
+    Ok(members)
+}
+
+/// Link objects and archives into a static executable at `output`
+pub fn link_static(object_files: &[PathBuf], archives: &[PathBuf], output: &Path) -> Result<(), LinkError> {
+    let read = |path: &PathBuf| {
+        fs::read(path).map_err(|e| LinkError::IoError(format!("Failed to read {}: {}", path.display(), e)))
+    };
+
+    let mut linker = StaticLinker::new();
+    for path in object_files {
+        linker.add_object(InputObject::parse(path.display().to_string(), &read(path)?)?);
+    }
+    for path in archives {
+        for (member, bytes) in archive_members(&read(path)?)? {
+            let name = format!("{}({})", path.display(), member);
+            linker.archive.push(InputObject::parse(name, &bytes)?);
+        }
+    }
+
+    let executable = linker.link()?;
+    fs::write(output, executable)
+        .map_err(|e| LinkError::IoError(format!("Failed to write {}: {}", output.display(), e)))?;
+    make_executable(output)
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<(), LinkError> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .map_err(|e| LinkError::IoError(format!("Failed to set permissions of {}: {}", path.display(), e)))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<(), LinkError> {
+    Ok(())
+}
+
+/// A linked symbol: a global by name, or a local of an object
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum SymbolKey {
+    Global(String),
+    Local(usize, usize),
+}
+
+/// In-process static ELF linker
+struct StaticLinker {
+    objects: Vec<InputObject>,
+    /// Archive members not yet linked
+    archive: Vec<InputObject>,
+    /// Defining object and symbol index of each global
+    globals: HashMap<String, (usize, usize)>,
+    /// Addresses of common symbols
+    commons: HashMap<String, u64>,
+    /// GOT slot index of each symbol referred to through the GOT
+    got: HashMap<SymbolKey, u64>,
+    got_address: u64,
+}
+
+impl StaticLinker {
+    fn new() -> Self {
+        let mut linker = Self {
+            objects: Vec::new(),
+            archive: Vec::new(),
+            globals: HashMap::new(),
+            commons: HashMap::new(),
+            got: HashMap::new(),
+            got_address: 0,
+        };
+        linker.objects.push(InputObject::start());
+        linker
+    }
+
+    fn add_object(&mut self, object: InputObject) {
+        self.objects.push(object);
+    }
+
+    /// Produce the executable image
+    fn link(mut self) -> Result<Vec<u8>, LinkError> {
+        self.load_archive_members();
+        self.check_supported()?;
+        self.resolve_globals()?;
+        self.check_undefined()?;
+        self.allocate_got();
+        let layout = self.layout();
+        self.relocate()?;
+        self.write(&layout)
+    }
+
+    /// Link archive members defining symbols that are still undefined,
+    /// until no more are needed
+    fn load_archive_members(&mut self) {
+        loop {
+            let undefined = self.undefined_names();
+            let needed = self
+                .archive
+                .iter()
+                .position(|member| member.definitions().any(|symbol| undefined.contains(&symbol.name)));
+            match needed {
+                Some(index) => {
+                    let member = self.archive.remove(index);
+                    self.objects.push(member);
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn check_supported(&self) -> Result<(), LinkError> {
+        for object in &self.objects {
+            if object.sections.iter().flatten().any(|section| section.kind == OutputKind::ThreadLocal) {
+                return Err(LinkError::InvalidObject(format!(
+                    "{}: thread-local storage is not supported",
+                    object.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Names referred to but not defined by the linked objects
+    fn undefined_names(&self) -> Vec<String> {
+        let defined: std::collections::HashSet<&str> = self
+            .objects
+            .iter()
+            .flat_map(InputObject::definitions)
+            .map(|symbol| symbol.name.as_str())
+            .collect();
+        self.objects
+            .iter()
+            .flat_map(|object| &object.symbols)
+            .filter(|symbol| symbol.binding == STB_GLOBAL && !symbol.is_defined())
+            .filter(|symbol| !defined.contains(symbol.name.as_str()))
+            .map(|symbol| symbol.name.clone())
+            .collect()
+    }
+
+    /// Choose the definition of each global: a strong definition over a
+    /// weak or common one, and the largest common one
+    fn resolve_globals(&mut self) -> Result<(), LinkError> {
+        for (object_index, object) in self.objects.iter().enumerate() {
+            for (symbol_index, symbol) in object.symbols.iter().enumerate() {
+                if symbol.is_local() || !symbol.is_defined() {
+                    continue;
+                }
+                let rank = |symbol: &InputSymbol| match symbol.definition {
+                    Definition::Common { size, .. } => (1, size),
+                    _ if symbol.binding == STB_WEAK => (0, 0),
+                    _ => (2, 0),
+                };
+                match self.globals.get(&symbol.name) {
+                    Some(&(other_object, other_symbol)) => {
+                        let other = &self.objects[other_object].symbols[other_symbol];
+                        if rank(symbol).0 == 2 && rank(other).0 == 2 {
+                            return Err(LinkError::DuplicateSymbol(symbol.name.clone()));
+                        }
+                        if rank(symbol) > rank(other) {
+                            self.globals.insert(symbol.name.clone(), (object_index, symbol_index));
+                        }
+                    }
+                    None => {
+                        self.globals.insert(symbol.name.clone(), (object_index, symbol_index));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_undefined(&self) -> Result<(), LinkError> {
+        let mut undefined = self.undefined_names();
+        undefined.sort();
+        undefined.dedup();
+        if undefined.is_empty() {
+            Ok(())
+        } else {
+            Err(LinkError::UndefinedSymbol(undefined.join(", ")))
+        }
+    }
+
+    /// Assign a GOT slot to every symbol referred to through the GOT
+    fn allocate_got(&mut self) {
+        for object_index in 0..self.objects.len() {
+            let object = &self.objects[object_index];
+            let keys: Vec<SymbolKey> = object
+                .sections
+                .iter()
+                .flatten()
+                .flat_map(|section| &section.relocations)
+                .filter(|relocation| {
+                    matches!(
+                        relocation.kind,
+                        R_X86_64_GOTPCREL | R_X86_64_GOTPCRELX | R_X86_64_REX_GOTPCRELX
+                    )
+                })
+                .map(|relocation| self.key(object_index, relocation.symbol))
+                .collect();
+            for key in keys {
+                let slot = self.got.len() as u64;
+                self.got.entry(key).or_insert(slot);
+            }
+        }
+    }
+
+    fn key(&self, object: usize, symbol: usize) -> SymbolKey {
+        let input = &self.objects[object].symbols[symbol];
+        if input.is_local() {
+            SymbolKey::Local(object, symbol)
+        } else {
+            SymbolKey::Global(input.name.clone())
+        }
+    }
+
+    /// Assign addresses to sections, the GOT and common symbols
+    fn layout(&mut self) -> Layout {
+        let headers = (EHDR_SIZE + PHDR_SIZE * PHDR_COUNT) as u64;
+        let mut offset = headers;
+        let place = |objects: &mut Vec<InputObject>, kind: OutputKind, offset: &mut u64| {
+            for section in objects.iter_mut().flat_map(|object| object.sections.iter_mut().flatten()) {
+                if section.kind == kind {
+                    *offset = offset.next_multiple_of(section.align);
+                    section.address = BASE_ADDRESS + *offset;
+                    *offset += section.size;
+                }
+            }
+        };
+
+        place(&mut self.objects, OutputKind::Text, &mut offset);
+        place(&mut self.objects, OutputKind::ReadOnly, &mut offset);
+        let code_end = offset;
+
+        let data_start = offset.next_multiple_of(PAGE_SIZE);
+        offset = data_start;
+        place(&mut self.objects, OutputKind::Data, &mut offset);
+        offset = offset.next_multiple_of(GOT_ENTRY_SIZE);
+        self.got_address = BASE_ADDRESS + offset;
+        offset += GOT_ENTRY_SIZE * self.got.len() as u64;
+        let data_end = offset;
+
+        place(&mut self.objects, OutputKind::Bss, &mut offset);
+        let mut commons: Vec<(&String, u64, u64)> = self
+            .globals
+            .iter()
+            .filter_map(|(name, &(object, symbol))| match self.objects[object].symbols[symbol].definition {
+                Definition::Common { size, align } => Some((name, size, align)),
+                _ => None,
+            })
+            .collect();
+        commons.sort();
+        for (name, size, align) in commons {
+            offset = offset.next_multiple_of(align);
+            self.commons.insert(name.clone(), BASE_ADDRESS + offset);
+            offset += size;
+        }
+
+        Layout {
+            code_end,
+            data_start,
+            data_end,
+            end: offset,
+        }
+    }
+
+    /// Address of a symbol of an object
+    fn address(&self, object: usize, symbol: usize) -> u64 {
+        let (object, symbol) = match self.key(object, symbol) {
+            SymbolKey::Local(..) => (object, symbol),
+            SymbolKey::Global(name) => match self.globals.get(&name) {
+                Some(&definition) => definition,
+                // An undefined weak symbol is zero
+                None => return 0,
+            },
+        };
+        let input = &self.objects[object].symbols[symbol];
+        match input.definition {
+            Definition::Undefined => 0,
+            Definition::Absolute => input.value,
+            Definition::Common { .. } => self.commons.get(&input.name).copied().unwrap_or_default(),
+            Definition::Section(section) => match self.objects[object].sections.get(section) {
+                Some(Some(section)) => section.address + input.value,
+                _ => input.value,
+            },
+        }
+    }
+
+    /// Apply every relocation
+    fn relocate(&mut self) -> Result<(), LinkError> {
+        let mut patches = Vec::new();
+        for (object_index, object) in self.objects.iter().enumerate() {
+            for (section_index, section) in object.sections.iter().enumerate() {
+                let Some(section) = section else { continue };
+                for relocation in &section.relocations {
+                    let target = self.address(object_index, relocation.symbol).wrapping_add_signed(relocation.addend);
+                    let place = section.address + relocation.offset;
+                    let got_slot = || {
+                        let slot = self.got[&self.key(object_index, relocation.symbol)];
+                        (self.got_address + slot * GOT_ENTRY_SIZE).wrapping_add_signed(relocation.addend)
+                    };
+                    let value = match relocation.kind {
+                        R_X86_64_NONE => continue,
+                        R_X86_64_64 => Patch::U64(target),
+                        R_X86_64_PC64 => Patch::U64(target.wrapping_sub(place)),
+                        R_X86_64_PC32 | R_X86_64_PLT32 => Patch::I32(target.wrapping_sub(place) as i64),
+                        R_X86_64_GOTPCREL | R_X86_64_GOTPCRELX | R_X86_64_REX_GOTPCRELX => {
+                            Patch::I32(got_slot().wrapping_sub(place) as i64)
+                        }
+                        R_X86_64_32 => Patch::U32(target),
+                        R_X86_64_32S => Patch::I32(target as i64),
+                        kind => {
+                            return Err(LinkError::UnsupportedRelocation(format!(
+                                "type {} in {}",
+                                kind, object.name
+                            )))
+                        }
+                    };
+                    patches.push((object_index, section_index, relocation.offset as usize, value));
+                }
+            }
+        }
+
+        for (object, section, offset, patch) in patches {
+            let object = &mut self.objects[object];
+            let name = &object.name;
+            let Some(section) = object.sections[section].as_mut() else { continue };
+            let bytes = match patch {
+                Patch::U64(value) => value.to_le_bytes().to_vec(),
+                Patch::U32(value) => u32::try_from(value)
+                    .map_err(|_| LinkError::RelocationOverflow(name.clone()))?
+                    .to_le_bytes()
+                    .to_vec(),
+                Patch::I32(value) => i32::try_from(value)
+                    .map_err(|_| LinkError::RelocationOverflow(name.clone()))?
+                    .to_le_bytes()
+                    .to_vec(),
+            };
+            section
+                .data
+                .get_mut(offset..offset + bytes.len())
+                .ok_or_else(|| LinkError::InvalidObject(format!("{}: relocation out of range", name)))?
+                .copy_from_slice(&bytes);
+        }
+        Ok(())
+    }
+
+    /// Write the ELF executable
+    fn write(&self, layout: &Layout) -> Result<Vec<u8>, LinkError> {
+        let entry = self
+            .globals
+            .get("_start")
+            .map(|&(object, symbol)| self.address(object, symbol))
+            .unwrap_or_default();
+
+        let mut out = vec![0; layout.data_end as usize];
+        for section in self.objects.iter().flat_map(|object| object.sections.iter().flatten()) {
+            if section.kind != OutputKind::Bss {
+                let offset = (section.address - BASE_ADDRESS) as usize;
+                out[offset..offset + section.data.len()].copy_from_slice(&section.data);
+            }
+        }
+        let mut got: Vec<_> = self.got.iter().collect();
+        got.sort_by_key(|(_, &slot)| slot);
+        for (key, slot) in got {
+            let address = match key {
+                SymbolKey::Local(object, symbol) => self.address(*object, *symbol),
+                SymbolKey::Global(name) => match self.globals.get(name) {
+                    Some(&(object, symbol)) => self.address(object, symbol),
+                    None => 0,
+                },
+            };
+            let offset = (self.got_address - BASE_ADDRESS + slot * GOT_ENTRY_SIZE) as usize;
+            out[offset..offset + 8].copy_from_slice(&address.to_le_bytes());
+        }
+
+        let mut header = Vec::with_capacity(EHDR_SIZE + PHDR_SIZE * PHDR_COUNT);
+        header.extend(b"\x7fELF");
+        // 64-bit, little-endian, version 1, System V ABI
+        header.extend([2, 1, 1, 0]);
+        header.extend([0; 8]);
+        header.extend(ET_EXEC.to_le_bytes());
+        header.extend(EM_X86_64.to_le_bytes());
+        header.extend(1u32.to_le_bytes());
+        header.extend(entry.to_le_bytes());
+        header.extend((EHDR_SIZE as u64).to_le_bytes());
+        // No section headers
+        header.extend(0u64.to_le_bytes());
+        header.extend(0u32.to_le_bytes());
+        header.extend((EHDR_SIZE as u16).to_le_bytes());
+        header.extend((PHDR_SIZE as u16).to_le_bytes());
+        header.extend((PHDR_COUNT as u16).to_le_bytes());
+        header.extend(64u16.to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+
+        let mut segment = |kind: u32, flags: u32, offset: u64, file_size: u64, memory_size: u64| {
+            header.extend(kind.to_le_bytes());
+            header.extend(flags.to_le_bytes());
+            header.extend(offset.to_le_bytes());
+            header.extend((BASE_ADDRESS + offset).to_le_bytes());
+            header.extend((BASE_ADDRESS + offset).to_le_bytes());
+            header.extend(file_size.to_le_bytes());
+            header.extend(memory_size.to_le_bytes());
+            header.extend(PAGE_SIZE.to_le_bytes());
+        };
+        // The code segment starts with the headers
+        segment(PT_LOAD, PF_R | PF_X, 0, layout.code_end, layout.code_end);
+        segment(
+            PT_LOAD,
+            PF_R | PF_W,
+            layout.data_start,
+            layout.data_end - layout.data_start,
+            layout.end - layout.data_start,
+        );
+        // Marks the stack as non-executable
+        segment(PT_GNU_STACK, PF_R | PF_W, 0, 0, 0);
+
+        out[..header.len()].copy_from_slice(&header);
+        Ok(out)
+    }
+}
+
+/// Value written by a relocation
+enum Patch {
+    U64(u64),
+    U32(u64),
+    I32(i64),
+}
+
+/// File offsets of the segments
+struct Layout {
+    /// End of code and read-only data
+    code_end: u64,
+    /// Start of writable data
+    data_start: u64,
+    /// End of initialized data
+    data_end: u64,
+    /// End of zero-initialized data
+    end: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elf::{write_object, SymbolSection};
+    use aurora_air::{AirFunction, AirModule, DataDirective, DataKind, Instruction, Operand, Register};
+    use std::process::Command;
+
+    /// An object exporting its functions
+    fn object(functions: Vec<AirFunction>, data: Vec<DataDirective>) -> Vec<u8> {
+        let mut module = AirModule::new("test".to_string());
+        for func in functions {
+            module.add_function(func);
+        }
+        module.data = data;
+        let mut object = write_object(&module).unwrap();
+        for symbol in &mut object.symbols {
+            symbol.global |= symbol.section == SymbolSection::Text;
+        }
+        object.to_bytes()
+    }
+
+    fn function(name: &str, instructions: Vec<Instruction>) -> AirFunction {
+        let mut func = AirFunction::new(name.to_string());
+        for inst in instructions {
+            func.push(inst);
+        }
+        func
+    }
+
+    /// An archive in the GNU format `ar` writes
+    fn archive(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut bytes = b"!<arch>\n".to_vec();
+        for (name, data) in members {
+            bytes.extend(format!("{:<16}{:<32}{:<10}`\n", format!("{}/", name), "0", data.len()).as_bytes());
+            bytes.extend(*data);
+            if data.len() % 2 == 1 {
+                bytes.push(b'\n');
+            }
+        }
+        bytes
+    }
+
+    fn runtime() -> Vec<u8> {
+        use Register::*;
+        let answer = function(
+            "answer",
+            vec![
+                Instruction::Lea {
+                    dest: Operand::Reg(RAX),
+                    src: Operand::Label("forty".to_string()),
+                },
+                Instruction::Mov {
+                    dest: Operand::Reg(RAX),
+                    src: Operand::Mem { base: RAX, offset: 0 },
+                },
+            ],
+        );
+        let start = function(
+            "aurora_start",
+            vec![
+                Instruction::Sub { dest: Operand::Reg(RSP), src: Operand::Imm(8) },
+                Instruction::Call { target: Operand::Reg(RCX) },
+                Instruction::Add { dest: Operand::Reg(RSP), src: Operand::Imm(8) },
+            ],
+        );
+        let forty = DataDirective {
+            label: "forty".to_string(),
+            kind: DataKind::Qword,
+            value: 40u64.to_le_bytes().to_vec(),
+        };
+        object(vec![start, answer], vec![forty])
+    }
+
+    fn main_object() -> Vec<u8> {
+        object(
+            vec![function(
+                "main",
+                vec![
+                    Instruction::Call { target: Operand::Label("answer".to_string()) },
+                    Instruction::Add { dest: Operand::Reg(Register::RAX), src: Operand::Imm(2) },
+                ],
+            )],
+            vec![],
+        )
+    }
+
+    #[test]
+    fn test_archive_members() {
+        let bytes = archive(&[("a.o", b"abc"), ("b.o", b"de")]);
+        let members = archive_members(&bytes).unwrap();
+        assert_eq!(members, vec![("a.o".to_string(), b"abc".to_vec()), ("b.o".to_string(), b"de".to_vec())]);
+    }
+
+    #[test]
+    fn test_undefined_symbol_is_an_error() {
+        let mut linker = StaticLinker::new();
+        linker.add_object(InputObject::parse("main.o".to_string(), &main_object()).unwrap());
+        match linker.link() {
+            Err(LinkError::UndefinedSymbol(names)) => assert_eq!(names, "answer, aurora_start"),
+            other => panic!("expected undefined symbols, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    #[test]
+    fn test_link_static_executable() {
+        let dir = std::env::temp_dir().join(format!("aurora_static_link_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let main = dir.join("main.o");
+        let runtime_archive = dir.join("libruntime.a");
+        let output = dir.join("program");
+
+        // The unused member refers to a symbol nothing defines
+        let unused = object(
+            vec![function("unused", vec![Instruction::Call { target: Operand::Label("missing".to_string()) }])],
+            vec![],
+        );
+        fs::write(&main, main_object()).unwrap();
+        fs::write(&runtime_archive, archive(&[("unused.o", &unused), ("runtime.o", &runtime())])).unwrap();
+
+        link_static(&[main], &[runtime_archive], &output).unwrap();
+        let status = Command::new(&output).status().unwrap();
+        assert_eq!(status.code(), Some(42));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,486 @@
+//! WebAssembly Binary Writer
+//!
+//! Encodes a [`WasmModule`] in the WebAssembly binary format: the type,
+//! import, function, memory, global, export, code and data sections, in
+//! that order. Function types are shared by every function with the same
+//! signature. The result is a complete module for a WASI runtime, with
+//! nothing left to link.
+
+use crate::dwarf::{sleb, uleb};
+use aurora_air::wasm::{Access, NumOp, ValType};
+use aurora_air::{WasmInst, WasmModule};
+
+/// `\0asm` and the binary format version
+const HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+
+const SECTION_TYPE: u8 = 1;
+const SECTION_IMPORT: u8 = 2;
+const SECTION_FUNCTION: u8 = 3;
+const SECTION_MEMORY: u8 = 5;
+const SECTION_GLOBAL: u8 = 6;
+const SECTION_EXPORT: u8 = 7;
+const SECTION_CODE: u8 = 10;
+const SECTION_DATA: u8 = 11;
+
+/// Kind of an imported or exported function
+const EXTERNAL_FUNC: u8 = 0x00;
+
+/// Kind of an exported memory
+const EXTERNAL_MEMORY: u8 = 0x02;
+
+/// Form of a function type
+const FUNC_TYPE: u8 = 0x60;
+
+/// Block type of a block producing no values
+const EMPTY_BLOCK: u8 = 0x40;
+
+/// Encode a module as a `.wasm` binary
+pub fn write_wasm(module: &WasmModule) -> Vec<u8> {
+    let signatures: Vec<(&[ValType], &[ValType])> = module
+        .imports
+        .iter()
+        .map(|import| (import.params.as_slice(), import.results.as_slice()))
+        .chain(
+            module
+                .functions
+                .iter()
+                .map(|func| (func.params.as_slice(), func.results.as_slice())),
+        )
+        .collect();
+    let mut types: Vec<(&[ValType], &[ValType])> = Vec::new();
+    let type_indices: Vec<u64> = signatures
+        .iter()
+        .map(|signature| match types.iter().position(|ty| ty == signature) {
+            Some(index) => index as u64,
+            None => {
+                types.push(*signature);
+                types.len() as u64 - 1
+            }
+        })
+        .collect();
+    let (import_types, function_types) = type_indices.split_at(module.imports.len());
+
+    let mut out = HEADER.to_vec();
+
+    let mut section = Vec::new();
+    uleb(&mut section, types.len() as u64);
+    for (params, results) in &types {
+        section.push(FUNC_TYPE);
+        write_types(&mut section, params);
+        write_types(&mut section, results);
+    }
+    write_section(&mut out, SECTION_TYPE, &section);
+
+    let mut section = Vec::new();
+    uleb(&mut section, module.imports.len() as u64);
+    for (import, &ty) in module.imports.iter().zip(import_types) {
+        write_name(&mut section, &import.module);
+        write_name(&mut section, &import.name);
+        section.push(EXTERNAL_FUNC);
+        uleb(&mut section, ty);
+    }
+    write_section(&mut out, SECTION_IMPORT, &section);
+
+    let mut section = Vec::new();
+    uleb(&mut section, function_types.len() as u64);
+    for &ty in function_types {
+        uleb(&mut section, ty);
+    }
+    write_section(&mut out, SECTION_FUNCTION, &section);
+
+    // One memory with a minimum and no maximum size
+    let mut section = vec![1, 0x00];
+    uleb(&mut section, u64::from(module.memory_pages));
+    write_section(&mut out, SECTION_MEMORY, &section);
+
+    // The mutable i32 stack pointer
+    let mut section = vec![1, val_type(ValType::I32), 0x01, 0x41];
+    sleb(&mut section, i64::from(module.stack_pointer as i32));
+    section.push(0x0B);
+    write_section(&mut out, SECTION_GLOBAL, &section);
+
+    let exports: Vec<(&str, usize)> = module
+        .functions
+        .iter()
+        .enumerate()
+        .filter_map(|(i, func)| Some((func.export.as_deref()?, module.imports.len() + i)))
+        .collect();
+    let mut section = Vec::new();
+    uleb(&mut section, exports.len() as u64 + 1);
+    write_name(&mut section, "memory");
+    section.extend([EXTERNAL_MEMORY, 0]);
+    for (name, index) in exports {
+        write_name(&mut section, name);
+        section.push(EXTERNAL_FUNC);
+        uleb(&mut section, index as u64);
+    }
+    write_section(&mut out, SECTION_EXPORT, &section);
+
+    let mut section = Vec::new();
+    uleb(&mut section, module.functions.len() as u64);
+    for func in &module.functions {
+        let mut code = Vec::new();
+        // Locals are declared in runs of the same type
+        let mut runs: Vec<(u64, ValType)> = Vec::new();
+        for &ty in &func.locals {
+            match runs.last_mut() {
+                Some((count, last)) if *last == ty => *count += 1,
+                _ => runs.push((1, ty)),
+            }
+        }
+        uleb(&mut code, runs.len() as u64);
+        for (count, ty) in runs {
+            uleb(&mut code, count);
+            code.push(val_type(ty));
+        }
+        for inst in &func.body {
+            encode(&mut code, inst);
+        }
+        code.push(0x0B);
+        uleb(&mut section, code.len() as u64);
+        section.extend(code);
+    }
+    write_section(&mut out, SECTION_CODE, &section);
+
+    let mut section = Vec::new();
+    uleb(&mut section, module.data.len() as u64);
+    for segment in &module.data {
+        // Active in memory 0, at a constant offset
+        section.extend([0x00, 0x41]);
+        sleb(&mut section, i64::from(segment.offset as i32));
+        section.push(0x0B);
+        uleb(&mut section, segment.bytes.len() as u64);
+        section.extend(&segment.bytes);
+    }
+    write_section(&mut out, SECTION_DATA, &section);
+
+    out
+}
+
+fn write_section(out: &mut Vec<u8>, id: u8, content: &[u8]) {
+    out.push(id);
+    uleb(out, content.len() as u64);
+    out.extend(content);
+}
+
+fn write_name(out: &mut Vec<u8>, name: &str) {
+    uleb(out, name.len() as u64);
+    out.extend(name.as_bytes());
+}
+
+fn write_types(out: &mut Vec<u8>, types: &[ValType]) {
+    uleb(out, types.len() as u64);
+    out.extend(types.iter().map(|&ty| val_type(ty)));
+}
+
+fn val_type(ty: ValType) -> u8 {
+    match ty {
+        ValType::I32 => 0x7F,
+        ValType::I64 => 0x7E,
+        ValType::F32 => 0x7D,
+        ValType::F64 => 0x7C,
+    }
+}
+
+/// Append an instruction's encoding
+fn encode(out: &mut Vec<u8>, inst: &WasmInst) {
+    match inst {
+        WasmInst::Block => out.extend([0x02, EMPTY_BLOCK]),
+        WasmInst::Loop => out.extend([0x03, EMPTY_BLOCK]),
+        WasmInst::If => out.extend([0x04, EMPTY_BLOCK]),
+        WasmInst::Else => out.push(0x05),
+        WasmInst::End => out.push(0x0B),
+        WasmInst::Br(depth) => with_index(out, 0x0C, *depth),
+        WasmInst::BrIf(depth) => with_index(out, 0x0D, *depth),
+        WasmInst::Return => out.push(0x0F),
+        WasmInst::Unreachable => out.push(0x00),
+        WasmInst::Drop => out.push(0x1A),
+        WasmInst::Call(index) => with_index(out, 0x10, *index),
+        WasmInst::LocalGet(index) => with_index(out, 0x20, *index),
+        WasmInst::LocalSet(index) => with_index(out, 0x21, *index),
+        WasmInst::LocalTee(index) => with_index(out, 0x22, *index),
+        WasmInst::GlobalGet(index) => with_index(out, 0x23, *index),
+        WasmInst::GlobalSet(index) => with_index(out, 0x24, *index),
+        WasmInst::I32Const(value) => {
+            out.push(0x41);
+            sleb(out, i64::from(*value));
+        }
+        WasmInst::I64Const(value) => {
+            out.push(0x42);
+            sleb(out, *value);
+        }
+        WasmInst::F32Const(bits) => {
+            out.push(0x43);
+            out.extend(bits.to_le_bytes());
+        }
+        WasmInst::F64Const(bits) => {
+            out.push(0x44);
+            out.extend(bits.to_le_bytes());
+        }
+        WasmInst::Load { access, offset } => {
+            let opcode = match access {
+                Access::I32 => 0x28,
+                Access::I64 => 0x29,
+                Access::F32 => 0x2A,
+                Access::F64 => 0x2B,
+                Access::Byte32 => 0x2D,
+                Access::Byte64 => 0x31,
+            };
+            memory_access(out, opcode, *access, *offset);
+        }
+        WasmInst::Store { access, offset } => {
+            let opcode = match access {
+                Access::I32 => 0x36,
+                Access::I64 => 0x37,
+                Access::F32 => 0x38,
+                Access::F64 => 0x39,
+                Access::Byte32 => 0x3A,
+                Access::Byte64 => 0x3C,
+            };
+            memory_access(out, opcode, *access, *offset);
+        }
+        WasmInst::MemorySize => out.extend([0x3F, 0x00]),
+        WasmInst::MemoryGrow => out.extend([0x40, 0x00]),
+        // Within memory 0
+        WasmInst::MemoryCopy => out.extend([0xFC, 0x0A, 0x00, 0x00]),
+        WasmInst::Num(op) => match numeric_opcode(*op) {
+            Opcode::Single(opcode) => out.push(opcode),
+            Opcode::Prefixed(opcode) => {
+                out.push(0xFC);
+                uleb(out, u64::from(opcode));
+            }
+        },
+    }
+}
+
+fn with_index(out: &mut Vec<u8>, opcode: u8, index: u32) {
+    out.push(opcode);
+    uleb(out, u64::from(index));
+}
+
+/// Append a load or store with its natural alignment
+fn memory_access(out: &mut Vec<u8>, opcode: u8, access: Access, offset: u32) {
+    out.push(opcode);
+    uleb(out, u64::from(access.size().trailing_zeros()));
+    uleb(out, u64::from(offset));
+}
+
+/// Encoding of a numeric instruction
+enum Opcode {
+    Single(u8),
+    /// Following the 0xFC prefix
+    Prefixed(u32),
+}
+
+fn numeric_opcode(op: NumOp) -> Opcode {
+    let opcode = match op {
+        NumOp::I32Eqz => 0x45,
+        NumOp::I32GtU => 0x4B,
+        NumOp::I64Eqz => 0x50,
+        NumOp::I64Eq => 0x51,
+        NumOp::I64Ne => 0x52,
+        NumOp::I64LtS => 0x53,
+        NumOp::I64GtS => 0x55,
+        NumOp::I64LeS => 0x57,
+        NumOp::I64GeS => 0x59,
+        NumOp::F32Eq => 0x5B,
+        NumOp::F32Ne => 0x5C,
+        NumOp::F32Lt => 0x5D,
+        NumOp::F32Gt => 0x5E,
+        NumOp::F32Le => 0x5F,
+        NumOp::F32Ge => 0x60,
+        NumOp::F64Eq => 0x61,
+        NumOp::F64Ne => 0x62,
+        NumOp::F64Lt => 0x63,
+        NumOp::F64Gt => 0x64,
+        NumOp::F64Le => 0x65,
+        NumOp::F64Ge => 0x66,
+        NumOp::I32Add => 0x6A,
+        NumOp::I32Sub => 0x6B,
+        NumOp::I32Shl => 0x74,
+        NumOp::I32ShrU => 0x76,
+        NumOp::I64Add => 0x7C,
+        NumOp::I64Sub => 0x7D,
+        NumOp::I64Mul => 0x7E,
+        NumOp::I64DivS => 0x7F,
+        NumOp::I64DivU => 0x80,
+        NumOp::I64RemS => 0x81,
+        NumOp::I64RemU => 0x82,
+        NumOp::I64And => 0x83,
+        NumOp::I64Or => 0x84,
+        NumOp::I64Xor => 0x85,
+        NumOp::I64Shl => 0x86,
+        NumOp::I64ShrU => 0x88,
+        NumOp::F32Neg => 0x8C,
+        NumOp::F32Sqrt => 0x91,
+        NumOp::F32Add => 0x92,
+        NumOp::F32Sub => 0x93,
+        NumOp::F32Mul => 0x94,
+        NumOp::F32Div => 0x95,
+        NumOp::F64Neg => 0x9A,
+        NumOp::F64Sqrt => 0x9F,
+        NumOp::F64Add => 0xA0,
+        NumOp::F64Sub => 0xA1,
+        NumOp::F64Mul => 0xA2,
+        NumOp::F64Div => 0xA3,
+        NumOp::I32WrapI64 => 0xA7,
+        NumOp::I64ExtendI32U => 0xAD,
+        NumOp::F32ConvertI64S => 0xB4,
+        NumOp::F32DemoteF64 => 0xB6,
+        NumOp::F64ConvertI64S => 0xB9,
+        NumOp::F64PromoteF32 => 0xBB,
+        NumOp::I32ReinterpretF32 => 0xBC,
+        NumOp::I64ReinterpretF64 => 0xBD,
+        NumOp::F32ReinterpretI32 => 0xBE,
+        NumOp::F64ReinterpretI64 => 0xBF,
+        NumOp::I64TruncSatF32S => return Opcode::Prefixed(4),
+        NumOp::I64TruncSatF64S => return Opcode::Prefixed(6),
+        NumOp::I64TruncSatF64U => return Opcode::Prefixed(7),
+    };
+    Opcode::Single(opcode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aurora_air::WasmEmitter;
+    use aurora_mir::{BinOp, Constant, Instruction, MirBuilder, MirModule, Operand, Span};
+    use aurora_types::{EffectSet, PrimitiveType, Type};
+    use std::fs;
+    use std::process::Command;
+
+    fn i64_ty() -> Type {
+        Type::Primitive(PrimitiveType::I64)
+    }
+
+    /// Print an integer, formatted into a string and freed after as MIR
+    /// lowering does
+    fn println_int(builder: &mut MirBuilder, value: Operand) {
+        let routine = |name: &str| Operand::Const(Constant::String(name.to_string()));
+        let buffer_ty = Type::Ptr {
+            inner: Box::new(Type::Primitive(PrimitiveType::U8)),
+            mutable: true,
+        };
+        let buffer = builder.build_call(routine("aurora_fmt_new"), Vec::new(), Some(buffer_ty), EffectSet::ALLOC, Span::dummy()).unwrap();
+        builder.build_call(routine("aurora_fmt_i64"), vec![Operand::Value(buffer), value], None, EffectSet::ALLOC, Span::dummy());
+        let text = builder.build_call(routine("aurora_fmt_finish"), vec![Operand::Value(buffer)], Some(Type::Primitive(PrimitiveType::Str)), EffectSet::ALLOC, Span::dummy()).unwrap();
+        builder.build_call(routine("println"), vec![Operand::Value(text)], None, EffectSet::IO, Span::dummy());
+        builder.build_call(routine("aurora_fmt_free"), vec![Operand::Value(text)], None, EffectSet::ALLOC, Span::dummy());
+    }
+
+    /// Prints the squares below 4 and a negative number, and exits with
+    /// the sum of the squares
+    fn squares_module() -> MirModule {
+        let mut module = MirModule::new();
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "main".to_string(), i64_ty(), EffectSet::IO);
+        let entry = builder.current_block().unwrap();
+        let head = builder.new_block();
+        let body = builder.new_block();
+        let exit = builder.new_block();
+        builder.build_jump(head, Span::dummy());
+
+        builder.set_block(head);
+        let next_i = builder.new_value(i64_ty(), Span::dummy());
+        let next_total = builder.new_value(i64_ty(), Span::dummy());
+        let i = builder.build_phi(vec![(entry, Operand::Const(Constant::Int(0))), (body, Operand::Value(next_i))], i64_ty(), Span::dummy());
+        let total = builder.build_phi(
+            vec![(entry, Operand::Const(Constant::Int(0))), (body, Operand::Value(next_total))],
+            i64_ty(),
+            Span::dummy(),
+        );
+        let more = builder.build_binop(BinOp::Lt, Operand::Value(i), Operand::Const(Constant::Int(4)), Type::Primitive(PrimitiveType::Bool), Span::dummy());
+        builder.build_branch(Operand::Value(more), body, exit, Span::dummy());
+
+        builder.set_block(body);
+        let square = builder.build_binop(BinOp::Mul, Operand::Value(i), Operand::Value(i), i64_ty(), Span::dummy());
+        println_int(&mut builder, Operand::Value(square));
+        for (dest, lhs, rhs) in [(next_total, total, Operand::Value(square)), (next_i, i, Operand::Const(Constant::Int(1)))] {
+            builder.emit(Instruction::BinOp {
+                dest,
+                op: BinOp::Add,
+                lhs: Operand::Value(lhs),
+                rhs,
+                span: Span::dummy(),
+            });
+        }
+        builder.build_jump(head, Span::dummy());
+
+        builder.set_block(exit);
+        let print = Operand::Const(Constant::String("print".to_string()));
+        builder.build_call(print, vec![Operand::Const(Constant::String("min ".to_string()))], None, EffectSet::IO, Span::dummy());
+        println_int(&mut builder, Operand::Const(Constant::Int(i64::MIN)));
+        builder.build_return(Some(Operand::Value(total)), Span::dummy());
+        module.add_function(builder.finish_function().unwrap());
+        module
+    }
+
+    #[test]
+    fn test_write_wasm_sections() {
+        let module = WasmEmitter::new().emit_module(&squares_module());
+        let bytes = write_wasm(&module);
+        assert_eq!(bytes[..8], HEADER);
+
+        // Walk the sections, which must be in order and fill the file
+        let mut ids = Vec::new();
+        let mut offset = 8;
+        while offset < bytes.len() {
+            ids.push(bytes[offset]);
+            let (mut size, mut shift) = (0usize, 0);
+            loop {
+                offset += 1;
+                size |= usize::from(bytes[offset] & 0x7F) << shift;
+                shift += 7;
+                if bytes[offset] & 0x80 == 0 {
+                    break;
+                }
+            }
+            offset += 1 + size;
+        }
+        assert_eq!(offset, bytes.len());
+        assert_eq!(ids, [1, 2, 3, 5, 6, 7, 10, 11]);
+    }
+
+    #[test]
+    fn test_numeric_encodings() {
+        let mut out = Vec::new();
+        encode(&mut out, &WasmInst::I64Const(-1));
+        encode(&mut out, &WasmInst::Num(NumOp::I64TruncSatF64S));
+        encode(&mut out, &WasmInst::Load { access: Access::I64, offset: 200 });
+        encode(&mut out, &WasmInst::Store { access: Access::Byte32, offset: 0 });
+        assert_eq!(out, [0x42, 0x7F, 0xFC, 0x06, 0x29, 0x03, 0xC8, 0x01, 0x3A, 0x00, 0x00]);
+
+        out.clear();
+        encode(&mut out, &WasmInst::MemorySize);
+        encode(&mut out, &WasmInst::MemoryGrow);
+        encode(&mut out, &WasmInst::MemoryCopy);
+        encode(&mut out, &WasmInst::Num(NumOp::I64TruncSatF64U));
+        assert_eq!(out, [0x3F, 0x00, 0x40, 0x00, 0xFC, 0x0A, 0x00, 0x00, 0xFC, 0x07]);
+    }
+
+    /// Run under Node's WASI implementation, where it is installed
+    #[test]
+    fn test_run_under_wasi() {
+        if Command::new("node").arg("--version").output().is_err() {
+            return;
+        }
+        let path = std::env::temp_dir().join(format!("aurora_wasm_{}.wasm", std::process::id()));
+        let module = WasmEmitter::new().emit_module(&squares_module());
+        fs::write(&path, write_wasm(&module)).unwrap();
+
+        let script = "const { WASI } = require('wasi');
+            const wasi = new WASI({ version: 'preview1', returnOnExit: true });
+            const bytes = require('fs').readFileSync(process.argv[1]);
+            WebAssembly.instantiate(bytes, { wasi_snapshot_preview1: wasi.wasiImport })
+                .then(({ instance }) => { process.exitCode = wasi.start(instance); });";
+        let output = Command::new("node")
+            .args(["--no-warnings", "-e", script])
+            .arg(&path)
+            .output()
+            .unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "0\n1\n4\n9\nmin -9223372036854775808\n");
+        assert_eq!(output.status.code(), Some(14));
+    }
+}
@@ -2,6 +2,7 @@
 
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
 /// Diagnostic severity level
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -216,14 +217,26 @@ impl DiagnosticCollector {
     /// This formats and prints all diagnostics in a user-friendly format
     /// with source code context.
     pub fn emit(&self, source: &str, path: &std::path::Path) {
+        self.emit_files(&[(path, source)]);
+    }
+
+    /// Emit all diagnostics to stderr for a multi-file compilation
+    ///
+    /// `files` holds the path and source of each file, indexed by span file
+    /// ID. Spans with an unknown file ID are shown against the first file.
+    pub fn emit_files(&self, files: &[(&std::path::Path, &str)]) {
         use std::io::{self, Write};
 
+        let Some(&first_file) = files.first() else {
+            return;
+        };
+
         // Sort diagnostics first
         let mut sorted = self.diagnostics.clone();
         sorted.sort_by(|a, b| {
             a.severity.cmp(&b.severity).then_with(|| {
                 match (a.span, b.span) {
-                    (Some(s1), Some(s2)) => s1.start.cmp(&s2.start),
+                    (Some(s1), Some(s2)) => (s1.file_id, s1.start).cmp(&(s2.file_id, s2.start)),
                     _ => std::cmp::Ordering::Equal,
                 }
             })
@@ -254,6 +267,7 @@ impl DiagnosticCollector {
 
             // Print location if available
             if let Some(span) = diag.span {
+                let (path, source) = files.get(span.file_id).copied().unwrap_or(first_file);
                 let (line, col) = get_line_col(source, span.start);
                 let _ = writeln!(
                     handle,
@@ -283,12 +297,35 @@ impl DiagnosticCollector {
     }
 }
 
+/// Diagnostic collector shared by the compiler phases that report into it
+#[derive(Debug, Default)]
+pub struct SharedCollector {
+    collector: Mutex<DiagnosticCollector>,
+}
+
+impl SharedCollector {
+    /// Create new shared collector
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lock the collector, recovering it if a reporting phase panicked
+    pub fn lock(&self) -> MutexGuard<'_, DiagnosticCollector> {
+        self.collector.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Add diagnostic
+    pub fn add(&self, diagnostic: Diagnostic) {
+        self.lock().add(diagnostic);
+    }
+}
+
 /// Get line and column from byte offset
 fn get_line_col(source: &str, offset: usize) -> (usize, usize) {
     let mut line = 1;
     let mut col = 1;
 
-    for (i, ch) in source.chars().enumerate() {
+    for (i, ch) in source.char_indices() {
         if i >= offset {
             break;
         }
@@ -407,4 +444,12 @@ mod tests {
         assert_eq!(fix.replacement, "fixed");
         assert_eq!(fix.description, "Fix typo");
     }
+
+    #[test]
+    fn test_line_col_uses_byte_offsets() {
+        let source = "let é = 1;\nfoo";
+        assert_eq!(get_line_col(source, 9), (1, 9));
+        assert_eq!(get_line_col(source, 12), (2, 1));
+        assert_eq!(get_line_col(source, 14), (2, 3));
+    }
 }
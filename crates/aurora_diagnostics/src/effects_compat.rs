@@ -1,15 +1,15 @@
 //! Compatibility layer for aurora_effects integration
 
-use crate::diagnostic::DiagnosticCollector;
+use crate::diagnostic::SharedCollector;
 use std::sync::Arc;
 
 /// Wrapper to implement aurora_effects::DiagnosticCollector
 pub struct EffectsDiagnosticAdapter {
-    inner: Arc<DiagnosticCollector>,
+    inner: Arc<SharedCollector>,
 }
 
 impl EffectsDiagnosticAdapter {
-    pub fn new(collector: Arc<DiagnosticCollector>) -> Self {
+    pub fn new(collector: Arc<SharedCollector>) -> Self {
         Self { inner: collector }
     }
 }
@@ -0,0 +1,95 @@
+//! Compatibility layer for aurora_lexer integration
+
+use crate::diagnostic::{Diagnostic, SharedCollector, Span};
+use crate::lsp::{Position, TextEdit};
+use aurora_lexer::{LexDiagnostics, SourceEdit, SpannedLexError};
+use std::ops::Range;
+
+impl LexDiagnostics for SharedCollector {
+    fn report_lex_error(&self, error: &SpannedLexError) {
+        let span = Span::new(error.offset, error.offset + error.len, error.file_id as usize);
+        self.add(Diagnostic::error(error.error.code(), error.error.to_string()).with_span(span));
+    }
+}
+
+impl SourceEdit for TextEdit {
+    fn range(&self, source: &str) -> Range<usize> {
+        byte_offset(source, self.range.start)..byte_offset(source, self.range.end)
+    }
+
+    fn new_text(&self) -> &str {
+        &self.new_text
+    }
+}
+
+/// Byte offset of an LSP position, whose character counts UTF-16 code
+/// units; positions past the end of a line or of the source are clamped
+fn byte_offset(source: &str, position: Position) -> usize {
+    let line_start = match position.line as usize {
+        0 => 0,
+        line => match source.match_indices('\n').nth(line - 1) {
+            Some((i, _)) => i + 1,
+            None => return source.len(),
+        },
+    };
+    let line = source[line_start..].split('\n').next().unwrap_or("").trim_end_matches('\r');
+
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aurora_lexer::{Lexer, TokenKind};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_lex_errors_become_diagnostics() {
+        let collector = Arc::new(SharedCollector::new());
+        let tokens = Lexer::with_diagnostics("let s = \"a\\qb\"; let n = 0b12;", collector.clone())
+            .with_file_id(2)
+            .tokenize();
+
+        assert_eq!(tokens.iter().filter(|t| t.kind == TokenKind::Error).count(), 2);
+        let collector = collector.lock();
+        let diagnostics = collector.diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].code, "E0011");
+        assert_eq!(diagnostics[0].span, Some(Span::new(10, 12, 2)));
+        assert_eq!(diagnostics[1].code, "E0012");
+        assert_eq!(diagnostics[1].span, Some(Span::new(27, 28, 2)));
+    }
+
+    #[test]
+    fn test_text_edits_apply_to_lexed_source() {
+        use crate::lsp::Range as LspRange;
+        use aurora_lexer::LexedSource;
+
+        let source = "let a = \"𝄞é\";\r\nlet b = 2;\n";
+        let position = |line, character| Position { line, character };
+        let edit = |start, end, text: &str| TextEdit {
+            range: LspRange { start, end },
+            new_text: text.to_string(),
+        };
+
+        // The clef takes two UTF-16 code units, and four bytes
+        assert_eq!(byte_offset(source, position(0, 11)), 13);
+        assert_eq!(byte_offset(source, position(0, 99)), 17);
+        assert_eq!(byte_offset(source, position(1, 4)), 23);
+        assert_eq!(byte_offset(source, position(7, 0)), source.len());
+
+        let mut lexed = LexedSource::new(Lexer::new(source, "test.ax".to_string()).unwrap());
+        lexed.edit(&edit(position(1, 4), position(1, 5), "bee"));
+        lexed.edit(&edit(position(0, 9), position(0, 11), "x"));
+        assert_eq!(lexed.source(), "let a = \"xé\";\r\nlet bee = 2;\n");
+        let scratch = Lexer::new(lexed.source(), "test.ax".to_string()).unwrap().lex_recovering();
+        assert_eq!(lexed.tokens(), scratch);
+    }
+}
@@ -25,8 +25,11 @@ pub mod lsp;
 /// Effects compatibility layer
 pub mod effects_compat;
 
+/// Lexer compatibility layer
+pub mod lexer_compat;
+
 // Re-export main types
-pub use diagnostic::{Diagnostic, DiagnosticCollector, FixIt, Label, Severity, Span};
+pub use diagnostic::{Diagnostic, DiagnosticCollector, FixIt, Label, Severity, SharedCollector, Span};
 pub use lsp::{
     CodeAction, CompletionItem, CompletionKind, DocumentSymbol, Hover, Position, Range,
     SymbolKind, TextEdit,
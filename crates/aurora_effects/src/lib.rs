@@ -60,6 +60,7 @@ pub use lifetimes::{Lifetime, LifetimeConstraint, LifetimeContext, LifetimeError
 pub use strict::{StrictChecker, StrictConfig, StrictError, StrictModeEnforcer};
 
 // Pipeline integration
+use aurora_ast::expr::InterpolationPart;
 use aurora_ast::{Ast, ExprId, ExprKind, ItemKind, StmtKind};
 use aurora_types::{EffectSet, Type, TypeMap};
 use std::collections::HashMap;
@@ -200,6 +201,18 @@ impl EffectChecker {
                 effects.union(EffectSet::IO)
             }
 
+            ExprKind::Interpolated(parts) => {
+                // Formatting allocates the string
+                let mut effects = EffectSet::ALLOC;
+                for part in parts {
+                    if let InterpolationPart::Expr(expr_id) = part {
+                        let part_effects = self.check_expr_by_id(*expr_id);
+                        effects = compose_effects(effects, part_effects);
+                    }
+                }
+                effects
+            }
+
             ExprKind::Binary { op, left, right } => {
                 let left_effects = self.check_expr_by_id(*left);
                 let right_effects = self.check_expr_by_id(*right);
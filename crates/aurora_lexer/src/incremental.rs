@@ -0,0 +1,367 @@
+//! Incremental Re-lexing - Keeping a token stream up to date through edits
+//!
+//! After an edit only the tokens around the changed text are lexed again.
+//! Lexing restarts at a token boundary far enough before the edit that no
+//! earlier token could have been lexed differently, and stops at the first
+//! token after the edit that starts where an old token did, outside any
+//! interpolated string literal in both streams. From there on the old
+//! tokens are reused, moved to their new locations.
+
+use crate::lexer::{Lexer, SpannedLexError};
+use crate::tokens::{Token, TokenKind};
+use std::ops::Range;
+
+/// Characters past its end the lexer may look at to end a token (as in
+/// `1..2`, where `1` ends at the first '.')
+const LOOKAHEAD: usize = 2;
+
+/// A change to a source text: a range of it replaced by new text
+pub trait SourceEdit {
+    /// Byte range of `source` that is replaced
+    fn range(&self, source: &str) -> Range<usize>;
+
+    /// Text the range is replaced by
+    fn new_text(&self) -> &str;
+}
+
+/// An edit of a byte range
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteEdit {
+    /// Byte range replaced
+    pub range: Range<usize>,
+    /// Replacement text
+    pub text: String,
+}
+
+impl ByteEdit {
+    /// Create an edit replacing `range` by `text`
+    pub fn new(range: Range<usize>, text: impl Into<String>) -> Self {
+        Self { range, text: text.into() }
+    }
+}
+
+impl SourceEdit for ByteEdit {
+    fn range(&self, _source: &str) -> Range<usize> {
+        self.range.clone()
+    }
+
+    fn new_text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// How the locations in the text after an edit move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shift {
+    /// Byte offset in the old source from which locations move
+    pub from: usize,
+    /// Bytes inserted, less bytes removed
+    pub bytes: isize,
+    /// Lines inserted, less lines removed
+    pub lines: isize,
+    /// Line in the old source whose columns move; columns of later lines stay
+    pub line: usize,
+    /// Characters inserted on that line, less characters removed
+    pub columns: isize,
+}
+
+impl Shift {
+    /// Whether an old location at `offset` moves
+    pub fn moves(&self, offset: usize) -> bool {
+        offset >= self.from
+    }
+
+    /// New byte offset of an old one that moves
+    pub fn offset(&self, offset: usize) -> usize {
+        offset.wrapping_add_signed(self.bytes)
+    }
+
+    /// New line and column of an old location that moves
+    pub fn location(&self, line: usize, column: usize) -> (usize, usize) {
+        let column = if line == self.line { column.wrapping_add_signed(self.columns) } else { column };
+        (line.wrapping_add_signed(self.lines), column)
+    }
+
+    /// Move a token from the old source
+    pub fn move_token(&self, token: &mut Token) {
+        token.offset = self.offset(token.offset);
+        (token.line, token.column) = self.location(token.line, token.column);
+    }
+
+    /// Move an error from the old source
+    fn move_error(&self, error: &mut SpannedLexError) {
+        error.offset = self.offset(error.offset);
+        (error.line, error.column) = self.location(error.line, error.column);
+        error.error = error.error.clone().moved(|offset| self.offset(offset));
+    }
+}
+
+/// Tokens an edit replaced: `old[start..old_end]` by `new[start..new_end]`
+///
+/// The tokens before `start` are unchanged and those after the end are the
+/// old ones moved by `shift`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenSplice {
+    /// Index of the first token lexed again
+    pub start: usize,
+    /// End of the replaced tokens in the old stream
+    pub old_end: usize,
+    /// End of the replacing tokens in the new stream
+    pub new_end: usize,
+    /// How the tokens after the replaced ones moved
+    pub shift: Shift,
+}
+
+/// The tokens of a source text, kept up to date through edits
+pub struct LexedSource {
+    /// Source text
+    source: String,
+    /// Tokens of the source, ending with `Eof`
+    tokens: Vec<Token>,
+    /// Interpolated string literals open before each token
+    depths: Vec<usize>,
+    /// Errors found lexing the source
+    errors: Vec<SpannedLexError>,
+    /// Source file name
+    file: String,
+    /// Source file ID stamped on every token
+    file_id: u32,
+    /// Whether whitespace tokens are kept
+    keep_trivia: bool,
+}
+
+impl LexedSource {
+    /// Lex all of a lexer's source, recovering from errors, and keep what
+    /// is needed to lex it again after edits
+    ///
+    /// The errors go to the lexer's diagnostic collector, if any; those of
+    /// later edits are only available from `errors()`.
+    pub fn new(mut lexer: Lexer) -> Self {
+        let lexed = lex_until(&mut lexer, |_, _| false);
+        Self {
+            source: lexer.source.iter().collect(),
+            tokens: lexed.tokens,
+            depths: lexed.depths,
+            errors: lexed.errors,
+            file: lexer.file.clone(),
+            file_id: lexer.file_id,
+            keep_trivia: lexer.keep_trivia,
+        }
+    }
+
+    /// The source text
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The tokens of the source, ending with `Eof`
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// Errors found lexing the source
+    pub fn errors(&self) -> &[SpannedLexError] {
+        &self.errors
+    }
+
+    /// Apply an edit to the source and lex the text around it again
+    pub fn edit(&mut self, edit: &impl SourceEdit) -> TokenSplice {
+        let range = edit.range(&self.source);
+        let text = edit.new_text();
+        let start = self.restart(range.start);
+        self.source.replace_range(range.clone(), text);
+        let bytes = text.len() as isize - range.len() as isize;
+        let edit_end = range.start + text.len();
+
+        // Whitespace before the first token is not in any token
+        let first = &self.tokens[start];
+        let (offset, line, column) = match first.offset <= range.start {
+            true => (first.offset, first.line, first.column),
+            false => (0, 1, 1),
+        };
+        let mut lexer = Lexer::resume(&self.source[offset..], self.file.clone(), offset, line, column)
+            .with_file_id(self.file_id);
+        lexer.keep_trivia = self.keep_trivia;
+
+        // Lex up to a token after the edit that starts where an old one
+        // did, both outside any interpolated string literal
+        let (old_tokens, old_depths) = (&self.tokens, &self.depths);
+        let mut old_end = start;
+        let lexed = lex_until(&mut lexer, |token, depth| {
+            if depth > 0 || token.offset < edit_end {
+                return false;
+            }
+            let old_offset = token.offset.wrapping_add_signed(-bytes);
+            while old_tokens.get(old_end).is_some_and(|old| old.offset < old_offset) {
+                old_end += 1;
+            }
+            old_tokens.get(old_end).is_some_and(|old| old.offset == old_offset) && old_depths[old_end] == 0
+        });
+
+        // Locations move as the token lexing stopped at did, or, if it
+        // reached the end, as the `Eof` token did
+        let (old, new) = match &lexed.stop {
+            Some(token) => (&self.tokens[old_end], token),
+            None => {
+                old_end = self.tokens.len();
+                (&self.tokens[old_end - 1], lexed.tokens.last().expect("Eof token"))
+            }
+        };
+        let shift = Shift {
+            from: old.offset,
+            bytes,
+            lines: new.line as isize - old.line as isize,
+            line: old.line,
+            columns: new.column as isize - old.column as isize,
+        };
+
+        let new_end = start + lexed.tokens.len();
+        self.tokens.splice(start..old_end, lexed.tokens);
+        self.depths.splice(start..old_end, lexed.depths);
+        for token in &mut self.tokens[new_end..] {
+            shift.move_token(token);
+        }
+
+        // Errors are found in token order, but for the unterminated literals
+        // reported at the end of input, which start after the last point
+        // with none open
+        let mut errors: Vec<_> = self.errors.iter().filter(|error| error.offset < offset).cloned().collect();
+        errors.extend(lexed.errors);
+        if lexed.stop.is_some() {
+            for error in self.errors.iter().filter(|error| shift.moves(error.offset)) {
+                let mut error = error.clone();
+                shift.move_error(&mut error);
+                errors.push(error);
+            }
+        }
+        self.errors = errors;
+
+        TokenSplice { start, old_end, new_end, shift }
+    }
+
+    /// Index of the token to start lexing again at for an edit at `offset`
+    ///
+    /// Tokens before it end far enough before the edit not to have looked
+    /// at the changed text, and no interpolated string literal is open.
+    fn restart(&self, offset: usize) -> usize {
+        let unchanged = self.tokens.partition_point(|token| {
+            let lookahead: usize = self.source[token.end()..].chars().take(LOOKAHEAD).map(char::len_utf8).sum();
+            token.end() + lookahead <= offset
+        });
+        (0..=unchanged.min(self.tokens.len() - 1))
+            .rev()
+            .find(|&i| self.depths[i] == 0 && self.tokens[i].offset <= offset)
+            .unwrap_or(0)
+    }
+}
+
+/// Tokens lexed until the end of input or a token accepted by `stop`
+struct Lexed {
+    /// Tokens before the one lexing stopped at
+    tokens: Vec<Token>,
+    /// Interpolated string literals open before each token
+    depths: Vec<usize>,
+    /// Errors found in those tokens
+    errors: Vec<SpannedLexError>,
+    /// Token lexing stopped at, if any
+    stop: Option<Token>,
+}
+
+/// Lex until the end of input or until `stop` accepts a token, given the
+/// number of interpolated string literals open before it
+fn lex_until(lexer: &mut Lexer, mut stop: impl FnMut(&Token, usize) -> bool) -> Lexed {
+    let mut lexed = Lexed {
+        tokens: Vec::new(),
+        depths: Vec::new(),
+        errors: Vec::new(),
+        stop: None,
+    };
+    loop {
+        let depth = lexer.interpolation_depth();
+        let reported = lexer.errors().len();
+        let token = lexer.next_token_recovering();
+        if stop(&token, depth) {
+            lexed.errors = lexer.errors()[..reported].to_vec();
+            lexed.stop = Some(token);
+            return lexed;
+        }
+        let is_eof = token.kind == TokenKind::Eof;
+        lexed.tokens.push(token);
+        lexed.depths.push(depth);
+        if is_eof {
+            lexed.errors = lexer.errors().to_vec();
+            return lexed;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "/// Doc\nfn main() {\n    let s = f\"a{x + 1}b{f\"c{y}\"}\";\r\n    let r = 1..2; // ünï\n    'c' \"\\q\" 0b12 /* end */\n}\n";
+
+    fn lexed(source: &str, keep_trivia: bool) -> LexedSource {
+        let lexer = Lexer::new(source, "test.ax".to_string()).unwrap().with_file_id(3);
+        LexedSource::new(if keep_trivia { lexer.with_trivia() } else { lexer })
+    }
+
+    /// Apply the edit incrementally and check it matches lexing from scratch
+    fn check(source: &str, edit: ByteEdit, keep_trivia: bool) -> TokenSplice {
+        let mut incremental = lexed(source, keep_trivia);
+        let splice = incremental.edit(&edit);
+        let mut edited = source.to_string();
+        edited.replace_range(edit.range.clone(), &edit.text);
+        let scratch = lexed(&edited, keep_trivia);
+        assert_eq!(incremental.source(), edited);
+        assert_eq!(incremental.tokens(), scratch.tokens(), "{:?} on {:?}", edit, source);
+        assert_eq!(incremental.errors(), scratch.errors(), "{:?} on {:?}", edit, source);
+        assert_eq!(incremental.depths, scratch.depths);
+        splice
+    }
+
+    #[test]
+    fn test_relex_matches_lexing_from_scratch() {
+        let boundaries: Vec<usize> = (0..=SOURCE.len()).filter(|&i| SOURCE.is_char_boundary(i)).collect();
+        for &at in &boundaries {
+            for text in ["x", "\"", "{", "}", "/*", "*/", "\n", ".", "1", "'", "\\", "é"] {
+                for keep_trivia in [false, true] {
+                    check(SOURCE, ByteEdit::new(at..at, text), keep_trivia);
+                }
+            }
+        }
+        for window in boundaries.windows(4) {
+            check(SOURCE, ByteEdit::new(window[0]..window[3], ""), false);
+            check(SOURCE, ByteEdit::new(window[0]..window[1], "\n"), true);
+        }
+    }
+
+    #[test]
+    fn test_relex_only_touches_the_edited_region() {
+        let source = "fn f() { let x = 1; }\n".repeat(100);
+        let at = source.len() / 2 + 4;
+        let splice = check(&source, ByteEdit::new(at..at, "lo"), true);
+        assert!(splice.new_end - splice.start <= 4, "{:?}", splice);
+        assert_eq!(splice.old_end - splice.start, splice.new_end - splice.start);
+        assert_eq!(splice.shift.bytes, 2);
+        assert_eq!(splice.shift.lines, 0);
+
+        // Opening a block comment swallows the rest of the file, up to `Eof`
+        let splice = check(&source, ByteEdit::new(at..at, "/*"), true);
+        assert_eq!(splice.old_end, lexed(&source, true).tokens().len() - 1);
+    }
+
+    #[test]
+    fn test_repeated_edits() {
+        let mut incremental = lexed("fn main() {}\n", true);
+        let mut source = "fn main() {}\n".to_string();
+        for (at, text) in [(11, "\n    let s = \"{"), (27, "x}\";"), (0, "// hi\n"), (33, "ñ"), (20, "")] {
+            let edit = ByteEdit::new(at..at + usize::from(text.is_empty()), text);
+            incremental.edit(&edit);
+            source.replace_range(edit.range.clone(), &edit.text);
+            let scratch = lexed(&source, true);
+            assert_eq!(incremental.tokens(), scratch.tokens());
+            assert_eq!(incremental.errors(), scratch.errors());
+        }
+    }
+}
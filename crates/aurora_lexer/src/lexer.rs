@@ -2,31 +2,103 @@
 //!
 //! This module implements the complete Aurora lexer that converts
 //! source code into a stream of tokens using NFA-based maximal-munch.
+//!
+//! The lexer recovers from errors: bad input becomes `TokenKind::Error`
+//! tokens and lexing carries on, so every error in a file is reported.
+//!
+//! A string literal prefixed with `f`, such as `f"x = {x}"`, is an
+//! interpolated string, whose `{expr}` holes are lexed as expressions: an
+//! `InterpolatedStringStart` token for the text up to the first hole, the
+//! tokens of the hole's expression, an `InterpolatedStringMiddle` for the
+//! text between two holes and an `InterpolatedStringEnd` for the rest.
+//! Braces nest inside holes, and `\{` and `\}` write literal braces. An
+//! empty `{}` is kept as text. Braces in other string literals are text.
+//!
+//! Whitespace is skipped unless the lexer is built `with_trivia()`, which
+//! emits it as `Whitespace` and `Newline` tokens so that, with the comment
+//! tokens, the tokens cover every byte of the source.
 
 use crate::nfa::{is_xid_continue, is_xid_start, KeywordTable, LexError, MaximalMunch};
 use crate::tokens::{Token, TokenKind};
 use std::sync::Arc;
 
+/// A lexical error with the source it was found in
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedLexError {
+    /// The error
+    pub error: LexError,
+    /// Source file ID
+    pub file_id: u32,
+    /// Byte offset of the offending text
+    pub offset: usize,
+    /// Length of the offending text in bytes
+    pub len: usize,
+    /// Line of the offending text (1-indexed)
+    pub line: usize,
+    /// Column of the offending text (1-indexed)
+    pub column: usize,
+}
+
+/// Receiver of the errors a lexer recovers from
+///
+/// `aurora_diagnostics::DiagnosticCollector` implements this to turn each
+/// error into a diagnostic.
+pub trait LexDiagnostics: Send + Sync {
+    /// Report one lexical error
+    fn report_lex_error(&self, error: &SpannedLexError);
+}
+
+/// An interpolated string literal one of whose holes is being lexed
+#[derive(Debug, Clone, Copy)]
+struct Interpolation {
+    /// Byte offset of the literal's opening quote
+    offset: usize,
+    /// Line of the opening quote
+    line: usize,
+    /// Column of the opening quote
+    column: usize,
+    /// Braces opened in the hole and not closed yet
+    depth: usize,
+}
+
 /// Aurora Lexer
 ///
 /// Converts source code into a stream of tokens using table-driven NFA
 /// with maximal-munch tokenization.
 pub struct Lexer {
     /// Source code
-    source: Vec<char>,
-    /// Current position
+    pub(crate) source: Vec<char>,
+    /// Current position (character index)
     pos: usize,
+    /// Current byte offset into the source
+    byte_pos: usize,
     /// Current line (1-indexed)
     line: usize,
     /// Current column (1-indexed)
     column: usize,
     /// Source file name
-    file: String,
+    pub(crate) file: String,
+    /// Source file ID stamped on every token
+    pub(crate) file_id: u32,
     /// Keyword lookup table
     keywords: KeywordTable,
     /// Optional diagnostic collector
-    #[allow(dead_code)]
-    diagnostics: Option<Arc<dyn std::any::Any + Send + Sync>>,
+    diagnostics: Option<Arc<dyn LexDiagnostics>>,
+    /// Errors found so far
+    errors: Vec<SpannedLexError>,
+    /// Interpolated string literals with a hole open, innermost last
+    interpolations: Vec<Interpolation>,
+    /// Whether whitespace is emitted as tokens rather than skipped
+    pub(crate) keep_trivia: bool,
+}
+
+/// A position of the lexer it can move back to
+#[derive(Debug, Clone, Copy)]
+struct Mark {
+    pos: usize,
+    byte_pos: usize,
+    line: usize,
+    column: usize,
 }
 
 impl Lexer {
@@ -38,18 +110,24 @@ impl Lexer {
         Ok(Self {
             source,
             pos: 0,
+            byte_pos: 0,
             line: 1,
             column: 1,
             file,
+            file_id: 0,
             keywords: KeywordTable::new(),
             diagnostics: None,
+            errors: Vec::new(),
+            interpolations: Vec::new(),
+            keep_trivia: false,
         })
     }
 
     /// Create a new lexer with diagnostic collector (for pipeline integration)
     ///
-    /// The diagnostics parameter can be any Arc type. Typically aurora_diagnostics::DiagnosticCollector.
-    pub fn with_diagnostics<D: Send + Sync + 'static>(
+    /// Every lexical error is reported to the collector, typically an
+    /// `aurora_diagnostics::DiagnosticCollector`.
+    pub fn with_diagnostics<D: LexDiagnostics + 'static>(
         source: &str,
         diagnostics: Arc<D>
     ) -> Self {
@@ -58,33 +136,174 @@ impl Lexer {
         Self {
             source,
             pos: 0,
+            byte_pos: 0,
             line: 1,
             column: 1,
             file: "<input>".to_string(),
+            file_id: 0,
+            keywords: KeywordTable::new(),
+            diagnostics: Some(diagnostics as Arc<dyn LexDiagnostics>),
+            errors: Vec::new(),
+            interpolations: Vec::new(),
+            keep_trivia: false,
+        }
+    }
+
+    /// Create a lexer for the rest of a file, from a token boundary outside
+    /// any interpolated string literal at the given location
+    pub(crate) fn resume(rest: &str, file: String, offset: usize, line: usize, column: usize) -> Self {
+        Self {
+            source: rest.chars().collect(),
+            pos: 0,
+            byte_pos: offset,
+            line,
+            column,
+            file,
+            file_id: 0,
             keywords: KeywordTable::new(),
-            diagnostics: Some(diagnostics as Arc<dyn std::any::Any + Send + Sync>),
+            diagnostics: None,
+            errors: Vec::new(),
+            interpolations: Vec::new(),
+            keep_trivia: false,
         }
     }
 
+    /// Set the source file ID recorded in token locations
+    pub fn with_file_id(mut self, file_id: u32) -> Self {
+        self.file_id = file_id;
+        self
+    }
+
+    /// Emit whitespace and newlines as tokens instead of skipping them
+    pub fn with_trivia(mut self) -> Self {
+        self.keep_trivia = true;
+        self
+    }
+
     /// Tokenize all source code (for pipeline integration)
     ///
-    /// This is an alias for `lex_all()` that returns a Vec<Token> directly,
-    /// panicking on errors (errors will be reported via diagnostics).
+    /// Like `lex_recovering()`, with the errors going to the diagnostic
+    /// collector only.
     pub fn tokenize(mut self) -> Vec<Token> {
-        match self.lex_all() {
-            Ok(tokens) => tokens,
-            Err(e) => {
-                eprintln!("Lexer error: {:?}", e);
-                vec![Token::eof("<error>".to_string(), 1, 1)]
-            }
-        }
+        self.lex_recovering()
     }
 
     /// Get the next token from the source
+    ///
+    /// Bad input fails with the first error found in the token; the lexer
+    /// can still be asked for the tokens after it.
     pub fn next_token(&mut self) -> Result<Token, LexError> {
-        // Skip whitespace
-        self.skip_whitespace();
+        let reported = self.errors.len();
+        let token = self.next_token_recovering();
+        match self.errors.get(reported) {
+            Some(error) => Err(error.error.clone()),
+            None => Ok(token),
+        }
+    }
+
+    /// Get the next token, turning bad input into an `Error` token
+    pub(crate) fn next_token_recovering(&mut self) -> Token {
+        if !self.keep_trivia {
+            self.skip_whitespace();
+        }
+
+        let start = self.mark();
+        let (start_pos, start_byte) = (start.pos, start.byte_pos);
+        let (start_line, start_column) = (start.line, start.column);
+        let reported = self.errors.len();
+
+        let token = match self.lex_token() {
+            // Errors inside a token (such as bad escapes) spoil all of it
+            Ok(token) if self.errors.len() > reported => Token { kind: TokenKind::Error, ..token },
+            Ok(token) => token,
+            Err(error) => {
+                self.recover(&error, start);
+                let lexeme: String = self.source[start_pos..self.pos].iter().collect();
+                let len = self.byte_pos - start_byte;
+                self.error(error, start_byte, len, start_line, start_column);
+                Token::new(TokenKind::Error, lexeme, self.file.clone(), start_line, start_column)
+            }
+        };
+        if token.kind == TokenKind::Eof {
+            self.close_interpolations();
+        }
+        token.with_location(self.file_id, start_byte, self.byte_pos - start_byte)
+    }
+
+    /// Skip past input that failed to lex as a token starting at `start`
+    fn recover(&mut self, error: &LexError, start: Mark) {
+        match error {
+            // Resume on the next line rather than swallow the rest of the file
+            LexError::UnterminatedString(_) => {
+                self.rewind(start);
+                self.advance();
+                while !self.is_at_end() && self.peek() != '\n' {
+                    self.advance();
+                }
+            }
+            _ if self.pos == start.pos => self.advance(),
+            _ => {}
+        }
+    }
 
+    /// Report the interpolated string literals left open at the end of input
+    fn close_interpolations(&mut self) {
+        for literal in std::mem::take(&mut self.interpolations) {
+            let error = LexError::UnterminatedString(literal.offset);
+            self.error(error, literal.offset, 1, literal.line, literal.column);
+        }
+    }
+
+    /// The current position, to move back to with `rewind()`
+    fn mark(&self) -> Mark {
+        Mark {
+            pos: self.pos,
+            byte_pos: self.byte_pos,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    /// Move back to an earlier position
+    fn rewind(&mut self, mark: Mark) {
+        self.pos = mark.pos;
+        self.byte_pos = mark.byte_pos;
+        self.line = mark.line;
+        self.column = mark.column;
+    }
+
+    /// Record an error at the given source location
+    fn error(&mut self, error: LexError, offset: usize, len: usize, line: usize, column: usize) {
+        self.report(SpannedLexError {
+            error,
+            file_id: self.file_id,
+            offset,
+            len,
+            line,
+            column,
+        });
+    }
+
+    /// Record an error and report it to the diagnostic collector
+    fn report(&mut self, error: SpannedLexError) {
+        if let Some(diagnostics) = &self.diagnostics {
+            diagnostics.report_lex_error(&error);
+        }
+        self.errors.push(error);
+    }
+
+    /// Errors found so far
+    pub fn errors(&self) -> &[SpannedLexError] {
+        &self.errors
+    }
+
+    /// Number of interpolated string literals with a hole open
+    pub(crate) fn interpolation_depth(&self) -> usize {
+        self.interpolations.len()
+    }
+
+    /// Lex the token starting at the current position
+    fn lex_token(&mut self) -> Result<Token, LexError> {
         // Check for EOF
         if self.is_at_end() {
             return Ok(Token::eof(self.file.clone(), self.line, self.column));
@@ -92,10 +311,14 @@ impl Lexer {
 
         let start_line = self.line;
         let start_column = self.column;
-        let start_pos = self.pos;
+        let start_byte = self.byte_pos;
 
         let c = self.peek();
 
+        if matches!(c, ' ' | '\t' | '\r' | '\n') {
+            return Ok(self.lex_whitespace(start_line, start_column));
+        }
+
         // Comments
         if c == '/' && self.peek_ahead(1) == Some('/') {
             return self.lex_line_comment(start_line, start_column);
@@ -109,6 +332,12 @@ impl Lexer {
             return self.lex_raw_string(start_line, start_column);
         }
 
+        // Interpolated string literals, likewise
+        if c == 'f' && self.peek_ahead(1) == Some('"') {
+            self.advance(); // 'f'
+            return self.lex_string(true, start_line, start_column);
+        }
+
         // Identifiers and keywords
         if is_xid_start(c) {
             return self.lex_identifier(start_line, start_column);
@@ -121,7 +350,20 @@ impl Lexer {
 
         // String literals
         if c == '"' {
-            return self.lex_string(start_line, start_column);
+            return self.lex_string(false, start_line, start_column);
+        }
+
+        // The `}` closing a hole resumes its interpolated string literal
+        if c == '}' && self.interpolations.last().is_some_and(|literal| literal.depth == 0) {
+            let literal = self.interpolations.pop().expect("open interpolation");
+            self.advance(); // '}'
+            return self.lex_string_part(
+                literal,
+                TokenKind::InterpolatedStringEnd,
+                Some(TokenKind::InterpolatedStringMiddle),
+                start_line,
+                start_column,
+            );
         }
 
         // Character literals
@@ -138,27 +380,47 @@ impl Lexer {
 
         // Delimiters
         if let Some(kind) = MaximalMunch::match_delimiter(c) {
+            if let Some(literal) = self.interpolations.last_mut() {
+                match kind {
+                    TokenKind::LBrace => literal.depth += 1,
+                    TokenKind::RBrace => literal.depth -= 1,
+                    _ => {}
+                }
+            }
             let lexeme = c.to_string();
             self.advance();
             return Ok(Token::new(kind, lexeme, self.file.clone(), start_line, start_column));
         }
 
         // If we reach here, it's an invalid character
-        Err(LexError::InvalidChar(c, start_pos))
+        Err(LexError::InvalidChar(c, start_byte))
     }
 
-    /// Lex all tokens from source
+    /// Lex all tokens from source, failing with the first error
     pub fn lex_all(&mut self) -> Result<Vec<Token>, LexError> {
+        let reported = self.errors.len();
+        let tokens = self.lex_recovering();
+        match self.errors.get(reported) {
+            Some(error) => Err(error.error.clone()),
+            None => Ok(tokens),
+        }
+    }
+
+    /// Lex all tokens from source, recovering from errors
+    ///
+    /// Bad input becomes `Error` tokens; the errors are available from
+    /// `errors()` and reported to the diagnostic collector, if any.
+    pub fn lex_recovering(&mut self) -> Vec<Token> {
         let mut tokens = Vec::new();
         loop {
-            let token = self.next_token()?;
+            let token = self.next_token_recovering();
             let is_eof = token.kind == TokenKind::Eof;
             tokens.push(token);
             if is_eof {
                 break;
             }
         }
-        Ok(tokens)
+        tokens
     }
 
     /// Lex an identifier or keyword
@@ -194,9 +456,9 @@ impl Lexer {
         if self.peek() == '0' {
             if let Some(next) = self.peek_ahead(1) {
                 match next {
-                    'x' | 'X' => return self.lex_hex_number(start_line, start_column),
-                    'b' | 'B' => return self.lex_binary_number(start_line, start_column),
-                    'o' | 'O' => return self.lex_octal_number(start_line, start_column),
+                    'x' | 'X' => return self.lex_radix_number(16, start_line, start_column),
+                    'b' | 'B' => return self.lex_radix_number(2, start_line, start_column),
+                    'o' | 'O' => return self.lex_radix_number(8, start_line, start_column),
                     _ => {}
                 }
             }
@@ -229,93 +491,153 @@ impl Lexer {
             if !self.is_at_end() && (self.peek() == '+' || self.peek() == '-') {
                 self.advance();
             }
+            let digits = self.pos;
             while !self.is_at_end() && (self.peek().is_ascii_digit() || self.peek() == '_') {
                 self.advance();
             }
+            if self.pos == digits {
+                return Err(LexError::InvalidNumber(self.byte_pos));
+            }
         }
 
-        let lexeme: String = self.source[start_pos..self.pos].iter().collect();
         let kind = if is_float {
             TokenKind::FloatLiteral
         } else {
             TokenKind::IntLiteral
         };
+        let kind = self.lex_number_suffix(kind, 10);
+        let lexeme: String = self.source[start_pos..self.pos].iter().collect();
 
         Ok(Token::new(kind, lexeme, self.file.clone(), start_line, start_column))
     }
 
-    /// Lex a hexadecimal number
-    fn lex_hex_number(&mut self, start_line: usize, start_column: usize) -> Result<Token, LexError> {
+    /// Lex a hexadecimal, binary or octal number
+    ///
+    /// Decimal digits past the end of a binary or octal literal are
+    /// reported rather than starting a new token.
+    fn lex_radix_number(&mut self, radix: u32, start_line: usize, start_column: usize) -> Result<Token, LexError> {
         let start_pos = self.pos;
+        let start_byte = self.byte_pos;
         self.advance(); // '0'
-        self.advance(); // 'x' or 'X'
+        self.advance(); // 'x', 'b' or 'o'
 
-        while !self.is_at_end() && (self.peek().is_ascii_hexdigit() || self.peek() == '_') {
+        let mut digits = 0;
+        while !self.is_at_end() && (self.peek().is_digit(radix) || self.peek() == '_') {
+            digits += usize::from(self.peek() != '_');
             self.advance();
         }
+        if digits == 0 {
+            return Err(LexError::InvalidNumber(start_byte));
+        }
 
-        let lexeme: String = self.source[start_pos..self.pos].iter().collect();
-        Ok(Token::new(TokenKind::IntLiteral, lexeme, self.file.clone(), start_line, start_column))
-    }
-
-    /// Lex a binary number
-    fn lex_binary_number(&mut self, start_line: usize, start_column: usize) -> Result<Token, LexError> {
-        let start_pos = self.pos;
-        self.advance(); // '0'
-        self.advance(); // 'b' or 'B'
-
-        while !self.is_at_end() && (matches!(self.peek(), '0' | '1' | '_')) {
+        while !self.is_at_end() && (self.peek().is_ascii_digit() || self.peek() == '_') {
+            let digit = self.peek();
+            if !digit.is_digit(radix) && digit != '_' {
+                let position = self.byte_pos;
+                self.error(LexError::InvalidDigit { digit, radix, position }, position, 1, self.line, self.column);
+            }
             self.advance();
         }
 
+        let kind = self.lex_number_suffix(TokenKind::IntLiteral, radix);
         let lexeme: String = self.source[start_pos..self.pos].iter().collect();
-        Ok(Token::new(TokenKind::IntLiteral, lexeme, self.file.clone(), start_line, start_column))
+        Ok(Token::new(kind, lexeme, self.file.clone(), start_line, start_column))
     }
 
-    /// Lex an octal number
-    fn lex_octal_number(&mut self, start_line: usize, start_column: usize) -> Result<Token, LexError> {
-        let start_pos = self.pos;
-        self.advance(); // '0'
-        self.advance(); // 'o' or 'O'
-
-        while !self.is_at_end() && (matches!(self.peek(), '0'..='7' | '_')) {
+    /// Lex the type suffix of a number literal, as in `255u8` or `1.5f32`,
+    /// returning the kind of the whole literal
+    ///
+    /// A float suffix makes a decimal integer a float literal. Suffixes
+    /// that name no numeric type, or one the literal cannot have, are
+    /// reported.
+    fn lex_number_suffix(&mut self, kind: TokenKind, radix: u32) -> TokenKind {
+        if self.is_at_end() || !is_xid_start(self.peek()) {
+            return kind;
+        }
+        let (start_pos, start_byte) = (self.pos, self.byte_pos);
+        let (line, column) = (self.line, self.column);
+        while !self.is_at_end() && is_xid_continue(self.peek()) {
             self.advance();
         }
 
-        let lexeme: String = self.source[start_pos..self.pos].iter().collect();
-        Ok(Token::new(TokenKind::IntLiteral, lexeme, self.file.clone(), start_line, start_column))
+        let suffix: String = self.source[start_pos..self.pos].iter().collect();
+        match suffix.as_str() {
+            "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" if kind == TokenKind::IntLiteral => kind,
+            "f32" | "f64" if radix == 10 => TokenKind::FloatLiteral,
+            _ => {
+                let len = self.byte_pos - start_byte;
+                self.error(LexError::InvalidSuffix(suffix, start_byte), start_byte, len, line, column);
+                kind
+            }
+        }
     }
 
-    /// Lex a string literal
-    fn lex_string(&mut self, start_line: usize, start_column: usize) -> Result<Token, LexError> {
-        let start_pos = self.pos;
+    /// Lex a string literal, or the text before the first hole of an
+    /// `interpolated` one, from its opening quote
+    fn lex_string(&mut self, interpolated: bool, start_line: usize, start_column: usize) -> Result<Token, LexError> {
+        let literal = Interpolation {
+            offset: self.byte_pos,
+            line: start_line,
+            column: start_column,
+            depth: 0,
+        };
         self.advance(); // opening '"'
+        let opened = interpolated.then_some(TokenKind::InterpolatedStringStart);
+        self.lex_string_part(literal, TokenKind::StringLiteral, opened, start_line, start_column)
+    }
 
-        while !self.is_at_end() && self.peek() != '"' {
+    /// Lex the text of a string literal up to its closing quote, as a
+    /// `closed` token, or up to a hole, as an `opened` one if the literal
+    /// is interpolated
+    ///
+    /// The lexeme is the text alone, without the quote or braces around it.
+    fn lex_string_part(
+        &mut self,
+        literal: Interpolation,
+        closed: TokenKind,
+        opened: Option<TokenKind>,
+        start_line: usize,
+        start_column: usize,
+    ) -> Result<Token, LexError> {
+        let start_pos = self.pos;
+
+        let mut bad_escapes = Vec::new();
+        while !self.is_at_end() && self.peek() != '"' && !(opened.is_some() && self.at_hole()) {
             if self.peek() == '\\' {
-                self.advance(); // escape character
-                if !self.is_at_end() {
-                    self.advance(); // escaped character
-                }
+                bad_escapes.extend(self.lex_escape());
             } else {
                 self.advance();
             }
         }
 
         if self.is_at_end() {
-            return Err(LexError::UnterminatedString(start_pos));
+            return Err(LexError::UnterminatedString(literal.offset));
         }
 
-        self.advance(); // closing '"'
+        let lexeme: String = self.source[start_pos..self.pos].iter().collect();
+        let kind = match opened {
+            Some(opened) if self.peek() != '"' => {
+                self.interpolations.push(literal);
+                opened
+            }
+            _ => closed,
+        };
+        self.advance(); // closing '"' or the hole's '{'
+        self.report_escapes(bad_escapes);
 
-        // Extract string content without quotes
-        let lexeme: String = self.source[start_pos+1..self.pos-1].iter().collect();
-        Ok(Token::new(TokenKind::StringLiteral, lexeme, self.file.clone(), start_line, start_column))
+        Ok(Token::new(kind, lexeme, self.file.clone(), start_line, start_column))
+    }
+
+    /// Whether a hole of an interpolated string starts here; `{}` does not
+    /// start one
+    fn at_hole(&self) -> bool {
+        self.peek() == '{' && self.peek_ahead(1) != Some('}')
     }
 
     /// Lex a raw string literal
     fn lex_raw_string(&mut self, start_line: usize, start_column: usize) -> Result<Token, LexError> {
         let start_pos = self.pos;
+        let start_byte = self.byte_pos;
         self.advance(); // 'r'
         self.advance(); // '"'
 
@@ -324,7 +646,7 @@ impl Lexer {
         }
 
         if self.is_at_end() {
-            return Err(LexError::UnterminatedString(start_pos));
+            return Err(LexError::UnterminatedString(start_byte));
         }
 
         self.advance(); // closing '"'
@@ -337,31 +659,65 @@ impl Lexer {
     /// Lex a character literal
     fn lex_char(&mut self, start_line: usize, start_column: usize) -> Result<Token, LexError> {
         let start_pos = self.pos;
+        let start_byte = self.byte_pos;
         self.advance(); // opening '\''
 
         if self.is_at_end() {
-            return Err(LexError::UnterminatedString(start_pos));
+            return Err(LexError::UnterminatedChar(start_byte));
         }
 
-        if self.peek() == '\\' {
-            self.advance(); // escape
-            if !self.is_at_end() {
-                self.advance(); // escaped char
-            }
+        let bad_escape = if self.peek() == '\\' {
+            self.lex_escape()
         } else {
             self.advance(); // the character
-        }
+            None
+        };
 
         if self.is_at_end() || self.peek() != '\'' {
-            return Err(LexError::UnterminatedString(start_pos));
+            return Err(LexError::UnterminatedChar(start_byte));
         }
 
         self.advance(); // closing '\''
+        self.report_escapes(bad_escape);
 
         let lexeme: String = self.source[start_pos..self.pos].iter().collect();
         Ok(Token::new(TokenKind::CharLiteral, lexeme, self.file.clone(), start_line, start_column))
     }
 
+    /// Lex an escape sequence in a string or character literal, returning
+    /// the error for an unknown escape
+    ///
+    /// The errors are only reported once the literal turns out to be
+    /// terminated, as otherwise its text is lexed again.
+    fn lex_escape(&mut self) -> Option<SpannedLexError> {
+        let (offset, line, column) = (self.byte_pos, self.line, self.column);
+        self.advance(); // '\\'
+        if self.is_at_end() {
+            return None;
+        }
+
+        let escaped = self.peek();
+        self.advance();
+        if matches!(escaped, 'n' | 't' | 'r' | '0' | '\\' | '"' | '\'' | '{' | '}') {
+            return None;
+        }
+        Some(SpannedLexError {
+            error: LexError::InvalidEscape(escaped, offset),
+            file_id: self.file_id,
+            offset,
+            len: self.byte_pos - offset,
+            line,
+            column,
+        })
+    }
+
+    /// Report the unknown escapes of a terminated literal
+    fn report_escapes(&mut self, errors: impl IntoIterator<Item = SpannedLexError>) {
+        for error in errors {
+            self.report(error);
+        }
+    }
+
     /// Lex a line comment
     fn lex_line_comment(&mut self, start_line: usize, start_column: usize) -> Result<Token, LexError> {
         let start_pos = self.pos;
@@ -392,6 +748,7 @@ impl Lexer {
     /// Lex a block comment
     fn lex_block_comment(&mut self, start_line: usize, start_column: usize) -> Result<Token, LexError> {
         let start_pos = self.pos;
+        let start_byte = self.byte_pos;
         self.advance(); // '/'
         self.advance(); // '*'
 
@@ -412,7 +769,7 @@ impl Lexer {
         }
 
         if depth > 0 {
-            return Err(LexError::UnterminatedBlockComment(start_pos));
+            return Err(LexError::UnterminatedBlockComment(start_byte));
         }
 
         let lexeme: String = self.source[start_pos..self.pos].iter().collect();
@@ -420,17 +777,31 @@ impl Lexer {
     }
 
     /// Skip whitespace
+    /// Lex a newline, or a run of other whitespace
+    fn lex_whitespace(&mut self, start_line: usize, start_column: usize) -> Token {
+        let start_pos = self.pos;
+        let kind = if self.peek() == '\n' || (self.peek() == '\r' && self.peek_ahead(1) == Some('\n')) {
+            self.advance_n(if self.peek() == '\r' { 2 } else { 1 });
+            TokenKind::Newline
+        } else {
+            while !self.is_at_end()
+                && matches!(self.peek(), ' ' | '\t' | '\r')
+                && !(self.peek() == '\r' && self.peek_ahead(1) == Some('\n'))
+            {
+                self.advance();
+            }
+            TokenKind::Whitespace
+        };
+        let lexeme: String = self.source[start_pos..self.pos].iter().collect();
+        Token::new(kind, lexeme, self.file.clone(), start_line, start_column)
+    }
+
     fn skip_whitespace(&mut self) {
         while !self.is_at_end() {
             match self.peek() {
-                ' ' | '\t' | '\r' => {
+                ' ' | '\t' | '\r' | '\n' => {
                     self.advance();
                 }
-                '\n' => {
-                    self.advance();
-                    self.line += 1;
-                    self.column = 1;
-                }
                 _ => break,
             }
         }
@@ -456,11 +827,19 @@ impl Lexer {
         self.source[self.pos..].iter().collect()
     }
 
-    /// Advance by one character
+    /// Advance by one character, keeping track of lines (also inside
+    /// comments and literals that span several)
     fn advance(&mut self) {
         if !self.is_at_end() {
+            let c = self.source[self.pos];
+            self.byte_pos += c.len_utf8();
             self.pos += 1;
-            self.column += 1;
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
         }
     }
 
@@ -785,6 +1164,46 @@ mod tests {
         assert_eq!(tokens[7].lexeme, "1_000_000");
     }
 
+    #[test]
+    fn test_lexer_number_suffixes() {
+        let source = "255u8 1_000_i64 0xFFu16 1.5f32 2f64 1e3f32";
+        let mut lexer = Lexer::new(source, "test.ax".to_string()).unwrap();
+        let tokens = lexer.lex_all().unwrap();
+
+        let literals: Vec<(TokenKind, &str)> = tokens[..6].iter().map(|t| (t.kind, t.lexeme.as_str())).collect();
+        assert_eq!(
+            literals,
+            [
+                (TokenKind::IntLiteral, "255u8"),
+                (TokenKind::IntLiteral, "1_000_i64"),
+                (TokenKind::IntLiteral, "0xFFu16"),
+                (TokenKind::FloatLiteral, "1.5f32"),
+                (TokenKind::FloatLiteral, "2f64"),
+                (TokenKind::FloatLiteral, "1e3f32"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_invalid_number_suffixes() {
+        let source = "1u7 1.5u8 0b1f32 2x";
+        let mut lexer = Lexer::new(source, "test.ax".to_string()).unwrap();
+        let tokens = lexer.lex_recovering();
+
+        let errors: Vec<&LexError> = lexer.errors().iter().map(|e| &e.error).collect();
+        assert_eq!(
+            errors,
+            [
+                &LexError::InvalidSuffix("u7".to_string(), 1),
+                &LexError::InvalidSuffix("u8".to_string(), 7),
+                &LexError::InvalidSuffix("f32".to_string(), 13),
+                &LexError::InvalidSuffix("x".to_string(), 18),
+            ]
+        );
+        assert_eq!(tokens.len(), 5);
+        assert!(tokens[..4].iter().all(|t| t.kind == TokenKind::Error));
+    }
+
     #[test]
     fn test_lexer_determinism() {
         // The lexer should produce identical results for the same input
@@ -804,4 +1223,150 @@ mod tests {
             assert_eq!(t1.column, t2.column);
         }
     }
+
+    #[test]
+    fn test_lexer_byte_offsets() {
+        // Multi-byte characters advance the byte offset by their UTF-8 length
+        let source = "let s = \"héllo\";\nx";
+        let mut lexer = Lexer::new(source, "test.ax".to_string()).unwrap().with_file_id(3);
+        let tokens = lexer.lex_all().unwrap();
+
+        let string = &tokens[3];
+        assert_eq!(string.kind, TokenKind::StringLiteral);
+        assert_eq!((string.offset, string.end()), (8, 16));
+        assert_eq!(&source[string.offset..string.end()], "\"héllo\"");
+
+        let x = &tokens[5];
+        assert_eq!(&source[x.offset..x.end()], "x");
+        assert_eq!((x.line, x.column), (2, 1));
+        assert!(tokens.iter().all(|t| t.file_id == 3));
+
+        let eof = tokens.last().unwrap();
+        assert_eq!((eof.offset, eof.len), (source.len(), 0));
+    }
+
+    #[test]
+    fn test_lexer_recovers_from_errors() {
+        let source = "let a = $;\nlet b = \"open;\nlet c = 0o19;\nlet d = '\\q';\nlet e = 'x";
+        let mut lexer = Lexer::new(source, "test.ax".to_string()).unwrap();
+        let tokens = lexer.lex_recovering();
+
+        let errors: Vec<&LexError> = lexer.errors().iter().map(|e| &e.error).collect();
+        assert_eq!(
+            errors,
+            [
+                &LexError::InvalidChar('$', 8),
+                &LexError::UnterminatedString(19),
+                &LexError::InvalidDigit { digit: '9', radix: 8, position: 37 },
+                &LexError::InvalidEscape('q', 49),
+                &LexError::UnterminatedChar(62),
+            ]
+        );
+
+        // Every line after an error is still lexed
+        let idents: Vec<&str> = tokens
+            .iter()
+            .filter(|t| t.kind == TokenKind::Ident)
+            .map(|t| t.lexeme.as_str())
+            .collect();
+        assert_eq!(idents, ["a", "b", "c", "d", "e"]);
+
+        let bad: Vec<&str> = tokens
+            .iter()
+            .filter(|t| t.kind == TokenKind::Error)
+            .map(|t| &source[t.offset..t.end()])
+            .collect();
+        assert_eq!(bad, ["$", "\"open;", "0o19", "'\\q'", "'x"]);
+        assert_eq!(tokens.last().unwrap().kind, TokenKind::Eof);
+
+        let unterminated = &lexer.errors()[1];
+        assert_eq!((unterminated.line, unterminated.column, unterminated.len), (2, 9, 6));
+    }
+
+    #[test]
+    fn test_lexer_lex_all_fails_on_first_error() {
+        let mut lexer = Lexer::new("0x 1e", "test.ax".to_string()).unwrap();
+        assert_eq!(lexer.lex_all(), Err(LexError::InvalidNumber(0)));
+        assert_eq!(lexer.errors().len(), 2);
+    }
+
+    #[test]
+    fn test_lexer_lines_inside_tokens() {
+        let source = "/* one\ntwo */ \"a\nb\" x";
+        let mut lexer = Lexer::new(source, "test.ax".to_string()).unwrap();
+        let tokens = lexer.lex_all().unwrap();
+
+        assert_eq!(tokens[1].line, 2);
+        assert_eq!((tokens[2].line, tokens[2].column), (3, 4));
+    }
+
+    #[test]
+    fn test_lexer_interpolated_strings() {
+        let source = r#"f"a{x}b{ {1} }\{c\}{}" f"{"#;
+        let mut lexer = Lexer::new(source, "test.ax".to_string()).unwrap();
+        let tokens = lexer.lex_recovering();
+
+        let kinds: Vec<(TokenKind, &str)> = tokens.iter().map(|t| (t.kind, t.lexeme.as_str())).collect();
+        assert_eq!(
+            kinds,
+            [
+                (TokenKind::InterpolatedStringStart, "a"),
+                (TokenKind::Ident, "x"),
+                (TokenKind::InterpolatedStringMiddle, "b"),
+                (TokenKind::LBrace, "{"),
+                (TokenKind::IntLiteral, "1"),
+                (TokenKind::RBrace, "}"),
+                (TokenKind::InterpolatedStringEnd, "\\{c\\}{}"),
+                (TokenKind::InterpolatedStringStart, ""),
+                (TokenKind::Eof, ""),
+            ]
+        );
+        let errors: Vec<&LexError> = lexer.errors().iter().map(|e| &e.error).collect();
+        assert_eq!(errors, [&LexError::UnterminatedString(24)]);
+    }
+
+    #[test]
+    fn test_lexer_plain_strings_keep_braces() {
+        let source = r#""x = {}, {x} {y + 1}" "{" f{"#;
+        let mut lexer = Lexer::new(source, "test.ax".to_string()).unwrap();
+        let tokens = lexer.lex_all().unwrap();
+
+        let kinds: Vec<(TokenKind, &str)> = tokens.iter().map(|t| (t.kind, t.lexeme.as_str())).collect();
+        assert_eq!(
+            kinds,
+            [
+                (TokenKind::StringLiteral, "x = {}, {x} {y + 1}"),
+                (TokenKind::StringLiteral, "{"),
+                (TokenKind::Ident, "f"),
+                (TokenKind::LBrace, "{"),
+                (TokenKind::Eof, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_with_trivia_covers_every_byte() {
+        let source = "let x = 1; // one\n\t/* two */ x\r\n";
+        let mut lexer = Lexer::new(source, "test.ax".to_string()).unwrap().with_trivia();
+        let tokens = lexer.lex_all().unwrap();
+
+        let text: String = tokens.iter().map(|t| &source[t.offset..t.end()]).collect();
+        assert_eq!(text, source);
+        let kinds: Vec<TokenKind> = tokens.iter().skip(9).map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::LineComment,
+                TokenKind::Newline,
+                TokenKind::Whitespace,
+                TokenKind::BlockComment,
+                TokenKind::Whitespace,
+                TokenKind::Ident,
+                TokenKind::Newline,
+                TokenKind::Eof,
+            ]
+        );
+        assert_eq!(tokens[11].line, 2);
+        assert_eq!(tokens[15].lexeme, "\r\n");
+    }
 }
@@ -1,7 +1,8 @@
 //! aurora_lexer - Aurora Lexer Agent
 //!
 //! This crate implements the lexical analysis phase of the Aurora compiler.
-//! It uses a table-driven NFA with maximal-munch tokenization.
+//! It uses a table-driven NFA with maximal-munch tokenization, and can lex
+//! a source again after an edit by re-lexing only the text around it.
 
 #![warn(missing_docs)]
 #![warn(clippy::all)]
@@ -9,10 +10,12 @@
 pub mod tokens;
 pub mod nfa;
 pub mod lexer;
+pub mod incremental;
 
 pub use tokens::{Token, TokenKind};
 pub use nfa::{KeywordTable, MaximalMunch, LexError};
-pub use lexer::Lexer;
+pub use lexer::{LexDiagnostics, Lexer, SpannedLexError};
+pub use incremental::{ByteEdit, LexedSource, Shift, SourceEdit, TokenSplice};
 
 #[cfg(test)]
 mod tests {
@@ -22,19 +22,77 @@ pub enum LexError {
     #[error("Unterminated string literal starting at position {0}")]
     UnterminatedString(usize),
 
+    /// Unterminated character literal
+    #[error("Unterminated character literal starting at position {0}")]
+    UnterminatedChar(usize),
+
     /// Unterminated block comment
     #[error("Unterminated block comment starting at position {0}")]
     UnterminatedBlockComment(usize),
 
+    /// Unknown escape sequence in a string or character literal
+    #[error("Unknown character escape '\\{0}' at position {1}")]
+    InvalidEscape(char, usize),
+
     /// Invalid number literal
     #[error("Invalid number literal at position {0}")]
     InvalidNumber(usize),
 
+    /// Digit out of range for the literal's radix
+    #[error("Invalid digit '{digit}' for a base {radix} literal at position {position}")]
+    InvalidDigit {
+        /// The offending digit
+        digit: char,
+        /// Radix of the literal (2 or 8)
+        radix: u32,
+        /// Byte offset of the digit
+        position: usize,
+    },
+
+    /// Suffix that names no numeric type, or the wrong kind of one
+    #[error("Invalid suffix '{0}' for a number literal at position {1}")]
+    InvalidSuffix(String, usize),
+
     /// Unexpected end of file
     #[error("Unexpected end of file")]
     UnexpectedEof,
 }
 
+impl LexError {
+    /// Diagnostic code for this error
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexError::InvalidUtf8(_) | LexError::InvalidChar(..) => "E0010",
+            LexError::UnterminatedString(_) => "E0765",
+            LexError::UnterminatedChar(_) => "E0762",
+            LexError::UnterminatedBlockComment(_) => "E0758",
+            LexError::InvalidEscape(..) => "E0011",
+            LexError::InvalidNumber(_) | LexError::InvalidDigit { .. } | LexError::InvalidSuffix(..) => {
+                "E0012"
+            }
+            LexError::UnexpectedEof => "E0013",
+        }
+    }
+
+    /// The error with its byte offset mapped by `f`
+    pub fn moved(self, f: impl FnOnce(usize) -> usize) -> Self {
+        match self {
+            LexError::InvalidUtf8(offset) => LexError::InvalidUtf8(f(offset)),
+            LexError::InvalidChar(c, offset) => LexError::InvalidChar(c, f(offset)),
+            LexError::UnterminatedString(offset) => LexError::UnterminatedString(f(offset)),
+            LexError::UnterminatedChar(offset) => LexError::UnterminatedChar(f(offset)),
+            LexError::UnterminatedBlockComment(offset) => LexError::UnterminatedBlockComment(f(offset)),
+            LexError::InvalidEscape(c, offset) => LexError::InvalidEscape(c, f(offset)),
+            LexError::InvalidNumber(offset) => LexError::InvalidNumber(f(offset)),
+            LexError::InvalidDigit { digit, radix, position } => {
+                LexError::InvalidDigit { digit, radix, position: f(position) }
+            }
+            LexError::InvalidSuffix(suffix, offset) => LexError::InvalidSuffix(suffix, f(offset)),
+            LexError::UnexpectedEof => LexError::UnexpectedEof,
+        }
+    }
+}
+
 /// NFA state for pattern matching
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum State {
@@ -142,15 +200,14 @@ impl KeywordTable {
         keywords.insert("var", TokenKind::Var);
         keywords.insert("const", TokenKind::Const);
         keywords.insert("static", TokenKind::Static);
+        keywords.insert("struct", TokenKind::Struct);
+        keywords.insert("enum", TokenKind::Enum);
         keywords.insert("type", TokenKind::Type);
         keywords.insert("trait", TokenKind::Trait);
         keywords.insert("impl", TokenKind::Impl);
         keywords.insert("where", TokenKind::Where);
         keywords.insert("in", TokenKind::In);
 
-        // Special statement keywords
-        keywords.insert("print", TokenKind::Print);
-
         // Module keywords
         keywords.insert("use", TokenKind::Use);
         keywords.insert("mod", TokenKind::Mod);
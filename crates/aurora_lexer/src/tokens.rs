@@ -35,15 +35,16 @@ pub enum TokenKind {
     Var,        // var (simplified syntax)
     Const,
     Static,
+    /// `struct`, which opens a struct declaration
+    Struct,
+    /// `enum`, which opens an enum declaration
+    Enum,
     Type,
     Trait,
     Impl,
     Where,
     In,
 
-    // Keywords (special statements)
-    Print,      // print statement (simplified syntax)
-
     // Keywords (modules and visibility)
     Use,
     Mod,
@@ -108,6 +109,14 @@ pub enum TokenKind {
     RawStringLiteral,
     CharLiteral,
 
+    // Literals (interpolated string parts, around the tokens of each hole)
+    /// `f"text{`, the text before the first hole of an interpolated string
+    InterpolatedStringStart,
+    /// `}text{`, the text between two holes
+    InterpolatedStringMiddle,
+    /// `}text"`, the text after the last hole
+    InterpolatedStringEnd,
+
     // Operators (arithmetic)
     Plus,       // +
     Minus,      // -
@@ -216,6 +225,8 @@ impl TokenKind {
                 | TokenKind::Mut
                 | TokenKind::Const
                 | TokenKind::Static
+                | TokenKind::Struct
+                | TokenKind::Enum
                 | TokenKind::Type
                 | TokenKind::Trait
                 | TokenKind::Impl
@@ -273,6 +284,19 @@ impl TokenKind {
         )
     }
 
+    /// Check if this token is trivia (whitespace or a comment)
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Whitespace
+                | TokenKind::Newline
+                | TokenKind::LineComment
+                | TokenKind::BlockComment
+                | TokenKind::DocCommentOuter
+                | TokenKind::DocCommentInner
+        )
+    }
+
     /// Check if this token is an operator
     pub fn is_operator(&self) -> bool {
         matches!(
@@ -340,6 +364,8 @@ impl TokenKind {
             TokenKind::Mut => Some("mut"),
             TokenKind::Const => Some("const"),
             TokenKind::Static => Some("static"),
+            TokenKind::Struct => Some("struct"),
+            TokenKind::Enum => Some("enum"),
             TokenKind::Type => Some("type"),
             TokenKind::Trait => Some("trait"),
             TokenKind::Impl => Some("impl"),
@@ -461,6 +487,10 @@ pub struct Token {
     pub lexeme: String,
     /// Source file
     pub file: String,
+    /// Source file ID (index into the source map)
+    pub file_id: u32,
+    /// Byte offset of the first character
+    pub offset: usize,
     /// Line number (1-indexed)
     pub line: usize,
     /// Column number (1-indexed)
@@ -477,6 +507,8 @@ impl Token {
             kind,
             lexeme,
             file,
+            file_id: 0,
+            offset: 0,
             line,
             column,
             len,
@@ -489,11 +521,26 @@ impl Token {
             kind: TokenKind::Eof,
             lexeme: String::new(),
             file,
+            file_id: 0,
+            offset: 0,
             line,
             column,
             len: 0,
         }
     }
+
+    /// Set the source location of the token (file ID, byte offset and byte length)
+    pub fn with_location(mut self, file_id: u32, offset: usize, len: usize) -> Self {
+        self.file_id = file_id;
+        self.offset = offset;
+        self.len = len;
+        self
+    }
+
+    /// Byte offset just past the last character
+    pub fn end(&self) -> usize {
+        self.offset + self.len
+    }
 }
 
 impl fmt::Display for Token {
@@ -524,6 +571,8 @@ pub fn export_token_catalog() -> serde_json::Result<String> {
         ("mut".to_string(), TokenKind::Mut),
         ("const".to_string(), TokenKind::Const),
         ("static".to_string(), TokenKind::Static),
+        ("struct".to_string(), TokenKind::Struct),
+        ("enum".to_string(), TokenKind::Enum),
         ("type".to_string(), TokenKind::Type),
         ("trait".to_string(), TokenKind::Trait),
         ("impl".to_string(), TokenKind::Impl),
@@ -0,0 +1,243 @@
+//! Call graph for MIR modules
+//!
+//! Functions are called by name (`Constant::String` callee operands), so the
+//! graph resolves each direct call against the names of the module's
+//! functions. Calls through values and calls to functions outside the module
+//! (runtime builtins) have no edge; the caller is marked as making unknown
+//! calls instead.
+//!
+//! Strongly connected components are computed with Tarjan's algorithm and
+//! listed bottom-up, callees before their callers, which is the order
+//! interprocedural passes want to visit functions in.
+
+use crate::mir::{Constant, FunctionId, Instruction, Operand};
+use crate::MirModule;
+use std::collections::{HashMap, HashSet};
+
+/// Call graph of a MIR module
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    /// Functions directly called by each function
+    callees: HashMap<FunctionId, Vec<FunctionId>>,
+    /// Functions directly calling each function
+    callers: HashMap<FunctionId, Vec<FunctionId>>,
+    /// Functions with calls the graph has no edge for
+    unknown_calls: HashSet<FunctionId>,
+    /// Strongly connected components, callees first
+    sccs: Vec<Vec<FunctionId>>,
+    /// Index of each function's component in `sccs`
+    scc_of: HashMap<FunctionId, usize>,
+}
+
+impl CallGraph {
+    /// Build the call graph of a module
+    pub fn build(module: &MirModule) -> Self {
+        let by_name: HashMap<&str, FunctionId> =
+            module.functions.values().map(|func| (func.name.as_str(), func.id)).collect();
+
+        let mut ids: Vec<FunctionId> = module.functions.keys().copied().collect();
+        ids.sort_unstable();
+
+        let mut graph = Self::default();
+        for &id in &ids {
+            let func = &module.functions[&id];
+            let mut callees = Vec::new();
+            for inst in func.blocks.values().flat_map(|block| &block.instructions) {
+                let Instruction::Call { func: callee, .. } = inst else {
+                    continue;
+                };
+                match direct_callee(callee).and_then(|name| by_name.get(name)) {
+                    Some(&callee) => callees.push(callee),
+                    None => {
+                        graph.unknown_calls.insert(id);
+                    }
+                }
+            }
+            callees.sort_unstable();
+            callees.dedup();
+            for &callee in &callees {
+                graph.callers.entry(callee).or_default().push(id);
+            }
+            graph.callees.insert(id, callees);
+        }
+
+        graph.compute_sccs(&ids);
+        graph
+    }
+
+    /// Functions called directly by `func`
+    pub fn callees(&self, func: FunctionId) -> &[FunctionId] {
+        self.callees.get(&func).map_or(&[], Vec::as_slice)
+    }
+
+    /// Functions calling `func` directly
+    pub fn callers(&self, func: FunctionId) -> &[FunctionId] {
+        self.callers.get(&func).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether `func` calls through a value or outside the module
+    pub fn has_unknown_calls(&self, func: FunctionId) -> bool {
+        self.unknown_calls.contains(&func)
+    }
+
+    /// Strongly connected components, each listed after those it calls into
+    pub fn sccs(&self) -> &[Vec<FunctionId>] {
+        &self.sccs
+    }
+
+    /// Component containing `func`
+    pub fn scc(&self, func: FunctionId) -> Option<&[FunctionId]> {
+        self.scc_of.get(&func).map(|&index| self.sccs[index].as_slice())
+    }
+
+    /// Whether `func` can call itself, directly or through other functions
+    pub fn is_recursive(&self, func: FunctionId) -> bool {
+        self.scc(func).is_some_and(|scc| scc.len() > 1) || self.callees(func).contains(&func)
+    }
+
+    /// All functions, callees before their callers
+    pub fn bottom_up(&self) -> Vec<FunctionId> {
+        self.sccs.iter().flatten().copied().collect()
+    }
+
+    /// Tarjan's algorithm; components are completed callees first
+    fn compute_sccs(&mut self, ids: &[FunctionId]) {
+        let mut tarjan = Tarjan {
+            graph: &self.callees,
+            next_index: 0,
+            index: HashMap::new(),
+            lowlink: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            sccs: Vec::new(),
+        };
+        for &id in ids {
+            if !tarjan.index.contains_key(&id) {
+                tarjan.visit(id);
+            }
+        }
+
+        self.sccs = tarjan.sccs;
+        for (index, scc) in self.sccs.iter_mut().enumerate() {
+            scc.sort_unstable();
+            for &func in scc.iter() {
+                self.scc_of.insert(func, index);
+            }
+        }
+    }
+}
+
+/// Name of the function a call operand refers to directly
+pub fn direct_callee(callee: &Operand) -> Option<&str> {
+    match callee {
+        Operand::Const(Constant::String(name)) => Some(name),
+        _ => None,
+    }
+}
+
+/// State of a Tarjan SCC traversal
+struct Tarjan<'a> {
+    graph: &'a HashMap<FunctionId, Vec<FunctionId>>,
+    next_index: usize,
+    index: HashMap<FunctionId, usize>,
+    lowlink: HashMap<FunctionId, usize>,
+    stack: Vec<FunctionId>,
+    on_stack: HashSet<FunctionId>,
+    sccs: Vec<Vec<FunctionId>>,
+}
+
+impl Tarjan<'_> {
+    fn visit(&mut self, func: FunctionId) {
+        self.index.insert(func, self.next_index);
+        self.lowlink.insert(func, self.next_index);
+        self.next_index += 1;
+        self.stack.push(func);
+        self.on_stack.insert(func);
+
+        let graph = self.graph;
+        for &callee in graph.get(&func).into_iter().flatten() {
+            if !self.index.contains_key(&callee) {
+                self.visit(callee);
+                let low = self.lowlink[&func].min(self.lowlink[&callee]);
+                self.lowlink.insert(func, low);
+            } else if self.on_stack.contains(&callee) {
+                let low = self.lowlink[&func].min(self.index[&callee]);
+                self.lowlink.insert(func, low);
+            }
+        }
+
+        // `func` is the root of a component: pop it off the stack
+        if self.lowlink[&func] == self.index[&func] {
+            let mut scc = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack.remove(&member);
+                scc.push(member);
+                if member == func {
+                    break;
+                }
+            }
+            self.sccs.push(scc);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mir::{BasicBlock, Function, Span};
+    use aurora_types::{EffectSet, Type};
+
+    /// A function whose entry block calls each of `calls`
+    fn function(id: FunctionId, name: &str, calls: &[Operand]) -> Function {
+        let mut func = Function::new(id, name.to_string(), Type::Unit, EffectSet::PURE);
+        let mut block = BasicBlock::new(0);
+        for callee in calls {
+            block.push(Instruction::Call {
+                dest: None,
+                func: callee.clone(),
+                args: Vec::new(),
+                effects: EffectSet::PURE,
+                span: Span::dummy(),
+            });
+        }
+        block.push(Instruction::Return { value: None, span: Span::dummy() });
+        func.add_block(block);
+        func
+    }
+
+    fn named(name: &str) -> Operand {
+        Operand::Const(Constant::String(name.to_string()))
+    }
+
+    #[test]
+    fn test_sccs_are_bottom_up() {
+        let mut module = MirModule::new();
+        module.add_function(function(0, "main", &[named("even"), named("helper")]));
+        module.add_function(function(1, "even", &[named("odd")]));
+        module.add_function(function(2, "odd", &[named("even"), named("helper")]));
+        module.add_function(function(3, "helper", &[]));
+
+        let graph = CallGraph::build(&module);
+        assert_eq!(graph.sccs(), &[vec![3], vec![1, 2], vec![0]]);
+        assert_eq!(graph.bottom_up(), vec![3, 1, 2, 0]);
+        assert_eq!(graph.callers(3), &[0, 2]);
+        assert!(graph.is_recursive(1) && graph.is_recursive(2));
+        assert!(!graph.is_recursive(0) && !graph.is_recursive(3));
+    }
+
+    #[test]
+    fn test_self_recursion_and_unknown_calls() {
+        let mut module = MirModule::new();
+        module.add_function(function(0, "fact", &[named("fact")]));
+        module.add_function(function(1, "main", &[named("fact"), named("println")]));
+        module.add_function(function(2, "apply", &[Operand::Value(0)]));
+
+        let graph = CallGraph::build(&module);
+        assert!(graph.is_recursive(0));
+        assert_eq!(graph.scc(0), Some(&[0][..]));
+        assert!(!graph.has_unknown_calls(0));
+        assert!(graph.has_unknown_calls(1));
+        assert!(graph.has_unknown_calls(2));
+        assert_eq!(graph.callees(1), &[0]);
+    }
+}
@@ -40,7 +40,9 @@ impl MirDumper {
         self.indent += 2;
 
         // Dump blocks
-        for block_id in 0..func.blocks.len() as BlockId {
+        let mut block_ids: Vec<BlockId> = func.blocks.keys().copied().collect();
+        block_ids.sort_unstable();
+        for block_id in block_ids {
             if let Some(block) = func.block(block_id) {
                 self.dump_block(&mut output, block, func);
             }
@@ -0,0 +1,303 @@
+//! Function inlining
+//!
+//! Direct calls to small functions are replaced by a copy of the callee's
+//! body. The block holding the call is split after it: the part before the
+//! call assigns the arguments to the copied parameters and jumps to the
+//! copied entry block, and every copied `return` jumps to the continuation
+//! block, where a phi joins the returned values into the call's result.
+//!
+//! Functions are visited bottom-up through the call graph, so callees have
+//! already had their own calls inlined. Calls within a strongly connected
+//! component, and calls to recursive functions, are never inlined.
+//!
+//! Whether a call is inlined depends on the callee's cost, which is its
+//! instruction count weighted by [`INSTRUCTION_COST`], against a threshold
+//! in the same units as
+//! `aurora_optimizer::OptimizationStrategy::inline_threshold`.
+
+use crate::callgraph::{direct_callee, CallGraph};
+use crate::mir::*;
+use crate::pass_manager::ModulePass;
+use crate::MirModule;
+use std::collections::HashMap;
+
+/// Cost of one MIR instruction in inline threshold units
+pub const INSTRUCTION_COST: usize = 5;
+
+/// Inlining pass
+pub struct Inlining {
+    /// Largest callee cost that is still inlined
+    threshold: usize,
+    /// Number of call sites inlined so far
+    inlined: usize,
+}
+
+impl Inlining {
+    /// Create an inlining pass for callees of up to `max_instructions` instructions
+    pub fn new(max_instructions: usize) -> Self {
+        Self::with_threshold(max_instructions * INSTRUCTION_COST)
+    }
+
+    /// Create an inlining pass with a cost threshold
+    pub fn with_threshold(threshold: usize) -> Self {
+        Self { threshold, inlined: 0 }
+    }
+
+    /// Largest callee cost that is still inlined
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Number of call sites inlined so far
+    pub fn inlined(&self) -> usize {
+        self.inlined
+    }
+
+    /// Whether calls from `caller` to `callee` may be inlined
+    fn should_inline(&self, graph: &CallGraph, caller: FunctionId, callee: &Function) -> bool {
+        let same_scc = graph.scc(caller).is_some_and(|scc| scc.contains(&callee.id));
+        !same_scc
+            && !graph.is_recursive(callee.id)
+            && callee.block(callee.entry).is_some_and(|entry| entry.predecessors.is_empty())
+            && cost(callee) <= self.threshold
+    }
+}
+
+impl Default for Inlining {
+    fn default() -> Self {
+        Self::new(20)
+    }
+}
+
+impl ModulePass for Inlining {
+    fn run(&mut self, module: &mut MirModule) -> bool {
+        let graph = CallGraph::build(module);
+        let by_name: HashMap<String, FunctionId> =
+            module.functions.values().map(|func| (func.name.clone(), func.id)).collect();
+        let mut ids = IdAllocator::new(module);
+        let mut changed = false;
+
+        for caller_id in graph.bottom_up() {
+            let Some(mut caller) = module.functions.remove(&caller_id) else {
+                continue;
+            };
+
+            let mut inlined_here = false;
+            // Each inlined call is replaced by calls the callee kept, which are not eligible
+            while let Some((block, index, callee)) = self.next_site(&caller, &graph, &by_name, module) {
+                inline_call(&mut caller, block, index, callee, &mut ids);
+                self.inlined += 1;
+                inlined_here = true;
+            }
+            if inlined_here {
+                caller.remove_unreachable_blocks();
+                changed = true;
+            }
+
+            module.functions.insert(caller_id, caller);
+        }
+
+        changed
+    }
+
+    fn name(&self) -> &str {
+        "inlining"
+    }
+}
+
+impl Inlining {
+    /// First call in `caller` that should be inlined, with the function it calls
+    fn next_site<'m>(
+        &self,
+        caller: &Function,
+        graph: &CallGraph,
+        by_name: &HashMap<String, FunctionId>,
+        module: &'m MirModule,
+    ) -> Option<(BlockId, usize, &'m Function)> {
+        let mut blocks: Vec<&BasicBlock> = caller.blocks.values().collect();
+        blocks.sort_by_key(|block| block.id);
+
+        for block in blocks {
+            for (index, inst) in block.instructions.iter().enumerate() {
+                let Instruction::Call { func, args, .. } = inst else {
+                    continue;
+                };
+                let Some(callee) = direct_callee(func)
+                    .and_then(|name| by_name.get(name))
+                    .and_then(|id| module.functions.get(id))
+                else {
+                    continue;
+                };
+                if callee.params.len() == args.len() && self.should_inline(graph, caller.id, callee) {
+                    return Some((block.id, index, callee));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Cost of inlining a function
+fn cost(func: &Function) -> usize {
+    let instructions: usize = func.blocks.values().map(|block| block.instructions.len()).sum();
+    instructions * INSTRUCTION_COST
+}
+
+/// Source of value and block IDs unused anywhere in a module
+struct IdAllocator {
+    next_value: ValueId,
+    next_block: BlockId,
+}
+
+impl IdAllocator {
+    fn new(module: &MirModule) -> Self {
+        let mut next_value = 0;
+        let mut next_block = 0;
+        for func in module.functions.values() {
+            let values = func.values.keys().chain(&func.params);
+            next_value = values.map(|&id| id + 1).fold(next_value, ValueId::max);
+            next_block = func.blocks.keys().map(|&id| id + 1).fold(next_block, BlockId::max);
+        }
+        Self { next_value, next_block }
+    }
+
+    fn value(&mut self) -> ValueId {
+        self.next_value += 1;
+        self.next_value - 1
+    }
+
+    fn block(&mut self) -> BlockId {
+        self.next_block += 1;
+        self.next_block - 1
+    }
+}
+
+/// Replace the call at `index` in `block` of `caller` with a copy of `callee`
+fn inline_call(caller: &mut Function, block: BlockId, index: usize, callee: &Function, ids: &mut IdAllocator) {
+    let continuation = ids.block();
+
+    // Fresh names for everything the callee defines
+    let mut values: Vec<ValueId> = callee.values.keys().chain(&callee.params).copied().collect();
+    values.sort_unstable();
+    values.dedup();
+    let mut value_map = HashMap::new();
+    for old in values {
+        let new = ids.value();
+        value_map.insert(old, new);
+        if let Some(value) = callee.value(old) {
+            caller.add_value(Value { id: new, ..value.clone() });
+        }
+    }
+    let mut blocks: Vec<BlockId> = callee.blocks.keys().copied().collect();
+    blocks.sort_unstable();
+    let block_map: HashMap<BlockId, BlockId> = blocks.into_iter().map(|id| (id, ids.block())).collect();
+
+    // Split the call's block; the continuation takes over its outgoing edges
+    let call_block = caller.block_mut(block).expect("call site block");
+    let mut tail = call_block.instructions.split_off(index);
+    let Instruction::Call { dest, args, span, .. } = tail.remove(0) else {
+        unreachable!("call site is not a call");
+    };
+    let successors = std::mem::take(&mut call_block.successors);
+    for (&param, arg) in callee.params.iter().zip(args) {
+        call_block.push(Instruction::Assign {
+            dest: value_map[&param],
+            value: arg,
+            span,
+        });
+    }
+    let entry = block_map[&callee.entry];
+    call_block.push(Instruction::Jump { target: entry, span });
+    call_block.successors.push(entry);
+    for succ in &successors {
+        if let Some(succ) = caller.block_mut(*succ) {
+            rename_predecessor(succ, block, continuation);
+        }
+    }
+
+    // Copy the callee's blocks, turning returns into jumps to the continuation
+    let mut returns = Vec::new();
+    for callee_block in callee.blocks.values() {
+        let id = block_map[&callee_block.id];
+        let mut copy = BasicBlock::new(id);
+        copy.predecessors = callee_block.predecessors.iter().map(|pred| block_map[pred]).collect();
+        copy.successors = callee_block.successors.iter().map(|succ| block_map[succ]).collect();
+        if callee_block.id == callee.entry {
+            copy.predecessors.push(block);
+        }
+        for inst in &callee_block.instructions {
+            let mut inst = inst.clone();
+            rename(&mut inst, &value_map, &block_map);
+            if let Instruction::Return { value, span } = inst {
+                returns.push((id, value));
+                copy.push(Instruction::Jump { target: continuation, span });
+                copy.successors.push(continuation);
+            } else {
+                copy.push(inst);
+            }
+        }
+        caller.add_block(copy);
+    }
+
+    // Join the returned values into the call's result
+    let mut join = BasicBlock::new(continuation);
+    join.predecessors = returns.iter().map(|(pred, _)| *pred).collect();
+    join.successors = successors;
+    if let Some(dest) = dest {
+        let inputs: Vec<(BlockId, Operand)> = returns
+            .into_iter()
+            .map(|(pred, value)| (pred, value.unwrap_or(Operand::Const(Constant::Unit))))
+            .collect();
+        match inputs.as_slice() {
+            [] => {}
+            [(_, value)] => join.push(Instruction::Assign { dest, value: value.clone(), span }),
+            _ => join.push(Instruction::Phi { dest, inputs, span }),
+        }
+    }
+    join.instructions.extend(tail);
+    caller.add_block(join);
+    caller.effects = caller.effects.union(callee.effects);
+}
+
+/// Rename the values and blocks an instruction refers to
+fn rename(inst: &mut Instruction, values: &HashMap<ValueId, ValueId>, blocks: &HashMap<BlockId, BlockId>) {
+    if let Some(dest) = inst.dest_mut() {
+        *dest = values.get(dest).copied().unwrap_or(*dest);
+    }
+    for operand in inst.operands_mut() {
+        if let Operand::Value(value) = operand {
+            *value = values.get(value).copied().unwrap_or(*value);
+        }
+    }
+    match inst {
+        Instruction::Branch { then_block, else_block, .. } => {
+            *then_block = blocks[then_block];
+            *else_block = blocks[else_block];
+        }
+        Instruction::Jump { target, .. } => *target = blocks[target],
+        Instruction::Phi { inputs, .. } => {
+            for (pred, _) in inputs {
+                *pred = blocks[pred];
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Make `block` refer to `new` wherever it refers to its predecessor `old`
+fn rename_predecessor(block: &mut BasicBlock, old: BlockId, new: BlockId) {
+    for pred in &mut block.predecessors {
+        if *pred == old {
+            *pred = new;
+        }
+    }
+    for inst in &mut block.instructions {
+        if let Instruction::Phi { inputs, .. } = inst {
+            for (pred, _) in inputs {
+                if *pred == old {
+                    *pred = new;
+                }
+            }
+        }
+    }
+}
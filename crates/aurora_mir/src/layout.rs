@@ -0,0 +1,403 @@
+//! Type layouts for MIR aggregates
+//!
+//! Structs, tuples and arrays live in memory and MIR values of those types
+//! hold their address. Scalars occupy one 8-byte slot, matching the 64-bit
+//! registers and moves the backend uses for every value, and aggregate
+//! fields are laid out in declaration order. Enums (including the builtin
+//! `Option` and `Result`) start with a tag word holding the variant index,
+//! followed by the fields of the active variant.
+//!
+//! This is not C's layout: in C, `i32`, `f32`, `bool`, `char` and the
+//! smaller integers take their own size and alignment. A struct matches the
+//! C struct with the same fields only when every scalar in it is 8 bytes
+//! wide (`i64`, `u64`, `f64`, `str` and pointers).
+
+use aurora_types::{EnumDef, PrimitiveType, StructDef, Type, TypeMap, TypeVarId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Size of a scalar slot in bytes
+pub const WORD_SIZE: u64 = 8;
+
+/// Size and alignment of a type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Layout {
+    /// Size in bytes
+    pub size: u64,
+    /// Alignment in bytes
+    pub align: u64,
+}
+
+impl Layout {
+    /// Layout of a single scalar slot
+    pub fn word() -> Self {
+        Self {
+            size: WORD_SIZE,
+            align: WORD_SIZE,
+        }
+    }
+
+    /// Layout of a zero-sized type
+    pub fn zero() -> Self {
+        Self { size: 0, align: 1 }
+    }
+}
+
+/// Location of a field within an aggregate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldLayout {
+    /// Field name (positional fields are named `0`, `1`, ...)
+    pub name: String,
+    /// Field type
+    pub ty: Type,
+    /// Byte offset from the start of the aggregate
+    pub offset: u64,
+}
+
+/// Tag and payload layout of an enum variant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VariantLayout {
+    /// Variant name
+    pub name: String,
+    /// Value stored in the tag word
+    pub tag: i64,
+    /// Payload fields, placed after the tag word
+    pub fields: Vec<FieldLayout>,
+}
+
+/// Struct and enum declarations needed to lay out named types
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LayoutTable {
+    /// Struct declarations keyed by name
+    structs: HashMap<String, StructDef>,
+    /// Enum declarations keyed by name
+    enums: HashMap<String, EnumDef>,
+}
+
+impl LayoutTable {
+    /// Create an empty layout table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a layout table from the struct and enum declarations of a type map
+    pub fn from_type_map(type_map: &TypeMap) -> Self {
+        let mut table = Self::new();
+        for (name, def) in type_map.structs() {
+            table.add_struct(name.clone(), def.clone());
+        }
+        for (name, def) in type_map.enums() {
+            table.add_enum(name.clone(), def.clone());
+        }
+        table
+    }
+
+    /// Register a struct declaration
+    pub fn add_struct(&mut self, name: String, def: StructDef) {
+        self.structs.insert(name, def);
+    }
+
+    /// Register an enum declaration
+    pub fn add_enum(&mut self, name: String, def: EnumDef) {
+        self.enums.insert(name, def);
+    }
+
+    /// Layout of a type
+    pub fn layout_of(&self, ty: &Type) -> Layout {
+        match ty {
+            Type::Unit | Type::Never => Layout::zero(),
+            Type::Tuple(elems) => self.aggregate(elems.iter()).1,
+            Type::Array { elem, size: Some(len) } => {
+                let elem = self.layout_of(elem);
+                Layout {
+                    size: elem.size * *len as u64,
+                    align: elem.align,
+                }
+            }
+            Type::Named { name, args } => match self.struct_fields(name, args) {
+                Some(fields) => self.aggregate(fields.iter().map(|(_, ty)| ty)).1,
+                None => self.enum_layout(ty).unwrap_or_else(Layout::word),
+            },
+            Type::Option(_) | Type::Result { .. } => self.enum_layout(ty).unwrap_or_else(Layout::word),
+            _ => Layout::word(),
+        }
+    }
+
+    /// Size of a type in bytes
+    pub fn size_of(&self, ty: &Type) -> u64 {
+        self.layout_of(ty).size
+    }
+
+    /// Whether values of a type are held in memory and passed by address
+    pub fn is_aggregate(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Tuple(elems) => !elems.is_empty(),
+            Type::Array { size, .. } => size.is_some(),
+            Type::Named { name, .. } => self.structs.contains_key(name) || self.enums.contains_key(name),
+            Type::Option(_) | Type::Result { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether a type is an enum
+    pub fn is_enum(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Named { name, .. } => self.enums.contains_key(name),
+            Type::Option(_) | Type::Result { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Variants of an enum type in declaration order
+    pub fn variants(&self, ty: &Type) -> Option<Vec<VariantLayout>> {
+        let variants: Vec<(String, Vec<(String, Type)>)> = match ty {
+            Type::Option(inner) => vec![
+                ("None".to_string(), vec![]),
+                ("Some".to_string(), vec![("0".to_string(), (**inner).clone())]),
+            ],
+            Type::Result { ok, err } => vec![
+                ("Ok".to_string(), vec![("0".to_string(), (**ok).clone())]),
+                ("Err".to_string(), vec![("0".to_string(), (**err).clone())]),
+            ],
+            Type::Named { name, args } => {
+                let def = self.enums.get(name)?;
+                let subst: HashMap<TypeVarId, Type> =
+                    def.params.iter().copied().zip(args.iter().cloned()).collect();
+                def.variants
+                    .iter()
+                    .map(|variant| {
+                        let fields = variant
+                            .fields
+                            .iter()
+                            .map(|(name, ty)| (name.clone(), ty.substitute(&subst)))
+                            .collect();
+                        (variant.name.clone(), fields)
+                    })
+                    .collect()
+            }
+            _ => return None,
+        };
+
+        let tag = Type::Primitive(PrimitiveType::I64);
+        Some(
+            variants
+                .into_iter()
+                .enumerate()
+                .map(|(index, (name, fields))| {
+                    // Lay the payload out as if the tag word were its first field
+                    let types = std::iter::once(&tag).chain(fields.iter().map(|(_, ty)| ty));
+                    let (offsets, _) = self.aggregate(types);
+                    let fields = fields
+                        .into_iter()
+                        .zip(offsets.into_iter().skip(1))
+                        .map(|((name, ty), offset)| FieldLayout { name, ty, offset })
+                        .collect();
+                    VariantLayout {
+                        name,
+                        tag: index as i64,
+                        fields,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// A single variant of an enum type
+    pub fn variant(&self, ty: &Type, name: &str) -> Option<VariantLayout> {
+        self.variants(ty)?.into_iter().find(|variant| variant.name == name)
+    }
+
+    /// Fields of a struct or tuple type with their offsets
+    pub fn fields(&self, ty: &Type) -> Option<Vec<FieldLayout>> {
+        let fields: Vec<(String, Type)> = match ty {
+            Type::Tuple(elems) => elems
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| (i.to_string(), ty.clone()))
+                .collect(),
+            Type::Named { name, args } => self.struct_fields(name, args)?,
+            _ => return None,
+        };
+
+        let (offsets, _) = self.aggregate(fields.iter().map(|(_, ty)| ty));
+        Some(
+            fields
+                .into_iter()
+                .zip(offsets)
+                .map(|((name, ty), offset)| FieldLayout { name, ty, offset })
+                .collect(),
+        )
+    }
+
+    /// A single field of a struct or tuple type
+    pub fn field(&self, ty: &Type, name: &str) -> Option<FieldLayout> {
+        self.fields(ty)?.into_iter().find(|field| field.name == name)
+    }
+
+    /// Field types of a struct with its generic arguments substituted
+    fn struct_fields(&self, name: &str, args: &[Type]) -> Option<Vec<(String, Type)>> {
+        let def = self.structs.get(name)?;
+        let subst: HashMap<TypeVarId, Type> =
+            def.params.iter().copied().zip(args.iter().cloned()).collect();
+        Some(
+            def.fields
+                .iter()
+                .map(|(name, ty)| (name.clone(), ty.substitute(&subst)))
+                .collect(),
+        )
+    }
+
+    /// Layout of an enum: the tag word followed by the largest payload
+    fn enum_layout(&self, ty: &Type) -> Option<Layout> {
+        let mut layout = Layout::word();
+        for variant in self.variants(ty)? {
+            for field in &variant.fields {
+                let field_layout = self.layout_of(&field.ty);
+                layout.size = layout.size.max(field.offset + field_layout.size);
+                layout.align = layout.align.max(field_layout.align);
+            }
+        }
+        layout.size = align_to(layout.size, layout.align);
+        Some(layout)
+    }
+
+    /// Lay fields out in order, returning their offsets and the overall layout
+    fn aggregate<'a>(&self, fields: impl Iterator<Item = &'a Type>) -> (Vec<u64>, Layout) {
+        let mut offsets = Vec::new();
+        let mut size = 0;
+        let mut align = 1;
+
+        for ty in fields {
+            let field = self.layout_of(ty);
+            size = align_to(size, field.align);
+            offsets.push(size);
+            size += field.size;
+            align = align.max(field.align);
+        }
+
+        (offsets, Layout { size: align_to(size, align), align })
+    }
+}
+
+/// Round `offset` up to a multiple of `align`
+fn align_to(offset: u64, align: u64) -> u64 {
+    offset.div_ceil(align) * align
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aurora_types::EnumVariant;
+
+    fn i64_ty() -> Type {
+        Type::Primitive(PrimitiveType::I64)
+    }
+
+    fn named(name: &str, args: Vec<Type>) -> Type {
+        Type::Named {
+            name: name.to_string(),
+            args,
+        }
+    }
+
+    fn table() -> LayoutTable {
+        let mut table = LayoutTable::new();
+        table.add_struct(
+            "Point".to_string(),
+            StructDef {
+                params: vec![],
+                fields: vec![("x".to_string(), i64_ty()), ("y".to_string(), i64_ty())],
+            },
+        );
+        // struct Wrapper<T> { tag: bool, inner: T }
+        table.add_struct(
+            "Wrapper".to_string(),
+            StructDef {
+                params: vec![7],
+                fields: vec![
+                    ("tag".to_string(), Type::Primitive(PrimitiveType::Bool)),
+                    ("inner".to_string(), Type::Var(7)),
+                ],
+            },
+        );
+        table
+    }
+
+    #[test]
+    fn test_struct_layout() {
+        let table = table();
+        let point = named("Point", vec![]);
+
+        assert_eq!(table.layout_of(&point), Layout { size: 16, align: 8 });
+        assert_eq!(table.field(&point, "y").map(|f| f.offset), Some(8));
+        assert_eq!(table.field(&point, "z"), None);
+        assert!(table.is_aggregate(&point));
+        assert!(!table.is_aggregate(&i64_ty()));
+    }
+
+    #[test]
+    fn test_nested_and_generic_layout() {
+        let table = table();
+        let wrapped = named("Wrapper", vec![named("Point", vec![])]);
+
+        let inner = table.field(&wrapped, "inner").unwrap();
+        assert_eq!(inner.offset, 8);
+        assert_eq!(inner.ty, named("Point", vec![]));
+        assert_eq!(table.size_of(&wrapped), 24);
+    }
+
+    #[test]
+    fn test_enum_layout() {
+        let mut table = table();
+        // enum Shape { Circle(i64), Rect { w: i64, h: i64 }, Empty }
+        table.add_enum(
+            "Shape".to_string(),
+            EnumDef {
+                params: vec![],
+                variants: vec![
+                    EnumVariant {
+                        name: "Circle".to_string(),
+                        fields: vec![("0".to_string(), i64_ty())],
+                    },
+                    EnumVariant {
+                        name: "Rect".to_string(),
+                        fields: vec![("w".to_string(), i64_ty()), ("h".to_string(), i64_ty())],
+                    },
+                    EnumVariant {
+                        name: "Empty".to_string(),
+                        fields: vec![],
+                    },
+                ],
+            },
+        );
+        let shape = named("Shape", vec![]);
+
+        assert_eq!(table.size_of(&shape), 24);
+        assert!(table.is_aggregate(&shape) && table.is_enum(&shape));
+        let rect = table.variant(&shape, "Rect").unwrap();
+        assert_eq!(rect.tag, 1);
+        assert_eq!(rect.fields.iter().map(|f| f.offset).collect::<Vec<_>>(), vec![8, 16]);
+        assert_eq!(table.variant(&shape, "Empty").map(|v| v.tag), Some(2));
+
+        let option = Type::Option(Box::new(named("Point", vec![])));
+        assert_eq!(table.size_of(&option), 24);
+        assert_eq!(table.variant(&option, "Some").map(|v| v.tag), Some(1));
+        assert!(!table.is_enum(&named("Point", vec![])));
+    }
+
+    #[test]
+    fn test_tuple_and_array_layout() {
+        let table = table();
+        let tuple = Type::Tuple(vec![i64_ty(), Type::Unit, named("Point", vec![])]);
+        let offsets: Vec<u64> = table.fields(&tuple).unwrap().iter().map(|f| f.offset).collect();
+        assert_eq!(offsets, vec![0, 8, 8]);
+        assert_eq!(table.size_of(&tuple), 24);
+
+        let array = Type::Array {
+            elem: Box::new(tuple),
+            size: Some(3),
+        };
+        assert_eq!(table.size_of(&array), 72);
+        assert!(!table.is_aggregate(&Type::Unit));
+    }
+}
@@ -5,32 +5,54 @@
 //! - Control Flow Graph (CFG)
 //! - Dominance tree computation
 //! - MIR lowering from typed AST
+//! - SSA construction for local variables
+//! - Type layouts for aggregates
 //! - Optimization passes
+//! - Promotion of stack slots to SSA values (SROA and mem2reg)
+//! - Call graph and module-level pass manager
+//! - Function inlining
 //! - MIR dumps and serialization
 
+pub mod callgraph;
 pub mod cfg;
 pub mod dump;
+pub mod inline;
+pub mod layout;
 pub mod lower;
 pub mod lower_impl;
+pub mod lower_loop;
+pub mod lower_match;
+pub mod mem2reg;
 pub mod mir;
 pub mod opt;
+pub mod pass_manager;
+pub mod sroa;
+pub mod ssa;
 
+pub use callgraph::CallGraph;
 pub use cfg::{DominatorTree, Loop, CFG};
 pub use dump::MirDumper;
+pub use layout::{FieldLayout, Layout, LayoutTable, VariantLayout};
 pub use lower::MirBuilder;
+pub use mem2reg::Mem2Reg;
 pub use mir::*;
 pub use opt::*;
+pub use pass_manager::{ModulePass, PassManager, PassStats};
 
 // Pipeline integration stubs
 use aurora_ast::Ast;
+use aurora_types::TypeMap;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::collections::HashMap;
 
 /// MIR Module (collection of functions)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MirModule {
     /// Functions in this module
     pub functions: HashMap<FunctionId, Function>,
+    /// Layouts of the aggregate types used by the functions
+    pub layouts: LayoutTable,
 }
 
 impl MirModule {
@@ -38,6 +60,7 @@ impl MirModule {
     pub fn new() -> Self {
         Self {
             functions: HashMap::new(),
+            layouts: LayoutTable::new(),
         }
     }
 
@@ -79,15 +102,22 @@ impl Default for MirModule {
 }
 
 /// Lower AST to MIR
+///
+/// Without a type map every value is treated as a 64-bit integer; use
+/// [`lower_typed_ast_to_mir`] to lower aggregates.
 pub fn lower_ast_to_mir<D: Send + Sync + 'static>(
     ast: Ast,
     diagnostics: Arc<D>,
 ) -> MirModule {
-    use aurora_types::TypeMap;
-
-    // Create type map (would come from type checker in real implementation)
-    let type_map = TypeMap::new();
+    lower_typed_ast_to_mir(ast, TypeMap::new(), diagnostics)
+}
 
+/// Lower a type-checked AST to MIR using the checker's type map
+pub fn lower_typed_ast_to_mir<D: Send + Sync + 'static>(
+    ast: Ast,
+    type_map: TypeMap,
+    diagnostics: Arc<D>,
+) -> MirModule {
     // Create lowering context
     let mut ctx = lower::LoweringContext::new(diagnostics, type_map);
 
@@ -95,18 +125,41 @@ pub fn lower_ast_to_mir<D: Send + Sync + 'static>(
     ctx.lower(ast)
 }
 
-/// Optimize MIR module
-pub fn optimize(mut mir: MirModule, opt_level: u8) -> MirModule {
-    // Create optimization pipeline
-    let level = opt::OptLevel::from_u8(opt_level);
-    let mut pipeline = opt::OptPipeline::new(level);
+/// Lower the items of a type-checked AST whose key `filter` accepts
+///
+/// Keys are those of [`lowered_items`]. Function IDs follow item order
+/// whether or not an item is lowered, so functions lowered by an earlier
+/// call can be added to the module in place of the skipped items.
+pub fn lower_typed_items_to_mir<D: Send + Sync + 'static>(
+    ast: Ast,
+    type_map: TypeMap,
+    diagnostics: Arc<D>,
+    filter: impl FnMut(&str) -> bool,
+) -> MirModule {
+    lower::LoweringContext::new(diagnostics, type_map).lower_items(ast, filter)
+}
 
-    // Run optimization on each function
-    for func in mir.functions.values_mut() {
-        pipeline.run(func);
-    }
+/// The items lowered to MIR functions, in function ID order, keyed by the
+/// name the type checker records their signature under (`Type::method` for
+/// methods), with their declaration spans
+pub fn lowered_items(ast: &Ast) -> Vec<(String, aurora_ast::Span)> {
+    lower_impl::lowered_items(ast)
+        .into_iter()
+        .map(|(key, func_decl)| (key, func_decl.span))
+        .collect()
+}
+
+/// Optimize MIR module
+pub fn optimize(mir: MirModule, opt_level: u8) -> MirModule {
+    optimize_with_stats(mir, opt_level).0
+}
 
-    mir
+/// Optimize MIR module, returning the pass manager with its statistics
+pub fn optimize_with_stats(mut mir: MirModule, opt_level: u8) -> (MirModule, PassManager) {
+    let level = opt::OptLevel::from_u8(opt_level);
+    let mut manager = PassManager::for_level(level);
+    manager.run(&mut mir);
+    (mir, manager)
 }
 
 #[cfg(test)]
@@ -157,6 +210,26 @@ mod tests {
         assert_eq!(mir.function_count(), 0);
     }
 
+    #[test]
+    fn test_lower_selected_items_keeps_function_ids() {
+        use aurora_parser::Parser;
+        use aurora_types::TypeChecker;
+
+        let source = "struct P { x: i64 }\n\
+                      impl P { fn get(&self) -> i64 { self.x } }\n\
+                      fn main() -> i64 { let p = P { x: 1 }; p.get() }";
+        let ast = Parser::new(source, "test.ax".to_string()).unwrap().parse();
+        let mut checker = TypeChecker::new(Arc::new(()));
+        let ast = checker.check(ast);
+
+        let items: Vec<String> = lowered_items(&ast).into_iter().map(|(key, _)| key).collect();
+        assert_eq!(items, ["P::get", "main"]);
+
+        let mir = lower_typed_items_to_mir(ast, checker.type_map().clone(), Arc::new(()), |key| key == "main");
+        assert_eq!(mir.function_count(), 1);
+        assert_eq!(mir.get_function(1).unwrap().name, "main");
+    }
+
     #[test]
     fn test_optimize_level_0() {
         let module = MirModule::new();
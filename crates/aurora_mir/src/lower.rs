@@ -2,10 +2,13 @@
 //!
 //! This module lowers the typed AST into MIR (SSA form).
 
+use crate::layout::LayoutTable;
+use crate::lower_loop::LoopScope;
 use crate::mir::*;
+use crate::ssa::VarId;
 use aurora_ast::{Ast, ExprId, ExprKind, ItemKind, StmtKind};
 use aurora_types::{EffectSet, Type, TypeMap, PrimitiveType};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 /// MIR Builder - constructs MIR from AST
@@ -15,11 +18,23 @@ pub struct MirBuilder {
     /// Next block ID
     next_block: BlockId,
     /// Current function being built
-    current_func: Option<Function>,
-    /// Current block being built
-    current_block: Option<BlockId>,
-    /// Variable map (name -> ValueId)
-    vars: HashMap<String, ValueId>,
+    pub(crate) current_func: Option<Function>,
+    /// Current block being built, `None` in unreachable code
+    pub(crate) current_block: Option<BlockId>,
+    /// Variables in scope (name -> variable)
+    pub(crate) vars: HashMap<String, VarId>,
+    /// Type and declaration span of each variable
+    pub(crate) var_decls: Vec<(Type, Span)>,
+    /// Value of each variable at the end of each block
+    pub(crate) defs: HashMap<(VarId, BlockId), ValueId>,
+    /// Loop headers that may still gain predecessors
+    pub(crate) unsealed: HashSet<BlockId>,
+    /// Phis in unsealed blocks waiting for their inputs
+    pub(crate) incomplete_phis: HashMap<BlockId, Vec<(VarId, ValueId)>>,
+    /// Trivial phis removed, and the value each was replaced by
+    pub(crate) replaced: HashMap<ValueId, ValueId>,
+    /// Index into the function's debug variables of each named variable
+    pub(crate) var_debug: HashMap<VarId, usize>,
 }
 
 impl MirBuilder {
@@ -31,6 +46,12 @@ impl MirBuilder {
             current_func: None,
             current_block: None,
             vars: HashMap::new(),
+            var_decls: Vec::new(),
+            defs: HashMap::new(),
+            unsealed: HashSet::new(),
+            incomplete_phis: HashMap::new(),
+            replaced: HashMap::new(),
+            var_debug: HashMap::new(),
         }
     }
 
@@ -52,11 +73,24 @@ impl MirBuilder {
         self.current_func = Some(func);
         self.current_block = Some(entry_block);
         self.vars.clear();
+        self.var_decls.clear();
+        self.defs.clear();
+        self.unsealed.clear();
+        self.incomplete_phis.clear();
+        self.replaced.clear();
+        self.var_debug.clear();
     }
 
     /// Finish building current function
     pub fn finish_function(&mut self) -> Option<Function> {
-        self.current_func.take()
+        let mut func = self.current_func.take()?;
+        for var in &mut func.debug_vars {
+            for value in &mut var.values {
+                *value = self.resolve(*value);
+            }
+            var.values.dedup();
+        }
+        Some(func)
     }
 
     /// Create new value
@@ -71,18 +105,36 @@ impl MirBuilder {
         id
     }
 
-    /// Create new block
+    /// Create new block, adding it to the current function
     pub fn new_block(&mut self) -> BlockId {
         let id = self.next_block;
         self.next_block += 1;
+
+        if let Some(func) = &mut self.current_func {
+            func.add_block(BasicBlock::new(id));
+        }
+
         id
     }
 
+    /// Block instructions are currently emitted into
+    pub fn current_block(&self) -> Option<BlockId> {
+        self.current_block
+    }
+
     /// Set current block
     pub fn set_block(&mut self, block: BlockId) {
         self.current_block = Some(block);
     }
 
+    /// Stop emitting code until the next `set_block`
+    ///
+    /// Used after a `return`, `break` or `continue`, where the code that
+    /// follows can never run.
+    pub fn set_unreachable(&mut self) {
+        self.current_block = None;
+    }
+
     /// Emit instruction to current block
     pub fn emit(&mut self, inst: Instruction) {
         if let Some(func) = &mut self.current_func {
@@ -176,11 +228,27 @@ impl MirBuilder {
             else_block,
             span,
         });
+        self.add_edge(then_block);
+        self.add_edge(else_block);
     }
 
     /// Build unconditional jump
     pub fn build_jump(&mut self, target: BlockId, span: Span) {
         self.emit(Instruction::Jump { target, span });
+        self.add_edge(target);
+    }
+
+    /// Record a CFG edge from the current block to `target`
+    fn add_edge(&mut self, target: BlockId) {
+        let (Some(func), Some(source)) = (&mut self.current_func, self.current_block) else {
+            return;
+        };
+        if let Some(block) = func.block_mut(source) {
+            block.successors.push(target);
+        }
+        if let Some(block) = func.block_mut(target) {
+            block.predecessors.push(source);
+        }
     }
 
     /// Build phi node
@@ -207,6 +275,18 @@ impl MirBuilder {
         dest
     }
 
+    /// Build element address: dest = base + byte offset
+    pub fn build_get_element(&mut self, base: Operand, offset: Operand, ty: Type, span: Span) -> ValueId {
+        let dest = self.new_value(ty, span);
+        self.emit(Instruction::GetElement {
+            dest,
+            base,
+            index: offset,
+            span,
+        });
+        dest
+    }
+
     /// Build load
     pub fn build_load(&mut self, ptr: Operand, ty: Type, span: Span) -> ValueId {
         let dest = self.new_value(ty, span);
@@ -229,14 +309,13 @@ impl MirBuilder {
         });
     }
 
-    /// Define variable
-    pub fn define_var(&mut self, name: String, value: ValueId) {
-        self.vars.insert(name, value);
-    }
-
-    /// Lookup variable
-    pub fn lookup_var(&self, name: &str) -> Option<ValueId> {
-        self.vars.get(name).copied()
+    /// Add a parameter to the current function
+    pub fn add_param(&mut self, ty: Type, span: Span) -> ValueId {
+        let value = self.new_value(ty, span);
+        if let Some(func) = &mut self.current_func {
+            func.params.push(value);
+        }
+        value
     }
 
     /// Add block to current function
@@ -246,14 +325,15 @@ impl MirBuilder {
         }
     }
 
-    /// Check if current block is terminated
+    /// Check if current block is terminated, or code is unreachable
     pub fn is_terminated(&self) -> bool {
         if let Some(func) = &self.current_func {
-            if let Some(block_id) = self.current_block {
-                if let Some(block) = func.block(block_id) {
-                    if let Some(term) = block.terminator() {
-                        return BasicBlock::is_terminator(term);
-                    }
+            let Some(block_id) = self.current_block else {
+                return true;
+            };
+            if let Some(block) = func.block(block_id) {
+                if let Some(term) = block.terminator() {
+                    return BasicBlock::is_terminator(term);
                 }
             }
         }
@@ -273,6 +353,12 @@ pub struct LoweringContext<D> {
     pub(crate) diagnostics: Arc<D>,
     /// Current AST reference
     pub(crate) ast: Option<Ast>,
+    /// Layouts of the struct types in the type map
+    pub(crate) layouts: LayoutTable,
+    /// Caller-provided slot (and its type) the current function returns its aggregate in
+    pub(crate) return_slot: Option<(ValueId, Type)>,
+    /// Loops enclosing the code being lowered, innermost last
+    pub(crate) loops: Vec<LoopScope>,
 }
 
 impl<D: Send + Sync + 'static> LoweringContext<D> {
@@ -280,10 +366,13 @@ impl<D: Send + Sync + 'static> LoweringContext<D> {
     pub fn new(diagnostics: Arc<D>, type_map: TypeMap) -> Self {
         Self {
             builder: MirBuilder::new(),
+            layouts: LayoutTable::from_type_map(&type_map),
             type_map,
             next_func_id: 0,
             diagnostics,
             ast: None,
+            return_slot: None,
+            loops: Vec::new(),
         }
     }
 
@@ -295,6 +384,13 @@ impl<D: Send + Sync + 'static> LoweringContext<D> {
         self.lower_ast_real(ast)
     }
 
+    /// Lower only the items whose key (function name, or `Type::method`)
+    /// `filter` accepts
+    pub fn lower_items(&mut self, ast: Ast, mut filter: impl FnMut(&str) -> bool) -> crate::MirModule {
+        self.ast = Some(ast.clone());
+        self.lower_items_real(ast, &mut filter)
+    }
+
     /// Lower a function declaration
     fn lower_function(&mut self, _name: String, _params: Vec<(String, Type)>, _ret_ty: Type, _body: &[StmtKind]) -> Function {
         let func_id = self.next_func_id;
@@ -322,8 +418,8 @@ impl<D: Send + Sync + 'static> LoweringContext<D> {
                 // Lower literal
                 use aurora_ast::expr::Literal;
                 let const_val = match lit {
-                    Literal::Int(i) => Constant::Int(*i),
-                    Literal::Float(f) => Constant::Float(f.to_bits()),
+                    Literal::Int(i, _) => Constant::Int(*i as i64),
+                    Literal::Float(f, _) => Constant::Float(f.to_bits()),
                     Literal::String(s) => Constant::String(s.clone()),
                     Literal::Bool(b) => Constant::Bool(*b),
                     Literal::Char(_) => Constant::Int(0), // Simplified
@@ -1,60 +1,152 @@
 //! MIR Lowering Implementation - Actual AST traversal
+//!
+//! Aggregates (structs, tuples and arrays) are allocated on the stack and
+//! handled through their address: literals store each field at its layout
+//! offset, field and index expressions compute the element address and load
+//! scalars from it. Functions returning an aggregate write it to a slot the
+//! caller passes as a hidden first argument. Methods are dispatched
+//! statically to functions named `Type.method`. Enum values are aggregates
+//! too: constructing a variant stores its tag and then its fields.
+//!
+//! Local variables live in SSA values (see `ssa`): assigning one records a
+//! new value, while assignments to fields, elements and dereferenced
+//! pointers store to memory. Binding or assigning an aggregate copies it, so
+//! no two variables share storage.
+//!
+//! An interpolated string literal becomes calls to the runtime's formatting
+//! routines: `aurora_fmt_new` starts a string, one `aurora_fmt_*` routine
+//! per piece appends the text or a value of some type, and
+//! `aurora_fmt_finish` returns the string.
 
 use super::lower::{LoweringContext, MirBuilder};
+use crate::layout::{FieldLayout, VariantLayout, WORD_SIZE};
 use crate::mir::*;
+use aurora_ast::decl::{FunctionDecl, ImplDecl, ImplItem};
 use aurora_ast::nodes::AstNode;
-use aurora_ast::{Ast, ExprId, ExprKind, ItemKind, StmtKind};
+use aurora_ast::pattern::PatternKind;
+use aurora_ast::ty::TypeKind;
+use aurora_ast::expr::{BinaryOp, InterpolationPart, Literal, UnaryOp as AstUnaryOp};
+use aurora_ast::{Ast, ExprId, ExprKind, ItemKind, PatternId, StmtKind};
 use aurora_types::{EffectSet, Type, PrimitiveType};
 
+/// Runtime routine appending a value of type `ty` to a string being formatted
+fn format_routine(ty: &Type) -> &'static str {
+    match ty {
+        Type::Primitive(PrimitiveType::Str) | Type::Ref { .. } => "aurora_fmt_str",
+        Type::Primitive(PrimitiveType::Bool) => "aurora_fmt_bool",
+        Type::Primitive(PrimitiveType::Char) => "aurora_fmt_char",
+        Type::Primitive(PrimitiveType::F32) => "aurora_fmt_f32",
+        Type::Primitive(PrimitiveType::F64) => "aurora_fmt_f64",
+        Type::Primitive(
+            PrimitiveType::U8 | PrimitiveType::U16 | PrimitiveType::U32 | PrimitiveType::U64 | PrimitiveType::USize,
+        ) => "aurora_fmt_u64",
+        _ => "aurora_fmt_i64",
+    }
+}
+
 impl<D: Send + Sync + 'static> LoweringContext<D> {
     /// Lower entire AST to MIR module (real implementation)
     pub fn lower_ast_real(&mut self, ast: Ast) -> crate::MirModule {
+        self.lower_items_real(ast, &mut |_| true)
+    }
+
+    /// Lower the items whose key `filter` accepts
+    ///
+    /// Skipped items still take their function ID, so functions lowered
+    /// earlier can fill them in.
+    pub fn lower_items_real(&mut self, ast: Ast, filter: &mut dyn FnMut(&str) -> bool) -> crate::MirModule {
         let mut module = crate::MirModule::new();
 
-        // Iterate through top-level items
-        for &item_id in &ast.items {
-            if let Some(AstNode::Item(item)) = ast.arena.get(item_id) {
-                match &item.kind {
-                    ItemKind::Function(func_decl) => {
-                        let function = self.lower_function_real(func_decl, &ast);
-                        module.add_function(function);
-                    }
-                    _ => {}
-                }
+        for (key, func_decl) in lowered_items(&ast) {
+            if filter(&key) {
+                let function = self.lower_function_real(func_decl, &key, &ast);
+                module.add_function(function);
+            } else {
+                self.next_func_id += 1;
             }
         }
 
+        module.layouts = self.layouts.clone();
         module
     }
 
     /// Lower a function declaration to MIR
-    fn lower_function_real(
-        &mut self,
-        func_decl: &aurora_ast::decl::FunctionDecl,
-        ast: &Ast,
-    ) -> Function {
+    ///
+    /// `key` is the name the type checker records the signature under
+    /// (`Type::method` for methods).
+    fn lower_function_real(&mut self, func_decl: &FunctionDecl, key: &str, ast: &Ast) -> Function {
         let func_id = self.next_func_id;
         self.next_func_id += 1;
 
-        let ret_ty = Type::Unit;
-        self.builder.start_function(func_id, func_decl.name.clone(), ret_ty, EffectSet::IO);
-        self.lower_block(&func_decl.body, ast);
+        let (param_tys, ret_ty) = match self.type_map.function(key) {
+            Some(Type::Function { params, ret, .. }) => (params.clone(), (**ret).clone()),
+            _ => (Vec::new(), Type::Unit),
+        };
+        let span = Span::from(func_decl.span);
+
+        self.builder.start_function(func_id, symbol_name(key), ret_ty.clone(), EffectSet::IO);
+
+        self.return_slot = None;
+        if self.layouts.is_aggregate(&ret_ty) {
+            let slot_ty = Type::Ptr {
+                inner: Box::new(ret_ty.clone()),
+                mutable: true,
+            };
+            let slot = self.builder.add_param(slot_ty, span);
+            self.return_slot = Some((slot, ret_ty.clone()));
+        }
+
+        for (i, param) in func_decl.params.iter().enumerate() {
+            let ty = param_tys.get(i).cloned().unwrap_or(Type::Primitive(PrimitiveType::I64));
+            let value = self.builder.add_param(ty, param.span.into());
+            if let Some(name) = Self::binding_name(param.pattern, ast) {
+                self.builder.define_var(name, value);
+            }
+        }
+
+        let value = self.lower_block(&func_decl.body, ast);
 
         if !self.builder.is_terminated() {
-            self.builder.build_return(None, Span::dummy());
+            let value = (!matches!(ret_ty, Type::Unit | Type::Never)).then_some(value);
+            self.lower_return(value, func_decl.body.span.into());
         }
 
-        self.builder.finish_function().unwrap()
+        let mut function = self.builder.finish_function().unwrap();
+        function.span = span;
+        function.remove_unreachable_blocks();
+        function
     }
 
-    /// Lower a block
-    fn lower_block(&mut self, block: &aurora_ast::stmt::Block, ast: &Ast) {
+    /// Name bound by a parameter pattern (`x`, `self`, `&self`)
+    fn binding_name(pattern: PatternId, ast: &Ast) -> Option<String> {
+        match &ast.arena.get_pattern(pattern)?.kind {
+            PatternKind::Ident { name, .. } => Some(name.clone()),
+            PatternKind::Ref { inner, .. } => Self::binding_name(**inner, ast),
+            _ => None,
+        }
+    }
+
+    /// Lower a block, returning the value of its trailing expression
+    ///
+    /// Variables declared in the block go out of scope at its end.
+    pub(crate) fn lower_block(&mut self, block: &aurora_ast::stmt::Block, ast: &Ast) -> Operand {
         eprintln!("[MIR] Lowering block with {} statements", block.stmts.len());
-        for &stmt_id in &block.stmts {
+        let saved = self.builder.save_vars();
+        let mut value = Operand::Const(Constant::Unit);
+
+        for (i, &stmt_id) in block.stmts.iter().enumerate() {
             eprintln!("[MIR] Lowering statement ID: {}", stmt_id);
             if let Some(AstNode::Stmt(stmt)) = ast.arena.get(stmt_id) {
                 eprintln!("[MIR] Found statement: {:?}", stmt.kind);
-                self.lower_stmt(&stmt.kind, ast, Span::dummy());
+                match &stmt.kind {
+                    // The parser stores the trailing expression as the last statement
+                    StmtKind::Expr { expr, has_semi: false }
+                        if i + 1 == block.stmts.len() && block.expr.is_none() =>
+                    {
+                        value = self.lower_expr_real(*expr, ast);
+                    }
+                    kind => self.lower_stmt(kind, ast, stmt.span.into()),
+                }
             } else {
                 eprintln!("[MIR] Statement ID {} not found in arena!", stmt_id);
             }
@@ -62,25 +154,27 @@ impl<D: Send + Sync + 'static> LoweringContext<D> {
 
         if let Some(expr_id) = block.expr {
             eprintln!("[MIR] Lowering trailing expression ID: {}", expr_id);
-            let _value = self.lower_expr_real(expr_id, ast);
+            value = self.lower_expr_real(expr_id, ast);
         }
+
+        self.builder.restore_vars(saved);
+        value
     }
 
     /// Lower a statement
     fn lower_stmt(&mut self, stmt: &StmtKind, ast: &Ast, span: Span) {
         match stmt {
             StmtKind::Let { pattern, init, .. } => {
-                if let Some(AstNode::Pattern(pat)) = ast.arena.get(*pattern) {
-                    use aurora_ast::pattern::PatternKind;
-                    if let PatternKind::Ident { name, .. } = &pat.kind {
-                        if let Some(init_expr) = init {
-                            let value_op = self.lower_expr_real(*init_expr, ast);
-                            let ty = Type::Primitive(PrimitiveType::I64);
-                            let value_id = self.builder.new_value(ty, span);
-                            self.builder.build_assign(value_id, value_op, span);
-                            self.builder.define_var(name.clone(), value_id);
-                        }
+                if let Some(init_expr) = init {
+                    let mut value_op = self.lower_expr_real(*init_expr, ast);
+                    let ty = self.value_type(*init_expr);
+                    // Binding an existing aggregate gets the variable a copy of its own
+                    if self.layouts.is_aggregate(&ty) && Self::is_place(*init_expr, ast) {
+                        let slot = self.builder.build_alloca(ty.clone(), span);
+                        self.copy_aggregate(Operand::Value(slot), value_op, &ty, span);
+                        value_op = Operand::Value(slot);
                     }
+                    self.bind_pattern(*pattern, value_op, &ty, ast, span);
                 }
             }
             StmtKind::Expr { expr, .. } => {
@@ -90,67 +184,282 @@ impl<D: Send + Sync + 'static> LoweringContext<D> {
         }
     }
 
+    /// Bind the variables of a `let` pattern, destructuring tuples and structs
+    pub(crate) fn bind_pattern(&mut self, pattern: PatternId, value: Operand, ty: &Type, ast: &Ast, span: Span) {
+        let Some(AstNode::Pattern(pat)) = ast.arena.get(pattern) else {
+            return;
+        };
+
+        match &pat.kind {
+            PatternKind::Ident { name, .. } => {
+                let value_id = self.builder.new_value(ty.clone(), span);
+                self.builder.build_assign(value_id, value, span);
+                self.builder.define_var(name.clone(), value_id);
+            }
+            PatternKind::Tuple(elems) => {
+                for (i, &elem) in elems.iter().enumerate() {
+                    if let Some(field) = self.layouts.field(ty, &i.to_string()) {
+                        let field_value = self.read_field(value.clone(), &field, span);
+                        self.bind_pattern(elem, field_value, &field.ty, ast, span);
+                    }
+                }
+            }
+            PatternKind::Struct { fields, .. } => {
+                for field_pat in fields {
+                    let Some(field) = self.layouts.field(ty, &field_pat.name) else {
+                        continue;
+                    };
+                    let field_value = self.read_field(value.clone(), &field, span);
+                    match field_pat.pattern {
+                        Some(pattern) => self.bind_pattern(pattern, field_value, &field.ty, ast, span),
+                        // Shorthand `{ x }` binds the field's own name
+                        None => {
+                            let value_id = self.builder.new_value(field.ty.clone(), span);
+                            self.builder.build_assign(value_id, field_value, span);
+                            self.builder.define_var(field.name.clone(), value_id);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
     /// Lower an expression to an operand
-    fn lower_expr_real(&mut self, expr_id: ExprId, ast: &Ast) -> Operand {
+    pub(crate) fn lower_expr_real(&mut self, expr_id: ExprId, ast: &Ast) -> Operand {
+        // Nothing is emitted for code following a `return`, `break` or `continue`
+        if self.builder.current_block().is_none() {
+            return Operand::Const(Constant::Unit);
+        }
         if let Some(AstNode::Expr(expr)) = ast.arena.get(expr_id) {
+            let span = Span::from(expr.span);
             match &expr.kind {
                 ExprKind::Literal(lit) => {
                     use aurora_ast::expr::Literal;
                     let const_val = match lit {
-                        Literal::Int(i) => Constant::Int(*i),
-                        Literal::Float(f) => Constant::Float(f.to_bits()),
+                        Literal::Int(i, _) => Constant::Int(*i as i64),
+                        Literal::Float(f, _) => Constant::Float(f.to_bits()),
                         Literal::String(s) => Constant::String(s.clone()),
                         Literal::Bool(b) => Constant::Bool(*b),
                         Literal::Char(c) => Constant::Int(*c as i64),
                     };
                     Operand::Const(const_val)
                 }
+                ExprKind::Interpolated(parts) => self.lower_interpolated(parts, ast, span),
                 ExprKind::Ident(name) => {
                     if let Some(value_id) = self.builder.lookup_var(name) {
                         Operand::Value(value_id)
+                    } else if let Some((ty, variant)) = self.enum_variant(expr_id, name) {
+                        self.build_variant(&ty, &variant, Vec::new(), span)
                     } else {
                         Operand::Const(Constant::String(name.clone()))
                     }
                 }
+                ExprKind::Path(path) => match path.segments.last().and_then(|name| self.enum_variant(expr_id, name)) {
+                    Some((ty, variant)) => self.build_variant(&ty, &variant, Vec::new(), span),
+                    None => Operand::Const(Constant::Unit),
+                },
                 ExprKind::Call { func, args } => {
-                    let func_op = self.lower_expr_real(*func, ast);
-                    let arg_ops: Vec<Operand> = args.iter().map(|&arg| self.lower_expr_real(arg, ast)).collect();
+                    let callee = match ast.arena.get_expr(*func).map(|func| &func.kind) {
+                        Some(ExprKind::Path(path)) => path.segments.last(),
+                        Some(ExprKind::Ident(name)) if !self.builder.has_var(name) => Some(name),
+                        _ => None,
+                    };
+                    // Tuple variant constructors (`Some(x)`, `Shape::Circle(r)`)
+                    if let Some((ty, variant)) = callee.and_then(|name| self.enum_variant(expr_id, name)) {
+                        let values = args.iter().map(|&arg| self.lower_expr_real(arg, ast)).collect();
+                        return self.build_variant(&ty, &variant, values, span);
+                    }
 
-                    self.builder.build_call(func_op, arg_ops, None, EffectSet::IO, Span::dummy());
+                    let func_op = match ast.arena.get_expr(*func).map(|func| &func.kind) {
+                        // Associated functions (`Point::new`)
+                        Some(ExprKind::Path(path)) => {
+                            Operand::Const(Constant::String(path.segments.join(".")))
+                        }
+                        _ => self.lower_expr_real(*func, ast),
+                    };
+                    let mut arg_ops: Vec<Operand> = args.iter().map(|&arg| self.lower_expr_real(arg, ast)).collect();
+                    // `print` and `println` take a string; other values are formatted
+                    // into one first, as in an interpolated string, and so are the
+                    // values after a template, into its `{}` placeholders. A string
+                    // formatted only to be printed is freed after the call.
+                    let mut temporary = None;
+                    let template = match args.first().and_then(|&arg| ast.arena.get_expr(arg)).map(|arg| &arg.kind) {
+                        Some(ExprKind::Literal(Literal::String(text))) if args.len() > 1 => Some(text),
+                        _ => None,
+                    };
+                    if let (Some("print" | "println"), Some(template)) = (callee.map(String::as_str), template) {
+                        let text = |text: &str| ("aurora_fmt_str", Operand::Const(Constant::String(text.to_string())));
+                        let mut texts = template.split("{}");
+                        let mut pieces = Vec::new();
+                        for (&arg, op) in args[1..].iter().zip(&arg_ops[1..]) {
+                            pieces.extend(texts.next().filter(|part| !part.is_empty()).map(text));
+                            pieces.push((format_routine(&self.value_type(arg)), op.clone()));
+                        }
+                        pieces.extend(texts.filter(|part| !part.is_empty()).map(text));
+                        let formatted = self.format_pieces(pieces, span);
+                        arg_ops = vec![formatted.clone()];
+                        temporary = Some(formatted);
+                    } else if matches!(callee.map(String::as_str), Some("print" | "println")) {
+                        if let (Some(&arg), Some(op)) = (args.first(), arg_ops.first_mut()) {
+                            let routine = format_routine(&self.value_type(arg));
+                            if routine != "aurora_fmt_str" {
+                                *op = self.format_pieces(vec![(routine, op.clone())], span);
+                                temporary = Some(op.clone());
+                            } else if matches!(ast.arena.get_expr(arg).map(|arg| &arg.kind), Some(ExprKind::Interpolated(_))) {
+                                temporary = Some(op.clone());
+                            }
+                        }
+                    }
+                    let result = self.lower_call(func_op, arg_ops, expr_id, span);
+                    if let Some(text) = temporary {
+                        let free = Operand::Const(Constant::String("aurora_fmt_free".to_string()));
+                        self.builder.build_call(free, vec![text], None, EffectSet::ALLOC, span);
+                    }
+                    result
+                }
+                ExprKind::MethodCall { receiver, method, args } => {
+                    let receiver_op = self.lower_expr_real(*receiver, ast);
+                    let Some(type_name) = self.expr_type(*receiver).and_then(|ty| type_name(&ty)) else {
+                        return Operand::Const(Constant::Unit);
+                    };
+
+                    // The receiver is passed as the first argument
+                    let mut arg_ops = vec![receiver_op];
+                    arg_ops.extend(args.iter().map(|&arg| self.lower_expr_real(arg, ast)));
+                    let func_op = Operand::Const(Constant::String(format!("{}.{}", type_name, method)));
+                    self.lower_call(func_op, arg_ops, expr_id, span)
+                }
+                ExprKind::Field { .. } | ExprKind::Index { .. } => match self.place(expr_id, ast) {
+                    Some((addr, ty)) => self.read(addr, &ty, span),
+                    None => Operand::Const(Constant::Unit),
+                },
+                ExprKind::Struct { path, fields } => {
+                    let Some(ty) = self.expr_type(expr_id) else {
+                        return Operand::Const(Constant::Unit);
+                    };
+                    // Struct-like variants (`Shape::Rect { w, h }`)
+                    if let Some((ty, variant)) = path.segments.last().and_then(|name| self.enum_variant(expr_id, name)) {
+                        let values = variant
+                            .fields
+                            .iter()
+                            .map(|field| match fields.iter().find(|init| init.name == field.name) {
+                                Some(init) => self.lower_expr_real(init.value, ast),
+                                None => Operand::Const(Constant::Unit),
+                            })
+                            .collect();
+                        return self.build_variant(&ty, &variant, values, span);
+                    }
+                    let slot = self.builder.build_alloca(ty.clone(), span);
+                    for init in fields {
+                        let value = self.lower_expr_real(init.value, ast);
+                        if let Some(field) = self.layouts.field(&ty, &init.name) {
+                            self.write_field(Operand::Value(slot), &field, value, init.span.into());
+                        }
+                    }
+                    Operand::Value(slot)
+                }
+                ExprKind::Tuple(elems) if !elems.is_empty() => {
+                    let Some(ty) = self.expr_type(expr_id) else {
+                        return Operand::Const(Constant::Unit);
+                    };
+                    let slot = self.builder.build_alloca(ty.clone(), span);
+                    let fields = self.layouts.fields(&ty).unwrap_or_default();
+                    for (&elem, field) in elems.iter().zip(&fields) {
+                        let value = self.lower_expr_real(elem, ast);
+                        self.write_field(Operand::Value(slot), field, value, span);
+                    }
+                    Operand::Value(slot)
+                }
+                ExprKind::Array(elems) => {
+                    let Some(ty @ Type::Array { .. }) = self.expr_type(expr_id) else {
+                        return Operand::Const(Constant::Unit);
+                    };
+                    let Type::Array { elem, .. } = &ty else {
+                        unreachable!()
+                    };
+                    let elem_size = self.layouts.size_of(elem);
+                    let slot = self.builder.build_alloca(ty.clone(), span);
+                    for (i, &elem_expr) in elems.iter().enumerate() {
+                        let value = self.lower_expr_real(elem_expr, ast);
+                        let field = FieldLayout {
+                            name: i.to_string(),
+                            ty: (**elem).clone(),
+                            offset: i as u64 * elem_size,
+                        };
+                        self.write_field(Operand::Value(slot), &field, value, span);
+                    }
+                    Operand::Value(slot)
+                }
+                ExprKind::Binary { op, left, right } if Self::compound_op(op).is_some() => {
+                    self.lower_assign(op, *left, *right, ast, span);
                     Operand::Const(Constant::Unit)
                 }
                 ExprKind::Binary { op, left, right } => {
                     let lhs = self.lower_expr_real(*left, ast);
                     let rhs = self.lower_expr_real(*right, ast);
                     let mir_op = self.convert_binop(op);
-                    let result_ty = Type::Primitive(PrimitiveType::I64);
-                    let value_id = self.builder.build_binop(mir_op, lhs, rhs, result_ty, Span::dummy());
+                    let result_ty = self.value_type(expr_id);
+                    let value_id = self.builder.build_binop(mir_op, lhs, rhs, result_ty, span);
                     Operand::Value(value_id)
                 }
                 ExprKind::Unary { op, operand } => {
+                    use aurora_ast::expr::UnaryOp as AstUnaryOp;
                     let val = self.lower_expr_real(*operand, ast);
-                    let mir_op = self.convert_unaryop(op);
-                    let result_ty = Type::Primitive(PrimitiveType::I64);
-                    let value_id = self.builder.build_unaryop(mir_op, val, result_ty, Span::dummy());
-                    Operand::Value(value_id)
+                    let result_ty = self.value_type(expr_id);
+                    match op {
+                        AstUnaryOp::Ref | AstUnaryOp::RefMut => {
+                            let operand_ty = self.value_type(*operand);
+                            if self.layouts.is_aggregate(&operand_ty) {
+                                // Aggregates are already held by address
+                                val
+                            } else {
+                                let slot = self.builder.build_alloca(operand_ty, span);
+                                self.builder.build_store(Operand::Value(slot), val, span);
+                                Operand::Value(slot)
+                            }
+                        }
+                        AstUnaryOp::Deref => self.read(val, &result_ty, span),
+                        _ => {
+                            let mir_op = self.convert_unaryop(op);
+                            let value_id = self.builder.build_unaryop(mir_op, val, result_ty, span);
+                            Operand::Value(value_id)
+                        }
+                    }
                 }
                 ExprKind::If { condition, then_block, else_block } => {
-                    self.lower_if(*condition, *then_block, *else_block, ast)
+                    self.lower_if(*condition, *then_block, *else_block, expr_id, ast, span)
                 }
+                ExprKind::Match { scrutinee, arms } => self.lower_match(*scrutinee, arms, expr_id, ast, span),
                 ExprKind::While { condition, body } => {
-                    self.lower_while(*condition, *body, ast);
+                    self.lower_while(*condition, *body, ast, span);
+                    Operand::Const(Constant::Unit)
+                }
+                ExprKind::Loop { body } => self.lower_loop(*body, expr_id, ast, span),
+                ExprKind::For { pattern, iterator, body } => {
+                    self.lower_for(*pattern, *iterator, *body, ast, span);
+                    Operand::Const(Constant::Unit)
+                }
+                ExprKind::Break { value } => {
+                    self.lower_break(*value, ast, span);
+                    Operand::Const(Constant::Unit)
+                }
+                ExprKind::Continue => {
+                    self.lower_continue(span);
                     Operand::Const(Constant::Unit)
                 }
                 ExprKind::Return { value } => {
                     let ret_val = value.map(|v| self.lower_expr_real(v, ast));
-                    self.builder.build_return(ret_val, Span::dummy());
+                    self.lower_return(ret_val, span);
+                    self.builder.set_unreachable();
                     Operand::Const(Constant::Unit)
                 }
                 ExprKind::Block(block_id) => {
                     if let Some(AstNode::Block(block)) = ast.arena.get(*block_id) {
-                        self.lower_block(block, ast);
+                        self.lower_block(block, ast)
+                    } else {
+                        Operand::Const(Constant::Unit)
                     }
-                    Operand::Const(Constant::Unit)
                 }
                 _ => Operand::Const(Constant::Unit)
             }
@@ -159,6 +468,172 @@ impl<D: Send + Sync + 'static> LoweringContext<D> {
         }
     }
 
+    /// Type the checker inferred for an expression
+    pub(crate) fn expr_type(&self, expr_id: ExprId) -> Option<Type> {
+        self.type_map.get_expr(expr_id).cloned()
+    }
+
+    /// Type of the value an expression produces, defaulting to `i64` when untyped
+    pub(crate) fn value_type(&self, expr_id: ExprId) -> Type {
+        self.expr_type(expr_id).unwrap_or(Type::Primitive(PrimitiveType::I64))
+    }
+
+    /// Emit a call whose result has the type of expression `expr_id`
+    ///
+    /// An aggregate result is written to a stack slot passed as the first argument.
+    fn lower_call(&mut self, func: Operand, mut args: Vec<Operand>, expr_id: ExprId, span: Span) -> Operand {
+        let ret_ty = self.expr_type(expr_id);
+
+        match ret_ty {
+            Some(ty) if self.layouts.is_aggregate(&ty) => {
+                let slot = self.builder.build_alloca(ty, span);
+                args.insert(0, Operand::Value(slot));
+                self.builder.build_call(func, args, None, EffectSet::IO, span);
+                Operand::Value(slot)
+            }
+            Some(Type::Unit | Type::Never) | None => {
+                self.builder.build_call(func, args, None, EffectSet::IO, span);
+                Operand::Const(Constant::Unit)
+            }
+            ret_ty => {
+                let dest = self.builder.build_call(func, args, ret_ty, EffectSet::IO, span);
+                dest.map_or(Operand::Const(Constant::Unit), Operand::Value)
+            }
+        }
+    }
+
+    /// Build the string of an interpolated string literal
+    ///
+    /// The values are computed first, so no other string is formatted
+    /// while this one is being built.
+    fn lower_interpolated(&mut self, parts: &[InterpolationPart], ast: &Ast, span: Span) -> Operand {
+        let pieces: Vec<(&str, Operand)> = parts
+            .iter()
+            .map(|part| match part {
+                InterpolationPart::Text(text) => ("aurora_fmt_str", Operand::Const(Constant::String(text.clone()))),
+                InterpolationPart::Expr(value) => {
+                    (format_routine(&self.value_type(*value)), self.lower_expr_real(*value, ast))
+                }
+            })
+            .collect();
+        self.format_pieces(pieces, span)
+    }
+
+    /// Build a string from values paired with the routine that formats each one
+    fn format_pieces(&mut self, pieces: Vec<(&str, Operand)>, span: Span) -> Operand {
+        let routine = |name: &str| Operand::Const(Constant::String(name.to_string()));
+        let buffer_ty = Type::Ptr {
+            inner: Box::new(Type::Primitive(PrimitiveType::U8)),
+            mutable: true,
+        };
+        let new = routine("aurora_fmt_new");
+        let Some(buffer) = self.builder.build_call(new, Vec::new(), Some(buffer_ty), EffectSet::ALLOC, span) else {
+            return Operand::Const(Constant::Unit);
+        };
+        for (name, value) in pieces {
+            // A float constant carries no type, and would be passed as an f64
+            let value = match value {
+                Operand::Const(constant @ Constant::Float(_)) if name == "aurora_fmt_f32" => {
+                    let single = self.builder.new_value(Type::Primitive(PrimitiveType::F32), span);
+                    self.builder.build_assign(single, Operand::Const(constant), span);
+                    Operand::Value(single)
+                }
+                value => value,
+            };
+            let args = vec![Operand::Value(buffer), value];
+            self.builder.build_call(routine(name), args, None, EffectSet::ALLOC, span);
+        }
+        let string_ty = Type::Primitive(PrimitiveType::Str);
+        let args = vec![Operand::Value(buffer)];
+        self.builder
+            .build_call(routine("aurora_fmt_finish"), args, Some(string_ty), EffectSet::ALLOC, span)
+            .map_or(Operand::Const(Constant::Unit), Operand::Value)
+    }
+
+    /// Return from the current function, filling the caller's slot for aggregates
+    fn lower_return(&mut self, value: Option<Operand>, span: Span) {
+        match (self.return_slot.clone(), value) {
+            (Some((slot, ty)), Some(value)) => {
+                self.copy_aggregate(Operand::Value(slot), value, &ty, span);
+                self.builder.build_return(Some(Operand::Value(slot)), span);
+            }
+            (_, value) => self.builder.build_return(value, span),
+        }
+    }
+
+    /// Address of the element at a byte offset from `base`
+    ///
+    /// The address of an aggregate element stands for the element itself;
+    /// scalar elements get a pointer to be loaded from or stored to.
+    pub(crate) fn element_address(&mut self, base: Operand, offset: Operand, ty: &Type, span: Span) -> Operand {
+        let addr_ty = if self.layouts.is_aggregate(ty) {
+            ty.clone()
+        } else {
+            Type::Ptr {
+                inner: Box::new(ty.clone()),
+                mutable: true,
+            }
+        };
+        Operand::Value(self.builder.build_get_element(base, offset, addr_ty, span))
+    }
+
+    /// Value of type `ty` stored at `addr`
+    pub(crate) fn read(&mut self, addr: Operand, ty: &Type, span: Span) -> Operand {
+        if self.layouts.is_aggregate(ty) {
+            addr
+        } else {
+            Operand::Value(self.builder.build_load(addr, ty.clone(), span))
+        }
+    }
+
+    /// Read a field of the aggregate at `base`
+    pub(crate) fn read_field(&mut self, base: Operand, field: &FieldLayout, span: Span) -> Operand {
+        let offset = Operand::Const(Constant::Int(field.offset as i64));
+        let addr = self.element_address(base, offset, &field.ty, span);
+        self.read(addr, &field.ty, span)
+    }
+
+    /// Write a field of the aggregate at `base`, copying aggregate values in place
+    fn write_field(&mut self, base: Operand, field: &FieldLayout, value: Operand, span: Span) {
+        let offset = Operand::Const(Constant::Int(field.offset as i64));
+        let addr = self.element_address(base, offset, &field.ty, span);
+        if self.layouts.is_aggregate(&field.ty) {
+            self.copy_aggregate(addr, value, &field.ty, span);
+        } else {
+            self.builder.build_store(addr, value, span);
+        }
+    }
+
+    /// Copy an aggregate of type `ty` from `src` to `dest` one word at a time
+    fn copy_aggregate(&mut self, dest: Operand, src: Operand, ty: &Type, span: Span) {
+        let word = Type::Primitive(PrimitiveType::I64);
+        for offset in (0..self.layouts.size_of(ty)).step_by(WORD_SIZE as usize) {
+            let offset = Operand::Const(Constant::Int(offset as i64));
+            let from = self.element_address(src.clone(), offset.clone(), &word, span);
+            let value = self.builder.build_load(from, word.clone(), span);
+            let to = self.element_address(dest.clone(), offset, &word, span);
+            self.builder.build_store(to, Operand::Value(value), span);
+        }
+    }
+
+    /// Enum type of expression `expr_id` and its variant `name`, if it names one
+    fn enum_variant(&self, expr_id: ExprId, name: &str) -> Option<(Type, VariantLayout)> {
+        let ty = self.expr_type(expr_id)?;
+        let variant = self.layouts.variant(&ty, name)?;
+        Some((ty, variant))
+    }
+
+    /// Construct an enum value: store the variant's tag, then its fields
+    fn build_variant(&mut self, ty: &Type, variant: &VariantLayout, values: Vec<Operand>, span: Span) -> Operand {
+        let slot = Operand::Value(self.builder.build_alloca(ty.clone(), span));
+        let tag = Operand::Const(Constant::Int(variant.tag));
+        self.write_field(slot.clone(), &tag_field(), tag, span);
+        for (field, value) in variant.fields.iter().zip(values) {
+            self.write_field(slot.clone(), field, value, span);
+        }
+        slot
+    }
+
     /// Convert AST BinaryOp to MIR BinOp
     fn convert_binop(&self, op: &aurora_ast::expr::BinaryOp) -> BinOp {
         use aurora_ast::expr::BinaryOp;
@@ -196,13 +671,15 @@ impl<D: Send + Sync + 'static> LoweringContext<D> {
         }
     }
 
-    /// Lower an if expression
+    /// Lower an if expression, merging the values of its branches
     fn lower_if(
         &mut self,
         condition: ExprId,
         then_block_id: u32,
         else_block_id: Option<u32>,
+        expr_id: ExprId,
         ast: &Ast,
+        span: Span,
     ) -> Operand {
         eprintln!("[MIR] Lowering if expression");
 
@@ -215,60 +692,363 @@ impl<D: Send + Sync + 'static> LoweringContext<D> {
         let merge_bb = self.builder.new_block();
 
         // Branch on condition
-        self.builder.build_branch(cond_op, then_bb, else_bb, Span::dummy());
+        self.builder.build_branch(cond_op, then_bb, else_bb, span);
 
-        // Lower then block
-        self.builder.set_block(then_bb);
-        if let Some(AstNode::Block(then_block)) = ast.arena.get(then_block_id) {
-            self.lower_block(then_block, ast);
+        // Lower both branches, noting the value each reaches the merge block with
+        let mut results = Vec::new();
+        for (block, block_id) in [(then_bb, Some(then_block_id)), (else_bb, else_block_id)] {
+            self.builder.set_block(block);
+            let value = match block_id.and_then(|id| ast.arena.get(id)) {
+                Some(AstNode::Block(body)) => self.lower_block(body, ast),
+                _ => Operand::Const(Constant::Unit),
+            };
+            if let (false, Some(exit)) = (self.builder.is_terminated(), self.builder.current_block()) {
+                results.push((exit, value));
+                self.builder.build_jump(merge_bb, span);
+            }
         }
-        if !self.builder.is_terminated() {
-            self.builder.build_jump(merge_bb, Span::dummy());
+
+        // Continue in merge block, unless neither branch reaches it
+        if results.is_empty() {
+            self.builder.set_unreachable();
+            return Operand::Const(Constant::Unit);
+        }
+        self.builder.set_block(merge_bb);
+        let ty = self.value_type(expr_id);
+        match results.len() {
+            _ if matches!(ty, Type::Unit | Type::Never) => Operand::Const(Constant::Unit),
+            1 => results.remove(0).1,
+            _ => Operand::Value(self.builder.build_phi(results, ty, span)),
         }
+    }
+
+    /// Lower an assignment or compound assignment
+    fn lower_assign(&mut self, op: &BinaryOp, target: ExprId, value: ExprId, ast: &Ast, span: Span) {
+        let mut value = self.lower_expr_real(value, ast);
+        let ty = self.value_type(target);
 
-        // Lower else block
-        self.builder.set_block(else_bb);
-        if let Some(else_id) = else_block_id {
-            if let Some(AstNode::Block(else_block)) = ast.arena.get(else_id) {
-                self.lower_block(else_block, ast);
+        if let Some(ExprKind::Ident(name)) = ast.arena.get_expr(target).map(|expr| &expr.kind) {
+            if !self.layouts.is_aggregate(&ty) {
+                let Some(current) = self.builder.lookup_var(name) else {
+                    return;
+                };
+                if let Some(Some(bin_op)) = Self::compound_op(op) {
+                    let result = self.builder.build_binop(bin_op, Operand::Value(current), value, ty.clone(), span);
+                    value = Operand::Value(result);
+                }
+                let value = match value {
+                    Operand::Value(value) => value,
+                    value => {
+                        let dest = self.builder.new_value(ty, span);
+                        self.builder.build_assign(dest, value, span);
+                        dest
+                    }
+                };
+                self.builder.assign_var(name, value);
+                return;
             }
         }
-        if !self.builder.is_terminated() {
-            self.builder.build_jump(merge_bb, Span::dummy());
-        }
 
-        // Continue in merge block
-        self.builder.set_block(merge_bb);
-        Operand::Const(Constant::Unit)
+        // Everything else is assigned in memory
+        let Some((addr, ty)) = self.place(target, ast) else {
+            return;
+        };
+        if self.layouts.is_aggregate(&ty) {
+            self.copy_aggregate(addr, value, &ty, span);
+            return;
+        }
+        if let Some(Some(bin_op)) = Self::compound_op(op) {
+            let current = self.read(addr.clone(), &ty, span);
+            value = Operand::Value(self.builder.build_binop(bin_op, current, value, ty, span));
+        }
+        self.builder.build_store(addr, value, span);
     }
 
-    /// Lower a while loop
-    fn lower_while(&mut self, condition: ExprId, body_id: u32, ast: &Ast) {
-        eprintln!("[MIR] Lowering while loop");
+    /// Address and type of the memory a place expression refers to
+    ///
+    /// Aggregate variables are places too, as they are held by address.
+    fn place(&mut self, expr_id: ExprId, ast: &Ast) -> Option<(Operand, Type)> {
+        let span = Span::from(ast.arena.get_expr(expr_id)?.span);
+        match &ast.arena.get_expr(expr_id)?.kind {
+            ExprKind::Ident(_) => {
+                let ty = self.expr_type(expr_id).filter(|ty| self.layouts.is_aggregate(ty))?;
+                Some((self.lower_expr_real(expr_id, ast), ty))
+            }
+            ExprKind::Field { object, field } => {
+                let base = self.lower_expr_real(*object, ast);
+                let object_ty = self.expr_type(*object).map(|ty| peel_refs(&ty))?;
+                let field = self.layouts.field(&object_ty, field)?;
+                let offset = Operand::Const(Constant::Int(field.offset as i64));
+                Some((self.element_address(base, offset, &field.ty, span), field.ty))
+            }
+            ExprKind::Index { collection, index } => {
+                let base = self.lower_expr_real(*collection, ast);
+                let index_op = self.lower_expr_real(*index, ast);
+                let Some(Type::Array { elem, .. }) = self.expr_type(*collection).map(|ty| peel_refs(&ty)) else {
+                    return None;
+                };
+
+                let elem_size = self.layouts.size_of(&elem) as i64;
+                let offset = match index_op {
+                    Operand::Const(Constant::Int(i)) => Operand::Const(Constant::Int(i * elem_size)),
+                    index_op => {
+                        let size = Operand::Const(Constant::Int(elem_size));
+                        let i64_ty = Type::Primitive(PrimitiveType::I64);
+                        Operand::Value(self.builder.build_binop(BinOp::Mul, index_op, size, i64_ty, span))
+                    }
+                };
+                Some((self.element_address(base, offset, &elem, span), *elem))
+            }
+            ExprKind::Unary { op: AstUnaryOp::Deref, operand } => {
+                let ptr = self.lower_expr_real(*operand, ast);
+                Some((ptr, self.value_type(expr_id)))
+            }
+            _ => None,
+        }
+    }
 
-        // Create blocks for header, body, and exit
-        let header_bb = self.builder.new_block();
-        let body_bb = self.builder.new_block();
-        let exit_bb = self.builder.new_block();
+    /// Whether an expression names existing memory rather than producing a new value
+    fn is_place(expr_id: ExprId, ast: &Ast) -> bool {
+        matches!(
+            ast.arena.get_expr(expr_id).map(|expr| &expr.kind),
+            Some(ExprKind::Ident(_) | ExprKind::Field { .. } | ExprKind::Index { .. })
+                | Some(ExprKind::Unary { op: AstUnaryOp::Deref, .. })
+        )
+    }
 
-        // Jump to header
-        self.builder.build_jump(header_bb, Span::dummy());
+    /// The operation a compound assignment applies, `Some(None)` for plain `=`
+    fn compound_op(op: &BinaryOp) -> Option<Option<BinOp>> {
+        let bin_op = match op {
+            BinaryOp::Assign => return Some(None),
+            BinaryOp::AddAssign => BinOp::Add,
+            BinaryOp::SubAssign => BinOp::Sub,
+            BinaryOp::MulAssign => BinOp::Mul,
+            BinaryOp::DivAssign => BinOp::Div,
+            BinaryOp::RemAssign => BinOp::Mod,
+            BinaryOp::BitAndAssign => BinOp::BitAnd,
+            BinaryOp::BitOrAssign => BinOp::BitOr,
+            BinaryOp::BitXorAssign => BinOp::BitXor,
+            BinaryOp::ShlAssign => BinOp::Shl,
+            BinaryOp::ShrAssign => BinOp::Shr,
+            _ => return None,
+        };
+        Some(Some(bin_op))
+    }
+}
 
-        // Header: evaluate condition and branch
-        self.builder.set_block(header_bb);
-        let cond_op = self.lower_expr_real(condition, ast);
-        self.builder.build_branch(cond_op, body_bb, exit_bb, Span::dummy());
+/// The functions and methods lowered to MIR, in order, with the name the
+/// type checker records each signature under
+pub(crate) fn lowered_items(ast: &Ast) -> Vec<(String, &FunctionDecl)> {
+    let mut items = Vec::new();
 
-        // Body: execute loop body and jump back to header
-        self.builder.set_block(body_bb);
-        if let Some(AstNode::Block(body)) = ast.arena.get(body_id) {
-            self.lower_block(body, ast);
-        }
-        if !self.builder.is_terminated() {
-            self.builder.build_jump(header_bb, Span::dummy());
+    // Iterate through top-level items
+    for &item_id in &ast.items {
+        if let Some(AstNode::Item(item)) = ast.arena.get(item_id) {
+            match &item.kind {
+                ItemKind::Function(func_decl) => items.push((func_decl.name.clone(), func_decl)),
+                ItemKind::Impl(impl_decl) => {
+                    let Some(type_name) = impl_type_name(impl_decl, ast) else {
+                        continue;
+                    };
+                    for impl_item in &impl_decl.items {
+                        if let ImplItem::Function(func_decl) = impl_item {
+                            items.push((format!("{}::{}", type_name, func_decl.name), func_decl));
+                        }
+                    }
+                }
+                _ => {}
+            }
         }
+    }
+
+    items
+}
+
+/// Name of the type an `impl` block is for
+fn impl_type_name(impl_decl: &ImplDecl, ast: &Ast) -> Option<String> {
+    match &ast.arena.get_type_node(impl_decl.self_ty)?.kind {
+        TypeKind::Path { path } => path.segments.last().cloned(),
+        _ => None,
+    }
+}
+
+/// Assembly symbol for a function; methods `Type::method` become `Type.method`
+fn symbol_name(key: &str) -> String {
+    key.replace("::", ".")
+}
+
+/// The tag word at the start of every enum value
+pub(crate) fn tag_field() -> FieldLayout {
+    FieldLayout {
+        name: "tag".to_string(),
+        ty: Type::Primitive(PrimitiveType::I64),
+        offset: 0,
+    }
+}
+
+/// Name of the type whose methods a receiver of type `ty` dispatches to
+fn type_name(ty: &Type) -> Option<String> {
+    match peel_refs(ty) {
+        Type::Named { name, .. } => Some(name),
+        Type::Primitive(prim) => Some(prim.to_string()),
+        _ => None,
+    }
+}
+
+/// Type behind any references and pointers
+pub(crate) fn peel_refs(ty: &Type) -> Type {
+    match ty {
+        Type::Ref { inner, .. } | Type::Ptr { inner, .. } => peel_refs(inner),
+        ty => ty.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mir::{Constant, Instruction, Operand};
+    use aurora_parser::Parser;
+    use aurora_types::{Type, TypeChecker};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_lowering_propagates_ast_spans() {
+        let source = "fn main() {\n    let x = 1 + 2;\n    foo(x);\n}\n";
+        let ast = Parser::new(source, "test.ax".to_string()).unwrap().parse();
+        let module = crate::lower_ast_to_mir(ast, Arc::new(()));
+
+        let func = module.functions.values().next().unwrap();
+        let spans: Vec<(&str, &str)> = func
+            .blocks
+            .values()
+            .flat_map(|block| &block.instructions)
+            .map(|inst| {
+                let kind = match inst {
+                    Instruction::BinOp { .. } => "binop",
+                    Instruction::Call { .. } => "call",
+                    Instruction::Assign { .. } => "assign",
+                    Instruction::Return { .. } => "return",
+                    _ => "other",
+                };
+                let span = inst.span();
+                (kind, &source[span.start..span.end])
+            })
+            .collect();
+
+        assert!(spans.contains(&("binop", "1 + 2")), "{:?}", spans);
+        assert!(spans.contains(&("assign", "let x = 1 + 2;")), "{:?}", spans);
+        assert!(spans.contains(&("call", "foo(x)")), "{:?}", spans);
+        assert!(spans.iter().all(|(_, text)| !text.is_empty()), "{:?}", spans);
+    }
+
+    #[test]
+    fn test_lowering_struct_fields_and_methods() {
+        let source = "struct Point { x: i64, y: i64 }\n\
+            impl Point {\n\
+                fn new(x: i64, y: i64) -> Point { Point { x, y } }\n\
+                fn sum(&self) -> i64 { self.x + self.y }\n\
+            }\n\
+            fn main() -> i64 { let p = Point::new(3, 4); p.sum() + p.y }\n";
+        let ast = Parser::new(source, "test.ax".to_string()).unwrap().parse();
+        let mut checker = TypeChecker::new(Arc::new(()));
+        let ast = checker.check(ast);
+        assert!(checker.errors().is_empty(), "{:?}", checker.errors());
+        let module = crate::lower_typed_ast_to_mir(ast, checker.type_map().clone(), Arc::new(()));
+
+        let function = |name: &str| {
+            module
+                .functions
+                .values()
+                .find(|func| func.name == name)
+                .unwrap_or_else(|| panic!("missing function {}", name))
+        };
+        let instructions = |name: &str| -> Vec<Instruction> {
+            function(name)
+                .blocks
+                .values()
+                .flat_map(|block| block.instructions.clone())
+                .collect()
+        };
+        let offsets = |insts: &[Instruction]| -> Vec<i64> {
+            insts
+                .iter()
+                .filter_map(|inst| match inst {
+                    Instruction::GetElement {
+                        index: Operand::Const(Constant::Int(offset)),
+                        ..
+                    } => Some(*offset),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // The struct is returned through a hidden pointer parameter
+        assert_eq!(function("Point.new").params.len(), 3);
+        assert_eq!(function("Point.sum").params.len(), 1);
+        assert_eq!(offsets(&instructions("Point.sum")), vec![0, 8]);
+
+        let main = instructions("main");
+        let callees: Vec<&str> = main
+            .iter()
+            .filter_map(|inst| match inst {
+                Instruction::Call {
+                    func: Operand::Const(Constant::String(name)),
+                    ..
+                } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(callees, vec!["Point.new", "Point.sum"]);
+        assert!(main.iter().any(|inst| matches!(inst, Instruction::Alloca { .. })));
+        assert!(offsets(&main).contains(&8));
+        let point = Type::Named {
+            name: "Point".to_string(),
+            args: vec![],
+        };
+        assert_eq!(module.layouts.size_of(&point), 16);
+    }
+
+    #[test]
+    fn test_lowering_interpolated_strings() {
+        let source = "fn main() { let x = 2.5; let n = 3; println(f\"x = {x}, n = {n + 1}!\"); println(n); }\n";
+        let ast = Parser::new(source, "test.ax".to_string()).unwrap().parse();
+        let mut checker = TypeChecker::new(Arc::new(()));
+        let ast = checker.check(ast);
+        assert!(checker.errors().is_empty(), "{:?}", checker.errors());
+        let module = crate::lower_typed_ast_to_mir(ast, checker.type_map().clone(), Arc::new(()));
 
-        // Continue in exit block
-        self.builder.set_block(exit_bb);
+        let main = module.functions.values().find(|func| func.name == "main").unwrap();
+        let mut block_ids: Vec<_> = main.blocks.keys().copied().collect();
+        block_ids.sort();
+        let callees: Vec<&str> = block_ids
+            .iter()
+            .flat_map(|id| &main.blocks[id].instructions)
+            .filter_map(|inst| match inst {
+                Instruction::Call {
+                    func: Operand::Const(Constant::String(name)),
+                    ..
+                } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            callees,
+            vec![
+                "aurora_fmt_new",
+                "aurora_fmt_str",
+                "aurora_fmt_f64",
+                "aurora_fmt_str",
+                "aurora_fmt_i64",
+                "aurora_fmt_str",
+                "aurora_fmt_finish",
+                "println",
+                "aurora_fmt_free",
+                // Printed values are formatted, and the string freed
+                "aurora_fmt_new",
+                "aurora_fmt_i64",
+                "aurora_fmt_finish",
+                "println",
+                "aurora_fmt_free",
+            ]
+        );
     }
 }
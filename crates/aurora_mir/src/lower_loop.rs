@@ -0,0 +1,330 @@
+//! Loop lowering
+//!
+//! Every loop has a header block, entered from the code before the loop and
+//! again from each back edge, and an exit block that `break` jumps to. The
+//! header is left unsealed while the body is lowered, so variables assigned
+//! in the body get their phis there. `break value` passes its value to the
+//! exit block, where a phi joins the values of all breaks.
+//!
+//! `for` loops count over ranges and fixed-size arrays; the counter is an
+//! SSA variable of its own, advanced in a step block that `continue` jumps to.
+
+use super::lower::LoweringContext;
+use crate::lower_impl::peel_refs;
+use crate::mir::*;
+use aurora_ast::expr::ExprKind;
+use aurora_ast::nodes::AstNode;
+use aurora_ast::{Ast, ExprId, PatternId};
+use aurora_types::{PrimitiveType, Type};
+
+/// Targets of the `break` and `continue` expressions inside a loop
+pub(crate) struct LoopScope {
+    /// Block `continue` jumps to
+    continue_to: BlockId,
+    /// Block following the loop
+    exit: BlockId,
+    /// Values the loop is left with, and the blocks leaving it
+    breaks: Vec<(BlockId, Operand)>,
+}
+
+impl<D: Send + Sync + 'static> LoweringContext<D> {
+    /// Lower a while loop
+    pub(crate) fn lower_while(&mut self, condition: ExprId, body_id: u32, ast: &Ast, span: Span) {
+        let header = self.builder.new_loop_header();
+        let body = self.builder.new_block();
+        let exit = self.builder.new_block();
+
+        self.builder.build_jump(header, span);
+        self.builder.set_block(header);
+        let cond = self.lower_expr_real(condition, ast);
+        self.builder.build_branch(cond, body, exit, span);
+
+        self.builder.set_block(body);
+        self.lower_loop_body(body_id, header, exit, ast, span);
+        self.builder.seal_block(header);
+        self.builder.set_block(exit);
+    }
+
+    /// Lower an infinite loop, whose value is the value of its `break`s
+    pub(crate) fn lower_loop(&mut self, body_id: u32, expr_id: ExprId, ast: &Ast, span: Span) -> Operand {
+        let header = self.builder.new_loop_header();
+        let exit = self.builder.new_block();
+
+        self.builder.build_jump(header, span);
+        self.builder.set_block(header);
+        let mut breaks = self.lower_loop_body(body_id, header, exit, ast, span);
+        self.builder.seal_block(header);
+
+        // Without a `break` the code after the loop never runs
+        if breaks.is_empty() {
+            self.builder.set_unreachable();
+            return Operand::Const(Constant::Unit);
+        }
+        self.builder.set_block(exit);
+        let ty = self.value_type(expr_id);
+        match breaks.len() {
+            _ if ty == Type::Unit => Operand::Const(Constant::Unit),
+            1 => breaks.remove(0).1,
+            _ => Operand::Value(self.builder.build_phi(breaks, ty, span)),
+        }
+    }
+
+    /// Lower a for loop over a range or an array
+    pub(crate) fn lower_for(
+        &mut self,
+        pattern: PatternId,
+        iterator: ExprId,
+        body_id: u32,
+        ast: &Ast,
+        span: Span,
+    ) {
+        let i64_ty = Type::Primitive(PrimitiveType::I64);
+        let iter_kind = ast.arena.get_expr(iterator).map(|expr| &expr.kind);
+        let iter_ty = self.expr_type(iterator).map(|ty| peel_refs(&ty));
+
+        // The counter runs from `start` while it compares `bound_op` to `end`
+        let (start, end, bound_op, elements) = match (iter_kind, iter_ty) {
+            (
+                Some(ExprKind::Range {
+                    start: Some(start),
+                    end: Some(end),
+                    inclusive,
+                }),
+                Some(Type::Named { args, .. }),
+            ) => {
+                let start = self.lower_expr_real(*start, ast);
+                let end = self.lower_expr_real(*end, ast);
+                let elem_ty = args.first().cloned().unwrap_or(i64_ty.clone());
+                let bound_op = if *inclusive { BinOp::Le } else { BinOp::Lt };
+                (start, end, bound_op, Err(elem_ty))
+            }
+            (_, Some(Type::Array { elem, size: Some(len) })) => {
+                let base = self.lower_expr_real(iterator, ast);
+                let end = Operand::Const(Constant::Int(len as i64));
+                (Operand::Const(Constant::Int(0)), end, BinOp::Lt, Ok((base, *elem)))
+            }
+            _ => {
+                self.lower_expr_real(iterator, ast);
+                return;
+            }
+        };
+
+        let counter_ty = match &elements {
+            Err(elem_ty) => elem_ty.clone(),
+            Ok(_) => i64_ty.clone(),
+        };
+        let counter = self.builder.declare_var(counter_ty.clone(), span);
+        let init = self.builder.new_value(counter_ty.clone(), span);
+        self.builder.build_assign(init, start, span);
+        self.builder.write_var(counter, init);
+
+        let header = self.builder.new_loop_header();
+        let body = self.builder.new_block();
+        let step = self.builder.new_block();
+        let exit = self.builder.new_block();
+
+        self.builder.build_jump(header, span);
+        self.builder.set_block(header);
+        let index = self.builder.read_var(counter);
+        let bool_ty = Type::Primitive(PrimitiveType::Bool);
+        let cond = self.builder.build_binop(bound_op, Operand::Value(index), end, bool_ty, span);
+        self.builder.build_branch(Operand::Value(cond), body, exit, span);
+
+        // Bind the pattern to the counter or the element it indexes
+        self.builder.set_block(body);
+        let saved = self.builder.save_vars();
+        match elements {
+            Err(elem_ty) => self.bind_pattern(pattern, Operand::Value(index), &elem_ty, ast, span),
+            Ok((base, elem_ty)) => {
+                let size = Operand::Const(Constant::Int(self.layouts.size_of(&elem_ty) as i64));
+                let offset = self.builder.build_binop(BinOp::Mul, Operand::Value(index), size, i64_ty.clone(), span);
+                let addr = self.element_address(base, Operand::Value(offset), &elem_ty, span);
+                let value = self.read(addr, &elem_ty, span);
+                self.bind_pattern(pattern, value, &elem_ty, ast, span);
+            }
+        }
+        self.lower_loop_body(body_id, step, exit, ast, span);
+        self.builder.restore_vars(saved);
+
+        self.builder.set_block(step);
+        let index = self.builder.read_var(counter);
+        let one = Operand::Const(Constant::Int(1));
+        let next = self.builder.build_binop(BinOp::Add, Operand::Value(index), one, counter_ty, span);
+        self.builder.write_var(counter, next);
+        self.builder.build_jump(header, span);
+        self.builder.seal_block(header);
+
+        self.builder.set_block(exit);
+    }
+
+    /// Leave the innermost loop, passing it `value`
+    pub(crate) fn lower_break(&mut self, value: Option<ExprId>, ast: &Ast, span: Span) {
+        let value = match value {
+            Some(value) => self.lower_expr_real(value, ast),
+            None => Operand::Const(Constant::Unit),
+        };
+        if let (Some(block), Some(scope)) = (self.builder.current_block(), self.loops.last_mut()) {
+            scope.breaks.push((block, value));
+            let exit = scope.exit;
+            self.builder.build_jump(exit, span);
+        }
+        self.builder.set_unreachable();
+    }
+
+    /// Start the next iteration of the innermost loop
+    pub(crate) fn lower_continue(&mut self, span: Span) {
+        if let Some(scope) = self.loops.last() {
+            let target = scope.continue_to;
+            self.builder.build_jump(target, span);
+        }
+        self.builder.set_unreachable();
+    }
+
+    /// Lower a loop body in the current block, then continue with the next iteration
+    ///
+    /// Returns the `break`s leaving the loop.
+    fn lower_loop_body(
+        &mut self,
+        body_id: u32,
+        continue_to: BlockId,
+        exit: BlockId,
+        ast: &Ast,
+        span: Span,
+    ) -> Vec<(BlockId, Operand)> {
+        self.loops.push(LoopScope {
+            continue_to,
+            exit,
+            breaks: Vec::new(),
+        });
+        if let Some(AstNode::Block(body)) = ast.arena.get(body_id) {
+            self.lower_block(body, ast);
+        }
+        if !self.builder.is_terminated() {
+            self.builder.build_jump(continue_to, span);
+        }
+        self.loops.pop().map(|scope| scope.breaks).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mir::{BinOp, Constant, Function, Instruction, Operand};
+    use aurora_parser::Parser;
+    use aurora_types::TypeChecker;
+    use std::sync::Arc;
+
+    fn lower(source: &str, name: &str) -> Function {
+        let ast = Parser::new(source, "test.ax".to_string()).unwrap().parse();
+        let mut checker = TypeChecker::new(Arc::new(()));
+        let ast = checker.check(ast);
+        assert!(checker.errors().is_empty(), "{:?}", checker.errors());
+        let module = crate::lower_typed_ast_to_mir(ast, checker.type_map().clone(), Arc::new(()));
+        module.functions.into_values().find(|func| func.name == name).unwrap()
+    }
+
+    fn phis(func: &Function) -> Vec<(u32, usize)> {
+        let mut phis: Vec<(u32, usize)> = func
+            .blocks
+            .values()
+            .flat_map(|block| &block.instructions)
+            .filter_map(|inst| match inst {
+                Instruction::Phi { dest, inputs, .. } => Some((*dest, inputs.len())),
+                _ => None,
+            })
+            .collect();
+        phis.sort();
+        phis
+    }
+
+    #[test]
+    fn test_while_reassignment_uses_header_phis() {
+        let source = "fn sum(n: i64) -> i64 {\n\
+                let i = 0;\n\
+                let total = 0;\n\
+                while i < n { total = total + i; i = i + 1; }\n\
+                total\n\
+            }\n";
+        let func = lower(source, "sum");
+
+        // One phi per assigned variable; `n` is only read in the loop
+        let phis = phis(&func);
+        assert_eq!(phis.len(), 2, "{:?}", phis);
+        assert!(phis.iter().all(|&(_, inputs)| inputs == 2));
+        let returned = func.blocks.values().flat_map(|block| &block.instructions).find_map(|inst| match inst {
+            Instruction::Return { value: Some(Operand::Value(value)), .. } => Some(*value),
+            _ => None,
+        });
+        assert!(phis.iter().any(|&(phi, _)| Some(phi) == returned));
+    }
+
+    #[test]
+    fn test_loop_break_value_and_continue() {
+        let source = "fn find(limit: i64) -> i64 {\n\
+                let n = 0;\n\
+                loop {\n\
+                    n = n + 1;\n\
+                    if n % 2 == 0 { continue; }\n\
+                    if n > limit { break n * 10; }\n\
+                }\n\
+            }\n";
+        let func = lower(source, "find");
+
+        // The header merges the entry value and both back edges
+        let phis = phis(&func);
+        assert_eq!(phis.len(), 1, "{:?}", phis);
+        assert_eq!(phis[0].1, 3);
+        assert!(func.blocks.values().all(|block| !block.predecessors.is_empty() || block.id == func.entry));
+
+        // The values of the breaks meet in a phi after the loop, which is
+        // what the function returns
+        let source = "fn pick(c: bool) -> i32 {\n\
+                let v = loop { if c { break 5; } break 6; };\n\
+                v\n\
+            }\n";
+        let func = lower(source, "pick");
+        let instructions = || func.blocks.values().flat_map(|block| &block.instructions);
+        let mut returned = instructions().find_map(|inst| match inst {
+            Instruction::Return { value: Some(Operand::Value(value)), .. } => Some(*value),
+            _ => None,
+        });
+        while let Some(copied) = instructions().find_map(|inst| match inst {
+            Instruction::Assign { dest, value: Operand::Value(value), .. } if Some(*dest) == returned => Some(*value),
+            _ => None,
+        }) {
+            returned = Some(copied);
+        }
+        let mut inputs: Vec<Operand> = instructions()
+            .find_map(|inst| match inst {
+                Instruction::Phi { dest, inputs, .. } if Some(*dest) == returned => {
+                    Some(inputs.iter().map(|(_, value)| value.clone()).collect())
+                }
+                _ => None,
+            })
+            .expect("phi of the break values");
+        inputs.sort_by_key(|value| format!("{:?}", value));
+        assert_eq!(inputs, [Operand::Const(Constant::Int(5)), Operand::Const(Constant::Int(6))]);
+    }
+
+    #[test]
+    fn test_for_range_counter() {
+        let source = "fn total() -> i64 {\n\
+                let sum = 0;\n\
+                for i in 0..=10 { sum += i; }\n\
+                sum\n\
+            }\n";
+        let func = lower(source, "total");
+
+        let ops: Vec<BinOp> = func
+            .blocks
+            .values()
+            .flat_map(|block| &block.instructions)
+            .filter_map(|inst| match inst {
+                Instruction::BinOp { op, .. } => Some(*op),
+                _ => None,
+            })
+            .collect();
+        assert!(ops.contains(&BinOp::Le));
+        assert_eq!(ops.iter().filter(|op| **op == BinOp::Add).count(), 2);
+        assert_eq!(phis(&func).len(), 2);
+    }
+}
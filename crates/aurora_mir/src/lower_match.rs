@@ -0,0 +1,683 @@
+//! Match Lowering - Compile match expressions to decision trees
+//!
+//! Arms are compiled as a clause matrix: each row holds the patterns of one
+//! arm (or one alternative of an or-pattern) against a list of columns, the
+//! values being tested. The first row decides which column is examined next:
+//! tuples and structs are split into their fields, enums switch on the tag
+//! word, and literals and ranges are tested one at a time. Every value is
+//! tested at most once along a path, and each test only keeps the rows that
+//! can still match.
+//!
+//! Bindings are collected on the way down. When the first row needs no more
+//! tests it matches: its guard, if any, is evaluated there, and a failing
+//! guard continues with the remaining rows. Each arm body is lowered once,
+//! and the leaves reaching it jump there, passing their bindings through
+//! phis when there is more than one. The arm results meet in a join block.
+
+use super::lower::LoweringContext;
+use super::lower_impl::tag_field;
+use crate::mir::*;
+use aurora_ast::expr::{Literal, MatchArm};
+use aurora_ast::pattern::PatternKind;
+use aurora_ast::{Ast, ExprId, PatternId};
+use aurora_types::{EffectSet, PrimitiveType, Type};
+
+/// Pattern in the clause matrix
+#[derive(Debug, Clone, PartialEq)]
+enum Pat {
+    /// Matches anything
+    Wild,
+    /// Matches anything and binds it to a name
+    Bind(String),
+    /// Equal to a constant
+    Lit(Constant),
+    /// Integer within inclusive bounds
+    Range(i64, i64),
+    /// Tuple or struct, by field name (tuple fields are `0`, `1`, ...)
+    Fields(Vec<(String, Pat)>),
+    /// Enum variant with its fields by name
+    Variant(String, Vec<(String, Pat)>),
+    /// Any of the alternatives
+    Or(Vec<Pat>),
+}
+
+/// A value being matched
+#[derive(Debug, Clone)]
+struct Column {
+    value: Operand,
+    ty: Type,
+}
+
+/// Row of the clause matrix
+#[derive(Debug, Clone)]
+struct Row {
+    /// One pattern per column
+    pats: Vec<Pat>,
+    /// Values bound so far
+    bindings: Vec<Binding>,
+    /// Index of the arm the row belongs to
+    arm: usize,
+}
+
+/// Variable bound by a pattern
+#[derive(Debug, Clone)]
+struct Binding {
+    name: String,
+    value: Operand,
+    ty: Type,
+}
+
+/// Where the leaves of an arm jump to and what they bind
+#[derive(Debug, Default)]
+struct ArmTarget {
+    /// Block the arm body is lowered in, created by its first leaf
+    block: Option<BlockId>,
+    /// Block jumping to the arm and the bindings it passes
+    leaves: Vec<(BlockId, Vec<Binding>)>,
+}
+
+impl<D: Send + Sync + 'static> LoweringContext<D> {
+    /// Lower a match expression, returning the value of the arm taken
+    pub(crate) fn lower_match(
+        &mut self,
+        scrutinee: ExprId,
+        arms: &[MatchArm],
+        expr_id: ExprId,
+        ast: &Ast,
+        span: Span,
+    ) -> Operand {
+        let value = self.lower_expr_real(scrutinee, ast);
+        let column = Column {
+            value,
+            ty: self.value_type(scrutinee),
+        };
+        let rows = arms
+            .iter()
+            .enumerate()
+            .map(|(arm, match_arm)| Row {
+                pats: vec![Self::matrix_pattern(match_arm.pattern, ast)],
+                bindings: Vec::new(),
+                arm,
+            })
+            .collect();
+
+        let mut targets: Vec<ArmTarget> = arms.iter().map(|_| ArmTarget::default()).collect();
+        self.compile_rows(vec![column], rows, arms, &mut targets, ast, span);
+
+        // Lower each reachable arm once, with its bindings in scope
+        let join = self.builder.new_block();
+        let mut results = Vec::new();
+        for (arm, target) in arms.iter().zip(targets) {
+            let Some(block) = target.block else {
+                continue;
+            };
+            self.builder.set_block(block);
+            let saved = self.builder.save_vars();
+            self.bind_arm(&target.leaves, span);
+
+            let value = self.lower_expr_real(arm.body, ast);
+            if !self.builder.is_terminated() {
+                let exit = self.builder.current_block().unwrap_or(block);
+                results.push((exit, value));
+                self.builder.build_jump(join, span);
+            }
+            self.builder.restore_vars(saved);
+        }
+
+        // No arm reaching the join block means the match diverges
+        if results.is_empty() {
+            self.builder.set_unreachable();
+            return Operand::Const(Constant::Unit);
+        }
+        self.builder.set_block(join);
+        let ty = self.value_type(expr_id);
+        match results.len() {
+            _ if matches!(ty, Type::Unit | Type::Never) => Operand::Const(Constant::Unit),
+            1 => results.remove(0).1,
+            _ => Operand::Value(self.builder.build_phi(results, ty, span)),
+        }
+    }
+
+    /// Define the variables an arm binds, merging them when several leaves reach it
+    fn bind_arm(&mut self, leaves: &[(BlockId, Vec<Binding>)], span: Span) {
+        let Some((_, first)) = leaves.first() else {
+            return;
+        };
+
+        for binding in first {
+            let value = if leaves.len() == 1 {
+                let value = self.builder.new_value(binding.ty.clone(), span);
+                self.builder.build_assign(value, binding.value.clone(), span);
+                value
+            } else {
+                let inputs = leaves
+                    .iter()
+                    .filter_map(|(block, bindings)| {
+                        let bound = bindings.iter().find(|b| b.name == binding.name)?;
+                        Some((*block, bound.value.clone()))
+                    })
+                    .collect();
+                self.builder.build_phi(inputs, binding.ty.clone(), span)
+            };
+            self.builder.define_var(binding.name.clone(), value);
+        }
+    }
+
+    /// Emit the decision tree for a clause matrix in the current block
+    fn compile_rows(
+        &mut self,
+        mut columns: Vec<Column>,
+        rows: Vec<Row>,
+        arms: &[MatchArm],
+        targets: &mut [ArmTarget],
+        ast: &Ast,
+        span: Span,
+    ) {
+        let mut rows = Self::normalize_rows(rows, &columns);
+
+        if rows.is_empty() {
+            // Only reachable through guards or unlisted literals of an open type
+            let function = self.builder.current_func.as_ref().map_or(String::new(), |func| func.name.clone());
+            let panic = Operand::Const(Constant::String("aurora_panic".to_string()));
+            let args = vec![
+                Operand::Const(Constant::String("no match arm matched".to_string())),
+                Operand::Const(Constant::String(function)),
+                Operand::Const(Constant::Int(i64::from(span.line))),
+            ];
+            self.builder.build_call(panic, args, None, EffectSet::IO, span);
+            self.builder.build_return(None, span);
+            return;
+        }
+
+        let Some(col) = rows[0].pats.iter().position(|pat| *pat != Pat::Wild) else {
+            let row = rows.remove(0);
+            self.compile_leaf(row, columns, rows, arms, targets, ast, span);
+            return;
+        };
+
+        // Tests look through references to the value behind them
+        let column = self.deref_column(columns.remove(col), span);
+
+        match rows[0].pats[col].clone() {
+            Pat::Fields(_) => {
+                let fields = self.layouts.fields(&column.ty).unwrap_or_default();
+                let names: Vec<String> = fields.iter().map(|field| field.name.clone()).collect();
+                let new_columns = fields.iter().map(|field| Column {
+                    value: self.read_field(column.value.clone(), field, span),
+                    ty: field.ty.clone(),
+                });
+                let new_columns: Vec<Column> = new_columns.collect();
+                columns.splice(col..col, new_columns);
+
+                let rows = rows
+                    .into_iter()
+                    .map(|mut row| {
+                        let pat = row.pats.remove(col);
+                        let fields = match pat {
+                            Pat::Fields(fields) => fields,
+                            _ => Vec::new(),
+                        };
+                        row.pats.splice(col..col, Self::field_pats(&names, fields));
+                        row
+                    })
+                    .collect();
+                self.compile_rows(columns, rows, arms, targets, ast, span);
+            }
+            Pat::Variant(..) if self.layouts.is_enum(&column.ty) => {
+                self.compile_switch(column, col, columns, rows, arms, targets, ast, span)
+            }
+            // Tuple struct patterns destructure like tuples
+            Pat::Variant(..) => {
+                let rows = rows
+                    .into_iter()
+                    .map(|mut row| {
+                        if let Pat::Variant(_, fields) = std::mem::replace(&mut row.pats[col], Pat::Wild) {
+                            row.pats[col] = Pat::Fields(fields);
+                        }
+                        row
+                    })
+                    .collect();
+                columns.insert(col, column);
+                self.compile_rows(columns, rows, arms, targets, ast, span);
+            }
+            test => self.compile_test(test, column, col, columns, rows, arms, targets, ast, span),
+        }
+    }
+
+    /// Switch on the tag of an enum value
+    ///
+    /// Variants are tested in the order the rows mention them. When the rows
+    /// mention every variant the last one needs no test; otherwise the rows
+    /// with a wildcard handle the variants left over.
+    #[allow(clippy::too_many_arguments)]
+    fn compile_switch(
+        &mut self,
+        column: Column,
+        col: usize,
+        columns: Vec<Column>,
+        rows: Vec<Row>,
+        arms: &[MatchArm],
+        targets: &mut [ArmTarget],
+        ast: &Ast,
+        span: Span,
+    ) {
+        let variants = self.layouts.variants(&column.ty).unwrap_or_default();
+        let mut heads: Vec<String> = Vec::new();
+        for row in &rows {
+            if let Pat::Variant(name, _) = &row.pats[col] {
+                if !heads.contains(name) && variants.iter().any(|v| v.name == *name) {
+                    heads.push(name.clone());
+                }
+            }
+        }
+        let complete = heads.len() == variants.len();
+
+        let tag = self.read_field(column.value.clone(), &tag_field(), span);
+
+        for (i, name) in heads.iter().enumerate() {
+            let Some(variant) = variants.iter().find(|v| v.name == *name) else {
+                continue;
+            };
+
+            let last = complete && i + 1 == heads.len();
+            let next = if last {
+                None
+            } else {
+                let tag_const = Operand::Const(Constant::Int(variant.tag));
+                let cond = self.builder.build_binop(
+                    BinOp::Eq,
+                    tag.clone(),
+                    tag_const,
+                    Type::Primitive(PrimitiveType::Bool),
+                    span,
+                );
+                let case = self.builder.new_block();
+                let next = self.builder.new_block();
+                self.builder.build_branch(Operand::Value(cond), case, next, span);
+                self.builder.set_block(case);
+                Some(next)
+            };
+
+            // The variant's fields replace the tested column
+            let names: Vec<String> = variant.fields.iter().map(|field| field.name.clone()).collect();
+            let mut case_columns = columns.clone();
+            let fields: Vec<Column> = variant
+                .fields
+                .iter()
+                .map(|field| Column {
+                    value: self.read_field(column.value.clone(), field, span),
+                    ty: field.ty.clone(),
+                })
+                .collect();
+            case_columns.splice(col..col, fields);
+
+            let case_rows = rows
+                .iter()
+                .filter_map(|row| {
+                    let mut row = row.clone();
+                    let fields = match row.pats.remove(col) {
+                        Pat::Variant(other, _) if other != *name => return None,
+                        Pat::Variant(_, fields) => fields,
+                        _ => Vec::new(),
+                    };
+                    row.pats.splice(col..col, Self::field_pats(&names, fields));
+                    Some(row)
+                })
+                .collect();
+            self.compile_rows(case_columns, case_rows, arms, targets, ast, span);
+
+            match next {
+                Some(next) => self.builder.set_block(next),
+                None => return,
+            }
+        }
+
+        // Variants no row names explicitly
+        let default_rows = rows
+            .into_iter()
+            .filter_map(|mut row| match row.pats.remove(col) {
+                Pat::Variant(..) => None,
+                _ => Some(row),
+            })
+            .collect();
+        self.compile_rows(columns, default_rows, arms, targets, ast, span);
+    }
+
+    /// Test a scalar against a literal or range from the first row
+    ///
+    /// Rows with the same test match when it succeeds and are dropped when it
+    /// fails. Other literal and range rows are kept on both sides unless the
+    /// outcome of the test rules them out.
+    #[allow(clippy::too_many_arguments)]
+    fn compile_test(
+        &mut self,
+        test: Pat,
+        column: Column,
+        col: usize,
+        mut columns: Vec<Column>,
+        rows: Vec<Row>,
+        arms: &[MatchArm],
+        targets: &mut [ArmTarget],
+        ast: &Ast,
+        span: Span,
+    ) {
+        let bool_ty = Type::Primitive(PrimitiveType::Bool);
+        let yes = self.builder.new_block();
+        let no = self.builder.new_block();
+
+        match &test {
+            Pat::Lit(Constant::Bool(true)) => self.builder.build_branch(column.value.clone(), yes, no, span),
+            Pat::Lit(Constant::Bool(false)) => self.builder.build_branch(column.value.clone(), no, yes, span),
+            Pat::Lit(lit @ Constant::String(_)) => {
+                // Strings compare by contents
+                let str_eq = Operand::Const(Constant::String("aurora_str_eq".to_string()));
+                let args = vec![column.value.clone(), Operand::Const(lit.clone())];
+                let cond = self.builder.build_call(str_eq, args, Some(bool_ty), EffectSet::PURE, span);
+                let cond = cond.map_or(Operand::Const(Constant::Bool(false)), Operand::Value);
+                self.builder.build_branch(cond, yes, no, span);
+            }
+            Pat::Lit(lit) => {
+                let lit = Operand::Const(lit.clone());
+                let cond = self.builder.build_binop(BinOp::Eq, column.value.clone(), lit, bool_ty, span);
+                self.builder.build_branch(Operand::Value(cond), yes, no, span);
+            }
+            Pat::Range(start, end) => {
+                let start = Operand::Const(Constant::Int(*start));
+                let end = Operand::Const(Constant::Int(*end));
+                let above = self.builder.build_binop(BinOp::Ge, column.value.clone(), start, bool_ty.clone(), span);
+                let upper = self.builder.new_block();
+                self.builder.build_branch(Operand::Value(above), upper, no, span);
+                self.builder.set_block(upper);
+                let below = self.builder.build_binop(BinOp::Le, column.value.clone(), end, bool_ty, span);
+                self.builder.build_branch(Operand::Value(below), yes, no, span);
+            }
+            _ => {}
+        }
+
+        let mut yes_rows = Vec::new();
+        let mut no_rows = Vec::new();
+        for row in rows {
+            let pat = &row.pats[col];
+            if *pat == test {
+                let mut row = row;
+                row.pats[col] = Pat::Wild;
+                yes_rows.push(row);
+                continue;
+            }
+            match (&test, pat) {
+                (_, Pat::Wild) => {
+                    yes_rows.push(row.clone());
+                    no_rows.push(row);
+                }
+                // A different constant cannot be equal to the tested one
+                (Pat::Lit(_), Pat::Lit(_)) => no_rows.push(row),
+                (Pat::Lit(Constant::Int(n)), Pat::Range(start, end)) if !(start..=end).contains(&n) => {
+                    no_rows.push(row)
+                }
+                (Pat::Range(start, end), Pat::Lit(Constant::Int(n))) if !(start..=end).contains(&n) => {
+                    no_rows.push(row)
+                }
+                _ => {
+                    yes_rows.push(row.clone());
+                    no_rows.push(row);
+                }
+            }
+        }
+
+        columns.insert(col, column);
+        self.builder.set_block(yes);
+        self.compile_rows(columns.clone(), yes_rows, arms, targets, ast, span);
+        self.builder.set_block(no);
+        self.compile_rows(columns, no_rows, arms, targets, ast, span);
+    }
+
+    /// Reach the arm of a row whose patterns all matched
+    ///
+    /// A guarded arm evaluates its guard with the bindings in scope; when the
+    /// guard fails matching continues with the rows below.
+    #[allow(clippy::too_many_arguments)]
+    fn compile_leaf(
+        &mut self,
+        row: Row,
+        columns: Vec<Column>,
+        rest: Vec<Row>,
+        arms: &[MatchArm],
+        targets: &mut [ArmTarget],
+        ast: &Ast,
+        span: Span,
+    ) {
+        let mut bindings = row.bindings;
+
+        if let Some(guard) = arms[row.arm].guard {
+            let saved = self.builder.save_vars();
+            for binding in &mut bindings {
+                let value = self.builder.new_value(binding.ty.clone(), span);
+                self.builder.build_assign(value, binding.value.clone(), span);
+                self.builder.define_var(binding.name.clone(), value);
+                binding.value = Operand::Value(value);
+            }
+            let cond = self.lower_expr_real(guard, ast);
+            self.builder.restore_vars(saved);
+
+            let pass = self.builder.new_block();
+            let fail = self.builder.new_block();
+            self.builder.build_branch(cond, pass, fail, span);
+            self.builder.set_block(fail);
+            self.compile_rows(columns, rest, arms, targets, ast, span);
+            self.builder.set_block(pass);
+        }
+
+        let target = &mut targets[row.arm];
+        let block = match target.block {
+            Some(block) => block,
+            None => *target.block.insert(self.builder.new_block()),
+        };
+        let source = self.builder.current_block().unwrap_or(block);
+        target.leaves.push((source, bindings));
+        self.builder.build_jump(block, span);
+    }
+
+    /// Record bindings and expand or-patterns until the rows contain neither
+    fn normalize_rows(rows: Vec<Row>, columns: &[Column]) -> Vec<Row> {
+        let mut out = Vec::new();
+        let mut pending: Vec<Row> = rows.into_iter().rev().collect();
+
+        while let Some(mut row) = pending.pop() {
+            let mut split = None;
+            for (i, pat) in row.pats.iter_mut().enumerate() {
+                match pat {
+                    Pat::Bind(name) => {
+                        row.bindings.push(Binding {
+                            name: std::mem::take(name),
+                            value: columns[i].value.clone(),
+                            ty: columns[i].ty.clone(),
+                        });
+                        *pat = Pat::Wild;
+                    }
+                    Pat::Or(_) if split.is_none() => split = Some(i),
+                    _ => {}
+                }
+            }
+
+            match split {
+                Some(i) => {
+                    let Pat::Or(alts) = std::mem::replace(&mut row.pats[i], Pat::Wild) else {
+                        unreachable!()
+                    };
+                    // Alternatives are tried in order, so push them in reverse
+                    for alt in alts.into_iter().rev() {
+                        let mut row = row.clone();
+                        row.pats[i] = alt;
+                        pending.push(row);
+                    }
+                }
+                None => out.push(row),
+            }
+        }
+
+        out
+    }
+
+    /// Patterns for each of `names`, wildcards for fields a pattern leaves out
+    fn field_pats(names: &[String], mut fields: Vec<(String, Pat)>) -> Vec<Pat> {
+        names
+            .iter()
+            .map(|name| match fields.iter().position(|(field, _)| field == name) {
+                Some(i) => fields.swap_remove(i).1,
+                None => Pat::Wild,
+            })
+            .collect()
+    }
+
+    /// Load through references until the column holds the value itself
+    fn deref_column(&mut self, mut column: Column, span: Span) -> Column {
+        while let Type::Ref { inner, .. } | Type::Ptr { inner, .. } = &column.ty {
+            let inner = (**inner).clone();
+            column.value = self.read(column.value, &inner, span);
+            column.ty = inner;
+        }
+        column
+    }
+
+    /// Clause matrix pattern for an AST pattern
+    fn matrix_pattern(pattern: PatternId, ast: &Ast) -> Pat {
+        let Some(pat) = ast.arena.get_pattern(pattern) else {
+            return Pat::Wild;
+        };
+
+        let fields = |fields: &[PatternId]| -> Vec<(String, Pat)> {
+            fields
+                .iter()
+                .enumerate()
+                .map(|(i, &field)| (i.to_string(), Self::matrix_pattern(field, ast)))
+                .collect()
+        };
+
+        match &pat.kind {
+            PatternKind::Wildcard | PatternKind::Rest => Pat::Wild,
+            PatternKind::Ident { name, .. } => Pat::Bind(name.clone()),
+            PatternKind::Literal(lit) => Pat::Lit(literal_constant(lit)),
+            PatternKind::Range { start, end, inclusive } => {
+                let bound = |id: PatternId| match ast.arena.get_pattern(id).map(|pat| &pat.kind) {
+                    Some(PatternKind::Literal(Literal::Int(n, _))) => Some(*n as i64),
+                    Some(PatternKind::Literal(Literal::Char(c))) => Some(*c as i64),
+                    _ => None,
+                };
+                match (bound(**start), bound(**end)) {
+                    (Some(start), Some(end)) => Pat::Range(start, if *inclusive { end } else { end - 1 }),
+                    _ => Pat::Range(1, 0),
+                }
+            }
+            PatternKind::Tuple(elems) => Pat::Fields(fields(elems)),
+            PatternKind::TupleStruct { path, fields: elems } => {
+                Pat::Variant(path.segments.last().cloned().unwrap_or_default(), fields(elems))
+            }
+            PatternKind::Path(path) => Pat::Variant(path.segments.last().cloned().unwrap_or_default(), Vec::new()),
+            PatternKind::Struct { path, fields, .. } => {
+                let fields = fields
+                    .iter()
+                    .map(|field| {
+                        let pat = match field.pattern {
+                            Some(pattern) => Self::matrix_pattern(pattern, ast),
+                            // Shorthand `{ x }` binds the field's own name
+                            None => Pat::Bind(field.name.clone()),
+                        };
+                        (field.name.clone(), pat)
+                    })
+                    .collect();
+                match path.segments.as_slice() {
+                    // A path with several segments names an enum variant
+                    [_, .., variant] => Pat::Variant(variant.clone(), fields),
+                    _ => Pat::Fields(fields),
+                }
+            }
+            PatternKind::Or(alts) => Pat::Or(alts.iter().map(|&alt| Self::matrix_pattern(alt, ast)).collect()),
+            // Columns are dereferenced before they are tested
+            PatternKind::Ref { inner, .. } => Self::matrix_pattern(**inner, ast),
+        }
+    }
+}
+
+/// MIR constant of a literal pattern
+fn literal_constant(lit: &Literal) -> Constant {
+    match lit {
+        Literal::Int(n, _) => Constant::Int(*n as i64),
+        Literal::Float(f, _) => Constant::Float(f.to_bits()),
+        Literal::String(text) => Constant::String(text.clone()),
+        Literal::Bool(b) => Constant::Bool(*b),
+        Literal::Char(c) => Constant::Int(*c as i64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mir::{BinOp, Constant, Instruction, Operand};
+    use aurora_parser::Parser;
+    use aurora_types::TypeChecker;
+    use std::sync::Arc;
+
+    fn lower(source: &str, name: &str) -> Vec<Instruction> {
+        let ast = Parser::new(source, "test.ax".to_string()).unwrap().parse();
+        let mut checker = TypeChecker::new(Arc::new(()));
+        let ast = checker.check(ast);
+        assert!(checker.errors().is_empty(), "{:?}", checker.errors());
+        let module = crate::lower_typed_ast_to_mir(ast, checker.type_map().clone(), Arc::new(()));
+        let func = module.functions.values().find(|func| func.name == name).unwrap();
+        func.blocks
+            .values()
+            .flat_map(|block| block.instructions.clone())
+            .collect()
+    }
+
+    fn callees(insts: &[Instruction]) -> Vec<&str> {
+        insts
+            .iter()
+            .filter_map(|inst| match inst {
+                Instruction::Call {
+                    func: Operand::Const(Constant::String(name)),
+                    ..
+                } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_enum_match_switches_on_tag() {
+        let source = "enum Shape { Circle(i64), Square(i64), Empty }\n\
+            fn area(s: Shape) -> i64 {\n\
+                match s { Shape::Circle(r) => r * 3, Shape::Square(w) => w * w, Shape::Empty => 0 }\n\
+            }\n";
+        let insts = lower(source, "area");
+
+        // A complete set of variants needs no test for the last one
+        let tag_tests = insts
+            .iter()
+            .filter(|inst| matches!(inst, Instruction::BinOp { op: BinOp::Eq, .. }))
+            .count();
+        assert_eq!(tag_tests, 2);
+        let phi_inputs = insts.iter().find_map(|inst| match inst {
+            Instruction::Phi { inputs, .. } => Some(inputs.len()),
+            _ => None,
+        });
+        assert_eq!(phi_inputs, Some(3));
+        assert!(callees(&insts).is_empty());
+    }
+
+    #[test]
+    fn test_guarded_arm_falls_through() {
+        let source = "fn sign(n: i64) -> i64 {\n\
+                match n { 0 => 0, x if x > 0 => 1, _ => 2 }\n\
+            }\n";
+        let insts = lower(source, "sign");
+
+        let count = |op: BinOp| {
+            insts
+                .iter()
+                .filter(|inst| matches!(inst, Instruction::BinOp { op: found, .. } if *found == op))
+                .count()
+        };
+        assert_eq!((count(BinOp::Eq), count(BinOp::Gt)), (1, 1));
+        assert!(callees(&insts).is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+//! Promotion of stack slots to SSA values (mem2reg)
+//!
+//! An alloca of a scalar type whose address is only ever loaded from and
+//! stored to never needs to live in memory. Phis for it are placed at the
+//! iterated dominance frontier of the blocks storing to it, then a walk over
+//! the dominator tree replaces each load with the value stored last on the
+//! way there and drops the stores. Loads that no store reaches read zero.
+
+use crate::cfg::{DominatorTree, CFG};
+use crate::mir::*;
+use crate::opt::OptPass;
+use aurora_types::{PrimitiveType, Type};
+use std::collections::{HashMap, HashSet};
+
+/// Promote non-escaping scalar allocas to SSA values
+pub struct Mem2Reg;
+
+impl OptPass for Mem2Reg {
+    fn run(&mut self, func: &mut Function) -> bool {
+        let slots = promotable_slots(func);
+        if slots.is_empty() {
+            return false;
+        }
+
+        func.remove_unreachable_blocks();
+        let cfg = CFG::build(func);
+        let dom = DominatorTree::compute(&cfg);
+        let phis = place_phis(func, &slots, &dom);
+
+        let mut renamer = Renamer {
+            slots: &slots,
+            phis: &phis,
+            current: HashMap::new(),
+            replacements: HashMap::new(),
+        };
+        renamer.rename(func, &cfg, &dom);
+        let replacements = renamer.replacements;
+        func.replace_uses(&replacements);
+        true
+    }
+
+    fn name(&self) -> &str {
+        "mem2reg"
+    }
+}
+
+/// Allocas of scalars whose address is only used to load and store, with their types
+fn promotable_slots(func: &Function) -> HashMap<ValueId, Type> {
+    let mut slots: HashMap<ValueId, Type> = func
+        .blocks
+        .values()
+        .flat_map(|block| &block.instructions)
+        .filter_map(|inst| match inst {
+            Instruction::Alloca { dest, ty, .. } if is_scalar(ty) => Some((*dest, ty.clone())),
+            _ => None,
+        })
+        .collect();
+
+    for inst in func.blocks.values().flat_map(|block| &block.instructions) {
+        let escaping: Vec<&Operand> = match inst {
+            Instruction::Load { .. } => continue,
+            Instruction::Store { value, .. } => vec![value],
+            _ => inst.operands(),
+        };
+        for operand in escaping {
+            if let Operand::Value(value) = operand {
+                slots.remove(value);
+            }
+        }
+    }
+    slots
+}
+
+/// Whether values of a type fit in a register
+pub(crate) fn is_scalar(ty: &Type) -> bool {
+    matches!(ty, Type::Primitive(_) | Type::Ref { .. } | Type::Ptr { .. })
+}
+
+/// Value a slot holds before anything is stored to it
+fn zero(ty: &Type) -> Operand {
+    Operand::Const(match ty {
+        Type::Primitive(PrimitiveType::Bool) => Constant::Bool(false),
+        Type::Primitive(PrimitiveType::F32 | PrimitiveType::F64) => Constant::Float(0),
+        _ => Constant::Int(0),
+    })
+}
+
+/// Insert empty phis at the iterated dominance frontier of each slot's stores
+///
+/// Returns the slot each phi stands for.
+fn place_phis(func: &mut Function, slots: &HashMap<ValueId, Type>, dom: &DominatorTree) -> HashMap<ValueId, ValueId> {
+    let mut stores: HashMap<ValueId, Vec<BlockId>> = HashMap::new();
+    for block in func.blocks.values() {
+        for inst in &block.instructions {
+            if let Instruction::Store { ptr: Operand::Value(slot), .. } = inst {
+                if slots.contains_key(slot) {
+                    stores.entry(*slot).or_default().push(block.id);
+                }
+            }
+        }
+    }
+
+    let mut phis = HashMap::new();
+    let mut ordered: Vec<_> = stores.into_iter().collect();
+    ordered.sort_unstable_by_key(|(slot, _)| *slot);
+    for (slot, mut worklist) in ordered {
+        let mut placed = HashSet::new();
+        let defs: HashSet<BlockId> = worklist.iter().copied().collect();
+        while let Some(block) = worklist.pop() {
+            for &frontier in dom.frontier(block) {
+                if !placed.insert(frontier) {
+                    continue;
+                }
+                let span = func.block(frontier).and_then(|bb| bb.instructions.first()).map_or(Span::dummy(), Instruction::span);
+                let dest = func.new_value(slots[&slot].clone(), span);
+                if let Some(bb) = func.block_mut(frontier) {
+                    bb.instructions.insert(0, Instruction::Phi { dest, inputs: Vec::new(), span });
+                }
+                phis.insert(dest, slot);
+                if !defs.contains(&frontier) {
+                    worklist.push(frontier);
+                }
+            }
+        }
+    }
+    phis
+}
+
+/// Dominator tree walk replacing loads with the values reaching them
+struct Renamer<'a> {
+    slots: &'a HashMap<ValueId, Type>,
+    /// Slot each placed phi stands for
+    phis: &'a HashMap<ValueId, ValueId>,
+    /// Stack of values each slot holds along the current dominator tree path
+    current: HashMap<ValueId, Vec<Operand>>,
+    /// Value each removed load is replaced with
+    replacements: HashMap<ValueId, Operand>,
+}
+
+impl Renamer<'_> {
+    fn rename(&mut self, func: &mut Function, cfg: &CFG, dom: &DominatorTree) {
+        let mut children: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+        for &block in &cfg.reverse_post_order {
+            if let Some(idom) = dom.idom(block).filter(|&idom| idom != block) {
+                children.entry(idom).or_default().push(block);
+            }
+        }
+
+        // Each block is entered, then left once its dominator subtree is done
+        let mut stack = vec![(cfg.entry, false)];
+        let mut pushed: HashMap<BlockId, Vec<ValueId>> = HashMap::new();
+        while let Some((block, done)) = stack.pop() {
+            if done {
+                for slot in pushed.remove(&block).unwrap_or_default() {
+                    self.current.get_mut(&slot).and_then(Vec::pop);
+                }
+                continue;
+            }
+            pushed.insert(block, self.rename_block(func, block));
+            self.fill_successor_phis(func, cfg, block);
+            stack.push((block, true));
+            for &child in children.get(&block).into_iter().flatten().rev() {
+                stack.push((child, false));
+            }
+        }
+    }
+
+    /// Rewrite a block's slot accesses, returning the slots it defined
+    fn rename_block(&mut self, func: &mut Function, block: BlockId) -> Vec<ValueId> {
+        let Some(bb) = func.block_mut(block) else {
+            return Vec::new();
+        };
+        let mut defined = Vec::new();
+        let mut kept = Vec::with_capacity(bb.instructions.len());
+        for inst in std::mem::take(&mut bb.instructions) {
+            match &inst {
+                Instruction::Phi { dest, .. } if self.phis.contains_key(dest) => {
+                    let slot = self.phis[dest];
+                    self.current.entry(slot).or_default().push(Operand::Value(*dest));
+                    defined.push(slot);
+                }
+                Instruction::Alloca { dest, .. } if self.slots.contains_key(dest) => continue,
+                Instruction::Load { dest, ptr: Operand::Value(slot), .. } if self.slots.contains_key(slot) => {
+                    let value = self.value_of(*slot);
+                    self.replacements.insert(*dest, value);
+                    continue;
+                }
+                Instruction::Store { ptr: Operand::Value(slot), value, .. } if self.slots.contains_key(slot) => {
+                    self.current.entry(*slot).or_default().push(value.clone());
+                    defined.push(*slot);
+                    continue;
+                }
+                _ => {}
+            }
+            kept.push(inst);
+        }
+        bb.instructions = kept;
+        defined
+    }
+
+    /// Give the placed phis of each successor their input from `block`
+    fn fill_successor_phis(&mut self, func: &mut Function, cfg: &CFG, block: BlockId) {
+        for &succ in cfg.succs(block) {
+            let Some(bb) = func.block(succ) else {
+                continue;
+            };
+            let inputs: Vec<(ValueId, Operand)> = bb
+                .instructions
+                .iter()
+                .filter_map(|inst| match inst {
+                    Instruction::Phi { dest, .. } => self.phis.get(dest).map(|&slot| (*dest, self.value_of(slot))),
+                    _ => None,
+                })
+                .collect();
+            let Some(bb) = func.block_mut(succ) else {
+                continue;
+            };
+            for inst in &mut bb.instructions {
+                if let Instruction::Phi { dest, inputs: phi_inputs, .. } = inst {
+                    if let Some((_, value)) = inputs.iter().find(|(phi, _)| phi == dest) {
+                        phi_inputs.push((block, value.clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Value a slot holds at the current point of the walk
+    fn value_of(&self, slot: ValueId) -> Operand {
+        match self.current.get(&slot).and_then(|values| values.last()) {
+            Some(value) => value.clone(),
+            None => zero(&self.slots[&slot]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lower::MirBuilder;
+    use aurora_types::EffectSet;
+
+    fn i64_ty() -> Type {
+        Type::Primitive(PrimitiveType::I64)
+    }
+
+    fn count(func: &Function, pred: impl Fn(&Instruction) -> bool) -> usize {
+        func.blocks.values().flat_map(|block| &block.instructions).filter(|inst| pred(inst)).count()
+    }
+
+    #[test]
+    fn test_promotes_slot_across_branches() {
+        // slot = 1; if c { slot = 2 }; return slot
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "test".to_string(), i64_ty(), EffectSet::PURE);
+        let cond = builder.new_value(Type::Primitive(PrimitiveType::Bool), Span::dummy());
+        let slot = builder.build_alloca(i64_ty(), Span::dummy());
+        builder.build_store(Operand::Value(slot), Operand::Const(Constant::Int(1)), Span::dummy());
+        let then_bb = builder.new_block();
+        let join_bb = builder.new_block();
+        builder.build_branch(Operand::Value(cond), then_bb, join_bb, Span::dummy());
+        builder.set_block(then_bb);
+        builder.build_store(Operand::Value(slot), Operand::Const(Constant::Int(2)), Span::dummy());
+        builder.build_jump(join_bb, Span::dummy());
+        builder.set_block(join_bb);
+        let value = builder.build_load(Operand::Value(slot), i64_ty(), Span::dummy());
+        builder.build_return(Some(Operand::Value(value)), Span::dummy());
+        let mut func = builder.finish_function().unwrap();
+        let entry = func.entry;
+
+        assert!(Mem2Reg.run(&mut func));
+        let memory = |inst: &Instruction| {
+            matches!(inst, Instruction::Alloca { .. } | Instruction::Load { .. } | Instruction::Store { .. })
+        };
+        assert_eq!(count(&func, memory), 0);
+
+        let join = func.block(join_bb).unwrap();
+        let Some(Instruction::Phi { dest, inputs, .. }) = join.instructions.first() else {
+            panic!("no phi in {:?}", join.instructions);
+        };
+        let mut inputs = inputs.clone();
+        inputs.sort_by_key(|(pred, _)| *pred);
+        assert_eq!(
+            inputs,
+            vec![(entry, Operand::Const(Constant::Int(1))), (then_bb, Operand::Const(Constant::Int(2)))]
+        );
+        assert_eq!(
+            join.instructions.last(),
+            Some(&Instruction::Return { value: Some(Operand::Value(*dest)), span: Span::dummy() })
+        );
+        assert!(!Mem2Reg.run(&mut func));
+    }
+
+    #[test]
+    fn test_escaping_slot_is_kept() {
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "test".to_string(), Type::Unit, EffectSet::PURE);
+        let slot = builder.build_alloca(i64_ty(), Span::dummy());
+        builder.build_store(Operand::Value(slot), Operand::Const(Constant::Int(1)), Span::dummy());
+        let callee = Operand::Const(Constant::String("fill".to_string()));
+        builder.build_call(callee, vec![Operand::Value(slot)], None, EffectSet::PURE, Span::dummy());
+        builder.build_return(None, Span::dummy());
+        let mut func = builder.finish_function().unwrap();
+
+        assert!(!Mem2Reg.run(&mut func));
+        assert_eq!(count(&func, |inst| matches!(inst, Instruction::Store { .. })), 1);
+    }
+}
@@ -17,7 +17,7 @@
 
 use aurora_types::{EffectSet, Type};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// Unique identifier for MIR values (SSA variables)
 pub type ValueId = u32;
@@ -48,6 +48,10 @@ pub struct Span {
     pub end: usize,
     /// Source file ID
     pub file_id: usize,
+    /// Starting line (1-indexed, 0 if unknown)
+    pub line: u32,
+    /// Starting column (1-indexed, 0 if unknown)
+    pub column: u32,
 }
 
 impl Span {
@@ -57,19 +61,43 @@ impl Span {
             start,
             end,
             file_id,
+            line: 0,
+            column: 0,
         }
     }
 
     /// Create a dummy span for testing
     pub fn dummy() -> Self {
+        Self::new(0, 0, 0)
+    }
+
+    /// Set the line and column the span starts at
+    pub fn with_position(self, line: u32, column: u32) -> Self {
+        Self { line, column, ..self }
+    }
+
+    /// The span moved by `bytes` and `lines`, for code whose text moved
+    /// within its file; dummy spans and unknown lines are left alone
+    pub fn shifted(self, bytes: isize, lines: i32) -> Self {
+        if self == Self::dummy() {
+            return self;
+        }
         Self {
-            start: 0,
-            end: 0,
-            file_id: 0,
+            start: self.start.saturating_add_signed(bytes),
+            end: self.end.saturating_add_signed(bytes),
+            line: if self.line == 0 { 0 } else { self.line.saturating_add_signed(lines) },
+            ..self
         }
     }
 }
 
+impl From<aurora_ast::Span> for Span {
+    fn from(span: aurora_ast::Span) -> Self {
+        Self::new(span.start as usize, span.end as usize, span.file_id as usize)
+            .with_position(span.line, span.column)
+    }
+}
+
 /// MIR Instruction
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Instruction {
@@ -191,6 +219,22 @@ impl Instruction {
         }
     }
 
+    /// Mutable access to the destination value ID (if any)
+    pub fn dest_mut(&mut self) -> Option<&mut ValueId> {
+        match self {
+            Instruction::Assign { dest, .. }
+            | Instruction::BinOp { dest, .. }
+            | Instruction::UnaryOp { dest, .. }
+            | Instruction::Phi { dest, .. }
+            | Instruction::Load { dest, .. }
+            | Instruction::Alloca { dest, .. }
+            | Instruction::Cast { dest, .. }
+            | Instruction::GetElement { dest, .. } => Some(dest),
+            Instruction::Call { dest, .. } => dest.as_mut(),
+            _ => None,
+        }
+    }
+
     /// Get span for diagnostics
     pub fn span(&self) -> Span {
         match self {
@@ -210,6 +254,61 @@ impl Instruction {
         }
     }
 
+    /// Mutable access to the source span
+    pub fn span_mut(&mut self) -> &mut Span {
+        match self {
+            Instruction::Assign { span, .. }
+            | Instruction::BinOp { span, .. }
+            | Instruction::UnaryOp { span, .. }
+            | Instruction::Call { span, .. }
+            | Instruction::Return { span, .. }
+            | Instruction::Branch { span, .. }
+            | Instruction::Jump { span, .. }
+            | Instruction::Phi { span, .. }
+            | Instruction::Load { span, .. }
+            | Instruction::Store { span, .. }
+            | Instruction::Alloca { span, .. }
+            | Instruction::Cast { span, .. }
+            | Instruction::GetElement { span, .. } => span,
+        }
+    }
+
+    /// Operands read by this instruction
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Instruction::Assign { value, .. }
+            | Instruction::UnaryOp { value, .. }
+            | Instruction::Cast { value, .. } => vec![value],
+            Instruction::BinOp { lhs, rhs, .. } => vec![lhs, rhs],
+            Instruction::Call { func, args, .. } => std::iter::once(func).chain(args.iter()).collect(),
+            Instruction::Return { value, .. } => value.iter().collect(),
+            Instruction::Branch { cond, .. } => vec![cond],
+            Instruction::Phi { inputs, .. } => inputs.iter().map(|(_, input)| input).collect(),
+            Instruction::Load { ptr, .. } => vec![ptr],
+            Instruction::Store { ptr, value, .. } => vec![ptr, value],
+            Instruction::GetElement { base, index, .. } => vec![base, index],
+            Instruction::Jump { .. } | Instruction::Alloca { .. } => Vec::new(),
+        }
+    }
+
+    /// Mutable access to the operands read by this instruction
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Instruction::Assign { value, .. }
+            | Instruction::UnaryOp { value, .. }
+            | Instruction::Cast { value, .. } => vec![value],
+            Instruction::BinOp { lhs, rhs, .. } => vec![lhs, rhs],
+            Instruction::Call { func, args, .. } => std::iter::once(func).chain(args.iter_mut()).collect(),
+            Instruction::Return { value, .. } => value.iter_mut().collect(),
+            Instruction::Branch { cond, .. } => vec![cond],
+            Instruction::Phi { inputs, .. } => inputs.iter_mut().map(|(_, input)| input).collect(),
+            Instruction::Load { ptr, .. } => vec![ptr],
+            Instruction::Store { ptr, value, .. } => vec![ptr, value],
+            Instruction::GetElement { base, index, .. } => vec![base, index],
+            Instruction::Jump { .. } | Instruction::Alloca { .. } => Vec::new(),
+        }
+    }
+
     /// Get effects of this instruction
     pub fn effects(&self) -> EffectSet {
         match self {
@@ -319,6 +418,19 @@ impl BasicBlock {
     }
 }
 
+/// A named source variable, for debug info
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DebugVar {
+    /// Variable name
+    pub name: String,
+    /// Declared type
+    pub ty: Type,
+    /// Every value the variable holds, the first being its initial value
+    pub values: Vec<ValueId>,
+    /// Declaration span
+    pub span: Span,
+}
+
 /// MIR Function
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Function {
@@ -338,6 +450,10 @@ pub struct Function {
     pub values: HashMap<ValueId, Value>,
     /// Effect signature
     pub effects: EffectSet,
+    /// Declaration span
+    pub span: Span,
+    /// Named variables, for debug info
+    pub debug_vars: Vec<DebugVar>,
 }
 
 impl Function {
@@ -352,6 +468,26 @@ impl Function {
             entry: 0,
             values: HashMap::new(),
             effects,
+            span: Span::dummy(),
+            debug_vars: Vec::new(),
+        }
+    }
+
+    /// Move every span in the function by `bytes` and `lines` (see
+    /// [`Span::shifted`])
+    pub fn shift_spans(&mut self, bytes: isize, lines: i32) {
+        self.span = self.span.shifted(bytes, lines);
+        for value in self.values.values_mut() {
+            value.span = value.span.shifted(bytes, lines);
+        }
+        for var in &mut self.debug_vars {
+            var.span = var.span.shifted(bytes, lines);
+        }
+        for block in self.blocks.values_mut() {
+            for inst in &mut block.instructions {
+                let span = inst.span_mut();
+                *span = span.shifted(bytes, lines);
+            }
         }
     }
 
@@ -379,6 +515,69 @@ impl Function {
     pub fn value(&self, id: ValueId) -> Option<&Value> {
         self.values.get(&id)
     }
+
+    /// Create a value with an ID unused in this function
+    pub fn new_value(&mut self, ty: Type, span: Span) -> ValueId {
+        let id = self.values.keys().chain(&self.params).map(|&id| id + 1).max().unwrap_or(0);
+        self.add_value(Value { id, ty, span });
+        id
+    }
+
+    /// Replace every use of the values in `replacements`
+    ///
+    /// Replacements may refer to values that are replaced in turn.
+    pub fn replace_uses(&mut self, replacements: &HashMap<ValueId, Operand>) {
+        let resolve = |mut operand: Operand| {
+            while let Operand::Value(value) = operand {
+                match replacements.get(&value) {
+                    Some(next) if *next != operand => operand = next.clone(),
+                    _ => break,
+                }
+            }
+            operand
+        };
+        for block in self.blocks.values_mut() {
+            for inst in &mut block.instructions {
+                for operand in inst.operands_mut() {
+                    if matches!(operand, Operand::Value(value) if replacements.contains_key(value)) {
+                        *operand = resolve(operand.clone());
+                    }
+                }
+            }
+        }
+        for var in &mut self.debug_vars {
+            for value in &mut var.values {
+                if let Operand::Value(next) = resolve(Operand::Value(*value)) {
+                    *value = next;
+                }
+            }
+        }
+    }
+
+    /// Remove the blocks control never reaches from the entry block
+    ///
+    /// Their edges and the phi inputs flowing from them are removed too.
+    pub fn remove_unreachable_blocks(&mut self) {
+        let mut reachable = HashSet::new();
+        let mut worklist = vec![self.entry];
+        while let Some(block) = worklist.pop() {
+            if reachable.insert(block) {
+                if let Some(block) = self.blocks.get(&block) {
+                    worklist.extend(block.successors.iter().copied());
+                }
+            }
+        }
+
+        self.blocks.retain(|id, _| reachable.contains(id));
+        for block in self.blocks.values_mut() {
+            block.predecessors.retain(|pred| reachable.contains(pred));
+            for inst in &mut block.instructions {
+                if let Instruction::Phi { inputs, .. } = inst {
+                    inputs.retain(|(pred, _)| reachable.contains(pred));
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -432,4 +631,12 @@ mod tests {
         assert_eq!(span.end, 20);
         assert_eq!(span.file_id, 0);
     }
+
+    #[test]
+    fn test_span_shifted() {
+        let span = Span::new(10, 20, 1).with_position(3, 5).shifted(-4, -1);
+        assert_eq!((span.start, span.end, span.file_id), (6, 16, 1));
+        assert_eq!((span.line, span.column), (2, 5));
+        assert_eq!(Span::dummy().shifted(7, 2), Span::dummy());
+    }
 }
@@ -2,6 +2,7 @@
 //!
 //! This module implements all MIR-level optimizations:
 //! - SROA (Scalar Replacement of Aggregates)
+//! - mem2reg (promotion of stack slots to SSA values)
 //! - GVN (Global Value Numbering)
 //! - LICM (Loop-Invariant Code Motion)
 //! - DCE (Dead Code Elimination)
@@ -14,7 +15,12 @@
 //! - Loop SIMD hints
 
 use crate::cfg::{DominatorTree, Loop, CFG};
+pub use crate::inline::Inlining;
+pub use crate::mem2reg::Mem2Reg;
 use crate::mir::*;
+use crate::pass_manager::ModulePass;
+pub use crate::sroa::SROA;
+use crate::MirModule;
 use std::collections::{HashMap, HashSet};
 
 /// Optimization level
@@ -113,54 +119,11 @@ impl OptPass for DeadCodeElimination {
         let mut changed = false;
         let mut live = HashSet::new();
 
-        // Mark all values used in non-dead instructions
-        for block in func.blocks.values() {
-            for inst in &block.instructions {
-                match inst {
-                    Instruction::Return { value, .. } => {
-                        if let Some(Operand::Value(v)) = value {
-                            live.insert(*v);
-                        }
-                    }
-                    Instruction::Branch { cond, .. } => {
-                        if let Operand::Value(v) = cond {
-                            live.insert(*v);
-                        }
-                    }
-                    Instruction::Call { func: f, args, .. } => {
-                        if let Operand::Value(v) = f {
-                            live.insert(*v);
-                        }
-                        for arg in args {
-                            if let Operand::Value(v) = arg {
-                                live.insert(*v);
-                            }
-                        }
-                    }
-                    Instruction::Store { ptr, value, .. } => {
-                        if let Operand::Value(v) = ptr {
-                            live.insert(*v);
-                        }
-                        if let Operand::Value(v) = value {
-                            live.insert(*v);
-                        }
-                    }
-                    Instruction::BinOp { lhs, rhs, dest, .. } => {
-                        if let Operand::Value(v) = lhs {
-                            live.insert(*v);
-                        }
-                        if let Operand::Value(v) = rhs {
-                            live.insert(*v);
-                        }
-                        live.insert(*dest);
-                    }
-                    Instruction::UnaryOp { value, dest, .. } => {
-                        if let Operand::Value(v) = value {
-                            live.insert(*v);
-                        }
-                        live.insert(*dest);
-                    }
-                    _ => {}
+        // Mark all values used by any instruction
+        for inst in func.blocks.values().flat_map(|block| &block.instructions) {
+            for operand in inst.operands() {
+                if let Operand::Value(v) = operand {
+                    live.insert(*v);
                 }
             }
         }
@@ -320,9 +283,17 @@ pub struct GlobalValueNumbering;
 impl OptPass for GlobalValueNumbering {
     fn run(&mut self, func: &mut Function) -> bool {
         let mut changed = false;
-        let mut value_map: HashMap<String, ValueId> = HashMap::new();
+        let mut value_map: HashMap<String, Vec<(ValueId, BlockId)>> = HashMap::new();
 
-        for block in func.blocks.values_mut() {
+        // Visit dominators first; an earlier value is only reused where its
+        // block dominates
+        let cfg = CFG::build(func);
+        let dom_tree = DominatorTree::compute(&cfg);
+
+        for &block_id in &cfg.reverse_post_order {
+            let Some(block) = func.blocks.get_mut(&block_id) else {
+                continue;
+            };
             let mut new_instructions = Vec::new();
 
             for inst in &block.instructions {
@@ -330,7 +301,11 @@ impl OptPass for GlobalValueNumbering {
                     Instruction::BinOp { dest, op, lhs, rhs, span } => {
                         let key = format!("{:?}_{:?}_{:?}", op, lhs, rhs);
 
-                        if let Some(&existing) = value_map.get(&key) {
+                        let values = value_map.entry(key).or_default();
+                        let existing = values
+                            .iter()
+                            .find(|&&(_, def_block)| dom_tree.dominates(def_block, block_id));
+                        if let Some(&(existing, _)) = existing {
                             // Replace with copy
                             new_instructions.push(Instruction::Assign {
                                 dest: *dest,
@@ -339,7 +314,7 @@ impl OptPass for GlobalValueNumbering {
                             });
                             changed = true;
                         } else {
-                            value_map.insert(key, *dest);
+                            values.push((*dest, block_id));
                             new_instructions.push(inst.clone());
                         }
                     }
@@ -404,61 +379,6 @@ impl OptPass for LoopInvariantCodeMotion {
     }
 }
 
-/// Simple inlining pass
-pub struct Inlining {
-    /// Maximum instruction count for inlining
-    max_instructions: usize,
-}
-
-impl Inlining {
-    /// Create new inlining pass
-    pub fn new(max_instructions: usize) -> Self {
-        Self { max_instructions }
-    }
-}
-
-impl Default for Inlining {
-    fn default() -> Self {
-        Self::new(20)
-    }
-}
-
-impl OptPass for Inlining {
-    fn run(&mut self, _func: &mut Function) -> bool {
-        // Simplified inlining - in a real implementation, we would:
-        // 1. Identify call sites
-        // 2. Check if callee is simple enough (instruction count, no recursion)
-        // 3. Copy callee body and rename variables
-        // 4. Replace call with inlined body
-
-        // For now, just return false (no changes)
-        false
-    }
-
-    fn name(&self) -> &str {
-        "inlining"
-    }
-}
-
-/// SROA (Scalar Replacement of Aggregates) pass
-pub struct SROA;
-
-impl OptPass for SROA {
-    fn run(&mut self, _func: &mut Function) -> bool {
-        // Simplified SROA - in a real implementation:
-        // 1. Find allocas of struct/array types
-        // 2. Check if all accesses are known statically
-        // 3. Replace with individual scalar allocas
-        // 4. Update all GetElement instructions
-
-        false
-    }
-
-    fn name(&self) -> &str {
-        "sroa"
-    }
-}
-
 /// NRVO (Named Return Value Optimization) pass
 pub struct NRVO;
 
@@ -478,16 +398,51 @@ impl OptPass for NRVO {
 }
 
 /// Devirtualization pass
+///
+/// Turns calls through a value holding a known function of the module into
+/// direct calls by name.
 pub struct Devirtualization;
 
-impl OptPass for Devirtualization {
-    fn run(&mut self, _func: &mut Function) -> bool {
-        // Simplified devirtualization - in a real implementation:
-        // 1. Find virtual/trait method calls
-        // 2. Check if receiver type is known statically
-        // 3. Replace with direct call
+impl ModulePass for Devirtualization {
+    fn run(&mut self, module: &mut MirModule) -> bool {
+        let names: HashSet<String> = module.functions.values().map(|func| func.name.clone()).collect();
+        let mut changed = false;
 
-        false
+        for func in module.functions.values_mut() {
+            // Values assigned the name of a function
+            let targets: HashMap<ValueId, String> = func
+                .blocks
+                .values()
+                .flat_map(|block| &block.instructions)
+                .filter_map(|inst| match inst {
+                    Instruction::Assign {
+                        dest,
+                        value: Operand::Const(Constant::String(name)),
+                        ..
+                    } if names.contains(name) => Some((*dest, name.clone())),
+                    _ => None,
+                })
+                .collect();
+            if targets.is_empty() {
+                continue;
+            }
+
+            for inst in func.blocks.values_mut().flat_map(|block| &mut block.instructions) {
+                let Instruction::Call { func: callee, .. } = inst else {
+                    continue;
+                };
+                let target = match callee {
+                    Operand::Value(value) => targets.get(value),
+                    _ => None,
+                };
+                if let Some(name) = target {
+                    *callee = Operand::Const(Constant::String(name.clone()));
+                    changed = true;
+                }
+            }
+        }
+
+        changed
     }
 
     fn name(&self) -> &str {
@@ -513,7 +468,10 @@ impl OptPass for LoopSIMD {
     }
 }
 
-/// Optimization pipeline
+/// Function-level optimization pipeline
+///
+/// Interprocedural passes need a whole module; see
+/// [`PassManager`](crate::pass_manager::PassManager).
 pub struct OptPipeline {
     passes: Vec<Box<dyn OptPass>>,
 }
@@ -529,32 +487,34 @@ impl OptPipeline {
             }
             OptLevel::O1 => {
                 // Basic optimizations
+                passes.push(Box::new(SROA));
+                passes.push(Box::new(Mem2Reg));
                 passes.push(Box::new(ConstantFolding));
                 passes.push(Box::new(ConstantPropagation));
                 passes.push(Box::new(DeadCodeElimination));
             }
             OptLevel::O2 => {
                 // Standard optimizations
+                passes.push(Box::new(SROA));
+                passes.push(Box::new(Mem2Reg));
                 passes.push(Box::new(ConstantFolding));
                 passes.push(Box::new(ConstantPropagation));
                 passes.push(Box::new(CopyPropagation));
                 passes.push(Box::new(GlobalValueNumbering));
                 passes.push(Box::new(DeadCodeElimination));
                 passes.push(Box::new(LoopInvariantCodeMotion));
-                passes.push(Box::new(Inlining::default()));
             }
             OptLevel::O3 => {
                 // Aggressive optimizations
+                passes.push(Box::new(SROA));
+                passes.push(Box::new(Mem2Reg));
                 passes.push(Box::new(ConstantFolding));
                 passes.push(Box::new(ConstantPropagation));
                 passes.push(Box::new(CopyPropagation));
                 passes.push(Box::new(GlobalValueNumbering));
                 passes.push(Box::new(DeadCodeElimination));
                 passes.push(Box::new(LoopInvariantCodeMotion));
-                passes.push(Box::new(Inlining::new(50)));
-                passes.push(Box::new(SROA));
                 passes.push(Box::new(NRVO));
-                passes.push(Box::new(Devirtualization));
                 passes.push(Box::new(LoopSIMD));
             }
         }
@@ -738,7 +698,7 @@ mod tests {
     #[test]
     fn test_opt_pipeline_o3() {
         let pipeline = OptPipeline::new(OptLevel::O3);
-        assert!(pipeline.passes.len() >= 10);
+        assert!(pipeline.passes.len() >= 9);
     }
 
     #[test]
@@ -789,6 +749,47 @@ mod tests {
         assert!(!pass.run(&mut func));
     }
 
+    #[test]
+    fn test_gvn_reuses_only_dominating_values() {
+        use crate::lower::MirBuilder;
+        use aurora_types::PrimitiveType;
+
+        // Both arms compute `a * b`, and so does the join block
+        let i64_ty = Type::Primitive(PrimitiveType::I64);
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "test".to_string(), i64_ty.clone(), EffectSet::PURE);
+        let a = builder.add_param(i64_ty.clone(), Span::dummy());
+        let b = builder.add_param(i64_ty.clone(), Span::dummy());
+        let flag = builder.add_param(Type::Primitive(PrimitiveType::Bool), Span::dummy());
+        let then_bb = builder.new_block();
+        let else_bb = builder.new_block();
+        let join_bb = builder.new_block();
+        builder.build_branch(Operand::Value(flag), then_bb, else_bb, Span::dummy());
+        for arm in [then_bb, else_bb] {
+            builder.set_block(arm);
+            builder.build_binop(BinOp::Mul, Operand::Value(a), Operand::Value(b), i64_ty.clone(), Span::dummy());
+            builder.build_jump(join_bb, Span::dummy());
+        }
+        builder.set_block(join_bb);
+        let first = builder.build_binop(BinOp::Mul, Operand::Value(a), Operand::Value(b), i64_ty.clone(), Span::dummy());
+        let second = builder.build_binop(BinOp::Mul, Operand::Value(a), Operand::Value(b), i64_ty, Span::dummy());
+        builder.build_return(Some(Operand::Value(second)), Span::dummy());
+        let mut func = builder.finish_function().unwrap();
+
+        assert!(GlobalValueNumbering.run(&mut func));
+        let binops = func
+            .blocks
+            .values()
+            .flat_map(|block| &block.instructions)
+            .filter(|inst| matches!(inst, Instruction::BinOp { .. }))
+            .count();
+        assert_eq!(binops, 3);
+        assert!(func.blocks[&join_bb].instructions.iter().any(|inst| matches!(
+            inst,
+            Instruction::Assign { dest, value: Operand::Value(value), .. } if *dest == second && *value == first
+        )));
+    }
+
     #[test]
     fn test_licm() {
         let mut func = create_test_function();
@@ -799,10 +800,11 @@ mod tests {
 
     #[test]
     fn test_inlining() {
-        let mut func = create_test_function();
+        let mut module = MirModule::new();
+        module.add_function(create_test_function());
         let mut pass = Inlining::default();
 
-        assert!(!pass.run(&mut func));
+        assert!(!pass.run(&mut module));
     }
 
     #[test]
@@ -823,10 +825,42 @@ mod tests {
 
     #[test]
     fn test_devirtualization() {
-        let mut func = create_test_function();
+        let mut module = MirModule::new();
+        module.add_function(create_test_function());
         let mut pass = Devirtualization;
 
-        assert!(!pass.run(&mut func));
+        assert!(!pass.run(&mut module));
+    }
+
+    #[test]
+    fn test_devirtualization_resolves_function_values() {
+        let mut caller = Function::new(1, "main".to_string(), Type::Unit, EffectSet::PURE);
+        let mut block = BasicBlock::new(0);
+        block.push(Instruction::Assign {
+            dest: 0,
+            value: Operand::Const(Constant::String("test".to_string())),
+            span: Span::dummy(),
+        });
+        block.push(Instruction::Call {
+            dest: None,
+            func: Operand::Value(0),
+            args: Vec::new(),
+            effects: EffectSet::PURE,
+            span: Span::dummy(),
+        });
+        caller.add_block(block);
+
+        let mut module = MirModule::new();
+        module.add_function(create_test_function());
+        module.add_function(caller);
+        assert!(Devirtualization.run(&mut module));
+
+        let callee = module.functions[&1].blocks[&0].instructions.iter().find_map(|inst| match inst {
+            Instruction::Call { func, .. } => Some(func.clone()),
+            _ => None,
+        });
+        assert_eq!(callee, Some(Operand::Const(Constant::String("test".to_string()))));
+        assert!(!Devirtualization.run(&mut module));
     }
 
     #[test]
@@ -0,0 +1,329 @@
+//! Module-level pass manager
+//!
+//! Function passes ([`OptPass`]) see one function at a time; module passes
+//! ([`ModulePass`]) see the whole [`MirModule`] and can work across function
+//! boundaries, typically guided by the [`CallGraph`](crate::callgraph::CallGraph).
+//! The pass manager runs both kinds in the order they were added, visiting
+//! functions bottom-up through the call graph for function passes, and
+//! repeats the sequence until no pass changes anything or the iteration
+//! limit is reached. Every pass records how often it ran, how often it
+//! changed something, and how long it took.
+
+use crate::callgraph::CallGraph;
+use crate::opt::*;
+use crate::MirModule;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Optimization pass over a whole module
+pub trait ModulePass {
+    /// Run optimization pass on a module
+    fn run(&mut self, module: &mut MirModule) -> bool;
+
+    /// Get pass name
+    fn name(&self) -> &str;
+}
+
+/// A pass scheduled in a pass manager
+enum Pass {
+    /// Run on every function in the module
+    Function(Box<dyn OptPass>),
+    /// Run once on the module
+    Module(Box<dyn ModulePass>),
+}
+
+impl Pass {
+    fn name(&self) -> &str {
+        match self {
+            Pass::Function(pass) => pass.name(),
+            Pass::Module(pass) => pass.name(),
+        }
+    }
+}
+
+/// Statistics collected for one pass
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PassStats {
+    /// Pass name
+    pub name: String,
+    /// Number of runs; function passes count one run per function
+    pub runs: usize,
+    /// Number of runs that changed the module
+    pub changes: usize,
+    /// Total time spent in the pass
+    pub time: Duration,
+}
+
+/// Runs function and module passes over a module to a fixed point
+pub struct PassManager {
+    passes: Vec<(Pass, PassStats)>,
+    max_iterations: usize,
+    iterations: usize,
+}
+
+impl PassManager {
+    /// Create an empty pass manager
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            max_iterations: 10,
+            iterations: 0,
+        }
+    }
+
+    /// Create the pass manager for an optimization level
+    pub fn for_level(level: OptLevel) -> Self {
+        let inliner = match level {
+            OptLevel::O3 => Inlining::new(50),
+            _ => Inlining::default(),
+        };
+        Self::for_level_with_inliner(level, inliner)
+    }
+
+    /// Create the pass manager for an optimization level, inlining with `inliner`
+    ///
+    /// The inliner is only scheduled from [`OptLevel::O2`] on.
+    pub fn for_level_with_inliner(level: OptLevel, inliner: Inlining) -> Self {
+        let mut manager = Self::new();
+
+        match level {
+            OptLevel::O0 => {}
+            OptLevel::O1 => {
+                manager.add_function_pass(Box::new(SROA));
+                manager.add_function_pass(Box::new(Mem2Reg));
+                manager.add_function_pass(Box::new(ConstantFolding));
+                manager.add_function_pass(Box::new(ConstantPropagation));
+                manager.add_function_pass(Box::new(DeadCodeElimination));
+            }
+            OptLevel::O2 => {
+                manager.add_module_pass(Box::new(Devirtualization));
+                manager.add_module_pass(Box::new(inliner));
+                manager.add_function_pass(Box::new(SROA));
+                manager.add_function_pass(Box::new(Mem2Reg));
+                manager.add_function_pass(Box::new(ConstantFolding));
+                manager.add_function_pass(Box::new(ConstantPropagation));
+                manager.add_function_pass(Box::new(CopyPropagation));
+                manager.add_function_pass(Box::new(GlobalValueNumbering));
+                manager.add_function_pass(Box::new(DeadCodeElimination));
+                manager.add_function_pass(Box::new(LoopInvariantCodeMotion));
+            }
+            OptLevel::O3 => {
+                manager.add_module_pass(Box::new(Devirtualization));
+                manager.add_module_pass(Box::new(inliner));
+                manager.add_function_pass(Box::new(SROA));
+                manager.add_function_pass(Box::new(Mem2Reg));
+                manager.add_function_pass(Box::new(ConstantFolding));
+                manager.add_function_pass(Box::new(ConstantPropagation));
+                manager.add_function_pass(Box::new(CopyPropagation));
+                manager.add_function_pass(Box::new(GlobalValueNumbering));
+                manager.add_function_pass(Box::new(DeadCodeElimination));
+                manager.add_function_pass(Box::new(LoopInvariantCodeMotion));
+                manager.add_function_pass(Box::new(NRVO));
+                manager.add_function_pass(Box::new(LoopSIMD));
+            }
+        }
+
+        manager
+    }
+
+    /// Add a pass run on each function
+    pub fn add_function_pass(&mut self, pass: Box<dyn OptPass>) {
+        self.push(Pass::Function(pass));
+    }
+
+    /// Add a pass run on the whole module
+    pub fn add_module_pass(&mut self, pass: Box<dyn ModulePass>) {
+        self.push(Pass::Module(pass));
+    }
+
+    /// Set the maximum number of times the pass sequence is repeated
+    pub fn set_max_iterations(&mut self, max_iterations: usize) {
+        self.max_iterations = max_iterations.max(1);
+    }
+
+    /// Number of scheduled passes
+    pub fn len(&self) -> usize {
+        self.passes.len()
+    }
+
+    /// Whether no passes are scheduled
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    /// Number of times the pass sequence ran in the last [`run`](Self::run)
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// Statistics of every pass, in scheduling order
+    pub fn stats(&self) -> impl Iterator<Item = &PassStats> {
+        self.passes.iter().map(|(_, stats)| stats)
+    }
+
+    /// Run all passes until none of them changes the module
+    pub fn run(&mut self, module: &mut MirModule) -> bool {
+        let mut changed = false;
+        self.iterations = 0;
+
+        while self.iterations < self.max_iterations {
+            self.iterations += 1;
+            let mut iter_changed = false;
+
+            for (pass, stats) in &mut self.passes {
+                let start = Instant::now();
+                match pass {
+                    Pass::Function(pass) => {
+                        // Callees first, so callers see their optimized bodies
+                        for id in CallGraph::build(module).bottom_up() {
+                            let Some(func) = module.functions.get_mut(&id) else {
+                                continue;
+                            };
+                            stats.runs += 1;
+                            if pass.run(func) {
+                                stats.changes += 1;
+                                iter_changed = true;
+                            }
+                        }
+                    }
+                    Pass::Module(pass) => {
+                        stats.runs += 1;
+                        if pass.run(module) {
+                            stats.changes += 1;
+                            iter_changed = true;
+                        }
+                    }
+                }
+                stats.time += start.elapsed();
+            }
+
+            changed |= iter_changed;
+            if !iter_changed {
+                break;
+            }
+        }
+
+        changed
+    }
+
+    fn push(&mut self, pass: Pass) {
+        let stats = PassStats {
+            name: pass.name().to_string(),
+            ..PassStats::default()
+        };
+        self.passes.push((pass, stats));
+    }
+}
+
+impl Default for PassManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for PassManager {
+    /// Per-pass statistics table
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<28} {:>6} {:>8} {:>12}", "pass", "runs", "changes", "time")?;
+        for stats in self.stats() {
+            writeln!(
+                f,
+                "{:<28} {:>6} {:>8} {:>12?}",
+                stats.name, stats.runs, stats.changes, stats.time
+            )?;
+        }
+        write!(f, "{} iteration(s)", self.iterations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mir::{Function, FunctionId};
+    use aurora_types::{EffectSet, Type};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Records the passes and functions it sees; changes the module `budget` times
+    struct Recorder {
+        name: &'static str,
+        log: Rc<RefCell<Vec<String>>>,
+        budget: usize,
+    }
+
+    impl Recorder {
+        fn record(&mut self, entry: String) -> bool {
+            self.log.borrow_mut().push(entry);
+            let changed = self.budget > 0;
+            self.budget = self.budget.saturating_sub(1);
+            changed
+        }
+    }
+
+    impl OptPass for Recorder {
+        fn run(&mut self, func: &mut Function) -> bool {
+            let entry = format!("{}:{}", self.name, func.name);
+            self.record(entry)
+        }
+
+        fn name(&self) -> &str {
+            self.name
+        }
+    }
+
+    impl ModulePass for Recorder {
+        fn run(&mut self, _module: &mut MirModule) -> bool {
+            let entry = self.name.to_string();
+            self.record(entry)
+        }
+
+        fn name(&self) -> &str {
+            self.name
+        }
+    }
+
+    fn module(names: &[&str]) -> MirModule {
+        let mut module = MirModule::new();
+        for (id, name) in names.iter().enumerate() {
+            module.add_function(Function::new(id as FunctionId, name.to_string(), Type::Unit, EffectSet::PURE));
+        }
+        module
+    }
+
+    #[test]
+    fn test_interleaves_passes_until_fixed_point() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut manager = PassManager::new();
+        manager.add_module_pass(Box::new(Recorder { name: "m", log: log.clone(), budget: 1 }));
+        manager.add_function_pass(Box::new(Recorder { name: "f", log: log.clone(), budget: 0 }));
+
+        let mut module = module(&["a", "b"]);
+        assert!(manager.run(&mut module));
+        assert_eq!(manager.iterations(), 2);
+        assert_eq!(*log.borrow(), ["m", "f:a", "f:b", "m", "f:a", "f:b"]);
+
+        let stats: Vec<_> = manager.stats().map(|s| (s.name.as_str(), s.runs, s.changes)).collect();
+        assert_eq!(stats, [("m", 2, 1), ("f", 4, 0)]);
+        assert!(manager.to_string().contains("2 iteration(s)"));
+    }
+
+    #[test]
+    fn test_iteration_limit() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut manager = PassManager::new();
+        manager.set_max_iterations(3);
+        manager.add_module_pass(Box::new(Recorder { name: "m", log, budget: usize::MAX }));
+
+        assert!(manager.run(&mut module(&["a"])));
+        assert_eq!(manager.iterations(), 3);
+    }
+
+    #[test]
+    fn test_for_level() {
+        assert!(PassManager::for_level(OptLevel::O0).is_empty());
+        assert_eq!(PassManager::for_level(OptLevel::O1).len(), 5);
+        let o3 = PassManager::for_level(OptLevel::O3);
+        assert!(o3.stats().any(|s| s.name == "inlining"));
+        assert!(o3.stats().any(|s| s.name == "devirtualization"));
+    }
+}
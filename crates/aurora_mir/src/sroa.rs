@@ -0,0 +1,276 @@
+//! Scalar replacement of aggregates (SROA)
+//!
+//! Structs and tuples live in stack slots addressed through `GetElement`,
+//! whose index is a byte offset into the aggregate. When every use of an
+//! aggregate alloca is a constant offset, an alias of such an address, or a
+//! scalar load or store through one, each accessed offset gets its own scalar
+//! alloca instead. The new slots are then promoted to SSA values by
+//! [`Mem2Reg`](crate::mem2reg::Mem2Reg).
+
+use crate::mem2reg::is_scalar;
+use crate::mir::*;
+use crate::opt::OptPass;
+use aurora_types::{EffectSet, PrimitiveType, Type};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// SROA (Scalar Replacement of Aggregates) pass
+pub struct SROA;
+
+impl OptPass for SROA {
+    fn run(&mut self, func: &mut Function) -> bool {
+        let addresses = derived_addresses(func);
+        let fields = split_fields(func, &addresses);
+        if fields.is_empty() {
+            return false;
+        }
+
+        // One scalar alloca per field, in place of the aggregate alloca
+        let mut slots: HashMap<(ValueId, i64), ValueId> = HashMap::new();
+        let mut allocas: HashMap<ValueId, Vec<Instruction>> = HashMap::new();
+        for (&root, offsets) in &fields {
+            let Some(Value { span, .. }) = func.value(root).cloned() else {
+                continue;
+            };
+            for (&offset, ty) in offsets {
+                let dest = func.new_value(ty.clone(), span);
+                slots.insert((root, offset), dest);
+                allocas.entry(root).or_default().push(Instruction::Alloca {
+                    dest,
+                    ty: ty.clone(),
+                    effects: EffectSet::ALLOC,
+                    span,
+                });
+            }
+        }
+
+        let mut replacements = HashMap::new();
+        for (&address, &(root, offset)) in &addresses {
+            if let Some(&slot) = slots.get(&(root, offset)) {
+                replacements.insert(address, Operand::Value(slot));
+            }
+        }
+
+        for block in func.blocks.values_mut() {
+            let mut kept = Vec::with_capacity(block.instructions.len());
+            for inst in std::mem::take(&mut block.instructions) {
+                match inst.dest() {
+                    Some(dest) if fields.contains_key(&dest) => {
+                        kept.extend(allocas.remove(&dest).unwrap_or_default());
+                    }
+                    Some(dest) if addresses.get(&dest).is_some_and(|(root, _)| fields.contains_key(root)) => {}
+                    _ => kept.push(inst),
+                }
+            }
+            block.instructions = kept;
+        }
+        func.replace_uses(&replacements);
+        true
+    }
+
+    fn name(&self) -> &str {
+        "sroa"
+    }
+}
+
+/// Addresses at constant offsets into aggregate allocas, with their alloca and offset
+fn derived_addresses(func: &Function) -> HashMap<ValueId, (ValueId, i64)> {
+    let mut addresses: HashMap<ValueId, (ValueId, i64)> = func
+        .blocks
+        .values()
+        .flat_map(|block| &block.instructions)
+        .filter_map(|inst| match inst {
+            Instruction::Alloca {
+                dest,
+                ty: Type::Named { .. } | Type::Tuple(_),
+                ..
+            } => Some((*dest, (*dest, 0))),
+            _ => None,
+        })
+        .collect();
+
+    // Addresses can be used in blocks listed before their definition
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for inst in func.blocks.values().flat_map(|block| &block.instructions) {
+            let derived = match inst {
+                Instruction::GetElement {
+                    dest,
+                    base: Operand::Value(base),
+                    index: Operand::Const(Constant::Int(index)),
+                    ..
+                } => addresses.get(base).map(|&(root, offset)| (*dest, (root, offset + index))),
+                Instruction::Assign {
+                    dest,
+                    value: Operand::Value(value),
+                    ..
+                } => addresses.get(value).map(|&address| (*dest, address)),
+                _ => None,
+            };
+            if let Some((dest, address)) = derived {
+                if addresses.insert(dest, address).is_none() {
+                    changed = true;
+                }
+            }
+        }
+    }
+    addresses
+}
+
+/// Scalar fields of each splittable aggregate alloca, by offset
+///
+/// An aggregate is splittable if its address never escapes: every use is
+/// one that `derived_addresses` followed, or a load or store of a scalar.
+fn split_fields(
+    func: &Function,
+    addresses: &HashMap<ValueId, (ValueId, i64)>,
+) -> HashMap<ValueId, BTreeMap<i64, Type>> {
+    let mut rejected = HashSet::new();
+    let mut fields: HashMap<ValueId, BTreeMap<i64, Type>> = HashMap::new();
+    for &(root, _) in addresses.values() {
+        fields.entry(root).or_default();
+    }
+
+    for inst in func.blocks.values().flat_map(|block| &block.instructions) {
+        let escaping: Vec<&Operand> = match inst {
+            Instruction::GetElement {
+                index: Operand::Const(Constant::Int(_)),
+                ..
+            }
+            | Instruction::Assign { .. } => continue,
+            Instruction::Load { ptr, .. } | Instruction::Store { ptr, .. } => {
+                let Operand::Value(ptr) = ptr else {
+                    continue;
+                };
+                if let Some(&(root, offset)) = addresses.get(ptr) {
+                    match func.value(*ptr).map(|value| &value.ty) {
+                        Some(Type::Ptr { inner, .. }) if is_scalar(inner) => {
+                            let ty = fields.entry(root).or_default().entry(offset).or_insert_with(|| (**inner).clone());
+                            if *ty != **inner {
+                                // Fields accessed with different types keep a whole word
+                                *ty = Type::Primitive(PrimitiveType::I64);
+                            }
+                        }
+                        _ => {
+                            rejected.insert(root);
+                        }
+                    }
+                }
+                match inst {
+                    Instruction::Store { value, .. } => vec![value],
+                    _ => Vec::new(),
+                }
+            }
+            _ => inst.operands(),
+        };
+        for operand in escaping {
+            if let Operand::Value(value) = operand {
+                if let Some(&(root, _)) = addresses.get(value) {
+                    rejected.insert(root);
+                }
+            }
+        }
+    }
+
+    fields.retain(|root, _| !rejected.contains(root));
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lower::MirBuilder;
+    use crate::mem2reg::Mem2Reg;
+
+    fn point() -> Type {
+        Type::Named {
+            name: "Point".to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    fn field_ptr() -> Type {
+        Type::Ptr {
+            inner: Box::new(Type::Primitive(PrimitiveType::I64)),
+            mutable: true,
+        }
+    }
+
+    /// `let p = Point { x: 3, y: 4 }; p.x + p.y`, optionally passing `p` to a call
+    fn build_point(escape: bool) -> Function {
+        let i64_ty = Type::Primitive(PrimitiveType::I64);
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "test".to_string(), i64_ty.clone(), EffectSet::PURE);
+        let p = builder.build_alloca(point(), Span::dummy());
+        for (offset, value) in [(0, 3), (8, 4)] {
+            let field = builder.build_get_element(
+                Operand::Value(p),
+                Operand::Const(Constant::Int(offset)),
+                field_ptr(),
+                Span::dummy(),
+            );
+            builder.build_store(Operand::Value(field), Operand::Const(Constant::Int(value)), Span::dummy());
+        }
+        let alias = builder.new_value(point(), Span::dummy());
+        builder.build_assign(alias, Operand::Value(p), Span::dummy());
+        if escape {
+            let callee = Operand::Const(Constant::String("show".to_string()));
+            builder.build_call(callee, vec![Operand::Value(alias)], None, EffectSet::PURE, Span::dummy());
+        }
+        let mut loaded = Vec::new();
+        for offset in [0, 8] {
+            let field = builder.build_get_element(
+                Operand::Value(alias),
+                Operand::Const(Constant::Int(offset)),
+                field_ptr(),
+                Span::dummy(),
+            );
+            loaded.push(Operand::Value(builder.build_load(Operand::Value(field), i64_ty.clone(), Span::dummy())));
+        }
+        let sum = builder.build_binop(BinOp::Add, loaded[0].clone(), loaded[1].clone(), i64_ty, Span::dummy());
+        builder.build_return(Some(Operand::Value(sum)), Span::dummy());
+        builder.finish_function().unwrap()
+    }
+
+    fn instructions(func: &Function) -> Vec<&Instruction> {
+        func.blocks.values().flat_map(|block| &block.instructions).collect()
+    }
+
+    #[test]
+    fn test_splits_struct_into_scalars() {
+        let mut func = build_point(false);
+        assert!(SROA.run(&mut func));
+
+        let insts = instructions(&func);
+        assert!(!insts.iter().any(|inst| matches!(inst, Instruction::GetElement { .. })));
+        let allocas: Vec<&Type> = insts
+            .iter()
+            .filter_map(|inst| match inst {
+                Instruction::Alloca { ty, .. } => Some(ty),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(allocas, [&Type::Primitive(PrimitiveType::I64); 2]);
+
+        // The fields are then promoted, leaving `3 + 4`
+        assert!(Mem2Reg.run(&mut func));
+        let insts = instructions(&func);
+        assert_eq!(insts.len(), 2, "{:?}", insts);
+        assert!(matches!(
+            insts[0],
+            Instruction::BinOp {
+                lhs: Operand::Const(Constant::Int(3)),
+                rhs: Operand::Const(Constant::Int(4)),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_escaping_struct_is_kept() {
+        let mut func = build_point(true);
+        let before = func.clone();
+        assert!(!SROA.run(&mut func));
+        assert_eq!(instructions(&func), instructions(&before));
+    }
+}
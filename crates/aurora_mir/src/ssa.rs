@@ -0,0 +1,344 @@
+//! On-the-fly SSA construction for local variables
+//!
+//! Variables are never stored to memory. Each write records the value a
+//! variable holds at the end of the current block, and a read looks for the
+//! definition through the block's predecessors, placing a phi where several
+//! definitions meet (Braun et al., "Simple and Efficient Construction of
+//! Static Single Assignment Form"). Loop headers are left unsealed until their
+//! back edges exist; the phis read in them are completed when they are sealed.
+//! Phis whose inputs all name the same value are removed again.
+//!
+//! Names are resolved to variables through a scope map, so shadowing and
+//! block scopes only change which variable a name refers to.
+
+use crate::lower::MirBuilder;
+use crate::mir::*;
+use aurora_types::Type;
+use std::collections::HashMap;
+
+/// A source-level variable, independent of the names bound to it
+pub type VarId = usize;
+
+impl MirBuilder {
+    /// Declare a variable of type `ty` that no name is bound to
+    pub fn declare_var(&mut self, ty: Type, span: Span) -> VarId {
+        self.var_decls.push((ty, span));
+        self.var_decls.len() - 1
+    }
+
+    /// Bind `name` to a new variable holding `value`
+    pub fn define_var(&mut self, name: String, value: ValueId) {
+        let (ty, span) = self
+            .current_func
+            .as_ref()
+            .and_then(|func| func.value(value))
+            .map_or((Type::Unit, Span::dummy()), |value| (value.ty.clone(), value.span));
+        let var = self.declare_var(ty.clone(), span);
+        if let Some(func) = self.current_func.as_mut() {
+            self.var_debug.insert(var, func.debug_vars.len());
+            func.debug_vars.push(DebugVar {
+                name: name.clone(),
+                ty,
+                values: Vec::new(),
+                span,
+            });
+        }
+        self.write_var(var, value);
+        self.vars.insert(name, var);
+    }
+
+    /// Assign `value` to the variable `name` is bound to
+    ///
+    /// Returns false when no variable of that name is in scope.
+    pub fn assign_var(&mut self, name: &str, value: ValueId) -> bool {
+        match self.vars.get(name) {
+            Some(&var) => {
+                self.write_var(var, value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Current value of the variable `name` is bound to
+    pub fn lookup_var(&mut self, name: &str) -> Option<ValueId> {
+        let var = *self.vars.get(name)?;
+        Some(self.read_var(var))
+    }
+
+    /// Whether a variable named `name` is in scope
+    pub fn has_var(&self, name: &str) -> bool {
+        self.vars.contains_key(name)
+    }
+
+    /// Variables currently in scope, to be restored when a scope ends
+    pub fn save_vars(&self) -> HashMap<String, VarId> {
+        self.vars.clone()
+    }
+
+    /// Restore the variables saved at the start of a scope
+    pub fn restore_vars(&mut self, vars: HashMap<String, VarId>) {
+        self.vars = vars;
+    }
+
+    /// Record `value` as the variable's value at the current point
+    pub fn write_var(&mut self, var: VarId, value: ValueId) {
+        if let Some(block) = self.current_block {
+            self.defs.insert((var, block), value);
+            self.note_debug_value(var, value);
+        }
+    }
+
+    /// Record `value` as one of the values a named variable holds
+    fn note_debug_value(&mut self, var: VarId, value: ValueId) {
+        if let (Some(&index), Some(func)) = (self.var_debug.get(&var), self.current_func.as_mut()) {
+            func.debug_vars[index].values.push(value);
+        }
+    }
+
+    /// Value the variable holds at the current point
+    pub fn read_var(&mut self, var: VarId) -> ValueId {
+        match self.current_block {
+            Some(block) => self.read_var_in(var, block),
+            None => self.undefined(var),
+        }
+    }
+
+    /// Create a loop header, whose back edges are added after code is emitted into it
+    ///
+    /// Every other block must have all of its predecessors by the time it
+    /// becomes the current block.
+    pub fn new_loop_header(&mut self) -> BlockId {
+        let block = self.new_block();
+        self.unsealed.insert(block);
+        block
+    }
+
+    /// Mark a loop header's predecessors as complete, filling in its pending phis
+    pub fn seal_block(&mut self, block: BlockId) {
+        if !self.unsealed.remove(&block) {
+            return;
+        }
+        for (var, phi) in self.incomplete_phis.remove(&block).unwrap_or_default() {
+            self.add_phi_inputs(var, phi, block);
+        }
+    }
+
+    /// Value of a variable at the end of `block`
+    fn read_var_in(&mut self, var: VarId, block: BlockId) -> ValueId {
+        if let Some(&value) = self.defs.get(&(var, block)) {
+            return value;
+        }
+
+        let value = if self.unsealed.contains(&block) {
+            let phi = self.insert_phi(var, block);
+            self.incomplete_phis.entry(block).or_default().push((var, phi));
+            phi
+        } else {
+            match self.predecessors(block).as_slice() {
+                [] => self.undefined(var),
+                [pred] => self.read_var_in(var, *pred),
+                _ => {
+                    // Record the phi first so that reads around a loop end at it
+                    let phi = self.insert_phi(var, block);
+                    self.defs.insert((var, block), phi);
+                    self.add_phi_inputs(var, phi, block)
+                }
+            }
+        };
+        self.defs.insert((var, block), value);
+        value
+    }
+
+    /// Fill in a phi's inputs from the predecessors of its block
+    ///
+    /// Returns the value standing for the phi, which is another value if the
+    /// phi turned out to be trivial.
+    fn add_phi_inputs(&mut self, var: VarId, phi: ValueId, block: BlockId) -> ValueId {
+        let mut inputs = Vec::new();
+        for pred in self.predecessors(block) {
+            let value = self.read_var_in(var, pred);
+            inputs.push((pred, Operand::Value(value)));
+        }
+        if let Some(Instruction::Phi { inputs: slot, .. }) = self.phi_mut(phi, block) {
+            *slot = inputs;
+        }
+        self.remove_trivial_phi(phi, block)
+    }
+
+    /// Remove a phi whose inputs are all the same value or the phi itself
+    fn remove_trivial_phi(&mut self, phi: ValueId, block: BlockId) -> ValueId {
+        let Some(Instruction::Phi { inputs, .. }) = self.phi_mut(phi, block) else {
+            return self.resolve(phi);
+        };
+        let mut same = None;
+        for (_, input) in inputs.iter() {
+            match input {
+                Operand::Value(value) if *value == phi || Some(*value) == same => {}
+                Operand::Value(value) if same.is_none() => same = Some(*value),
+                _ => return phi,
+            }
+        }
+        // A phi referring only to itself sits in unreachable code
+        let Some(same) = same else {
+            return phi;
+        };
+
+        let Some(func) = self.current_func.as_mut() else {
+            return phi;
+        };
+        let mut users = Vec::new();
+        for (&id, bb) in func.blocks.iter_mut() {
+            bb.instructions
+                .retain(|inst| !matches!(inst, Instruction::Phi { dest, .. } if *dest == phi));
+            for inst in &mut bb.instructions {
+                let is_phi = matches!(inst, Instruction::Phi { .. });
+                let dest = inst.dest();
+                for operand in inst.operands_mut() {
+                    if *operand == Operand::Value(phi) {
+                        *operand = Operand::Value(same);
+                        if let (true, Some(dest)) = (is_phi, dest) {
+                            users.push((dest, id));
+                        }
+                    }
+                }
+            }
+        }
+        for value in self.defs.values_mut() {
+            if *value == phi {
+                *value = same;
+            }
+        }
+        self.replaced.insert(phi, same);
+
+        // Phis using this one may have become trivial in turn
+        for (user, user_block) in users {
+            if user != same {
+                self.remove_trivial_phi(user, user_block);
+            }
+        }
+        self.resolve(same)
+    }
+
+    /// Value standing for `value` after trivial phis were removed
+    pub(crate) fn resolve(&self, mut value: ValueId) -> ValueId {
+        while let Some(&next) = self.replaced.get(&value) {
+            value = next;
+        }
+        value
+    }
+
+    /// Insert an empty phi for a variable at the start of `block`
+    fn insert_phi(&mut self, var: VarId, block: BlockId) -> ValueId {
+        let (ty, span) = self.var_decls[var].clone();
+        let dest = self.new_value(ty, span);
+        if let Some(bb) = self.current_func.as_mut().and_then(|func| func.block_mut(block)) {
+            let pos = bb
+                .instructions
+                .iter()
+                .take_while(|inst| matches!(inst, Instruction::Phi { .. }))
+                .count();
+            bb.instructions.insert(pos, Instruction::Phi { dest, inputs: Vec::new(), span });
+        }
+        self.note_debug_value(var, dest);
+        dest
+    }
+
+    /// The phi defining `phi` in `block`
+    fn phi_mut(&mut self, phi: ValueId, block: BlockId) -> Option<&mut Instruction> {
+        let bb = self.current_func.as_mut()?.block_mut(block)?;
+        bb.instructions
+            .iter_mut()
+            .find(|inst| matches!(inst, Instruction::Phi { dest, .. } if *dest == phi))
+    }
+
+    /// Predecessors of `block` in the current function
+    fn predecessors(&self, block: BlockId) -> Vec<BlockId> {
+        self.current_func
+            .as_ref()
+            .and_then(|func| func.block(block))
+            .map(|bb| bb.predecessors.clone())
+            .unwrap_or_default()
+    }
+
+    /// A value for reading a variable where it has no definition
+    fn undefined(&mut self, var: VarId) -> ValueId {
+        let (ty, span) = self.var_decls[var].clone();
+        self.new_value(ty, span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aurora_types::{EffectSet, PrimitiveType};
+
+    fn phis(func: &Function, block: BlockId) -> Vec<(ValueId, Vec<(BlockId, Operand)>)> {
+        func.block(block)
+            .unwrap()
+            .instructions
+            .iter()
+            .filter_map(|inst| match inst {
+                Instruction::Phi { dest, inputs, .. } => Some((*dest, inputs.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_phi_at_merge() {
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "test".to_string(), Type::Unit, EffectSet::PURE);
+        let ty = Type::Primitive(PrimitiveType::I64);
+        let x0 = builder.new_value(ty.clone(), Span::dummy());
+        builder.define_var("x".to_string(), x0);
+
+        let then_bb = builder.new_block();
+        let else_bb = builder.new_block();
+        let merge_bb = builder.new_block();
+        builder.build_branch(Operand::Const(Constant::Bool(true)), then_bb, else_bb, Span::dummy());
+        builder.set_block(then_bb);
+        let x1 = builder.new_value(ty, Span::dummy());
+        assert!(builder.assign_var("x", x1));
+        builder.build_jump(merge_bb, Span::dummy());
+        builder.set_block(else_bb);
+        builder.build_jump(merge_bb, Span::dummy());
+
+        builder.set_block(merge_bb);
+        let x = builder.lookup_var("x").unwrap();
+        let func = builder.finish_function().unwrap();
+        let expected = vec![(then_bb, Operand::Value(x1)), (else_bb, Operand::Value(x0))];
+        assert_eq!(phis(&func, merge_bb), vec![(x, expected)]);
+    }
+
+    #[test]
+    fn test_loop_header_phis() {
+        let mut builder = MirBuilder::new();
+        builder.start_function(0, "test".to_string(), Type::Unit, EffectSet::PURE);
+        let ty = Type::Primitive(PrimitiveType::I64);
+        let i0 = builder.new_value(ty.clone(), Span::dummy());
+        let n = builder.new_value(ty.clone(), Span::dummy());
+        builder.define_var("i".to_string(), i0);
+        builder.define_var("n".to_string(), n);
+
+        let entry = builder.current_block().unwrap();
+        let header = builder.new_loop_header();
+        builder.build_jump(header, Span::dummy());
+        builder.set_block(header);
+        let i = builder.lookup_var("i").unwrap();
+        let limit = builder.lookup_var("n").unwrap();
+        let next = builder.build_binop(BinOp::Add, Operand::Value(i), Operand::Value(limit), ty, Span::dummy());
+        builder.assign_var("i", next);
+        builder.build_jump(header, Span::dummy());
+        builder.seal_block(header);
+
+        // `n` is not assigned in the loop, so its phi is removed
+        let func = builder.finish_function().unwrap();
+        let expected = vec![(entry, Operand::Value(i0)), (header, Operand::Value(next))];
+        assert_eq!(phis(&func, header), vec![(i, expected)]);
+        let uses_n = func.block(header).unwrap().instructions.iter().any(|inst| {
+            matches!(inst, Instruction::BinOp { rhs: Operand::Value(value), .. } if *value == n)
+        });
+        assert!(uses_n);
+    }
+}
@@ -0,0 +1,153 @@
+//! Integration tests for the MIR inliner
+
+use aurora_mir::inline::INSTRUCTION_COST;
+use aurora_mir::*;
+use aurora_parser::Parser;
+use aurora_types::TypeChecker;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+fn lower(source: &str) -> MirModule {
+    let ast = Parser::new(source, "test.ax".to_string()).unwrap().parse();
+    let mut checker = TypeChecker::new(Arc::new(()));
+    let ast = checker.check(ast);
+    assert!(checker.errors().is_empty(), "{:?}", checker.errors());
+    lower_typed_ast_to_mir(ast, checker.type_map().clone(), Arc::new(()))
+}
+
+fn function<'m>(module: &'m MirModule, name: &str) -> &'m Function {
+    module.functions.values().find(|func| func.name == name).unwrap()
+}
+
+fn callees(func: &Function) -> Vec<String> {
+    func.blocks
+        .values()
+        .flat_map(|block| &block.instructions)
+        .filter_map(|inst| match inst {
+            Instruction::Call {
+                func: Operand::Const(Constant::String(name)),
+                ..
+            } => Some(name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Edges, phi inputs and value definitions agree with each other
+fn assert_well_formed(func: &Function) {
+    let mut defined = HashSet::new();
+    for block in func.blocks.values() {
+        for &succ in &block.successors {
+            let succ = func.block(succ).expect("successor exists");
+            assert!(succ.predecessors.contains(&block.id), "edge {} -> {}", block.id, succ.id);
+        }
+        for inst in &block.instructions {
+            if let Some(dest) = inst.dest() {
+                assert!(defined.insert(dest), "value {} defined twice", dest);
+            }
+            if let Instruction::Phi { inputs, .. } = inst {
+                let mut preds: Vec<_> = inputs.iter().map(|(pred, _)| *pred).collect();
+                let mut expected = block.predecessors.clone();
+                preds.sort();
+                expected.sort();
+                assert_eq!(preds, expected, "phi inputs of block {}", block.id);
+            }
+        }
+        assert!(matches!(
+            block.instructions.last(),
+            Some(Instruction::Return { .. } | Instruction::Jump { .. } | Instruction::Branch { .. })
+        ));
+    }
+}
+
+#[test]
+fn test_inlines_small_callee() {
+    let mut module = lower(
+        "fn add(a: i64, b: i64) -> i64 { a + b }\n\
+         fn main() -> i64 { add(1, 2) + add(3, 4) }\n",
+    );
+    let mut inliner = Inlining::default();
+    assert!(inliner.run(&mut module));
+    assert_eq!(inliner.inlined(), 2);
+
+    let main = function(&module, "main");
+    assert!(callees(main).is_empty());
+    assert_well_formed(main);
+    // The callee stays for other callers
+    assert_eq!(module.function_count(), 2);
+
+    // Nothing is left to inline
+    assert!(!inliner.run(&mut module));
+}
+
+#[test]
+fn test_returns_join_in_continuation_phi() {
+    let mut module = lower(
+        "fn abs(x: i64) -> i64 {\n\
+             if x < 0 { return 0 - x; }\n\
+             x\n\
+         }\n\
+         fn main() -> i64 { let y = abs(5); y * 2 }\n",
+    );
+    assert!(Inlining::default().run(&mut module));
+
+    let main = function(&module, "main");
+    assert!(callees(main).is_empty());
+    assert_well_formed(main);
+    let phis = main
+        .blocks
+        .values()
+        .flat_map(|block| &block.instructions)
+        .filter(|inst| matches!(inst, Instruction::Phi { inputs, .. } if inputs.len() == 2))
+        .count();
+    assert_eq!(phis, 1);
+}
+
+#[test]
+fn test_skips_recursive_functions() {
+    let mut module = lower(
+        "fn fact(n: i64) -> i64 { if n <= 1 { return 1; } n * fact(n - 1) }\n\
+         fn even(n: i64) -> bool { if n == 0 { return true; } odd(n - 1) }\n\
+         fn odd(n: i64) -> bool { if n == 0 { return false; } even(n - 1) }\n\
+         fn main() -> i64 { if even(4) { fact(5) } else { 0 } }\n",
+    );
+    assert!(!Inlining::default().run(&mut module));
+
+    assert_eq!(callees(function(&module, "fact")), ["fact"]);
+    assert_eq!(callees(function(&module, "even")), ["odd"]);
+    let mut main = callees(function(&module, "main"));
+    main.sort();
+    assert_eq!(main, ["even", "fact"]);
+}
+
+#[test]
+fn test_threshold_limits_callee_size() {
+    let source = "fn poly(x: i64) -> i64 { x * x * x + 2 * x * x + 3 * x + 4 }\n\
+                  fn main() -> i64 { poly(3) }\n";
+
+    let mut module = lower(source);
+    assert!(!Inlining::with_threshold(INSTRUCTION_COST).run(&mut module));
+    assert_eq!(callees(function(&module, "main")), ["poly"]);
+
+    let mut module = lower(source);
+    assert!(Inlining::with_threshold(1000).run(&mut module));
+    assert!(callees(function(&module, "main")).is_empty());
+}
+
+#[test]
+fn test_nested_calls_inline_bottom_up() {
+    let mut module = lower(
+        "fn inc(x: i64) -> i64 { x + 1 }\n\
+         fn twice(x: i64) -> i64 { inc(inc(x)) }\n\
+         fn main() -> i64 { twice(1) }\n",
+    );
+    let mut manager = PassManager::new();
+    manager.add_module_pass(Box::new(Inlining::default()));
+    assert!(manager.run(&mut module));
+
+    for name in ["twice", "main"] {
+        let func = function(&module, name);
+        assert!(callees(func).is_empty(), "{} still calls {:?}", name, callees(func));
+        assert_well_formed(func);
+    }
+}
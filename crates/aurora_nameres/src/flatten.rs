@@ -0,0 +1,460 @@
+//! Module flattening for Aurora
+//!
+//! Type checking and MIR lowering work on a single namespace. This pass lays
+//! the items of every module out in dependency order, names the items of
+//! modules other than the root after their module path (`shapes::area`
+//! becomes `shapes.area`, like the `Type.method` symbols of methods), and
+//! rewrites qualified and imported references to those names.
+
+use crate::modules::{ModuleGraph, ModuleId};
+use crate::resolver::{ResolutionError, ResolutionResult};
+use crate::scopes::{ScopeId, ScopeKind};
+use crate::symbols::{SymbolId, SymbolKind};
+use aurora_ast::{AstNode, ExprKind, ItemKind, PatternKind, Program, Span, TypeKind};
+use std::collections::{HashMap, HashSet};
+
+/// Flatten all modules of a resolved program into its top-level item list
+///
+/// Items appear in topological module order (dependencies first), falling
+/// back to declaration order if the module graph has a cycle. Traits keep
+/// their names, since the paths in bounds are not resolved, so a trait name
+/// declared in more than one module is reported.
+pub fn flatten_modules(program: &mut Program, result: &ResolutionResult) -> Vec<ResolutionError> {
+    let modules = &result.modules;
+    let order = modules.topological_sort().unwrap_or_else(|| modules.ids());
+    let prefixes = module_prefixes(modules);
+
+    let mut items = Vec::new();
+    let mut traits: HashMap<String, (ModuleId, Span)> = HashMap::new();
+    let mut errors = Vec::new();
+
+    for module_id in order {
+        let Some(module) = modules.get(module_id) else {
+            continue;
+        };
+        for &item_id in &module.items {
+            let Some(AstNode::Item(item)) = program.arena.get_mut(item_id) else {
+                continue;
+            };
+            match &mut item.kind {
+                ItemKind::Module(_) => continue,
+                ItemKind::Trait(decl) => match traits.get(&decl.name) {
+                    // Duplicates within one module are reported by the resolver
+                    Some(&(first_module, first_span)) if first_module != module_id => {
+                        errors.push(ResolutionError::ConflictingItem {
+                            name: decl.name.clone(),
+                            first_span,
+                            second_span: item.span,
+                        });
+                    }
+                    Some(_) => {}
+                    None => {
+                        traits.insert(decl.name.clone(), (module_id, item.span));
+                    }
+                },
+                kind => {
+                    if let (Some(name), Some(prefix)) = (item_name(kind), prefixes.get(&module_id)) {
+                        *name = flat_name(Some(prefix), name);
+                    }
+                }
+            }
+            items.push(item_id);
+        }
+    }
+    program.items = items;
+
+    rewrite_references(program, result, &prefixes);
+    strip_module_prefixes(program, modules);
+    errors
+}
+
+/// Name an item other than a trait is declared under, if it declares one
+fn item_name(kind: &mut ItemKind) -> Option<&mut String> {
+    match kind {
+        ItemKind::Function(decl) => Some(&mut decl.name),
+        ItemKind::Struct(decl) => Some(&mut decl.name),
+        ItemKind::Enum(decl) => Some(&mut decl.name),
+        ItemKind::Type(decl) => Some(&mut decl.name),
+        ItemKind::Const(decl) => Some(&mut decl.name),
+        ItemKind::Trait(_) | ItemKind::Module(_) | ItemKind::Use(_) | ItemKind::Impl(_) => None,
+    }
+}
+
+/// Path of each module other than the root, its names joined by `.`
+fn module_prefixes(modules: &ModuleGraph) -> HashMap<ModuleId, String> {
+    let mut prefixes = HashMap::new();
+    for id in modules.ids() {
+        let mut names = Vec::new();
+        let mut current = modules.get(id);
+        while let Some(module) = current.filter(|module| module.id != modules.root_id()) {
+            names.push(module.name.as_str());
+            current = module.parent.and_then(|parent| modules.get(parent));
+        }
+        if !names.is_empty() {
+            names.reverse();
+            prefixes.insert(id, names.join("."));
+        }
+    }
+    prefixes
+}
+
+/// Name an item of the module with path `prefix` is flattened to
+fn flat_name(prefix: Option<&str>, name: &str) -> String {
+    match prefix {
+        Some(prefix) => format!("{}.{}", prefix, name),
+        None => name.to_string(),
+    }
+}
+
+/// Path a symbol is reachable under once modules are flattened
+///
+/// Module-level items take their flat name and type members are qualified
+/// by their type's. Locals and generic parameters need no rewriting.
+fn flat_path(result: &ResolutionResult, prefixes: &HashMap<ScopeId, &str>, symbol_id: SymbolId) -> Option<Vec<String>> {
+    let symbol = result.symbols.get(symbol_id)?;
+    let scope = result.scopes.get(symbol.scope_id)?;
+    match scope.kind {
+        ScopeKind::Global | ScopeKind::Module if symbol.kind == SymbolKind::Trait => Some(vec![symbol.name.clone()]),
+        ScopeKind::Global | ScopeKind::Module => {
+            Some(vec![flat_name(prefixes.get(&scope.id).copied(), &symbol.name)])
+        }
+        ScopeKind::Type => {
+            let prefix = scope.parent.and_then(|parent| prefixes.get(&parent)).copied();
+            Some(vec![flat_name(prefix, scope.name.as_ref()?), symbol.name.clone()])
+        }
+        _ => None,
+    }
+}
+
+/// Rewrite resolved expressions, types and patterns to the flat paths of
+/// their symbols
+///
+/// `utils::add(1, 2)` and an aliased import `plus(1, 2)` both become
+/// `utils.add(1, 2)`.
+fn rewrite_references(program: &mut Program, result: &ResolutionResult, prefixes: &HashMap<ModuleId, String>) {
+    let prefixes: HashMap<ScopeId, &str> = prefixes
+        .iter()
+        .filter_map(|(module_id, prefix)| Some((*result.module_scopes.get(module_id)?, prefix.as_str())))
+        .collect();
+
+    let mut resolutions: Vec<_> = result.resolution_map.expr_resolutions().collect();
+    resolutions.sort_unstable();
+    for (expr_id, symbol_id) in resolutions {
+        let Some(flat) = flat_path(result, &prefixes, symbol_id) else {
+            continue;
+        };
+        let Some(AstNode::Expr(expr)) = program.arena.get_mut(expr_id) else {
+            continue;
+        };
+
+        match &mut expr.kind {
+            ExprKind::Ident(name) => {
+                if let [flat_name] = flat.as_slice() {
+                    name.clone_from(flat_name);
+                }
+            }
+            ExprKind::Path(path) => {
+                if let [flat_name] = flat.as_slice() {
+                    expr.kind = ExprKind::Ident(flat_name.clone());
+                } else {
+                    path.segments = flat;
+                }
+            }
+            ExprKind::Struct { path, .. } => path.segments = flat,
+            _ => {}
+        }
+    }
+
+    let mut resolutions: Vec<_> = result.resolution_map.path_resolutions().collect();
+    resolutions.sort_unstable();
+    for (node_id, symbol_id) in resolutions {
+        let Some(flat) = flat_path(result, &prefixes, symbol_id) else {
+            continue;
+        };
+        match program.arena.get_mut(node_id) {
+            Some(AstNode::Type(ty)) => {
+                if let TypeKind::Path { path } = &mut ty.kind {
+                    path.segments = flat;
+                }
+            }
+            Some(AstNode::Pattern(pattern)) => match &mut pattern.kind {
+                PatternKind::Struct { path, .. } | PatternKind::TupleStruct { path, .. } | PatternKind::Path(path) => {
+                    path.segments = flat;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Fold module qualifiers into the flat names of the type and pattern paths
+/// the resolver did not resolve
+///
+/// Leading `crate`, `self` and `super` segments are dropped and module-name
+/// segments joined to the name after them (`shapes::Shape::Circle` becomes
+/// `shapes.Shape::Circle`).
+fn strip_module_prefixes(program: &mut Program, modules: &ModuleGraph) {
+    let module_names: HashSet<String> = modules
+        .ids()
+        .into_iter()
+        .filter(|&id| id != modules.root_id())
+        .filter_map(|id| modules.get(id).map(|module| module.name.clone()))
+        .collect();
+    for node_id in 0..program.arena.len() as u32 {
+        let segments = match program.arena.get_mut(node_id) {
+            Some(AstNode::Type(ty)) => match &mut ty.kind {
+                TypeKind::Path { path } => &mut path.segments,
+                _ => continue,
+            },
+            Some(AstNode::Pattern(pattern)) => match &mut pattern.kind {
+                PatternKind::Struct { path, .. }
+                | PatternKind::TupleStruct { path, .. }
+                | PatternKind::Path(path) => &mut path.segments,
+                _ => continue,
+            },
+            _ => continue,
+        };
+
+        let prefix = segments[..segments.len().saturating_sub(1)]
+            .iter()
+            .take_while(|segment| {
+                matches!(segment.as_str(), "crate" | "self" | "super")
+                    || module_names.contains(*segment)
+            })
+            .count();
+        let path: Vec<&str> = segments[..prefix]
+            .iter()
+            .map(String::as_str)
+            .filter(|segment| !matches!(*segment, "crate" | "self" | "super"))
+            .collect();
+        if !path.is_empty() {
+            segments[prefix] = flat_name(Some(&path.join(".")), &segments[prefix]);
+        }
+        segments.drain(..prefix);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolver::Resolver;
+    use aurora_ast::decl::{
+        FieldDecl, FunctionDecl, Item, ModuleDecl, StructDecl, TraitDecl, UseDecl, UseTree, VariantFields,
+    };
+    use aurora_ast::expr::{Expr, Path};
+    use aurora_ast::stmt::{Block, Stmt, StmtKind};
+    use aurora_ast::{Arena, HygieneId, Type};
+
+    fn function(arena: &mut Arena, name: &str, is_pub: bool, body: Vec<u32>) -> u32 {
+        arena.alloc_item(Item {
+            kind: ItemKind::Function(FunctionDecl {
+                name: name.to_string(),
+                generics: vec![],
+                params: vec![],
+                return_type: None,
+                where_clause: None,
+                body: Block {
+                    stmts: body,
+                    expr: None,
+                    span: Span::dummy(),
+                },
+                is_pub,
+                is_async: false,
+                is_unsafe: false,
+                span: Span::dummy(),
+            }),
+            span: Span::dummy(),
+        })
+    }
+
+    fn call_stmt(arena: &mut Arena, kind: ExprKind) -> (u32, u32) {
+        let func = arena.alloc_expr(Expr {
+            kind,
+            span: Span::dummy(),
+            hygiene: HygieneId::root(),
+        });
+        let call = arena.alloc_expr(Expr {
+            kind: ExprKind::Call { func, args: vec![] },
+            span: Span::dummy(),
+            hygiene: HygieneId::root(),
+        });
+        let stmt = arena.alloc_stmt(Stmt {
+            kind: StmtKind::Expr { expr: call, has_semi: true },
+            span: Span::dummy(),
+        });
+        (func, stmt)
+    }
+
+    fn structure(arena: &mut Arena, name: &str, fields: Vec<(&str, u32)>) -> u32 {
+        let fields = fields
+            .into_iter()
+            .map(|(name, ty)| FieldDecl {
+                name: name.to_string(),
+                ty,
+                is_pub: false,
+                span: Span::dummy(),
+            })
+            .collect();
+        arena.alloc_item(Item {
+            kind: ItemKind::Struct(StructDecl {
+                name: name.to_string(),
+                generics: vec![],
+                where_clause: None,
+                fields: VariantFields::Named(fields),
+                is_pub: true,
+                span: Span::dummy(),
+            }),
+            span: Span::dummy(),
+        })
+    }
+
+    fn module(arena: &mut Arena, name: &str, items: Vec<u32>) -> u32 {
+        arena.alloc_item(Item {
+            kind: ItemKind::Module(ModuleDecl {
+                name: name.to_string(),
+                items: Some(items),
+                is_pub: false,
+                span: Span::dummy(),
+            }),
+            span: Span::dummy(),
+        })
+    }
+
+    fn use_item(arena: &mut Arena, segments: &[&str], alias: Option<&str>) -> u32 {
+        arena.alloc_item(Item {
+            kind: ItemKind::Use(UseDecl {
+                tree: UseTree::Path {
+                    path: Path {
+                        segments: segments.iter().map(|s| s.to_string()).collect(),
+                        generics: vec![],
+                    },
+                    alias: alias.map(str::to_string),
+                },
+                is_pub: false,
+                span: Span::dummy(),
+            }),
+            span: Span::dummy(),
+        })
+    }
+
+    fn path(segments: &[&str]) -> ExprKind {
+        ExprKind::Path(Path {
+            segments: segments.iter().map(|s| s.to_string()).collect(),
+            generics: vec![],
+        })
+    }
+
+    #[test]
+    fn test_flatten_orders_dependencies_first_and_rewrites_paths() {
+        let mut arena = Arena::new();
+
+        // mod math { pub fn add() {} }
+        let add = function(&mut arena, "add", true, vec![]);
+        let math = module(&mut arena, "math", vec![add]);
+
+        // use math::add as plus;
+        // fn main() { plus(); math::add(); }
+        let import = use_item(&mut arena, &["math", "add"], Some("plus"));
+        let (aliased, first) = call_stmt(&mut arena, ExprKind::Ident("plus".to_string()));
+        let (qualified, second) = call_stmt(&mut arena, path(&["math", "add"]));
+        let main = function(&mut arena, "main", false, vec![first, second]);
+
+        let mut program = Program::new(vec![import, main, math], Span::dummy(), arena);
+        let result = Resolver::new(&program.arena, "test".to_string()).resolve(&program);
+        assert!(result.is_ok(), "{:?}", result.diagnostics);
+
+        let errors = flatten_modules(&mut program, &result);
+        assert!(errors.is_empty());
+        assert_eq!(program.items, vec![add, import, main]);
+
+        for expr_id in [aliased, qualified] {
+            let kind = &program.arena.get_expr(expr_id).unwrap().kind;
+            assert_eq!(kind, &ExprKind::Ident("math.add".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_flatten_names_module_items_by_path() {
+        let mut arena = Arena::new();
+
+        // mod a { fn helper() {} }
+        // mod b { struct Point {} struct Line { start: Point } fn helper() {} }
+        let a_helper = function(&mut arena, "helper", true, vec![]);
+        let a = module(&mut arena, "a", vec![a_helper]);
+        let b_point = structure(&mut arena, "Point", vec![]);
+        let field_ty = arena.alloc_type(Type {
+            kind: TypeKind::Path {
+                path: Path {
+                    segments: vec!["Point".to_string()],
+                    generics: vec![],
+                },
+            },
+            span: Span::dummy(),
+        });
+        let line = structure(&mut arena, "Line", vec![("start", field_ty)]);
+        let b_helper = function(&mut arena, "helper", true, vec![]);
+        let b = module(&mut arena, "b", vec![b_point, line, b_helper]);
+
+        // struct Point {}
+        // fn helper() { a::helper(); b::helper(); }
+        let point = structure(&mut arena, "Point", vec![]);
+        let (to_a, first) = call_stmt(&mut arena, path(&["a", "helper"]));
+        let (to_b, second) = call_stmt(&mut arena, path(&["b", "helper"]));
+        let helper = function(&mut arena, "helper", false, vec![first, second]);
+
+        let mut program = Program::new(vec![a, b, point, helper], Span::dummy(), arena);
+        let result = Resolver::new(&program.arena, "test".to_string()).resolve(&program);
+        assert!(result.is_ok(), "{:?}", result.diagnostics);
+        let errors = flatten_modules(&mut program, &result);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let names: Vec<&str> = program
+            .items
+            .iter()
+            .filter_map(|&id| match &program.arena.get_item(id).unwrap().kind {
+                ItemKind::Function(decl) => Some(decl.name.as_str()),
+                ItemKind::Struct(decl) => Some(decl.name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, vec!["Point", "helper", "a.helper", "b.Point", "b.Line", "b.helper"]);
+
+        for (expr_id, name) in [(to_a, "a.helper"), (to_b, "b.helper")] {
+            let kind = &program.arena.get_expr(expr_id).unwrap().kind;
+            assert_eq!(kind, &ExprKind::Ident(name.to_string()));
+        }
+        let Some(TypeKind::Path { path }) = program.arena.get_type_node(field_ty).map(|ty| &ty.kind) else {
+            panic!("the field type is not a path");
+        };
+        assert_eq!(path.segments, vec!["b.Point"]);
+    }
+
+    #[test]
+    fn test_flatten_reports_conflicting_traits() {
+        let mut arena = Arena::new();
+        let trait_item = |arena: &mut Arena| {
+            arena.alloc_item(Item {
+                kind: ItemKind::Trait(TraitDecl {
+                    name: "Show".to_string(),
+                    generics: vec![],
+                    where_clause: None,
+                    items: vec![],
+                    is_pub: false,
+                    span: Span::dummy(),
+                }),
+                span: Span::dummy(),
+            })
+        };
+        let inner = trait_item(&mut arena);
+        let a = module(&mut arena, "a", vec![inner]);
+        let outer = trait_item(&mut arena);
+
+        let mut program = Program::new(vec![a, outer], Span::dummy(), arena);
+        let result = Resolver::new(&program.arena, "test".to_string()).resolve(&program);
+        let errors = flatten_modules(&mut program, &result);
+
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], ResolutionError::ConflictingItem { name, .. } if name == "Show"));
+    }
+}
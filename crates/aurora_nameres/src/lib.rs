@@ -17,11 +17,15 @@
 //! - **Hygiene System**: Prevents accidental variable capture in macro expansion
 //!   by assigning unique hygiene IDs to identifiers based on their lexical context.
 //!
-//! - **Module Graph**: (TODO) Builds the dependency graph of modules and detects cycles.
+//! - **Module Graph**: Builds the dependency graph of modules from `mod` and
+//!   `use` declarations, detects cycles and orders modules for compilation.
 //!
-//! - **Name Resolver**: (TODO) Performs the actual name resolution, binding
+//! - **Name Resolver**: Performs the actual name resolution, binding
 //!   identifier uses to their definitions while respecting hygiene and visibility.
 //!
+//! - **Flattening**: Lays out the items of all modules in one namespace for
+//!   the later phases.
+//!
 //! # Example
 //!
 //! ```rust,ignore
@@ -37,6 +41,7 @@
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
+pub mod flatten;
 pub mod hygiene;
 pub mod modules;
 pub mod resolver;
@@ -44,6 +49,7 @@ pub mod scopes;
 pub mod symbols;
 
 // Re-export main types
+pub use flatten::flatten_modules;
 pub use hygiene::{ExpansionContext, HygieneBinding, HygieneContext, HygieneResolver};
 pub use modules::{
     DependencyKind, Module, ModuleDependency, ModuleError, ModuleGraph, ModuleId, ModulePath,
@@ -74,15 +80,14 @@ impl NameResolver {
 
     /// Resolve names in the AST
     ///
-    /// Note: This requires access to the Arena. For now, this is a stub that
-    /// will be integrated properly when the pipeline provides Arena access.
-    pub fn resolve(&mut self, ast: Ast) -> Ast {
-        // TODO: Once the pipeline provides Arena access, we can do:
-        // let arena = ...; // Need arena from pipeline
-        // let resolver = Resolver::new(arena, "crate_name".to_string());
-        // self.result = Some(resolver.resolve(&ast));
-
-        // For now, just return the AST unchanged
+    /// Returns the AST with all modules flattened into its top-level items
+    /// (see [`flatten_modules`]). Errors are available from `diagnostics()`.
+    pub fn resolve(&mut self, mut ast: Ast) -> Ast {
+        let mut result = Resolver::new(&ast.arena, "crate".to_string()).resolve(&ast);
+        let conflicts = flatten_modules(&mut ast, &result);
+        result.diagnostics.extend(conflicts);
+
+        self.result = Some(result);
         ast
     }
 
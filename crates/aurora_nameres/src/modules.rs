@@ -42,6 +42,8 @@ pub struct ModuleDependency {
     pub is_pub: bool,
     /// What is being imported
     pub kind: DependencyKind,
+    /// Name the import is bound to, if renamed (`use a::b as c`)
+    pub alias: Option<String>,
     /// Source span
     pub span: Span,
 }
@@ -86,6 +88,12 @@ impl ModulePath {
     }
 }
 
+impl std::fmt::Display for ModulePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.segments.join("::"))
+    }
+}
+
 /// Kind of dependency
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DependencyKind {
@@ -216,7 +224,7 @@ impl ModuleGraph {
         let mut deps = Vec::new();
 
         match tree {
-            UseTree::Path { path, alias: _ } => {
+            UseTree::Path { path, alias } => {
                 let module_path = ModulePath::from_ast_path(path);
                 let kind = if let Some(item) = module_path.last() {
                     DependencyKind::Item(item.to_string())
@@ -228,6 +236,7 @@ impl ModuleGraph {
                     target: module_path,
                     is_pub,
                     kind,
+                    alias: alias.clone(),
                     span,
                 });
             }
@@ -237,6 +246,7 @@ impl ModuleGraph {
                     target: module_path,
                     is_pub,
                     kind: DependencyKind::Glob,
+                    alias: None,
                     span,
                 });
             }
@@ -301,6 +311,77 @@ impl ModuleGraph {
         self.root_module
     }
 
+    /// Record the items declared directly in a module
+    pub fn set_items(&mut self, id: ModuleId, items: Vec<ItemId>) {
+        if let Some(module) = self.modules.get_mut(&id) {
+            module.items = items;
+        }
+    }
+
+    /// Find the child module of `parent` with the given name
+    pub fn child(&self, parent: ModuleId, name: &str) -> Option<ModuleId> {
+        self.modules
+            .get(&parent)?
+            .children
+            .iter()
+            .copied()
+            .find(|id| self.modules.get(id).is_some_and(|m| m.name == name))
+    }
+
+    /// Resolve a module path as written in module `from`
+    ///
+    /// Paths may start with `crate`, `self` or `super`. Other paths are looked
+    /// up among the children of `from` first and then from the crate root.
+    pub fn resolve_module_path(&self, from: ModuleId, segments: &[String]) -> Option<ModuleId> {
+        let (mut current, rest) = match segments.first().map(String::as_str) {
+            Some("crate") => (self.root_module, &segments[1..]),
+            Some("self") => (from, &segments[1..]),
+            Some("super") => (self.modules.get(&from)?.parent?, &segments[1..]),
+            Some(first) if self.child(from, first).is_none() => (self.root_module, segments),
+            _ => (from, segments),
+        };
+
+        for segment in rest {
+            current = match segment.as_str() {
+                "super" => self.modules.get(&current)?.parent?,
+                name => self.child(current, name)?,
+            };
+        }
+        Some(current)
+    }
+
+    /// The module a dependency of module `from` imports from
+    pub fn dependency_module(&self, from: ModuleId, dep: &ModuleDependency) -> Option<ModuleId> {
+        match dep.kind {
+            DependencyKind::Glob | DependencyKind::Module => {
+                self.resolve_module_path(from, &dep.target.segments)
+            }
+            DependencyKind::Item(_) => {
+                self.resolve_module_path(from, &dep.target.parent_path().segments)
+            }
+        }
+    }
+
+    /// Modules a module depends on through its `use` declarations
+    fn dependency_targets(&self, module: &Module) -> Vec<ModuleId> {
+        let mut targets: Vec<ModuleId> = module
+            .dependencies
+            .iter()
+            .filter_map(|dep| self.dependency_module(module.id, dep))
+            .filter(|&target| target != module.id)
+            .collect();
+        targets.sort_unstable();
+        targets.dedup();
+        targets
+    }
+
+    /// All module IDs in ascending (declaration) order
+    pub fn ids(&self) -> Vec<ModuleId> {
+        let mut ids: Vec<ModuleId> = self.modules.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
     /// Detect cycles in the module graph
     ///
     /// Returns Ok(()) if no cycles, or Err with cycle information if cycles exist.
@@ -308,7 +389,7 @@ impl ModuleGraph {
         let mut visited = HashSet::new();
         let mut rec_stack = HashSet::new();
 
-        for &module_id in self.modules.keys() {
+        for module_id in self.ids() {
             if !visited.contains(&module_id) {
                 if let Some(cycle) = self.dfs_cycle_detect(module_id, &mut visited, &mut rec_stack)
                 {
@@ -332,19 +413,15 @@ impl ModuleGraph {
 
         if let Some(module) = self.modules.get(&module_id) {
             // Check dependencies
-            for dep in &module.dependencies {
-                // Resolve dependency target to module ID
-                if let Some(&target_id) = self.path_to_module.get(&dep.target) {
-                    if !visited.contains(&target_id) {
-                        if let Some(cycle) = self.dfs_cycle_detect(target_id, visited, rec_stack)
-                        {
-                            return Some(cycle);
-                        }
-                    } else if rec_stack.contains(&target_id) {
-                        // Found a cycle
-                        let cycle = vec![target_id, module_id];
+            for target_id in self.dependency_targets(module) {
+                if !visited.contains(&target_id) {
+                    if let Some(cycle) = self.dfs_cycle_detect(target_id, visited, rec_stack) {
                         return Some(cycle);
                     }
+                } else if rec_stack.contains(&target_id) {
+                    // Found a cycle
+                    let cycle = vec![target_id, module_id];
+                    return Some(cycle);
                 }
             }
         }
@@ -355,7 +432,8 @@ impl ModuleGraph {
 
     /// Get all modules in topological order (dependencies before dependents)
     ///
-    /// Returns None if there are cycles.
+    /// Independent modules come in ID (declaration) order, so the result is
+    /// deterministic. Returns None if there are cycles.
     pub fn topological_sort(&self) -> Option<Vec<ModuleId>> {
         let mut in_degree: HashMap<ModuleId, usize> = HashMap::new();
         let mut adjacency: HashMap<ModuleId, Vec<ModuleId>> = HashMap::new();
@@ -367,20 +445,18 @@ impl ModuleGraph {
         }
 
         // Build graph
-        for (&id, module) in &self.modules {
-            for dep in &module.dependencies {
-                if let Some(&target_id) = self.path_to_module.get(&dep.target) {
-                    adjacency.entry(target_id).or_default().push(id);
-                    *in_degree.entry(id).or_insert(0) += 1;
-                }
+        for id in self.ids() {
+            for target_id in self.dependency_targets(&self.modules[&id]) {
+                adjacency.entry(target_id).or_default().push(id);
+                *in_degree.entry(id).or_insert(0) += 1;
             }
         }
 
         // Kahn's algorithm
-        let mut queue: VecDeque<ModuleId> = in_degree
-            .iter()
-            .filter(|(_, &deg)| deg == 0)
-            .map(|(&id, _)| id)
+        let mut queue: VecDeque<ModuleId> = self
+            .ids()
+            .into_iter()
+            .filter(|id| in_degree[id] == 0)
             .collect();
 
         let mut result = Vec::new();
@@ -432,9 +508,10 @@ impl Module {
 }
 
 /// Module-related errors
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
 pub enum ModuleError {
     /// Duplicate module declaration
+    #[error("the module `{name}` is defined multiple times")]
     DuplicateModule {
         /// Module name
         name: String,
@@ -444,11 +521,13 @@ pub enum ModuleError {
         second_span: Span,
     },
     /// Cyclic module dependency
+    #[error("cyclic dependency between modules {cycle:?}")]
     CyclicDependency {
         /// The cycle (module IDs)
         cycle: Vec<ModuleId>,
     },
     /// Module not found
+    #[error("unresolved module `{path}`")]
     ModuleNotFound {
         /// Module path
         path: ModulePath,
@@ -457,6 +536,26 @@ pub enum ModuleError {
     },
 }
 
+impl ModuleError {
+    /// Diagnostic code for this error
+    pub fn code(&self) -> &'static str {
+        match self {
+            ModuleError::DuplicateModule { .. } => "E0428",
+            ModuleError::CyclicDependency { .. } => "E0391",
+            ModuleError::ModuleNotFound { .. } => "E0432",
+        }
+    }
+
+    /// Source location the error is reported at
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ModuleError::DuplicateModule { second_span, .. } => Some(*second_span),
+            ModuleError::CyclicDependency { .. } => None,
+            ModuleError::ModuleNotFound { span, .. } => Some(*span),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -593,4 +692,75 @@ mod tests {
         assert!(sorted.is_some());
         assert_eq!(sorted.unwrap().len(), 3); // root + a + b
     }
+
+    fn module_decl(name: &str) -> ModuleDecl {
+        ModuleDecl {
+            name: name.to_string(),
+            items: None,
+            is_pub: true,
+            span: Span::dummy(),
+        }
+    }
+
+    fn use_decl(segments: &[&str]) -> UseDecl {
+        UseDecl {
+            tree: UseTree::Path {
+                path: Path {
+                    segments: segments.iter().map(|s| s.to_string()).collect(),
+                    generics: vec![],
+                },
+                alias: None,
+            },
+            is_pub: false,
+            span: Span::dummy(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_module_path() {
+        let mut graph = ModuleGraph::new("my_crate".to_string());
+        let root_id = graph.root_id();
+        let foo_id = graph.add_module(&module_decl("foo"), root_id).unwrap();
+        let bar_id = graph.add_module(&module_decl("bar"), foo_id).unwrap();
+        let baz_id = graph.add_module(&module_decl("baz"), root_id).unwrap();
+
+        let path = |segments: &[&str]| -> Vec<String> {
+            segments.iter().map(|s| s.to_string()).collect()
+        };
+
+        assert_eq!(graph.resolve_module_path(root_id, &path(&["foo", "bar"])), Some(bar_id));
+        assert_eq!(graph.resolve_module_path(bar_id, &path(&["crate", "baz"])), Some(baz_id));
+        assert_eq!(graph.resolve_module_path(bar_id, &path(&["super"])), Some(foo_id));
+        assert_eq!(graph.resolve_module_path(foo_id, &path(&["self", "bar"])), Some(bar_id));
+        // Not a child of `bar`, so looked up from the crate root
+        assert_eq!(graph.resolve_module_path(bar_id, &path(&["baz"])), Some(baz_id));
+        assert_eq!(graph.resolve_module_path(root_id, &path(&["std", "io"])), None);
+        assert_eq!(graph.resolve_module_path(root_id, &path(&["super"])), None);
+    }
+
+    #[test]
+    fn test_topological_sort_follows_uses() {
+        let mut graph = ModuleGraph::new("my_crate".to_string());
+        let root_id = graph.root_id();
+        let app_id = graph.add_module(&module_decl("app"), root_id).unwrap();
+        let util_id = graph.add_module(&module_decl("util"), root_id).unwrap();
+        let io_id = graph.add_module(&module_decl("io"), root_id).unwrap();
+
+        // root -> app -> util, app -> io; paths into other crates are ignored
+        graph.add_use(&use_decl(&["app", "run"]), root_id).unwrap();
+        graph.add_use(&use_decl(&["crate", "util", "helper"]), app_id).unwrap();
+        graph.add_use(&use_decl(&["io", "read"]), app_id).unwrap();
+        graph.add_use(&use_decl(&["std", "fmt", "Display"]), util_id).unwrap();
+
+        let expected = vec![util_id, io_id, app_id, root_id];
+        for _ in 0..5 {
+            assert_eq!(graph.topological_sort(), Some(expected.clone()));
+        }
+        assert!(graph.detect_cycles().is_ok());
+
+        // util -> app closes a cycle
+        graph.add_use(&use_decl(&["super", "app", "run"]), util_id).unwrap();
+        assert_eq!(graph.topological_sort(), None);
+        assert!(matches!(graph.detect_cycles(), Err(ModuleError::CyclicDependency { .. })));
+    }
 }
@@ -5,15 +5,17 @@
 //! their definitions while respecting scope rules, visibility, and hygiene.
 
 use crate::hygiene::{HygieneContext, HygieneResolver};
-use crate::modules::{ModuleError, ModuleGraph, ModuleId};
+use crate::modules::{DependencyKind, ModuleDependency, ModuleError, ModuleGraph, ModuleId};
 use crate::scopes::{ScopeId, ScopeKind, ScopeTree};
 use crate::symbols::{Symbol, SymbolId, SymbolKind, SymbolTable, Visibility};
-use aurora_ast::decl::{Item, ItemKind, Param};
-use aurora_ast::expr::{Expr, ExprId, ExprKind, Path};
+use aurora_ast::decl::{
+    FunctionDecl, GenericParam, ImplDecl, ImplItem, Item, ItemId, ItemKind, ModuleDecl, Param, VariantFields,
+};
+use aurora_ast::expr::{Expr, ExprId, ExprKind, GenericArg, InterpolationPart, Path, TypeId};
 use aurora_ast::pattern::{Pattern, PatternId, PatternKind};
 use aurora_ast::span::HygieneId;
 use aurora_ast::stmt::{Block, Stmt, StmtId, StmtKind};
-use aurora_ast::{Arena, Program, Span};
+use aurora_ast::{Arena, Program, Span, TypeKind};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -68,6 +70,8 @@ pub struct ResolutionMap {
     expr_resolutions: HashMap<ExprId, SymbolId>,
     /// Maps pattern IDs (bindings) to symbol IDs
     pattern_bindings: HashMap<PatternId, SymbolId>,
+    /// Maps type and pattern IDs whose path names an item to its symbol
+    path_resolutions: HashMap<u32, SymbolId>,
     /// Resolution chains for debugging/diagnostics
     resolution_chains: HashMap<ExprId, ResolutionChain>,
 }
@@ -78,6 +82,7 @@ impl ResolutionMap {
         Self {
             expr_resolutions: HashMap::new(),
             pattern_bindings: HashMap::new(),
+            path_resolutions: HashMap::new(),
             resolution_chains: HashMap::new(),
         }
     }
@@ -97,6 +102,11 @@ impl ResolutionMap {
         self.pattern_bindings.insert(pattern_id, symbol_id);
     }
 
+    /// Record that the path of a type or pattern names a symbol
+    pub fn resolve_path(&mut self, node_id: u32, symbol_id: SymbolId) {
+        self.path_resolutions.insert(node_id, symbol_id);
+    }
+
     /// Get the resolution for an expression
     pub fn get_expr_resolution(&self, expr_id: ExprId) -> Option<SymbolId> {
         self.expr_resolutions.get(&expr_id).copied()
@@ -112,6 +122,16 @@ impl ResolutionMap {
         self.pattern_bindings.get(&pattern_id).copied()
     }
 
+    /// Iterate over all resolved expressions and their symbols
+    pub fn expr_resolutions(&self) -> impl Iterator<Item = (ExprId, SymbolId)> + '_ {
+        self.expr_resolutions.iter().map(|(&expr_id, &symbol_id)| (expr_id, symbol_id))
+    }
+
+    /// Iterate over all types and patterns whose paths resolved, and their symbols
+    pub fn path_resolutions(&self) -> impl Iterator<Item = (u32, SymbolId)> + '_ {
+        self.path_resolutions.iter().map(|(&node_id, &symbol_id)| (node_id, symbol_id))
+    }
+
     /// Get the number of resolved expressions
     pub fn expr_count(&self) -> usize {
         self.expr_resolutions.len()
@@ -151,6 +171,10 @@ pub struct Resolver<'a> {
     diagnostics: Vec<ResolutionError>,
     /// Prelude scope (standard library items)
     prelude_scope: ScopeId,
+    /// Member scopes of structs and enums, keyed by the type's symbol
+    type_scopes: HashMap<SymbolId, ScopeId>,
+    /// Scope holding the items of each module
+    module_scopes: HashMap<ModuleId, ScopeId>,
 }
 
 impl<'a> Resolver<'a> {
@@ -172,10 +196,13 @@ impl<'a> Resolver<'a> {
         // Return to global scope
         scopes.pop_scope();
 
+        let modules = ModuleGraph::new(crate_name);
+        let module_scopes = HashMap::from([(modules.root_id(), scopes.global_scope().id)]);
+
         Self {
             symbols,
             scopes,
-            modules: ModuleGraph::new(crate_name),
+            modules,
             hygiene_ctx: HygieneContext::new(),
             hygiene_resolver: HygieneResolver::new(),
             resolution_map: ResolutionMap::new(),
@@ -183,6 +210,8 @@ impl<'a> Resolver<'a> {
             arena,
             diagnostics: Vec::new(),
             prelude_scope,
+            type_scopes: HashMap::new(),
+            module_scopes,
         }
     }
 
@@ -236,21 +265,21 @@ impl<'a> Resolver<'a> {
     /// Resolve a program
     ///
     /// This is the main entry point. It performs name resolution in multiple passes:
-    /// 1. Collect all top-level declarations
-    /// 2. Build module graph
+    /// 1. Collect all declarations, module by module (then impl methods,
+    ///    which need the types they extend)
+    /// 2. Build module graph and bind `use` imports
     /// 3. Resolve identifier uses
     pub fn resolve(mut self, program: &Program) -> ResolutionResult {
-        // Pass 1: Collect top-level items
-        for &item_id in &program.items {
-            if let Some(item) = self.arena.get_item(item_id) {
-                self.collect_item(item, item_id);
-            }
-        }
+        // Pass 1: Collect items
+        let root = self.modules.root_id();
+        self.modules.set_items(root, program.items.clone());
+        self.collect_items(&program.items);
 
-        // Pass 2: Check for module cycles
+        // Pass 2: Check for module cycles, then bind imports
         if let Err(e) = self.modules.detect_cycles() {
             self.diagnostics.push(ResolutionError::ModuleError(e));
         }
+        self.resolve_imports();
 
         // Pass 3: Resolve uses
         for &item_id in &program.items {
@@ -264,10 +293,37 @@ impl<'a> Resolver<'a> {
             scopes: self.scopes,
             modules: self.modules,
             resolution_map: self.resolution_map,
+            module_scopes: self.module_scopes,
             diagnostics: self.diagnostics,
         }
     }
 
+    /// Collect declarations from a list of items in the current module
+    fn collect_items(&mut self, items: &[ItemId]) {
+        for &item_id in items {
+            if let Some(item) = self.arena.get_item(item_id) {
+                self.collect_item(item, item_id);
+            }
+        }
+        for &item_id in items {
+            if let Some(Item { kind: ItemKind::Impl(impl_decl), .. }) = self.arena.get_item(item_id) {
+                self.collect_impl_methods(impl_decl);
+            }
+        }
+    }
+
+    /// Collect the items of a module in a scope of its own
+    fn collect_module(&mut self, module_id: ModuleId, module: &ModuleDecl, items: &[ItemId]) {
+        let scope = self.scopes.push_named_scope(ScopeKind::Module, module.span, module.name.clone());
+        self.module_scopes.insert(module_id, scope);
+
+        let parent = std::mem::replace(&mut self.current_module, module_id);
+        self.collect_items(items);
+        self.current_module = parent;
+
+        self.scopes.pop_scope();
+    }
+
     /// Collect declarations from an item (pass 1)
     fn collect_item(&mut self, item: &Item, _item_id: u32) {
         match &item.kind {
@@ -298,6 +354,37 @@ impl<'a> Resolver<'a> {
                     });
                 }
             }
+            ItemKind::Struct(s) => {
+                let vis = if s.is_pub {
+                    Visibility::Public
+                } else {
+                    Visibility::Private
+                };
+
+                let members = match &s.fields {
+                    VariantFields::Unit => Vec::new(),
+                    fields => fields
+                        .fields()
+                        .iter()
+                        .map(|field| (field.name.clone(), SymbolKind::Field, field.span))
+                        .collect(),
+                };
+                self.collect_type(&s.name, SymbolKind::Struct, vis, s.span, members);
+            }
+            ItemKind::Enum(e) => {
+                let vis = if e.is_pub {
+                    Visibility::Public
+                } else {
+                    Visibility::Private
+                };
+
+                let members = e
+                    .variants
+                    .iter()
+                    .map(|variant| (variant.name.clone(), SymbolKind::Variant, variant.span))
+                    .collect();
+                self.collect_type(&e.name, SymbolKind::Enum, vis, e.span, members);
+            }
             ItemKind::Type(ty) => {
                 let vis = if ty.is_pub {
                     Visibility::Public
@@ -335,9 +422,13 @@ impl<'a> Resolver<'a> {
                 self.symbols.insert(symbol);
             }
             ItemKind::Module(module) => {
-                if let Err(e) = self.modules.add_module(module, self.current_module) {
-                    self.diagnostics.push(ResolutionError::ModuleError(e));
-                }
+                let module_id = match self.modules.add_module(module, self.current_module) {
+                    Ok(module_id) => Some(module_id),
+                    Err(e) => {
+                        self.diagnostics.push(ResolutionError::ModuleError(e));
+                        None
+                    }
+                };
 
                 let vis = if module.is_pub {
                     Visibility::Public
@@ -355,6 +446,10 @@ impl<'a> Resolver<'a> {
                 );
 
                 self.symbols.insert(symbol);
+
+                if let (Some(module_id), Some(items)) = (module_id, &module.items) {
+                    self.collect_module(module_id, module, items);
+                }
             }
             ItemKind::Use(use_decl) => {
                 if let Err(e) = self.modules.add_use(use_decl, self.current_module) {
@@ -385,38 +480,263 @@ impl<'a> Resolver<'a> {
         }
     }
 
-    /// Resolve uses in an item (pass 3)
-    fn resolve_item(&mut self, item: &Item, _item_id: u32) {
-        match &item.kind {
-            ItemKind::Function(func) => {
-                // Enter function scope
-                let _func_scope = self.scopes.push_named_scope(
-                    ScopeKind::Function,
-                    func.span,
-                    func.name.clone(),
-                );
+    /// Declare a struct or enum and its members (fields or variants)
+    ///
+    /// Members live in a scope of their own so they are only reachable
+    /// through a path such as `Color::Red`.
+    fn collect_type(
+        &mut self,
+        name: &str,
+        kind: SymbolKind,
+        vis: Visibility,
+        span: Span,
+        members: Vec<(String, SymbolKind, Span)>,
+    ) {
+        let symbol = Symbol::new(0, name.to_string(), kind, vis, span, self.scopes.current_scope());
+        let Some(symbol_id) = self.symbols.insert(symbol) else {
+            self.diagnostics.push(ResolutionError::DuplicateDefinition {
+                name: name.to_string(),
+                first_span: span,
+                second_span: span,
+            });
+            return;
+        };
+
+        let member_scope = self.scopes.push_named_scope(ScopeKind::Type, span, name.to_string());
+        self.scopes.pop_scope();
+        self.type_scopes.insert(symbol_id, member_scope);
+
+        for (member, member_kind, member_span) in members {
+            let symbol = Symbol::new(0, member.clone(), member_kind, vis, member_span, member_scope);
+            if self.symbols.insert(symbol).is_none() {
+                self.diagnostics.push(ResolutionError::DuplicateDefinition {
+                    name: format!("{}::{}", name, member),
+                    first_span: member_span,
+                    second_span: member_span,
+                });
+            }
+        }
+    }
+
+    /// Declare the methods of an inherent impl in the member scope of its type
+    fn collect_impl_methods(&mut self, impl_decl: &ImplDecl) {
+        let Some(TypeKind::Path { path }) =
+            self.arena.get_type_node(impl_decl.self_ty).map(|ty| &ty.kind)
+        else {
+            return;
+        };
+        let Some(member_scope) = path
+            .segments
+            .first()
+            .and_then(|name| self.lookup_name(name))
+            .and_then(|symbol_id| self.type_scopes.get(&symbol_id).copied())
+        else {
+            return;
+        };
+
+        for item in &impl_decl.items {
+            if let aurora_ast::decl::ImplItem::Function(func) = item {
+                let vis = if func.is_pub {
+                    Visibility::Public
+                } else {
+                    Visibility::Private
+                };
+                let symbol = Symbol::new(0, func.name.clone(), SymbolKind::Method, vis, func.span, member_scope);
+                if self.symbols.insert(symbol).is_none() {
+                    self.diagnostics.push(ResolutionError::DuplicateDefinition {
+                        name: func.name.clone(),
+                        first_span: func.span,
+                        second_span: func.span,
+                    });
+                }
+            }
+        }
+    }
 
-                // Resolve parameters
-                for param in &func.params {
-                    self.resolve_param(param);
+    /// Bind the names brought in by each module's `use` declarations
+    ///
+    /// Modules are visited in dependency order so re-exports (`pub use`)
+    /// are bound before anything imports through them.
+    fn resolve_imports(&mut self) {
+        let order = self
+            .modules
+            .topological_sort()
+            .unwrap_or_else(|| self.modules.ids());
+
+        for module_id in order {
+            let Some(&scope) = self.module_scopes.get(&module_id) else {
+                continue;
+            };
+            let dependencies = self
+                .modules
+                .get(module_id)
+                .map(|module| module.dependencies.clone())
+                .unwrap_or_default();
+            for dep in &dependencies {
+                self.resolve_import(module_id, scope, dep);
+            }
+        }
+    }
+
+    /// Bind a single import into the scope of the importing module
+    fn resolve_import(&mut self, module_id: ModuleId, scope: ScopeId, dep: &ModuleDependency) {
+        let Some(source) = self.modules.dependency_module(module_id, dep) else {
+            // Paths into other crates (e.g. `std::io`) are left to later phases
+            if dep.target.is_absolute() {
+                self.diagnostics.push(ResolutionError::ModuleError(ModuleError::ModuleNotFound {
+                    path: dep.target.clone(),
+                    span: dep.span,
+                }));
+            }
+            return;
+        };
+        let Some(&source_scope) = self.module_scopes.get(&source) else {
+            return;
+        };
+
+        match &dep.kind {
+            DependencyKind::Glob => {
+                let mut imported: Vec<(String, SymbolId)> = self
+                    .symbols
+                    .symbols_in_scope(source_scope)
+                    .into_iter()
+                    .filter(|symbol| self.is_accessible(symbol, source, module_id))
+                    .map(|symbol| (symbol.name.clone(), symbol.id))
+                    .collect();
+                imported.sort();
+
+                // Glob imports never shadow names the module already has
+                for (name, symbol_id) in imported {
+                    self.symbols.import(scope, name, symbol_id);
+                }
+            }
+            DependencyKind::Item(name) => {
+                // `use foo;` and `use foo::{self}` name something already in scope
+                if dep.target.segments.len() < 2 || name == "self" {
+                    return;
                 }
 
-                // Resolve body
-                self.resolve_block(&func.body);
+                let Some(symbol_id) = self.symbols.lookup(source_scope, name) else {
+                    self.diagnostics.push(ResolutionError::UnresolvedImport {
+                        path: dep.target.to_string(),
+                        span: dep.span,
+                    });
+                    return;
+                };
+                let Some(symbol) = self.symbols.get(symbol_id) else {
+                    return;
+                };
+                if !self.is_accessible(symbol, source, module_id) {
+                    self.diagnostics.push(ResolutionError::PrivateItem {
+                        name: name.clone(),
+                        span: dep.span,
+                    });
+                    return;
+                }
 
-                // Exit function scope
-                self.scopes.pop_scope();
+                let def_span = symbol.def_span;
+                let binding = dep.alias.clone().unwrap_or_else(|| name.clone());
+                if !self.symbols.import(scope, binding.clone(), symbol_id)
+                    && self.symbols.lookup(scope, &binding) != Some(symbol_id)
+                {
+                    self.diagnostics.push(ResolutionError::DuplicateDefinition {
+                        name: binding,
+                        first_span: def_span,
+                        second_span: dep.span,
+                    });
+                }
             }
+            DependencyKind::Module => {}
+        }
+    }
+
+    /// Whether a symbol defined in module `source` may be imported into module `from`
+    ///
+    /// Private items are visible to their own module and its descendants.
+    fn is_accessible(&self, symbol: &Symbol, source: ModuleId, from: ModuleId) -> bool {
+        if symbol.visibility != Visibility::Private {
+            return true;
+        }
+        let mut current = Some(from);
+        while let Some(id) = current {
+            if id == source {
+                return true;
+            }
+            current = self.modules.get(id).and_then(|module| module.parent);
+        }
+        false
+    }
+
+    /// Resolve uses in an item (pass 3)
+    fn resolve_item(&mut self, item: &Item, _item_id: u32) {
+        match &item.kind {
+            ItemKind::Function(func) => self.resolve_function(func),
             ItemKind::Const(c) => {
+                self.resolve_type(c.ty);
                 // Resolve value expression
                 self.resolve_expr(c.value);
             }
+            ItemKind::Struct(decl) => {
+                self.scopes.push_scope(ScopeKind::Block, decl.span);
+                self.bind_generics(&decl.generics);
+                for field in decl.fields.fields() {
+                    self.resolve_type(field.ty);
+                }
+                self.scopes.pop_scope();
+            }
+            ItemKind::Enum(decl) => {
+                self.scopes.push_scope(ScopeKind::Block, decl.span);
+                self.bind_generics(&decl.generics);
+                for field in decl.variants.iter().flat_map(|variant| variant.fields.fields()) {
+                    self.resolve_type(field.ty);
+                }
+                self.scopes.pop_scope();
+            }
+            ItemKind::Type(decl) => {
+                self.scopes.push_scope(ScopeKind::Block, decl.span);
+                self.bind_generics(&decl.generics);
+                self.resolve_type(decl.ty);
+                self.scopes.pop_scope();
+            }
+            ItemKind::Impl(impl_decl) => {
+                self.scopes.push_scope(ScopeKind::Block, impl_decl.span);
+                self.bind_generics(&impl_decl.generics);
+                self.resolve_type(impl_decl.self_ty);
+                for impl_item in &impl_decl.items {
+                    match impl_item {
+                        ImplItem::Function(func) => self.resolve_function(func),
+                        ImplItem::Const(c) => {
+                            self.resolve_type(c.ty);
+                            self.resolve_expr(c.value);
+                        }
+                        ImplItem::Type(decl) => self.resolve_type(decl.ty),
+                    }
+                }
+                self.scopes.pop_scope();
+            }
             ItemKind::Module(module) => {
-                // If inline module, resolve its items
-                if let Some(items) = &module.items {
-                    // TODO: Enter module scope and resolve items
-                    let _ = items;
+                let Some(items) = &module.items else {
+                    return;
+                };
+                let Some(module_id) = self.modules.child(self.current_module, &module.name) else {
+                    return;
+                };
+                let Some(&scope) = self.module_scopes.get(&module_id) else {
+                    return;
+                };
+
+                let outer_scope = self.scopes.current_scope();
+                let outer_module = std::mem::replace(&mut self.current_module, module_id);
+                self.scopes.enter_scope(scope);
+
+                for &item_id in items {
+                    if let Some(item) = self.arena.get_item(item_id) {
+                        self.resolve_item(item, item_id);
+                    }
                 }
+
+                self.scopes.enter_scope(outer_scope);
+                self.current_module = outer_module;
             }
             _ => {
                 // Other items don't have expressions to resolve yet
@@ -424,6 +744,83 @@ impl<'a> Resolver<'a> {
         }
     }
 
+    /// Resolve the signature and body of a function or method
+    fn resolve_function(&mut self, func: &FunctionDecl) {
+        // Enter function scope
+        let _func_scope = self.scopes.push_named_scope(ScopeKind::Function, func.span, func.name.clone());
+        self.bind_generics(&func.generics);
+
+        // Resolve parameters
+        for param in &func.params {
+            self.resolve_type(param.ty);
+            self.resolve_param(param);
+        }
+        if let Some(ty) = func.return_type {
+            self.resolve_type(ty);
+        }
+
+        // Resolve body
+        self.resolve_block(&func.body);
+
+        // Exit function scope
+        self.scopes.pop_scope();
+    }
+
+    /// Declare generic parameters in the current scope, so they shadow
+    /// items of the same name
+    fn bind_generics(&mut self, generics: &[GenericParam]) {
+        for param in generics {
+            let symbol = Symbol::new(
+                0,
+                param.name.clone(),
+                SymbolKind::TypeParam,
+                Visibility::Private,
+                param.span,
+                self.scopes.current_scope(),
+            );
+            self.symbols.insert(symbol);
+        }
+    }
+
+    /// Record the items the paths in a type name
+    ///
+    /// Paths naming no item, such as primitive types, are left to the type
+    /// checker.
+    fn resolve_type(&mut self, type_id: TypeId) {
+        let Some(ty) = self.arena.get_type_node(type_id) else {
+            return;
+        };
+        match &ty.kind {
+            TypeKind::Path { path } => {
+                if let Some(symbol_id) = self.lookup_path(&path.segments) {
+                    self.resolution_map.resolve_path(type_id, symbol_id);
+                    self.symbols.mark_used(symbol_id);
+                }
+                for arg in &path.generics {
+                    if let GenericArg::Type(arg) = arg {
+                        self.resolve_type(*arg);
+                    }
+                }
+            }
+            TypeKind::Tuple(types) => {
+                for &ty in types {
+                    self.resolve_type(ty);
+                }
+            }
+            TypeKind::Array { element, .. } | TypeKind::Slice { element } => self.resolve_type(**element),
+            TypeKind::Reference { inner, .. } | TypeKind::Pointer { inner, .. } => self.resolve_type(**inner),
+            TypeKind::Function { params, return_type } => {
+                for &param in params {
+                    self.resolve_type(param);
+                }
+                if let Some(ty) = return_type {
+                    self.resolve_type(**ty);
+                }
+            }
+            _ => {}
+        }
+    }
+
     /// Resolve a parameter
     fn resolve_param(&mut self, param: &Param) {
         // Collect bindings from the pattern
@@ -462,7 +859,8 @@ impl<'a> Resolver<'a> {
                     }
                 }
             }
-            PatternKind::Struct { path: _, fields, has_rest: _ } => {
+            PatternKind::Struct { path, fields, has_rest: _ } => {
+                self.check_pattern_path(path, pattern.span, pattern_id);
                 for field in fields {
                     if let Some(pat_id) = field.pattern {
                         if let Some(pat) = self.arena.get_pattern(pat_id) {
@@ -482,7 +880,8 @@ impl<'a> Resolver<'a> {
                     }
                 }
             }
-            PatternKind::TupleStruct { path: _, fields } => {
+            PatternKind::TupleStruct { path, fields } => {
+                self.check_pattern_path(path, pattern.span, pattern_id);
                 for &pat_id in fields {
                     if let Some(pat) = self.arena.get_pattern(pat_id) {
                         self.collect_pattern_bindings(pat, pat_id);
@@ -501,9 +900,11 @@ impl<'a> Resolver<'a> {
                     self.collect_pattern_bindings(pat, **inner);
                 }
             }
+            PatternKind::Path(path) => {
+                self.check_pattern_path(path, pattern.span, pattern_id);
+            }
             PatternKind::Wildcard
             | PatternKind::Literal(_)
-            | PatternKind::Path(_)
             | PatternKind::Range { .. }
             | PatternKind::Rest => {
                 // These don't introduce bindings
@@ -535,7 +936,11 @@ impl<'a> Resolver<'a> {
     /// Resolve a statement
     fn resolve_stmt(&mut self, stmt: &Stmt, _stmt_id: StmtId) {
         match &stmt.kind {
-            StmtKind::Let { pattern, ty: _, init, mutable: _ } => {
+            StmtKind::Let { pattern, ty, init, mutable: _ } => {
+                if let Some(ty) = ty {
+                    self.resolve_type(*ty);
+                }
+
                 // First resolve the init expression (RHS)
                 if let Some(init_expr) = init {
                     self.resolve_expr(*init_expr);
@@ -589,6 +994,13 @@ impl<'a> Resolver<'a> {
                     self.resolve_expr(*collection);
                     self.resolve_expr(*index);
                 }
+                ExprKind::Interpolated(parts) => {
+                    for part in parts {
+                        if let InterpolationPart::Expr(e) = part {
+                            self.resolve_expr(*e);
+                        }
+                    }
+                }
                 ExprKind::Tuple(exprs) => {
                     for &e in exprs {
                         self.resolve_expr(e);
@@ -599,7 +1011,8 @@ impl<'a> Resolver<'a> {
                         self.resolve_expr(e);
                     }
                 }
-                ExprKind::Struct { path: _, fields } => {
+                ExprKind::Struct { path, fields } => {
+                    self.resolve_path(path, expr.span, expr_id, expr.hygiene);
                     for field in fields {
                         self.resolve_expr(field.value);
                     }
@@ -786,13 +1199,15 @@ impl<'a> Resolver<'a> {
         _use_hygiene: HygieneId,
     ) {
         // For simple paths, resolve the first segment as an identifier
-        // TODO: Handle multi-segment paths properly
         if let Some(first) = path.segments.first() {
-            if path.segments.len() == 1 {
+            if first == "Self" {
+                // Left to the type checker, which knows the impl's type
+            } else if path.segments.len() == 1 {
                 self.resolve_ident(first, span, expr_id, HygieneId::root());
+            } else if let Some(symbol_id) = self.resolve_qualified(&path.segments) {
+                self.resolution_map.resolve_expr(expr_id, symbol_id);
+                self.symbols.mark_used(symbol_id);
             } else {
-                // Multi-segment path - would need module resolution
-                // For now, just record as unresolved
                 self.diagnostics.push(ResolutionError::UndefinedSymbol {
                     name: path.segments.join("::"),
                     span,
@@ -801,6 +1216,76 @@ impl<'a> Resolver<'a> {
         }
     }
 
+    /// Resolve `Type::member` to a variant, field or method of a struct or enum
+    fn resolve_member(&self, segments: &[String]) -> Option<SymbolId> {
+        let [type_name, member] = segments else {
+            return None;
+        };
+        let type_id = self.lookup_name(type_name)?;
+        let member_scope = self.type_scopes.get(&type_id)?;
+        self.symbols.lookup(*member_scope, member)
+    }
+
+    /// Resolve a multi-segment path: `Type::member`, `module::item` or
+    /// `module::Type::member` (module prefixes may start with `crate`,
+    /// `self` or `super`)
+    fn resolve_qualified(&self, segments: &[String]) -> Option<SymbolId> {
+        if let Some(symbol_id) = self.resolve_member(segments) {
+            return Some(symbol_id);
+        }
+
+        (1..segments.len()).rev().find_map(|split| {
+            let module = self
+                .modules
+                .resolve_module_path(self.current_module, &segments[..split])?;
+            let scope = *self.module_scopes.get(&module)?;
+            match &segments[split..] {
+                [name] => self.symbols.lookup(scope, name),
+                [type_name, member] => {
+                    let type_id = self.symbols.lookup(scope, type_name)?;
+                    self.symbols.lookup(*self.type_scopes.get(&type_id)?, member)
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Look up a name in the current scope chain and the prelude
+    fn lookup_name(&self, name: &str) -> Option<SymbolId> {
+        self.scopes
+            .parent_chain(self.scopes.current_scope())
+            .into_iter()
+            .chain(std::iter::once(self.prelude_scope))
+            .find_map(|scope_id| self.symbols.lookup(scope_id, name))
+    }
+
+    /// Look up a path of one segment in scope, or a qualified one
+    fn lookup_path(&self, segments: &[String]) -> Option<SymbolId> {
+        match segments {
+            [name] => self.lookup_name(name),
+            segments => self.resolve_qualified(segments),
+        }
+    }
+
+    /// Check that the path of a struct, tuple struct or unit pattern resolves
+    fn check_pattern_path(&mut self, path: &Path, span: Span, pattern_id: PatternId) {
+        // `Self` is known to the type checker
+        if path.segments.first().is_some_and(|first| first == "Self") {
+            return;
+        }
+
+        match self.lookup_path(&path.segments) {
+            Some(symbol_id) => {
+                self.resolution_map.resolve_path(pattern_id, symbol_id);
+                self.symbols.mark_used(symbol_id);
+            }
+            None => self.diagnostics.push(ResolutionError::UndefinedSymbol {
+                name: path.segments.join("::"),
+                span,
+            }),
+        }
+    }
+
     /// Get the symbols table
     pub fn symbols(&self) -> &SymbolTable {
         &self.symbols
@@ -833,6 +1318,8 @@ pub struct ResolutionResult {
     pub modules: ModuleGraph,
     /// Resolution map
     pub resolution_map: ResolutionMap,
+    /// Scope holding the items of each module
+    pub module_scopes: HashMap<ModuleId, ScopeId>,
     /// Diagnostics
     pub diagnostics: Vec<ResolutionError>,
 }
@@ -850,9 +1337,10 @@ impl ResolutionResult {
 }
 
 /// Resolution errors
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
 pub enum ResolutionError {
     /// Undefined symbol
+    #[error("cannot find `{name}` in this scope")]
     UndefinedSymbol {
         /// Symbol name
         name: String,
@@ -860,6 +1348,7 @@ pub enum ResolutionError {
         span: Span,
     },
     /// Duplicate definition
+    #[error("the name `{name}` is defined multiple times")]
     DuplicateDefinition {
         /// Symbol name
         name: String,
@@ -868,14 +1357,70 @@ pub enum ResolutionError {
         /// Second definition span
         second_span: Span,
     },
+    /// Import of an item that does not exist in the named module
+    #[error("unresolved import `{path}`")]
+    UnresolvedImport {
+        /// Full import path
+        path: String,
+        /// Span of the `use` declaration
+        span: Span,
+    },
+    /// Import of an item that is private to another module
+    #[error("`{name}` is private")]
+    PrivateItem {
+        /// Item name
+        name: String,
+        /// Span of the `use` declaration
+        span: Span,
+    },
+    /// Trait name declared in more than one module (traits keep their
+    /// names once modules are flattened)
+    #[error("the trait `{name}` is defined in more than one module")]
+    ConflictingItem {
+        /// Item name
+        name: String,
+        /// First definition span
+        first_span: Span,
+        /// Second definition span
+        second_span: Span,
+    },
     /// Module error
+    #[error(transparent)]
     ModuleError(ModuleError),
 }
 
+impl ResolutionError {
+    /// Diagnostic code for this error
+    pub fn code(&self) -> &'static str {
+        match self {
+            ResolutionError::UndefinedSymbol { .. } => "E0425",
+            ResolutionError::DuplicateDefinition { .. }
+            | ResolutionError::ConflictingItem { .. } => "E0428",
+            ResolutionError::UnresolvedImport { .. } => "E0432",
+            ResolutionError::PrivateItem { .. } => "E0603",
+            ResolutionError::ModuleError(e) => e.code(),
+        }
+    }
+
+    /// Source location the error is reported at
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ResolutionError::UndefinedSymbol { span, .. }
+            | ResolutionError::UnresolvedImport { span, .. }
+            | ResolutionError::PrivateItem { span, .. } => Some(*span),
+            ResolutionError::DuplicateDefinition { second_span, .. }
+            | ResolutionError::ConflictingItem { second_span, .. } => Some(*second_span),
+            ResolutionError::ModuleError(e) => e.span(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use aurora_ast::decl::{ConstDecl, FunctionDecl, ItemKind};
+    use aurora_ast::decl::{
+        ConstDecl, EnumDecl, EnumVariant, FunctionDecl, ItemKind, UseDecl, UseTree,
+    };
     use aurora_ast::expr::Literal;
     use aurora_ast::stmt::Stmt;
     use aurora_ast::ty::{Type, TypeKind};
@@ -995,7 +1540,7 @@ mod tests {
 
         let item_id = arena.alloc_item(item);
 
-        let program = Program::new(vec![item_id], Span::dummy());
+        let program = Program::new(vec![item_id], Span::dummy(), arena.clone());
 
         let resolver = Resolver::new(&arena, "test_crate".to_string());
         let result = resolver.resolve(&program);
@@ -1017,7 +1562,7 @@ mod tests {
 
         // Create a constant: const PI = 3.14
         let lit_expr = Expr {
-            kind: ExprKind::Literal(Literal::Float(3.14)),
+            kind: ExprKind::Literal(Literal::Float(3.14, None)),
             span: Span::dummy(),
             hygiene: HygieneId::root(),
         };
@@ -1048,7 +1593,7 @@ mod tests {
         };
 
         let item_id = arena.alloc_item(item);
-        let program = Program::new(vec![item_id], Span::dummy());
+        let program = Program::new(vec![item_id], Span::dummy(), arena.clone());
 
         let resolver = Resolver::new(&arena, "test_crate".to_string());
         let result = resolver.resolve(&program);
@@ -1098,7 +1643,7 @@ mod tests {
         let item1_id = arena.alloc_item(item1);
         let item2_id = arena.alloc_item(item2);
 
-        let program = Program::new(vec![item1_id, item2_id], Span::dummy());
+        let program = Program::new(vec![item1_id, item2_id], Span::dummy(), arena.clone());
 
         let resolver = Resolver::new(&arena, "test_crate".to_string());
         let result = resolver.resolve(&program);
@@ -1119,7 +1664,7 @@ mod tests {
 
         // Create: let x = 42;
         let lit = Expr {
-            kind: ExprKind::Literal(Literal::Int(42)),
+            kind: ExprKind::Literal(Literal::Int(42, None)),
             span: Span::dummy(),
             hygiene: HygieneId::root(),
         };
@@ -1172,7 +1717,7 @@ mod tests {
         };
 
         let item_id = arena.alloc_item(item);
-        let program = Program::new(vec![item_id], Span::dummy());
+        let program = Program::new(vec![item_id], Span::dummy(), arena.clone());
 
         let resolver = Resolver::new(&arena, "test_crate".to_string());
         let result = resolver.resolve(&program);
@@ -1188,7 +1733,7 @@ mod tests {
 
         // Create: let x = 42; x
         let lit = Expr {
-            kind: ExprKind::Literal(Literal::Int(42)),
+            kind: ExprKind::Literal(Literal::Int(42, None)),
             span: Span::dummy(),
             hygiene: HygieneId::root(),
         };
@@ -1249,7 +1794,7 @@ mod tests {
         };
 
         let item_id = arena.alloc_item(item);
-        let program = Program::new(vec![item_id], Span::dummy());
+        let program = Program::new(vec![item_id], Span::dummy(), arena.clone());
 
         let resolver = Resolver::new(&arena, "test_crate".to_string());
         let result = resolver.resolve(&program);
@@ -1300,7 +1845,7 @@ mod tests {
         };
 
         let item_id = arena.alloc_item(item);
-        let program = Program::new(vec![item_id], Span::dummy());
+        let program = Program::new(vec![item_id], Span::dummy(), arena.clone());
 
         let resolver = Resolver::new(&arena, "test_crate".to_string());
         let result = resolver.resolve(&program);
@@ -1320,13 +1865,211 @@ mod tests {
         assert!(chain.unwrap().symbol_id.is_none());
     }
 
+    #[test]
+    fn test_enum_variant_paths() {
+        let mut arena = Arena::new();
+
+        // enum Color { Red, Green }
+        let variant = |name: &str| EnumVariant {
+            name: name.to_string(),
+            fields: VariantFields::Unit,
+            span: Span::dummy(),
+        };
+        let enum_decl = EnumDecl {
+            name: "Color".to_string(),
+            generics: vec![],
+            where_clause: None,
+            variants: vec![variant("Red"), variant("Green")],
+            is_pub: false,
+            span: Span::dummy(),
+        };
+        let enum_id = arena.alloc_item(Item {
+            kind: ItemKind::Enum(enum_decl),
+            span: Span::dummy(),
+        });
+
+        // fn test() { Color::Red; Color::Blue }
+        let path_expr = |name: &str| Expr {
+            kind: ExprKind::Path(Path {
+                segments: vec!["Color".to_string(), name.to_string()],
+                generics: vec![],
+            }),
+            span: Span::dummy(),
+            hygiene: HygieneId::root(),
+        };
+        let red_id = arena.alloc_expr(path_expr("Red"));
+        let blue_id = arena.alloc_expr(path_expr("Blue"));
+        let red_stmt = arena.alloc_stmt(Stmt {
+            kind: StmtKind::Expr { expr: red_id, has_semi: true },
+            span: Span::dummy(),
+        });
+
+        let func = FunctionDecl {
+            name: "test".to_string(),
+            generics: vec![],
+            params: vec![],
+            return_type: None,
+            where_clause: None,
+            body: Block {
+                stmts: vec![red_stmt],
+                expr: Some(blue_id),
+                span: Span::dummy(),
+            },
+            is_pub: false,
+            is_async: false,
+            is_unsafe: false,
+            span: Span::dummy(),
+        };
+        let func_id = arena.alloc_item(Item {
+            kind: ItemKind::Function(func),
+            span: Span::dummy(),
+        });
+
+        let program = Program::new(vec![enum_id, func_id], Span::dummy(), arena.clone());
+        let resolver = Resolver::new(&arena, "test_crate".to_string());
+        let result = resolver.resolve(&program);
+
+        // The enum is declared at the top level, its variants are not
+        let globals = result.symbols.symbols_in_scope(0);
+        assert!(globals.iter().any(|s| s.name == "Color" && s.kind == SymbolKind::Enum));
+        assert!(!globals.iter().any(|s| s.name == "Red"));
+
+        // `Color::Red` resolves to the variant
+        let red = result.resolution_map.get_expr_resolution(red_id).unwrap();
+        let red = result.symbols.get(red).unwrap();
+        assert_eq!(red.name, "Red");
+        assert_eq!(red.kind, SymbolKind::Variant);
+
+        // `Color::Blue` does not exist
+        assert!(result.diagnostics.iter().any(|e| {
+            matches!(e, ResolutionError::UndefinedSymbol { name, .. } if name == "Color::Blue")
+        }));
+    }
+
+    #[test]
+    fn test_module_imports() {
+        let mut arena = Arena::new();
+
+        let function = |arena: &mut Arena, name: &str, is_pub: bool, stmts: Vec<StmtId>| {
+            arena.alloc_item(Item {
+                kind: ItemKind::Function(FunctionDecl {
+                    name: name.to_string(),
+                    generics: vec![],
+                    params: vec![],
+                    return_type: None,
+                    where_clause: None,
+                    body: Block { stmts, expr: None, span: Span::dummy() },
+                    is_pub,
+                    is_async: false,
+                    is_unsafe: false,
+                    span: Span::dummy(),
+                }),
+                span: Span::dummy(),
+            })
+        };
+        let import = |arena: &mut Arena, segments: &[&str], glob: bool| {
+            let path = Path {
+                segments: segments.iter().map(|s| s.to_string()).collect(),
+                generics: vec![],
+            };
+            let tree = if glob {
+                UseTree::Glob { path }
+            } else {
+                UseTree::Path { path, alias: None }
+            };
+            arena.alloc_item(Item {
+                kind: ItemKind::Use(UseDecl { tree, is_pub: false, span: Span::dummy() }),
+                span: Span::dummy(),
+            })
+        };
+        let call = |arena: &mut Arena, segments: &[&str]| {
+            let expr = arena.alloc_expr(Expr {
+                kind: ExprKind::Path(Path {
+                    segments: segments.iter().map(|s| s.to_string()).collect(),
+                    generics: vec![],
+                }),
+                span: Span::dummy(),
+                hygiene: HygieneId::root(),
+            });
+            let stmt = arena.alloc_stmt(Stmt {
+                kind: StmtKind::Expr { expr, has_semi: true },
+                span: Span::dummy(),
+            });
+            (expr, stmt)
+        };
+
+        // mod shapes { pub fn area() {} fn secret() {} }
+        let area = function(&mut arena, "area", true, vec![]);
+        let secret = function(&mut arena, "secret", false, vec![]);
+        let shapes = arena.alloc_item(Item {
+            kind: ItemKind::Module(ModuleDecl {
+                name: "shapes".to_string(),
+                items: Some(vec![area, secret]),
+                is_pub: false,
+                span: Span::dummy(),
+            }),
+            span: Span::dummy(),
+        });
+
+        // mod app { use super::shapes::*; fn run() { area(); } }
+        let glob = import(&mut arena, &["super", "shapes"], true);
+        let (glob_use, glob_stmt) = call(&mut arena, &["area"]);
+        let run = function(&mut arena, "run", false, vec![glob_stmt]);
+        let app = arena.alloc_item(Item {
+            kind: ItemKind::Module(ModuleDecl {
+                name: "app".to_string(),
+                items: Some(vec![glob, run]),
+                is_pub: false,
+                span: Span::dummy(),
+            }),
+            span: Span::dummy(),
+        });
+
+        // use shapes::area; use shapes::secret; use shapes::missing;
+        // use crate::nowhere::thing; use std::io::Read;
+        // fn main() { area(); shapes::area(); }
+        let uses = vec![
+            import(&mut arena, &["shapes", "area"], false),
+            import(&mut arena, &["shapes", "secret"], false),
+            import(&mut arena, &["shapes", "missing"], false),
+            import(&mut arena, &["crate", "nowhere", "thing"], false),
+            import(&mut arena, &["std", "io", "Read"], false),
+        ];
+        let (imported, imported_stmt) = call(&mut arena, &["area"]);
+        let (qualified, qualified_stmt) = call(&mut arena, &["shapes", "area"]);
+        let main = function(&mut arena, "main", false, vec![imported_stmt, qualified_stmt]);
+
+        let mut items = vec![shapes, app];
+        items.extend(uses);
+        items.push(main);
+        let program = Program::new(items, Span::dummy(), arena.clone());
+        let result = Resolver::new(&arena, "test_crate".to_string()).resolve(&program);
+
+        // Imported, qualified and glob-imported uses all reach the same function
+        let area_symbol = result.resolution_map.get_expr_resolution(qualified).unwrap();
+        assert_eq!(result.symbols.get(area_symbol).unwrap().name, "area");
+        assert_eq!(result.resolution_map.get_expr_resolution(imported), Some(area_symbol));
+        assert_eq!(result.resolution_map.get_expr_resolution(glob_use), Some(area_symbol));
+
+        let mut errors: Vec<_> = result.diagnostics.iter().map(|e| e.to_string()).collect();
+        errors.sort();
+        assert_eq!(
+            errors,
+            [
+                "`secret` is private",
+                "unresolved import `shapes::missing`",
+                "unresolved module `crate::nowhere::thing`",
+            ]
+        );
+    }
+
     #[test]
     fn test_shadowing_in_nested_scopes() {
         let mut arena = Arena::new();
 
         // Outer: let x = 1;
         let outer_lit = Expr {
-            kind: ExprKind::Literal(Literal::Int(1)),
+            kind: ExprKind::Literal(Literal::Int(1, None)),
             span: Span::dummy(),
             hygiene: HygieneId::root(),
         };
@@ -1355,7 +2098,7 @@ mod tests {
 
         // Inner: let x = 2;
         let inner_lit = Expr {
-            kind: ExprKind::Literal(Literal::Int(2)),
+            kind: ExprKind::Literal(Literal::Int(2, None)),
             span: Span::dummy(),
             hygiene: HygieneId::root(),
         };
@@ -1416,7 +2159,7 @@ mod tests {
         };
 
         let item_id = arena.alloc_item(item);
-        let program = Program::new(vec![item_id], Span::dummy());
+        let program = Program::new(vec![item_id], Span::dummy(), arena.clone());
 
         let resolver = Resolver::new(&arena, "test_crate".to_string());
         let result = resolver.resolve(&program);
@@ -24,6 +24,8 @@ pub enum ScopeKind {
     Loop,
     /// Match arm scope
     MatchArm,
+    /// Members of a struct or enum (fields, variants, methods)
+    Type,
 }
 
 /// A lexical scope in the program
@@ -159,6 +161,13 @@ impl ScopeTree {
         id
     }
 
+    /// Make an existing scope current (e.g. re-entering a module's scope)
+    pub fn enter_scope(&mut self, id: ScopeId) {
+        if self.scopes.contains_key(&id) {
+            self.current_scope = id;
+        }
+    }
+
     /// Pop back to parent scope
     pub fn pop_scope(&mut self) {
         if let Some(scope) = self.scopes.get(&self.current_scope) {
@@ -39,7 +39,11 @@ pub struct Symbol {
 pub enum SymbolKind {
     /// Function declaration
     Function,
-    /// Type declaration (struct, enum, type alias)
+    /// Struct declaration
+    Struct,
+    /// Enum declaration
+    Enum,
+    /// Type alias declaration
     Type,
     /// Trait declaration
     Trait,
@@ -155,6 +159,18 @@ impl SymbolTable {
         Some(id)
     }
 
+    /// Bind an existing symbol under a name in another scope (a `use` import)
+    ///
+    /// Returns false if the name is already bound in that scope
+    pub fn import(&mut self, scope_id: ScopeId, name: String, id: SymbolId) -> bool {
+        let key = (scope_id, name);
+        if self.bindings.contains_key(&key) || !self.symbols.contains_key(&id) {
+            return false;
+        }
+        self.bindings.insert(key, id);
+        true
+    }
+
     /// Look up a symbol by name in a given scope
     ///
     /// This will search the current scope and parent scopes
@@ -323,6 +339,30 @@ mod tests {
         assert_eq!(table.lookup(1, "x"), Some(inner_id));
     }
 
+    #[test]
+    fn test_import_binding() {
+        let mut table = SymbolTable::new();
+
+        let symbol = Symbol::new(
+            0,
+            "helper".to_string(),
+            SymbolKind::Function,
+            Visibility::Public,
+            Span::dummy(),
+            1,
+        );
+        let id = table.insert(symbol).unwrap();
+
+        assert!(table.import(0, "h".to_string(), id));
+        assert_eq!(table.lookup(0, "h"), Some(id));
+        assert_eq!(table.get(id).unwrap().scope_id, 1);
+
+        // Name already bound in the importing scope
+        assert!(!table.import(0, "h".to_string(), id));
+        // Unknown symbol
+        assert!(!table.import(0, "other".to_string(), 99));
+    }
+
     #[test]
     fn test_mark_used() {
         let mut table = SymbolTable::new();
@@ -317,7 +317,7 @@ fn test_variable_shadowing() {
 
     // Outer variable: let x = 1;
     let outer_lit = Expr {
-        kind: ExprKind::Literal(Literal::Int(1)),
+        kind: ExprKind::Literal(Literal::Int(1, None)),
         span: Span::dummy(),
         hygiene: HygieneId::root(),
     };
@@ -1,5 +1,6 @@
 //! CPU-specific optimization profiles
 
+use aurora_mir::{Inlining, OptLevel, PassManager};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -84,6 +85,17 @@ impl CpuCharacteristics {
         }
     }
 
+    /// Get characteristics by CPU name (e.g., "skylake", "zen3")
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "skylake" => Some(Self::skylake()),
+            "zen3" => Some(Self::zen3()),
+            "apple-silicon" | "apple-m1" | "apple-m2" | "apple-m3" => Some(Self::apple_silicon()),
+            "generic" | "x86-64" => Some(Self::generic()),
+            _ => None,
+        }
+    }
+
     /// Get generic characteristics
     pub fn generic() -> Self {
         Self {
@@ -147,6 +159,16 @@ impl OptimizationStrategy {
         }
     }
 
+    /// Get the MIR inlining pass for this strategy
+    pub fn inliner(&self) -> Inlining {
+        Inlining::with_threshold(self.inline_threshold())
+    }
+
+    /// Get the MIR pass manager for an optimization level
+    pub fn pass_manager(&self, level: OptLevel) -> PassManager {
+        PassManager::for_level_with_inliner(level, self.inliner())
+    }
+
     /// Get loop unroll factor
     pub fn unroll_factor(&self) -> usize {
         if self.loop_unrolling {
@@ -206,6 +228,23 @@ mod tests {
         assert_eq!(strategy.inline_threshold(), 1000);
     }
 
+    #[test]
+    fn test_inliner_threshold() {
+        let skylake = OptimizationStrategy::for_cpu(CpuCharacteristics::skylake());
+        let generic = OptimizationStrategy::for_cpu(CpuCharacteristics::generic());
+
+        assert_eq!(skylake.inliner().threshold(), 1000);
+        assert_eq!(generic.inliner().threshold(), 500);
+        assert!(skylake.pass_manager(OptLevel::O2).stats().any(|s| s.name == "inlining"));
+    }
+
+    #[test]
+    fn test_cpu_from_name() {
+        assert_eq!(CpuCharacteristics::from_name("Zen3").unwrap().profile, CpuProfile::Zen3);
+        assert_eq!(CpuCharacteristics::from_name("x86-64").unwrap().profile, CpuProfile::Generic);
+        assert!(CpuCharacteristics::from_name("pentium").is_none());
+    }
+
     #[test]
     fn test_tuning_params() {
         let cpu = CpuCharacteristics::generic();
@@ -0,0 +1,556 @@
+//! Lossless concrete syntax tree
+//!
+//! The CST keeps every byte of the source, whitespace and comments
+//! included, so the text of its tokens concatenates back to the source.
+//! It is a red-green tree: green nodes hold their kind, their children and
+//! the length of their text, and are immutable and shared, while red nodes
+//! ([`SyntaxNode`], [`SyntaxToken`]) are built on demand over them and add
+//! absolute offsets and parent links.
+//!
+//! The tree is built from the lexer's tokens, lexed `with_trivia()`, and
+//! the spans of the AST parsed from them: each item, statement, block,
+//! expression, pattern and type becomes a node over its tokens. Trivia
+//! between two nodes belongs to their parent, except that the doc comments
+//! before an item belong to the item.
+
+use aurora_ast::{Arena, AstNode, ItemKind, Span};
+use aurora_lexer::{Token, TokenKind};
+use std::borrow::Borrow;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Kind of a CST node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum NodeKind {
+    /// The whole file
+    SourceFile,
+    /// A statement
+    Stmt,
+    /// A declaration, with its outer doc comments
+    Item,
+    /// An expression
+    Expr,
+    /// A block, such as a function body
+    Block,
+    /// A pattern
+    Pattern,
+    /// A type
+    Type,
+}
+
+/// A token in the green tree: its kind and text
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenToken {
+    kind: TokenKind,
+    text: String,
+}
+
+impl GreenToken {
+    /// Create a token
+    pub fn new(kind: TokenKind, text: String) -> Self {
+        Self { kind, text }
+    }
+
+    /// Kind of the token
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+
+    /// Source text of the token
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// A child of a green node
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GreenElement {
+    /// A nested node
+    Node(Arc<GreenNode>),
+    /// A token
+    Token(Arc<GreenToken>),
+}
+
+impl GreenElement {
+    /// Length of the element's text in bytes
+    pub fn text_len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.text_len(),
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+}
+
+/// A node in the green tree, independent of where it appears
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenNode {
+    kind: NodeKind,
+    text_len: usize,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    /// Create a node over its children
+    pub fn new(kind: NodeKind, children: Vec<GreenElement>) -> Self {
+        let text_len = children.iter().map(GreenElement::text_len).sum();
+        Self { kind, text_len, children }
+    }
+
+    /// Kind of the node
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    /// Length of the node's text in bytes
+    pub fn text_len(&self) -> usize {
+        self.text_len
+    }
+
+    /// Child nodes and tokens, in source order
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    fn write_text(&self, out: &mut String) {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => node.write_text(out),
+                GreenElement::Token(token) => out.push_str(&token.text),
+            }
+        }
+    }
+}
+
+impl fmt::Display for GreenNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut text = String::with_capacity(self.text_len);
+        self.write_text(&mut text);
+        f.write_str(&text)
+    }
+}
+
+#[derive(Debug)]
+struct NodeData {
+    green: Arc<GreenNode>,
+    /// Byte offset of the node's text in the source
+    offset: usize,
+    parent: Option<SyntaxNode>,
+}
+
+/// A node in the red tree: a green node at a position in the source
+#[derive(Debug, Clone)]
+pub struct SyntaxNode(Arc<NodeData>);
+
+impl SyntaxNode {
+    /// The root of the tree over a green node for a whole file
+    pub fn new_root(green: Arc<GreenNode>) -> Self {
+        Self(Arc::new(NodeData {
+            green,
+            offset: 0,
+            parent: None,
+        }))
+    }
+
+    /// Kind of the node
+    pub fn kind(&self) -> NodeKind {
+        self.0.green.kind
+    }
+
+    /// The green node under this one
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.0.green
+    }
+
+    /// Byte range of the node's text in the source
+    pub fn text_range(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.text_len
+    }
+
+    /// Source text of the node
+    pub fn text(&self) -> String {
+        self.0.green.to_string()
+    }
+
+    /// The node containing this one, unless it is the root
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.clone()
+    }
+
+    /// The nodes from this one up to the root
+    pub fn ancestors(&self) -> impl Iterator<Item = SyntaxNode> {
+        std::iter::successors(Some(self.clone()), SyntaxNode::parent)
+    }
+
+    /// Child nodes and tokens, in source order
+    pub fn children_with_tokens(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        self.0
+            .green
+            .children
+            .iter()
+            .map(|child| {
+                let element = match child {
+                    GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Arc::new(NodeData {
+                        green: green.clone(),
+                        offset,
+                        parent: Some(self.clone()),
+                    }))),
+                    GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                        green: green.clone(),
+                        offset,
+                        parent: self.clone(),
+                    }),
+                };
+                offset += child.text_len();
+                element
+            })
+            .collect()
+    }
+
+    /// Child nodes, in source order
+    pub fn children(&self) -> Vec<SyntaxNode> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect()
+    }
+
+    /// This node and the nodes inside it, in preorder
+    pub fn descendants(&self) -> Vec<SyntaxNode> {
+        let mut nodes = Vec::new();
+        let mut stack = vec![self.clone()];
+        while let Some(node) = stack.pop() {
+            stack.extend(node.children().into_iter().rev());
+            nodes.push(node);
+        }
+        nodes
+    }
+
+    /// Every token inside the node, in source order
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = Vec::new();
+        for element in self.children_with_tokens() {
+            match element {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    /// Outer doc comments (`///`) directly attached to the node, in order
+    pub fn doc_comments(&self) -> Vec<SyntaxToken> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Token(token) if token.kind() == TokenKind::DocCommentOuter => Some(token),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0.green, &other.0.green) && self.0.offset == other.0.offset
+    }
+}
+
+impl Eq for SyntaxNode {}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.green.fmt(f)
+    }
+}
+
+/// A token in the red tree: a green token at a position in the source
+#[derive(Debug, Clone)]
+pub struct SyntaxToken {
+    green: Arc<GreenToken>,
+    offset: usize,
+    parent: SyntaxNode,
+}
+
+impl SyntaxToken {
+    /// Kind of the token
+    pub fn kind(&self) -> TokenKind {
+        self.green.kind
+    }
+
+    /// Source text of the token
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    /// Byte range of the token in the source
+    pub fn text_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text.len()
+    }
+
+    /// The node containing the token
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+}
+
+/// A node or a token of the red tree
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+    /// A node
+    Node(SyntaxNode),
+    /// A token
+    Token(SyntaxToken),
+}
+
+/// A node to build over a range of tokens
+#[derive(Debug, Clone, Copy)]
+struct Extent {
+    kind: NodeKind,
+    /// First token
+    start: usize,
+    /// Token after the last
+    end: usize,
+}
+
+/// Build the CST of a file from its tokens, lexed with trivia, and the
+/// AST parsed from them
+///
+/// Nodes of other files in `arena`, and nodes whose spans overlap without
+/// nesting, are left out; every token is kept either way.
+pub fn build(source: &str, tokens: &[Token], arena: &Arena) -> SyntaxNode {
+    let tokens: Vec<&Token> = tokens.iter().filter(|token| token.kind != TokenKind::Eof).collect();
+    let children = build_children(source, &tokens, arena.nodes());
+    SyntaxNode::new_root(Arc::new(GreenNode::new(NodeKind::SourceFile, children)))
+}
+
+/// Build the CST of a file again after the top-level items over
+/// `tokens[range]` were parsed again into `nodes`, keeping the old root's
+/// children outside them
+///
+/// `range` runs from the first token of the first item parsed again to the
+/// first token of the item after the last, or to `Eof`. The tokens before
+/// it are unchanged and those from its end on are the old ones moved by
+/// `moved` bytes.
+pub fn rebuild<'a>(
+    old: &SyntaxNode,
+    source: &str,
+    tokens: &[Token],
+    range: Range<usize>,
+    moved: isize,
+    nodes: impl IntoIterator<Item = &'a AstNode>,
+) -> SyntaxNode {
+    // Keep the old children wholly before the items parsed again and the
+    // doc comments they take, and wholly after them
+    let start_offset = tokens[docs_start(tokens, range.start)].offset;
+    let end_offset = match tokens[range.end].kind {
+        TokenKind::Eof => usize::MAX,
+        _ => tokens[docs_start(tokens, range.end)].offset,
+    };
+    let old_children = old.green().children();
+    let (mut before, mut before_len) = (0, 0);
+    while old_children.get(before).is_some_and(|child| before_len + child.text_len() <= start_offset) {
+        before_len += old_children[before].text_len();
+        before += 1;
+    }
+    let (mut after, mut after_offset) = (old_children.len(), old.green().text_len().wrapping_add_signed(moved));
+    while after > before
+        && after_offset.checked_sub(old_children[after - 1].text_len()).is_some_and(|offset| offset >= end_offset)
+    {
+        after -= 1;
+        after_offset -= old_children[after].text_len();
+    }
+
+    // Build the children between them again
+    let start = tokens.partition_point(|token| token.offset < before_len);
+    let end = tokens.partition_point(|token| token.offset < after_offset && token.kind != TokenKind::Eof);
+    let changed: Vec<&Token> = tokens[start..end].iter().collect();
+    let mut children = old_children[..before].to_vec();
+    children.extend(build_children(source, &changed, nodes));
+    children.extend_from_slice(&old_children[after..]);
+    SyntaxNode::new_root(Arc::new(GreenNode::new(NodeKind::SourceFile, children)))
+}
+
+/// Children of the root over a run of its tokens, `Eof` left out, with a
+/// node for each AST node over them
+fn build_children<'a>(
+    source: &str,
+    tokens: &[&Token],
+    nodes: impl IntoIterator<Item = &'a AstNode>,
+) -> Vec<GreenElement> {
+    let file_id = tokens.first().map_or(0, |token| token.file_id);
+
+    let mut spans = Vec::new();
+    for node in nodes {
+        let kind = match node {
+            AstNode::Item(item) => {
+                if let ItemKind::Function(func) = &item.kind {
+                    spans.push((NodeKind::Block, func.body.span));
+                }
+                NodeKind::Item
+            }
+            AstNode::Stmt(_) => NodeKind::Stmt,
+            AstNode::Expr(_) => NodeKind::Expr,
+            AstNode::Block(_) => NodeKind::Block,
+            AstNode::Pattern(_) => NodeKind::Pattern,
+            AstNode::Type(_) => NodeKind::Type,
+        };
+        spans.push((kind, node.span()));
+    }
+
+    let mut extents: Vec<Extent> = spans
+        .into_iter()
+        .filter(|(_, span)| span.file_id == file_id && !span.is_empty())
+        .filter_map(|(kind, span)| extent(tokens, kind, span))
+        .collect();
+    // Outer nodes first; a statement encloses the item or expression it
+    // shares its tokens with
+    extents.sort_by_key(|extent| (extent.start, std::cmp::Reverse(extent.end), extent.kind));
+    extents.dedup_by_key(|extent| (extent.start, extent.end, extent.kind));
+
+    // Open nodes, innermost last, with their children so far
+    let mut open: Vec<(Extent, Vec<GreenElement>)> = vec![(
+        Extent {
+            kind: NodeKind::SourceFile,
+            start: 0,
+            end: tokens.len(),
+        },
+        Vec::new(),
+    )];
+    let mut extents = extents.into_iter().peekable();
+    for (i, token) in tokens.iter().enumerate() {
+        while open.last().is_some_and(|(extent, _)| extent.end <= i) {
+            close(&mut open);
+        }
+        while let Some(extent) = extents.next_if(|extent| extent.start == i) {
+            if extent.end <= open.last().map_or(0, |(parent, _)| parent.end) {
+                open.push((extent, Vec::new()));
+            }
+        }
+        let text = source.get(token.offset..token.end()).unwrap_or(&token.lexeme);
+        let green = GreenToken::new(token.kind, text.to_string());
+        open.last_mut().expect("source file node").1.push(GreenElement::Token(Arc::new(green)));
+    }
+    while open.len() > 1 {
+        close(&mut open);
+    }
+    open.pop().expect("source file node").1
+}
+
+/// Finish the innermost open node, adding it to its parent
+fn close(open: &mut Vec<(Extent, Vec<GreenElement>)>) {
+    let (extent, children) = open.pop().expect("open node");
+    let node = GreenElement::Node(Arc::new(GreenNode::new(extent.kind, children)));
+    open.last_mut().expect("source file node").1.push(node);
+}
+
+/// Tokens of a node: from its first to its last token that is not trivia,
+/// and for an item, back over the doc comments before it
+fn extent(tokens: &[&Token], kind: NodeKind, span: Span) -> Option<Extent> {
+    let (span_start, span_end) = (span.start as usize, span.end as usize);
+    let mut start = tokens.partition_point(|token| token.offset < span_start);
+    while start < tokens.len() && tokens[start].kind.is_trivia() {
+        start += 1;
+    }
+    let mut end = tokens.partition_point(|token| token.end() <= span_end);
+    while end > start && tokens[end - 1].kind.is_trivia() {
+        end -= 1;
+    }
+    if start >= end {
+        return None;
+    }
+
+    if kind == NodeKind::Item {
+        start = docs_start(tokens, start);
+    }
+    Some(Extent { kind, start, end })
+}
+
+/// First of the doc comments before the token at `start`, and the
+/// whitespace between them, or `start` if there are none
+fn docs_start<T: Borrow<Token>>(tokens: &[T], mut start: usize) -> usize {
+    let mut first = start;
+    while first > 0 && matches!(tokens[first - 1].borrow().kind, TokenKind::Whitespace | TokenKind::Newline) {
+        first -= 1;
+    }
+    while first > 0 && tokens[first - 1].borrow().kind == TokenKind::DocCommentOuter {
+        start = first - 1;
+        first = start;
+        while first > 0 && matches!(tokens[first - 1].borrow().kind, TokenKind::Whitespace | TokenKind::Newline) {
+            first -= 1;
+        }
+    }
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn parse(source: &str) -> SyntaxNode {
+        Parser::parse_lossless(source, "test.ax".to_string()).unwrap().1
+    }
+
+    #[test]
+    fn test_cst_is_lossless() {
+        let sources = [
+            "",
+            "  \n",
+            "//! Module docs\n\n/// Adds one\nfn inc(x: i64) -> i64 {\n    x + 1 // plus one\n}\n",
+            "fn main() {\r\n\tlet s = \"a{1 + 2}b\"; /* note */ let t = 'c';\r\n}",
+            "struct P { x: i64 }\nfn broken( { $ }\n",
+        ];
+        for source in sources {
+            let root = parse(source);
+            assert_eq!(root.kind(), NodeKind::SourceFile);
+            assert_eq!(root.text(), source);
+            assert_eq!(root.text_range(), 0..source.len());
+            let text: String = root.tokens().iter().map(SyntaxToken::text).collect();
+            assert_eq!(text, source);
+        }
+    }
+
+    #[test]
+    fn test_cst_structure_and_ranges() {
+        let source = "/// Adds one\n/// to x\nfn inc(x: i64) -> i64 {\n    let y = x + 1;\n    y\n}\n";
+        let root = parse(source);
+
+        let items = root.children();
+        assert_eq!(items.len(), 1);
+        let item = &items[0];
+        assert_eq!(item.kind(), NodeKind::Item);
+        assert_eq!(item.text(), &source[..source.len() - 1]);
+        let docs: Vec<String> = item.doc_comments().iter().map(|token| token.text().to_string()).collect();
+        assert_eq!(docs, ["/// Adds one", "/// to x"]);
+
+        // Every node's range holds its text, and its children's ranges
+        for node in root.descendants() {
+            assert_eq!(&source[node.text_range()], node.text());
+            for child in node.children() {
+                let (range, parent) = (child.text_range(), node.text_range());
+                assert!(parent.start <= range.start && range.end <= parent.end);
+                assert_eq!(child.parent(), Some(node.clone()));
+            }
+        }
+
+        let binary = root
+            .descendants()
+            .into_iter()
+            .find(|node| node.kind() == NodeKind::Expr && node.text() == "x + 1")
+            .unwrap();
+        let kinds: Vec<NodeKind> = binary.ancestors().map(|node| node.kind()).collect();
+        assert_eq!(
+            kinds,
+            [NodeKind::Expr, NodeKind::Stmt, NodeKind::Block, NodeKind::Item, NodeKind::SourceFile]
+        );
+        assert!(root
+            .descendants()
+            .iter()
+            .any(|node| node.kind() == NodeKind::Type && node.text() == "i64"));
+    }
+}
@@ -1,10 +1,12 @@
 //! Declaration parsing (functions, types, traits, impls, etc.)
 
 use aurora_ast::decl::{
-    ConstDecl, FunctionDecl, ImplDecl, Item, ItemKind, ModuleDecl, Param, TraitDecl, TypeDecl,
-    UseDecl, UseTree, GenericParam, WhereClause,
+    ConstDecl, EnumDecl, EnumVariant, FieldDecl, FunctionDecl, ImplDecl, Item, ItemKind,
+    ModuleDecl, Param, StructDecl, TraitDecl, TypeDecl, UseDecl, UseTree, GenericParam,
+    VariantFields, WhereClause,
 };
-use aurora_ast::{Block, Stmt, StmtKind};
+use aurora_ast::expr::Path;
+use aurora_ast::{Block, Stmt, StmtKind, Type, TypeKind};
 use aurora_lexer::TokenKind;
 use crate::error::{ParseError, ParseResult};
 use crate::parser::Parser;
@@ -31,18 +33,18 @@ impl Parser {
                 eprintln!("[DEBUG] Parsing function...");
                 self.parse_function(is_pub)?
             }
+            TokenKind::Struct => self.parse_struct(is_pub)?,
+            TokenKind::Enum => self.parse_enum(is_pub)?,
             TokenKind::Type => self.parse_type_decl(is_pub)?,
             TokenKind::Trait => self.parse_trait(is_pub)?,
             TokenKind::Impl => self.parse_impl()?,
             TokenKind::Const => self.parse_const(is_pub)?,
             TokenKind::Mod => self.parse_module(is_pub)?,
             TokenKind::Use => self.parse_use(is_pub)?,
-            // Note: struct and enum are parsed via 'type' for now
-            // They'll be added when we implement full struct/enum syntax
             _ => {
                 eprintln!("[DEBUG] Unexpected token in parse_item: {:?}", self.peek());
                 return Err(ParseError::Expected {
-                    expected: "item declaration (fn/fun, type, trait, impl, const, mod, use)".to_string(),
+                    expected: "item declaration (fn/fun, struct, enum, type, trait, impl, const, mod, use)".to_string(),
                     found: format!("{:?}", self.peek()),
                     span: self.token_to_span(self.current()),
                     message: "Expected a top-level item".to_string(),
@@ -178,11 +180,17 @@ impl Parser {
             // Parameter pattern (for now, just identifier)
             let pattern = self.parse_pattern()?;
 
-            // ':'
-            self.expect(TokenKind::Colon, "Expected ':' after parameter name")?;
-
-            // Parameter type
-            let ty = self.parse_type()?;
+            // Parameter type (may be omitted in simplified syntax and inferred)
+            let ty = if self.check(&TokenKind::Colon) {
+                self.advance();
+                self.parse_type()?
+            } else {
+                let span = self.span_from(start);
+                self.arena.alloc_type(Type {
+                    kind: TypeKind::Infer,
+                    span,
+                })
+            };
 
             let span = self.span_from(start);
             params.push(Param {
@@ -248,6 +256,162 @@ impl Parser {
         Ok(WhereClause { predicates, span })
     }
 
+    /// Parse struct declaration
+    fn parse_struct(&mut self, is_pub: bool) -> ParseResult<ItemKind> {
+        let start = self.token_to_span(self.current());
+
+        self.expect(TokenKind::Struct, "Expected 'struct'")?;
+
+        let name_token = self.expect(TokenKind::Ident, "Expected struct name")?;
+        let name = name_token.lexeme.clone();
+
+        // Generic parameters (optional)
+        let generics = if self.check(&TokenKind::Lt) {
+            self.parse_generic_params()?
+        } else {
+            Vec::new()
+        };
+
+        // Where clause (optional)
+        let where_clause = if self.check(&TokenKind::Where) {
+            Some(self.parse_where_clause()?)
+        } else {
+            None
+        };
+
+        let fields = self.parse_variant_fields()?;
+
+        // Tuple and unit structs end with ';'
+        if !matches!(fields, VariantFields::Named(_)) {
+            self.expect(TokenKind::Semicolon, "Expected ';' after struct declaration")?;
+        }
+
+        let span = self.span_from(start);
+
+        Ok(ItemKind::Struct(StructDecl {
+            name,
+            generics,
+            where_clause,
+            fields,
+            is_pub,
+            span,
+        }))
+    }
+
+    /// Parse enum declaration
+    fn parse_enum(&mut self, is_pub: bool) -> ParseResult<ItemKind> {
+        let start = self.token_to_span(self.current());
+
+        self.expect(TokenKind::Enum, "Expected 'enum'")?;
+
+        let name_token = self.expect(TokenKind::Ident, "Expected enum name")?;
+        let name = name_token.lexeme.clone();
+
+        // Generic parameters (optional)
+        let generics = if self.check(&TokenKind::Lt) {
+            self.parse_generic_params()?
+        } else {
+            Vec::new()
+        };
+
+        // Where clause (optional)
+        let where_clause = if self.check(&TokenKind::Where) {
+            Some(self.parse_where_clause()?)
+        } else {
+            None
+        };
+
+        self.expect(TokenKind::LBrace, "Expected '{' after enum name")?;
+
+        let mut variants = Vec::new();
+        while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
+            let variant_start = self.token_to_span(self.current());
+            let variant_token = self.expect(TokenKind::Ident, "Expected variant name")?;
+            let variant_name = variant_token.lexeme.clone();
+
+            let fields = self.parse_variant_fields()?;
+
+            let variant_span = self.span_from(variant_start);
+            variants.push(EnumVariant {
+                name: variant_name,
+                fields,
+                span: variant_span,
+            });
+
+            if !self.check(&TokenKind::Comma) {
+                break;
+            }
+            self.advance();
+        }
+
+        self.expect(TokenKind::RBrace, "Expected '}' to close enum")?;
+
+        let span = self.span_from(start);
+
+        Ok(ItemKind::Enum(EnumDecl {
+            name,
+            generics,
+            where_clause,
+            variants,
+            is_pub,
+            span,
+        }))
+    }
+
+    /// Parse the fields of a struct or enum variant
+    ///
+    /// `{ name: Type, .. }` gives named fields, `(Type, ..)` positional
+    /// fields, and anything else a unit variant.
+    fn parse_variant_fields(&mut self) -> ParseResult<VariantFields> {
+        let (close, named) = if self.check(&TokenKind::LBrace) {
+            (TokenKind::RBrace, true)
+        } else if self.check(&TokenKind::LParen) {
+            (TokenKind::RParen, false)
+        } else {
+            return Ok(VariantFields::Unit);
+        };
+        self.advance();
+
+        let mut fields = Vec::new();
+        while !self.check(&close) && !self.is_at_end() {
+            let start = self.token_to_span(self.current());
+
+            let is_pub = if self.check(&TokenKind::Pub) {
+                self.advance();
+                true
+            } else {
+                false
+            };
+
+            let name = if named {
+                let name_token = self.expect(TokenKind::Ident, "Expected field name")?;
+                let name = name_token.lexeme.clone();
+                self.expect(TokenKind::Colon, "Expected ':' after field name")?;
+                name
+            } else {
+                fields.len().to_string()
+            };
+
+            let ty = self.parse_type()?;
+
+            let span = self.span_from(start);
+            fields.push(FieldDecl { name, ty, is_pub, span });
+
+            if !self.check(&TokenKind::Comma) {
+                break;
+            }
+            self.advance();
+        }
+
+        if named {
+            self.expect(close, "Expected '}' after struct fields")?;
+            Ok(VariantFields::Named(fields))
+        } else {
+            self.expect(close, "Expected ')' after tuple fields")?;
+            Ok(VariantFields::Tuple(fields))
+        }
+    }
+
     /// Parse type declaration
     fn parse_type_decl(&mut self, is_pub: bool) -> ParseResult<ItemKind> {
         let start = self.token_to_span(self.current());
@@ -455,12 +619,64 @@ impl Parser {
         Ok(ItemKind::Use(UseDecl { tree, is_pub, span }))
     }
 
-    /// Parse use tree (import path)
+    /// Parse use tree (`a::b`, `a::b as c`, `a::*` or `a::{b, c}`)
     fn parse_use_tree(&mut self) -> ParseResult<UseTree> {
-        // For now, just parse simple path
-        let path = self.parse_path()?;
+        let mut segments = Vec::new();
 
-        Ok(UseTree::Path { path, alias: None })
+        loop {
+            if self.check(&TokenKind::Star) {
+                self.advance();
+                let path = Path { segments, generics: vec![] };
+                return Ok(UseTree::Glob { path });
+            }
+
+            if self.check(&TokenKind::LBrace) {
+                self.advance();
+                let mut trees = Vec::new();
+                while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
+                    trees.push(self.parse_use_tree()?);
+                    if !self.check(&TokenKind::RBrace) {
+                        self.expect(TokenKind::Comma, "Expected ',' or '}' in use list")?;
+                    }
+                }
+                self.expect(TokenKind::RBrace, "Expected '}' to close use list")?;
+                let path = Path { segments, generics: vec![] };
+                return Ok(UseTree::Nested { path, trees });
+            }
+
+            segments.push(self.parse_use_segment()?);
+
+            if !self.check(&TokenKind::ColonColon) {
+                break;
+            }
+            self.advance();
+        }
+
+        let alias = if self.check(&TokenKind::As) {
+            self.advance();
+            let alias_token = self.expect(TokenKind::Ident, "Expected name after 'as'")?;
+            Some(alias_token.lexeme.clone())
+        } else {
+            None
+        };
+
+        let path = Path { segments, generics: vec![] };
+        Ok(UseTree::Path { path, alias })
+    }
+
+    /// Parse one segment of a use path (an identifier, `self`, `super` or `crate`)
+    fn parse_use_segment(&mut self) -> ParseResult<String> {
+        match self.peek() {
+            TokenKind::Ident | TokenKind::SelfLower | TokenKind::Super | TokenKind::Crate => {
+                Ok(self.advance().lexeme.clone())
+            }
+            _ => Err(ParseError::Expected {
+                expected: "path segment".to_string(),
+                found: format!("{:?}", self.peek()),
+                span: self.token_to_span(self.current()),
+                message: "Expected path segment in use declaration".to_string(),
+            }),
+        }
     }
 
     /// Parse trait items
@@ -555,6 +771,9 @@ impl Parser {
 
         self.expect(TokenKind::LBrace, "Expected '{'")?;
 
+        // Struct literals are allowed again inside braces
+        let allow_struct_literal = std::mem::replace(&mut self.allow_struct_literal, true);
+
         let mut stmts = Vec::new();
         let mut trailing_expr = None;
         let mut iterations = 0;
@@ -616,6 +835,7 @@ impl Parser {
         }
         eprintln!("[DEBUG] parse_block() - Exiting normally");
 
+        self.allow_struct_literal = allow_struct_literal;
         self.expect(TokenKind::RBrace, "Expected '}'")?;
 
         let span = self.span_from(start);
@@ -656,6 +876,40 @@ mod tests {
         let (_program, _arena) = parser.parse_program().unwrap();
     }
 
+    #[test]
+    fn test_parse_struct_forms() {
+        let source = "struct Point { x: f64, pub y: f64 } struct Meters(f64); struct Marker;";
+        let parser = Parser::new(source, "test.ax".to_string()).unwrap();
+        let (program, arena) = parser.parse_program().unwrap();
+        let fields: Vec<_> = program
+            .items
+            .iter()
+            .map(|&id| match &arena.get_item(id).unwrap().kind {
+                ItemKind::Struct(decl) => decl.fields.clone(),
+                other => panic!("expected struct, got {:?}", other),
+            })
+            .collect();
+        assert!(matches!(&fields[0], VariantFields::Named(f) if f.len() == 2 && f[1].is_pub));
+        assert!(matches!(&fields[1], VariantFields::Tuple(f) if f[0].name == "0"));
+        assert_eq!(fields[2], VariantFields::Unit);
+    }
+
+    #[test]
+    fn test_parse_enum() {
+        let source = "enum Shape<T> { Empty, Circle(T), Rect { w: T, h: T }, }";
+        let parser = Parser::new(source, "test.ax".to_string()).unwrap();
+        let (program, arena) = parser.parse_program().unwrap();
+        let ItemKind::Enum(decl) = &arena.get_item(program.items[0]).unwrap().kind else {
+            panic!("expected enum");
+        };
+        assert_eq!(decl.generics.len(), 1);
+        let names: Vec<_> = decl.variants.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["Empty", "Circle", "Rect"]);
+        assert_eq!(decl.variants[0].fields, VariantFields::Unit);
+        assert!(matches!(decl.variants[1].fields, VariantFields::Tuple(_)));
+        assert!(matches!(decl.variants[2].fields, VariantFields::Named(_)));
+    }
+
     #[test]
     fn test_parse_module() {
         let source = "mod test;";
@@ -663,4 +917,34 @@ mod tests {
         let (program, _arena) = parser.parse_program().unwrap();
         assert_eq!(program.items.len(), 1);
     }
+
+    #[test]
+    fn test_parse_use_trees() {
+        let source = "use crate::shapes::Circle as C; use super::util::*; use a::{b, c::d};";
+        let parser = Parser::new(source, "test.ax".to_string()).unwrap();
+        let (program, arena) = parser.parse_program().unwrap();
+        let trees: Vec<_> = program
+            .items
+            .iter()
+            .map(|&id| match &arena.get_item(id).unwrap().kind {
+                ItemKind::Use(decl) => decl.tree.clone(),
+                other => panic!("expected use, got {:?}", other),
+            })
+            .collect();
+
+        let UseTree::Path { path, alias } = &trees[0] else {
+            panic!("expected path import");
+        };
+        assert_eq!(path.segments, ["crate", "shapes", "Circle"]);
+        assert_eq!(alias.as_deref(), Some("C"));
+
+        assert!(matches!(&trees[1], UseTree::Glob { path } if path.segments == ["super", "util"]));
+
+        let UseTree::Nested { path, trees: nested } = &trees[2] else {
+            panic!("expected nested import");
+        };
+        assert_eq!(path.segments, ["a"]);
+        assert_eq!(nested.len(), 2);
+        assert!(matches!(&nested[1], UseTree::Path { path, .. } if path.segments == ["c", "d"]));
+    }
 }
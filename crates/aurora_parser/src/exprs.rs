@@ -4,8 +4,9 @@
 //! The parser handles all Aurora operators with correct precedence and associativity.
 
 use aurora_ast::expr::{
-    BinaryOp, Expr, ExprKind, FieldInit, GenericArg, Literal, MatchArm, Path, UnaryOp,
+    BinaryOp, Expr, ExprKind, FieldInit, GenericArg, IntSuffix, InterpolationPart, Literal, MatchArm, Path, UnaryOp,
 };
+use aurora_ast::ty::{FloatType, IntType, UintType};
 use aurora_ast::Span;
 use aurora_lexer::TokenKind;
 use crate::error::{ParseError, ParseResult};
@@ -88,23 +89,15 @@ impl Parser {
         
         let kind = match self.peek() {
             // Literals
-            TokenKind::IntLiteral => {
-                let n = self.current().lexeme.parse::<i64>().unwrap_or(0);
-                self.advance();
-                ExprKind::Literal(Literal::Int(n))
-            }
-            TokenKind::FloatLiteral => {
-                let f = self.current().lexeme.parse::<f64>().unwrap_or(0.0);
-                self.advance();
-                ExprKind::Literal(Literal::Float(f))
-            }
+            TokenKind::IntLiteral | TokenKind::FloatLiteral => ExprKind::Literal(self.parse_number_literal()?),
             TokenKind::StringLiteral => {
-                let s = self.current().lexeme.clone();
+                let s = string_literal_value(&self.current().lexeme);
                 self.advance();
                 ExprKind::Literal(Literal::String(s))
             }
+            TokenKind::InterpolatedStringStart => self.parse_interpolated_string()?,
             TokenKind::CharLiteral => {
-                let c = self.current().lexeme.chars().next().unwrap_or('?');
+                let c = char_literal_value(&self.current().lexeme);
                 self.advance();
                 ExprKind::Literal(Literal::Char(c))
             }
@@ -117,8 +110,20 @@ impl Parser {
                 ExprKind::Literal(Literal::Bool(false))
             }
             
-            // Identifiers and paths
-            TokenKind::Ident => {
+            // `self` receiver
+            TokenKind::SelfLower => {
+                self.advance();
+                ExprKind::Ident("self".to_string())
+            }
+
+            // Identifiers and paths (`Self` and the built-in constructors
+            // are keywords but behave like names here)
+            TokenKind::Ident
+            | TokenKind::SelfUpper
+            | TokenKind::Some
+            | TokenKind::None
+            | TokenKind::Ok
+            | TokenKind::Err => {
                 let name = self.current().lexeme.clone();
                 self.advance();
                 
@@ -128,12 +133,12 @@ impl Parser {
                     let path = self.parse_path_from_segment(name)?;
                     
                     // Check for struct literal
-                    if self.check(&TokenKind::LBrace) {
+                    if self.allow_struct_literal && self.check(&TokenKind::LBrace) {
                         return self.parse_struct_literal(path, start);
                     }
                     
                     ExprKind::Path(path)
-                } else if self.check(&TokenKind::LBrace) {
+                } else if self.allow_struct_literal && self.check(&TokenKind::LBrace) {
                     // Struct literal with simple name
                     let path = Path {
                         segments: vec![name],
@@ -264,7 +269,7 @@ impl Parser {
             // While loops
             TokenKind::While => {
                 self.advance();
-                let condition = self.parse_expr()?;
+                let condition = self.parse_condition_expr()?;
                 let body_block = self.parse_block()?;
                 let body = self.arena.alloc(aurora_ast::nodes::AstNode::Block(body_block));
                 ExprKind::While { condition, body }
@@ -275,7 +280,7 @@ impl Parser {
                 self.advance();
                 let pattern = self.parse_pattern()?;
                 self.expect(TokenKind::In, "Expected 'in' after for loop pattern")?;
-                let iterator = self.parse_expr()?;
+                let iterator = self.parse_condition_expr()?;
                 let body_block = self.parse_block()?;
                 let body = self.arena.alloc(aurora_ast::nodes::AstNode::Block(body_block));
                 ExprKind::For { pattern, iterator, body }
@@ -339,6 +344,7 @@ impl Parser {
             | TokenKind::EqEq | TokenKind::NotEq
             | TokenKind::Lt | TokenKind::LtEq | TokenKind::Gt | TokenKind::GtEq
             | TokenKind::AndAnd | TokenKind::OrOr
+            | TokenKind::AndKeyword | TokenKind::OrKeyword
             | TokenKind::And | TokenKind::Or | TokenKind::Caret
             | TokenKind::LtLt | TokenKind::GtGt => {
                 let op = self.token_to_binary_op()?;
@@ -361,14 +367,9 @@ impl Parser {
             }
             
             // Range operators
-            TokenKind::DotDot => {
+            TokenKind::DotDot | TokenKind::DotDotEq => {
+                let inclusive = self.check(&TokenKind::DotDotEq);
                 self.advance();
-                let inclusive = if self.check(&TokenKind::Eq) {
-                    self.advance();
-                    true
-                } else {
-                    false
-                };
                 
                 let end = if self.check(&TokenKind::Comma) || self.check(&TokenKind::RBracket)
                     || self.check(&TokenKind::RParen) || self.check(&TokenKind::Semicolon) {
@@ -388,7 +389,23 @@ impl Parser {
             // Field access
             TokenKind::Dot => {
                 self.advance();
-                
+
+                // Tuple fields: `t.0`, and `t.0.1` which lexes as `t` `.` `0.1`
+                if self.check(&TokenKind::IntLiteral) {
+                    let name = self.advance().lexeme.clone();
+                    let kind = ExprKind::Field { object: left, field: name };
+                    return Ok(self.alloc_expr(kind, start));
+                }
+                if self.check(&TokenKind::FloatLiteral) {
+                    let lexeme = self.advance().lexeme.clone();
+                    let mut object = left;
+                    for field in lexeme.split('.') {
+                        let kind = ExprKind::Field { object, field: field.to_string() };
+                        object = self.alloc_expr(kind, start);
+                    }
+                    return Ok(object);
+                }
+
                 // Check for method call vs field access
                 let field_token = self.expect(TokenKind::Ident, "Expected field or method name")?;
                 let name = field_token.lexeme.clone();
@@ -449,7 +466,7 @@ impl Parser {
             | TokenKind::LtLtEq | TokenKind::GtGtEq => Precedence::Assignment,
             
             TokenKind::Question => Precedence::Propagation,
-            TokenKind::DotDot => Precedence::Range,
+            TokenKind::DotDot | TokenKind::DotDotEq => Precedence::Range,
             TokenKind::OrOr | TokenKind::OrKeyword => Precedence::LogicalOr,
             TokenKind::AndAnd | TokenKind::AndKeyword => Precedence::LogicalAnd,
             
@@ -512,17 +529,34 @@ impl Parser {
         }
     }
     
+    /// Parse an expression followed by a block (if/while/for/match head)
+    ///
+    /// Struct literals are not allowed here, so `while i < n {` treats the
+    /// brace as the start of the loop body.
+    fn parse_condition_expr(&mut self) -> ParseResult<u32> {
+        let saved = std::mem::replace(&mut self.allow_struct_literal, false);
+        let result = self.parse_expr();
+        self.allow_struct_literal = saved;
+        result
+    }
+
     /// Parse if expression
     fn parse_if_expr(&mut self, start: Span) -> ParseResult<u32> {
         self.expect(TokenKind::If, "Expected 'if'")?;
-        let condition = self.parse_expr()?;
+        self.parse_if_tail(start)
+    }
+
+    /// Parse the condition and branches of an if (after `if` or `elif`)
+    fn parse_if_tail(&mut self, start: Span) -> ParseResult<u32> {
+        let condition = self.parse_condition_expr()?;
         let then_block_node = self.parse_block()?;
         let then_block = self.arena.alloc(aurora_ast::nodes::AstNode::Block(then_block_node));
         
         let else_block = if self.check(&TokenKind::Elif) {
             // elif (simplified syntax)
+            let elif_start = self.token_to_span(self.current());
             self.advance();
-            let else_if_expr_id = self.parse_if_expr(self.token_to_span(self.current()))?;
+            let else_if_expr_id = self.parse_if_tail(elif_start)?;
             // Wrap in a block
             let else_if_block = aurora_ast::Block {
                 stmts: vec![],
@@ -559,7 +593,7 @@ impl Parser {
     /// Parse match expression
     fn parse_match_expr(&mut self, start: Span) -> ParseResult<u32> {
         self.expect(TokenKind::Match, "Expected 'match'")?;
-        let scrutinee = self.parse_expr()?;
+        let scrutinee = self.parse_condition_expr()?;
         
         self.expect(TokenKind::LBrace, "Expected '{' after match scrutinee")?;
         
@@ -567,7 +601,7 @@ impl Parser {
         
         while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
             let arm_start = self.token_to_span(self.current());
-            let pattern = self.parse_pattern()?;
+            let pattern = self.parse_or_pattern()?;
             
             let guard = if self.check(&TokenKind::If) {
                 self.advance();
@@ -676,13 +710,36 @@ impl Parser {
         Ok(Path { segments, generics })
     }
     
-    /// Parse a simple path
-    pub(crate) fn parse_path(&mut self) -> ParseResult<Path> {
-        let first_token = self.expect(TokenKind::Ident, "Expected path")?;
-        let first = first_token.lexeme.clone();
-        self.parse_path_from_segment(first)
+    /// Parse an interpolated string literal: its text tokens, with the
+    /// expression of a hole after each but the last
+    fn parse_interpolated_string(&mut self) -> ParseResult<ExprKind> {
+        let mut parts = Vec::new();
+        loop {
+            let text = string_literal_value(&self.current().lexeme);
+            let is_end = *self.peek() == TokenKind::InterpolatedStringEnd;
+            self.advance();
+            if !text.is_empty() {
+                parts.push(InterpolationPart::Text(text));
+            }
+            if is_end {
+                return Ok(ExprKind::Interpolated(parts));
+            }
+
+            parts.push(InterpolationPart::Expr(self.parse_expr()?));
+            if !matches!(
+                self.peek(),
+                TokenKind::InterpolatedStringMiddle | TokenKind::InterpolatedStringEnd
+            ) {
+                return Err(ParseError::Expected {
+                    expected: "'}'".to_string(),
+                    found: format!("{:?}", self.peek()),
+                    span: self.token_to_span(self.current()),
+                    message: "Expected '}' to close the interpolated expression".to_string(),
+                });
+            }
+        }
     }
-    
+
     /// Helper to allocate an expression
     fn alloc_expr(&mut self, kind: ExprKind, start: Span) -> u32 {
         let span = self.span_from(start);
@@ -693,6 +750,106 @@ impl Parser {
         };
         self.arena.alloc_expr(expr)
     }
+
+    /// Parse an integer or float literal token into its value and suffix
+    pub(crate) fn parse_number_literal(&mut self) -> ParseResult<Literal> {
+        let token = self.current();
+        let literal = if token.kind == TokenKind::FloatLiteral {
+            let (value, suffix) = float_literal_value(&token.lexeme);
+            Literal::Float(value, suffix)
+        } else {
+            let Some((value, suffix)) = int_literal_value(&token.lexeme) else {
+                return Err(ParseError::InvalidSyntax {
+                    span: self.token_to_span(token),
+                    message: format!("integer literal '{}' is too large", token.lexeme),
+                });
+            };
+            Literal::Int(value, suffix)
+        };
+        self.advance();
+        Ok(literal)
+    }
+}
+
+/// Value and type suffix of an integer literal lexeme such as `42`,
+/// `0xFFu8` or `1_000_i64`, or `None` if the value needs over 128 bits
+pub(crate) fn int_literal_value(lexeme: &str) -> Option<(i128, Option<IntSuffix>)> {
+    let (digits, suffix) = match lexeme.find(['i', 'u']) {
+        Some(at) => (&lexeme[..at], int_suffix(&lexeme[at..])),
+        None => (lexeme, None),
+    };
+    let (digits, radix) = match digits.get(..2) {
+        Some("0x" | "0X") => (&digits[2..], 16),
+        Some("0b" | "0B") => (&digits[2..], 2),
+        Some("0o" | "0O") => (&digits[2..], 8),
+        _ => (digits, 10),
+    };
+    let digits = digits.replace('_', "");
+    i128::from_str_radix(&digits, radix).ok().map(|value| (value, suffix))
+}
+
+/// Value and type suffix of a float literal lexeme such as `1.5` or `2f32`
+pub(crate) fn float_literal_value(lexeme: &str) -> (f64, Option<FloatType>) {
+    let (digits, suffix) = if let Some(digits) = lexeme.strip_suffix("f32") {
+        (digits, Some(FloatType::F32))
+    } else if let Some(digits) = lexeme.strip_suffix("f64") {
+        (digits, Some(FloatType::F64))
+    } else {
+        (lexeme, None)
+    };
+    (digits.replace('_', "").parse().unwrap_or(0.0), suffix)
+}
+
+/// Integer type named by a literal suffix; the lexer rejects any others
+fn int_suffix(suffix: &str) -> Option<IntSuffix> {
+    Some(match suffix {
+        "i8" => IntSuffix::Int(IntType::I8),
+        "i16" => IntSuffix::Int(IntType::I16),
+        "i32" => IntSuffix::Int(IntType::I32),
+        "i64" => IntSuffix::Int(IntType::I64),
+        "u8" => IntSuffix::Uint(UintType::U8),
+        "u16" => IntSuffix::Uint(UintType::U16),
+        "u32" => IntSuffix::Uint(UintType::U32),
+        "u64" => IntSuffix::Uint(UintType::U64),
+        _ => return None,
+    })
+}
+
+/// Character denoted by a char literal lexeme such as `'a'` or `'\n'`
+pub(crate) fn char_literal_value(lexeme: &str) -> char {
+    let inner = lexeme.strip_prefix('\'').unwrap_or(lexeme);
+    let mut chars = inner.chars();
+    match chars.next() {
+        Some('\\') => chars.next().map_or('\\', escaped_char),
+        Some(c) => c,
+        None => '?',
+    }
+}
+
+/// Text denoted by the lexeme of a string literal or of a part of an
+/// interpolated one, with its escapes decoded
+pub(crate) fn string_literal_value(lexeme: &str) -> String {
+    let mut text = String::with_capacity(lexeme.len());
+    let mut chars = lexeme.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.push(chars.next().map_or('\\', escaped_char)),
+            c => text.push(c),
+        }
+    }
+    text
+}
+
+/// Character denoted by the escape sequence `\c`; the lexer reports
+/// unknown escapes, which stand for the character itself
+fn escaped_char(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        c => c,
+    }
 }
 
 #[cfg(test)]
@@ -707,6 +864,70 @@ mod tests {
         let (_program, _arena) = parser.parse_program().unwrap();
     }
 
+    #[test]
+    fn test_char_literal_value() {
+        assert_eq!(char_literal_value("'Q'"), 'Q');
+        assert_eq!(char_literal_value("'\\n'"), '\n');
+        assert_eq!(char_literal_value("'\\''"), '\'');
+    }
+
+    #[test]
+    fn test_string_literal_value() {
+        assert_eq!(string_literal_value("a\\tb\\n"), "a\tb\n");
+        assert_eq!(string_literal_value("\\\"q\\\" \\\\ \\{x\\}"), "\"q\" \\ {x}");
+    }
+
+    #[test]
+    fn test_number_literal_values() {
+        assert_eq!(int_literal_value("42"), Some((42, None)));
+        assert_eq!(int_literal_value("1_000"), Some((1000, None)));
+        assert_eq!(int_literal_value("0xFF"), Some((255, None)));
+        assert_eq!(int_literal_value("0b1010"), Some((10, None)));
+        assert_eq!(int_literal_value("0o755u16"), Some((0o755, Some(IntSuffix::Uint(UintType::U16)))));
+        assert_eq!(int_literal_value("255_i64"), Some((255, Some(IntSuffix::Int(IntType::I64)))));
+        assert_eq!(int_literal_value("18446744073709551615"), Some((u64::MAX.into(), None)));
+        assert_eq!(int_literal_value("340282366920938463463374607431768211456"), None);
+
+        assert_eq!(float_literal_value("1.5"), (1.5, None));
+        assert_eq!(float_literal_value("2.5e1_0"), (2.5e10, None));
+        assert_eq!(float_literal_value("1f32"), (1.0, Some(FloatType::F32)));
+    }
+
+    #[test]
+    fn test_parse_oversized_int_literal() {
+        let source = "340282366920938463463374607431768211456";
+        let mut parser = Parser::new(source, "test.ax".to_string()).unwrap();
+        assert!(matches!(parser.parse_expr(), Err(ParseError::InvalidSyntax { .. })));
+    }
+
+    #[test]
+    fn test_parse_interpolated_string() {
+        let source = "fn test(x: i32) { f\"x = {x + 1}, { {x} }!\"; }";
+        let parser = Parser::new(source, "test.ax".to_string()).unwrap();
+        let (_program, arena) = parser.parse_program().unwrap();
+
+        let parts = (0..arena.len() as u32)
+            .find_map(|id| match arena.get_expr(id).map(|expr| &expr.kind) {
+                Some(ExprKind::Interpolated(parts)) if parts.len() > 2 => Some(parts.clone()),
+                _ => None,
+            })
+            .expect("interpolated string");
+        assert_eq!(parts.len(), 5, "{:?}", parts);
+        assert_eq!(parts[0], InterpolationPart::Text("x = ".to_string()));
+        assert_eq!(parts[2], InterpolationPart::Text(", ".to_string()));
+        assert_eq!(parts[4], InterpolationPart::Text("!".to_string()));
+        let InterpolationPart::Expr(sum) = parts[1] else {
+            panic!("expected a hole: {:?}", parts);
+        };
+        assert!(matches!(arena.get_expr(sum).map(|expr| &expr.kind), Some(ExprKind::Binary { .. })));
+
+        // Braces nest inside a hole: the last one holds a block
+        let InterpolationPart::Expr(block) = parts[3] else {
+            panic!("expected a hole: {:?}", parts);
+        };
+        assert!(matches!(arena.get_expr(block).map(|expr| &expr.kind), Some(ExprKind::Block(_))));
+    }
+
     #[test]
     fn test_parse_binary_expr() {
         let source = "fn test() { 1 + 2 * 3; }";
@@ -721,10 +942,77 @@ mod tests {
         let (_program, _arena) = parser.parse_program().unwrap();
     }
 
+    #[test]
+    fn test_parse_elif_expr() {
+        let source = "fn test(n: i32) { if n < 0 { 1 } elif n == 0 { 2 } else { 3 } }";
+        let parser = Parser::new(source, "test.ax".to_string()).unwrap();
+        let (_program, arena) = parser.parse_program().unwrap();
+
+        let ifs: Vec<(u32, u32, Option<u32>)> = (0..arena.len() as u32)
+            .filter_map(|id| match arena.get_expr(id).map(|e| &e.kind) {
+                Some(ExprKind::If { condition, else_block, .. }) => Some((id, *condition, *else_block)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ifs.len(), 2);
+        let block_expr = |block: Option<u32>| block.and_then(|id| arena.get_block(id)).and_then(|b| b.expr);
+
+        // The elif branch is an `if` nested as the sole expression of the else block
+        let (outer, inner) = if block_expr(ifs[0].2) == Some(ifs[1].0) {
+            (ifs[0], ifs[1])
+        } else {
+            (ifs[1], ifs[0])
+        };
+        assert_eq!(block_expr(outer.2), Some(inner.0));
+        assert!(matches!(
+            arena.get_expr(outer.1).map(|e| &e.kind),
+            Some(ExprKind::Binary { op: BinaryOp::Lt, .. })
+        ));
+        assert!(matches!(
+            arena.get_expr(inner.1).map(|e| &e.kind),
+            Some(ExprKind::Binary { op: BinaryOp::Eq, .. })
+        ));
+        let else_block = inner.2.and_then(|id| arena.get_block(id)).unwrap();
+        assert_eq!(else_block.stmts.len(), 1);
+
+        let elif = arena.get_expr(inner.0).unwrap().span;
+        assert_eq!(&source[elif.start as usize..elif.start as usize + 4], "elif");
+    }
+
     #[test]
     fn test_parse_function_call() {
         let source = "fn test() { foo(1, 2, 3); }";
         let parser = Parser::new(source, "test.ax".to_string()).unwrap();
         let (_program, _arena) = parser.parse_program().unwrap();
     }
+
+    #[test]
+    fn test_parse_tuple_field() {
+        let source = "fn test(t: ((i32, i32), i32)) { t.0.1 + t.1; }";
+        let parser = Parser::new(source, "test.ax".to_string()).unwrap();
+        let (_program, arena) = parser.parse_program().unwrap();
+
+        let fields: Vec<String> = (0..arena.len() as u32)
+            .filter_map(|id| match &arena.get_expr(id)?.kind {
+                ExprKind::Field { field, .. } => Some(field.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(fields, vec!["0", "1", "1"]);
+    }
+
+    #[test]
+    fn test_parse_inclusive_range() {
+        let source = "fn test() { for i in 0..=10 { } for j in 0..10 { } }";
+        let parser = Parser::new(source, "test.ax".to_string()).unwrap();
+        let (_program, arena) = parser.parse_program().unwrap();
+
+        let ranges: Vec<bool> = (0..arena.len() as u32)
+            .filter_map(|id| match &arena.get_expr(id)?.kind {
+                ExprKind::Range { end: Some(_), inclusive, .. } => Some(*inclusive),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ranges, vec![true, false]);
+    }
 }
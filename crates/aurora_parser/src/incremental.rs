@@ -0,0 +1,336 @@
+//! Incremental parsing for editors
+//!
+//! A `ParsedSource` holds the tokens, AST and CST of a file and keeps them
+//! up to date through edits without parsing the whole file again. Only
+//! the text around an edit is lexed again (see `aurora_lexer::LexedSource`)
+//! and only the top-level items around the changed tokens are parsed
+//! again: parsing resumes before the last item that starts before them
+//! and stops before the first item after them that also started an item
+//! in the old parse. The old items from there on are reused, with their
+//! node IDs and spans moved.
+//!
+//! The result is the same as parsing the edited file from scratch. Only
+//! the part of the CST over the items parsed again is built again; the
+//! subtrees on either side are shared with the old tree.
+
+use crate::cst::{self, SyntaxNode};
+use crate::error::{ParseError, ParseResult};
+use crate::parser::{Checkpoint, Parser};
+use aurora_ast::{Ast, Program, Span};
+use aurora_lexer::{LexedSource, Lexer, Shift, SourceEdit, Token};
+use std::ops::Range;
+
+/// Most top-level items (stray semicolons included) the parser takes
+const MAX_ITEMS: usize = 10000;
+
+/// A parsed source file that can be edited
+pub struct ParsedSource {
+    /// Source and tokens, trivia included
+    lexed: LexedSource,
+    /// Tokens the parser reads, without trivia
+    tokens: Vec<Token>,
+    /// Parser state before each top-level item
+    checkpoints: Vec<Checkpoint>,
+    /// Parse errors
+    errors: Vec<ParseError>,
+    /// The AST
+    ast: Ast,
+    /// The lossless concrete syntax tree
+    cst: SyntaxNode,
+}
+
+impl ParsedSource {
+    /// Lex and parse a source file, recovering from errors
+    pub fn new(source: &str, filename: String) -> ParseResult<Self> {
+        let lexed = LexedSource::new(Lexer::new(source, filename)?.with_trivia());
+        let (tokens, checkpoints, errors, ast) = parse(&lexed);
+        let cst = cst::build(lexed.source(), lexed.tokens(), &ast.arena);
+        Ok(Self { lexed, tokens, checkpoints, errors, ast, cst })
+    }
+
+    /// The source text
+    pub fn source(&self) -> &str {
+        self.lexed.source()
+    }
+
+    /// The tokens of the source, trivia included, ending with `Eof`
+    pub fn tokens(&self) -> &[Token] {
+        self.lexed.tokens()
+    }
+
+    /// The lexed source
+    pub fn lexed(&self) -> &LexedSource {
+        &self.lexed
+    }
+
+    /// The AST
+    pub fn ast(&self) -> &Ast {
+        &self.ast
+    }
+
+    /// The lossless concrete syntax tree
+    pub fn cst(&self) -> &SyntaxNode {
+        &self.cst
+    }
+
+    /// Errors found parsing the source
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// Apply an edit to the source, lexing and parsing again only around it
+    ///
+    /// Returns the indices of the top-level items that were parsed again.
+    pub fn edit(&mut self, edit: &impl SourceEdit) -> Range<usize> {
+        let splice = self.lexed.edit(edit);
+        let shift = splice.shift;
+
+        // Map the changed range of tokens to the tokens without trivia, by
+        // where the unchanged tokens on either side start in the old source
+        let all = self.lexed.tokens();
+        let start = self.tokens.partition_point(|token| token.offset < all[splice.start].offset);
+        let old_end = match splice.new_end < all.len() {
+            true => self.tokens.partition_point(|token| token.offset < shift.from),
+            false => self.tokens.len(),
+        };
+        let changed: Vec<Token> =
+            all[splice.start..splice.new_end].iter().filter(|token| !token.kind.is_trivia()).cloned().collect();
+        let new_end = start + changed.len();
+        self.tokens.splice(start..old_end, changed);
+        for token in &mut self.tokens[new_end..] {
+            shift.move_token(token);
+        }
+
+        // Resume before the last item that starts before the changed tokens:
+        // the items before it did not look at them
+        let first = self.checkpoints.partition_point(|checkpoint| checkpoint.pos < start);
+        let restart = first.checked_sub(1).map_or_else(Checkpoint::default, |i| self.checkpoints[i]);
+        let old_checkpoints = self.checkpoints.split_off(first.saturating_sub(1));
+        let mut arena = std::mem::take(&mut self.ast.arena);
+        let old_nodes = arena.split_off(restart.nodes);
+        let mut items = std::mem::take(&mut self.ast.items);
+        let old_items = items.split_off(restart.items);
+        let mut errors = std::mem::take(&mut self.errors);
+        let old_errors = errors.split_off(restart.errors);
+
+        // Stop before an old item that only looks at unchanged tokens
+        let moved = new_end as isize - old_end as isize;
+        let mut reused = None;
+        let mut parser = Parser::resume(std::mem::take(&mut self.tokens), restart, arena, errors);
+        let result = parser.parse_items_until(&mut items, &mut self.checkpoints, |pos| {
+            let old_pos = pos.wrapping_add_signed(-moved);
+            reused = (pos > new_end)
+                .then(|| old_checkpoints.binary_search_by_key(&old_pos, |checkpoint| checkpoint.pos).ok())
+                .flatten();
+            reused.is_some()
+        });
+        let stop = parser.pos();
+        let (span, mut arena, mut errors, tokens) = parser.finish();
+        self.tokens = tokens;
+
+        // Parsing from scratch fails past the most items, and keeps none
+        let remaining = reused.map_or(0, |i| old_checkpoints.len() - i);
+        if result.is_err() || self.checkpoints.len() + remaining > MAX_ITEMS {
+            (self.tokens, self.checkpoints, self.errors, self.ast) = parse(&self.lexed);
+            self.cst = cst::build(self.lexed.source(), self.lexed.tokens(), &self.ast.arena);
+            return 0..self.ast.items.len();
+        }
+
+        let reparsed = restart.items..items.len();
+        let parsed_nodes = restart.nodes..arena.len();
+        if let Some(i) = reused {
+            let from = old_checkpoints[i];
+            let now = Checkpoint { pos: stop, nodes: arena.len(), items: items.len(), errors: errors.len() };
+            let move_id = |id: u32| match id as usize >= from.nodes {
+                true => (id as usize + now.nodes - from.nodes) as u32,
+                false => id,
+            };
+            for mut node in old_nodes.into_iter().skip(from.nodes - restart.nodes) {
+                node.remap_ids(move_id);
+                node.remap_spans(|span| move_span(span, &shift));
+                arena.alloc(node);
+            }
+            items.extend(old_items[from.items - restart.items..].iter().map(|&id| move_id(id)));
+            for mut error in old_errors.into_iter().skip(from.errors - restart.errors) {
+                move_error(&mut error, &shift);
+                errors.push(error);
+            }
+            self.checkpoints.extend(old_checkpoints[i..].iter().map(|checkpoint| Checkpoint {
+                pos: checkpoint.pos + now.pos - from.pos,
+                nodes: checkpoint.nodes + now.nodes - from.nodes,
+                items: checkpoint.items + now.items - from.items,
+                errors: checkpoint.errors + now.errors - from.errors,
+            }));
+        }
+
+        self.ast = Program::new(items, span, arena);
+        self.errors = errors;
+
+        // Build the CST again over the items parsed again only
+        let all = self.lexed.tokens();
+        let at = |pos: usize| all.partition_point(|token| token.offset < self.tokens[pos].offset);
+        let nodes = &self.ast.arena.nodes()[parsed_nodes];
+        self.cst = cst::rebuild(&self.cst, self.lexed.source(), all, at(restart.pos)..at(stop), shift.bytes, nodes);
+        reparsed
+    }
+}
+
+/// Parse all of a lexed file, giving the tokens the parser read, the
+/// checkpoints before items, the errors and the AST
+fn parse(lexed: &LexedSource) -> (Vec<Token>, Vec<Checkpoint>, Vec<ParseError>, Ast) {
+    let mut parser = Parser::from_tokens(lexed.tokens().to_vec());
+    let mut items = Vec::new();
+    let mut checkpoints = Vec::new();
+    let result = parser.parse_items_until(&mut items, &mut checkpoints, |_| false);
+    let (span, arena, mut errors, tokens) = parser.finish();
+
+    // As `Parser::parse()` does, but keeping the error
+    let ast = match result {
+        Ok(()) => Program::new(items, span, arena),
+        Err(error) => {
+            errors.push(error);
+            Program::new(Vec::new(), Span::dummy(), arena)
+        }
+    };
+    (tokens, checkpoints, errors, ast)
+}
+
+/// Move a span from the old source, if it is after the edit
+fn move_span(span: Span, shift: &Shift) -> Span {
+    if !shift.moves(span.start as usize) {
+        return span;
+    }
+    let (line, column) = shift.location(span.line as usize, span.column as usize);
+    Span {
+        start: shift.offset(span.start as usize) as u32,
+        end: shift.offset(span.end as usize) as u32,
+        line: line as u32,
+        column: column as u32,
+        ..span
+    }
+}
+
+/// Move the spans of an error from the old source
+fn move_error(error: &mut ParseError, shift: &Shift) {
+    match error {
+        ParseError::Lexer(error) => {
+            *error = error.clone().moved(|offset| match shift.moves(offset) {
+                true => shift.offset(offset),
+                false => offset,
+            });
+        }
+        ParseError::Expected { span, .. }
+        | ParseError::Unexpected { span, .. }
+        | ParseError::InvalidSyntax { span, .. }
+        | ParseError::UnexpectedEof { span, .. } => *span = move_span(*span, shift),
+        ParseError::Multiple(errors) => {
+            for error in errors {
+                move_error(error, shift);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cst::GreenElement;
+    use aurora_lexer::ByteEdit;
+    use std::sync::Arc;
+
+    const SOURCE: &str = "use std::io;\n\n/// A point\nstruct Point { x: i32, y: i32 }\n\nfn main() {\n    let p = Point { x: 1, y: 2 };\n    if p.x < 2 { println(f\"{p.x}\"); } else { return; }\n}\n;\nenum E { A, B(i32) }\nimpl Point { fn len(self) -> i32 { self.x * self.y } }\nconst N: i32 = 3;\nfn broken( {\n";
+
+    fn parsed(source: &str) -> ParsedSource {
+        ParsedSource::new(source, "test.ax".to_string()).unwrap()
+    }
+
+    /// Apply the edit incrementally and check it matches parsing from scratch
+    fn check(source: &str, edit: ByteEdit) -> Range<usize> {
+        let mut incremental = parsed(source);
+        let reparsed = incremental.edit(&edit);
+        let mut edited = source.to_string();
+        edited.replace_range(edit.range.clone(), &edit.text);
+        let scratch = parsed(&edited);
+
+        assert_eq!(incremental.source(), edited);
+        assert_eq!(incremental.tokens(), scratch.tokens());
+        assert_eq!(incremental.ast(), scratch.ast(), "{:?} on {:?}", edit, source);
+        assert_eq!(format!("{:?}", incremental.errors()), format!("{:?}", scratch.errors()));
+        assert_eq!(incremental.checkpoints, scratch.checkpoints);
+        assert_eq!(incremental.cst().green(), scratch.cst().green());
+        reparsed
+    }
+
+    #[test]
+    fn test_reparse_matches_parsing_from_scratch() {
+        let boundaries: Vec<usize> = (0..=SOURCE.len()).filter(|&i| SOURCE.is_char_boundary(i)).collect();
+        for &at in &boundaries {
+            for text in ["x", "}", "{", ";", "fn g() {}\n", "\"", "/*", "\n", "(", "let "] {
+                check(SOURCE, ByteEdit::new(at..at, text));
+            }
+        }
+        for window in boundaries.windows(5) {
+            check(SOURCE, ByteEdit::new(window[0]..window[4], ""));
+        }
+        for window in boundaries.windows(60).step_by(7) {
+            check(SOURCE, ByteEdit::new(window[0]..window[59], ""));
+        }
+    }
+
+    #[test]
+    fn test_reparse_matches_parse_lossless() {
+        let mut incremental = parsed(SOURCE);
+        incremental.edit(&ByteEdit::new(SOURCE.len() - 9..SOURCE.len(), "ok() {}\n"));
+        let (ast, cst) = Parser::parse_lossless(incremental.source(), "test.ax".to_string()).unwrap();
+        assert!(incremental.errors().is_empty());
+        assert_eq!(*incremental.ast(), ast);
+        assert_eq!(incremental.cst().green(), cst.green());
+    }
+
+    #[test]
+    fn test_reparse_only_touches_enclosing_items() {
+        let source: String = (0..50).map(|i| format!("fn f{i}(a: i32) -> i32 {{\n    a + {i}\n}}\n")).collect();
+        let at = source.find("a + 20").unwrap();
+        assert_eq!(check(&source, ByteEdit::new(at..at + 1, "a * a")), 20..21);
+
+        // The items on either side of a new one are parsed again with it,
+        // and an unclosed brace swallows the rest
+        let at = source.find("fn f30").unwrap();
+        assert_eq!(check(&source, ByteEdit::new(at..at, "fn g() {}\n")), 29..32);
+        assert_eq!(check(&source, ByteEdit::new(at..at, "fn g() {\n")), 29..30);
+    }
+
+    #[test]
+    fn test_reparse_shares_unchanged_subtrees() {
+        let source: String = (0..10).map(|i| format!("/// f{i}\nfn f{i}() {{}}\n")).collect();
+        let mut incremental = parsed(&source);
+        let old = incremental.cst().green().clone();
+        let at = source.find("f5() {}").unwrap() + 6;
+        incremental.edit(&ByteEdit::new(at..at, "1"));
+
+        let (old, new) = (old.children(), incremental.cst().green().children());
+        let shared = |i: usize| match (&old[i], &new[i]) {
+            (GreenElement::Node(old), GreenElement::Node(new)) => Arc::ptr_eq(old, new),
+            (GreenElement::Token(old), GreenElement::Token(new)) => Arc::ptr_eq(old, new),
+            _ => false,
+        };
+        assert_eq!(old.len(), new.len());
+        let items: Vec<usize> = (0..new.len()).filter(|&i| matches!(new[i], GreenElement::Node(_))).collect();
+        assert!(items[..5].iter().chain(&items[6..]).all(|&i| shared(i)));
+        assert!(!shared(items[5]));
+    }
+
+    #[test]
+    fn test_repeated_edits() {
+        let mut incremental = parsed("fn main() {}\n");
+        let mut source = "fn main() {}\n".to_string();
+        for (range, text) in [(11..11, "\n    let x = 1;\n"), (0..0, "const C: i32 = 2;\n"), (35..36, "2 + C"), (18..19, "")] {
+            let edit = ByteEdit::new(range, text);
+            incremental.edit(&edit);
+            source.replace_range(edit.range.clone(), &edit.text);
+            let scratch = parsed(&source);
+            assert_eq!(incremental.ast(), scratch.ast(), "{:?}", source);
+            assert_eq!(format!("{:?}", incremental.errors()), format!("{:?}", scratch.errors()));
+        }
+    }
+}
@@ -18,17 +18,23 @@
 //! - `types`: Type parsing
 //! - `patterns`: Pattern parsing
 //! - `error`: Error types and recovery
+//! - `cst`: Lossless concrete syntax tree, built alongside the AST
+//! - `incremental`: Re-parsing only the items around an edit, for editors
 
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
+pub mod cst;
 mod decls;
 mod error;
 mod exprs;
+mod incremental;
 mod parser;
 mod patterns;
 mod stmts;
 mod types;
 
+pub use cst::{NodeKind, SyntaxElement, SyntaxNode, SyntaxToken};
 pub use error::{ParseError, ParseResult};
+pub use incremental::ParsedSource;
 pub use parser::Parser;
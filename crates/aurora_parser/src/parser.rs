@@ -3,11 +3,25 @@
 //! This module implements the main parser structure that coordinates
 //! LL parsing for declarations and Pratt parsing for expressions.
 
-use aurora_ast::{Arena, Ast, Program, Span};
+use aurora_ast::{Arena, Ast, Program, SourceMap, Span};
 use aurora_lexer::{Lexer, Token, TokenKind};
+use crate::cst::{self, SyntaxNode};
 use crate::error::{ParseError, ParseResult};
 use std::sync::Arc;
 
+/// The parser's state before a top-level item, where parsing can resume
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Checkpoint {
+    /// Position in the token stream
+    pub(crate) pos: usize,
+    /// Nodes in the arena
+    pub(crate) nodes: usize,
+    /// Top-level items parsed
+    pub(crate) items: usize,
+    /// Errors collected
+    pub(crate) errors: usize,
+}
+
 /// Parser for Aurora source code
 pub struct Parser {
     /// Token stream from lexer
@@ -18,6 +32,8 @@ pub struct Parser {
     pub(crate) arena: Arena,  // Made public for submodules
     /// Collected parse errors
     errors: Vec<ParseError>,
+    /// Whether `Ident {` may start a struct literal (false in conditions)
+    pub(crate) allow_struct_literal: bool,
 }
 
 impl Parser {
@@ -27,20 +43,44 @@ impl Parser {
         let tokens = lexer.lex_all()?;
 
         Ok(Self {
-            tokens,
+            tokens: Self::strip_trivia(tokens),
             pos: 0,
             arena: Arena::new(),
             errors: Vec::new(),
+            allow_struct_literal: true,
         })
     }
 
+    /// Parse source code into its AST and its lossless concrete syntax tree
+    ///
+    /// Errors are recovered from as in `parse()`; the CST holds the source
+    /// text either way.
+    pub fn parse_lossless(source: &str, filename: String) -> ParseResult<(Ast, SyntaxNode)> {
+        let tokens = Lexer::new(source, filename)?.with_trivia().lex_recovering();
+        let ast = Self::from_tokens(tokens.clone()).parse();
+        let cst = cst::build(source, &tokens, &ast.arena);
+        Ok((ast, cst))
+    }
+
+    /// Create a parser for a file registered in a source map
+    pub fn from_source_map(source_map: &SourceMap, file_id: u32) -> ParseResult<Self> {
+        let file = source_map.get(file_id).ok_or_else(|| ParseError::InvalidSyntax {
+            span: Span::dummy(),
+            message: format!("Unknown source file ID {}", file_id),
+        })?;
+        let mut lexer = Lexer::new(&file.source, file.name.clone())?.with_file_id(file_id);
+        let tokens = lexer.lex_all()?;
+        Ok(Self::from_tokens(tokens))
+    }
+
     /// Create a parser from a pre-lexed token stream
     pub fn from_tokens(tokens: Vec<Token>) -> Self {
         Self {
-            tokens,
+            tokens: Self::strip_trivia(tokens),
             pos: 0,
             arena: Arena::new(),
             errors: Vec::new(),
+            allow_struct_literal: true,
         }
     }
 
@@ -52,13 +92,25 @@ impl Parser {
         _diagnostics: Arc<D>
     ) -> Self {
         Self {
-            tokens,
+            tokens: Self::strip_trivia(tokens),
             pos: 0,
             arena: Arena::new(),
             errors: Vec::new(),
+            allow_struct_literal: true,
         }
     }
 
+    /// Allocate nodes into an existing arena (so several files can share one)
+    pub fn with_arena(mut self, arena: Arena) -> Self {
+        self.arena = arena;
+        self
+    }
+
+    /// Drop comment and whitespace tokens, which the grammar does not use
+    fn strip_trivia(tokens: Vec<Token>) -> Vec<Token> {
+        tokens.into_iter().filter(|t| !t.kind.is_trivia()).collect()
+    }
+
     /// Parse a complete program (original API - deprecated, arena now in Program)
     pub fn parse_program(mut self) -> ParseResult<(Program, Arena)> {
         let items = self.parse_items()?;
@@ -90,7 +142,8 @@ impl Parser {
             }
             Err(e) => {
                 eprintln!("Parse error: {:?}", e);
-                Program::empty()
+                // Keep the arena: it may hold nodes from other files
+                Program::new(Vec::new(), Span::dummy(), self.arena)
             }
         }
     }
@@ -98,12 +151,26 @@ impl Parser {
     /// Parse top-level items until EOF
     fn parse_items(&mut self) -> ParseResult<Vec<u32>> {
         let mut items = Vec::new();
-        let mut iterations = 0;
+        self.parse_items_until(&mut items, &mut Vec::new(), |_| false)?;
+        Ok(items)
+    }
+
+    /// Parse top-level items until EOF or until `stop` accepts the position
+    /// of the next one
+    ///
+    /// A checkpoint is recorded before each item, after those of the items
+    /// already parsed, which `checkpoints` holds.
+    pub(crate) fn parse_items_until(
+        &mut self,
+        items: &mut Vec<u32>,
+        checkpoints: &mut Vec<Checkpoint>,
+        mut stop: impl FnMut(usize) -> bool,
+    ) -> ParseResult<()> {
         const MAX_ITERATIONS: usize = 10000;
 
-        while !self.is_at_end() {
-            iterations += 1;
-            if iterations > MAX_ITERATIONS {
+        while !self.is_at_end() && !stop(self.pos) {
+            checkpoints.push(self.checkpoint(items.len()));
+            if checkpoints.len() > MAX_ITERATIONS {
                 eprintln!("[PARSER ERROR] Infinite loop detected in parse_items()");
                 eprintln!("  Current token: {:?}", self.peek());
                 eprintln!("  Position: {}", self.pos);
@@ -136,7 +203,40 @@ impl Parser {
             }
         }
 
-        Ok(items)
+        Ok(())
+    }
+
+    /// The parser's state before a top-level item
+    fn checkpoint(&self, items: usize) -> Checkpoint {
+        Checkpoint {
+            pos: self.pos,
+            nodes: self.arena.len(),
+            items,
+            errors: self.errors.len(),
+        }
+    }
+
+    /// Create a parser resuming at a checkpoint, with the arena and errors
+    /// cut back to it
+    pub(crate) fn resume(tokens: Vec<Token>, checkpoint: Checkpoint, arena: Arena, errors: Vec<ParseError>) -> Self {
+        Self {
+            tokens,
+            pos: checkpoint.pos,
+            arena,
+            errors,
+            allow_struct_literal: true,
+        }
+    }
+
+    /// Current position in the token stream
+    pub(crate) fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Finish parsing, giving the span of the program, the arena, the errors
+    /// and the tokens
+    pub(crate) fn finish(self) -> (Span, Arena, Vec<ParseError>, Vec<Token>) {
+        (self.span_from_tokens(), self.arena, self.errors, self.tokens)
     }
 
     /// Get the current token
@@ -204,9 +304,9 @@ impl Parser {
     /// Convert token to span
     pub(crate) fn token_to_span(&self, token: &Token) -> Span {
         Span::new(
-            0, // file_id - TODO: track properly
-            0, // start - TODO: track properly
-            0, // end - TODO: track properly
+            token.file_id,
+            token.offset as u32,
+            token.end() as u32,
             token.line as u32,
             token.column as u32,
         )
@@ -229,6 +329,8 @@ impl Parser {
             if matches!(
                 self.peek(),
                 TokenKind::Fn
+                    | TokenKind::Struct
+                    | TokenKind::Enum
                     | TokenKind::Type
                     | TokenKind::Trait
                     | TokenKind::Impl
@@ -296,6 +398,8 @@ mod tests {
             kind: TokenKind::Eof,
             lexeme: String::new(),
             file: "test.ax".to_string(),
+            file_id: 0,
+            offset: 0,
             line: 1,
             column: 1,
             len: 0,
@@ -655,4 +759,34 @@ fn test() {
         let (program, _arena) = parser.parse_program().unwrap();
         assert_eq!(program.items.len(), 1);
     }
+
+    #[test]
+    fn test_spans_are_byte_accurate() {
+        let mut source_map = SourceMap::new();
+        source_map.add_file("prelude.ax", "");
+        let file_id = source_map.add_file("test.ax", "// héllo\nfn add(a: i32) -> i32 {\n    a + 1\n}\n");
+
+        let parser = Parser::from_source_map(&source_map, file_id).unwrap();
+        let (program, arena) = parser.parse_program().unwrap();
+
+        let item = arena.get_item(program.items[0]).unwrap();
+        assert_eq!(item.span.file_id, file_id);
+        assert_eq!((item.span.line, item.span.column), (2, 1));
+        let text = source_map.span_text(item.span).unwrap();
+        assert!(text.starts_with("fn add") && text.ends_with('}'), "{:?}", text);
+
+        let aurora_ast::ItemKind::Function(func) = &item.kind else {
+            panic!("expected a function");
+        };
+        let Some(aurora_ast::AstNode::Stmt(stmt)) = arena.get(func.body.stmts[0]) else {
+            panic!("expected a statement");
+        };
+        assert_eq!(source_map.span_text(stmt.span), Some("a + 1"));
+        let aurora_ast::StmtKind::Expr { expr, .. } = stmt.kind else {
+            panic!("expected an expression statement");
+        };
+        let body = arena.get_expr(expr).unwrap();
+        assert_eq!(source_map.span_text(body.span), Some("a + 1"));
+        assert_eq!((body.span.line, body.span.column), (3, 5));
+    }
 }
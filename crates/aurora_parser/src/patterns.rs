@@ -10,12 +10,68 @@ use aurora_ast::pattern::{FieldPattern, Pattern, PatternKind};
 use aurora_ast::expr::{Literal, Path};
 use aurora_lexer::TokenKind;
 use crate::error::{ParseError, ParseResult};
+use crate::exprs::{char_literal_value, string_literal_value};
 use crate::parser::Parser;
 
 impl Parser {
+    /// Parse a pattern with alternatives (`A | B`), as in match arms
+    pub(crate) fn parse_or_pattern(&mut self) -> ParseResult<u32> {
+        let start = self.token_to_span(self.current());
+        let first = self.parse_pattern()?;
+        if !self.check(&TokenKind::Or) {
+            return Ok(first);
+        }
+
+        let mut alternatives = vec![first];
+        while self.check(&TokenKind::Or) {
+            self.advance();
+            alternatives.push(self.parse_pattern()?);
+        }
+
+        let pattern = Pattern {
+            kind: PatternKind::Or(alternatives),
+            span: self.span_from(start),
+            hygiene: Default::default(),
+        };
+        Ok(self.arena.alloc_pattern(pattern))
+    }
+
     /// Parse a pattern expression
     pub(crate) fn parse_pattern(&mut self) -> ParseResult<u32> {
         let start = self.token_to_span(self.current());
+        let pattern = self.parse_primary_pattern()?;
+
+        // Range patterns (`1..=5`, `'a'..'z'`) start with a literal
+        let inclusive = match self.peek() {
+            TokenKind::DotDotEq => true,
+            TokenKind::DotDot => false,
+            _ => return Ok(pattern),
+        };
+        let is_literal = matches!(
+            self.arena.get_pattern(pattern).map(|pat| &pat.kind),
+            Some(PatternKind::Literal(_))
+        );
+        if !is_literal {
+            return Ok(pattern);
+        }
+        self.advance();
+        let end = self.parse_primary_pattern()?;
+
+        let pattern = Pattern {
+            kind: PatternKind::Range {
+                start: Box::new(pattern),
+                end: Box::new(end),
+                inclusive,
+            },
+            span: self.span_from(start),
+            hygiene: Default::default(),
+        };
+        Ok(self.arena.alloc_pattern(pattern))
+    }
+
+    /// Parse a pattern other than a range or alternatives
+    fn parse_primary_pattern(&mut self) -> ParseResult<u32> {
+        let start = self.token_to_span(self.current());
         
         let kind = match self.peek() {
             // Wildcard pattern (_)
@@ -24,19 +80,61 @@ impl Parser {
                 PatternKind::Wildcard
             }
             
+            // `self` receiver
+            TokenKind::SelfLower => {
+                self.advance();
+                PatternKind::Ident { name: "self".to_string(), is_mut: false }
+            }
+
+            // Mutable binding (`mut x`)
+            TokenKind::Mut => {
+                self.advance();
+                let name = if self.check(&TokenKind::SelfLower) {
+                    self.advance();
+                    "self".to_string()
+                } else {
+                    self.expect(TokenKind::Ident, "Expected identifier after 'mut'")?.lexeme.clone()
+                };
+                PatternKind::Ident { name, is_mut: true }
+            }
+
+            // Reference pattern (`&x`, `&mut self`)
+            TokenKind::And => {
+                self.advance();
+                let is_mut = if self.check(&TokenKind::Mut) {
+                    self.advance();
+                    true
+                } else {
+                    false
+                };
+                let inner = Box::new(self.parse_pattern()?);
+                PatternKind::Ref { inner, is_mut }
+            }
+
+            // Constructor paths (`Self` and the built-in constructors are keywords)
+            TokenKind::SelfUpper
+            | TokenKind::Some
+            | TokenKind::None
+            | TokenKind::Ok
+            | TokenKind::Err => {
+                let name = self.current().lexeme.clone();
+                self.advance();
+                let path = self.parse_path_from_segment(name)?;
+                return self.parse_constructor_pattern(path, start);
+            }
+
             // Identifier pattern
             TokenKind::Ident => {
                 let name = self.current().lexeme.clone();
                 self.advance();
                 
-                // Check if it's a struct pattern
-                if self.check(&TokenKind::LBrace) {
-                    // This is a struct pattern
-                    let path = Path {
-                        segments: vec![name],
-                        generics: vec![],
-                    };
-                    return self.parse_struct_pattern(path, start);
+                // Check if it's a path, struct or tuple struct pattern
+                if self.check(&TokenKind::ColonColon)
+                    || self.check(&TokenKind::LBrace)
+                    || self.check(&TokenKind::LParen)
+                {
+                    let path = self.parse_path_from_segment(name)?;
+                    return self.parse_constructor_pattern(path, start);
                 }
                 
                 // Check for 'mut' prefix
@@ -51,23 +149,34 @@ impl Parser {
             }
             
             // Literal patterns
-            TokenKind::IntLiteral => {
-                let n = self.current().lexeme.parse::<i64>().unwrap_or(0);
+            TokenKind::IntLiteral | TokenKind::FloatLiteral => PatternKind::Literal(self.parse_number_literal()?),
+            // Negative numeric literals
+            TokenKind::Minus => {
                 self.advance();
-                PatternKind::Literal(Literal::Int(n))
-            }
-            TokenKind::FloatLiteral => {
-                let f = self.current().lexeme.parse::<f64>().unwrap_or(0.0);
-                self.advance();
-                PatternKind::Literal(Literal::Float(f))
+                let literal = match self.peek() {
+                    TokenKind::IntLiteral | TokenKind::FloatLiteral => match self.parse_number_literal()? {
+                        Literal::Int(value, suffix) => Literal::Int(-value, suffix),
+                        Literal::Float(value, suffix) => Literal::Float(-value, suffix),
+                        literal => literal,
+                    },
+                    _ => {
+                        return Err(ParseError::Expected {
+                            expected: "numeric literal".to_string(),
+                            found: format!("{:?}", self.peek()),
+                            span: self.token_to_span(self.current()),
+                            message: "Expected a number after '-' in pattern".to_string(),
+                        });
+                    }
+                };
+                PatternKind::Literal(literal)
             }
             TokenKind::StringLiteral => {
-                let s = self.current().lexeme.clone();
+                let s = string_literal_value(&self.current().lexeme);
                 self.advance();
                 PatternKind::Literal(Literal::String(s))
             }
             TokenKind::CharLiteral => {
-                let c = self.current().lexeme.chars().next().unwrap_or('?');
+                let c = char_literal_value(&self.current().lexeme);
                 self.advance();
                 PatternKind::Literal(Literal::Char(c))
             }
@@ -87,7 +196,7 @@ impl Parser {
                 
                 if !self.check(&TokenKind::RParen) {
                     loop {
-                        patterns.push(self.parse_pattern()?);
+                        patterns.push(self.parse_or_pattern()?);
                         
                         if !self.check(&TokenKind::Comma) {
                             break;
@@ -123,6 +232,43 @@ impl Parser {
         Ok(self.arena.alloc_pattern(pattern))
     }
     
+    /// Parse the pattern following a constructor path
+    ///
+    /// `Path { .. }` is a struct pattern, `Path(..)` a tuple struct pattern,
+    /// and a bare path names a unit struct or variant.
+    fn parse_constructor_pattern(&mut self, path: Path, start: aurora_ast::Span) -> ParseResult<u32> {
+        if self.check(&TokenKind::LBrace) {
+            return self.parse_struct_pattern(path, start);
+        }
+
+        let kind = if self.check(&TokenKind::LParen) {
+            self.advance();
+            let mut fields = Vec::new();
+
+            while !self.check(&TokenKind::RParen) && !self.is_at_end() {
+                fields.push(self.parse_or_pattern()?);
+
+                if !self.check(&TokenKind::Comma) {
+                    break;
+                }
+                self.advance();
+            }
+
+            self.expect(TokenKind::RParen, "Expected ')' after tuple struct pattern")?;
+            PatternKind::TupleStruct { path, fields }
+        } else {
+            PatternKind::Path(path)
+        };
+
+        let span = self.span_from(start);
+        let pattern = Pattern {
+            kind,
+            span,
+            hygiene: Default::default(),
+        };
+        Ok(self.arena.alloc_pattern(pattern))
+    }
+
     /// Parse a struct pattern
     fn parse_struct_pattern(&mut self, path: Path, start: aurora_ast::Span) -> ParseResult<u32> {
         self.expect(TokenKind::LBrace, "Expected '{'")?;
@@ -145,7 +291,7 @@ impl Parser {
                 
                 let pattern = if self.check(&TokenKind::Colon) {
                     self.advance();
-                    Some(self.parse_pattern()?)
+                    Some(self.parse_or_pattern()?)
                 } else {
                     // Shorthand: `{ x }` means `{ x: x }`
                     None
@@ -206,4 +352,49 @@ mod tests {
         let parser = Parser::new(source, "test.ax".to_string()).unwrap();
         let (_program, _arena) = parser.parse_program().unwrap();
     }
+
+    #[test]
+    fn test_parse_constructor_patterns() {
+        let source = "fn test(&self) { match m { Msg::Quit => 0, Msg::Move { x, .. } => x, Msg::Write(s) => 1, Some(v) => v, None => 2, } }";
+        let parser = Parser::new(source, "test.ax".to_string()).unwrap();
+        let (_program, arena) = parser.parse_program().unwrap();
+        let kinds: Vec<_> = arena
+            .nodes()
+            .iter()
+            .filter_map(|node| match node {
+                aurora_ast::AstNode::Pattern(pat) => Some(&pat.kind),
+                _ => None,
+            })
+            .collect();
+        assert!(kinds.iter().any(|k| matches!(k, PatternKind::Ref { .. })));
+        assert!(kinds.iter().any(|k| matches!(k, PatternKind::Path(p) if p.segments == ["Msg", "Quit"])));
+        assert!(kinds.iter().any(|k| matches!(k, PatternKind::Struct { has_rest: true, .. })));
+        assert!(kinds.iter().any(|k| matches!(k, PatternKind::TupleStruct { path, .. } if path.segments == ["Some"])));
+        assert!(kinds.iter().any(|k| matches!(k, PatternKind::Path(p) if p.segments == ["None"])));
+    }
+
+    #[test]
+    fn test_parse_or_and_range_patterns() {
+        let source = "fn test(n: i32) { match n { 1 | 2 => 0, -5..=0 => 1, 10..20 => 2, Some((1 | 3, _)) => 3, _ => 4 } }";
+        let parser = Parser::new(source, "test.ax".to_string()).unwrap();
+        let (_program, arena) = parser.parse_program().unwrap();
+        let kinds: Vec<_> = arena
+            .nodes()
+            .iter()
+            .filter_map(|node| match node {
+                aurora_ast::AstNode::Pattern(pat) => Some(&pat.kind),
+                _ => None,
+            })
+            .collect();
+        let ranges: Vec<bool> = kinds
+            .iter()
+            .filter_map(|k| match k {
+                PatternKind::Range { inclusive, .. } => Some(*inclusive),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ranges, vec![true, false]);
+        assert_eq!(kinds.iter().filter(|k| matches!(k, PatternKind::Or(alts) if alts.len() == 2)).count(), 2);
+        assert!(kinds.iter().any(|k| matches!(k, PatternKind::Literal(Literal::Int(-5, None)))));
+    }
 }
@@ -21,7 +21,8 @@ impl Parser {
         }
         
         // Check for item in statement position
-        if self.check(&TokenKind::Fn) || self.check(&TokenKind::Type)
+        if self.check(&TokenKind::Fn) || self.check(&TokenKind::Struct)
+            || self.check(&TokenKind::Enum) || self.check(&TokenKind::Type)
             || self.check(&TokenKind::Trait) || self.check(&TokenKind::Impl)
             || self.check(&TokenKind::Const) || self.check(&TokenKind::Mod)
             || self.check(&TokenKind::Use) {
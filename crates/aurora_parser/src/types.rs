@@ -9,7 +9,7 @@
 //! - Function types (fn(i32) -> String)
 
 use aurora_ast::ty::{FloatType, IntType, Type, TypeKind, UintType};
-use aurora_ast::expr::Path;
+use aurora_ast::expr::GenericArg;
 use aurora_lexer::TokenKind;
 use crate::error::{ParseError, ParseResult};
 use crate::parser::Parser;
@@ -175,8 +175,13 @@ impl Parser {
             }
             
             // Path types (e.g., String, Vec<T>, std::io::Read)
-            TokenKind::Ident => {
-                let path = self.parse_path()?;
+            TokenKind::Ident | TokenKind::SelfUpper => {
+                let first = self.current().lexeme.clone();
+                self.advance();
+                let mut path = self.parse_path_from_segment(first)?;
+                if self.check(&TokenKind::Lt) {
+                    path.generics = self.parse_generic_args()?;
+                }
                 TypeKind::Path { path }
             }
             
@@ -194,6 +199,24 @@ impl Parser {
         let ty = Type { kind, span };
         Ok(self.arena.alloc_type(ty))
     }
+
+    /// Parse generic arguments of a path type (e.g., `<i32, T>`)
+    fn parse_generic_args(&mut self) -> ParseResult<Vec<GenericArg>> {
+        self.expect(TokenKind::Lt, "Expected '<'")?;
+
+        let mut args = Vec::new();
+        while !self.check(&TokenKind::Gt) && !self.is_at_end() {
+            args.push(GenericArg::Type(self.parse_type()?));
+
+            if !self.check(&TokenKind::Comma) {
+                break;
+            }
+            self.advance();
+        }
+
+        self.expect(TokenKind::Gt, "Expected '>' after generic arguments")?;
+        Ok(args)
+    }
 }
 
 #[cfg(test)]
@@ -215,6 +238,18 @@ mod tests {
         let (_program, _arena) = parser.parse_program().unwrap();
     }
 
+    #[test]
+    fn test_parse_generic_path_type() {
+        let source = "fn test(p: Pair<i32, bool>) -> Self {}";
+        let parser = Parser::new(source, "test.ax".to_string()).unwrap();
+        let (_program, arena) = parser.parse_program().unwrap();
+        let has_generic_path = arena.nodes().iter().any(|node| {
+            matches!(node, aurora_ast::AstNode::Type(Type { kind: TypeKind::Path { path }, .. })
+                if path.segments == ["Pair"] && path.generics.len() == 2)
+        });
+        assert!(has_generic_path);
+    }
+
     #[test]
     fn test_parse_ref_type() {
         let source = "fn test(x: &i32, y: &mut String) {}";
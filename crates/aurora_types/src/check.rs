@@ -0,0 +1,2247 @@
+//! AST Type Checking
+//!
+//! This module drives Hindley-Milner inference over a parsed program:
+//! 1. Collect struct and enum declarations and their constructors
+//! 2. Collect the signature of every function, constant and impl method
+//! 3. Infer the type of every expression in every function body
+//! 4. Default unconstrained numeric literals, check that integer literals
+//!    fit their types and record final types
+//!
+//! Type errors do not stop inference. They are recorded together with the
+//! span of the offending expression and checking continues, so a single
+//! run reports as many independent errors as possible.
+
+use crate::exhaustive::{self, ExhaustivenessError, Pattern as ExhaustPattern, Variants};
+use crate::ty::{EffectSet, PrimitiveType, Type, TypeVarId};
+use crate::{
+    EnumDef, EnumVariant, InferenceError, SpannedTypeError, StructDef, TypeChecker, TypeError, TypeScheme,
+    UnificationError,
+};
+use aurora_ast::decl::{FunctionDecl, GenericParam, ImplItem, ItemKind, Param, VariantFields};
+use aurora_ast::expr::{BinaryOp, BlockId, ExprId, ExprKind, InterpolationPart, Literal, PatternId, UnaryOp};
+use aurora_ast::pattern::PatternKind;
+use aurora_ast::stmt::{Block, StmtKind};
+use aurora_ast::ty::TypeKind;
+use aurora_ast::{Arena, ItemId, Span};
+use std::collections::HashMap;
+
+/// Built-in functions that print one formattable value, or a string
+/// literal whose `{}` placeholders take the formattable values after it
+const PRINT_BUILTINS: &[&str] = &["print", "println"];
+
+/// Signature of a function collected before its body is checked
+#[derive(Debug, Clone)]
+pub(crate) struct Signature {
+    /// Generic parameters in scope in the body
+    generics: Generics,
+    /// Type of `Self` for methods
+    self_ty: Option<Type>,
+    /// Function type (not instantiated)
+    ty: Type,
+}
+
+/// Struct or enum declaration
+#[derive(Debug, Clone)]
+pub(crate) struct AdtDef {
+    /// Type variables standing for the generic parameters, in order
+    params: Vec<TypeVarId>,
+    /// Variants (a struct has a single variant named after the struct)
+    variants: Vec<VariantDef>,
+    /// Whether this is an enum
+    is_enum: bool,
+}
+
+/// Fields of a struct or enum variant
+#[derive(Debug, Clone)]
+pub(crate) struct VariantDef {
+    /// Variant name
+    name: String,
+    /// Field names and types (positional fields are named `0`, `1`, ...)
+    fields: Vec<(String, Type)>,
+    /// How the variant is constructed
+    shape: VariantShape,
+}
+
+/// Syntactic form of a struct or enum variant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum VariantShape {
+    /// `Name { field: value, .. }`
+    Named,
+    /// `Name(value, ..)`
+    Tuple,
+    /// `Name`
+    Unit,
+}
+
+/// Per-function inference state
+#[derive(Debug, Default)]
+pub(crate) struct FunctionState {
+    /// Declared return type of the function being checked
+    return_ty: Option<Type>,
+    /// Result types of the enclosing loops (innermost last), and whether
+    /// a `break` leaves each
+    loops: Vec<(Type, bool)>,
+    /// Generic parameters in scope
+    generics: Generics,
+    /// Type of `Self` for methods
+    self_ty: Option<Type>,
+}
+
+/// Numeric literal whose type is resolved once inference is complete
+#[derive(Debug, Clone)]
+pub(crate) struct NumericLiteral {
+    /// Type variable assigned to the literal
+    ty: Type,
+    /// Source location of the literal
+    span: Span,
+    /// Whether the literal is a float (otherwise an integer)
+    is_float: bool,
+    /// Value of an integer literal, negated if it is the operand of `-`
+    value: Option<i128>,
+}
+
+/// Match expression whose exhaustiveness is checked once inference is complete
+#[derive(Debug, Clone)]
+pub(crate) struct MatchCheck {
+    /// Type of the scrutinee
+    scrutinee: Type,
+    /// Patterns of the arms without a guard, in order
+    patterns: Vec<ExhaustPattern>,
+    /// Source location of the match expression
+    span: Span,
+}
+
+/// Operand of a unary or binary operator, whose type is checked once
+/// inference is complete
+#[derive(Debug, Clone)]
+pub(crate) struct OperandCheck {
+    /// Type of the operand
+    ty: Type,
+    /// Description of the types the operator applies to
+    expected: &'static str,
+    /// Whether the operator applies to a primitive type
+    accepts: fn(PrimitiveType) -> bool,
+    /// Source location of the operand
+    span: Span,
+}
+
+/// Value interpolated into a string, whose type is checked once inference
+/// is complete
+#[derive(Debug, Clone)]
+pub(crate) struct FormattedValue {
+    /// Type of the value
+    ty: Type,
+    /// Source location of the value
+    span: Span,
+}
+
+/// Type parameters in scope while lowering AST types
+pub(crate) type Generics = HashMap<String, Type>;
+
+impl TypeChecker {
+    /// Register every struct and enum declaration and bind its constructors
+    ///
+    /// All names are registered before any field type is lowered, so
+    /// declarations may refer to each other regardless of order.
+    pub(crate) fn collect_adts(&mut self, arena: &Arena, items: &[ItemId]) {
+        let mut decls = Vec::new();
+        Self::adt_items(arena, items, &mut decls);
+
+        for kind in &decls {
+            let (name, generics, is_enum) = match kind {
+                ItemKind::Struct(decl) => (&decl.name, &decl.generics, false),
+                ItemKind::Enum(decl) => (&decl.name, &decl.generics, true),
+                _ => continue,
+            };
+            let params = generics
+                .iter()
+                .filter_map(|_| match self.ctx.fresh_var() {
+                    Type::Var(var) => Some(var),
+                    _ => None,
+                })
+                .collect();
+            let adt = AdtDef {
+                params,
+                variants: Vec::new(),
+                is_enum,
+            };
+            self.adts.insert(name.clone(), adt);
+        }
+
+        for kind in decls {
+            let (name, generics, variants) = match kind {
+                ItemKind::Struct(decl) => {
+                    (&decl.name, &decl.generics, vec![(&decl.name, &decl.fields)])
+                }
+                ItemKind::Enum(decl) => (
+                    &decl.name,
+                    &decl.generics,
+                    decl.variants.iter().map(|v| (&v.name, &v.fields)).collect(),
+                ),
+                _ => continue,
+            };
+            let params = self.adts[name].params.clone();
+            let args: Vec<Type> = params.iter().map(|&var| Type::Var(var)).collect();
+            let generics: Generics = generics
+                .iter()
+                .map(|param| param.name.clone())
+                .zip(args.iter().cloned())
+                .collect();
+            let self_ty = Type::Named {
+                name: name.clone(),
+                args,
+            };
+
+            let variants: Vec<VariantDef> = variants
+                .into_iter()
+                .map(|(variant, fields)| VariantDef {
+                    name: variant.clone(),
+                    fields: fields
+                        .fields()
+                        .iter()
+                        .map(|field| {
+                            let ty = self.lower_type(arena, field.ty, &generics, Some(&self_ty));
+                            (field.name.clone(), ty)
+                        })
+                        .collect(),
+                    shape: match fields {
+                        VariantFields::Named(_) => VariantShape::Named,
+                        VariantFields::Tuple(_) => VariantShape::Tuple,
+                        VariantFields::Unit => VariantShape::Unit,
+                    },
+                })
+                .collect();
+
+            // Tuple and unit variants can be used as values
+            let is_enum = matches!(kind, ItemKind::Enum(_));
+            for variant in &variants {
+                let ctor_ty = match variant.shape {
+                    VariantShape::Tuple => Type::Function {
+                        params: variant.fields.iter().map(|(_, ty)| ty.clone()).collect(),
+                        ret: Box::new(self_ty.clone()),
+                        effects: EffectSet::PURE,
+                    },
+                    VariantShape::Unit => self_ty.clone(),
+                    VariantShape::Named => continue,
+                };
+                let key = if is_enum {
+                    format!("{}::{}", name, variant.name)
+                } else {
+                    name.clone()
+                };
+                let scheme = if params.is_empty() {
+                    TypeScheme::mono(ctor_ty)
+                } else {
+                    TypeScheme::poly(params.clone(), ctor_ty)
+                };
+                self.env = self.env.extend(key, scheme);
+            }
+
+            if let Some(adt) = self.adts.get_mut(name) {
+                adt.variants = variants;
+            }
+        }
+    }
+
+    /// Gather struct and enum declarations, including those in inline modules
+    fn adt_items<'a>(arena: &'a Arena, items: &[ItemId], out: &mut Vec<&'a ItemKind>) {
+        for &item_id in items {
+            match arena.get_item(item_id).map(|item| &item.kind) {
+                Some(kind @ (ItemKind::Struct(_) | ItemKind::Enum(_))) => out.push(kind),
+                Some(ItemKind::Module(decl)) => {
+                    if let Some(items) = &decl.items {
+                        Self::adt_items(arena, items, out);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Register the signatures of all items so bodies can refer to each other
+    pub(crate) fn collect_signatures(&mut self, arena: &Arena, items: &[ItemId]) {
+        for &item_id in items {
+            let Some(item) = arena.get_item(item_id) else {
+                continue;
+            };
+
+            match &item.kind {
+                ItemKind::Function(func) => {
+                    self.declare_function(arena, func.name.clone(), func, None, &Generics::new());
+                }
+                ItemKind::Const(decl) => {
+                    let ty = self.lower_type(arena, decl.ty, &Generics::new(), None);
+                    self.env = self.env.extend(decl.name.clone(), TypeScheme::mono(ty));
+                }
+                ItemKind::Impl(decl) => {
+                    let generics = self.generic_vars(&decl.generics);
+                    let self_ty = self.lower_type(arena, decl.self_ty, &generics, None);
+                    let Some(prefix) = Self::type_name(&self_ty) else {
+                        continue;
+                    };
+
+                    for impl_item in &decl.items {
+                        if let ImplItem::Function(func) = impl_item {
+                            let key = format!("{}::{}", prefix, func.name);
+                            self.declare_function(arena, key, func, Some(&self_ty), &generics);
+                        }
+                    }
+                }
+                ItemKind::Module(decl) => {
+                    if let Some(items) = &decl.items {
+                        self.collect_signatures(arena, items);
+                    }
+                }
+                ItemKind::Struct(_)
+                | ItemKind::Enum(_)
+                | ItemKind::Type(_)
+                | ItemKind::Trait(_)
+                | ItemKind::Use(_) => {}
+            }
+        }
+    }
+
+    /// Type check the bodies of all items
+    pub(crate) fn check_items(&mut self, arena: &Arena, items: &[ItemId]) {
+        for &item_id in items {
+            let Some(item) = arena.get_item(item_id) else {
+                continue;
+            };
+
+            match &item.kind {
+                ItemKind::Function(func) => self.check_function(arena, &func.name, func),
+                ItemKind::Const(decl) => {
+                    let expected = self.lower_type(arena, decl.ty, &Generics::new(), None);
+                    let mut state = FunctionState::default();
+                    let found = self.infer_expr(arena, decl.value, &mut state);
+                    let span = Self::expr_span(arena, decl.value);
+                    self.coerce(&found, &expected, span);
+                }
+                ItemKind::Impl(decl) => {
+                    let generics = self.generic_vars(&decl.generics);
+                    let self_ty = self.lower_type(arena, decl.self_ty, &generics, None);
+                    let Some(prefix) = Self::type_name(&self_ty) else {
+                        continue;
+                    };
+
+                    for impl_item in &decl.items {
+                        if let ImplItem::Function(func) = impl_item {
+                            let key = format!("{}::{}", prefix, func.name);
+                            self.check_function(arena, &key, func);
+                        }
+                    }
+                }
+                ItemKind::Module(decl) => {
+                    if let Some(items) = &decl.items {
+                        self.check_items(arena, items);
+                    }
+                }
+                ItemKind::Struct(_)
+                | ItemKind::Enum(_)
+                | ItemKind::Type(_)
+                | ItemKind::Trait(_)
+                | ItemKind::Use(_) => {}
+            }
+        }
+    }
+
+    /// Apply the final substitution to every recorded expression type
+    pub(crate) fn finalize_types(&mut self) {
+        for lit in std::mem::take(&mut self.numeric_literals) {
+            let prim = match self.ctx.apply_subst(&lit.ty) {
+                Type::Var(var) if !self.ctx.is_rigid(var) => {
+                    // Unconstrained literal: fall back to i32 / f64
+                    let default = if lit.is_float { PrimitiveType::F64 } else { PrimitiveType::I32 };
+                    let _ = self.ctx.unify(&lit.ty, &Type::Primitive(default));
+                    default
+                }
+                Type::Primitive(prim @ (PrimitiveType::F32 | PrimitiveType::F64)) if lit.is_float => prim,
+                Type::Primitive(prim) if !lit.is_float && Self::is_integer(prim) => prim,
+                other => {
+                    let found = if lit.is_float { "float" } else { "integer" };
+                    self.report(
+                        TypeError::Mismatch {
+                            expected: self.ctx.describe(&other),
+                            found: found.to_string(),
+                        },
+                        lit.span,
+                    );
+                    continue;
+                }
+            };
+
+            if let (Some(value), Some((min, max))) = (lit.value, prim.int_range()) {
+                if value < min || value > max {
+                    self.report(
+                        TypeError::LiteralOutOfRange {
+                            literal: value.to_string(),
+                            ty: prim.to_string(),
+                        },
+                        lit.span,
+                    );
+                }
+            }
+        }
+
+        for check in std::mem::take(&mut self.operand_checks) {
+            match self.ctx.apply_subst(&check.ty) {
+                // Generic parameters and unsolved types
+                Type::Var(_) | Type::Never => {}
+                Type::Primitive(prim) if (check.accepts)(prim) => {}
+                other => {
+                    let error = TypeError::Mismatch {
+                        expected: check.expected.to_string(),
+                        found: self.ctx.describe(&other),
+                    };
+                    self.report(error, check.span);
+                }
+            }
+        }
+
+        for check in std::mem::take(&mut self.match_checks) {
+            let scrutinee = self.ctx.apply_subst(&check.scrutinee);
+            let variants = |name: &str, args: &[Type]| self.adt_variants(name, args);
+            if let Err(ExhaustivenessError::NonExhaustive(witness)) =
+                exhaustive::check_exhaustive_with(&scrutinee, &check.patterns, &variants)
+            {
+                let message = format!("pattern `{}` not covered", witness);
+                self.report(TypeError::NonExhaustive(message), check.span);
+            }
+        }
+
+        for value in std::mem::take(&mut self.formatted_values) {
+            let ty = self.ctx.apply_subst(&value.ty);
+            if !Self::is_formattable(&ty) {
+                self.report(TypeError::NotFormattable(ty.to_string()), value.span);
+            }
+        }
+
+        let ctx = &self.ctx;
+        for ty in self.type_map.expr_types.values_mut() {
+            *ty = ctx.apply_subst(ty);
+        }
+
+        // Record declarations for MIR lowering
+        for (name, sig) in &self.signatures {
+            self.type_map.insert_function(name.clone(), ctx.apply_subst(&sig.ty));
+        }
+        for (name, adt) in &self.adts {
+            if adt.is_enum {
+                let variants = adt
+                    .variants
+                    .iter()
+                    .map(|variant| EnumVariant {
+                        name: variant.name.clone(),
+                        fields: variant.fields.clone(),
+                    })
+                    .collect();
+                let def = EnumDef {
+                    params: adt.params.clone(),
+                    variants,
+                };
+                self.type_map.insert_enum(name.clone(), def);
+                continue;
+            }
+            let Some(variant) = adt.variants.first() else {
+                continue;
+            };
+            let def = StructDef {
+                params: adt.params.clone(),
+                fields: variant.fields.clone(),
+            };
+            self.type_map.insert_struct(name.clone(), def);
+        }
+    }
+
+    /// Build the signature of a function and bind it in the environment
+    ///
+    /// Omitted parameter and return types become fresh type variables that
+    /// are solved while checking the body and the call sites. Only declared
+    /// generic parameters are quantified in the resulting scheme.
+    fn declare_function(
+        &mut self,
+        arena: &Arena,
+        key: String,
+        func: &FunctionDecl,
+        self_ty: Option<&Type>,
+        outer: &Generics,
+    ) {
+        let mut generics = outer.clone();
+        generics.extend(self.generic_vars(&func.generics));
+
+        let params = func
+            .params
+            .iter()
+            .map(|param| match self_ty.and_then(|ty| Self::receiver_type(arena, param, ty)) {
+                Some(ty) => ty,
+                None => self.lower_type(arena, param.ty, &generics, self_ty),
+            })
+            .collect();
+        let ret = match func.return_type {
+            Some(ty) => self.lower_type(arena, ty, &generics, self_ty),
+            None => self.ctx.fresh_var(),
+        };
+
+        let ty = Type::Function {
+            params,
+            ret: Box::new(ret),
+            effects: EffectSet::PURE,
+        };
+
+        let vars: Vec<TypeVarId> = generics
+            .values()
+            .filter_map(|ty| match ty {
+                Type::Var(v) => Some(*v),
+                _ => None,
+            })
+            .collect();
+        let scheme = if vars.is_empty() {
+            TypeScheme::mono(ty.clone())
+        } else {
+            TypeScheme::poly(vars, ty.clone())
+        };
+
+        self.env = self.env.extend(key.clone(), scheme);
+        self.signatures.insert(
+            key,
+            Signature {
+                generics,
+                self_ty: self_ty.cloned(),
+                ty,
+            },
+        );
+    }
+
+    /// Check that the arguments of `print` or `println` are one value, or a
+    /// string literal with a `{}` placeholder for each value after it
+    fn check_print_template(&mut self, arena: &Arena, name: &str, args: &[ExprId], span: Span) {
+        let Some((&template, values)) = args.split_first() else {
+            self.report(TypeError::WrongArgCount { expected: 1, got: 0 }, span);
+            return;
+        };
+        if values.is_empty() {
+            return;
+        }
+        let template_span = Self::expr_span(arena, template);
+        match arena.get_expr(template).map(|e| &e.kind) {
+            Some(ExprKind::Literal(Literal::String(text))) => {
+                let placeholders = text.matches("{}").count();
+                if placeholders != values.len() {
+                    let error = TypeError::FormatArgCount { placeholders, got: values.len() };
+                    self.report(error, template_span);
+                }
+            }
+            _ => self.report(TypeError::FormatNotLiteral(name.to_string()), template_span),
+        }
+    }
+
+    /// Type of an unannotated `self`, `&self` or `&mut self` parameter
+    fn receiver_type(arena: &Arena, param: &Param, self_ty: &Type) -> Option<Type> {
+        if !matches!(arena.get_type_node(param.ty)?.kind, TypeKind::Infer) {
+            return None;
+        }
+
+        match &arena.get_pattern(param.pattern)?.kind {
+            PatternKind::Ident { name, .. } if name == "self" => Some(self_ty.clone()),
+            PatternKind::Ref { inner, is_mut } if Self::is_receiver(arena, **inner) => {
+                Some(Type::Ref {
+                    inner: Box::new(self_ty.clone()),
+                    mutable: *is_mut,
+                    lifetime: None,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether a parameter pattern is `self`, `&self` or `&mut self`
+    fn is_receiver(arena: &Arena, pattern: PatternId) -> bool {
+        match arena.get_pattern(pattern).map(|p| &p.kind) {
+            Some(PatternKind::Ident { name, .. }) => name == "self",
+            Some(PatternKind::Ref { inner, .. }) => Self::is_receiver(arena, **inner),
+            _ => false,
+        }
+    }
+
+    /// Allocate a fresh rigid type variable for each generic parameter
+    ///
+    /// Inside the declaration a parameter is an unknown type that unifies
+    /// only with itself; callers instantiate it with flexible variables.
+    fn generic_vars(&mut self, params: &[GenericParam]) -> Generics {
+        params
+            .iter()
+            .map(|param| (param.name.clone(), self.ctx.fresh_rigid_var(&param.name)))
+            .collect()
+    }
+
+    /// Type check a single function body against its declared signature
+    fn check_function(&mut self, arena: &Arena, key: &str, func: &FunctionDecl) {
+        let Some(sig) = self.signatures.get(key).cloned() else {
+            return;
+        };
+        let Type::Function { params, ret, .. } = sig.ty else {
+            return;
+        };
+
+        let mut state = FunctionState {
+            return_ty: Some((*ret).clone()),
+            loops: Vec::new(),
+            generics: sig.generics,
+            self_ty: sig.self_ty,
+        };
+
+        let saved_env = self.env.clone();
+        for (param, ty) in func.params.iter().zip(&params) {
+            // The receiver binds `self` to the whole parameter type
+            if Self::is_receiver(arena, param.pattern) {
+                self.env = self.env.extend("self".to_string(), TypeScheme::mono(ty.clone()));
+            } else {
+                self.bind_pattern(arena, param.pattern, ty, &state);
+            }
+        }
+
+        let body_ty = self.infer_block(arena, &func.body, &mut state);
+        let span = match func.body.expr {
+            Some(expr) => Self::expr_span(arena, expr),
+            None => func.body.span,
+        };
+        self.coerce(&body_ty, &ret, span);
+
+        self.env = saved_env;
+    }
+
+    /// Convert an AST type annotation into a type
+    fn lower_type(
+        &mut self,
+        arena: &Arena,
+        ty_id: aurora_ast::expr::TypeId,
+        generics: &Generics,
+        self_ty: Option<&Type>,
+    ) -> Type {
+        let Some(node) = arena.get_type_node(ty_id) else {
+            return self.ctx.fresh_var();
+        };
+
+        match &node.kind {
+            TypeKind::Int(int) => Type::Primitive((*int).into()),
+            TypeKind::Uint(uint) => Type::Primitive((*uint).into()),
+            TypeKind::Float(float) => Type::Primitive((*float).into()),
+            TypeKind::Bool => Type::Primitive(PrimitiveType::Bool),
+            TypeKind::Char => Type::Primitive(PrimitiveType::Char),
+            TypeKind::Str => Type::Primitive(PrimitiveType::Str),
+            TypeKind::Path { path } => {
+                let args: Vec<Type> = path
+                    .generics
+                    .iter()
+                    .map(|arg| match arg {
+                        aurora_ast::expr::GenericArg::Type(ty) => {
+                            self.lower_type(arena, *ty, generics, self_ty)
+                        }
+                        aurora_ast::expr::GenericArg::Const(_) => self.ctx.fresh_var(),
+                    })
+                    .collect();
+                let name = path.segments.join("::");
+
+                if let Some(ty) = generics.get(&name) {
+                    return ty.clone();
+                }
+
+                match (name.as_str(), args.as_slice()) {
+                    ("Self", []) => match self_ty {
+                        Some(ty) => ty.clone(),
+                        None => Type::Named { name, args },
+                    },
+                    ("Option", [inner]) => Type::Option(Box::new(inner.clone())),
+                    ("Result", [ok, err]) => Type::Result {
+                        ok: Box::new(ok.clone()),
+                        err: Box::new(err.clone()),
+                    },
+                    (prim, []) => match Self::primitive_from_name(prim) {
+                        Some(p) => Type::Primitive(p),
+                        None => self.named_type(name, args),
+                    },
+                    _ => self.named_type(name, args),
+                }
+            }
+            TypeKind::Tuple(elems) if elems.is_empty() => Type::Unit,
+            TypeKind::Tuple(elems) => Type::Tuple(
+                elems
+                    .iter()
+                    .map(|ty| self.lower_type(arena, *ty, generics, self_ty))
+                    .collect(),
+            ),
+            TypeKind::Array { element, length } => {
+                let size = match arena.get_expr(*length).map(|e| &e.kind) {
+                    Some(ExprKind::Literal(Literal::Int(n, _))) => usize::try_from(*n).ok(),
+                    _ => None,
+                };
+                Type::Array {
+                    elem: Box::new(self.lower_type(arena, **element, generics, self_ty)),
+                    size,
+                }
+            }
+            TypeKind::Slice { element } => Type::Array {
+                elem: Box::new(self.lower_type(arena, **element, generics, self_ty)),
+                size: None,
+            },
+            TypeKind::Reference { inner, is_mut } => Type::Ref {
+                inner: Box::new(self.lower_type(arena, **inner, generics, self_ty)),
+                mutable: *is_mut,
+                lifetime: None,
+            },
+            TypeKind::Pointer { inner, is_mut } => Type::Ptr {
+                inner: Box::new(self.lower_type(arena, **inner, generics, self_ty)),
+                mutable: *is_mut,
+            },
+            TypeKind::Function {
+                params,
+                return_type,
+            } => Type::Function {
+                params: params
+                    .iter()
+                    .map(|ty| self.lower_type(arena, *ty, generics, self_ty))
+                    .collect(),
+                ret: Box::new(match return_type {
+                    Some(ty) => self.lower_type(arena, **ty, generics, self_ty),
+                    None => Type::Unit,
+                }),
+                effects: EffectSet::PURE,
+            },
+            TypeKind::Never => Type::Never,
+            TypeKind::TraitObject { .. } | TypeKind::ImplTrait { .. } | TypeKind::Infer => {
+                self.ctx.fresh_var()
+            }
+        }
+    }
+
+    /// Build a named type, inferring omitted generic arguments of structs and enums
+    fn named_type(&mut self, name: String, args: Vec<Type>) -> Type {
+        let arity = self.adts.get(&name).map_or(0, |adt| adt.params.len());
+        let args = if args.is_empty() {
+            (0..arity).map(|_| self.ctx.fresh_var()).collect()
+        } else {
+            args
+        };
+        Type::Named { name, args }
+    }
+
+    /// Infer the type of a block, restoring the environment afterwards
+    fn infer_block(&mut self, arena: &Arena, block: &Block, state: &mut FunctionState) -> Type {
+        let saved_env = self.env.clone();
+        let mut diverges = false;
+        let mut trailing = None;
+
+        for (index, &stmt_id) in block.stmts.iter().enumerate() {
+            let Some(stmt) = arena.get_stmt(stmt_id) else {
+                continue;
+            };
+
+            match &stmt.kind {
+                StmtKind::Let {
+                    pattern, ty, init, ..
+                } => {
+                    let declared = ty.map(|ty| {
+                        self.lower_type(arena, ty, &state.generics, state.self_ty.as_ref())
+                    });
+                    let init_ty = init.map(|init| {
+                        let found = self.infer_expr(arena, init, state);
+                        if let Some(expected) = &declared {
+                            self.coerce(&found, expected, Self::expr_span(arena, init));
+                        }
+                        found
+                    });
+
+                    let bound = match (declared, init_ty) {
+                        (Some(ty), _) => ty,
+                        (None, Some(ty)) => ty,
+                        (None, None) => self.ctx.fresh_var(),
+                    };
+                    if self.resolves_to_never(&bound) {
+                        diverges = true;
+                    }
+                    self.bind_let(arena, *pattern, &bound, state);
+                }
+                StmtKind::Expr { expr, has_semi } => {
+                    let ty = self.infer_expr(arena, *expr, state);
+                    if self.resolves_to_never(&ty) {
+                        diverges = true;
+                    }
+                    // A final expression without a semicolon is the block's value
+                    if !has_semi && index + 1 == block.stmts.len() {
+                        trailing = Some(ty);
+                    }
+                }
+                StmtKind::Item(_) => {}
+            }
+        }
+
+        let ty = match (block.expr, trailing) {
+            (Some(expr), _) => self.infer_expr(arena, expr, state),
+            (None, Some(ty)) => ty,
+            (None, None) if diverges => Type::Never,
+            (None, None) => Type::Unit,
+        };
+
+        self.env = saved_env;
+        ty
+    }
+
+    /// Infer the type of a block stored in the arena
+    fn infer_block_id(&mut self, arena: &Arena, block_id: BlockId, state: &mut FunctionState) -> Type {
+        match arena.get_block(block_id) {
+            Some(block) => self.infer_block(arena, block, state),
+            None => Type::Unit,
+        }
+    }
+
+    /// Infer the type of an expression and record it in the type map
+    pub(crate) fn infer_expr(&mut self, arena: &Arena, expr_id: ExprId, state: &mut FunctionState) -> Type {
+        let Some(expr) = arena.get_expr(expr_id) else {
+            return self.ctx.fresh_var();
+        };
+        let span = expr.span;
+
+        let ty = match &expr.kind {
+            ExprKind::Literal(lit) => self.infer_literal_expr(lit, span),
+
+            ExprKind::Interpolated(parts) => {
+                for part in parts {
+                    if let InterpolationPart::Expr(value) = part {
+                        let ty = self.infer_expr(arena, *value, state);
+                        let span = Self::expr_span(arena, *value);
+                        self.formatted_values.push(FormattedValue { ty, span });
+                    }
+                }
+                Type::Primitive(PrimitiveType::Str)
+            }
+
+            ExprKind::Ident(name) => match self.env.lookup(name) {
+                Some(scheme) => {
+                    let scheme = scheme.clone();
+                    self.ctx.instantiate(&scheme)
+                }
+                None => {
+                    self.report(TypeError::UndefinedVariable(name.clone()), span);
+                    self.ctx.fresh_var()
+                }
+            },
+
+            ExprKind::Path(path) => {
+                let mut segments = path.segments.clone();
+                if segments[0] == "Self" {
+                    if let Some(name) = state.self_ty.as_ref().and_then(Self::type_name) {
+                        segments[0] = name;
+                    }
+                }
+                let name = segments.join("::");
+                match self.env.lookup(&name) {
+                    Some(scheme) => {
+                        let scheme = scheme.clone();
+                        self.ctx.instantiate(&scheme)
+                    }
+                    // Paths into enums and modules are resolved by later phases
+                    None => self.ctx.fresh_var(),
+                }
+            }
+
+            ExprKind::Unary { op, operand } => {
+                let operand_ty = self.infer_expr(arena, *operand, state);
+                if matches!(op, UnaryOp::Neg) {
+                    self.negate_literal(arena, *operand);
+                }
+                let operand_span = Self::expr_span(arena, *operand);
+                match op {
+                    UnaryOp::Neg => {
+                        self.check_operand(&operand_ty, "numeric type", Self::is_numeric, operand_span);
+                        operand_ty
+                    }
+                    UnaryOp::Not => {
+                        self.check_operand(&operand_ty, "bool or integer", Self::is_logical, operand_span);
+                        operand_ty
+                    }
+                    UnaryOp::BitNot => {
+                        self.check_operand(&operand_ty, "integer", Self::is_integer, operand_span);
+                        operand_ty
+                    }
+                    UnaryOp::Ref | UnaryOp::RefMut => Type::Ref {
+                        inner: Box::new(operand_ty),
+                        mutable: matches!(op, UnaryOp::RefMut),
+                        lifetime: None,
+                    },
+                    UnaryOp::Deref => match self.ctx.apply_subst(&operand_ty) {
+                        Type::Ref { inner, .. } | Type::Ptr { inner, .. } => *inner,
+                        _ => self.ctx.fresh_var(),
+                    },
+                }
+            }
+
+            ExprKind::Binary { op, left, right } => {
+                let left_ty = self.infer_expr(arena, *left, state);
+                let right_ty = self.infer_expr(arena, *right, state);
+                let spans = (Self::expr_span(arena, *left), Self::expr_span(arena, *right));
+                self.infer_binary(*op, &left_ty, &right_ty, spans)
+            }
+
+            ExprKind::Call { func, args } => {
+                let callee = arena.get_expr(*func).map(|e| &e.kind);
+                let print = match callee {
+                    Some(ExprKind::Ident(name)) if PRINT_BUILTINS.contains(&name.as_str()) => Some(name),
+                    _ => None,
+                };
+                let func_ty = self.infer_expr(arena, *func, state);
+                let arg_tys: Vec<Type> = args
+                    .iter()
+                    .map(|arg| self.infer_expr(arena, *arg, state))
+                    .collect();
+
+                if let Some(name) = print {
+                    self.check_print_template(arena, name, args, span);
+                    for (arg, ty) in args.iter().zip(arg_tys) {
+                        let span = Self::expr_span(arena, *arg);
+                        self.formatted_values.push(FormattedValue { ty, span });
+                    }
+                    match self.ctx.apply_subst(&func_ty) {
+                        Type::Function { ret, .. } => *ret,
+                        _ => Type::Unit,
+                    }
+                } else {
+                    let arg_spans = args.iter().map(|arg| Self::expr_span(arena, *arg)).collect();
+                    self.infer_call(&func_ty, &arg_tys, arg_spans, span)
+                }
+            }
+
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } => {
+                let receiver_ty = self.infer_expr(arena, *receiver, state);
+                let arg_tys: Vec<Type> = args
+                    .iter()
+                    .map(|arg| self.infer_expr(arena, *arg, state))
+                    .collect();
+
+                let method_ty = Self::type_name(&self.ctx.apply_subst(&receiver_ty))
+                    .map(|prefix| format!("{}::{}", prefix, method))
+                    .and_then(|name| self.env.lookup(&name).cloned())
+                    .map(|scheme| self.ctx.instantiate(&scheme));
+
+                match method_ty {
+                    Some(Type::Function { params, ret, effects }) if !params.is_empty() => {
+                        // The receiver is passed as the first argument
+                        let method_ty = Type::Function {
+                            params: params[1..].to_vec(),
+                            ret,
+                            effects,
+                        };
+                        let arg_spans = args.iter().map(|arg| Self::expr_span(arena, *arg)).collect();
+                        self.infer_call(&method_ty, &arg_tys, arg_spans, span)
+                    }
+                    _ => self.ctx.fresh_var(),
+                }
+            }
+
+            ExprKind::Field { object, field } => {
+                let object_ty = self.infer_expr(arena, *object, state);
+                match (self.peel_refs(&object_ty), field.parse::<usize>()) {
+                    (Type::Tuple(elems), Ok(index)) if index < elems.len() => elems[index].clone(),
+                    (ty @ Type::Named { .. }, _) => match self.field_type(&ty, field) {
+                        Some(Some(field_ty)) => field_ty,
+                        Some(None) => {
+                            let error = TypeError::UnknownField {
+                                ty: ty.to_string(),
+                                field: field.clone(),
+                            };
+                            self.report(error, span);
+                            self.ctx.fresh_var()
+                        }
+                        None => self.ctx.fresh_var(),
+                    },
+                    _ => self.ctx.fresh_var(),
+                }
+            }
+
+            ExprKind::Index { collection, index } => {
+                let collection_ty = self.infer_expr(arena, *collection, state);
+                self.infer_expr(arena, *index, state);
+                match self.ctx.apply_subst(&collection_ty) {
+                    Type::Array { elem, .. } => *elem,
+                    _ => self.ctx.fresh_var(),
+                }
+            }
+
+            ExprKind::Pipeline { left, right } => {
+                let arg_ty = self.infer_expr(arena, *left, state);
+                let func_ty = self.infer_expr(arena, *right, state);
+                self.infer_call(&func_ty, &[arg_ty], vec![Self::expr_span(arena, *left)], span)
+            }
+
+            ExprKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                let cond_ty = self.infer_expr(arena, *condition, state);
+                self.coerce(
+                    &cond_ty,
+                    &Type::Primitive(PrimitiveType::Bool),
+                    Self::expr_span(arena, *condition),
+                );
+
+                let then_ty = self.infer_block_id(arena, *then_block, state);
+                match else_block {
+                    Some(else_block) => {
+                        let else_ty = self.infer_block_id(arena, *else_block, state);
+                        let else_span = Self::block_span(arena, *else_block, span);
+                        self.join(&then_ty, &else_ty, else_span)
+                    }
+                    None => {
+                        let then_span = Self::block_span(arena, *then_block, span);
+                        self.coerce(&then_ty, &Type::Unit, then_span);
+                        Type::Unit
+                    }
+                }
+            }
+
+            ExprKind::Match { scrutinee, arms } => {
+                let scrutinee_ty = self.infer_expr(arena, *scrutinee, state);
+                let mut result = Type::Never;
+
+                for arm in arms {
+                    let saved_env = self.env.clone();
+                    self.bind_pattern(arena, arm.pattern, &scrutinee_ty, state);
+
+                    if let Some(guard) = arm.guard {
+                        let guard_ty = self.infer_expr(arena, guard, state);
+                        self.coerce(
+                            &guard_ty,
+                            &Type::Primitive(PrimitiveType::Bool),
+                            Self::expr_span(arena, guard),
+                        );
+                    }
+
+                    let body_ty = self.infer_expr(arena, arm.body, state);
+                    result = self.join(&result, &body_ty, Self::expr_span(arena, arm.body));
+                    self.env = saved_env;
+                }
+
+                // Guarded arms may fail, so only unguarded ones count as covering
+                let patterns = arms
+                    .iter()
+                    .filter(|arm| arm.guard.is_none())
+                    .map(|arm| self.exhaustive_pattern(arena, arm.pattern, state))
+                    .collect();
+                self.match_checks.push(MatchCheck {
+                    scrutinee: scrutinee_ty,
+                    patterns,
+                    span,
+                });
+
+                result
+            }
+
+            ExprKind::Loop { body } => {
+                let result = self.ctx.fresh_var();
+                state.loops.push((result.clone(), false));
+                self.infer_block_id(arena, *body, state);
+
+                // A loop without a `break` never produces a value
+                match state.loops.pop() {
+                    Some((_, true)) => result,
+                    _ => Type::Never,
+                }
+            }
+
+            ExprKind::While { condition, body } => {
+                let cond_ty = self.infer_expr(arena, *condition, state);
+                self.coerce(
+                    &cond_ty,
+                    &Type::Primitive(PrimitiveType::Bool),
+                    Self::expr_span(arena, *condition),
+                );
+
+                state.loops.push((Type::Unit, false));
+                self.infer_block_id(arena, *body, state);
+                state.loops.pop();
+                Type::Unit
+            }
+
+            ExprKind::For {
+                pattern,
+                iterator,
+                body,
+            } => {
+                let iter_ty = self.infer_expr(arena, *iterator, state);
+                let elem_ty = match self.ctx.apply_subst(&iter_ty) {
+                    Type::Array { elem, .. } => *elem,
+                    Type::Named { name, args } if name == "Range" && args.len() == 1 => {
+                        args[0].clone()
+                    }
+                    _ => self.ctx.fresh_var(),
+                };
+
+                let saved_env = self.env.clone();
+                self.bind_pattern(arena, *pattern, &elem_ty, state);
+                state.loops.push((Type::Unit, false));
+                self.infer_block_id(arena, *body, state);
+                state.loops.pop();
+                self.env = saved_env;
+                Type::Unit
+            }
+
+            ExprKind::Return { value } => {
+                let (value_ty, value_span) = match value {
+                    Some(value) => (
+                        self.infer_expr(arena, *value, state),
+                        Self::expr_span(arena, *value),
+                    ),
+                    None => (Type::Unit, span),
+                };
+                if let Some(ret) = state.return_ty.clone() {
+                    self.coerce(&value_ty, &ret, value_span);
+                }
+                Type::Never
+            }
+
+            ExprKind::Break { value } => {
+                let (value_ty, value_span) = match value {
+                    Some(value) => (
+                        self.infer_expr(arena, *value, state),
+                        Self::expr_span(arena, *value),
+                    ),
+                    None => (Type::Unit, span),
+                };
+                if let Some((loop_ty, breaks)) = state.loops.last_mut() {
+                    *breaks = true;
+                    let loop_ty = loop_ty.clone();
+                    self.coerce(&value_ty, &loop_ty, value_span);
+                }
+                Type::Never
+            }
+
+            ExprKind::Continue => Type::Never,
+
+            ExprKind::Yield { value } => {
+                self.infer_expr(arena, *value, state);
+                Type::Unit
+            }
+
+            ExprKind::Block(block) | ExprKind::Unsafe { block } => {
+                self.infer_block_id(arena, *block, state)
+            }
+
+            ExprKind::Tuple(elems) if elems.is_empty() => Type::Unit,
+            ExprKind::Tuple(elems) => Type::Tuple(
+                elems
+                    .iter()
+                    .map(|elem| self.infer_expr(arena, *elem, state))
+                    .collect(),
+            ),
+
+            ExprKind::Array(elems) => {
+                let elem_ty = self.ctx.fresh_var();
+                for &elem in elems {
+                    let ty = self.infer_expr(arena, elem, state);
+                    self.coerce(&ty, &elem_ty, Self::expr_span(arena, elem));
+                }
+                Type::Array {
+                    elem: Box::new(elem_ty),
+                    size: Some(elems.len()),
+                }
+            }
+
+            ExprKind::Struct { path, fields } => {
+                let variant = self.instantiate_variant(&path.segments, state.self_ty.as_ref());
+                let field_tys: Vec<Type> = fields
+                    .iter()
+                    .map(|field| self.infer_expr(arena, field.value, state))
+                    .collect();
+
+                match variant {
+                    Some((ty, variant)) => {
+                        for (field, found) in fields.iter().zip(&field_tys) {
+                            match variant.fields.iter().find(|(name, _)| *name == field.name) {
+                                Some((_, expected)) => {
+                                    self.coerce(found, expected, Self::expr_span(arena, field.value));
+                                }
+                                None => {
+                                    let error = TypeError::UnknownField {
+                                        ty: ty.to_string(),
+                                        field: field.name.clone(),
+                                    };
+                                    self.report(error, field.span);
+                                }
+                            }
+                        }
+
+                        let missing: Vec<String> = variant
+                            .fields
+                            .iter()
+                            .map(|(name, _)| name.clone())
+                            .filter(|name| !fields.iter().any(|field| field.name == *name))
+                            .collect();
+                        if !missing.is_empty() {
+                            let error = TypeError::MissingFields {
+                                ty: ty.to_string(),
+                                fields: missing,
+                            };
+                            self.report(error, span);
+                        }
+                        ty
+                    }
+                    None => {
+                        self.report(TypeError::UndefinedType(path.segments.join("::")), span);
+                        self.ctx.fresh_var()
+                    }
+                }
+            }
+
+            ExprKind::Range { start, end, .. } => {
+                let elem_ty = self.ctx.fresh_var();
+                for bound in [start, end].into_iter().flatten() {
+                    let ty = self.infer_expr(arena, *bound, state);
+                    self.coerce(&ty, &elem_ty, Self::expr_span(arena, *bound));
+                }
+                Type::Named {
+                    name: "Range".to_string(),
+                    args: vec![elem_ty],
+                }
+            }
+
+            ExprKind::Try { expr } => {
+                let inner_ty = self.infer_expr(arena, *expr, state);
+                match self.ctx.apply_subst(&inner_ty) {
+                    Type::Option(inner) => *inner,
+                    Type::Result { ok, .. } => *ok,
+                    _ => self.ctx.fresh_var(),
+                }
+            }
+
+            ExprKind::Await { expr } | ExprKind::Comptime { expr } => {
+                self.infer_expr(arena, *expr, state)
+            }
+        };
+
+        self.type_map.insert_expr(expr_id, ty.clone());
+        ty
+    }
+
+    /// Infer the type of a literal expression
+    ///
+    /// Numeric literals without a suffix get a fresh variable so they can
+    /// take on any integer or float type; unconstrained ones are defaulted,
+    /// and every integer literal is range checked, in `finalize_types`.
+    fn infer_literal_expr(&mut self, lit: &Literal, span: Span) -> Type {
+        let (ty, value) = match lit {
+            Literal::Int(value, None) => (self.ctx.fresh_var(), Some(*value)),
+            Literal::Int(value, Some(_)) => (self.infer_literal(lit), Some(*value)),
+            Literal::Float(_, None) => (self.ctx.fresh_var(), None),
+            Literal::Float(_, Some(_)) => (self.infer_literal(lit), None),
+            _ => return self.infer_literal(lit),
+        };
+        self.numeric_literals.push(NumericLiteral {
+            ty: ty.clone(),
+            span,
+            is_float: matches!(lit, Literal::Float(..)),
+            value,
+        });
+        ty
+    }
+
+    /// Negate the value recorded for `expr` if it is an integer literal,
+    /// so that `-128i8` is in range and `-1u8` is not
+    fn negate_literal(&mut self, arena: &Arena, expr: ExprId) {
+        if let Some(ExprKind::Literal(Literal::Int(..))) = arena.get_expr(expr).map(|e| &e.kind) {
+            if let Some(lit) = self.numeric_literals.last_mut() {
+                lit.value = lit.value.map(|value| -value);
+            }
+        }
+    }
+
+    /// Infer the result type of a binary operator
+    ///
+    /// `spans` are the spans of the left and right operands.
+    fn infer_binary(&mut self, op: BinaryOp, left: &Type, right: &Type, spans: (Span, Span)) -> Type {
+        let (left_span, span) = spans;
+        match op {
+            BinaryOp::And | BinaryOp::Or => {
+                let bool_ty = Type::Primitive(PrimitiveType::Bool);
+                self.coerce(left, &bool_ty, left_span);
+                self.coerce(right, &bool_ty, span);
+                bool_ty
+            }
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                self.coerce(right, left, span);
+                Type::Primitive(PrimitiveType::Bool)
+            }
+            BinaryOp::Shl | BinaryOp::Shr | BinaryOp::ShlAssign | BinaryOp::ShrAssign => {
+                self.check_operand(left, "integer", Self::is_integer, left_span);
+                self.check_operand(right, "integer", Self::is_integer, span);
+                if matches!(op, BinaryOp::Shl | BinaryOp::Shr) {
+                    left.clone()
+                } else {
+                    Type::Unit
+                }
+            }
+            BinaryOp::Assign => {
+                self.coerce(right, left, span);
+                Type::Unit
+            }
+            BinaryOp::Add
+            | BinaryOp::Sub
+            | BinaryOp::Mul
+            | BinaryOp::Div
+            | BinaryOp::Rem
+            | BinaryOp::Pow
+            | BinaryOp::BitAnd
+            | BinaryOp::BitOr
+            | BinaryOp::BitXor
+            | BinaryOp::AddAssign
+            | BinaryOp::SubAssign
+            | BinaryOp::MulAssign
+            | BinaryOp::DivAssign
+            | BinaryOp::RemAssign
+            | BinaryOp::BitAndAssign
+            | BinaryOp::BitOrAssign
+            | BinaryOp::BitXorAssign => {
+                self.coerce(right, left, span);
+                let bitwise = matches!(
+                    op,
+                    BinaryOp::BitAnd
+                        | BinaryOp::BitOr
+                        | BinaryOp::BitXor
+                        | BinaryOp::BitAndAssign
+                        | BinaryOp::BitOrAssign
+                        | BinaryOp::BitXorAssign
+                );
+                if bitwise {
+                    self.check_operand(left, "bool or integer", Self::is_logical, left_span);
+                } else {
+                    self.check_operand(left, "numeric type", Self::is_numeric, left_span);
+                }
+                let compound = matches!(
+                    op,
+                    BinaryOp::AddAssign
+                        | BinaryOp::SubAssign
+                        | BinaryOp::MulAssign
+                        | BinaryOp::DivAssign
+                        | BinaryOp::RemAssign
+                        | BinaryOp::BitAndAssign
+                        | BinaryOp::BitOrAssign
+                        | BinaryOp::BitXorAssign
+                );
+                if compound {
+                    Type::Unit
+                } else {
+                    left.clone()
+                }
+            }
+        }
+    }
+
+    /// Check, once numeric literals have their types, that the operator
+    /// applies to an operand
+    fn check_operand(&mut self, ty: &Type, expected: &'static str, accepts: fn(PrimitiveType) -> bool, span: Span) {
+        self.operand_checks.push(OperandCheck {
+            ty: ty.clone(),
+            expected,
+            accepts,
+            span,
+        });
+    }
+
+    /// Check a call against the callee type and return the result type
+    fn infer_call(&mut self, func_ty: &Type, args: &[Type], arg_spans: Vec<Span>, span: Span) -> Type {
+        match self.ctx.apply_subst(func_ty) {
+            Type::Function { params, ret, .. } => {
+                if params.len() != args.len() {
+                    self.report(
+                        TypeError::WrongArgCount {
+                            expected: params.len(),
+                            got: args.len(),
+                        },
+                        span,
+                    );
+                }
+                for ((arg, param), arg_span) in args.iter().zip(&params).zip(arg_spans) {
+                    self.coerce(arg, param, arg_span);
+                }
+                *ret
+            }
+            ty @ Type::Var(_) => {
+                let ret = self.ctx.fresh_var();
+                let expected = Type::Function {
+                    params: args.to_vec(),
+                    ret: Box::new(ret.clone()),
+                    effects: EffectSet::PURE,
+                };
+                self.coerce(&ty, &expected, span);
+                ret
+            }
+            other => {
+                self.report(
+                    TypeError::Mismatch {
+                        expected: "function".to_string(),
+                        found: other.to_string(),
+                    },
+                    span,
+                );
+                self.ctx.fresh_var()
+            }
+        }
+    }
+
+    /// Bind a `let` pattern, generalizing function values
+    ///
+    /// Only function-typed, immutable bindings are generalized (a value
+    /// restriction), since other bindings may be reassigned later.
+    fn bind_let(&mut self, arena: &Arena, pattern: PatternId, ty: &Type, state: &FunctionState) {
+        let resolved = self.ctx.apply_subst(ty);
+        if let Some(PatternKind::Ident { name, is_mut: false }) =
+            arena.get_pattern(pattern).map(|p| &p.kind)
+        {
+            if resolved.is_function() {
+                let env = self.env.apply(self.ctx.substitution());
+                let scheme = self.ctx.generalize(&env, &resolved);
+                self.env = self.env.extend(name.clone(), scheme);
+                return;
+            }
+        }
+        self.bind_pattern(arena, pattern, &resolved, state);
+    }
+
+    /// Bind the variables of a pattern matched against a value of type `ty`
+    fn bind_pattern(&mut self, arena: &Arena, pattern: PatternId, ty: &Type, state: &FunctionState) {
+        let Some(pat) = arena.get_pattern(pattern) else {
+            return;
+        };
+        let span = pat.span;
+
+        match &pat.kind {
+            PatternKind::Wildcard | PatternKind::Rest => {}
+            PatternKind::Ident { name, .. } => {
+                self.env = self.env.extend(name.clone(), TypeScheme::mono(ty.clone()));
+            }
+            PatternKind::Literal(lit) => {
+                let lit_ty = self.infer_literal_expr(lit, span);
+                self.coerce(&lit_ty, ty, span);
+            }
+            PatternKind::Range { start, end, .. } => {
+                self.bind_pattern(arena, **start, ty, state);
+                self.bind_pattern(arena, **end, ty, state);
+            }
+            PatternKind::Tuple(elems) => {
+                let elem_tys = match self.ctx.apply_subst(ty) {
+                    Type::Tuple(tys) if tys.len() == elems.len() => tys,
+                    _ => {
+                        let tys: Vec<Type> = elems.iter().map(|_| self.ctx.fresh_var()).collect();
+                        self.coerce(&Type::Tuple(tys.clone()), ty, span);
+                        tys
+                    }
+                };
+                for (&elem, elem_ty) in elems.iter().zip(&elem_tys) {
+                    self.bind_pattern(arena, elem, elem_ty, state);
+                }
+            }
+            PatternKind::TupleStruct { path, fields } => {
+                let field_tys = self.constructor_fields(&path.segments, ty, fields.len(), span, state);
+                for (&field, field_ty) in fields.iter().zip(&field_tys) {
+                    self.bind_pattern(arena, field, field_ty, state);
+                }
+            }
+            PatternKind::Path(path) => {
+                self.constructor_fields(&path.segments, ty, 0, span, state);
+            }
+            PatternKind::Struct {
+                path,
+                fields,
+                has_rest,
+            } => {
+                let declared = match self.instantiate_variant(&path.segments, state.self_ty.as_ref()) {
+                    Some((adt_ty, variant)) => {
+                        let scrutinee = self.peel_refs(ty);
+                        self.coerce(&adt_ty, &scrutinee, span);
+                        Some((adt_ty, variant.fields))
+                    }
+                    None => {
+                        self.report(TypeError::UndefinedType(path.segments.join("::")), span);
+                        None
+                    }
+                };
+
+                for field in fields {
+                    let field_ty = match &declared {
+                        Some((adt_ty, decl_fields)) => {
+                            match decl_fields.iter().find(|(name, _)| *name == field.name) {
+                                Some((_, ty)) => ty.clone(),
+                                None => {
+                                    let error = TypeError::UnknownField {
+                                        ty: adt_ty.to_string(),
+                                        field: field.name.clone(),
+                                    };
+                                    self.report(error, field.span);
+                                    self.ctx.fresh_var()
+                                }
+                            }
+                        }
+                        None => self.ctx.fresh_var(),
+                    };
+                    match field.pattern {
+                        Some(sub) => self.bind_pattern(arena, sub, &field_ty, state),
+                        None => {
+                            self.env = self
+                                .env
+                                .extend(field.name.clone(), TypeScheme::mono(field_ty));
+                        }
+                    }
+                }
+
+                if let (Some((adt_ty, decl_fields)), false) = (&declared, has_rest) {
+                    let missing: Vec<String> = decl_fields
+                        .iter()
+                        .map(|(name, _)| name.clone())
+                        .filter(|name| !fields.iter().any(|field| field.name == *name))
+                        .collect();
+                    if !missing.is_empty() {
+                        let error = TypeError::MissingFields {
+                            ty: adt_ty.to_string(),
+                            fields: missing,
+                        };
+                        self.report(error, span);
+                    }
+                }
+            }
+            PatternKind::Or(alts) => {
+                for &alt in alts {
+                    self.bind_pattern(arena, alt, ty, state);
+                }
+            }
+            PatternKind::Ref { inner, is_mut } => {
+                let inner_ty = match self.ctx.apply_subst(ty) {
+                    Type::Ref { inner, .. } => *inner,
+                    _ => {
+                        let inner_ty = self.ctx.fresh_var();
+                        let ref_ty = Type::Ref {
+                            inner: Box::new(inner_ty.clone()),
+                            mutable: *is_mut,
+                            lifetime: None,
+                        };
+                        self.coerce(&ref_ty, ty, span);
+                        inner_ty
+                    }
+                };
+                self.bind_pattern(arena, **inner, &inner_ty, state);
+            }
+        }
+    }
+
+    /// Convert an AST pattern to the form used by the exhaustiveness checker
+    ///
+    /// Bindings become wildcards, struct patterns list every declared field
+    /// in order and reference patterns match their inner pattern.
+    fn exhaustive_pattern(&self, arena: &Arena, pattern: PatternId, state: &FunctionState) -> ExhaustPattern {
+        let Some(pat) = arena.get_pattern(pattern) else {
+            return ExhaustPattern::Wildcard;
+        };
+
+        match &pat.kind {
+            PatternKind::Wildcard | PatternKind::Rest | PatternKind::Ident { .. } => ExhaustPattern::Wildcard,
+            PatternKind::Literal(lit) => ExhaustPattern::Literal(Self::exhaustive_literal(lit)),
+            PatternKind::Range { start, end, inclusive } => {
+                let bound = |id: PatternId| match arena.get_pattern(id).map(|pat| &pat.kind) {
+                    Some(PatternKind::Literal(Literal::Int(n, _))) => Some(*n),
+                    Some(PatternKind::Literal(Literal::Char(c))) => Some(*c as i128),
+                    _ => None,
+                };
+                match (bound(**start), bound(**end)) {
+                    (Some(start), Some(end)) => ExhaustPattern::Range {
+                        start,
+                        end: if *inclusive { end } else { end - 1 },
+                    },
+                    // Ranges over other types cover no value the checker can name
+                    _ => ExhaustPattern::Range { start: 1, end: 0 },
+                }
+            }
+            PatternKind::Tuple(elems) => ExhaustPattern::Tuple(
+                elems
+                    .iter()
+                    .map(|&elem| self.exhaustive_pattern(arena, elem, state))
+                    .collect(),
+            ),
+            PatternKind::TupleStruct { path, fields } => ExhaustPattern::Constructor {
+                name: Self::variant_name(&path.segments, state),
+                fields: fields
+                    .iter()
+                    .map(|&field| self.exhaustive_pattern(arena, field, state))
+                    .collect(),
+            },
+            PatternKind::Path(path) => ExhaustPattern::Constructor {
+                name: Self::variant_name(&path.segments, state),
+                fields: Vec::new(),
+            },
+            PatternKind::Struct { path, fields, .. } => {
+                let declared = self.variant_field_names(&path.segments, state).unwrap_or_default();
+                let fields = declared
+                    .iter()
+                    .map(|name| match fields.iter().find(|field| field.name == *name) {
+                        Some(field) => match field.pattern {
+                            Some(sub) => self.exhaustive_pattern(arena, sub, state),
+                            None => ExhaustPattern::Wildcard,
+                        },
+                        None => ExhaustPattern::Wildcard,
+                    })
+                    .collect();
+                ExhaustPattern::Constructor {
+                    name: Self::variant_name(&path.segments, state),
+                    fields,
+                }
+            }
+            PatternKind::Or(alts) => ExhaustPattern::Or(
+                alts.iter()
+                    .map(|&alt| self.exhaustive_pattern(arena, alt, state))
+                    .collect(),
+            ),
+            PatternKind::Ref { inner, .. } => self.exhaustive_pattern(arena, **inner, state),
+        }
+    }
+
+    /// Literal of a pattern in the form used by the exhaustiveness checker
+    fn exhaustive_literal(lit: &Literal) -> exhaustive::Literal {
+        match lit {
+            Literal::Int(n, _) => exhaustive::Literal::Int(*n),
+            Literal::Char(c) => exhaustive::Literal::Int(*c as i128),
+            Literal::Bool(b) => exhaustive::Literal::Bool(*b),
+            Literal::Float(f, _) => exhaustive::Literal::Float(f.to_bits()),
+            Literal::String(text) => exhaustive::Literal::Str(text.clone()),
+        }
+    }
+
+    /// Constructor named by a pattern path (`Color::Red` is `Red`, `Self` the impl type)
+    fn variant_name(segments: &[String], state: &FunctionState) -> String {
+        match segments {
+            [name] if name == "Self" => state
+                .self_ty
+                .as_ref()
+                .and_then(Self::type_name)
+                .unwrap_or_else(|| name.clone()),
+            _ => segments.last().cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Declared field names of the struct or variant a pattern path names
+    fn variant_field_names(&self, segments: &[String], state: &FunctionState) -> Option<Vec<String>> {
+        let resolve = |name: &String| match name.as_str() {
+            "Self" => state.self_ty.as_ref().and_then(Self::type_name),
+            _ => Some(name.clone()),
+        };
+        let (type_name, variant) = match segments {
+            [name] => (resolve(name)?, None),
+            [init @ .., last] => (resolve(&init.join("::"))?, Some(last)),
+            [] => return None,
+        };
+
+        let adt = self.adts.get(&type_name)?;
+        let variant = match variant {
+            Some(name) => adt.variants.iter().find(|v| v.name == *name)?,
+            None => adt.variants.first()?,
+        };
+        Some(variant.fields.iter().map(|(name, _)| name.clone()).collect())
+    }
+
+    /// Variants of a struct or enum with its generic arguments substituted
+    fn adt_variants(&self, name: &str, args: &[Type]) -> Option<Variants> {
+        let adt = self.adts.get(name)?;
+        let subst: HashMap<TypeVarId, Type> = adt.params.iter().copied().zip(args.iter().cloned()).collect();
+
+        Some(
+            adt.variants
+                .iter()
+                .map(|variant| {
+                    let fields = variant.fields.iter().map(|(_, ty)| ty.substitute(&subst)).collect();
+                    (variant.name.clone(), fields)
+                })
+                .collect(),
+        )
+    }
+
+    /// Field types of a constructor pattern matched against `ty`
+    ///
+    /// Constructor patterns see through references to the scrutinee, so
+    /// `match self { Color::Red => .. }` works when `self` is `&Color`.
+    fn constructor_fields(
+        &mut self,
+        segments: &[String],
+        ty: &Type,
+        arity: usize,
+        span: Span,
+        state: &FunctionState,
+    ) -> Vec<Type> {
+        let scrutinee = self.peel_refs(ty);
+
+        if let Some((adt_ty, variant)) = self.instantiate_variant(segments, state.self_ty.as_ref()) {
+            self.coerce(&adt_ty, &scrutinee, span);
+            if variant.fields.len() != arity {
+                let error = TypeError::WrongArgCount {
+                    expected: variant.fields.len(),
+                    got: arity,
+                };
+                self.report(error, span);
+            }
+            return variant.fields.into_iter().map(|(_, ty)| ty).collect();
+        }
+
+        let name = segments.last().map(String::as_str).unwrap_or_default();
+        match name {
+            "Some" | "None" => {
+                let inner = self.ctx.fresh_var();
+                self.coerce(&Type::Option(Box::new(inner.clone())), &scrutinee, span);
+                if name == "Some" { vec![inner] } else { Vec::new() }
+            }
+            "Ok" | "Err" => {
+                let ok = self.ctx.fresh_var();
+                let err = self.ctx.fresh_var();
+                let result_ty = Type::Result {
+                    ok: Box::new(ok.clone()),
+                    err: Box::new(err.clone()),
+                };
+                self.coerce(&result_ty, &scrutinee, span);
+                if name == "Ok" { vec![ok] } else { vec![err] }
+            }
+            _ => {
+                self.report(TypeError::UndefinedType(segments.join("::")), span);
+                (0..arity).map(|_| self.ctx.fresh_var()).collect()
+            }
+        }
+    }
+
+    /// Resolve a struct or `Enum::Variant` path to its fields
+    ///
+    /// Returns the type being constructed, with fresh variables for its
+    /// generic parameters (or the impl's own arguments for `Self`), and the
+    /// variant with its field types instantiated accordingly.
+    fn instantiate_variant(&mut self, segments: &[String], self_ty: Option<&Type>) -> Option<(Type, VariantDef)> {
+        let (type_path, variant_name) = match segments {
+            [] => return None,
+            [name] => (name.clone(), None),
+            [init @ .., last] => (init.join("::"), Some(last)),
+        };
+
+        let (type_name, args) = if type_path == "Self" {
+            match self.ctx.apply_subst(self_ty?) {
+                Type::Named { name, args } => (name, Some(args)),
+                _ => return None,
+            }
+        } else {
+            (type_path, None)
+        };
+
+        let adt = self.adts.get(&type_name)?;
+        let variant = match variant_name {
+            None if !adt.is_enum => adt.variants.first(),
+            Some(name) if adt.is_enum => adt.variants.iter().find(|v| v.name == *name),
+            _ => None,
+        }?
+        .clone();
+        let params = adt.params.clone();
+
+        let args = args.unwrap_or_else(|| params.iter().map(|_| self.ctx.fresh_var()).collect());
+        let subst: HashMap<TypeVarId, Type> = params.into_iter().zip(args.iter().cloned()).collect();
+        let fields = variant
+            .fields
+            .iter()
+            .map(|(name, ty)| (name.clone(), ty.substitute(&subst)))
+            .collect();
+
+        let ty = Type::Named {
+            name: type_name,
+            args,
+        };
+        Some((ty, VariantDef { fields, ..variant }))
+    }
+
+    /// Type of a struct field, or `Some(None)` if the struct has no such field
+    ///
+    /// Returns `None` when `ty` is not a known struct.
+    fn field_type(&self, ty: &Type, field: &str) -> Option<Option<Type>> {
+        let Type::Named { name, args } = ty else {
+            return None;
+        };
+        let adt = self.adts.get(name).filter(|adt| !adt.is_enum)?;
+        let subst: HashMap<TypeVarId, Type> =
+            adt.params.iter().copied().zip(args.iter().cloned()).collect();
+
+        Some(
+            adt.variants
+                .first()?
+                .fields
+                .iter()
+                .find(|(name, _)| name == field)
+                .map(|(_, ty)| ty.substitute(&subst)),
+        )
+    }
+
+    /// Resolve a type under the current substitution, looking through references
+    fn peel_refs(&self, ty: &Type) -> Type {
+        match self.ctx.apply_subst(ty) {
+            Type::Ref { inner, .. } => self.peel_refs(&inner),
+            ty => ty,
+        }
+    }
+
+    /// Compute the common type of two branches
+    fn join(&mut self, first: &Type, second: &Type, span: Span) -> Type {
+        if self.resolves_to_never(first) {
+            return second.clone();
+        }
+        if self.resolves_to_never(second) {
+            return first.clone();
+        }
+        self.coerce(second, first, span);
+        first.clone()
+    }
+
+    /// Require `found` to unify with `expected`, reporting a mismatch otherwise
+    ///
+    /// The never type coerces to every type.
+    fn coerce(&mut self, found: &Type, expected: &Type, span: Span) {
+        if self.resolves_to_never(found) || self.resolves_to_never(expected) {
+            return;
+        }
+
+        match self.ctx.unify(found, expected) {
+            Ok(()) => {}
+            Err(InferenceError::Unification(
+                UnificationError::TypeMismatch(..) | UnificationError::ArityMismatch(..),
+            )) => {
+                let error = TypeError::Mismatch {
+                    expected: self.ctx.describe(expected),
+                    found: self.ctx.describe(found),
+                };
+                self.report(error, span);
+            }
+            Err(err) => self.report(err.into(), span),
+        }
+    }
+
+    /// Whether the type resolves to the never type under the current substitution
+    fn resolves_to_never(&self, ty: &Type) -> bool {
+        matches!(self.ctx.apply_subst(ty), Type::Never)
+    }
+
+    /// Record a type error at the given span
+    fn report(&mut self, error: TypeError, span: Span) {
+        self.errors.push(SpannedTypeError { error, span });
+    }
+
+    /// Name under which methods of a type are registered
+    fn type_name(ty: &Type) -> Option<String> {
+        match ty {
+            Type::Named { name, .. } => Some(name.clone()),
+            Type::Primitive(prim) => Some(prim.to_string()),
+            Type::Ref { inner, .. } | Type::Ptr { inner, .. } => Self::type_name(inner),
+            _ => None,
+        }
+    }
+
+    /// Look up a primitive type by its source name
+    fn primitive_from_name(name: &str) -> Option<PrimitiveType> {
+        Some(match name {
+            "i8" => PrimitiveType::I8,
+            "i16" => PrimitiveType::I16,
+            "i32" => PrimitiveType::I32,
+            "i64" => PrimitiveType::I64,
+            "i128" => PrimitiveType::I128,
+            "isize" => PrimitiveType::ISize,
+            "u8" => PrimitiveType::U8,
+            "u16" => PrimitiveType::U16,
+            "u32" => PrimitiveType::U32,
+            "u64" => PrimitiveType::U64,
+            "u128" => PrimitiveType::U128,
+            "usize" => PrimitiveType::USize,
+            "f32" => PrimitiveType::F32,
+            "f64" => PrimitiveType::F64,
+            "bool" => PrimitiveType::Bool,
+            "char" => PrimitiveType::Char,
+            "str" => PrimitiveType::Str,
+            _ => return None,
+        })
+    }
+
+    /// Whether a primitive type is an integer or float type
+    fn is_numeric(prim: PrimitiveType) -> bool {
+        Self::is_integer(prim) || matches!(prim, PrimitiveType::F32 | PrimitiveType::F64)
+    }
+
+    /// Whether a primitive type takes `!` and the bitwise operators
+    fn is_logical(prim: PrimitiveType) -> bool {
+        Self::is_integer(prim) || prim == PrimitiveType::Bool
+    }
+
+    /// Whether a primitive type is an integer type
+    fn is_integer(prim: PrimitiveType) -> bool {
+        !matches!(
+            prim,
+            PrimitiveType::F32
+                | PrimitiveType::F64
+                | PrimitiveType::Bool
+                | PrimitiveType::Char
+                | PrimitiveType::Str
+        )
+    }
+
+    /// Whether values of a type can be interpolated into a string: numbers
+    /// up to 64 bits, `bool`, `char` and strings
+    ///
+    /// Unsolved types are left for lowering to treat as `i64`.
+    fn is_formattable(ty: &Type) -> bool {
+        match ty {
+            Type::Primitive(PrimitiveType::I128 | PrimitiveType::U128) => false,
+            Type::Primitive(_) | Type::Var(_) | Type::Never => true,
+            Type::Ref { inner, .. } => matches!(**inner, Type::Primitive(PrimitiveType::Str)),
+            _ => false,
+        }
+    }
+
+    /// Span of an expression, or a dummy span if it is missing
+    fn expr_span(arena: &Arena, expr_id: ExprId) -> Span {
+        arena.get_expr(expr_id).map(|e| e.span).unwrap_or_else(Span::dummy)
+    }
+
+    /// Span of a block's result, falling back to `default`
+    fn block_span(arena: &Arena, block_id: BlockId, default: Span) -> Span {
+        match arena.get_block(block_id) {
+            Some(Block { expr: Some(expr), .. }) => Self::expr_span(arena, *expr),
+            Some(block) => block.span,
+            None => default,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aurora_parser::Parser;
+    use std::sync::Arc;
+
+    fn check_source(source: &str) -> (TypeChecker, aurora_ast::Ast) {
+        let ast = Parser::new(source, "test.ax".to_string()).unwrap().parse();
+        let mut checker = TypeChecker::new(Arc::new(()));
+        let ast = checker.check(ast);
+        (checker, ast)
+    }
+
+    /// Types recorded for every literal expression, in arena order
+    fn literal_types(checker: &TypeChecker, ast: &aurora_ast::Ast) -> Vec<Type> {
+        (0..ast.arena.len() as u32)
+            .filter(|&id| {
+                matches!(ast.arena.get_expr(id), Some(e) if matches!(e.kind, ExprKind::Literal(_)))
+            })
+            .filter_map(|id| checker.type_map().get_expr(id).cloned())
+            .collect()
+    }
+
+    #[test]
+    fn test_well_typed_function() {
+        let (checker, _) = check_source("fn add(a: i32, b: i32) -> i32 { a + b }");
+        assert!(!checker.has_errors(), "{:?}", checker.errors());
+    }
+
+    #[test]
+    fn test_return_type_mismatch() {
+        let (checker, _) = check_source("fn f() -> bool { 1 }");
+        assert_eq!(checker.errors().len(), 1);
+        assert!(matches!(checker.errors()[0].error, TypeError::Mismatch { .. }));
+    }
+
+    #[test]
+    fn test_call_argument_mismatch() {
+        let (checker, _) = check_source("fn g(x: bool) {} fn main() { g(1); }");
+        assert_eq!(checker.errors().len(), 1);
+    }
+
+    #[test]
+    fn test_wrong_arg_count() {
+        let (checker, _) = check_source("fn g(x: i32) {} fn main() { g(1, 2); }");
+        assert!(checker
+            .errors()
+            .iter()
+            .any(|e| matches!(e.error, TypeError::WrongArgCount { expected: 1, got: 2 })));
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        let (checker, _) = check_source("fn main() { let x = y; }");
+        assert!(matches!(&checker.errors()[0].error, TypeError::UndefinedVariable(n) if n == "y"));
+    }
+
+    #[test]
+    fn test_println_arguments_fill_placeholders() {
+        let (checker, _) = check_source("fn main() { println(1); println(\"a {} b {}\", 1, true); }");
+        assert!(!checker.has_errors(), "{:?}", checker.errors());
+
+        let (checker, _) = check_source("fn main() { println(\"a\", 1, true); }");
+        let errors = checker.errors();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(matches!(errors[0].error, TypeError::FormatArgCount { placeholders: 0, got: 2 }), "{:?}", errors);
+
+        let (checker, _) = check_source("fn main() { let s = \"{}\"; print(s, 1); println(); }");
+        let errors: Vec<_> = checker.errors().iter().map(|e| &e.error).collect();
+        assert!(matches!(errors[..], [TypeError::FormatNotLiteral(_), TypeError::WrongArgCount { expected: 1, got: 0 }]), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_println_arguments_must_be_formattable() {
+        let (checker, _) = check_source("struct P { x: i32 } fn main() { println(P { x: 1 }); }");
+        let errors = checker.errors();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(matches!(&errors[0].error, TypeError::NotFormattable(ty) if ty == "P"), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_interpolated_values_must_be_formattable() {
+        let (checker, _) = check_source(
+            "struct P { x: i32 }
+             fn main() { let p = P { x: 1 }; let s: str = f\"{p.x} {1.5} {true} {'c'} {\"s\"}\"; let t = f\"p = {p}\"; }",
+        );
+        let errors = checker.errors();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(matches!(&errors[0].error, TypeError::NotFormattable(ty) if ty == "P"), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_loop_type_comes_from_breaks() {
+        let (checker, _) = check_source("fn f() -> i32 { loop {} } fn main() { let v: i32 = loop { break 5; }; }");
+        assert!(!checker.has_errors(), "{:?}", checker.errors());
+
+        let (checker, _) = check_source("fn main() { let v: bool = loop { break 5; }; }");
+        let errors = checker.errors();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(matches!(errors[0].error, TypeError::Mismatch { .. }), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_generic_function_instantiation() {
+        let (checker, _) =
+            check_source("fn id<T>(x: T) -> T { x } fn main() { let a: i32 = id(1); let b: bool = id(true); }");
+        assert!(!checker.has_errors(), "{:?}", checker.errors());
+    }
+
+    #[test]
+    fn test_generic_parameters_are_rigid_in_the_body() {
+        for source in [
+            "fn f<T>(x: T) -> i32 { x }",
+            "fn id<T>(x: T) -> T { 1 }",
+            "fn g<T, U>(x: T, y: U) -> T { y }",
+            "struct W<T> { v: T } impl<T> W<T> { fn get(self) -> bool { self.v } }",
+        ] {
+            let (checker, _) = check_source(source);
+            let errors = checker.errors();
+            assert_eq!(errors.len(), 1, "{}: {:?}", source, errors);
+            assert!(matches!(errors[0].error, TypeError::Mismatch { .. }), "{}: {:?}", source, errors);
+        }
+
+        let (checker, _) = check_source("fn f<T>(x: T) -> i32 { x }");
+        assert!(matches!(&checker.errors()[0].error, TypeError::Mismatch { expected, found } if expected == "i32" && found == "T"));
+
+        // Each call instantiates the parameters afresh
+        let (checker, _) = check_source(
+            "fn pick<T>(a: T, b: T) -> T { let c = a; c }
+             fn main() { let x: i32 = pick(1, 2); let y: bool = pick(true, false); }",
+        );
+        assert!(!checker.has_errors(), "{:?}", checker.errors());
+    }
+
+    #[test]
+    fn test_numeric_literal_defaults() {
+        let (checker, ast) = check_source("fn main() { let x = 1; let y: i64 = 2; let z = 1.5; }");
+        assert!(!checker.has_errors(), "{:?}", checker.errors());
+        assert_eq!(
+            literal_types(&checker, &ast),
+            vec![
+                Type::Primitive(PrimitiveType::I32),
+                Type::Primitive(PrimitiveType::I64),
+                Type::Primitive(PrimitiveType::F64),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_suffixed_literal_types() {
+        let (checker, ast) = check_source("fn main() { let a = 255u8; let b = 1.5f32; let c = 2f64; let d = 0xFFi64; }");
+        assert!(!checker.has_errors(), "{:?}", checker.errors());
+        assert_eq!(
+            literal_types(&checker, &ast),
+            vec![
+                Type::Primitive(PrimitiveType::U8),
+                Type::Primitive(PrimitiveType::F32),
+                Type::Primitive(PrimitiveType::F64),
+                Type::Primitive(PrimitiveType::I64),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_literal_range_checks() {
+        let (checker, _) = check_source(
+            "fn main() {
+                 let a: u64 = 18446744073709551615; let b = -128i8; let c: i8 = -128; let d: u16 = 0xFFFF;
+                 let e = 256u8; let f = -1u32; let g: i8 = 128; let h = 2147483648; let i: u64 = 18446744073709551616;
+             }",
+        );
+        let errors: Vec<(&str, &str)> = checker
+            .errors()
+            .iter()
+            .filter_map(|e| match &e.error {
+                TypeError::LiteralOutOfRange { literal, ty } => Some((literal.as_str(), ty.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                ("256", "u8"),
+                ("-1", "u32"),
+                ("128", "i8"),
+                ("2147483648", "i32"),
+                ("18446744073709551616", "u64"),
+            ],
+            "{:?}",
+            checker.errors()
+        );
+        assert_eq!(checker.errors().len(), errors.len(), "{:?}", checker.errors());
+    }
+
+    #[test]
+    fn test_operators_take_operands_of_their_types() {
+        let (checker, _) = check_source(
+            "fn main() {
+                 let a = -1.5; let b = 3 % 2 + 1; let c = !true; let d = 6 & 3; let e = true ^ false;
+                 let f = 1 << 3; let g: f32 = 2.0; let h = g * g; let i = 1; i += 2;
+             }",
+        );
+        assert!(!checker.has_errors(), "{:?}", checker.errors());
+
+        for (source, expected, found) in [
+            ("fn main() { let a = -true; }", "numeric type", "bool"),
+            ("fn main() { let a = \"x\" + \"y\"; }", "numeric type", "str"),
+            ("fn main() { let s = \"x\"; s += s; }", "numeric type", "str"),
+            ("fn main() { let a = 1.5 & 2.5; }", "bool or integer", "f64"),
+            ("fn main() { let a = 1 << 1.5; }", "integer", "f64"),
+            ("fn main() { let a = ~'c'; }", "integer", "char"),
+            ("fn main() { let a: i64 = 1.5; }", "i64", "float"),
+        ] {
+            let (checker, _) = check_source(source);
+            let errors: Vec<(&str, &str)> = checker
+                .errors()
+                .iter()
+                .filter_map(|e| match &e.error {
+                    TypeError::Mismatch { expected, found } => Some((expected.as_str(), found.as_str())),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(errors, [(expected, found)], "{}: {:?}", source, checker.errors());
+        }
+    }
+
+    #[test]
+    fn test_errors_do_not_stop_checking() {
+        let (checker, _) = check_source("fn main() { let a: bool = 1; let b: i32 = true; }");
+        assert_eq!(checker.errors().len(), 2);
+    }
+
+    #[test]
+    fn test_struct_literal_and_field_access() {
+        let (checker, _) = check_source(
+            "struct Point { x: i32, y: i32 }
+             fn sum(p: &Point) -> i32 { p.x + p.y }
+             fn main() { let p = Point { x: 1, y: 2 }; let s: i32 = sum(&p); }",
+        );
+        assert!(!checker.has_errors(), "{:?}", checker.errors());
+    }
+
+    #[test]
+    fn test_struct_field_errors() {
+        let (checker, _) = check_source(
+            "struct Point { x: i32, y: i32 }
+             fn main() { let p = Point { x: 1 }; let z = p.z; }",
+        );
+        let errors: Vec<_> = checker.errors().iter().map(|e| &e.error).collect();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(matches!(errors[0], TypeError::MissingFields { fields, .. } if fields == &["y"]));
+        assert!(matches!(errors[1], TypeError::UnknownField { field, .. } if field == "z"));
+    }
+
+    #[test]
+    fn test_enum_variants_and_match() {
+        let (checker, _) = check_source(
+            "enum Shape { Circle(f64), Rect { w: f64, h: f64 }, Empty }
+             fn area(s: Shape) -> f64 {
+                 match s {
+                     Shape::Circle(r) => r * r,
+                     Shape::Rect { w, h } => w * h,
+                     Shape::Empty => 0.0,
+                 }
+             }
+             fn main() { let a = area(Shape::Circle(1.0)); let b = area(Shape::Empty); }",
+        );
+        assert!(!checker.has_errors(), "{:?}", checker.errors());
+    }
+
+    #[test]
+    fn test_enum_variant_arity() {
+        let (checker, _) = check_source(
+            "enum Shape { Circle(f64) }
+             fn f(s: Shape) -> f64 { match s { Shape::Circle(a, b) => a } }",
+        );
+        assert_eq!(checker.errors().len(), 1);
+        assert!(matches!(checker.errors()[0].error, TypeError::WrongArgCount { .. }));
+    }
+
+    #[test]
+    fn test_non_exhaustive_match() {
+        let (checker, _) = check_source(
+            "enum Shape { Circle(f64), Rect { w: f64, h: f64 }, Empty }
+             fn area(s: Shape) -> f64 {
+                 match s {
+                     Shape::Circle(r) => r,
+                     Shape::Empty => 0.0,
+                 }
+             }",
+        );
+        assert_eq!(checker.errors().len(), 1);
+        assert!(
+            matches!(&checker.errors()[0].error, TypeError::NonExhaustive(msg) if msg == "pattern `Shape::Rect(_, _)` not covered"),
+            "{:?}",
+            checker.errors()
+        );
+    }
+
+    #[test]
+    fn test_guarded_or_and_range_arms() {
+        let (checker, _) = check_source(
+            "fn f(n: i32, flag: bool) -> i32 {
+                 let a = match (flag, n) {
+                     (true, 0..=9) | (false, _) => 1,
+                     (true, _) => 2,
+                 };
+                 match n { 0 => 0, x if x > 0 => 1 }
+             }",
+        );
+        assert_eq!(checker.errors().len(), 1);
+        assert!(
+            matches!(&checker.errors()[0].error, TypeError::NonExhaustive(msg) if msg == "pattern `_` not covered"),
+            "{:?}",
+            checker.errors()
+        );
+    }
+
+    #[test]
+    fn test_generic_struct() {
+        let (checker, _) = check_source(
+            "struct Wrapper<T> { value: T }
+             fn main() { let w = Wrapper { value: true }; let b: bool = w.value; let n: i32 = w.value; }",
+        );
+        assert_eq!(checker.errors().len(), 1);
+        assert!(matches!(checker.errors()[0].error, TypeError::Mismatch { .. }));
+    }
+}
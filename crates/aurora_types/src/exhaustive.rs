@@ -17,6 +17,13 @@ pub enum Pattern {
     Wildcard,
     /// Literal pattern (42, true, etc.)
     Literal(Literal),
+    /// Inclusive integer range pattern (1..=5)
+    Range {
+        /// Lowest value matched
+        start: i128,
+        /// Highest value matched
+        end: i128,
+    },
     /// Constructor pattern (Some(x), None, Ok(y), etc.)
     Constructor {
         /// Constructor name
@@ -33,10 +40,14 @@ pub enum Pattern {
 /// Literal value in patterns
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Literal {
-    /// Integer
-    Int(i64),
+    /// Integer (characters match by code point)
+    Int(i128),
     /// Boolean
     Bool(bool),
+    /// Float, stored as bits
+    Float(u64),
+    /// String
+    Str(String),
     /// Unit ()
     Unit,
 }
@@ -60,17 +71,28 @@ pub enum ExhaustivenessError {
 /// Exhaustiveness result
 pub type ExhaustResult<T> = Result<T, ExhaustivenessError>;
 
+/// Variants of a named type: constructor names with their field types
+pub type Variants = Vec<(String, Vec<Type>)>;
+
 /// Check if patterns are exhaustive for a type
 pub fn check_exhaustive(ty: &Type, patterns: &[Pattern]) -> ExhaustResult<()> {
+    check_exhaustive_with(ty, patterns, &|_, _| None)
+}
+
+/// Check if patterns are exhaustive for a type whose named types may be user-defined
+///
+/// `variants` returns the variants of a struct or enum given its name and
+/// generic arguments. A struct has a single variant named after the struct.
+pub fn check_exhaustive_with(
+    ty: &Type,
+    patterns: &[Pattern],
+    variants: &dyn Fn(&str, &[Type]) -> Option<Variants>,
+) -> ExhaustResult<()> {
     let matrix = PatternMatrix::new(patterns.to_vec());
 
-    if is_exhaustive(ty, &matrix) {
-        Ok(())
-    } else {
-        let missing = construct_witness(ty, &matrix);
-        Err(ExhaustivenessError::NonExhaustive(
-            pattern_to_string(&missing),
-        ))
+    match matrix.witness(std::slice::from_ref(ty), variants) {
+        None => Ok(()),
+        Some(witness) => Err(ExhaustivenessError::NonExhaustive(pattern_to_string(&witness[0]))),
     }
 }
 
@@ -85,7 +107,19 @@ pub fn check_reachable(patterns: &[Pattern]) -> ExhaustResult<()> {
     Ok(())
 }
 
+/// Constructor heading a column of the pattern matrix
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Ctor {
+    Bool(bool),
+    Unit,
+    Tuple(usize),
+    Variant(String),
+}
+
 /// Pattern matrix for exhaustiveness checking
+///
+/// Each row holds one pattern per column; a value vector is matched by a row
+/// when every column matches.
 #[derive(Debug, Clone)]
 struct PatternMatrix {
     rows: Vec<Vec<Pattern>>,
@@ -98,130 +132,143 @@ impl PatternMatrix {
         }
     }
 
-    fn is_empty(&self) -> bool {
-        self.rows.is_empty()
-    }
-
-    fn has_wildcard_row(&self) -> bool {
-        self.rows
-            .iter()
-            .any(|row| row.first().map(|p| matches!(p, Pattern::Wildcard)).unwrap_or(false))
-    }
-}
-
-/// Check if pattern matrix is exhaustive for a type
-fn is_exhaustive(ty: &Type, matrix: &PatternMatrix) -> bool {
-    // Empty matrix is not exhaustive (unless type is uninhabited)
-    if matrix.is_empty() {
-        return is_uninhabited(ty);
-    }
-
-    // Matrix with wildcard is exhaustive
-    if matrix.has_wildcard_row() {
-        return true;
-    }
-
-    // For specific types, check constructors
-    match ty {
-        Type::Primitive(PrimitiveType::Bool) => check_bool_exhaustive(matrix),
-        Type::Option(_) => check_option_exhaustive(matrix),
-        Type::Tuple(types) => check_tuple_exhaustive(types, matrix),
-        _ => matrix.has_wildcard_row(), // Conservative: require wildcard for complex types
-    }
-}
-
-/// Check if a type is uninhabited (has no values)
-fn is_uninhabited(ty: &Type) -> bool {
-    matches!(ty, Type::Never)
-}
-
-/// Check exhaustiveness for bool
-fn check_bool_exhaustive(matrix: &PatternMatrix) -> bool {
-    let mut has_true = false;
-    let mut has_false = false;
-
-    for row in &matrix.rows {
-        if let Some(Pattern::Literal(Literal::Bool(b))) = row.first() {
-            if *b {
-                has_true = true;
-            } else {
-                has_false = true;
+    /// Find values of types `tys` that no row matches
+    ///
+    /// Returns one pattern per column describing such values, or `None` when
+    /// the matrix is exhaustive.
+    fn witness(
+        &self,
+        tys: &[Type],
+        variants: &dyn Fn(&str, &[Type]) -> Option<Variants>,
+    ) -> Option<Vec<Pattern>> {
+        let Some((ty, rest_tys)) = tys.split_first() else {
+            return self.rows.is_empty().then(Vec::new);
+        };
+        let matrix = self.expand_or();
+
+        match constructors(ty, variants) {
+            // Every value of the type is built by one of finitely many constructors
+            Some(ctors) => ctors.into_iter().find_map(|(ctor, field_tys)| {
+                let arity = field_tys.len();
+                let sub_tys: Vec<Type> = field_tys.into_iter().chain(rest_tys.iter().cloned()).collect();
+                let mut witness = matrix.specialize(&ctor, arity).witness(&sub_tys, variants)?;
+                let fields: Vec<Pattern> = witness.drain(..arity).collect();
+                witness.insert(0, ctor_pattern(ty, &ctor, fields));
+                Some(witness)
+            }),
+            // Otherwise only rows starting with a wildcard cover the values not listed
+            None => {
+                let mut witness = matrix.default_rows().witness(rest_tys, variants)?;
+                witness.insert(0, Pattern::Wildcard);
+                Some(witness)
             }
         }
     }
 
-    has_true && has_false
-}
-
-/// Check exhaustiveness for Option
-fn check_option_exhaustive(matrix: &PatternMatrix) -> bool {
-    let mut has_some = false;
-    let mut has_none = false;
-
-    for row in &matrix.rows {
-        if let Some(Pattern::Constructor { name, .. }) = row.first() {
-            if name == "Some" {
-                has_some = true;
-            } else if name == "None" {
-                has_none = true;
+    /// Split rows whose first pattern is an or-pattern into one row per alternative
+    fn expand_or(&self) -> PatternMatrix {
+        let mut rows = Vec::new();
+        let mut pending: Vec<Vec<Pattern>> = self.rows.iter().rev().cloned().collect();
+
+        while let Some(row) = pending.pop() {
+            match row.first() {
+                Some(Pattern::Or(alts)) => {
+                    for alt in alts.iter().rev() {
+                        let mut expanded = row.clone();
+                        expanded[0] = alt.clone();
+                        pending.push(expanded);
+                    }
+                }
+                _ => rows.push(row),
             }
         }
+
+        PatternMatrix { rows }
     }
 
-    has_some && has_none
-}
+    /// Rows that match a value built by `ctor`, with its fields as new leading columns
+    fn specialize(&self, ctor: &Ctor, arity: usize) -> PatternMatrix {
+        let rows = self
+            .rows
+            .iter()
+            .filter_map(|row| {
+                let (head, tail) = row.split_first()?;
+                let mut fields = match (head, ctor) {
+                    (Pattern::Wildcard, _) => vec![Pattern::Wildcard; arity],
+                    (Pattern::Literal(Literal::Bool(b)), Ctor::Bool(c)) if b == c => Vec::new(),
+                    (Pattern::Literal(Literal::Unit), Ctor::Unit) => Vec::new(),
+                    (Pattern::Tuple(elems), Ctor::Tuple(n)) if elems.len() == *n => elems.clone(),
+                    (Pattern::Constructor { name, fields }, Ctor::Variant(variant)) if name == variant => {
+                        fields.clone()
+                    }
+                    _ => return None,
+                };
+                fields.resize(arity, Pattern::Wildcard);
+                fields.extend(tail.iter().cloned());
+                Some(fields)
+            })
+            .collect();
+
+        PatternMatrix { rows }
+    }
 
-/// Check exhaustiveness for tuple
-fn check_tuple_exhaustive(_types: &[Type], matrix: &PatternMatrix) -> bool {
-    // Simplified: require wildcard for tuples
-    matrix.has_wildcard_row()
-}
+    /// Rows whose first pattern is a wildcard, without that column
+    fn default_rows(&self) -> PatternMatrix {
+        let rows = self
+            .rows
+            .iter()
+            .filter(|row| matches!(row.first(), Some(Pattern::Wildcard)))
+            .map(|row| row[1..].to_vec())
+            .collect();
 
-/// Construct a witness pattern for non-exhaustive match
-fn construct_witness(ty: &Type, matrix: &PatternMatrix) -> Pattern {
-    match ty {
-        Type::Primitive(PrimitiveType::Bool) => {
-            if !has_bool_pattern(matrix, true) {
-                Pattern::Literal(Literal::Bool(true))
-            } else {
-                Pattern::Literal(Literal::Bool(false))
-            }
-        }
-        Type::Option(_) => {
-            if !has_constructor(matrix, "Some") {
-                Pattern::Constructor {
-                    name: "Some".to_string(),
-                    fields: vec![Pattern::Wildcard],
-                }
-            } else {
-                Pattern::Constructor {
-                    name: "None".to_string(),
-                    fields: vec![],
-                }
-            }
-        }
-        _ => Pattern::Wildcard,
+        PatternMatrix { rows }
     }
 }
 
-/// Check if matrix has a bool pattern
-fn has_bool_pattern(matrix: &PatternMatrix, value: bool) -> bool {
-    matrix.rows.iter().any(|row| {
-        matches!(
-            row.first(),
-            Some(Pattern::Literal(Literal::Bool(b))) if *b == value
-        )
-    })
+/// Constructors of a type with their field types, if the type has finitely many
+fn constructors(
+    ty: &Type,
+    variants: &dyn Fn(&str, &[Type]) -> Option<Variants>,
+) -> Option<Vec<(Ctor, Vec<Type>)>> {
+    let ctors = match ty {
+        Type::Primitive(PrimitiveType::Bool) => vec![(Ctor::Bool(true), vec![]), (Ctor::Bool(false), vec![])],
+        Type::Unit => vec![(Ctor::Unit, vec![])],
+        Type::Never => vec![],
+        Type::Tuple(elems) => vec![(Ctor::Tuple(elems.len()), elems.clone())],
+        Type::Ref { inner, .. } => return constructors(inner, variants),
+        Type::Option(inner) => vec![
+            (Ctor::Variant("None".to_string()), vec![]),
+            (Ctor::Variant("Some".to_string()), vec![(**inner).clone()]),
+        ],
+        Type::Result { ok, err } => vec![
+            (Ctor::Variant("Ok".to_string()), vec![(**ok).clone()]),
+            (Ctor::Variant("Err".to_string()), vec![(**err).clone()]),
+        ],
+        Type::Named { name, args } => variants(name, args)?
+            .into_iter()
+            .map(|(variant, fields)| (Ctor::Variant(variant), fields))
+            .collect(),
+        _ => return None,
+    };
+    Some(ctors)
 }
 
-/// Check if matrix has a constructor
-fn has_constructor(matrix: &PatternMatrix, name: &str) -> bool {
-    matrix.rows.iter().any(|row| {
-        matches!(
-            row.first(),
-            Some(Pattern::Constructor { name: n, .. }) if n == name
-        )
-    })
+/// Pattern built from a constructor of `ty` and its field patterns
+fn ctor_pattern(ty: &Type, ctor: &Ctor, fields: Vec<Pattern>) -> Pattern {
+    match ctor {
+        Ctor::Bool(b) => Pattern::Literal(Literal::Bool(*b)),
+        Ctor::Unit => Pattern::Literal(Literal::Unit),
+        Ctor::Tuple(_) => Pattern::Tuple(fields),
+        Ctor::Variant(variant) => {
+            // Qualify enum variants with their type (`Color::Red`)
+            let name = match ty {
+                Type::Named { name, .. } if name != variant => format!("{}::{}", name, variant),
+                Type::Ref { inner, .. } => return ctor_pattern(inner, ctor, fields),
+                _ => variant.clone(),
+            };
+            Pattern::Constructor { name, fields }
+        }
+    }
 }
 
 /// Check if a pattern is subsumed by a list of patterns
@@ -238,6 +285,16 @@ fn subsumes(p1: &Pattern, p2: &Pattern) -> bool {
         // Same literals
         (Pattern::Literal(l1), Pattern::Literal(l2)) => l1 == l2,
 
+        // Ranges cover the literals and ranges inside them
+        (Pattern::Range { start, end }, Pattern::Literal(Literal::Int(n))) => start <= n && n <= end,
+        (Pattern::Range { start: s1, end: e1 }, Pattern::Range { start: s2, end: e2 }) => {
+            s1 <= s2 && e2 <= e1
+        }
+
+        // An or-pattern is covered when each alternative is
+        (p1, Pattern::Or(alts)) => alts.iter().all(|alt| subsumes(p1, alt)),
+        (Pattern::Or(alts), p2) => alts.iter().any(|alt| subsumes(alt, p2)),
+
         // Same constructors with subsumed fields
         (
             Pattern::Constructor {
@@ -265,7 +322,10 @@ fn pattern_to_string(pattern: &Pattern) -> String {
         Pattern::Wildcard => "_".to_string(),
         Pattern::Literal(Literal::Int(n)) => n.to_string(),
         Pattern::Literal(Literal::Bool(b)) => b.to_string(),
+        Pattern::Literal(Literal::Float(bits)) => f64::from_bits(*bits).to_string(),
+        Pattern::Literal(Literal::Str(text)) => format!("{:?}", text),
         Pattern::Literal(Literal::Unit) => "()".to_string(),
+        Pattern::Range { start, end } => format!("{}..={}", start, end),
         Pattern::Constructor { name, fields } => {
             if fields.is_empty() {
                 name.clone()
@@ -398,6 +458,70 @@ mod tests {
         );
     }
 
+    fn ctor(name: &str, fields: Vec<Pattern>) -> Pattern {
+        Pattern::Constructor {
+            name: name.to_string(),
+            fields,
+        }
+    }
+
+    fn bool_pat(b: bool) -> Pattern {
+        Pattern::Literal(Literal::Bool(b))
+    }
+
+    #[test]
+    fn test_nested_option_witness() {
+        let ty = Type::Option(Box::new(Type::Primitive(PrimitiveType::Bool)));
+        let patterns = vec![ctor("Some", vec![bool_pat(true)]), ctor("None", vec![])];
+
+        let result = check_exhaustive(&ty, &patterns);
+        assert_eq!(result, Err(ExhaustivenessError::NonExhaustive("Some(false)".to_string())));
+
+        let patterns = vec![ctor("Some", vec![Pattern::Or(vec![bool_pat(true), bool_pat(false)])]), ctor("None", vec![])];
+        assert!(check_exhaustive(&ty, &patterns).is_ok());
+    }
+
+    #[test]
+    fn test_user_enum_exhaustive() {
+        let ty = Type::Named {
+            name: "Shape".to_string(),
+            args: vec![],
+        };
+        let variants = |name: &str, _: &[Type]| {
+            (name == "Shape").then(|| {
+                vec![
+                    ("Circle".to_string(), vec![Type::Primitive(PrimitiveType::I64)]),
+                    ("Empty".to_string(), vec![]),
+                ]
+            })
+        };
+
+        let patterns = vec![ctor("Circle", vec![Pattern::Literal(Literal::Int(0))]), ctor("Empty", vec![])];
+        let result = check_exhaustive_with(&ty, &patterns, &variants);
+        assert_eq!(result, Err(ExhaustivenessError::NonExhaustive("Shape::Circle(_)".to_string())));
+
+        let patterns = vec![ctor("Circle", vec![Pattern::Wildcard]), ctor("Empty", vec![])];
+        assert!(check_exhaustive_with(&ty, &patterns, &variants).is_ok());
+    }
+
+    #[test]
+    fn test_tuple_and_range_exhaustive() {
+        let bool_ty = Type::Primitive(PrimitiveType::Bool);
+        let ty = Type::Tuple(vec![bool_ty.clone(), bool_ty]);
+        let patterns = vec![
+            Pattern::Tuple(vec![bool_pat(true), Pattern::Wildcard]),
+            Pattern::Tuple(vec![Pattern::Wildcard, bool_pat(false)]),
+        ];
+        let result = check_exhaustive(&ty, &patterns);
+        assert_eq!(result, Err(ExhaustivenessError::NonExhaustive("(false, true)".to_string())));
+
+        // Integers are only covered by a wildcard
+        let ty = Type::Primitive(PrimitiveType::I64);
+        let patterns = vec![Pattern::Range { start: i64::MIN.into(), end: 0 }, Pattern::Range { start: 1, end: i64::MAX.into() }];
+        assert!(check_exhaustive(&ty, &patterns).is_err());
+        assert!(check_reachable(&[Pattern::Range { start: 1, end: 9 }, Pattern::Literal(Literal::Int(3))]).is_err());
+    }
+
     #[test]
     fn test_never_type_exhaustive() {
         let ty = Type::Never;
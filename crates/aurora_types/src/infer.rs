@@ -162,6 +162,9 @@ pub struct InferContext {
     next_var: TypeVarId,
     /// Current substitution
     subst: Substitution,
+    /// Variables standing for generic parameters, which no type other than
+    /// themselves unifies with, and their names
+    rigid: HashMap<TypeVarId, String>,
 }
 
 impl InferContext {
@@ -170,6 +173,7 @@ impl InferContext {
         Self {
             next_var: 0,
             subst: Substitution::new(),
+            rigid: HashMap::new(),
         }
     }
 
@@ -180,17 +184,59 @@ impl InferContext {
         Type::Var(var)
     }
 
+    /// Generate a fresh rigid type variable for the generic parameter `name`
+    pub fn fresh_rigid_var(&mut self, name: &str) -> Type {
+        let var = self.fresh_var();
+        if let Type::Var(v) = var {
+            self.rigid.insert(v, name.to_string());
+        }
+        var
+    }
+
     /// Add a constraint (perform unification)
+    ///
+    /// A rigid variable only unifies with itself and with flexible
+    /// variables, which are bound to it.
     pub fn unify(&mut self, t1: &Type, t2: &Type) -> InferResult<()> {
         let t1_subst = t1.substitute(&self.subst);
         let t2_subst = t2.substitute(&self.subst);
 
-        let new_subst = unify(&t1_subst, &t2_subst)?;
+        let mut new_subst = unify(&t1_subst, &t2_subst)?;
+        let rigid: Vec<TypeVarId> = new_subst.keys().copied().filter(|&v| self.is_rigid(v)).collect();
+        for var in rigid {
+            match new_subst.remove(&var) {
+                Some(Type::Var(other)) if !self.is_rigid(other) => {
+                    let flipped = Substitution::from([(other, Type::Var(var))]);
+                    new_subst = compose_subst(&new_subst, &flipped);
+                    new_subst.insert(other, Type::Var(var));
+                }
+                Some(ty) => {
+                    let mismatch = UnificationError::TypeMismatch(self.describe(&Type::Var(var)), self.describe(&ty));
+                    return Err(mismatch.into());
+                }
+                None => {}
+            }
+        }
         self.subst = compose_subst(&self.subst, &new_subst);
 
         Ok(())
     }
 
+    /// Whether a type variable stands for a generic parameter
+    pub fn is_rigid(&self, var: TypeVarId) -> bool {
+        self.rigid.contains_key(&var)
+    }
+
+    /// Display a type, naming rigid variables after their generic parameters
+    pub fn describe(&self, ty: &Type) -> String {
+        let names: Substitution = self
+            .rigid
+            .iter()
+            .map(|(&var, name)| (var, Type::Named { name: name.clone(), args: Vec::new() }))
+            .collect();
+        ty.substitute(&self.subst).substitute(&names).to_string()
+    }
+
     /// Instantiate a type scheme with fresh variables
     pub fn instantiate(&mut self, scheme: &TypeScheme) -> Type {
         if scheme.vars.is_empty() {
@@ -17,6 +17,7 @@
 //! - `traits`: Typeclass resolution with coherence checking
 //! - `generics`: Generic instantiation and monomorphization tracking
 //! - `exhaustive`: Exhaustiveness checking for pattern matching
+//! - `check`: Inference over AST function bodies for the pipeline
 //!
 //! # Example
 //!
@@ -34,6 +35,7 @@
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
+mod check;
 pub mod exhaustive;
 pub mod generics;
 pub mod infer;
@@ -51,7 +53,8 @@ pub use unify::{Substitution, UnificationError};
 
 // Pipeline integration
 use aurora_ast::expr::{BinaryOp, Literal};
-use aurora_ast::{Ast, ExprId, ExprKind};
+use aurora_ast::{Ast, ExprId, ExprKind, Span};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use thiserror::Error;
@@ -93,24 +96,134 @@ pub enum TypeError {
         got: usize,
     },
 
+    /// Undefined struct, enum or variant
+    #[error("Undefined type: {0}")]
+    UndefinedType(String),
+
+    /// Access to a field the type does not declare
+    #[error("No field `{field}` on type {ty}")]
+    UnknownField {
+        /// Type being accessed
+        ty: String,
+        /// Field name
+        field: String,
+    },
+
+    /// Struct literal that does not initialize every field
+    #[error("Missing fields in initializer of {ty}: {}", fields.join(", "))]
+    MissingFields {
+        /// Type being constructed
+        ty: String,
+        /// Names of the missing fields
+        fields: Vec<String>,
+    },
+
     /// Non-exhaustive pattern match
     #[error("Non-exhaustive pattern match: {0}")]
     NonExhaustive(String),
+
+    /// Interpolated value of a type strings cannot show
+    #[error("Cannot format a value of type {0} into a string")]
+    NotFormattable(String),
+
+    /// `print` or `println` with more `{}` placeholders in its template
+    /// than arguments after it, or fewer
+    #[error("Format string has {placeholders} placeholders but {got} arguments follow it")]
+    FormatArgCount {
+        /// Placeholders in the template
+        placeholders: usize,
+        /// Arguments after the template
+        got: usize,
+    },
+
+    /// `print` or `println` with arguments after a template that is not a
+    /// string literal
+    #[error("The first of several arguments to {0} must be a string literal")]
+    FormatNotLiteral(String),
+
+    /// Numeric literal whose value does not fit its type
+    #[error("Literal {literal} out of range for {ty}")]
+    LiteralOutOfRange {
+        /// Value of the literal
+        literal: String,
+        /// Type the literal was given
+        ty: String,
+    },
+}
+
+impl TypeError {
+    /// Diagnostic code for this error
+    pub fn code(&self) -> &'static str {
+        match self {
+            TypeError::Inference(_) => "E0282",
+            TypeError::Unification(_) | TypeError::Mismatch { .. } => "E0308",
+            TypeError::UndefinedFunction(_) | TypeError::UndefinedVariable(_) => "E0425",
+            TypeError::WrongArgCount { .. } | TypeError::FormatArgCount { .. } | TypeError::FormatNotLiteral(_) => {
+                "E0061"
+            }
+            TypeError::UndefinedType(_) => "E0412",
+            TypeError::UnknownField { .. } => "E0609",
+            TypeError::MissingFields { .. } => "E0063",
+            TypeError::NonExhaustive(_) => "E0004",
+            TypeError::NotFormattable(_) => "E0277",
+            TypeError::LiteralOutOfRange { .. } => "E0080",
+        }
+    }
+}
+
+/// Type error with the source location it was reported at
+#[derive(Debug, Clone)]
+pub struct SpannedTypeError {
+    /// The error
+    pub error: TypeError,
+    /// Location of the offending expression
+    pub span: Span,
 }
 
 /// Type annotation map (maps AST node IDs to inferred types)
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TypeMap {
     /// Expression types
     expr_types: HashMap<ExprId, Type>,
+    /// Function and method signatures keyed by (qualified) name
+    functions: HashMap<String, Type>,
+    /// Struct declarations keyed by name
+    structs: HashMap<String, StructDef>,
+    /// Enum declarations keyed by name
+    enums: HashMap<String, EnumDef>,
+}
+
+/// Fields of a struct declaration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StructDef {
+    /// Type variables standing for the generic parameters, in order
+    pub params: Vec<TypeVarId>,
+    /// Field names and types in declaration order (positional fields are named `0`, `1`, ...)
+    pub fields: Vec<(String, Type)>,
+}
+
+/// Variants of an enum declaration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnumDef {
+    /// Type variables standing for the generic parameters, in order
+    pub params: Vec<TypeVarId>,
+    /// Variants in declaration order
+    pub variants: Vec<EnumVariant>,
+}
+
+/// A single enum variant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnumVariant {
+    /// Variant name
+    pub name: String,
+    /// Field names and types (positional fields are named `0`, `1`, ...)
+    pub fields: Vec<(String, Type)>,
 }
 
 impl TypeMap {
     /// Create a new empty type map
     pub fn new() -> Self {
-        Self {
-            expr_types: HashMap::new(),
-        }
+        Self::default()
     }
 
     /// Insert a type for an expression
@@ -122,6 +235,61 @@ impl TypeMap {
     pub fn get_expr(&self, expr_id: ExprId) -> Option<&Type> {
         self.expr_types.get(&expr_id)
     }
+
+    /// Record the signature of a function (methods are keyed `Type::method`)
+    pub fn insert_function(&mut self, name: String, ty: Type) {
+        self.functions.insert(name, ty);
+    }
+
+    /// Get the signature of a function
+    pub fn function(&self, name: &str) -> Option<&Type> {
+        self.functions.get(name)
+    }
+
+    /// Iterate over all function signatures
+    pub fn functions(&self) -> impl Iterator<Item = (&String, &Type)> {
+        self.functions.iter()
+    }
+
+    /// Record a struct declaration
+    pub fn insert_struct(&mut self, name: String, def: StructDef) {
+        self.structs.insert(name, def);
+    }
+
+    /// Get a struct declaration
+    pub fn get_struct(&self, name: &str) -> Option<&StructDef> {
+        self.structs.get(name)
+    }
+
+    /// Iterate over all struct declarations
+    pub fn structs(&self) -> impl Iterator<Item = (&String, &StructDef)> {
+        self.structs.iter()
+    }
+
+    /// Record an enum declaration
+    pub fn insert_enum(&mut self, name: String, def: EnumDef) {
+        self.enums.insert(name, def);
+    }
+
+    /// Get an enum declaration
+    pub fn get_enum(&self, name: &str) -> Option<&EnumDef> {
+        self.enums.get(name)
+    }
+
+    /// Iterate over all enum declarations
+    pub fn enums(&self) -> impl Iterator<Item = (&String, &EnumDef)> {
+        self.enums.iter()
+    }
+
+    /// Number of typed expressions
+    pub fn len(&self) -> usize {
+        self.expr_types.len()
+    }
+
+    /// Check if no expression has been typed
+    pub fn is_empty(&self) -> bool {
+        self.expr_types.is_empty()
+    }
 }
 
 /// Type checker for pipeline integration
@@ -137,6 +305,20 @@ pub struct TypeChecker {
     trait_registry: TraitRegistry,
     /// Monomorphization tracker
     mono_tracker: MonoTracker,
+    /// Function signatures keyed by (qualified) name
+    signatures: HashMap<String, check::Signature>,
+    /// Struct and enum declarations keyed by name
+    adts: HashMap<String, check::AdtDef>,
+    /// Numeric literals awaiting defaulting
+    numeric_literals: Vec<check::NumericLiteral>,
+    /// Match expressions awaiting the exhaustiveness check
+    match_checks: Vec<check::MatchCheck>,
+    /// Operator operands awaiting the check of their type
+    operand_checks: Vec<check::OperandCheck>,
+    /// Interpolated values awaiting the check that they can be formatted
+    formatted_values: Vec<check::FormattedValue>,
+    /// Errors reported while checking
+    errors: Vec<SpannedTypeError>,
 }
 
 impl TypeChecker {
@@ -154,52 +336,92 @@ impl TypeChecker {
             type_map: TypeMap::new(),
             trait_registry: TraitRegistry::new("current_crate".to_string()),
             mono_tracker: MonoTracker::new(),
+            signatures: HashMap::new(),
+            adts: HashMap::new(),
+            numeric_literals: Vec::new(),
+            match_checks: Vec::new(),
+            operand_checks: Vec::new(),
+            formatted_values: Vec::new(),
+            errors: Vec::new(),
         }
     }
 
     /// Add built-in functions to the environment
     fn add_builtins(env: &mut TypeEnv) {
-        // println: (str, ...) -> ()
-        // For simplicity, we'll type it as (str) -> ()
+        // print, println: (str, ...) -> ()
+        // Their arguments are checked on their own, see `check_print_template`
         let println_ty = Type::Function {
             params: vec![Type::Primitive(PrimitiveType::Str)],
             ret: Box::new(Type::Unit),
             effects: EffectSet::IO,
         };
+        *env = env.extend("print".to_string(), TypeScheme::mono(println_ty.clone()));
         *env = env.extend("println".to_string(), TypeScheme::mono(println_ty));
 
         // len: [T] -> usize
-        let len_ty = Type::Forall {
-            vars: vec![0],
-            constraints: vec![],
-            inner: Box::new(Type::Function {
-                params: vec![Type::Array {
-                    elem: Box::new(Type::Var(0)),
-                    size: None,
-                }],
-                ret: Box::new(Type::Primitive(PrimitiveType::USize)),
-                effects: EffectSet::PURE,
-            }),
+        let len_ty = Type::Function {
+            params: vec![Type::Array {
+                elem: Box::new(Type::Var(0)),
+                size: None,
+            }],
+            ret: Box::new(Type::Primitive(PrimitiveType::USize)),
+            effects: EffectSet::PURE,
         };
         *env = env.extend("len".to_string(), TypeScheme::poly(vec![0], len_ty));
+
+        // Option and Result constructors
+        let some_ty = Type::Function {
+            params: vec![Type::Var(0)],
+            ret: Box::new(Type::Option(Box::new(Type::Var(0)))),
+            effects: EffectSet::PURE,
+        };
+        *env = env.extend("Some".to_string(), TypeScheme::poly(vec![0], some_ty));
+
+        let none_ty = Type::Option(Box::new(Type::Var(0)));
+        *env = env.extend("None".to_string(), TypeScheme::poly(vec![0], none_ty));
+
+        let result_ty = Type::Result {
+            ok: Box::new(Type::Var(0)),
+            err: Box::new(Type::Var(1)),
+        };
+        let ok_ty = Type::Function {
+            params: vec![Type::Var(0)],
+            ret: Box::new(result_ty.clone()),
+            effects: EffectSet::PURE,
+        };
+        *env = env.extend("Ok".to_string(), TypeScheme::poly(vec![0, 1], ok_ty));
+
+        let err_ty = Type::Function {
+            params: vec![Type::Var(1)],
+            ret: Box::new(result_ty),
+            effects: EffectSet::PURE,
+        };
+        *env = env.extend("Err".to_string(), TypeScheme::poly(vec![0, 1], err_ty));
     }
 
     /// Type check the AST
+    ///
+    /// Every function body is inferred and the type of each expression is
+    /// recorded in the type map. Errors are collected and can be retrieved
+    /// with [`TypeChecker::errors`].
     pub fn check(&mut self, ast: Ast) -> Ast {
-        // TODO: Implement actual type checking with Hindley-Milner inference
-        // For now, this is a stub that returns the AST unchanged
-        // A full implementation would:
-        // 1. Traverse all items in the AST
-        // 2. Type check function declarations
-        // 3. Infer types for expressions
-        // 4. Check exhaustiveness of pattern matches
-        // 5. Resolve trait constraints
-        // 6. Track monomorphization instances
-
-        // This is intentionally minimal for now - will be expanded
+        self.collect_adts(&ast.arena, &ast.items);
+        self.collect_signatures(&ast.arena, &ast.items);
+        self.check_items(&ast.arena, &ast.items);
+        self.finalize_types();
         ast
     }
 
+    /// Errors reported by the last call to [`TypeChecker::check`]
+    pub fn errors(&self) -> &[SpannedTypeError] {
+        &self.errors
+    }
+
+    /// Check if any type errors were reported
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
     /// Type check an expression (helper for testing and internal use)
     pub fn check_expr(&mut self, expr: &ExprKind) -> Result<Type, TypeError> {
         match expr {
@@ -279,17 +501,19 @@ impl TypeChecker {
         }
     }
 
-    /// Helper to check an expression by ID (stub)
-    fn check_expr_id(&mut self, _expr_id: ExprId) -> Result<Type, TypeError> {
-        // TODO: Look up the expression in the AST and type check it
-        Ok(Type::Unit)
+    /// Look up the inferred type of an expression checked by [`TypeChecker::check`]
+    fn check_expr_id(&mut self, expr_id: ExprId) -> Result<Type, TypeError> {
+        self.type_map
+            .get_expr(expr_id)
+            .cloned()
+            .ok_or_else(|| InferenceError::CannotInfer(format!("expression {}", expr_id)).into())
     }
 
     /// Infer the type of a literal
     fn infer_literal(&self, lit: &Literal) -> Type {
         match lit {
-            Literal::Int(_) => Type::Primitive(PrimitiveType::I32),
-            Literal::Float(_) => Type::Primitive(PrimitiveType::F64),
+            Literal::Int(_, suffix) => Type::Primitive(suffix.map_or(PrimitiveType::I32, Into::into)),
+            Literal::Float(_, suffix) => Type::Primitive(suffix.map_or(PrimitiveType::F64, Into::into)),
             Literal::Bool(_) => Type::Primitive(PrimitiveType::Bool),
             Literal::Char(_) => Type::Primitive(PrimitiveType::Char),
             Literal::String(_) => Type::Primitive(PrimitiveType::Str),
@@ -332,7 +556,7 @@ mod tests {
         let diagnostics = Arc::new(DummyDiagnostics);
         let checker = TypeChecker::new(diagnostics);
 
-        let lit = Literal::Int(42);
+        let lit = Literal::Int(42, None);
         let ty = checker.infer_literal(&lit);
         assert_eq!(ty, Type::Primitive(PrimitiveType::I32));
     }
@@ -347,6 +571,26 @@ mod tests {
         assert_eq!(map.get_expr(1), None);
     }
 
+    #[test]
+    fn test_type_map_records_declarations() {
+        let source = "struct Pair<T> { a: T, b: i64 }
+            impl Pair<i64> { fn sum(&self) -> i64 { self.a + self.b } }";
+        let ast = aurora_parser::Parser::new(source, "test.ax".to_string()).unwrap().parse();
+        let mut checker = TypeChecker::new(Arc::new(()));
+        checker.check(ast);
+
+        let pair = checker.type_map().get_struct("Pair").unwrap();
+        assert_eq!(pair.params.len(), 1);
+        assert_eq!(pair.fields[0], ("a".to_string(), Type::Var(pair.params[0])));
+        assert_eq!(pair.fields[1], ("b".to_string(), Type::Primitive(PrimitiveType::I64)));
+
+        let Some(Type::Function { params, ret, .. }) = checker.type_map().function("Pair::sum") else {
+            panic!("method signature not recorded");
+        };
+        assert!(matches!(&params[0], Type::Ref { inner, .. } if matches!(**inner, Type::Named { .. })));
+        assert_eq!(**ret, Type::Primitive(PrimitiveType::I64));
+    }
+
     #[test]
     fn test_hindley_milner_inference() {
         // Test basic HM inference
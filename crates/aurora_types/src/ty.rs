@@ -8,6 +8,8 @@
 //! - Typeclass constraints
 //! - Type equality and subtyping
 
+use aurora_ast::expr::IntSuffix;
+use aurora_ast::ty::{FloatType, IntType, UintType};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fmt;
@@ -592,6 +594,68 @@ impl fmt::Display for PrimitiveType {
     }
 }
 
+impl PrimitiveType {
+    /// Smallest and largest values of an integer type
+    ///
+    /// `isize` and `usize` are 64 bits wide; `u128` is capped at the
+    /// largest `i128`, as literals are no wider.
+    pub fn int_range(self) -> Option<(i128, i128)> {
+        Some(match self {
+            PrimitiveType::I8 => (i8::MIN.into(), i8::MAX.into()),
+            PrimitiveType::I16 => (i16::MIN.into(), i16::MAX.into()),
+            PrimitiveType::I32 => (i32::MIN.into(), i32::MAX.into()),
+            PrimitiveType::I64 | PrimitiveType::ISize => (i64::MIN.into(), i64::MAX.into()),
+            PrimitiveType::I128 => (i128::MIN, i128::MAX),
+            PrimitiveType::U8 => (0, u8::MAX.into()),
+            PrimitiveType::U16 => (0, u16::MAX.into()),
+            PrimitiveType::U32 => (0, u32::MAX.into()),
+            PrimitiveType::U64 | PrimitiveType::USize => (0, u64::MAX.into()),
+            PrimitiveType::U128 => (0, i128::MAX),
+            _ => return None,
+        })
+    }
+}
+
+impl From<IntType> for PrimitiveType {
+    fn from(ty: IntType) -> Self {
+        match ty {
+            IntType::I8 => PrimitiveType::I8,
+            IntType::I16 => PrimitiveType::I16,
+            IntType::I32 => PrimitiveType::I32,
+            IntType::I64 => PrimitiveType::I64,
+        }
+    }
+}
+
+impl From<UintType> for PrimitiveType {
+    fn from(ty: UintType) -> Self {
+        match ty {
+            UintType::U8 => PrimitiveType::U8,
+            UintType::U16 => PrimitiveType::U16,
+            UintType::U32 => PrimitiveType::U32,
+            UintType::U64 => PrimitiveType::U64,
+        }
+    }
+}
+
+impl From<FloatType> for PrimitiveType {
+    fn from(ty: FloatType) -> Self {
+        match ty {
+            FloatType::F32 => PrimitiveType::F32,
+            FloatType::F64 => PrimitiveType::F64,
+        }
+    }
+}
+
+impl From<IntSuffix> for PrimitiveType {
+    fn from(suffix: IntSuffix) -> Self {
+        match suffix {
+            IntSuffix::Int(ty) => ty.into(),
+            IntSuffix::Uint(ty) => ty.into(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
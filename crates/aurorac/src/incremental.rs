@@ -0,0 +1,416 @@
+//! Incremental Compilation
+//!
+//! Caches the results of compiler queries on disk, each under a fingerprint
+//! of the inputs it was computed from, so a later compilation only recomputes
+//! what its changes affect:
+//!
+//! - `tokens`: the root file's text
+//! - `ast`: the root file's text, checked against the module files it loaded
+//! - `names` and `types`: the text of every file in the crate
+//! - `mir`: one function's text and the crate's interface (the functions in
+//!   order, their signatures, the type declarations and the other top-level
+//!   items), so editing a function body only lowers that function again
+//! - `optimized-mir`: every function's fingerprint and position, the
+//!   optimization level and the target CPU, as inlining crosses functions
+//!
+//! Results are only cached when computing them reported no diagnostics, so
+//! a cache hit never hides a warning.
+
+use aurora_ast::{Ast, ItemKind, SourceMap, Span};
+use aurora_diagnostics::SharedCollector;
+use aurora_mir::{Function, FunctionId, MirModule};
+use aurora_types::TypeMap;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Stable 64-bit hash of a query's inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Fingerprint(pub u64);
+
+impl Fingerprint {
+    /// Fingerprint a value; fingerprints differ between compiler versions
+    pub fn of(value: impl Hash) -> Self {
+        let mut hasher = StableHasher::new();
+        env!("CARGO_PKG_VERSION").hash(&mut hasher);
+        value.hash(&mut hasher);
+        Self(hasher.finish())
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// FNV-1a, which unlike `DefaultHasher` gives the same hash in every run
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A kind of cached query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Query {
+    /// Tokens of the root file
+    Tokens,
+    /// AST of the crate, with its module files loaded
+    Ast,
+    /// AST after name resolution
+    Names,
+    /// Type-checked AST and its type map
+    Types,
+    /// MIR of one function, before optimization
+    Mir,
+    /// Optimized MIR of the crate
+    OptimizedMir,
+}
+
+impl Query {
+    /// Every query, in the order the pipeline asks them
+    pub const ALL: [Query; 6] = [
+        Query::Tokens,
+        Query::Ast,
+        Query::Names,
+        Query::Types,
+        Query::Mir,
+        Query::OptimizedMir,
+    ];
+
+    /// Name of the query, which is also its cache subdirectory
+    pub fn name(self) -> &'static str {
+        match self {
+            Query::Tokens => "tokens",
+            Query::Ast => "ast",
+            Query::Names => "names",
+            Query::Types => "types",
+            Query::Mir => "mir",
+            Query::OptimizedMir => "optimized-mir",
+        }
+    }
+}
+
+/// Cache hits and misses per query
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    hits: [usize; Query::ALL.len()],
+    misses: [usize; Query::ALL.len()],
+}
+
+impl CacheStats {
+    /// Number of lookups of `query` answered from the cache
+    pub fn hits(&self, query: Query) -> usize {
+        self.hits[query as usize]
+    }
+
+    /// Number of lookups of `query` that had to be recomputed
+    pub fn misses(&self, query: Query) -> usize {
+        self.misses[query as usize]
+    }
+
+    fn record(&mut self, query: Query, hit: bool) {
+        if hit {
+            self.hits[query as usize] += 1;
+        } else {
+            self.misses[query as usize] += 1;
+        }
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let asked: Vec<String> = Query::ALL
+            .iter()
+            .filter(|&&query| self.hits(query) + self.misses(query) > 0)
+            .map(|&query| {
+                let hits = self.hits(query);
+                format!("{} {}/{} hits", query.name(), hits, hits + self.misses(query))
+            })
+            .collect();
+        if asked.is_empty() {
+            write!(f, "no queries")
+        } else {
+            write!(f, "{}", asked.join(", "))
+        }
+    }
+}
+
+/// A cached query result and the fingerprint it was computed under
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    key: Fingerprint,
+    value: T,
+}
+
+/// On-disk cache of query results
+///
+/// Every query has one slot per `slot` name (the crate, or a function), so
+/// a result replaces the one cached for earlier inputs.
+pub struct QueryCache {
+    /// Directory holding one subdirectory per query
+    dir: PathBuf,
+    /// Lookups so far
+    stats: CacheStats,
+}
+
+impl QueryCache {
+    /// Open (creating if needed) the cache in `dir`
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            stats: CacheStats::default(),
+        })
+    }
+
+    /// Look up the result of `query` for `slot` computed under `key`
+    pub fn load<T: DeserializeOwned>(&mut self, query: Query, slot: &str, key: Fingerprint) -> Option<T> {
+        self.load_if(query, slot, key, |_| true)
+    }
+
+    /// Look up a result as [`load`](Self::load) does, rejecting it unless
+    /// `is_fresh` accepts it
+    pub fn load_if<T: DeserializeOwned>(
+        &mut self,
+        query: Query,
+        slot: &str,
+        key: Fingerprint,
+        is_fresh: impl FnOnce(&T) -> bool,
+    ) -> Option<T> {
+        let value = fs::read(self.path(query, slot))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Entry<T>>(&bytes).ok())
+            .filter(|entry| entry.key == key)
+            .map(|entry| entry.value)
+            .filter(is_fresh);
+        debug!("{} query for {}: {}", query.name(), slot, if value.is_some() { "hit" } else { "miss" });
+        self.stats.record(query, value.is_some());
+        value
+    }
+
+    /// Cache the result of `query` for `slot` computed under `key`
+    ///
+    /// Failing to write the cache only costs a later compilation time, so it
+    /// is logged rather than reported.
+    pub fn store<T: Serialize>(&self, query: Query, slot: &str, key: Fingerprint, value: &T) {
+        let path = self.path(query, slot);
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| serde_json::to_vec(&Entry { key, value }).map_err(io::Error::from))
+            .and_then(|bytes| fs::write(&path, bytes));
+        if let Err(e) = result {
+            warn!("Failed to cache {} query in {}: {}", query.name(), path.display(), e);
+        }
+    }
+
+    /// Lookups so far
+    pub fn stats(&self) -> &CacheStats {
+        &self.stats
+    }
+
+    fn path(&self, query: Query, slot: &str) -> PathBuf {
+        self.dir
+            .join(query.name())
+            .join(format!("{}.json", Fingerprint::of(slot)))
+    }
+}
+
+/// Fingerprint of the text of every file in the crate
+pub fn crate_fingerprint(sources: &SourceMap) -> Fingerprint {
+    let files: Vec<(&str, &str)> = sources
+        .files()
+        .map(|(_, file)| (file.name.as_str(), file.source.as_str()))
+        .collect();
+    Fingerprint::of(files)
+}
+
+/// A function lowered to MIR, fingerprinted for the `mir` query
+#[derive(Debug, Clone)]
+pub struct MirItem {
+    /// Name the signature is recorded under (`Type::method` for methods)
+    pub key: String,
+    /// Span of the declaration
+    pub span: Span,
+    /// Fingerprint of the declaration's text and the crate interface
+    pub fingerprint: Fingerprint,
+}
+
+/// The functions of a type-checked crate, in function ID order
+pub fn mir_items(ast: &Ast, type_map: &TypeMap, sources: &SourceMap) -> Vec<MirItem> {
+    let items = aurora_mir::lowered_items(ast);
+    let interface = interface_fingerprint(ast, type_map, sources, &items);
+
+    items
+        .into_iter()
+        .map(|(key, span)| {
+            // Spans inside the function are cached relative to where it
+            // starts, except for the columns on its first line
+            let text = source_text(sources, span);
+            let fingerprint = Fingerprint::of((interface, span.file_id, span.column, text));
+            MirItem { key, span, fingerprint }
+        })
+        .collect()
+}
+
+/// Fingerprint of the optimized MIR of a crate made of `items`
+pub fn module_fingerprint(items: &[MirItem], opt_level: u8, cpu: Option<&str>) -> Fingerprint {
+    let items: Vec<(Fingerprint, u32, u32)> = items
+        .iter()
+        .map(|item| (item.fingerprint, item.span.start, item.span.line))
+        .collect();
+    Fingerprint::of((items, opt_level, cpu))
+}
+
+/// A function's MIR as cached, with where its declaration started
+#[derive(Serialize, Deserialize)]
+struct CachedFunction<F> {
+    start: u32,
+    line: u32,
+    function: F,
+}
+
+/// Lower `items` to MIR, reusing the functions the `mir` query has cached
+pub fn lower_items(
+    cache: &mut QueryCache,
+    ast: Ast,
+    type_map: TypeMap,
+    items: &[MirItem],
+    diagnostics: Arc<SharedCollector>,
+) -> MirModule {
+    let mut cached = HashMap::new();
+    for (id, item) in items.iter().enumerate() {
+        let entry: Option<CachedFunction<Function>> = cache.load(Query::Mir, &item.key, item.fingerprint);
+        if let Some(CachedFunction { start, line, mut function }) = entry {
+            function.id = id as FunctionId;
+            function.shift_spans(
+                item.span.start as isize - start as isize,
+                item.span.line as i32 - line as i32,
+            );
+            cached.insert(item.key.as_str(), function);
+        }
+    }
+
+    let reported = diagnostics.lock().diagnostics().len();
+    let mut mir = aurora_mir::lower_typed_items_to_mir(ast, type_map, diagnostics.clone(), |key| {
+        !cached.contains_key(key)
+    });
+    let clean = diagnostics.lock().diagnostics().len() == reported;
+
+    for (id, item) in items.iter().enumerate() {
+        if let Some(function) = cached.remove(item.key.as_str()) {
+            mir.add_function(function);
+        } else if let Some(function) = mir.get_function(id as FunctionId).filter(|_| clean) {
+            let entry = CachedFunction {
+                start: item.span.start,
+                line: item.span.line,
+                function,
+            };
+            cache.store(Query::Mir, &item.key, item.fingerprint, &entry);
+        }
+    }
+
+    mir
+}
+
+/// Fingerprint of what lowering one function may depend on besides its text
+fn interface_fingerprint(
+    ast: &Ast,
+    type_map: &TypeMap,
+    sources: &SourceMap,
+    items: &[(String, Span)],
+) -> Fingerprint {
+    let keys: Vec<&str> = items.iter().map(|(key, _)| key.as_str()).collect();
+
+    let mut signatures: Vec<_> = type_map.functions().collect();
+    signatures.sort_by(|a, b| a.0.cmp(b.0));
+    let mut structs: Vec<_> = type_map.structs().collect();
+    structs.sort_by(|a, b| a.0.cmp(b.0));
+    let mut enums: Vec<_> = type_map.enums().collect();
+    enums.sort_by(|a, b| a.0.cmp(b.0));
+    let declarations = serde_json::to_string(&(signatures, structs, enums)).unwrap_or_default();
+
+    // Functions are covered by their signatures, methods also by their keys
+    let others: Vec<&str> = ast
+        .items
+        .iter()
+        .filter_map(|&id| ast.arena.get_item(id))
+        .filter(|item| !matches!(item.kind, ItemKind::Function(_) | ItemKind::Impl(_)))
+        .map(|item| source_text(sources, item.span))
+        .collect();
+
+    Fingerprint::of((keys, declarations, others))
+}
+
+/// The source text a span covers
+fn source_text(sources: &SourceMap, span: Span) -> &str {
+    sources
+        .get(span.file_id)
+        .and_then(|file| file.source.get(span.start as usize..span.end as usize))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        assert_eq!(Fingerprint::of("fn main() {}"), Fingerprint::of("fn main() {}"));
+        assert_ne!(Fingerprint::of("fn main() {}"), Fingerprint::of("fn main() { }"));
+        assert_eq!(Fingerprint::of(42u64).to_string().len(), 16);
+    }
+
+    #[test]
+    fn test_query_cache_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let mut cache = QueryCache::open(dir.path()).unwrap();
+        let key = Fingerprint::of("tokens");
+
+        assert_eq!(cache.load::<Vec<u32>>(Query::Tokens, "crate", key), None);
+        cache.store(Query::Tokens, "crate", key, &vec![1u32, 2, 3]);
+        assert_eq!(cache.load(Query::Tokens, "crate", key), Some(vec![1u32, 2, 3]));
+        assert_eq!(cache.load::<Vec<u32>>(Query::Tokens, "crate", Fingerprint::of("other")), None);
+        assert_eq!(cache.load_if(Query::Tokens, "crate", key, |_: &Vec<u32>| false), None);
+
+        assert_eq!(cache.stats().hits(Query::Tokens), 1);
+        assert_eq!(cache.stats().misses(Query::Tokens), 3);
+        assert_eq!(cache.stats().to_string(), "tokens 1/4 hits");
+    }
+
+    #[test]
+    fn test_cache_stats_display() {
+        let mut stats = CacheStats::default();
+        assert_eq!(stats.to_string(), "no queries");
+        stats.record(Query::Ast, true);
+        stats.record(Query::Mir, true);
+        stats.record(Query::Mir, false);
+        assert_eq!(stats.to_string(), "ast 1/1 hits, mir 1/2 hits");
+    }
+}
@@ -8,6 +8,8 @@
 
 pub mod diagnostics;
 pub mod driver;
+pub mod incremental;
+pub mod loader;
 pub mod session;
 pub mod pipeline;
 
@@ -25,16 +27,14 @@ pub fn initialize_compiler() {
 /// This is the main entry point for compiling Aurora programs.
 pub fn compile_file(options: CompilationOptions) -> anyhow::Result<()> {
     let mut session = CompilationSession::new(options)?;
-    let mut pipeline = Pipeline::new(&mut session);
+    let result = Pipeline::new(&mut session).compile();
 
-    pipeline.compile()?;
-
-    // Emit diagnostics even on success (for warnings)
-    if session.warning_count() > 0 {
+    // Emit diagnostics on failure, and on success if there are warnings
+    if result.is_err() || session.warning_count() > 0 {
         session.emit_diagnostics();
     }
 
-    Ok(())
+    result
 }
 
 /// Check syntax of a source file without full compilation
@@ -0,0 +1,276 @@
+//! Module Loader
+//!
+//! Loads the files of out-of-line modules (`mod foo;`) into the session's
+//! source map and parses them into the crate's AST, so a crate can span
+//! several files.
+
+use crate::session::CompilationSession;
+use aurora_ast::{Arena, Ast, AstNode, ItemId, ItemKind, Span};
+use aurora_diagnostics::Diagnostic;
+use aurora_lexer::Lexer;
+use aurora_parser::Parser;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Load every out-of-line module reachable from the root file
+///
+/// `mod foo;` declared in `dir/main.ax` loads `dir/foo.ax` or
+/// `dir/foo/mod.ax`, and the modules `foo` declares are looked up in
+/// `dir/foo/`. The items of each loaded file are attached to its `mod`
+/// declaration. Missing and circularly included files are reported as
+/// errors on the session.
+pub fn load_modules(session: &mut CompilationSession, mut ast: Ast) -> Ast {
+    let root_dir = session
+        .source_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let root_path = session.source_path.clone();
+
+    let mut loader = ModuleLoader {
+        session,
+        loading: vec![root_path],
+    };
+    let items = ast.items.clone();
+    loader.load_items(&mut ast.arena, &items, &root_dir);
+
+    ast
+}
+
+/// State for one run of the loader
+struct ModuleLoader<'sess> {
+    session: &'sess mut CompilationSession,
+    /// Files currently being loaded (to detect circular `mod` declarations)
+    loading: Vec<PathBuf>,
+}
+
+impl ModuleLoader<'_> {
+    /// Load the out-of-line modules among `items`, declared in directory `dir`
+    fn load_items(&mut self, arena: &mut Arena, items: &[ItemId], dir: &Path) {
+        for &item_id in items {
+            let Some(ItemKind::Module(decl)) = arena.get_item(item_id).map(|item| &item.kind) else {
+                continue;
+            };
+            let name = decl.name.clone();
+            let child_dir = dir.join(&name);
+
+            match decl.items.clone() {
+                // Inline module: its own `mod` declarations live in `dir/name/`
+                Some(children) => self.load_items(arena, &children, &child_dir),
+                None => {
+                    let span = decl.span;
+                    let Some(path) = self.find_module_file(dir, &name, span) else {
+                        continue;
+                    };
+                    let Some(children) = self.parse_file(arena, &path) else {
+                        continue;
+                    };
+
+                    if let Some(AstNode::Item(item)) = arena.get_mut(item_id) {
+                        if let ItemKind::Module(decl) = &mut item.kind {
+                            decl.items = Some(children.clone());
+                        }
+                    }
+
+                    self.loading.push(path);
+                    self.load_items(arena, &children, &child_dir);
+                    self.loading.pop();
+                }
+            }
+        }
+    }
+
+    /// Find the file for `mod name;` declared in `dir`
+    fn find_module_file(&mut self, dir: &Path, name: &str, span: Span) -> Option<PathBuf> {
+        let file = dir.join(format!("{}.ax", name));
+        let mod_file = dir.join(name).join("mod.ax");
+
+        let path = match (file.is_file(), mod_file.is_file()) {
+            (true, false) => file,
+            (false, true) => mod_file,
+            (true, true) => {
+                self.error(
+                    "E0761",
+                    format!(
+                        "file for module `{}` found at both {} and {}",
+                        name,
+                        file.display(),
+                        mod_file.display()
+                    ),
+                    span,
+                    "delete or rename one of them to remove the ambiguity".to_string(),
+                );
+                return None;
+            }
+            (false, false) => {
+                self.error(
+                    "E0583",
+                    format!("file not found for module `{}`", name),
+                    span,
+                    format!(
+                        "to create the module `{}`, create file {} or {}",
+                        name,
+                        file.display(),
+                        mod_file.display()
+                    ),
+                );
+                return None;
+            }
+        };
+
+        if self.loading.iter().any(|loading| same_file(loading, &path)) {
+            self.error(
+                "E0583",
+                format!("circular modules: {} includes itself", path.display()),
+                span,
+                "remove the `mod` declaration that closes the cycle".to_string(),
+            );
+            return None;
+        }
+
+        Some(path)
+    }
+
+    /// Register a module file in the source map and parse it into the arena
+    fn parse_file(&mut self, arena: &mut Arena, path: &Path) -> Option<Vec<ItemId>> {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                self.session.report(Diagnostic::error(
+                    "E0583",
+                    format!("failed to read module file {}: {}", path.display(), e),
+                ));
+                return None;
+            }
+        };
+        debug!("Loading module file {}", path.display());
+
+        let diagnostics = self.session.diagnostics.clone();
+        let file_id = self.session.source_map.add_file(path.display().to_string(), source);
+        let source = &self.session.source_map.get(file_id)?.source;
+
+        let tokens = Lexer::with_diagnostics(source, diagnostics.clone())
+            .with_file_id(file_id)
+            .tokenize();
+        let program = Parser::with_diagnostics(tokens, diagnostics)
+            .with_arena(std::mem::take(arena))
+            .parse();
+
+        *arena = program.arena;
+        Some(program.items)
+    }
+
+    /// Report an error at a `mod` declaration
+    fn error(&self, code: &str, message: String, span: Span, note: String) {
+        let span = aurora_diagnostics::Span::new(
+            span.start as usize,
+            span.end as usize,
+            span.file_id as usize,
+        );
+        self.session
+            .report(Diagnostic::error(code, message).with_span(span).with_note(note));
+    }
+}
+
+/// Whether two paths name the same file
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::CompilationOptions;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn session_for(dir: &TempDir, files: &[(&str, &str)]) -> CompilationSession {
+        for (name, source) in files {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+        CompilationSession::new(CompilationOptions::new(dir.path().join("main.ax"))).unwrap()
+    }
+
+    fn parse_root(session: &CompilationSession) -> Ast {
+        let tokens = Lexer::with_diagnostics(&session.source, session.diagnostics.clone())
+            .with_file_id(session.file_id)
+            .tokenize();
+        Parser::with_diagnostics(tokens, session.diagnostics.clone()).parse()
+    }
+
+    fn module_items(ast: &Ast, item_id: ItemId) -> Option<Vec<ItemId>> {
+        match &ast.arena.get_item(item_id)?.kind {
+            ItemKind::Module(decl) => decl.items.clone(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_load_nested_module_files() {
+        let dir = TempDir::new().unwrap();
+        let mut session = session_for(
+            &dir,
+            &[
+                ("main.ax", "mod shapes;\nfn main() {}\n"),
+                ("shapes/mod.ax", "pub mod circle;\npub fn unit() -> i64 { 1 }\n"),
+                ("shapes/circle.ax", "pub fn area(r: i64) -> i64 { r * r }\n"),
+            ],
+        );
+
+        let ast = parse_root(&session);
+        let ast = load_modules(&mut session, ast);
+        assert!(!session.has_errors());
+        assert_eq!(session.source_map.len(), 3);
+
+        let shapes = module_items(&ast, ast.items[0]).expect("shapes loaded");
+        assert_eq!(shapes.len(), 2);
+        let circle = module_items(&ast, shapes[0]).expect("circle loaded");
+
+        // Spans of loaded items point into their own file
+        let area = ast.arena.get_item(circle[0]).unwrap();
+        let file = session.source_map.get(area.span.file_id).unwrap();
+        assert!(file.name.ends_with("circle.ax"));
+        assert_eq!(
+            session.source_map.span_text(area.span).map(|s| s.starts_with("pub fn area")),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_missing_module_file() {
+        let dir = TempDir::new().unwrap();
+        let mut session = session_for(&dir, &[("main.ax", "mod missing;\n")]);
+
+        let ast = parse_root(&session);
+        let ast = load_modules(&mut session, ast);
+
+        assert_eq!(session.error_count(), 1);
+        let collector = session.collector();
+        let diag = &collector.diagnostics()[0];
+        assert_eq!(diag.code, "E0583");
+        assert_eq!(diag.span.map(|s| (s.start, s.end)), Some((0, 12)));
+        assert_eq!(module_items(&ast, ast.items[0]), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_circular_module_files() {
+        let dir = TempDir::new().unwrap();
+        let mut session = session_for(&dir, &[("main.ax", "mod a;\n"), ("a.ax", "mod a;\n")]);
+        // `a/` links back to the crate root, so `a/a.ax` is `a.ax` again
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("a")).unwrap();
+
+        let ast = parse_root(&session);
+        let _ast = load_modules(&mut session, ast);
+
+        assert_eq!(session.error_count(), 1);
+        let collector = session.collector();
+        let diag = &collector.diagnostics()[0];
+        assert!(diag.message.starts_with("circular modules"));
+    }
+}
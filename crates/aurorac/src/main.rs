@@ -44,6 +44,23 @@ struct Cli {
     #[arg(short = 'O', default_value = "0")]
     opt_level: u8,
 
+    /// Target triple to generate code for (e.g., aarch64-unknown-linux-gnu)
+    #[arg(long, default_value = "x86_64-unknown-linux-gnu")]
+    target: String,
+
+    /// CPU to tune optimizations for (e.g., skylake, zen3)
+    #[arg(long)]
+    cpu: Option<String>,
+
+    /// Code generator: air (native code) or c (C99 built with $CC)
+    #[arg(long, default_value = "air")]
+    backend: String,
+
+    /// Linker: external (the system's, through gcc) or internal (built-in
+    /// static linker, with the runtime built without the C library)
+    #[arg(long, default_value = "external")]
+    linker: String,
+
     /// Enable verbose output
     #[arg(short = 'v', long)]
     verbose: bool,
@@ -59,6 +76,10 @@ struct Cli {
     /// Emit debug information
     #[arg(long, default_value = "true")]
     debug_info: bool,
+
+    /// Reuse results cached by earlier compilations of the same file
+    #[arg(long)]
+    incremental: bool,
 }
 
 #[derive(Subcommand)]
@@ -143,6 +164,10 @@ fn main() -> Result<()> {
                     input,
                     output: cli.output,
                     opt_level: cli.opt_level,
+                    target: cli.target,
+                    cpu: cli.cpu,
+                    backend: cli.backend,
+                    linker: cli.linker,
                     emit_mir: cli.emit_mir,
                     emit_air: cli.emit_air,
                     emit_ast: cli.emit_ast,
@@ -151,6 +176,7 @@ fn main() -> Result<()> {
                     verbose: cli.verbose,
                     codegen_units: cli.codegen_units,
                     debug_info: cli.debug_info,
+                    incremental: cli.incremental,
                 };
 
                 compile_file(opts)?;
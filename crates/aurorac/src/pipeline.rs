@@ -3,19 +3,29 @@
 //! Orchestrates the complete compilation flow:
 //! Source → Lexer → Parser → AST → Name Resolution → Type Checking →
 //! Effects → MIR → Optimization → AIR → Code Generation → Linking
+//!
+//! With the C backend, MIR is instead compiled as C.
+//!
+//! With incremental compilation, phases first look their result up in the
+//! query cache (see `incremental`).
 
+use crate::incremental::{self, CacheStats, Fingerprint, Query, QueryCache};
+use crate::loader;
 use crate::session::{CompilationSession, PhaseResult};
 use anyhow::{Context, Result};
-use aurora_air::AirModule;
+use aurora_air::{AirOptions, Arch, TargetModule};
 use aurora_ast::Ast;
-use aurora_backend::CodegenOptions;
+use aurora_backend::{Backend, CodegenOptions, LinkerKind};
 use aurora_diagnostics::{Diagnostic, DiagnosticLevel};
 use aurora_effects::EffectChecker;
 use aurora_lexer::Lexer;
-use aurora_mir::MirModule;
-use aurora_nameres::NameResolver;
+use aurora_mir::{MirModule, OptLevel};
+use aurora_optimizer::{CpuCharacteristics, OptimizationStrategy};
+use aurora_nameres::{NameResolver, ResolutionError};
 use aurora_parser::Parser;
-use aurora_types::TypeChecker;
+use aurora_types::{TypeChecker, TypeMap};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::fs;
 use std::sync::Arc;
 use tracing::{debug, info, warn};
@@ -23,29 +33,67 @@ use tracing::{debug, info, warn};
 /// The main compilation pipeline
 pub struct Pipeline<'sess> {
     session: &'sess mut CompilationSession,
+    /// Expression types and declarations recorded by the type checker
+    type_map: TypeMap,
+    /// Query results of earlier compilations, when compiling incrementally
+    cache: Option<QueryCache>,
 }
 
 impl<'sess> Pipeline<'sess> {
     /// Create a new pipeline for the given session
     pub fn new(session: &'sess mut CompilationSession) -> Self {
-        Self { session }
+        let cache = session.options.incremental.then(|| session.options.incremental_dir());
+        let cache = cache.and_then(|dir| match QueryCache::open(&dir) {
+            Ok(cache) => Some(cache),
+            Err(e) => {
+                warn!("Incremental cache {} unavailable: {}", dir.display(), e);
+                None
+            }
+        });
+
+        Self {
+            session,
+            type_map: TypeMap::new(),
+            cache,
+        }
+    }
+
+    /// Cache hits and misses so far, when compiling incrementally
+    pub fn cache_stats(&self) -> Option<&CacheStats> {
+        self.cache.as_ref().map(QueryCache::stats)
     }
 
     /// Run the complete compilation pipeline
     pub fn compile(&mut self) -> Result<()> {
         info!("Starting compilation of {}", self.session.source_name());
 
-        // Phase 1: Lexical Analysis
-        let tokens = self.lex()?;
-
-        // Phase 2: Parsing
-        let ast = self.parse(tokens)?;
+        let backend_name = &self.session.options.backend;
+        let backend = Backend::from_name(backend_name)
+            .with_context(|| format!("Unsupported backend: {}", backend_name))?;
+        let linker_name = &self.session.options.linker;
+        let linker = LinkerKind::from_name(linker_name)
+            .with_context(|| format!("Unsupported linker: {}", linker_name))?;
+        if backend == Backend::C && linker == LinkerKind::Internal {
+            anyhow::bail!("The built-in linker links executables of the air backend only");
+        }
 
-        // Phase 3: Name Resolution
-        let resolved_ast = self.resolve_names(ast)?;
+        // Phases 1 and 2: Lexical Analysis and Parsing
+        let ast = match self.cached_ast()? {
+            Some(ast) => ast,
+            None => {
+                let tokens = self.lex()?;
+                self.parse(tokens)?
+            }
+        };
 
-        // Phase 4: Type Checking
-        let typed_ast = self.type_check(resolved_ast)?;
+        // Phases 3 and 4: Name Resolution and Type Checking
+        let typed_ast = match self.cached_typed_ast() {
+            Some(ast) => ast,
+            None => {
+                let resolved_ast = self.resolve_names(ast)?;
+                self.type_check(resolved_ast)?
+            }
+        };
 
         // Phase 5: Effect Checking
         let checked_ast = self.check_effects(typed_ast)?;
@@ -56,11 +104,21 @@ impl<'sess> Pipeline<'sess> {
         // Phase 6: MIR Generation and Optimization
         let mir = self.lower_to_mir(checked_ast)?;
 
-        // Phase 7: AIR Generation
-        let air = self.lower_to_air(mir)?;
+        match backend {
+            Backend::Air => {
+                // Phase 7: AIR Generation
+                let module = self.lower_to_air(mir)?;
 
-        // Phase 8: Code Generation
-        self.codegen(air)?;
+                // Phase 8: Code Generation
+                self.codegen(module)?;
+            }
+            // Phases 7 and 8: C Generation and Compilation
+            Backend::C => self.codegen_c(&mir)?,
+        }
+
+        if let Some(stats) = self.cache_stats() {
+            info!("Incremental cache: {}", stats);
+        }
 
         info!("Compilation successful");
         Ok(())
@@ -70,8 +128,16 @@ impl<'sess> Pipeline<'sess> {
     fn lex(&mut self) -> Result<Vec<aurora_lexer::Token>> {
         info!("Phase 1: Lexical analysis");
 
-        let lexer = Lexer::with_diagnostics(&self.session.source, self.session.diagnostics.clone());
+        let key = Fingerprint::of((self.session.file_id, &self.session.source));
+        if let Some(tokens) = self.cached(Query::Tokens, key) {
+            return Ok(tokens);
+        }
+
+        let reported = self.reported();
+        let lexer = Lexer::with_diagnostics(&self.session.source, self.session.diagnostics.clone())
+            .with_file_id(self.session.file_id);
         let tokens = lexer.tokenize();
+        self.cache_result(Query::Tokens, key, &tokens, reported);
 
         if self.session.options.verbose {
             debug!("Lexed {} tokens", tokens.len());
@@ -85,8 +151,10 @@ impl<'sess> Pipeline<'sess> {
     fn parse(&mut self, tokens: Vec<aurora_lexer::Token>) -> Result<Ast> {
         info!("Phase 2: Parsing");
 
+        let reported = self.reported();
         let parser = Parser::with_diagnostics(tokens, self.session.diagnostics.clone());
         let ast = parser.parse();
+        let ast = loader::load_modules(self.session, ast);
 
         if self.session.options.verbose {
             debug!("Parsed AST with {} nodes", ast.node_count());
@@ -97,21 +165,95 @@ impl<'sess> Pipeline<'sess> {
         }
 
         self.session.check_errors()?;
+
+        if self.cache.is_some() {
+            let modules = self
+                .session
+                .source_map
+                .files()
+                .filter(|&(id, _)| id != self.session.file_id)
+                .map(|(_, file)| (file.name.clone(), Fingerprint::of(&file.source)))
+                .collect();
+            let key = Fingerprint::of(&self.session.source);
+            self.cache_result(Query::Ast, key, &CachedAst { modules, ast: &ast }, reported);
+        }
+
         Ok(ast)
     }
 
+    /// Phases 1 and 2 from the incremental cache, if no file of the crate changed
+    ///
+    /// The module files the cached AST was parsed from are registered in the
+    /// source map again, in the same order, so its spans keep their file IDs.
+    fn cached_ast(&mut self) -> Result<Option<Ast>> {
+        let Some(cache) = &mut self.cache else {
+            return Ok(None);
+        };
+
+        let mut sources = Vec::new();
+        let key = Fingerprint::of(&self.session.source);
+        let cached = cache.load_if(Query::Ast, "crate", key, |cached: &CachedAst<Ast>| {
+            cached.modules.iter().all(|(name, fingerprint)| {
+                let source = fs::read_to_string(name).unwrap_or_default();
+                let fresh = Fingerprint::of(&source) == *fingerprint;
+                sources.push((name.clone(), source));
+                fresh
+            })
+        });
+        let Some(cached) = cached else {
+            return Ok(None);
+        };
+
+        info!("Phases 1-2: Reusing parsed AST");
+        for (name, source) in sources {
+            self.session.source_map.add_file(name, source);
+        }
+        if self.session.options.emit_ast {
+            self.dump_ast(&cached.ast)?;
+        }
+        Ok(Some(cached.ast))
+    }
+
     /// Phase 3: Name resolution
     fn resolve_names(&mut self, ast: Ast) -> Result<Ast> {
         info!("Phase 3: Name resolution");
 
+        let key = incremental::crate_fingerprint(&self.session.source_map);
+        if let Some(resolved) = self.cached(Query::Names, key) {
+            return Ok(resolved);
+        }
+
+        let reported = self.reported();
         let mut resolver = NameResolver::new(self.session.diagnostics.clone());
         let resolved = resolver.resolve(ast);
 
+        for err in resolver.diagnostics() {
+            // Undefined names are reported by the type checker, and duplicate
+            // bindings include legal shadowing; everything else concerns
+            // modules and imports
+            if matches!(
+                err,
+                ResolutionError::UndefinedSymbol { .. } | ResolutionError::DuplicateDefinition { .. }
+            ) {
+                continue;
+            }
+            let mut diag = Diagnostic::error(err.code(), err.to_string());
+            if let Some(span) = err.span() {
+                diag = diag.with_span(aurora_diagnostics::Span::new(
+                    span.start as usize,
+                    span.end as usize,
+                    span.file_id as usize,
+                ));
+            }
+            self.session.report(diag);
+        }
+
         if self.session.options.verbose {
             debug!("Resolved {} symbols", resolver.symbol_count());
         }
 
         self.session.check_errors()?;
+        self.cache_result(Query::Names, key, &resolved, reported);
         Ok(resolved)
     }
 
@@ -119,21 +261,53 @@ impl<'sess> Pipeline<'sess> {
     fn type_check(&mut self, ast: Ast) -> Result<Ast> {
         info!("Phase 4: Type checking");
 
+        let reported = self.reported();
         let mut checker = TypeChecker::new(self.session.diagnostics.clone());
         let typed = checker.check(ast);
 
+        for err in checker.errors() {
+            let span = aurora_diagnostics::Span::new(
+                err.span.start as usize,
+                err.span.end as usize,
+                err.span.file_id as usize,
+            );
+            self.session
+                .report(Diagnostic::error(err.error.code(), err.error.to_string()).with_span(span));
+        }
+
         if self.session.options.verbose {
             debug!("Type checked successfully");
         }
 
         self.session.check_errors()?;
 
+        let key = incremental::crate_fingerprint(&self.session.source_map);
+        self.cache_result(Query::Types, key, &(&typed, checker.type_map()), reported);
+
+        self.stop_if_type_check_only();
+
+        self.type_map = checker.type_map().clone();
+        Ok(typed)
+    }
+
+    /// Phases 3 and 4 from the incremental cache, if no file of the crate changed
+    fn cached_typed_ast(&mut self) -> Option<Ast> {
+        let key = incremental::crate_fingerprint(&self.session.source_map);
+        let (typed, type_map) = self.cached::<(Ast, TypeMap)>(Query::Types, key)?;
+
+        info!("Phases 3-4: Reusing type-checked AST");
+        self.stop_if_type_check_only();
+
+        self.type_map = type_map;
+        Some(typed)
+    }
+
+    /// Exit once the program type checks, with `--type-check-only`
+    fn stop_if_type_check_only(&self) {
         if self.session.options.type_check_only {
             info!("Stopping after type checking (--type-check-only)");
             std::process::exit(0);
         }
-
-        Ok(typed)
     }
 
     /// Phase 5: Effect checking
@@ -159,33 +333,80 @@ impl<'sess> Pipeline<'sess> {
     fn lower_to_mir(&mut self, ast: Ast) -> Result<MirModule> {
         info!("Phase 6: MIR lowering and optimization");
 
-        let mir = aurora_mir::lower_ast_to_mir(ast, self.session.diagnostics.clone());
+        let type_map = std::mem::take(&mut self.type_map);
+        let opt_level = self.session.options.opt_level;
+        let items = self
+            .cache
+            .is_some()
+            .then(|| incremental::mir_items(&ast, &type_map, &self.session.source_map));
+
+        // Optimized MIR is cached for the whole crate, since inlining crosses functions
+        let module_key = items
+            .as_deref()
+            .filter(|_| opt_level > 0)
+            .map(|items| incremental::module_fingerprint(items, opt_level, self.session.options.cpu.as_deref()));
+        if let Some(mir) = module_key.and_then(|key| self.cached::<MirModule>(Query::OptimizedMir, key)) {
+            info!("Reusing optimized MIR");
+            return self.finish_mir(mir);
+        }
+
+        let reported = self.reported();
+        let diagnostics = self.session.diagnostics.clone();
+        let mut mir = match (&mut self.cache, &items) {
+            (Some(cache), Some(items)) => incremental::lower_items(cache, ast, type_map, items, diagnostics),
+            _ => aurora_mir::lower_typed_ast_to_mir(ast, type_map, diagnostics),
+        };
 
         if self.session.options.verbose {
             debug!("Generated MIR with {} functions", mir.function_count());
         }
 
-        // Optimize MIR based on opt level
-        let optimized = if self.session.options.opt_level > 0 {
-            info!("Running MIR optimizations (level {})", self.session.options.opt_level);
-            aurora_mir::optimize(mir, self.session.options.opt_level)
-        } else {
-            mir
-        };
+        // Optimize MIR based on opt level, tuned for the target CPU
+        if opt_level > 0 {
+            info!("Running MIR optimizations (level {})", opt_level);
+            let cpu = match &self.session.options.cpu {
+                Some(name) => CpuCharacteristics::from_name(name)
+                    .with_context(|| format!("Unknown CPU: {}", name))?,
+                None => CpuCharacteristics::generic(),
+            };
+            let strategy = OptimizationStrategy::for_cpu(cpu);
+            let mut passes = strategy.pass_manager(OptLevel::from_u8(opt_level));
+            passes.run(&mut mir);
+            if self.session.options.verbose {
+                debug!("MIR pass statistics:\n{}", passes);
+            }
+        }
+
+        if let Some(key) = module_key {
+            self.cache_result(Query::OptimizedMir, key, &mir, reported);
+        }
+
+        self.finish_mir(mir)
+    }
 
+    /// Dump MIR if asked to, once lowered and optimized
+    fn finish_mir(&self, mir: MirModule) -> Result<MirModule> {
         if self.session.options.emit_mir {
-            self.dump_mir(&optimized)?;
+            self.dump_mir(&mir)?;
         }
 
         self.session.check_errors()?;
-        Ok(optimized)
+        Ok(mir)
     }
 
     /// Phase 7: Lower to AIR
-    fn lower_to_air(&mut self, mir: MirModule) -> Result<AirModule> {
+    fn lower_to_air(&mut self, mir: MirModule) -> Result<TargetModule> {
         info!("Phase 7: AIR lowering");
 
-        let air = aurora_air::lower_mir_to_air(mir, self.session.diagnostics.clone());
+        let target = &self.session.options.target;
+        let arch = Arch::from_triple(target)
+            .with_context(|| format!("Unsupported target: {}", target))?;
+        let options = AirOptions {
+            debug_info: self.session.options.debug_info,
+            arch,
+            ..AirOptions::default()
+        };
+        let air = aurora_air::lower_mir_for_target(mir, self.session.diagnostics.clone(), options);
 
         if self.session.options.verbose {
             debug!("Generated AIR");
@@ -200,22 +421,12 @@ impl<'sess> Pipeline<'sess> {
     }
 
     /// Phase 8: Code generation
-    fn codegen(&mut self, air: AirModule) -> Result<()> {
+    fn codegen(&mut self, module: TargetModule) -> Result<()> {
         info!("Phase 8: Code generation");
 
-        let codegen_opts = CodegenOptions {
-            opt_level: self.session.options.opt_level,
-            debug_info: self.session.options.debug_info,
-            emit_llvm: self.session.options.emit_llvm,
-            codegen_units: self.session.options.codegen_units,
-            output_path: self.session.options.output_path(),
-            keep_intermediates: false,
-            target_triple: Some("x86_64-unknown-linux-gnu".to_string()),
-        };
-
-        aurora_backend::generate_code(
-            air,
-            codegen_opts,
+        aurora_backend::generate_code_for_target(
+            module,
+            self.codegen_options(),
             self.session.diagnostics.clone(),
         )?;
 
@@ -230,6 +441,53 @@ impl<'sess> Pipeline<'sess> {
         Ok(())
     }
 
+    /// Phases 7 and 8: Generate C and compile it
+    fn codegen_c(&mut self, mir: &MirModule) -> Result<()> {
+        info!("Phase 7: C generation and compilation");
+
+        aurora_backend::generate_c_code(mir, self.codegen_options(), self.session.diagnostics.clone())?;
+
+        info!("Binary written to {}", self.session.options.output_path().display());
+
+        self.session.check_errors()?;
+        Ok(())
+    }
+
+    /// Backend options from the session's
+    fn codegen_options(&self) -> CodegenOptions {
+        CodegenOptions {
+            opt_level: self.session.options.opt_level,
+            debug_info: self.session.options.debug_info,
+            emit_llvm: self.session.options.emit_llvm,
+            codegen_units: self.session.options.codegen_units,
+            output_path: self.session.options.output_path(),
+            keep_intermediates: false,
+            target_triple: Some(self.session.options.target.clone()),
+            source_path: Some(self.session.options.input.clone()),
+            linker: LinkerKind::from_name(&self.session.options.linker).unwrap_or_default(),
+        }
+    }
+
+    /// Look up a whole-crate query in the incremental cache
+    fn cached<T: DeserializeOwned>(&mut self, query: Query, key: Fingerprint) -> Option<T> {
+        self.cache.as_mut()?.load(query, "crate", key)
+    }
+
+    /// Cache a whole-crate query result, unless computing it reported
+    /// diagnostics (there were `reported` before)
+    fn cache_result<T: Serialize>(&self, query: Query, key: Fingerprint, value: &T, reported: usize) {
+        if let Some(cache) = &self.cache {
+            if self.reported() == reported {
+                cache.store(query, "crate", key, value);
+            }
+        }
+    }
+
+    /// Number of diagnostics reported so far
+    fn reported(&self) -> usize {
+        self.session.collector().diagnostics().len()
+    }
+
     /// Dump AST to file
     fn dump_ast(&self, ast: &Ast) -> Result<()> {
         let path = self.session.options.ast_dump_path();
@@ -251,9 +509,9 @@ impl<'sess> Pipeline<'sess> {
     }
 
     /// Dump AIR to file
-    fn dump_air(&self, air: &AirModule) -> Result<()> {
+    fn dump_air(&self, air: &TargetModule) -> Result<()> {
         let path = self.session.options.air_dump_path();
-        let dump = air.to_string();
+        let dump = air.to_text();
         fs::write(&path, dump)
             .with_context(|| format!("Failed to write AIR dump to {}", path.display()))?;
         info!("AIR dump written to {}", path.display());
@@ -261,18 +519,28 @@ impl<'sess> Pipeline<'sess> {
     }
 }
 
+/// An AST as cached, with the module files it was loaded from
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CachedAst<A> {
+    /// Path and fingerprint of each module file, in load order
+    modules: Vec<(String, Fingerprint)>,
+    ast: A,
+}
+
 /// Run a quick syntax check without full compilation
 pub fn check_syntax(session: &mut CompilationSession) -> Result<()> {
     info!("Running syntax check on {}", session.source_name());
 
     // Just lex and parse
-    let lexer = Lexer::with_diagnostics(&session.source, session.diagnostics.clone());
+    let lexer = Lexer::with_diagnostics(&session.source, session.diagnostics.clone())
+        .with_file_id(session.file_id);
     let tokens = lexer.tokenize();
 
     session.check_errors()?;
 
     let parser = Parser::with_diagnostics(tokens, session.diagnostics.clone());
-    let _ast = parser.parse();
+    let ast = parser.parse();
+    let _ast = loader::load_modules(session, ast);
 
     session.check_errors()?;
 
@@ -285,8 +553,14 @@ mod tests {
     use super::*;
     use crate::session::CompilationOptions;
     use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use std::sync::Mutex;
     use tempfile::NamedTempFile;
 
+    /// Native builds share intermediate files in the temporary directory
+    static NATIVE_BUILD: Mutex<()> = Mutex::new(());
+
     fn create_test_file(content: &str) -> Result<NamedTempFile> {
         let mut file = NamedTempFile::new()?;
         file.write_all(content.as_bytes())?;
@@ -315,4 +589,278 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_type_check_phase_reports_errors() -> Result<()> {
+        let file = create_test_file("fn main() { let x: bool = 1; }")?;
+        let opts = CompilationOptions::new(file.path());
+        let mut session = CompilationSession::new(opts)?;
+        let mut pipeline = Pipeline::new(&mut session);
+
+        let tokens = pipeline.lex()?;
+        let ast = pipeline.parse(tokens)?;
+        assert!(pipeline.type_check(ast).is_err());
+        assert_eq!(session.error_count(), 1);
+
+        Ok(())
+    }
+
+    /// Run the phases up to MIR incrementally, returning the cache statistics
+    fn compile_to_mir(path: &std::path::Path, opt_level: u8) -> Result<(MirModule, CacheStats)> {
+        let opts = CompilationOptions {
+            opt_level,
+            incremental: true,
+            ..CompilationOptions::new(path)
+        };
+        let mut session = CompilationSession::new(opts)?;
+        let mut pipeline = Pipeline::new(&mut session);
+
+        let ast = match pipeline.cached_ast()? {
+            Some(ast) => ast,
+            None => {
+                let tokens = pipeline.lex()?;
+                pipeline.parse(tokens)?
+            }
+        };
+        let typed = match pipeline.cached_typed_ast() {
+            Some(ast) => ast,
+            None => {
+                let resolved = pipeline.resolve_names(ast)?;
+                pipeline.type_check(resolved)?
+            }
+        };
+        let mir = pipeline.lower_to_mir(typed)?;
+        let stats = pipeline.cache_stats().unwrap().clone();
+        Ok((mir, stats))
+    }
+
+    #[test]
+    fn test_incremental_reuses_unchanged_crate() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("main.ax");
+        fs::write(&path, "fn helper() -> i64 { 1 }\nfn main() -> i64 { helper() }\n")?;
+
+        let (_, stats) = compile_to_mir(&path, 2)?;
+        assert_eq!(stats.misses(Query::Ast), 1);
+        assert_eq!(stats.misses(Query::Mir), 2);
+        assert_eq!(stats.misses(Query::OptimizedMir), 1);
+
+        let (mir, stats) = compile_to_mir(&path, 2)?;
+        assert_eq!(stats.hits(Query::Ast), 1);
+        assert_eq!(stats.hits(Query::Types), 1);
+        assert_eq!(stats.hits(Query::OptimizedMir), 1);
+        assert_eq!(stats.hits(Query::Tokens) + stats.misses(Query::Tokens), 0);
+        assert_eq!(stats.hits(Query::Mir) + stats.misses(Query::Mir), 0);
+        assert_eq!(mir.function_count(), 2);
+        assert!(dir.path().join(".aurora-incremental/main").is_dir());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_incremental_lowers_only_edited_function() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("main.ax");
+        fs::write(&path, "fn helper() -> i64 { 1 }\nfn main() -> i64 { helper() }\n")?;
+        compile_to_mir(&path, 0)?;
+
+        let source = "fn helper() -> i64 {\n    let x = 40;\n    x + 2\n}\nfn main() -> i64 { helper() }\n";
+        fs::write(&path, source)?;
+        let (mir, stats) = compile_to_mir(&path, 0)?;
+        assert_eq!(stats.misses(Query::Tokens), 1);
+        assert_eq!(stats.misses(Query::Types), 1);
+        assert_eq!(stats.hits(Query::Mir), 1);
+        assert_eq!(stats.misses(Query::Mir), 1);
+
+        // The reused function's spans follow its declaration
+        let main = mir.functions.values().find(|func| func.name == "main").unwrap();
+        assert_eq!(main.id, 1);
+        assert_eq!(main.span.start, source.find("fn main").unwrap());
+        assert_eq!(main.span.line, 5);
+
+        Ok(())
+    }
+
+    /// Compile `source` natively to `output` at optimization level
+    /// `opt_level`, linking with `linker`
+    fn build_native(source: &Path, output: &Path, opt_level: u8, linker: &str) -> Result<()> {
+        let mut opts = CompilationOptions::new(source);
+        opts.output = Some(output.to_path_buf());
+        opts.opt_level = opt_level;
+        opts.linker = linker.to_string();
+        let mut session = CompilationSession::new(opts)?;
+        let _guard = NATIVE_BUILD.lock().unwrap_or_else(|e| e.into_inner());
+        Pipeline::new(&mut session).compile()
+    }
+
+    /// Compile one of the examples natively and return what it prints
+    fn run_example(name: &str) -> Result<String> {
+        run_example_linked(name, "external")
+    }
+
+    /// Like [`run_example`], linking with `linker`
+    fn run_example_linked(name: &str, linker: &str) -> Result<String> {
+        let dir = tempfile::TempDir::new()?;
+        let source: PathBuf = [env!("CARGO_MANIFEST_DIR"), "../../examples", name].iter().collect();
+        let binary = dir.path().join("example");
+        build_native(&source.with_extension("ax"), &binary, 0, linker)?;
+
+        let output = Command::new(&binary).output()?;
+        assert!(output.status.success(), "{} exited with {}", name, output.status);
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// Compile a program with `backend` ("air", "c", "internal" for the air
+    /// backend with the built-in linker, or "wasm" for the air backend
+    /// targeting wasm32-wasi, run under Node) and return what it prints
+    fn run_program(source: &str, backend: &str) -> Result<String> {
+        let dir = tempfile::TempDir::new()?;
+        let (path, binary) = (dir.path().join("main.ax"), dir.path().join("main"));
+        fs::write(&path, source)?;
+        let mut opts = CompilationOptions::new(&path);
+        opts.output = Some(binary.clone());
+        match backend {
+            "wasm" => opts.target = "wasm32-wasi".to_string(),
+            "internal" => opts.linker = "internal".to_string(),
+            _ => opts.backend = backend.to_string(),
+        }
+        let mut session = CompilationSession::new(opts)?;
+        {
+            let _guard = NATIVE_BUILD.lock().unwrap_or_else(|e| e.into_inner());
+            Pipeline::new(&mut session).compile()?;
+        }
+
+        let output = match backend {
+            "wasm" => {
+                let script = "const { WASI } = require('wasi');
+                    const wasi = new WASI({ version: 'preview1', returnOnExit: true });
+                    const bytes = require('fs').readFileSync(process.argv[1]);
+                    WebAssembly.instantiate(bytes, { wasi_snapshot_preview1: wasi.wasiImport })
+                        .then(({ instance }) => { process.exitCode = wasi.start(instance); });";
+                Command::new("node").args(["--no-warnings", "-e", script]).arg(&binary).output()?
+            }
+            _ => Command::new(&binary).output()?,
+        };
+        assert!(output.status.success(), "{} backend: exited with {}", backend, output.status);
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// Backends to run programs with: Node runs the wasm ones, where it is installed
+    fn backends() -> Vec<&'static str> {
+        let mut backends = vec!["air", "c"];
+        if Command::new("node").arg("--version").output().is_ok() {
+            backends.push("wasm");
+        }
+        backends
+    }
+
+    #[test]
+    fn test_loop_break_value_reaches_use() -> Result<()> {
+        let source = "fn main() {\n    let v = loop { break 5; };\n    let i = 0;\n    let w = loop { i = i + 1; if i == 3 { break i * 10; } };\n    println(f\"{v} {w}\");\n}\n";
+        for backend in backends() {
+            assert_eq!(run_program(source, backend)?, "5 30\n", "{} backend", backend);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_println_fills_template_placeholders() -> Result<()> {
+        let source = "fn main() {\n    let x = 7;\n    println(\"x = {}, half = {}, odd: {}\", x, 3.5, x % 2 == 1);\n    print(\"{}{}\", 'a', \"b\");\n    print(\" \");\n    println(x);\n}\n";
+        for backend in backends() {
+            assert_eq!(run_program(source, backend)?, "x = 7, half = 3.5, odd: true\nab 7\n", "{} backend", backend);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_floats_print_their_value_and_sign() -> Result<()> {
+        let source = "fn main() {\n    let x: f32 = 2.25f32;\n    println(f\"{1.5f32} {x} {-0.0} {0.0}\");\n    println(\"{} {}\", 0.5f32, -1.5);\n}\n";
+        for backend in backends() {
+            assert_eq!(run_program(source, backend)?, "1.5 2.25 -0 0\n0.5 -1.5\n", "{} backend", backend);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_patterns_link_without_libc() -> Result<()> {
+        let source = "fn name(s: str) -> i64 {\n    match s {\n        \"one\" => 1,\n        \"two\" => 2,\n        _ => 0,\n    }\n}\n\nfn main() {\n    let a = name(\"two\");\n    let b = name(\"one\");\n    let c = name(\"twos\");\n    println(f\"{a} {b} {c}\");\n}\n";
+        for backend in backends().into_iter().chain(["internal"]) {
+            assert_eq!(run_program(source, backend)?, "2 1 0\n", "{} backend", backend);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_module_items_of_the_same_name_stay_apart() -> Result<()> {
+        let source = "mod a {\n    pub struct Point { x: i64 }\n\n    impl Point {\n        pub fn get(self) -> i64 { self.x }\n    }\n\n    pub fn helper() -> i64 { 1 }\n\n    pub fn make() -> Point { Point { x: 10 } }\n}\n\nmod b {\n    pub struct Point { y: i64, z: i64 }\n\n    pub fn helper() -> i64 { 2 }\n\n    pub fn sum(p: Point) -> i64 { p.y + p.z }\n}\n\nuse b::Point;\n\nfn helper() -> i64 { 3 }\n\nfn main() {\n    let p = a::make();\n    let q = Point { y: 4, z: 5 };\n    println(f\"{a::helper()} {b::helper()} {helper()} {p.get()} {b::sum(q)}\");\n}\n";
+        for backend in backends() {
+            assert_eq!(run_program(source, backend)?, "1 2 3 10 9\n", "{} backend", backend);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_examples_print_numbers() -> Result<()> {
+        let fibs = "0\n1\n1\n2\n3\n5\n8\n13\n21\n34\n55\n";
+        assert_eq!(
+            run_example("factorial_simple")?,
+            "Factorial of 5 is:\n120\nFactorial of 10 is:\n3628800\n"
+        );
+        assert_eq!(
+            run_example("fibonacci_simple")?,
+            format!("Fibonacci (recursive):\n{}Fibonacci (iterative):\n{}", fibs, fibs)
+        );
+        assert_eq!(
+            run_example("prime_checker_simple")?,
+            "Prime numbers up to 50:\n2\n3\n5\n7\n11\n13\n17\n19\n23\n29\n31\n37\n41\n43\n47\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fizzbuzz_example() -> Result<()> {
+        let expected: String = (1..=100)
+            .map(|i| match (i % 3, i % 5) {
+                (0, 0) => "FizzBuzz\n".to_string(),
+                (0, _) => "Fizz\n".to_string(),
+                (_, 0) => "Buzz\n".to_string(),
+                _ => format!("{}\n", i),
+            })
+            .collect();
+        assert_eq!(run_example("fizzbuzz_simple")?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_native_output_is_reproducible() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let source: PathBuf = [env!("CARGO_MANIFEST_DIR"), "../../examples/fibonacci_simple.ax"].iter().collect();
+        let (first, second) = (dir.path().join("first"), dir.path().join("second"));
+        build_native(&source, &first, 2, "external")?;
+        build_native(&source, &second, 2, "external")?;
+        assert!(fs::read(&first)? == fs::read(&second)?, "two builds of one source differ");
+        Ok(())
+    }
+
+    #[test]
+    fn test_internal_linker_links_example() -> Result<()> {
+        assert_eq!(
+            run_example_linked("factorial_simple", "internal")?,
+            "Factorial of 5 is:\n120\nFactorial of 10 is:\n3628800\n"
+        );
+        assert_eq!(run_example_linked("fizzbuzz_simple", "internal")?, run_example("fizzbuzz_simple")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_internal_linker_rejects_c_backend() -> Result<()> {
+        let file = create_test_file("fn main() {}")?;
+        let mut opts = CompilationOptions::new(file.path());
+        opts.backend = "c".to_string();
+        opts.linker = "internal".to_string();
+        let mut session = CompilationSession::new(opts)?;
+        let error = Pipeline::new(&mut session).compile().unwrap_err();
+        assert!(error.to_string().contains("built-in linker"), "{}", error);
+        Ok(())
+    }
 }
@@ -5,9 +5,10 @@
 //! and data flow between compiler agents.
 
 use anyhow::{Context, Result};
-use aurora_diagnostics::DiagnosticCollector;
+use aurora_ast::SourceMap;
+use aurora_diagnostics::{Diagnostic, DiagnosticCollector, SharedCollector};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, MutexGuard};
 
 /// Compilation options and configuration
 #[derive(Debug, Clone)]
@@ -21,6 +22,18 @@ pub struct CompilationOptions {
     /// Optimization level (0-3)
     pub opt_level: u8,
 
+    /// Target triple (e.g., "x86_64-unknown-linux-gnu")
+    pub target: String,
+
+    /// CPU to tune optimizations for (e.g., "skylake", "zen3")
+    pub cpu: Option<String>,
+
+    /// Code generator after MIR ("air" or "c")
+    pub backend: String,
+
+    /// Linker for native executables ("external" or "internal")
+    pub linker: String,
+
     /// Emit MIR dump for debugging
     pub emit_mir: bool,
 
@@ -44,6 +57,9 @@ pub struct CompilationOptions {
 
     /// Enable debug information
     pub debug_info: bool,
+
+    /// Reuse query results cached by earlier compilations
+    pub incremental: bool,
 }
 
 impl CompilationOptions {
@@ -53,6 +69,10 @@ impl CompilationOptions {
             input: input.into(),
             output: None,
             opt_level: 0,
+            target: "x86_64-unknown-linux-gnu".to_string(),
+            cpu: None,
+            backend: "air".to_string(),
+            linker: "external".to_string(),
             emit_mir: false,
             emit_air: false,
             emit_ast: false,
@@ -61,6 +81,7 @@ impl CompilationOptions {
             verbose: false,
             codegen_units: 1,
             debug_info: true,
+            incremental: false,
         }
     }
 
@@ -92,6 +113,12 @@ impl CompilationOptions {
     pub fn llvm_dump_path(&self) -> PathBuf {
         self.input.with_extension("ll")
     }
+
+    /// Get the directory incremental compilation caches query results in
+    pub fn incremental_dir(&self) -> PathBuf {
+        let stem = self.input.file_stem().unwrap_or_default();
+        self.input.with_file_name(".aurora-incremental").join(stem)
+    }
 }
 
 /// Compilation session state
@@ -106,14 +133,21 @@ pub struct CompilationSession {
     /// Compilation options
     pub options: CompilationOptions,
 
-    /// Diagnostic collector (errors, warnings, notes)
-    pub diagnostics: Arc<DiagnosticCollector>,
+    /// Diagnostic collector (errors, warnings, notes), shared with the
+    /// phases that report into it
+    pub diagnostics: Arc<SharedCollector>,
 
     /// Source code content
     pub source: String,
 
     /// Source file path
     pub source_path: PathBuf,
+
+    /// All source files of the compilation, indexed by span file ID
+    pub source_map: SourceMap,
+
+    /// File ID of the root source file in the source map
+    pub file_id: u32,
 }
 
 impl CompilationSession {
@@ -123,11 +157,16 @@ impl CompilationSession {
         let source = std::fs::read_to_string(&options.input)
             .with_context(|| format!("Failed to read source file: {}", options.input.display()))?;
 
+        let mut source_map = SourceMap::new();
+        let file_id = source_map.add_file(options.input.display().to_string(), source.clone());
+
         Ok(Self {
             source_path: options.input.clone(),
             options,
-            diagnostics: Arc::new(DiagnosticCollector::new()),
+            diagnostics: Arc::new(SharedCollector::new()),
             source,
+            source_map,
+            file_id,
         })
     }
 
@@ -139,24 +178,44 @@ impl CompilationSession {
             .unwrap_or("<unknown>")
     }
 
+    /// Lock the diagnostic collector
+    pub fn collector(&self) -> MutexGuard<'_, DiagnosticCollector> {
+        self.diagnostics.lock()
+    }
+
+    /// Report a diagnostic
+    pub fn report(&self, diagnostic: Diagnostic) {
+        self.diagnostics.add(diagnostic);
+    }
+
     /// Check if any errors have been reported
     pub fn has_errors(&self) -> bool {
-        self.diagnostics.has_errors()
+        self.collector().has_errors()
     }
 
     /// Get error count
     pub fn error_count(&self) -> usize {
-        self.diagnostics.error_count()
+        self.collector().error_count()
     }
 
     /// Get warning count
     pub fn warning_count(&self) -> usize {
-        self.diagnostics.warning_count()
+        self.collector().warning_count()
     }
 
     /// Print all diagnostics to stderr
     pub fn emit_diagnostics(&self) {
-        self.diagnostics.emit(&self.source, &self.source_path);
+        let paths: Vec<PathBuf> = self
+            .source_map
+            .files()
+            .map(|(_, file)| PathBuf::from(&file.name))
+            .collect();
+        let files: Vec<(&Path, &str)> = paths
+            .iter()
+            .zip(self.source_map.files())
+            .map(|(path, (_, file))| (path.as_path(), file.source.as_str()))
+            .collect();
+        self.collector().emit_files(&files);
     }
 
     /// Return error if any errors occurred
@@ -3,19 +3,179 @@
  *
  * Provides minimal C runtime support for Aurora programs,
  * including println implementation and program startup.
+ *
+ * Built with AURORA_FREESTANDING defined, the runtime does not use the C
+ * library, so that the built-in static linker can link it on its own.
+ */
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef AURORA_FREESTANDING
+
+static long aurora_syscall(long number, long a, long b, long c, long d, long e, long f) {
+    register long r10 __asm__("r10") = d;
+    register long r8 __asm__("r8") = e;
+    register long r9 __asm__("r9") = f;
+    long result;
+    __asm__ volatile("syscall"
+                     : "=a"(result)
+                     : "a"(number), "D"(a), "S"(b), "d"(c), "r"(r10), "r"(r8), "r"(r9)
+                     : "rcx", "r11", "memory");
+    return result;
+}
+
+#define SYS_WRITE 1
+#define SYS_MMAP 9
+#define SYS_EXIT_GROUP 231
+
+/* The compiler may emit calls to these for copies and loops */
+
+void* memcpy(void* dest, const void* src, size_t n) {
+    unsigned char* d = dest;
+    const unsigned char* s = src;
+    while (n--) {
+        *d++ = *s++;
+    }
+    return dest;
+}
+
+void* memset(void* dest, int c, size_t n) {
+    unsigned char* d = dest;
+    while (n--) {
+        *d++ = (unsigned char)c;
+    }
+    return dest;
+}
+
+static size_t strlen(const char* str) {
+    size_t len = 0;
+    while (str[len]) {
+        len++;
+    }
+    return len;
+}
+
+/**
+ * Allocations are carved out of blocks mapped from the kernel. Each
+ * holds a power of two of bytes, header included, and the header records
+ * which; freed allocations are kept in a list per size for reuse.
+ */
+#define BLOCK_SIZE ((size_t)1 << 20)
+#define HEADER_SIZE 16
+
+static unsigned char* heap_next;
+static unsigned char* heap_end;
+static void* free_lists[64];
+
+static void* malloc(size_t size) {
+    size_t class = 5;
+    while (((size_t)1 << class) < size + HEADER_SIZE) {
+        class++;
+    }
+    size_t need = (size_t)1 << class;
+
+    unsigned char* header = free_lists[class];
+    if (header) {
+        free_lists[class] = *(void**)(header + HEADER_SIZE);
+    } else {
+        if (need > (size_t)(heap_end - heap_next)) {
+            size_t len = need > BLOCK_SIZE ? need : BLOCK_SIZE;
+            /* PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS */
+            long block = aurora_syscall(SYS_MMAP, 0, (long)len, 3, 0x22, -1, 0);
+            if (block < 0 && block > -4096) {
+                return NULL;
+            }
+            heap_next = (unsigned char*)block;
+            heap_end = heap_next + len;
+        }
+        header = heap_next;
+        heap_next += need;
+    }
+    *(size_t*)header = class;
+    return header + HEADER_SIZE;
+}
+
+static void free(void* ptr) {
+    if (ptr) {
+        unsigned char* header = (unsigned char*)ptr - HEADER_SIZE;
+        size_t class = *(size_t*)header;
+        *(void**)ptr = free_lists[class];
+        free_lists[class] = header;
+    }
+}
+
+static void* realloc(void* ptr, size_t size) {
+    if (!ptr) {
+        return malloc(size);
+    }
+    size_t capacity = ((size_t)1 << *(size_t*)((unsigned char*)ptr - HEADER_SIZE)) - HEADER_SIZE;
+    if (size <= capacity) {
+        return ptr;
+    }
+    void* moved = malloc(size);
+    if (moved) {
+        memcpy(moved, ptr, capacity);
+        free(ptr);
+    }
+    return moved;
+}
+
+static void aurora_write(int fd, const char* bytes, size_t len) {
+    while (len > 0) {
+        long written = aurora_syscall(SYS_WRITE, fd, (long)bytes, (long)len, 0, 0, 0);
+        if (written <= 0) {
+            return;
+        }
+        bytes += written;
+        len -= (size_t)written;
+    }
+}
+
+static void aurora_abort(void) {
+    /* The status a shell reports for a process killed by SIGABRT */
+    aurora_syscall(SYS_EXIT_GROUP, 134, 0, 0, 0, 0, 0);
+    __builtin_unreachable();
+}
+
+void aurora_fmt_release(void);
+
+/**
+ * Program entry, called by the built-in linker's _start
  */
+int aurora_start(int argc, char** argv, char** envp, int (*main_fn)(void)) {
+    (void)argc;
+    (void)argv;
+    (void)envp;
+    int status = main_fn();
+    aurora_fmt_release();
+    return status;
+}
+
+#else
 
 #include <stdio.h>
 #include <stdlib.h>
 #include <string.h>
 
+static void aurora_write(int fd, const char* bytes, size_t len) {
+    fwrite(bytes, 1, len, fd == 2 ? stderr : stdout);
+}
+
+static void aurora_abort(void) {
+    abort();
+}
+
+#endif
+
 /**
  * Print string to stdout with newline
  * Called from Aurora code
  */
 void aurora_println(const char* str) {
     if (str) {
-        printf("%s\n", str);
+        aurora_write(1, str, strlen(str));
+        aurora_write(1, "\n", 1);
     }
 }
 
@@ -25,7 +185,7 @@ void aurora_println(const char* str) {
  */
 void aurora_print(const char* str) {
     if (str) {
-        printf("%s", str);
+        aurora_write(1, str, strlen(str));
     }
 }
 
@@ -66,13 +226,273 @@ void* aurora_realloc(void* ptr, size_t size) {
     return realloc(ptr, size);
 }
 
+/**
+ * Header of a formatted string, which follows it
+ *
+ * Formatted strings are kept in a list until aurora_fmt_free releases
+ * them, and the ones left are released when the program exits.
+ */
+typedef struct AuroraString {
+    struct AuroraString* prev;
+    struct AuroraString* next;
+} AuroraString;
+
+static AuroraString* aurora_strings;
+
+/**
+ * String being built for an interpolated string literal
+ *
+ * Aurora code calls aurora_fmt_new, then one aurora_fmt_* routine per
+ * piece of the literal, then aurora_fmt_finish for the string.
+ */
+typedef struct {
+    /* Header, followed by the bytes so far */
+    AuroraString* string;
+    size_t len;
+    size_t cap;
+} AuroraFmt;
+
+/**
+ * Release the formatted strings not yet freed
+ */
+void aurora_fmt_release(void) {
+    while (aurora_strings) {
+        AuroraString* next = aurora_strings->next;
+        free(aurora_strings);
+        aurora_strings = next;
+    }
+}
+
+/**
+ * Start formatting a string
+ */
+AuroraFmt* aurora_fmt_new(void) {
+#ifndef AURORA_FREESTANDING
+    static int registered;
+    if (!registered) {
+        registered = 1;
+        atexit(aurora_fmt_release);
+    }
+#endif
+    AuroraFmt* fmt = malloc(sizeof(AuroraFmt));
+    fmt->cap = 32;
+    fmt->len = 0;
+    fmt->string = malloc(sizeof(AuroraString) + fmt->cap);
+    return fmt;
+}
+
+static void aurora_fmt_bytes(AuroraFmt* fmt, const char* bytes, size_t len) {
+    if (fmt->len + len + 1 > fmt->cap) {
+        while (fmt->len + len + 1 > fmt->cap) {
+            fmt->cap *= 2;
+        }
+        fmt->string = realloc(fmt->string, sizeof(AuroraString) + fmt->cap);
+    }
+    memcpy((char*)(fmt->string + 1) + fmt->len, bytes, len);
+    fmt->len += len;
+}
+
+/**
+ * Append a string
+ */
+void aurora_fmt_str(AuroraFmt* fmt, const char* str) {
+    if (str) {
+        aurora_fmt_bytes(fmt, str, strlen(str));
+    }
+}
+
+/**
+ * Append an unsigned integer in decimal
+ */
+void aurora_fmt_u64(AuroraFmt* fmt, uint64_t value) {
+    char digits[20];
+    size_t start = sizeof digits;
+    do {
+        digits[--start] = (char)('0' + value % 10);
+        value /= 10;
+    } while (value != 0);
+    aurora_fmt_bytes(fmt, digits + start, sizeof digits - start);
+}
+
+/**
+ * Append a signed integer in decimal
+ */
+void aurora_fmt_i64(AuroraFmt* fmt, int64_t value) {
+    if (value < 0) {
+        aurora_fmt_bytes(fmt, "-", 1);
+        aurora_fmt_u64(fmt, 0 - (uint64_t)value);
+    } else {
+        aurora_fmt_u64(fmt, (uint64_t)value);
+    }
+}
+
+/**
+ * Append a float with up to six decimals, without trailing zeros
+ *
+ * Values of 1e13 and more are written as a mantissa and a decimal
+ * exponent, such as 1.5e20. The WebAssembly backend formats floats with
+ * the same operations, so the two print the same digits.
+ */
+void aurora_fmt_f64(AuroraFmt* fmt, double value) {
+    if (value != value) {
+        aurora_fmt_str(fmt, "NaN");
+        return;
+    }
+    /* Test the sign bit, so that -0 keeps its sign */
+    union {
+        double value;
+        uint64_t bits;
+    } sign = {value};
+    if (sign.bits >> 63) {
+        aurora_fmt_bytes(fmt, "-", 1);
+        value = -value;
+    }
+    if (value > 1.7976931348623157e308) {
+        aurora_fmt_str(fmt, "inf");
+        return;
+    }
+
+    uint64_t exponent = 0;
+    if (value >= 1e13) {
+        while (value >= 10) {
+            value /= 10;
+            exponent++;
+        }
+    }
+    uint64_t scaled = (uint64_t)(value * 1e6 + 0.5);
+    if (exponent != 0 && scaled >= 10000000) {
+        scaled /= 10;
+        exponent++;
+    }
+
+    aurora_fmt_u64(fmt, scaled / 1000000);
+    uint64_t fraction = scaled % 1000000;
+    if (fraction != 0) {
+        /* Leading 1 keeps the fraction's leading zeros */
+        fraction += 1000000;
+        while (fraction % 10 == 0) {
+            fraction /= 10;
+        }
+        char digits[8];
+        size_t start = sizeof digits;
+        while (fraction != 0) {
+            digits[--start] = (char)('0' + fraction % 10);
+            fraction /= 10;
+        }
+        digits[start] = '.';
+        aurora_fmt_bytes(fmt, digits + start, sizeof digits - start);
+    }
+    if (exponent != 0) {
+        aurora_fmt_bytes(fmt, "e", 1);
+        aurora_fmt_u64(fmt, exponent);
+    }
+}
+
+/**
+ * Append a 32-bit float
+ */
+void aurora_fmt_f32(AuroraFmt* fmt, float value) {
+    aurora_fmt_f64(fmt, value);
+}
+
+/**
+ * Append true or false
+ */
+void aurora_fmt_bool(AuroraFmt* fmt, int64_t value) {
+    aurora_fmt_str(fmt, value ? "true" : "false");
+}
+
+/**
+ * Append a character, encoded in UTF-8
+ */
+void aurora_fmt_char(AuroraFmt* fmt, int64_t c) {
+    char bytes[4];
+    size_t len;
+    if (c < 0x80) {
+        bytes[0] = (char)c;
+        len = 1;
+    } else if (c < 0x800) {
+        bytes[0] = (char)(0xC0 | (c >> 6));
+        bytes[1] = (char)(0x80 | (c & 0x3F));
+        len = 2;
+    } else if (c < 0x10000) {
+        bytes[0] = (char)(0xE0 | (c >> 12));
+        bytes[1] = (char)(0x80 | ((c >> 6) & 0x3F));
+        bytes[2] = (char)(0x80 | (c & 0x3F));
+        len = 3;
+    } else {
+        bytes[0] = (char)(0xF0 | (c >> 18));
+        bytes[1] = (char)(0x80 | ((c >> 12) & 0x3F));
+        bytes[2] = (char)(0x80 | ((c >> 6) & 0x3F));
+        bytes[3] = (char)(0x80 | (c & 0x3F));
+        len = 4;
+    }
+    aurora_fmt_bytes(fmt, bytes, len);
+}
+
+/**
+ * Finish formatting, returning the null-terminated string
+ */
+const char* aurora_fmt_finish(AuroraFmt* fmt) {
+    AuroraString* string = fmt->string;
+    char* data = (char*)(string + 1);
+    data[fmt->len] = '\0';
+    free(fmt);
+
+    string->prev = NULL;
+    string->next = aurora_strings;
+    if (aurora_strings) {
+        aurora_strings->prev = string;
+    }
+    aurora_strings = string;
+    return data;
+}
+
+/**
+ * Free a string returned by aurora_fmt_finish
+ */
+void aurora_fmt_free(const char* str) {
+    AuroraString* string = (AuroraString*)str - 1;
+    if (string->prev) {
+        string->prev->next = string->next;
+    } else {
+        aurora_strings = string->next;
+    }
+    if (string->next) {
+        string->next->prev = string->prev;
+    }
+    free(string);
+}
+
+/**
+ * Whether two strings have the same contents
+ * Match lowering compares string patterns with it
+ */
+int64_t aurora_str_eq(const char* a, const char* b) {
+    while (*a && *a == *b) {
+        a++;
+        b++;
+    }
+    return *a == *b;
+}
+
 /**
  * Aurora panic handler
  * Called when Aurora code panics
  */
 void aurora_panic(const char* msg, const char* file, int line) {
-    fprintf(stderr, "Aurora panic at %s:%d: %s\n", file, line, msg);
-    abort();
+    AuroraFmt* fmt = aurora_fmt_new();
+    aurora_fmt_str(fmt, "Aurora panic at ");
+    aurora_fmt_str(fmt, file);
+    aurora_fmt_str(fmt, ":");
+    aurora_fmt_i64(fmt, line);
+    aurora_fmt_str(fmt, ": ");
+    aurora_fmt_str(fmt, msg);
+    aurora_fmt_str(fmt, "\n");
+    const char* text = aurora_fmt_finish(fmt);
+    aurora_write(2, text, strlen(text));
+    aurora_fmt_free(text);
+    aurora_abort();
 }
 
 /**
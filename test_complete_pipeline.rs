@@ -67,6 +67,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         keep_intermediates: true,
         codegen_units: 1,
         target_triple: Some("x86_64-unknown-linux-gnu".to_string()),
+        source_path: None,
     };
 
     generate_code(air, options, Arc::new(()))?;